@@ -0,0 +1,212 @@
+/// Something for the frontend to play, by the id of a file in data/audio, like
+/// `data/audio/door-creak.ogg` for `door-creak`. Levels declare cues with a room's or
+/// region's `music`, and the `sound` effect.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cue {
+    /// A sound that's played once over anything else, like a door creaking.
+    Sound(String),
+    /// Music or ambience that loops until it's changed, or None for silence.
+    Music(Option<String>),
+}
+
+/// Whether a cue id can name a file in data/audio, so that levels can't reach files
+/// outside of it.
+pub fn is_cue_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')
+}
+
+/// Plays cues through the speakers. Without the "audio" feature, or without a sound
+/// device, it quietly does nothing, and so do cues that have no file.
+#[cfg(feature = "native")]
+pub struct Audio {
+    #[cfg(feature = "audio")]
+    player: Option<player::Player>,
+}
+
+#[cfg(feature = "native")]
+impl Audio {
+    pub fn new() -> Audio {
+        Audio {
+            #[cfg(feature = "audio")]
+            player: player::Player::new(),
+        }
+    }
+
+    pub fn play(&mut self, cue: &Cue) {
+        #[cfg(feature = "audio")]
+        if let Some(ref mut player) = self.player {
+            player.play(cue);
+        }
+        #[cfg(not(feature = "audio"))]
+        let _ = cue;
+    }
+}
+
+#[cfg(feature = "native")]
+impl Default for Audio {
+    fn default() -> Audio {
+        Audio::new()
+    }
+}
+
+#[cfg(feature = "audio")]
+mod player {
+    use super::{is_cue_id, Cue};
+    use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+    use std::{fs::File, io::BufReader, path::PathBuf};
+
+    /// Where the files of the sound cues are kept, named by their ids.
+    const AUDIO_DIR: &str = "data/audio";
+    /// The kinds of files that cues are looked for as, in order.
+    const EXTENSIONS: &[&str] = &["ogg", "wav", "flac", "mp3"];
+
+    pub struct Player {
+        dir: PathBuf,
+        // The stream stops playing when it's dropped, so it's kept with its handle.
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+        music: Option<Sink>,
+    }
+
+    impl Player {
+        pub fn new() -> Option<Player> {
+            let (stream, handle) = OutputStream::try_default().ok()?;
+            Some(Player {
+                dir: PathBuf::from(AUDIO_DIR),
+                _stream: stream,
+                handle,
+                music: None,
+            })
+        }
+
+        /// Open the file for a cue id, trying each of the kinds of audio files.
+        fn open(&self, id: &str) -> Option<BufReader<File>> {
+            if !is_cue_id(id) {
+                return None;
+            }
+            EXTENSIONS
+                .iter()
+                .map(|extension| self.dir.join(id).with_extension(extension))
+                .find_map(|path| File::open(path).ok())
+                .map(BufReader::new)
+        }
+
+        pub fn play(&mut self, cue: &Cue) {
+            match cue {
+                Cue::Sound(id) => {
+                    if let Some(file) = self.open(id) {
+                        if let Ok(sink) = self.handle.play_once(file) {
+                            sink.detach();
+                        }
+                    }
+                }
+                Cue::Music(id) => {
+                    if let Some(music) = self.music.take() {
+                        music.stop();
+                    }
+                    let file = match id.as_deref().and_then(|id| self.open(id)) {
+                        Some(file) => file,
+                        None => return,
+                    };
+                    let (source, sink) = match (Decoder::new(file), Sink::try_new(&self.handle)) {
+                        (Ok(source), Ok(sink)) => (source, sink),
+                        _ => return,
+                    };
+                    sink.append(source.repeat_infinite());
+                    self.music = Some(sink);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn test_cue_ids() {
+        assert!(is_cue_id("door-creak"));
+        assert!(is_cue_id("theme_2"));
+        assert!(!is_cue_id(""));
+        assert!(!is_cue_id("../save"));
+        assert!(!is_cue_id("music/theme"));
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_play() {
+        // Playing without the feature, or a file, does nothing.
+        let mut audio = Audio::new();
+        audio.play(&Cue::Sound("no-such-cue".into()));
+        audio.play(&Cue::Music(None));
+    }
+
+    #[test]
+    fn test_audio() {
+        let level = r#"
+maps: [["-----", "-...-", "-----"]]
+entry: [1, 1, 0]
+regions:
+  docks: { music: gulls }
+npcs: {}
+rooms:
+  - title: Hall
+    coord: [1, 1, 0]
+    description: A hall with a bell.
+    music: hall-theme
+    actions:
+      - { verb: Custom, alias: ring, targets: [bell], value: Dong., effects: [{ sound: bell }] }
+  - { title: Quay, coord: [2, 1, 0], description: A quay., regions: [docks] }
+  - { title: Pier, coord: [3, 1, 0], description: A pier., regions: [docks] }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        let mut output = Vec::new();
+        // The music only changes when a room plays something else, and muting stops it
+        // until the sound is turned back on.
+        for command in [
+            "ring bell",
+            "east",
+            "east",
+            "mute",
+            "west",
+            "west",
+            "ring bell",
+            "mute",
+        ] {
+            game.step(command.into());
+            output.push(format!("> {}", command));
+            for cue in std::mem::take(&mut game.output().cues) {
+                output.push(format!("{:?}", cue));
+            }
+            output.extend(
+                take_output(&game)
+                    .into_iter()
+                    .filter(|line| line.starts_with("The sound")),
+            );
+        }
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - "> ring bell"
+        - "Sound(\"bell\")"
+        - "Music(Some(\"hall-theme\"))"
+        - "> east"
+        - "Music(Some(\"gulls\"))"
+        - "> east"
+        - "> mute"
+        - Music(None)
+        - The sound is off. Type mute again to turn it back on.
+        - "> west"
+        - "> west"
+        - "> ring bell"
+        - "> mute"
+        - "Music(Some(\"hall-theme\"))"
+        - The sound is back on.
+        "###);
+    }
+}
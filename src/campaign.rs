@@ -0,0 +1,512 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const CAMPAIGN_PATH: &str = "data/campaign.yml";
+
+/// The levels that make up a game, which the player can travel between through the
+/// exits of rooms. The player begins in the start level.
+///
+/// ```yml
+/// start: stone-end-market
+/// levels:
+///   stone-end-market: data/levels/stone-end-market.yml
+///   sewer: data/levels/sewer.yml
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Campaign {
+    pub start: String,
+    /// The path to each level, by the level's id.
+    pub levels: BTreeMap<String, String>,
+    /// Count the turns of the campaign as days and seasons, see `Calendar`.
+    #[serde(default)]
+    pub calendar: Option<Calendar>,
+}
+
+/// How the turns of a campaign add up to days and seasons, so that dialogue can mention
+/// how long the player has been traveling. The calendar carries on through every level,
+/// and through New Game Plus when the campaign asks for it.
+///
+/// ```yml
+/// calendar:
+///   turns_per_day: 24
+///   days_per_season: 30
+///   seasons: [spring, summer, autumn, winter]
+///   new_game_plus: true
+/// ```
+///
+/// The season can follow the real date instead, so that a festival comes around when
+/// it does for the player. Players can also force a season with their `season` setting.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Calendar {
+    #[serde(default = "default_turns_per_day")]
+    pub turns_per_day: usize,
+    #[serde(default = "default_days_per_season")]
+    pub days_per_season: usize,
+    #[serde(default = "default_seasons")]
+    pub seasons: Vec<String>,
+    /// Keep the calendar going when the player restarts, rather than starting over on
+    /// the first day.
+    #[serde(default)]
+    pub new_game_plus: bool,
+    /// Take the season from the real date, rather than from the turns played, see
+    /// `Calendar::real_season`.
+    #[serde(default)]
+    pub real_seasons: bool,
+}
+
+fn default_turns_per_day() -> usize {
+    24
+}
+
+fn default_days_per_season() -> usize {
+    30
+}
+
+fn default_seasons() -> Vec<String> {
+    ["spring", "summer", "autumn", "winter"]
+        .iter()
+        .map(|season| season.to_string())
+        .collect()
+}
+
+impl Calendar {
+    /// The date after some turns of the calendar have passed.
+    pub fn date(&self, clock: usize, playthrough: usize) -> Date<'_> {
+        let day = clock / self.turns_per_day.max(1) + 1;
+        let season = match self.seasons.len() {
+            0 => "",
+            count => &self.seasons[(day - 1) / self.days_per_season.max(1) % count],
+        };
+        Date {
+            day,
+            season,
+            playthrough,
+        }
+    }
+
+    /// The season of a real date. The year is split evenly between the seasons,
+    /// starting with the first of them in March, like spring in the north.
+    pub fn real_season(&self, today: Today) -> &str {
+        match self.seasons.len() {
+            0 => "",
+            count => {
+                let month = (today.month as usize + 12 - 3) % 12;
+                &self.seasons[month * count / 12]
+            }
+        }
+    }
+}
+
+/// The real date, which templates and conditions can read as `today.month` and
+/// `today.day`, for content that only shows up on a holiday.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Today {
+    /// The month from 1 to 12.
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Today {
+    /// Today's date in UTC.
+    pub fn now() -> Today {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        Today::from_days(seconds / (24 * 60 * 60))
+    }
+
+    /// The date some days after the first of January, 1970. This is the "civil from
+    /// days" algorithm by Howard Hinnant, which counts years from March so that the
+    /// leap day comes last.
+    fn from_days(days: u64) -> Today {
+        let days = days + 719_468;
+        let era = days / 146_097;
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_from_march = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+        let month = if month_from_march < 10 {
+            month_from_march + 3
+        } else {
+            month_from_march - 9
+        };
+        Today {
+            month: month as u32,
+            day: day as u32,
+        }
+    }
+}
+
+/// A day of the campaign's calendar, which templates and conditions can read as
+/// `calendar.day`, `calendar.season`, and `calendar.playthrough`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Date<'a> {
+    /// The day of the calendar, starting from 1.
+    pub day: usize,
+    pub season: &'a str,
+    /// How many times the campaign has been played, counting New Game Plus.
+    pub playthrough: usize,
+}
+
+impl Campaign {
+    pub fn level_path(&self, id: &str) -> Result<&str, String> {
+        match self.levels.get(id) {
+            Some(path) => Ok(path),
+            None => Err(format!("The campaign has no level {:?}.", id)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{testing::*, Game, GameLoopResponse, ItemDatabase};
+    use std::fs;
+
+    #[test]
+    fn test_calendar() {
+        let calendar: Calendar =
+            serde_yaml::from_str("{ turns_per_day: 10, days_per_season: 2, seasons: [wet, dry] }")
+                .unwrap();
+        let date = |clock| calendar.date(clock, 1);
+        assert_eq!((date(0).day, date(0).season), (1, "wet"));
+        assert_eq!((date(19).day, date(19).season), (2, "wet"));
+        assert_eq!((date(20).day, date(20).season), (3, "dry"));
+        assert_eq!((date(40).day, date(40).season), (5, "wet"));
+        assert!(!calendar.new_game_plus);
+
+        let today = |month| Today { month, day: 1 };
+        assert_eq!(calendar.real_season(today(3)), "wet");
+        assert_eq!(calendar.real_season(today(8)), "wet");
+        assert_eq!(calendar.real_season(today(9)), "dry");
+        assert_eq!(calendar.real_season(today(2)), "dry");
+    }
+
+    #[test]
+    fn test_today() {
+        assert_eq!(Today::from_days(0), Today { month: 1, day: 1 });
+        // 2024-02-29, a leap day.
+        assert_eq!(Today::from_days(19_782), Today { month: 2, day: 29 });
+        // 2024-12-31.
+        assert_eq!(Today::from_days(20_088), Today { month: 12, day: 31 });
+    }
+
+    #[test]
+    fn test_calendar_in_game() {
+        let path = std::env::temp_dir().join("text-adventure-test-new-game-plus.yml");
+        let _ = fs::remove_file(&path);
+        // Answer the restart question.
+        let mut command_runner = CommandRunner::new(vec!["yes"]);
+        command_runner.new_game_plus_path = Some(path.clone());
+        command_runner.files.insert(
+            CAMPAIGN_PATH,
+            r#"
+start: town
+levels: { town: town.yml }
+calendar: { turns_per_day: 2, days_per_season: 1, new_game_plus: true }
+"#
+            .into(),
+        );
+        command_runner.files.insert(
+            "town.yml",
+            r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+rooms:
+  - title: Town
+    coord: [1, 1, 0]
+    description: "Day {calendar.day} of {calendar.season}.{if calendar.playthrough > 1} It feels familiar.{end}"
+"#
+            .into(),
+        );
+
+        let mut game = Game::new(ItemDatabase::new(), &mut command_runner).unwrap();
+        for command in ["look", "look", "look", "look"] {
+            assert!(game.step(command.into()).is_none());
+        }
+        assert!(matches!(
+            game.step("restart".into()),
+            Some(GameLoopResponse::Restart)
+        ));
+        drop(game);
+        let mut game = Game::new(ItemDatabase::new(), &mut command_runner).unwrap();
+        assert!(!path.exists());
+        assert_eq!(game.save_state.turn, 0);
+        assert!(game.step("look".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Town
+        - ""
+        - "    Day 3 of autumn. It feels familiar. "
+        - ""
+        - ""
+        - "Exits: _ _ _ _"
+        "###);
+    }
+
+    #[test]
+    fn test_seasons() {
+        let mut command_runner = CommandRunner::new(vec![]);
+        command_runner.today = Some(Today { month: 12, day: 25 });
+        command_runner.files.insert(
+            CAMPAIGN_PATH,
+            r#"
+start: town
+levels: { town: town.yml }
+calendar: { real_seasons: true }
+"#
+            .into(),
+        );
+        command_runner.files.insert(
+            "town.yml",
+            r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+rooms:
+  - title: Town
+    coord: [1, 1, 0]
+    description: "It is {calendar.season}.{if today.month == 12 and today.day == 25} Bells ring for the festival.{end}"
+    actions:
+      - verb: Look
+        targets: [well]
+        when: calendar.season == "summer"
+        value: The well is cool in the heat.
+"#
+            .into(),
+        );
+
+        let mut game = Game::new(ItemDatabase::new(), &mut command_runner).unwrap();
+        for command in [
+            "look",
+            "look at well",
+            "config season summer",
+            "look at well",
+        ] {
+            game.step(command.into());
+        }
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Town
+        - ""
+        - "    It is winter. Bells ring for the festival. "
+        - ""
+        - ""
+        - "Exits: _ _ _ _"
+        - "You don't see a well."
+        - ""
+        - Changed the season setting.
+        - The well is cool in the heat.
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_campaign() {
+        let mut command_runner = CommandRunner::new(vec![]);
+        command_runner.files.insert(
+            CAMPAIGN_PATH,
+            "{ start: town, levels: { town: town.yml, cellar: cellar.yml } }".into(),
+        );
+        command_runner.files.insert(
+            "town.yml",
+            r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+entries: { hatch: [1, 1, 0] }
+npcs: {}
+regions: {}
+rooms:
+  - title: Town
+    coord: [1, 1, 0]
+    description: A quiet town, with a hatch leading down.
+    exits: { south: { level: cellar, entry: stairs } }
+"#
+            .into(),
+        );
+        command_runner.files.insert(
+            "cellar.yml",
+            r#"
+maps: [["----", "-..-", "----"]]
+entry: [2, 1, 0]
+entries: { stairs: [1, 1, 0] }
+npcs: {}
+regions: {}
+rooms:
+  - title: Cellar Stairs
+    coord: [1, 1, 0]
+    description: Stairs lead back up to the town.
+    exits: { north: { level: town, entry: hatch } }
+  - title: Cellar
+    coord: [2, 1, 0]
+    description: A damp cellar.
+"#
+            .into(),
+        );
+
+        let mut game = Game::new(ItemDatabase::new(), &mut command_runner).unwrap();
+        // What's done in a level is kept by its own ids, so it stays with that level.
+        game.save_state.disarmed.insert("trap".into());
+        game.save_state.solved.insert("dial".into());
+        assert!(game
+            .step("drop sword then south then east".into())
+            .is_none());
+        assert_eq!(game.save_state.level, "cellar");
+        assert!(game.save_state.disarmed.is_empty());
+        assert!(game.save_state.solved.is_empty());
+        assert!(game.step("west then north".into()).is_none());
+        assert_eq!(game.save_state.level, "town");
+        assert!(game.save_state.disarmed.contains("trap"));
+        assert!(game.save_state.solved.contains("dial"));
+        game.output().output.clear();
+        assert!(game.step("take sword".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - You place the sword in your inventory.
+        "###);
+        assert!(game.step("look".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Town
+        - ""
+        - "    A quiet town, with a hatch leading down. "
+        - ""
+        - ""
+        - "Exits: _ _ s _"
+        "###);
+    }
+
+    #[test]
+    fn test_daemons_by_level() {
+        let mut command_runner = CommandRunner::new(vec![]);
+        command_runner.files.insert(
+            CAMPAIGN_PATH,
+            "{ start: town, levels: { town: town.yml, cellar: cellar.yml } }".into(),
+        );
+        command_runner.files.insert(
+            "town.yml",
+            r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+entries: { hatch: [1, 1, 0] }
+npcs: {}
+regions: {}
+daemons:
+  bell: { every: 1, effects: [{ say: The town bell rings. }] }
+rooms:
+  - title: Town
+    coord: [1, 1, 0]
+    description: A quiet town.
+    exits: { south: { level: cellar, entry: stairs } }
+    actions:
+      - { verb: Look, targets: [rope], value: You tie up the bell rope., effects: [{ disable: bell }] }
+"#
+            .into(),
+        );
+        command_runner.files.insert(
+            "cellar.yml",
+            r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+entries: { stairs: [1, 1, 0] }
+npcs: {}
+regions: {}
+daemons:
+  bell: { every: 1, effects: [{ say: A bell tolls below. }] }
+rooms:
+  - title: Cellar
+    coord: [1, 1, 0]
+    description: A damp cellar.
+    exits: { north: { level: town, entry: hatch } }
+"#
+            .into(),
+        );
+
+        let mut game = Game::new(ItemDatabase::new(), &mut command_runner).unwrap();
+        // Disabling the town's bell leaves the cellar's, with the same id, ringing.
+        assert!(game.step("look rope".into()).is_none());
+        assert!(game.step("south".into()).is_none());
+        game.output().output.clear();
+        assert!(game.step("look".into()).is_none());
+        assert!(take_output(&game).contains(&"A bell tolls below.".to_string()));
+        assert!(game.save_state.daemons["bell"].enabled);
+        assert!(game.step("north".into()).is_none());
+        game.output().output.clear();
+        assert!(game.step("look".into()).is_none());
+        assert!(!take_output(&game).contains(&"The town bell rings.".to_string()));
+        assert!(!game.save_state.daemons["bell"].enabled);
+    }
+
+    #[test]
+    fn test_timers_by_level() {
+        let mut command_runner = CommandRunner::new(vec![]);
+        command_runner.files.insert(
+            CAMPAIGN_PATH,
+            "{ start: town, levels: { town: town.yml, cellar: cellar.yml } }".into(),
+        );
+        command_runner.files.insert(
+            "town.yml",
+            r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+entries: { hatch: [1, 1, 0] }
+npcs: {}
+regions: {}
+timers:
+  fuse: { turns: 3, effects: [{ say: The fireworks go off over the town. }] }
+rooms:
+  - title: Town
+    coord: [1, 1, 0]
+    description: A quiet town.
+    exits: { south: { level: cellar, entry: stairs } }
+    actions:
+      - { verb: Look, targets: [fireworks], value: You light the fuse., effects: [{ arm: fuse }] }
+"#
+            .into(),
+        );
+        command_runner.files.insert(
+            "cellar.yml",
+            r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+entries: { stairs: [1, 1, 0] }
+npcs: {}
+regions: {}
+timers:
+  fuse: { turns: 1, effects: [{ say: The cellar floods. }] }
+rooms:
+  - title: Cellar
+    coord: [1, 1, 0]
+    description: A damp cellar.
+    exits: { north: { level: town, entry: hatch } }
+"#
+            .into(),
+        );
+
+        let mut game = Game::new(ItemDatabase::new(), &mut command_runner).unwrap();
+        // The town's fuse waits for the player in the town, and never sets off the
+        // cellar's timer with the same id.
+        assert!(game.step("look fireworks".into()).is_none());
+        assert_eq!(game.save_state.timers.get("fuse"), Some(&2));
+        assert!(game.step("south".into()).is_none());
+        assert!(game.save_state.timers.is_empty());
+        game.output().output.clear();
+        assert!(game.step("look then look".into()).is_none());
+        assert!(!take_output(&game).contains(&"The cellar floods.".to_string()));
+        assert!(game.step("north".into()).is_none());
+        assert_eq!(game.save_state.timers.get("fuse"), Some(&1));
+        game.output().output.clear();
+        assert!(game.step("look".into()).is_none());
+        assert!(take_output(&game).contains(&"The fireworks go off over the town.".to_string()));
+        assert!(game.save_state.timers.is_empty());
+    }
+}
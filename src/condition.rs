@@ -0,0 +1,471 @@
+use crate::{
+    campaign::{Date, Today},
+    level::{Lighting, Weather},
+    template::Variables,
+    ItemDatabase,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+};
+
+/// A condition on the game's state, written as a small expression. It can be used in
+/// the `when` of actions and exits, and in the {if} blocks of templates.
+///
+/// ```yml
+/// when: flags.gate-open && items.gold >= 10
+/// ```
+///
+/// Values are named like in templates: `flags.<id>`, `stats.<id>`, `items.<id>` for
+/// how many are carried, and `player_name`. They can be compared with `==`, `!=`, `<`,
+/// `<=`, `>`, and `>=`, and combined with `&&`, `||`, `!`, and parentheses, or the
+/// words `and`, `or`, and `not`. A value on its own is true unless it's empty or 0.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Condition(pub String);
+
+impl Condition {
+    /// Whether the condition is true. A condition that doesn't parse is never true,
+    /// validating the level reports it.
+    pub fn holds(&self, variables: &Variables) -> bool {
+        evaluate(&self.0, variables).unwrap_or(false)
+    }
+}
+
+/// Evaluate a condition, or explain why it doesn't parse.
+pub fn evaluate(text: &str, variables: &Variables) -> Result<bool, String> {
+    Ok(parse(text)?.value(variables).is_true())
+}
+
+/// Check that a condition parses, and that every value in it is known. As with
+/// templates, the flags and stats can't be checked.
+pub fn check(text: &str, item_db: &ItemDatabase) -> Result<(), String> {
+    let expression = parse(text)?;
+    let variables = Variables {
+        player_name: "",
+        item_db,
+        inventory: &[],
+        flags: &BTreeSet::new(),
+        stats: &BTreeMap::new(),
+        reputation: &BTreeMap::new(),
+        solved: &BTreeSet::new(),
+        companions: &BTreeMap::new(),
+        party: &BTreeSet::new(),
+        timers: &BTreeMap::new(),
+        light: Lighting::Normal,
+        weather: Some(Weather::Clear),
+        // Any campaign could have a calendar.
+        date: Some(Date::default()),
+        today: Some(Today::default()),
+    };
+    let mut names = Vec::new();
+    expression.collect_names(&mut names);
+    for name in names {
+        if variables.get(name).is_none() {
+            return Err(format!("the unknown value {:?}", name));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'t> {
+    And,
+    Or,
+    Not,
+    Compare(Operator),
+    Open,
+    Close,
+    Number(i64),
+    Text(String),
+    Name(&'t str),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, PartialEq)]
+enum Expression<'t> {
+    Or(Box<Expression<'t>>, Box<Expression<'t>>),
+    And(Box<Expression<'t>>, Box<Expression<'t>>),
+    Not(Box<Expression<'t>>),
+    Compare(Box<Expression<'t>>, Operator, Box<Expression<'t>>),
+    Number(i64),
+    Text(String),
+    Name(&'t str),
+}
+
+/// The value of part of an expression.
+enum Value {
+    Number(i64),
+    Text(String),
+}
+
+impl Value {
+    fn is_true(&self) -> bool {
+        match self {
+            Value::Number(number) => *number != 0,
+            Value::Text(text) => !text.is_empty() && text != "0",
+        }
+    }
+
+    fn compare(&self, other: &Value) -> Ordering {
+        match (self.as_number(), other.as_number()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => self.as_text().cmp(&other.as_text()),
+        }
+    }
+
+    fn as_number(&self) -> Option<i64> {
+        match self {
+            Value::Number(number) => Some(*number),
+            Value::Text(text) => text.parse().ok(),
+        }
+    }
+
+    fn as_text(&self) -> String {
+        match self {
+            Value::Number(number) => number.to_string(),
+            Value::Text(text) => text.clone(),
+        }
+    }
+}
+
+impl Expression<'_> {
+    fn value(&self, variables: &Variables) -> Value {
+        let boolean = |is_true: bool| Value::Number(is_true as i64);
+        match self {
+            Expression::Or(a, b) => {
+                boolean(a.value(variables).is_true() || b.value(variables).is_true())
+            }
+            Expression::And(a, b) => {
+                boolean(a.value(variables).is_true() && b.value(variables).is_true())
+            }
+            Expression::Not(a) => boolean(!a.value(variables).is_true()),
+            Expression::Compare(a, operator, b) => {
+                let ordering = a.value(variables).compare(&b.value(variables));
+                boolean(match operator {
+                    Operator::Equal => ordering == Ordering::Equal,
+                    Operator::NotEqual => ordering != Ordering::Equal,
+                    Operator::Less => ordering == Ordering::Less,
+                    Operator::LessOrEqual => ordering != Ordering::Greater,
+                    Operator::Greater => ordering == Ordering::Greater,
+                    Operator::GreaterOrEqual => ordering != Ordering::Less,
+                })
+            }
+            Expression::Number(number) => Value::Number(*number),
+            Expression::Text(text) => Value::Text(text.clone()),
+            // Values that aren't known are empty, and so false.
+            Expression::Name(name) => Value::Text(variables.get(name).unwrap_or_default()),
+        }
+    }
+
+    fn collect_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Expression::Or(a, b) | Expression::And(a, b) | Expression::Compare(a, _, b) => {
+                a.collect_names(names);
+                b.collect_names(names);
+            }
+            Expression::Not(a) => a.collect_names(names),
+            Expression::Name(name) => names.push(name),
+            Expression::Number(_) | Expression::Text(_) => {}
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token<'_>>, String> {
+    let is_name_char = |ch: char| ch.is_alphanumeric() || matches!(ch, '_' | '-' | '.');
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(ch) = rest.chars().next() {
+        let two = rest.get(..2).unwrap_or("");
+        let (token, length) = match (ch, two) {
+            (_, "&&") => (Token::And, 2),
+            (_, "||") => (Token::Or, 2),
+            (_, "==") => (Token::Compare(Operator::Equal), 2),
+            (_, "!=") => (Token::Compare(Operator::NotEqual), 2),
+            (_, "<=") => (Token::Compare(Operator::LessOrEqual), 2),
+            (_, ">=") => (Token::Compare(Operator::GreaterOrEqual), 2),
+            ('<', _) => (Token::Compare(Operator::Less), 1),
+            ('>', _) => (Token::Compare(Operator::Greater), 1),
+            ('!', _) => (Token::Not, 1),
+            ('(', _) => (Token::Open, 1),
+            (')', _) => (Token::Close, 1),
+            ('"', _) => {
+                let end = rest[1..]
+                    .find('"')
+                    .ok_or("a string is missing its closing quote")?;
+                (Token::Text(rest[1..end + 1].to_string()), end + 2)
+            }
+            _ if is_name_char(ch) => {
+                let length = rest.find(|ch| !is_name_char(ch)).unwrap_or(rest.len());
+                let word = &rest[..length];
+                let token = match word {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    "true" => Token::Number(1),
+                    "false" => Token::Number(0),
+                    _ => match word.parse() {
+                        Ok(number) => Token::Number(number),
+                        Err(_) => Token::Name(word),
+                    },
+                };
+                (token, length)
+            }
+            _ => return Err(format!("an unexpected {:?}", ch)),
+        };
+        tokens.push(token);
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+fn parse(text: &str) -> Result<Expression<'_>, String> {
+    let tokens = tokenize(text)?;
+    if tokens.is_empty() {
+        return Err("an empty condition".into());
+    }
+    let mut parser = Parser { tokens, index: 0 };
+    let expression = parser.or()?;
+    match parser.tokens.get(parser.index) {
+        Some(token) => Err(format!("an unexpected {}", describe(token))),
+        None => Ok(expression),
+    }
+}
+
+/// Parses tokens from the loosest binding operator to the tightest, which is `||`,
+/// then `&&`, then `!`, then the comparisons.
+struct Parser<'t> {
+    tokens: Vec<Token<'t>>,
+    index: usize,
+}
+
+impl<'t> Parser<'t> {
+    fn next_if(&mut self, matches: impl Fn(&Token) -> bool) -> bool {
+        let is_match = self.tokens.get(self.index).is_some_and(matches);
+        if is_match {
+            self.index += 1;
+        }
+        is_match
+    }
+
+    fn or(&mut self) -> Result<Expression<'t>, String> {
+        let mut expression = self.and()?;
+        while self.next_if(|token| *token == Token::Or) {
+            expression = Expression::Or(Box::new(expression), Box::new(self.and()?));
+        }
+        Ok(expression)
+    }
+
+    fn and(&mut self) -> Result<Expression<'t>, String> {
+        let mut expression = self.not()?;
+        while self.next_if(|token| *token == Token::And) {
+            expression = Expression::And(Box::new(expression), Box::new(self.not()?));
+        }
+        Ok(expression)
+    }
+
+    fn not(&mut self) -> Result<Expression<'t>, String> {
+        if self.next_if(|token| *token == Token::Not) {
+            return Ok(Expression::Not(Box::new(self.not()?)));
+        }
+        let expression = self.operand()?;
+        if let Some(Token::Compare(operator)) = self.tokens.get(self.index) {
+            let operator = *operator;
+            self.index += 1;
+            let other = self.operand()?;
+            return Ok(Expression::Compare(
+                Box::new(expression),
+                operator,
+                Box::new(other),
+            ));
+        }
+        Ok(expression)
+    }
+
+    fn operand(&mut self) -> Result<Expression<'t>, String> {
+        let token = match self.tokens.get_mut(self.index) {
+            Some(token) => std::mem::replace(token, Token::Close),
+            None => return Err("something missing at the end".into()),
+        };
+        self.index += 1;
+        match token {
+            Token::Number(number) => Ok(Expression::Number(number)),
+            Token::Text(text) => Ok(Expression::Text(text)),
+            Token::Name(name) => Ok(Expression::Name(name)),
+            Token::Open => {
+                let expression = self.or()?;
+                if !self.next_if(|token| *token == Token::Close) {
+                    return Err("a ( with no )".into());
+                }
+                Ok(expression)
+            }
+            token => Err(format!("an unexpected {}", describe(&token))),
+        }
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::And => "&&".into(),
+        Token::Or => "||".into(),
+        Token::Not => "!".into(),
+        Token::Compare(_) => "comparison".into(),
+        Token::Open => "(".into(),
+        Token::Close => ")".into(),
+        Token::Number(number) => number.to_string(),
+        Token::Text(text) => format!("{:?}", text),
+        Token::Name(name) => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn test_conditions() {
+        let item_db = ItemDatabase::new();
+        let mut gold = item_db.find("gold").unwrap().clone();
+        gold.quantity = 12;
+        let flags: BTreeSet<String> = vec!["gate-open".to_string()].into_iter().collect();
+        let stats: BTreeMap<String, i64> = vec![("health".to_string(), 3)].into_iter().collect();
+        let reputation: BTreeMap<String, i64> =
+            vec![("guild".to_string(), 12)].into_iter().collect();
+        let solved: BTreeSet<String> = vec!["dial".to_string()].into_iter().collect();
+        let companions: BTreeMap<String, Vec<_>> = vec![("dog".to_string(), vec![gold.clone()])]
+            .into_iter()
+            .collect();
+        let timers: BTreeMap<String, usize> = vec![("tide".to_string(), 2)].into_iter().collect();
+        let variables = Variables {
+            player_name: "Ada",
+            item_db: &item_db,
+            inventory: &[gold],
+            flags: &flags,
+            stats: &stats,
+            reputation: &reputation,
+            solved: &solved,
+            companions: &companions,
+            party: &BTreeSet::new(),
+            timers: &timers,
+            light: Lighting::Dim,
+            weather: None,
+            date: Some(Date {
+                day: 3,
+                season: "winter",
+                playthrough: 2,
+            }),
+            today: Some(Today { month: 10, day: 31 }),
+        };
+        let evaluate = |text| evaluate(text, &variables);
+
+        assert_eq!(evaluate("flags.gate-open && items.gold >= 10"), Ok(true));
+        assert_eq!(evaluate("money == 1200"), Ok(true));
+        assert_eq!(evaluate("light.dim and not light.bright"), Ok(true));
+        assert_eq!(evaluate("flags.gate-open && items.gold > 12"), Ok(false));
+        assert_eq!(evaluate("!flags.sailor || stats.health < 2"), Ok(true));
+        assert_eq!(evaluate("not (flags.sailor or items.sword)"), Ok(true));
+        assert_eq!(
+            evaluate("reputation.guild >= 10 and reputation.thieves == 0"),
+            Ok(true)
+        );
+        assert_eq!(
+            evaluate("calendar.season == \"winter\" and calendar.playthrough > 1"),
+            Ok(true)
+        );
+        assert_eq!(evaluate("puzzles.dial and not puzzles.levers"), Ok(true));
+        assert_eq!(
+            evaluate("companions.dog.gold == 12 and not companions.cat.gold"),
+            Ok(true)
+        );
+        assert_eq!(evaluate("today.month == 10 and today.day == 31"), Ok(true));
+        assert_eq!(evaluate("timers.tide < 3 and not timers.keg"), Ok(true));
+        assert_eq!(
+            evaluate("player_name == \"Ada\" and stats.mana == 0"),
+            Ok(true)
+        );
+        assert_eq!(evaluate("flags.unknown"), Ok(false));
+
+        assert_eq!(evaluate(""), Err("an empty condition".into()));
+        assert_eq!(evaluate("(flags.a && flags.b"), Err("a ( with no )".into()));
+        assert_eq!(
+            evaluate("items.gold >="),
+            Err("something missing at the end".into())
+        );
+        assert_eq!(evaluate("items.gold 10"), Err("an unexpected 10".into()));
+        assert_eq!(check("items.gold >= 10", &item_db), Ok(()));
+        assert_eq!(
+            check("items.unicorn", &item_db),
+            Err("the unknown value \"items.unicorn\"".into())
+        );
+        assert_eq!(
+            check("gate-open", &item_db),
+            Err("the unknown value \"gate-open\"".into())
+        );
+    }
+
+    #[test]
+    fn test_conditions_in_game() {
+        let level = r#"
+maps: [["-----", "-.-.-", "-----"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+rooms:
+  - title: Cellar
+    coord: [1, 1, 0]
+    description: A damp cellar.
+    exits:
+      east: { entry: vault, when: flags.unlocked, blocked: The vault door won't budge. }
+    actions:
+      - { verb: Look, targets: [door], value: It's locked tight., when: not flags.unlocked }
+      - { verb: Look, targets: [door], value: It stands open., when: flags.unlocked }
+      - verb: Look
+        targets: [lever]
+        value: You pull the lever.
+        when: "!flags.unlocked"
+        effects: [{ set_flag: unlocked }]
+  - { title: Vault, coord: [3, 1, 0], id: vault, description: An empty vault. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        for command in [
+            "look door",
+            "east",
+            "look lever",
+            "look lever",
+            "look door",
+            "east",
+        ] {
+            assert!(game.step(command.into()).is_none());
+        }
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - "It's locked tight."
+        - ""
+        - "The vault door won't budge."
+        - You pull the lever.
+        - ""
+        - "You don't see a lever."
+        - ""
+        - It stands open.
+        - ""
+        - Vault
+        - ""
+        - "    An empty vault. "
+        - ""
+        - ""
+        - "Exits: _ _ _ _"
+        "###);
+    }
+}
@@ -0,0 +1,365 @@
+use crate::text_filter::TextFilter;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+pub const CONFIG_PATH: &str = "config.toml";
+
+/// The shortest line the output can be wrapped to, no matter how narrow the terminal.
+pub const MIN_WIDTH: usize = 20;
+/// The furthest a room's description can be indented.
+const MAX_INDENT: usize = 16;
+/// The longest pause between each character or line that's typed out.
+const MAX_TEXT_DELAY: u64 = 1000;
+
+/// The player's preferences for how the game is shown, read from config.toml. Only
+/// the settings that differ from the defaults need to be listed.
+///
+/// ```toml
+/// prompt = ">"
+/// width = 60
+/// indent = 2
+/// theme = "high-contrast"
+/// confirm = false
+/// text_speed = "lines"
+/// text_delay = 50
+/// name = "Ada"
+/// text_filters = ["strip_emoji", "letter_spacing"]
+/// mute = true
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// What's shown before the player types a command.
+    pub prompt: String,
+    /// The longest a line of the output can be, narrower terminals wrap sooner.
+    pub width: usize,
+    /// How far the paragraphs of a room's description are indented.
+    pub indent: usize,
+    /// The name of the color theme, from data/themes.yml.
+    pub theme: String,
+    /// Whether to ask before doing something that can't be taken back, like restarting,
+    /// or taking a level's point of no return.
+    pub confirm: bool,
+    /// Whether the text is typed out, a character or a line at a time.
+    pub text_speed: TextSpeed,
+    /// The milliseconds to wait after each character or line that's typed out.
+    pub text_delay: u64,
+    /// What the game calls the player, in text like "Welcome, {player_name}."
+    pub name: String,
+    /// Changes to the output for accessibility or limited frontends, see `TextFilter`.
+    pub text_filters: Vec<TextFilter>,
+    /// Force the season of the campaign's calendar, or None to let it follow the
+    /// calendar, which is written as "auto".
+    #[serde(with = "auto")]
+    pub season: Option<String>,
+    /// Whether the sound and music are turned off.
+    pub mute: bool,
+}
+
+/// How the game's text is shown in the terminal. Typing it out is slower, but gives the
+/// game more atmosphere. Pressing enter shows the rest of the text at once.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextSpeed {
+    #[serde(alias = "off")]
+    Instant,
+    Characters,
+    Lines,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            prompt: "»".into(),
+            width: 90,
+            indent: 4,
+            theme: "default".into(),
+            confirm: true,
+            text_speed: TextSpeed::Instant,
+            text_delay: 30,
+            name: "Traveler".into(),
+            text_filters: Vec::new(),
+            season: None,
+            mute: false,
+        }
+    }
+}
+
+impl Config {
+    /// Read the config file, or use the defaults if there isn't one.
+    pub fn load(path: &Path) -> Result<Config, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text)
+                .map_err(|message| format!("Unable to read {}, {}", path.display(), message)),
+            Err(_) => Ok(Config::default()),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let config: Config = toml::from_str(text).map_err(|err| err.message().to_string())?;
+        config.check()?;
+        Ok(config)
+    }
+
+    /// Check that the settings are in range, which the types alone don't ensure.
+    fn check(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name can't be empty".into());
+        }
+        if self.width < MIN_WIDTH {
+            return Err(format!("width must be a number of at least {}", MIN_WIDTH));
+        }
+        if self.indent > MAX_INDENT {
+            return Err(format!("indent must be a number up to {}", MAX_INDENT));
+        }
+        if self.text_delay > MAX_TEXT_DELAY {
+            return Err(format!(
+                "text_delay must be a number of milliseconds up to {}",
+                MAX_TEXT_DELAY
+            ));
+        }
+        Ok(())
+    }
+
+    /// Change a setting from what the player typed, like `width 60` in the "config"
+    /// command. Nothing changes if the value isn't valid.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        let flag = |key: &str| match value {
+            "true" | "yes" | "on" => Ok(true),
+            "false" | "no" | "off" => Ok(false),
+            _ => Err(format!("{} must be true or false", key)),
+        };
+        let mut config = self.clone();
+        match key {
+            "prompt" => config.prompt = value.to_string(),
+            "name" => config.name = value.to_string(),
+            "theme" => config.theme = value.to_string(),
+            "season" => {
+                config.season = match value {
+                    "" | "auto" => None,
+                    season => Some(season.to_string()),
+                }
+            }
+            "text_filters" => config.text_filters = TextFilter::parse_list(value)?,
+            "width" => {
+                config.width = value
+                    .parse()
+                    .map_err(|_| format!("width must be a number of at least {}", MIN_WIDTH))?
+            }
+            "indent" => {
+                config.indent = value
+                    .parse()
+                    .map_err(|_| format!("indent must be a number up to {}", MAX_INDENT))?
+            }
+            "confirm" => config.confirm = flag(key)?,
+            "mute" => config.mute = flag(key)?,
+            "text_speed" => {
+                config.text_speed = match value {
+                    "instant" | "off" => TextSpeed::Instant,
+                    "characters" => TextSpeed::Characters,
+                    "lines" => TextSpeed::Lines,
+                    _ => return Err("text_speed must be instant, characters, or lines".into()),
+                }
+            }
+            "text_delay" => {
+                config.text_delay = value.parse().map_err(|_| {
+                    format!(
+                        "text_delay must be a number of milliseconds up to {}",
+                        MAX_TEXT_DELAY
+                    )
+                })?
+            }
+            _ => {
+                return Err(format!(
+                    "there is no setting {:?}, the settings are prompt, width, indent, theme, \
+                     confirm, text_speed, text_delay, name, text_filters, season, and mute",
+                    key
+                ))
+            }
+        }
+        config.check()?;
+        *self = config;
+        Ok(())
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("Unable to serialize the config.")
+    }
+}
+
+/// Reads and writes a setting that is left to the game when it's "auto".
+mod auto {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<String>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(value.as_deref().unwrap_or("auto"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<String>, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Ok(Some(value).filter(|value| value != "auto"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{testing::*, GameLoopResponse};
+
+    #[test]
+    fn test_config() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+
+        let config = Config::parse(
+            r#"
+            # For a narrow screen.
+            width = 40 # Characters.
+            indent = 0
+            prompt = "say \"what\"? # "
+            confirm = false
+            text_speed = "lines"
+            name = "Ada"
+            text_filters = ["uppercase_headings", "strip_emoji"]
+            season = "winter"
+            mute = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.width, 40);
+        assert_eq!(config.indent, 0);
+        assert_eq!(config.prompt, "say \"what\"? # ");
+        assert!(!config.confirm);
+        assert_eq!(config.text_speed, TextSpeed::Lines);
+        assert_eq!(config.name, "Ada");
+        assert_eq!(
+            config.text_filters,
+            vec![TextFilter::UppercaseHeadings, TextFilter::StripEmoji]
+        );
+        assert_eq!(config.season.as_deref(), Some("winter"));
+        assert!(config.mute);
+        assert_eq!(config.theme, "default");
+        assert_eq!(Config::parse(&config.to_toml()).unwrap(), config);
+        assert_eq!(
+            Config::parse(&Config::default().to_toml()).unwrap(),
+            Config::default()
+        );
+        assert_eq!(Config::parse("season = \"auto\"").unwrap().season, None);
+
+        assert_eq!(
+            Config::parse("width = 5").unwrap_err(),
+            "width must be a number of at least 20"
+        );
+        assert!(Config::parse("\ncolor = \"red\"")
+            .unwrap_err()
+            .starts_with("unknown field `color`"));
+        assert_eq!(
+            Config::parse("text_delay = 5000").unwrap_err(),
+            "text_delay must be a number of milliseconds up to 1000"
+        );
+        // Only TOML is read, so strings have to be quoted.
+        assert!(Config::parse("season = winter").is_err());
+        assert!(Config::parse("text_filters = [\"bold\"]")
+            .unwrap_err()
+            .starts_with("unknown variant `bold`"));
+
+        let mut config = Config::default();
+        config
+            .set("text_filters", "strip_emoji, letter_spacing")
+            .unwrap();
+        assert_eq!(
+            config.text_filters,
+            vec![TextFilter::StripEmoji, TextFilter::LetterSpacing]
+        );
+        assert_eq!(
+            config.set("width", "5").unwrap_err(),
+            "width must be a number of at least 20"
+        );
+        assert_eq!(config.width, 90);
+        assert_eq!(
+            config.set("confirm", "maybe").unwrap_err(),
+            "confirm must be true or false"
+        );
+    }
+
+    #[test]
+    fn test_config_command() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+rooms:
+  - title: Library
+    coord: [1, 1, 0]
+    description: Shelves of dusty books reach up to a ceiling painted with stars.
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        assert!(game.step("config width 30".into()).is_none());
+        assert!(game.step("config indent = 2".into()).is_none());
+        assert!(game.step("config width 3".into()).is_none());
+        assert!(game.step("config indent".into()).is_none());
+        assert!(game.step("look".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Changed the width setting.
+        - Changed the indent setting.
+        - "That setting can't be changed: width must be a number of at least 20."
+        - "Type the new value after the setting: config indent <value>"
+        - Library
+        - ""
+        - "  Shelves of dusty books reach "
+        - "  up to a ceiling painted with "
+        - "  stars. "
+        - ""
+        - ""
+        - "Exits: _ _ _ _"
+        "###);
+
+        // A narrow terminal wraps sooner than the config, and the description is
+        // wrapped again when it changes.
+        game.output().width = Some(24);
+        assert!(game.step("look".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Library
+        - ""
+        - "  Shelves of dusty books "
+        - "  reach up to a ceiling "
+        - "  painted with stars. "
+        - ""
+        - ""
+        - "Exits: _ _ _ _"
+        "###);
+
+        assert!(game.step("config confirm off".into()).is_none());
+        assert!(game.step("config".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Changed the confirm setting.
+        - "prompt = \"»\""
+        - width = 30
+        - indent = 2
+        - "theme = \"default\""
+        - confirm = false
+        - "text_speed = \"instant\""
+        - text_delay = 30
+        - "name = \"Traveler\""
+        - "text_filters = []"
+        - "season = \"auto\""
+        - mute = false
+        - ""
+        - "Change a setting with: config width 60"
+        "###);
+        assert!(matches!(
+            game.step("restart".into()),
+            Some(GameLoopResponse::Restart)
+        ));
+    }
+}
@@ -0,0 +1,241 @@
+//! Money is carried as coins of a few denominations, like copper, silver, and gold. The
+//! denominations are the items with the `Money` variant, and each coin is worth its
+//! `value` stat. Prices, and other amounts of money, are counted in the smallest
+//! denomination.
+
+use crate::level::{InventoryItem, ItemDatabase, ItemVariant};
+
+/// Some number of coins of a denomination.
+pub type Coins<'a> = (&'a InventoryItem, usize);
+
+/// The denominations of money, from the most valuable down.
+pub fn denominations(item_db: &ItemDatabase) -> Vec<&InventoryItem> {
+    let mut denominations: Vec<_> = item_db
+        .iter()
+        .filter(|item| item.variant == ItemVariant::Money)
+        .collect();
+    denominations.sort_by_key(|item| std::cmp::Reverse(worth(item)));
+    denominations
+}
+
+/// What a single coin is worth, in the smallest denomination.
+pub fn worth(item: &InventoryItem) -> usize {
+    item.stats.value.unwrap_or(1).max(1)
+}
+
+/// How much all of the coins among some items are worth.
+pub fn total(items: &[InventoryItem]) -> usize {
+    items
+        .iter()
+        .filter(|item| item.variant == ItemVariant::Money)
+        .map(|item| item.quantity * worth(item))
+        .sum()
+}
+
+/// Break an amount into the fewest coins, like the change a merchant gives back.
+pub fn change(amount: usize, item_db: &ItemDatabase) -> Vec<Coins<'_>> {
+    let mut left = amount;
+    let mut coins = Vec::new();
+    for item in denominations(item_db) {
+        let count = left / worth(item);
+        if count > 0 {
+            coins.push((item, count));
+            left -= count * worth(item);
+        }
+    }
+    coins
+}
+
+/// Write an amount of money, like "2 gold, 5 silver".
+pub fn format(amount: usize, item_db: &ItemDatabase) -> String {
+    let coins = change(amount, item_db);
+    if coins.is_empty() {
+        return match denominations(item_db).last() {
+            Some(item) => format!("0 {}", item.name),
+            None => "0".into(),
+        };
+    }
+    format_coins(&coins)
+}
+
+/// Write out some coins, like "1 gold, 3 copper".
+pub fn format_coins(coins: &[Coins]) -> String {
+    coins
+        .iter()
+        .map(|(item, count)| format!("{} {}", count, item.name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Read an amount of money the player typed, like "15", "2 silver", or
+/// "1 gold, 5 silver". A number on its own is in the smallest denomination.
+pub fn parse(text: &str, item_db: &ItemDatabase) -> Option<usize> {
+    let denominations = denominations(item_db);
+    let smallest = denominations.last().map_or(1, |item| worth(item));
+    let mut amount = 0;
+    let mut count: Option<usize> = None;
+    for word in text.split(|ch: char| ch.is_whitespace() || ch == ',') {
+        if word.is_empty() || word == "coin" || word == "coins" {
+            continue;
+        }
+        if let Ok(number) = word.parse::<usize>() {
+            amount += count.replace(number).map_or(0, |count| count * smallest);
+            continue;
+        }
+        let word = word.to_lowercase();
+        let item = denominations.iter().find(|item| {
+            item.name.to_lowercase() == word
+                || item.plural.as_deref() == Some(word.as_str())
+                || item.targets.iter().any(|target| *target == word)
+        })?;
+        amount += count.take()? * worth(item);
+    }
+    Some(amount + count.map_or(0, |count| count * smallest))
+}
+
+/// The coins handed over for a price, and the change that comes back.
+#[derive(Debug, PartialEq)]
+pub struct Payment<'a> {
+    pub paid: Vec<Coins<'a>>,
+    pub change: Vec<Coins<'a>>,
+}
+
+/// Work out how to pay a price with the coins among some items, or None if they
+/// aren't worth enough. The price is paid exactly when the coins allow it. Otherwise a
+/// single coin that covers it is handed over, or failing that the least valuable coin
+/// that covers what's left, and the rest comes back as change.
+pub fn pay<'a>(
+    items: &[InventoryItem],
+    price: usize,
+    item_db: &'a ItemDatabase,
+) -> Option<Payment<'a>> {
+    if total(items) < price {
+        return None;
+    }
+    let carried = |item: &InventoryItem| -> usize {
+        items
+            .iter()
+            .filter(|other| other.id == item.id)
+            .map(|other| other.quantity)
+            .sum()
+    };
+    let denominations = denominations(item_db);
+
+    // Pay as much as possible exactly, with the most valuable coins first.
+    let mut left = price;
+    let mut paid = Vec::new();
+    let mut spare = None;
+    for &item in denominations.iter() {
+        let count = carried(item).min(left / worth(item));
+        if count > 0 {
+            paid.push((item, count));
+            left -= count * worth(item);
+        }
+        if carried(item) > count {
+            spare = Some(item);
+        }
+    }
+    if left == 0 {
+        return Some(Payment {
+            paid,
+            change: Vec::new(),
+        });
+    }
+
+    let single = denominations
+        .iter()
+        .rev()
+        .find(|&&item| worth(item) >= price && carried(item) > 0);
+    if let Some(&item) = single {
+        return Some(Payment {
+            paid: vec![(item, 1)],
+            change: change(worth(item) - price, item_db),
+        });
+    }
+
+    // Each spare coin is worth more than what's left, or it would have been paid.
+    let spare = spare?;
+    match paid.iter_mut().find(|(item, _)| item.id == spare.id) {
+        Some((_, count)) => *count += 1,
+        None => paid.push((spare, 1)),
+    }
+    Some(Payment {
+        paid,
+        change: change(worth(spare) - left, item_db),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::level::{Respawn, SaleItem};
+
+    #[test]
+    fn test_currency() {
+        let item_db = ItemDatabase::new();
+        let coins = |counts: &[(&str, usize)]| -> Vec<InventoryItem> {
+            counts
+                .iter()
+                .map(|(id, count)| InventoryItem {
+                    quantity: *count,
+                    ..item_db.get(id).clone()
+                })
+                .collect()
+        };
+        let ids = |coins: &[Coins]| -> Vec<(String, usize)> {
+            coins
+                .iter()
+                .map(|(item, count)| (item.id.clone(), *count))
+                .collect()
+        };
+
+        assert_eq!(format(0, &item_db), "0 copper");
+        assert_eq!(format(1234, &item_db), "12 gold, 3 silver, 4 copper");
+        assert_eq!(total(&coins(&[("gold", 2), ("copper", 5)])), 205);
+        assert_eq!(parse("15", &item_db), Some(15));
+        assert_eq!(parse("1 gold, 5 silver", &item_db), Some(150));
+        assert_eq!(parse("2 silver coins", &item_db), Some(20));
+        assert_eq!(parse("silver", &item_db), None);
+
+        // Exact change.
+        let purse = coins(&[("gold", 1), ("silver", 5), ("copper", 3)]);
+        let payment = pay(&purse, 152, &item_db).unwrap();
+        assert_eq!(
+            ids(&payment.paid),
+            [
+                ("gold".into(), 1),
+                ("silver".into(), 5),
+                ("copper".into(), 2)
+            ]
+        );
+        assert!(payment.change.is_empty());
+
+        // A single coin, with change.
+        let payment = pay(&coins(&[("gold", 1), ("silver", 5)]), 70, &item_db).unwrap();
+        assert_eq!(ids(&payment.paid), [("gold".into(), 1)]);
+        assert_eq!(ids(&payment.change), [("silver".into(), 3)]);
+
+        // Topping up with one more coin.
+        let payment = pay(&coins(&[("gold", 2), ("silver", 3)]), 125, &item_db).unwrap();
+        assert_eq!(
+            ids(&payment.paid),
+            [("gold".into(), 1), ("silver".into(), 3)]
+        );
+        assert_eq!(ids(&payment.change), [("copper".into(), 5)]);
+
+        assert_eq!(pay(&coins(&[("silver", 9)]), 100, &item_db), None);
+    }
+
+    #[test]
+    fn test_prices_in_gold() {
+        // Levels from before coins gave prices in gold.
+        let items: Vec<SaleItem> =
+            serde_yaml::from_str("[{ id: apple, cost: 2 }, { id: pear, price: 15 }]").unwrap();
+        let prices: Vec<usize> = items.iter().map(|item| item.price).collect();
+        assert_eq!(prices, vec![200, 15]);
+        let both = serde_yaml::from_str::<SaleItem>("{ id: apple, cost: 2, price: 15 }");
+        assert!(both.is_err());
+        let respawn: Respawn = serde_yaml::from_str("{ gold: 1, points: 5 }").unwrap();
+        assert_eq!(respawn.money, 100);
+    }
+}
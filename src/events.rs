@@ -0,0 +1,99 @@
+use crate::level::{Coord, Level, Trigger};
+
+/// Something that happened in the game, which the engine's rules and the level's scripts
+/// respond to. Commands and effects emit them as they happen, and they're dispatched in
+/// that order once the command is done, see `Game::dispatch_events`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    /// The player took an item, by id.
+    ItemTaken(String),
+    /// The player walked or was moved into a room, from a room of the same level, or
+    /// None from another level.
+    RoomEntered { from: Option<Coord>, to: Coord },
+    /// The player talked to an NPC, or asked them about something, by id.
+    NpcTalkedTo(String),
+    /// A flag was set that wasn't already.
+    FlagSet(String),
+    /// The player finished a turn, and the world takes its own.
+    TurnEnded,
+}
+
+impl GameEvent {
+    /// Whether a script runs on this event.
+    pub fn triggers(&self, trigger: &Trigger, level: &Level) -> bool {
+        match (self, trigger) {
+            (GameEvent::ItemTaken(id), Trigger::ItemTaken(other))
+            | (GameEvent::NpcTalkedTo(id), Trigger::NpcTalkedTo(other))
+            | (GameEvent::FlagSet(id), Trigger::FlagSet(other)) => id == other,
+            (GameEvent::RoomEntered { to, .. }, Trigger::RoomEntered(entry)) => {
+                level.find_entry(entry) == Some(*to)
+            }
+            (GameEvent::TurnEnded, Trigger::TurnEnded) => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::*;
+
+    #[test]
+    fn test_events() {
+        let level = r#"
+maps: [["----", "-..-", "----"]]
+entry: [1, 1, 0]
+npcs:
+  fisher: { name: Fisher, description: A fisher., targets: [fisher], talk: "\"Fish?\"", items: [] }
+regions: {}
+scripts:
+  - on: { item_taken: apple }
+    when: not flags.shaking
+    effects: [{ say: The ground begins to shake. }, { set_flag: shaking }]
+  - on: { flag_set: shaking }
+    effects: [{ say: Birds take flight. }]
+  - on: turn_ended
+    when: flags.shaking
+    effects: [{ say: Dust falls. }]
+  - on: { npc_talked_to: fisher }
+    effects: [{ clear_flag: shaking }]
+  - on: { room_entered: shed }
+    effects: [{ say: The shed creaks. }]
+rooms:
+  - title: Pier
+    coord: [1, 1, 0]
+    description: A pier.
+    npcs: [fisher]
+    items: [{ id: apple, quantity: 1, name: ~, pickup: ~ }]
+  - { title: Shed, id: shed, coord: [2, 1, 0], description: A shed. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        game.output().output.clear();
+        let mut lines = Vec::new();
+        for command in ["take apple", "look", "talk to fisher", "look", "east"] {
+            assert!(game.step(command.into()).is_none());
+            lines.extend(
+                take_output(&game)
+                    .into_iter()
+                    .filter(|line| !line.is_empty() && !line.starts_with("Exits")),
+            );
+        }
+        insta::assert_yaml_snapshot!(lines, @r###"
+        ---
+        - You place the apple in your inventory.
+        - The ground begins to shake.
+        - Birds take flight.
+        - Dust falls.
+        - Pier
+        - "    A pier. "
+        - Dust falls.
+        - "\"Fish?\""
+        - Pier
+        - "    A pier. "
+        - Shed
+        - "    A shed. "
+        - The shed creaks.
+        "###);
+    }
+}
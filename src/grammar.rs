@@ -0,0 +1,146 @@
+use crate::{
+    level::{Article, InventoryItem, RoomItem},
+    narrator::Narrator,
+};
+
+/// How to write something into a sentence: its name, its article, and the name of more
+/// than one. The articles come from the narrator, so that they can be translated.
+pub struct Noun<'a> {
+    pub name: &'a str,
+    pub article: Option<Article>,
+    pub plural: Option<&'a str>,
+}
+
+impl<'a> Noun<'a> {
+    pub fn of(item: &'a InventoryItem) -> Noun<'a> {
+        Noun {
+            name: &item.name,
+            article: item.article,
+            plural: item.plural.as_deref(),
+        }
+    }
+
+    /// An item as it's called in a room, where the room's name for it takes the
+    /// place of the item's. Without an article, the room's name is left as it's
+    /// written, as it's often a sentence.
+    pub fn in_room(room_item: &'a RoomItem, item: &'a InventoryItem) -> Noun<'a> {
+        match room_item.name {
+            Some(ref name) => Noun {
+                name,
+                article: room_item.article.or(Some(Article::None)),
+                plural: room_item.plural.as_deref(),
+            },
+            None => Noun::of(item),
+        }
+    }
+
+    /// The name of more than one.
+    pub fn plural(&self) -> String {
+        match self.plural {
+            Some(plural) => plural.to_string(),
+            None => guess_plural(self.name),
+        }
+    }
+
+    /// The name for some of it, like "apple" or "3 apples". Items without a quantity
+    /// count as one.
+    fn counted(&self, narrator: &Narrator, count: usize) -> String {
+        match count {
+            0 | 1 => self.name.to_string(),
+            _ => narrator.say_with(
+                "item-count",
+                &[("count", &count.to_string()), ("name", &self.plural())],
+            ),
+        }
+    }
+
+    /// Some of it that the player already knows about, like "the lantern" or "the 3
+    /// apples". Names without an article, like "Excalibur", are left as they are.
+    pub fn the(&self, narrator: &Narrator, count: usize) -> String {
+        let name = self.counted(narrator, count);
+        match self.article {
+            Some(Article::None) => name,
+            _ => narrator.say_with("article-the", &[("name", &name)]),
+        }
+    }
+
+    /// Some of it as it's first mentioned, like "an apple" or "3 apples".
+    pub fn a(&self, narrator: &Narrator, count: usize) -> String {
+        if count > 1 {
+            return self.counted(narrator, count);
+        }
+        let id = match self.article.unwrap_or_else(|| guess_article(self.name)) {
+            Article::A => "article-a",
+            Article::An => "article-an",
+            Article::The => "article-the",
+            Article::None => return self.name.to_string(),
+        };
+        narrator.say_with(id, &[("name", self.name)])
+    }
+}
+
+/// "An" before a vowel, and "a" otherwise. Names that sound otherwise, like "hour" or
+/// "unicorn", give their article.
+fn guess_article(name: &str) -> Article {
+    match name.chars().next().map(|ch| ch.to_ascii_lowercase()) {
+        Some('a' | 'e' | 'i' | 'o' | 'u') => Article::An,
+        _ => Article::A,
+    }
+}
+
+/// The plural of a name by the usual rules of English, like "apples", "boxes", or
+/// "berries". Anything else, like "loaves of bread", is given by the item.
+fn guess_plural(name: &str) -> String {
+    let ends_with = |suffixes: &[&str]| suffixes.iter().any(|suffix| name.ends_with(suffix));
+    if ends_with(&["s", "x", "z", "ch", "sh"]) {
+        format!("{}es", name)
+    } else if ends_with(&["y"]) && !ends_with(&["ay", "ey", "iy", "oy", "uy"]) {
+        format!("{}ies", &name[..name.len() - 1])
+    } else {
+        format!("{}s", name)
+    }
+}
+
+/// Start a sentence with a capital letter, for names that can come first.
+pub fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_nouns() {
+        let narrator = Narrator::default();
+        let noun = |name, article, plural| Noun {
+            name,
+            article,
+            plural,
+        };
+        let apple = noun("apple", None, None);
+        assert_eq!(apple.a(&narrator, 1), "an apple");
+        assert_eq!(apple.a(&narrator, 3), "3 apples");
+        assert_eq!(apple.the(&narrator, 1), "the apple");
+        assert_eq!(apple.the(&narrator, 3), "the 3 apples");
+        let sword = noun("sword", None, None);
+        assert_eq!(sword.a(&narrator, 0), "a sword");
+        let hour = noun("hourglass", Some(Article::An), None);
+        assert_eq!(hour.a(&narrator, 1), "an hourglass");
+        let crown = noun("crown of kings", Some(Article::The), None);
+        assert_eq!(crown.a(&narrator, 1), "the crown of kings");
+        let gold = noun("gold", Some(Article::None), Some("gold"));
+        assert_eq!(gold.a(&narrator, 17), "17 gold");
+        assert_eq!(gold.the(&narrator, 17), "17 gold");
+        let bread = noun("loaf of bread", None, Some("loaves of bread"));
+        assert_eq!(bread.a(&narrator, 2), "2 loaves of bread");
+        assert_eq!(guess_plural("box"), "boxes");
+        assert_eq!(guess_plural("berry"), "berries");
+        assert_eq!(guess_plural("key"), "keys");
+        assert_eq!(capitalize("the apple"), "The apple");
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{
+    level::{Coord, Direction, Level},
+    map::RoomGraph,
+    utils::parse_yml,
+    LEVEL_PATH,
+};
+use std::{fmt::Write, path::PathBuf};
+
+pub enum GraphFormat {
+    /// Graphviz, render it with `dot -Tsvg`.
+    Dot,
+    /// Mermaid, which renders in GitHub markdown.
+    Mermaid,
+}
+
+/// Load a level and graph it. The default level is used when no path is given.
+pub fn graph_file(level_path: Option<&str>, format: GraphFormat) -> Result<String, String> {
+    let level: Level = parse_yml(&PathBuf::from(level_path.unwrap_or(LEVEL_PATH)));
+    graph(&level, format)
+}
+
+/// Draw the rooms of a level and the connections between them, for visualizing the
+/// level outside of the game. An error is returned if the level's map has problems.
+pub fn graph(level: &Level, format: GraphFormat) -> Result<String, String> {
+    let room_graph = RoomGraph::parse(level)?;
+    let mut coords: Vec<&Coord> = room_graph.coords().collect();
+    coords.sort_by_key(|coord| (coord.z, coord.y, coord.x));
+
+    let title = |coord: &Coord| match level.get_room(coord) {
+        Some(room) => room.title.clone(),
+        None => coord.to_string(),
+    };
+    // Every connection goes both ways, so only follow them east and south.
+    let connections = coords.iter().flat_map(|coord| {
+        room_graph
+            .neighbors(coord)
+            .filter(|(direction, _)| matches!(direction, Direction::East | Direction::South))
+            .map(move |(_, next)| (**coord, next))
+    });
+
+    let mut out = String::new();
+    match format {
+        GraphFormat::Dot => {
+            writeln!(out, "graph level {{").unwrap();
+            writeln!(out, "  node [shape=box];").unwrap();
+            for coord in coords.iter() {
+                let label = title(coord).replace('"', "\\\"");
+                writeln!(out, "  {} [label=\"{}\"];", node_id(coord), label).unwrap();
+            }
+            for (from, to) in connections {
+                writeln!(out, "  {} -- {};", node_id(&from), node_id(&to)).unwrap();
+            }
+            writeln!(out, "}}").unwrap();
+        }
+        GraphFormat::Mermaid => {
+            writeln!(out, "graph TD").unwrap();
+            for coord in coords.iter() {
+                let label = title(coord).replace('"', "#quot;");
+                writeln!(out, "  {}[\"{}\"]", node_id(coord), label).unwrap();
+            }
+            for (from, to) in connections {
+                writeln!(out, "  {} --- {}", node_id(&from), node_id(&to)).unwrap();
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn node_id(coord: &Coord) -> String {
+    format!("room_{}_{}_{}", coord.x, coord.y, coord.z)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_graph() {
+        let level: Level = serde_yaml::from_str(
+            r#"
+maps:
+  - - "-----"
+    - "-..--"
+    - "--.--"
+    - "-----"
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+rooms:
+  - { title: Gate, coord: [1, 1, 0], description: A gate. }
+  - { title: Road, coord: [2, 1, 0], description: A road. }
+  - { title: The "Square", coord: [2, 2, 0], description: A square. }
+"#,
+        )
+        .unwrap();
+        insta::assert_snapshot!(graph(&level, GraphFormat::Dot).unwrap(), @r###"
+        graph level {
+          node [shape=box];
+          room_1_1_0 [label="Gate"];
+          room_2_1_0 [label="Road"];
+          room_2_2_0 [label="The \"Square\""];
+          room_1_1_0 -- room_2_1_0;
+          room_2_1_0 -- room_2_2_0;
+        }
+        "###);
+        insta::assert_snapshot!(graph(&level, GraphFormat::Mermaid).unwrap(), @r###"
+        graph TD
+          room_1_1_0["Gate"]
+          room_2_1_0["Road"]
+          room_2_2_0["The #quot;Square#quot;"]
+          room_1_1_0 --- room_2_1_0
+          room_2_1_0 --- room_2_2_0
+        "###);
+    }
+}
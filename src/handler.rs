@@ -0,0 +1,117 @@
+use crate::{Environment, Game};
+
+/// A command that programs embedding the engine add to it, without changing the
+/// parser. Handlers are registered with `Game::register_command`, and run for verbs
+/// the engine doesn't know. The level's own custom actions with the same verb come
+/// first, so that a level can still describe its rooms its own way.
+///
+/// ```
+/// use text_adventure::{CommandHandler, Environment, Game};
+///
+/// struct Jig;
+///
+/// impl<T: Environment> CommandHandler<T> for Jig {
+///     fn verbs(&self) -> &[&str] {
+///         &["jig", "caper"]
+///     }
+///
+///     fn help(&self) -> &str {
+///         "Dance a little jig"
+///     }
+///
+///     fn run(&self, game: &mut Game<T>, target: Option<&str>) -> Result<(), String> {
+///         match target {
+///             Some(target) => Err(format!("You can't jig with the {}.", target)),
+///             None => {
+///                 game.print("You dance a little jig.");
+///                 Ok(())
+///             }
+///         }
+///     }
+/// }
+/// ```
+pub trait CommandHandler<T: Environment> {
+    /// The words that start the command, the first of which is shown in the help.
+    fn verbs(&self) -> &[&str];
+
+    /// What the command does, for the help.
+    fn help(&self) -> &str;
+
+    /// Run the command with the rest of what the player typed, if anything, already
+    /// understood by the level's vocabulary. The turn passes when it's Ok, and an Err
+    /// is shown to the player as a failure.
+    fn run(&self, game: &mut Game<T>, target: Option<&str>) -> Result<(), String>;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn test_command_handlers() {
+        struct Jig;
+
+        impl<T: Environment> CommandHandler<T> for Jig {
+            fn verbs(&self) -> &[&str] {
+                &["jig", "caper"]
+            }
+
+            fn help(&self) -> &str {
+                "Dance a little jig"
+            }
+
+            fn run(&self, game: &mut Game<T>, target: Option<&str>) -> Result<(), String> {
+                if let Some(target) = target {
+                    return Err(format!("You can't jig with the {}.", target));
+                }
+                match game.has_flag("jigged") {
+                    true => game.print("You dance another jig."),
+                    false => game.print("You dance a little jig."),
+                }
+                game.set_flag("jigged");
+                Ok(())
+            }
+        }
+
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+scripts: [{ on: { flag_set: jigged }, effects: [{ say: The crowd claps. }] }]
+rooms:
+  - title: Square
+    coord: [1, 1, 0]
+    description: A square.
+    actions:
+      - { verb: Custom, alias: jig, targets: [fountain], value: You jig around the fountain. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        game.register_command(Jig);
+        game.output().output.clear();
+        assert!(game.step("help".into()).is_none());
+        assert!(take_output(&game).contains(&"  jig             Dance a little jig".to_string()));
+        let mut lines = Vec::new();
+        for command in ["jig", "caper", "jig fountain", "jig cat", "jgi"] {
+            assert!(game.step(command.into()).is_none());
+            lines.extend(
+                take_output(&game)
+                    .into_iter()
+                    .filter(|line| !line.is_empty()),
+            );
+        }
+        insta::assert_yaml_snapshot!(lines, @r###"
+        ---
+        - You dance a little jig.
+        - The crowd claps.
+        - You dance another jig.
+        - You jig around the fountain.
+        - "You can't jig with the cat."
+        - "You don't know how to \"jgi\". Type \"help\" for help."
+        - "Did you mean 'jig'?"
+        "###);
+        assert_eq!(game.save_state.turn, 3);
+    }
+}
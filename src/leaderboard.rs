@@ -0,0 +1,210 @@
+use crate::utils::{format_elapsed, hash_str, try_parse_yml_str};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Write as _, fs, path::Path, time::Duration};
+
+pub const RESULTS_PATH: &str = "data/results.yml";
+
+/// Mixed into the signatures, so that a result can't be signed by hashing its fields
+/// alone. This only keeps honest players honest, as anyone with the source can sign a
+/// result.
+const SIGNING_KEY: &str = "text-adventure results";
+
+/// A finished game, kept in a results file so that players can compare how they did,
+/// like in a tournament on a shared level. The signature catches results that were
+/// changed by hand.
+///
+/// ```yml
+/// - player: Ada
+///   level: stone-end-market
+///   seed: 42
+///   seconds: 182
+///   turns: 45
+///   score: 120
+///   signature: eab8e841a4c72658
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GameResult {
+    pub player: String,
+    /// The id of the level in the campaign.
+    pub level: String,
+    pub seed: u64,
+    /// How long the player took to finish, in seconds.
+    pub seconds: u64,
+    pub turns: usize,
+    pub score: usize,
+    pub signature: String,
+}
+
+impl GameResult {
+    pub fn new(
+        player: &str,
+        level: &str,
+        seed: u64,
+        seconds: u64,
+        turns: usize,
+        score: usize,
+    ) -> GameResult {
+        let mut result = GameResult {
+            player: player.to_string(),
+            level: level.to_string(),
+            seed,
+            seconds,
+            turns,
+            score,
+            signature: String::new(),
+        };
+        result.signature = result.sign();
+        result
+    }
+
+    fn sign(&self) -> String {
+        let fields = format!(
+            "{}|{}|{}|{}|{}|{}|{}",
+            SIGNING_KEY, self.player, self.level, self.seed, self.seconds, self.turns, self.score
+        );
+        format!("{:016x}", hash_str(&fields))
+    }
+
+    /// Whether the result is the same as when it was signed.
+    pub fn is_signed(&self) -> bool {
+        self.signature == self.sign()
+    }
+}
+
+/// Read the results in a file.
+pub fn load(path: &Path) -> Result<Vec<GameResult>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| format!("Unable to read {}: {}", path.display(), err))?;
+    try_parse_yml_str(&text, &path.display().to_string())
+}
+
+/// Add a result to the end of a results file, starting the file if there isn't one.
+pub fn append(path: &Path, result: GameResult) -> Result<(), String> {
+    let mut results = if path.exists() {
+        load(path)?
+    } else {
+        Vec::new()
+    };
+    results.push(result);
+    let yml = serde_yaml::to_string(&results)
+        .map_err(|err| format!("Unable to serialize the results: {}", err))?;
+    fs::write(path, yml).map_err(|err| format!("Unable to save {}: {}", path.display(), err))
+}
+
+/// Merge the results from several files, by path, into a ranking. The best games come
+/// first for each level: the highest score, then the fewest turns, then the fastest.
+/// Results that aren't signed are left out and reported, and the same result from more
+/// than one file is only kept once.
+pub fn merge(files: &[(String, Vec<GameResult>)]) -> (Vec<GameResult>, Vec<String>) {
+    let mut merged: Vec<GameResult> = Vec::new();
+    let mut problems = Vec::new();
+    for (path, results) in files.iter() {
+        for result in results.iter() {
+            if !result.is_signed() {
+                problems.push(format!(
+                    "The result of {:?} on {:?} in {} isn't signed, so it's left out.",
+                    result.player, result.level, path
+                ));
+            } else if !merged.contains(result) {
+                merged.push(result.clone());
+            }
+        }
+    }
+    merged.sort_by(|a, b| {
+        a.level
+            .cmp(&b.level)
+            .then(b.score.cmp(&a.score))
+            .then(a.turns.cmp(&b.turns))
+            .then(a.seconds.cmp(&b.seconds))
+    });
+    (merged, problems)
+}
+
+/// Show a ranking from `merge`, with a table for each level.
+pub fn format_ranking(results: &[GameResult]) -> String {
+    let width = results
+        .iter()
+        .map(|result| result.player.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut text = String::new();
+    let mut level = None;
+    let mut place = 0;
+    for result in results.iter() {
+        if level != Some(&result.level) {
+            if level.is_some() {
+                text.push('\n');
+            }
+            writeln!(text, "{}", result.level).unwrap();
+            level = Some(&result.level);
+            place = 0;
+        }
+        place += 1;
+        writeln!(
+            text,
+            "  {:>2}. {:<width$}  {:>5} points  {:>4} turns  {:>7}  seed {}",
+            place,
+            result.player,
+            result.score,
+            result.turns,
+            format_elapsed(Duration::from_secs(result.seconds)),
+            result.seed,
+            width = width
+        )
+        .unwrap();
+    }
+    text
+}
+
+/// Merge the results files at the paths and rank them, see `merge`. The merged results
+/// are saved to `out`, when it's given, so they can be shared as one file.
+pub fn merge_files(paths: &[String], out: Option<&str>) -> Result<(String, Vec<String>), String> {
+    let files = paths
+        .iter()
+        .map(|path| Ok((path.clone(), load(Path::new(path))?)))
+        .collect::<Result<Vec<_>, String>>()?;
+    let (merged, problems) = merge(&files);
+    if let Some(out) = out {
+        let yml = serde_yaml::to_string(&merged)
+            .map_err(|err| format!("Unable to serialize the results: {}", err))?;
+        fs::write(out, yml).map_err(|err| format!("Unable to save {}: {}", out, err))?;
+    }
+    Ok((format_ranking(&merged), problems))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let ada = GameResult::new("Ada", "market", 42, 182, 45, 120);
+        let grace = GameResult::new("Grace", "market", 7, 95, 40, 120);
+        let alan = GameResult::new("Alan", "market", 42, 60, 30, 80);
+        let edsger = GameResult::new("Edsger", "sewer", 1, 3725, 210, 40);
+        let mut cheat = GameResult::new("Mallory", "market", 3, 10, 5, 10);
+        cheat.score = 9999;
+        assert!(ada.is_signed());
+        assert!(!cheat.is_signed());
+
+        let files = vec![
+            ("ada.yml".to_string(), vec![ada.clone(), edsger]),
+            ("club.yml".to_string(), vec![alan, ada, cheat, grace]),
+        ];
+        let (merged, problems) = merge(&files);
+        assert_eq!(
+            problems,
+            vec!["The result of \"Mallory\" on \"market\" in club.yml isn't signed, so it's left out."]
+        );
+        insta::assert_snapshot!(format_ranking(&merged), @r###"
+        ---
+        market
+           1. Grace     120 points    40 turns     1:35  seed 7
+           2. Ada       120 points    45 turns     3:02  seed 42
+           3. Alan       80 points    30 turns     1:00  seed 42
+
+        sewer
+           1. Edsger     40 points   210 turns  1:02:05  seed 1
+        "###);
+    }
+}
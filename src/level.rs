@@ -1,25 +1,794 @@
 use std::{
-    cell::RefCell,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    convert::TryFrom,
     rc::Rc,
 };
 
-use crate::utils::parse_yml;
+use crate::{
+    condition::Condition,
+    narrator::Narrator,
+    passage::Passage,
+    style::Color,
+    utils::{derive_targets, hash_str, parse_yml},
+    vocabulary::Vocabulary,
+    ITEMS_PATH,
+};
+use rand::{seq::SliceRandom, SeedableRng};
+use rand_pcg::Pcg32;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Level {
     pub maps: LevelMap,
-    pub rooms: Vec<Rc<Room>>,
+    /// The characters that can be drawn on the maps besides the built in ones, like `^`
+    /// for lava, with the rules for crossing them, see `Tile`.
+    #[serde(default)]
+    pub tiles: BTreeMap<char, Tile>,
+    pub rooms: Rooms,
     pub entry: Coord,
+    /// Named places that exits from other levels can lead to. Rooms with an id can be
+    /// led to by their id as well.
+    #[serde(default)]
+    pub entries: HashMap<String, Coord>,
     pub npcs: HashMap<String, NPC>,
     pub regions: HashMap<String, Region>,
+    /// What the player can ride or board, like a horse or a boat, by id.
+    #[serde(default)]
+    pub vehicles: BTreeMap<String, Vehicle>,
+    #[serde(default)]
+    pub crowds: HashMap<String, Crowd>,
+    /// How many typos away a word can be before it's no longer suggested as a
+    /// correction.
+    #[serde(default = "default_suggestion_distance")]
+    pub suggestion_distance: usize,
+    #[serde(default)]
+    pub narrator: Narrator,
+    /// Synonyms specific to this level. These get merged with the global vocabulary
+    /// when the level is loaded.
+    #[serde(default)]
+    pub vocabulary: Vocabulary,
+    /// The content tags that passages in this level can be flagged with, and a
+    /// description of each for the player's content filter.
+    #[serde(default)]
+    pub content_tags: BTreeMap<String, String>,
+    #[serde(default)]
+    pub par: Option<Par>,
+    /// The checkpoints of a race through the level, in order, see `Split`.
+    #[serde(default)]
+    pub race: Vec<Split>,
+    /// How the player begins the game.
+    #[serde(default)]
+    pub start: Start,
+    /// Different ways to begin the level, which the player picks from in a new game.
+    #[serde(default)]
+    pub scenarios: Vec<Scenario>,
+    /// Achievements the player can unlock, by id.
+    #[serde(default)]
+    pub achievements: BTreeMap<String, Achievement>,
+    /// Points for picking up an item for the first time in a game, by item id.
+    #[serde(default)]
+    pub pickup_points: HashMap<String, usize>,
+    /// Let the player come back after dying, rather than only loading their save.
+    #[serde(default)]
+    pub respawn: Option<Respawn>,
+    /// Effects that run on their own after the player's turns, by id.
+    #[serde(default)]
+    pub daemons: HashMap<String, Daemon>,
+    /// Effects that go off once, some turns after they're armed, by id.
+    #[serde(default)]
+    pub timers: HashMap<String, Timer>,
+    /// Effects that run when something happens in the game, see `Script`.
+    #[serde(default)]
+    pub scripts: Vec<Script>,
+    /// Groups that NPCs belong to, which keep track of the player's reputation, by id.
+    #[serde(default)]
+    pub factions: BTreeMap<String, Faction>,
+    /// Rules that levels opt into, so that story-only games aren't affected.
+    #[serde(default)]
+    pub mechanics: Mechanics,
+    /// Conditions like poison or a blessing that last for some turns, by id.
+    #[serde(default)]
+    pub statuses: BTreeMap<String, Status>,
+    /// Worldbuilding that the player unlocks as they play, by id, see `Lore`.
+    #[serde(default)]
+    pub lore: BTreeMap<String, Lore>,
+    /// Tasks the player is given, which are kept in their journal, by id.
+    #[serde(default)]
+    pub quests: BTreeMap<String, Quest>,
+    /// The level's weather, and how it changes, see `Climate`.
+    #[serde(default)]
+    pub climate: Option<Climate>,
+}
+
+/// The weather of a level, which changes every few turns to one of the kinds that can
+/// follow it, picked at random. Outdoor rooms, and the regions they're in, add what
+/// the weather is like to their descriptions. The weather can also hide the exits of
+/// outdoor rooms, or keep the player from walking out into it. Conditions can check it
+/// with `weather.clear`, `weather.rain`, `weather.fog`, and `weather.storm`.
+///
+/// ```yml
+/// climate:
+///   start: clear
+///   every: 5
+///   changes:
+///     clear: [clear, rain, fog]
+///     rain: [clear, storm]
+///     fog: [clear]
+///     storm: [rain]
+///   messages:
+///     rain: It starts to rain.
+///     storm: Thunder rolls in from the sea.
+///   hides_exits: [fog]
+///   blocks_travel: [storm]
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Climate {
+    /// The weather a new game begins with.
+    #[serde(default)]
+    pub start: Weather,
+    /// How many turns pass between each change of the weather.
+    #[serde(default = "default_every")]
+    pub every: usize,
+    /// What each kind of weather can change into. Weather that isn't listed stays.
+    #[serde(default)]
+    pub changes: BTreeMap<Weather, Vec<Weather>>,
+    /// What's said to a player outdoors when the weather changes into each kind.
+    #[serde(default)]
+    pub messages: BTreeMap<Weather, Passage>,
+    /// The weather that hides the exits of outdoor rooms.
+    #[serde(default)]
+    pub hides_exits: Vec<Weather>,
+    /// The weather that the player can't walk out into, from one room to an outdoor one.
+    #[serde(default)]
+    pub blocks_travel: Vec<Weather>,
+}
+
+/// A kind of weather, see `Climate`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Weather {
+    #[default]
+    Clear,
+    Rain,
+    Fog,
+    Storm,
+}
+
+impl Weather {
+    pub fn lowercase_str(&self) -> &'static str {
+        match self {
+            Weather::Clear => "clear",
+            Weather::Rain => "rain",
+            Weather::Fog => "fog",
+            Weather::Storm => "storm",
+        }
+    }
+}
+
+/// A task for the player, like delivering a parcel, which is listed in their journal
+/// while they work on it. A `quest` effect starts it, and a `complete` effect finishes
+/// it, such as on the gift of the delivered item. Quests with `turns` have to be
+/// finished within that many turns of starting. Finishing in the `grace` turns after
+/// that still counts for something, and otherwise the quest fails.
+///
+/// ```yml
+/// quests:
+///   fish-delivery:
+///     name: Fish for the Inn
+///     description: Bring the innkeeper's fish before the dinner rush.
+///     turns: 20
+///     grace: 5
+///     reward: [{ award: { id: fish-delivery, points: 10 } }]
+///     late: [{ say: "\"Late, but it'll do.\"" }]
+///     failed: [{ reputation: { inn: -2 } }]
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Quest {
+    pub name: String,
+    pub description: Passage,
+    /// How many turns the player has to finish the quest once it starts.
+    #[serde(default)]
+    pub turns: Option<usize>,
+    /// The turns after the deadline in which finishing runs the `late` effects.
+    #[serde(default)]
+    pub grace: usize,
+    /// What happens when the quest is finished in time.
+    #[serde(default)]
+    pub reward: Vec<Effect>,
+    /// What happens when it's finished in the grace turns, like a smaller reward.
+    #[serde(default)]
+    pub late: Vec<Effect>,
+    /// What happens when the time runs out, or the escort is lost.
+    #[serde(default)]
+    pub failed: Vec<Effect>,
+    #[serde(default)]
+    pub escort: Option<Escort>,
+}
+
+/// An NPC the player has to keep safe and with them while on a quest, like a lost child
+/// to walk home. The NPC follows the player, finding their way back after falling
+/// behind, and the quest fails when they are away for more than `behind` turns in a
+/// row, or when a `harm` effect hurts them. Bringing them to the `to` room finishes
+/// the quest.
+///
+/// ```yml
+/// escort: { npc: child, to: [4, 2, 0], behind: 3 }
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Escort {
+    /// The id of the NPC.
+    pub npc: String,
+    #[serde(default)]
+    pub to: Option<Coord>,
+    #[serde(default = "default_escort_behind")]
+    pub behind: usize,
+}
+
+fn default_escort_behind() -> usize {
+    2
+}
+
+/// An entry in the level's encyclopedia, for history and other details that don't fit
+/// in a room's description. It's unlocked by a `lore` effect, like on a topic or an
+/// action, or by reading an item with the same `lore`, and then the player can look it
+/// up with `lore <topic>`.
+///
+/// ```yml
+/// lore:
+///   drowned-king:
+///     title: The Drowned King
+///     targets: [king, drowned king]
+///     text: The last king of Stone End was lost with his fleet in the great storm.
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Lore {
+    pub title: String,
+    /// The words to look the entry up by, along with its id and title.
+    #[serde(default)]
+    pub targets: Vec<String>,
+    pub text: Passage,
+}
+
+/// A condition that lasts for some turns once an effect gives it to the player, like
+/// poison from a trap or drunkenness from a mug of ale. Giving it again while it lasts
+/// starts the turns over, and adds a stack when it can stack.
+///
+/// ```yml
+/// statuses:
+///   drunk:
+///     turns: 8
+///     message: The room starts to spin.
+///     expires: Your head clears.
+///     stumble: 0.5
+///     scramble: true
+///   poison:
+///     turns: 5
+///     max_stacks: 3
+///     tick: { health: -1 }
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    pub turns: usize,
+    #[serde(default = "default_max_stacks")]
+    pub max_stacks: usize,
+    /// Shown when the player gets the status.
+    #[serde(default)]
+    pub message: Option<Passage>,
+    /// Shown when the status wears off.
+    #[serde(default)]
+    pub expires: Option<Passage>,
+    /// Added to the player's stats after each turn, for each stack.
+    #[serde(default)]
+    pub tick: BTreeMap<String, i64>,
+    /// Added to the player's stats while the status lasts, and taken back after.
+    #[serde(default)]
+    pub stats: BTreeMap<String, i64>,
+    /// The chance from 0 to 1 that moving goes some other way.
+    #[serde(default)]
+    pub stumble: f64,
+    /// Jumble the letters of the words the player reads.
+    #[serde(default)]
+    pub scramble: bool,
+}
+
+fn default_max_stacks() -> usize {
+    1
+}
+
+/// Rules that a level can turn on.
+///
+/// ```yml
+/// mechanics:
+///   survival:
+///     food:
+///       every: 5
+///       warnings:
+///         - { below: 30, message: Your stomach growls. }
+///         - { below: 10, message: You feel weak., penalty: { strength: -2 } }
+///       death: You starve.
+/// ```
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Mechanics {
+    /// Needs like food, water, or rest, which run down as the turns pass. Each is kept in
+    /// the player's stat with the same id, and items that `restore` it fill it back up.
+    #[serde(default)]
+    pub survival: BTreeMap<String, Need>,
+}
+
+impl Mechanics {
+    /// The stats to start a game with, with every need full.
+    pub fn starting_stats(&self, stats: &BTreeMap<String, i64>) -> BTreeMap<String, i64> {
+        let mut stats = stats.clone();
+        for (id, need) in self.survival.iter() {
+            stats.entry(id.clone()).or_insert(need.max);
+        }
+        stats
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Need {
+    /// How full the need can get, which is where it starts.
+    #[serde(default = "default_need_max")]
+    pub max: i64,
+    /// How many turns pass between each point lost.
+    #[serde(default = "default_every")]
+    pub every: usize,
+    /// What happens as the need runs low, see `Warning`.
+    #[serde(default)]
+    pub warnings: Vec<Warning>,
+    /// The epitaph when the need runs out.
+    pub death: Passage,
+}
+
+fn default_need_max() -> i64 {
+    100
+}
+
+/// A message shown when a need falls below a threshold, with a penalty to the player's
+/// stats that lasts until the need is back above it.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Warning {
+    pub below: i64,
+    pub message: Passage,
+    #[serde(default)]
+    pub penalty: BTreeMap<String, i64>,
+}
+
+/// A group of NPCs, like a guild or a gang, that remembers how the player has treated
+/// it. Effects change the player's reputation with it, and conditions can check it with
+/// `reputation.<id>`. Reputation starts at 0.
+///
+/// ```yml
+/// factions:
+///   guild:
+///     name: the Merchants' Guild
+///     hostile_when: reputation.guild < -5
+///     hostile: The merchant pretends not to see you.
+///     prices: { 10: 90, 25: 75 }
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Faction {
+    pub name: String,
+    /// The members turn the player away while this holds, like while the player's
+    /// reputation is low.
+    #[serde(default)]
+    pub hostile_when: Option<Condition>,
+    /// What the members say while they are hostile, in place of their usual talk.
+    #[serde(default)]
+    pub hostile: Option<Passage>,
+    /// The percent of the usual price the members charge, by the reputation it takes.
+    /// The highest reputation the player has reached is used.
+    #[serde(default)]
+    pub prices: BTreeMap<i64, usize>,
+}
+
+/// Effects that run on their own after the player's turns, the way hunger grows or a
+/// fuse burns down. Actions and other daemons can enable and disable them. Their state
+/// is kept in the save with the level's, so each level of a campaign has its own, even
+/// when they use the same ids.
+///
+/// ```yml
+/// daemons:
+///   hunger:
+///     every: 10
+///     effects:
+///       - say: Your stomach growls.
+///       - stats: { health: -1 }
+///   fuse:
+///     enabled: false
+///     every: 3
+///     times: 1
+///     effects: [{ say: The powder keg explodes! }, { death: You were too close. }]
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Daemon {
+    /// Whether the daemon runs from the start of the game, rather than waiting for an
+    /// `enable` effect.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// How many turns pass between each run, counted from when it was enabled.
+    #[serde(default = "default_every")]
+    pub every: usize,
+    /// How many times it runs before disabling itself, or None to run until disabled.
+    #[serde(default)]
+    pub times: Option<usize>,
+    pub effects: Vec<Effect>,
+}
+
+/// Effects that go off once, a number of turns after they're armed, like a bomb's fuse,
+/// a guard coming back, or the tide coming in. Actions and daemons arm them with `arm`,
+/// and the player can stop them in time with an action that has `defuse`. The turns left
+/// are kept in the save, and conditions can check them with `timers.<id>`.
+///
+/// ```yml
+/// timers:
+///   powder-keg:
+///     turns: 3
+///     warnings:
+///       2: The fuse hisses and spits.
+///       1: The fuse is almost gone!
+///     effects: [{ say: The powder keg explodes! }, { death: You were too close. }]
+///     defused: [{ say: You pinch out the fuse. }]
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Timer {
+    /// How many turns it counts down from, including the turn it's armed in.
+    pub turns: usize,
+    /// What's said at the end of a turn, by how many turns are left.
+    #[serde(default)]
+    pub warnings: BTreeMap<usize, Passage>,
+    pub effects: Vec<Effect>,
+    /// What happens when it's defused before it goes off.
+    #[serde(default)]
+    pub defused: Vec<Effect>,
+}
+
+/// Effects that run when something happens in the game, like the player taking an item
+/// or walking into a room, as long as their condition holds. Scripts run in the order
+/// they're listed, after the engine's own rules for the same event. To run a script
+/// only once, have it set a flag that its condition checks.
+///
+/// ```yml
+/// scripts:
+///   - on: { item_taken: idol }
+///     when: not flags.temple-shaking
+///     effects: [{ say: The temple begins to shake. }, { set_flag: temple-shaking }]
+///   - on: { room_entered: vault }
+///     effects: [{ arm: alarm }]
+///   - on: { npc_talked_to: fisher }
+///     effects: [{ quest: fish-delivery }]
+///   - on: { flag_set: temple-shaking }
+///     effects: [{ arm: collapse }]
+///   - on: turn_ended
+///     when: flags.temple-shaking
+///     effects: [{ say: Dust falls from the ceiling. }]
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Script {
+    pub on: Trigger,
+    #[serde(default)]
+    pub when: Option<Condition>,
+    pub effects: Vec<Effect>,
+}
+
+/// What a script runs on, see `GameEvent`.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    /// The player takes an item, by id.
+    ItemTaken(String),
+    /// The player walks or is moved into a room, by its id or the name of its entry.
+    RoomEntered(String),
+    /// The player talks to an NPC, or asks them about something, by id.
+    NpcTalkedTo(String),
+    /// A flag is set that wasn't already.
+    FlagSet(String),
+    /// The player finishes a turn.
+    TurnEnded,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_every() -> usize {
+    1
+}
+
+/// Something that happens to the game, from an action or a daemon.
+///
+/// ```yml
+/// effects:
+///   - say: The fuse hisses.
+///   - stats: { health: -2 }
+///   - set_flag: lit-the-fuse
+///   - enable: fuse
+///   - arm: powder-keg
+///   - reputation: { guild: -3 }
+///   - status: poison
+///   - reveal: { gold: 20 }
+///   - lore: drowned-king
+///   - quest: fish-delivery
+///   - complete: fish-delivery
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Effect {
+    /// Show some text.
+    Say(Passage),
+    /// Add to the player's stats, or take away with a negative amount.
+    Stats(BTreeMap<String, i64>),
+    SetFlag(String),
+    ClearFlag(String),
+    /// Start a daemon, counting its turns from now.
+    Enable(String),
+    Disable(String),
+    Teleport(Destination),
+    Award(Award),
+    Death(Passage),
+    /// Add to the player's reputation with factions, or take away with a negative
+    /// amount.
+    Reputation(BTreeMap<String, i64>),
+    /// Give the player a status, see `Status`.
+    Status(String),
+    /// Take a status away before it wears off.
+    Cure(String),
+    /// Take a step of the sequence puzzles in the player's room, see `Puzzle`.
+    Step(String),
+    /// Put items in the player's room, by id and quantity.
+    Reveal(BTreeMap<String, usize>),
+    /// Unlock an entry of the level's lore, see `Lore`.
+    Lore(String),
+    /// Start a quest, see `Quest`.
+    Quest(String),
+    /// Finish a quest that the player is on.
+    Complete(String),
+    /// Hurt an NPC, by id, which fails the quests escorting them.
+    Harm(String),
+    /// Play a sound once, by the id of its file in data/audio, see `Cue`.
+    Sound(String),
+    /// Start a timer counting down, or start it over if it's already armed, see `Timer`.
+    Arm(String),
+    /// Stop a timer before it goes off, which runs its `defused` effects.
+    Defuse(String),
+    /// Take an NPC into the player's party, by id, see `Companion`.
+    Join(String),
+    /// Have an NPC leave the player's party, staying where they are.
+    Leave(String),
+}
+
+/// How the player comes back after dying. They wake up in the last checkpoint room
+/// they reached, or the level's entry, and lose some of what they had.
+///
+/// ```yml
+/// respawn:
+///   money: 10
+///   points: 5
+///   message: You wake up in the temple, your purse a little lighter.
+/// ```
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "RespawnFile")]
+pub struct Respawn {
+    /// How much money is lost, in the smallest denomination.
+    pub money: usize,
+    /// How many points are taken from the score.
+    pub points: usize,
+    pub message: Option<Passage>,
+}
+
+/// A respawn as it's written in a level. Levels from before coins gave the penalty in
+/// `gold`, which is worth 100 of the smallest denomination.
+#[derive(Deserialize)]
+struct RespawnFile {
+    #[serde(default)]
+    money: usize,
+    #[serde(default)]
+    gold: usize,
+    #[serde(default)]
+    points: usize,
+    #[serde(default)]
+    message: Option<Passage>,
+}
+
+impl From<RespawnFile> for Respawn {
+    fn from(file: RespawnFile) -> Respawn {
+        Respawn {
+            money: file.money + file.gold * GOLD,
+            points: file.points,
+            message: file.message,
+        }
+    }
+}
+
+/// What the stock gold coin is worth, for the levels written before prices were given
+/// in the smallest denomination.
+const GOLD: usize = 100;
+
+/// A way to begin a level, such as a prologue or a harder start. Anything that isn't
+/// given comes from the level.
+///
+/// ```yml
+/// scenarios:
+///   - id: stowaway
+///     name: The Stowaway
+///     description: Sneak off the ship with nothing to your name.
+///     entry: [12, 18, 0]
+///     start: { items: [] }
+///     intro: You slip out of a barrel and onto the docks.
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Scenario {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub entry: Option<Coord>,
+    #[serde(default)]
+    pub start: Option<Start>,
+    /// Shown after the game's introduction.
+    #[serde(default)]
+    pub intro: Option<Passage>,
+}
+
+/// What the player starts with in a new game.
+///
+/// ```yml
+/// start:
+///   items:
+///     - id: sword
+///     - id: gold
+///       quantity: 17
+///   stats:
+///     health: 10
+///   flags: [knows-the-captain]
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Start {
+    #[serde(default)]
+    pub items: Vec<StartItem>,
+    /// Numbers that track the player, like their health.
+    #[serde(default)]
+    pub stats: BTreeMap<String, i64>,
+    /// Facts about the player that events in the level can check.
+    #[serde(default)]
+    pub flags: BTreeSet<String>,
+}
+
+impl Default for Start {
+    /// Levels that don't say otherwise start with a sword and some gold.
+    fn default() -> Start {
+        Start {
+            items: vec![
+                StartItem {
+                    id: "sword".into(),
+                    quantity: None,
+                },
+                StartItem {
+                    id: "gold".into(),
+                    quantity: None,
+                },
+            ],
+            stats: BTreeMap::new(),
+            flags: BTreeSet::new(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct StartItem {
+    pub id: String,
+    /// How many of the item to start with, instead of the item's own quantity.
+    #[serde(default)]
+    pub quantity: Option<usize>,
+}
+
+/// A challenge to reach the goal room within a number of turns. Players who make it
+/// on or under par are rewarded with the bonus text.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Par {
+    pub turns: usize,
+    pub goal: Coord,
+    #[serde(default)]
+    pub bonus: Option<Passage>,
+}
+
+/// A checkpoint for racing through the level with `--race`. It's reached when the
+/// player is `at` the entry or room, and its condition holds. Each one is timed from
+/// the start of the race, and compared to the best run that made it to the end.
+///
+/// ```yml
+/// race:
+///   - { name: Docks, at: docks }
+///   - { name: Key, when: items.key > 0 }
+///   - { name: Escape, at: east-gate }
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Split {
+    pub name: String,
+    #[serde(default)]
+    pub at: Option<String>,
+    #[serde(default)]
+    pub when: Option<Condition>,
+}
+
+/// Something the player can unlock by playing. Achievements stay unlocked across
+/// games, even after a restart.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Achievement {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Points, and optionally an achievement, given the first time something happens in
+/// a game. The id tells awards apart, so each one is only given once.
+///
+/// ```yml
+/// award: { id: met-the-raven, points: 10, achievement: raven-friend }
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Award {
+    pub id: String,
+    #[serde(default)]
+    pub points: usize,
+    #[serde(default)]
+    pub achievement: Option<String>,
+}
+
+fn default_suggestion_distance() -> usize {
+    2
 }
 
 impl Level {
     pub fn get_room(&self, coord: &Coord) -> Option<&Rc<Room>> {
-        self.rooms.iter().find(|room| room.coord == *coord)
+        self.rooms.get(coord)
+    }
+
+    /// Whether anything in the level gives points or achievements. The score is only
+    /// shown for levels that keep one.
+    pub fn has_scoring(&self) -> bool {
+        !self.achievements.is_empty()
+            || !self.pickup_points.is_empty()
+            || self
+                .rooms
+                .iter()
+                .flat_map(|room| room.actions.iter().flatten())
+                .chain(
+                    self.regions
+                        .values()
+                        .flat_map(|region| region.actions.iter()),
+                )
+                .any(|action| action.award.is_some())
+    }
+
+    /// Find where a named entry or room id is.
+    pub fn find_entry(&self, name: &str) -> Option<Coord> {
+        self.entries
+            .get(name)
+            .copied()
+            .or_else(|| self.rooms.get_by_id(name).map(|room| room.coord))
+    }
+
+    /// Pick the name and description for NPCs that have pools of them. Each NPC gets
+    /// its own generator from the seed and its id, so the same seed always resolves to
+    /// the same NPCs, even as other NPCs are added to the level.
+    pub fn resolve_npcs(&mut self, seed: u64) {
+        for (id, npc) in self.npcs.iter_mut() {
+            let mut rng = Pcg32::seed_from_u64(seed ^ hash_str(id));
+            if let Some(name) = npc.names.choose(&mut rng) {
+                npc.name = name.clone();
+                let target = name.to_lowercase();
+                if !npc.targets.contains(&target) {
+                    npc.targets.push(target);
+                }
+            }
+            if let Some(description) = npc.descriptions.choose(&mut rng) {
+                npc.description = description.clone();
+            }
+        }
     }
 }
 
@@ -41,53 +810,456 @@ impl Level {
 // ]
 pub type LevelMap = Vec<Vec<String>>;
 
+/// The rooms of a level, indexed by their coordinates so that finding the current room
+/// doesn't search through the whole level, and by their ids. When rooms share a
+/// coordinate or an id, the first one is used.
+#[derive(Debug, Default, PartialEq)]
+pub struct Rooms {
+    list: Vec<Rc<Room>>,
+    by_coord: HashMap<Coord, Rc<Room>>,
+    by_id: HashMap<String, Rc<Room>>,
+}
+
+impl Rooms {
+    pub fn iter(&self) -> std::slice::Iter<'_, Rc<Room>> {
+        self.list.iter()
+    }
+
+    pub fn get(&self, coord: &Coord) -> Option<&Rc<Room>> {
+        self.by_coord.get(coord)
+    }
+
+    pub fn get_by_id(&self, id: &str) -> Option<&Rc<Room>> {
+        self.by_id.get(id)
+    }
+
+    /// Take the rooms out of their index, so that they can be changed. This panics if
+    /// a room is still shared, like the game's current room.
+    pub fn into_vec(self) -> Vec<Room> {
+        let Rooms {
+            list,
+            by_coord,
+            by_id,
+        } = self;
+        drop((by_coord, by_id));
+        list.into_iter()
+            .map(|room| Rc::try_unwrap(room).expect("Unable to take a room that is in use."))
+            .collect()
+    }
+}
+
+impl From<Vec<Rc<Room>>> for Rooms {
+    fn from(list: Vec<Rc<Room>>) -> Rooms {
+        let mut by_coord = HashMap::new();
+        let mut by_id = HashMap::new();
+        for room in list.iter() {
+            by_coord.entry(room.coord).or_insert_with(|| room.clone());
+            if let Some(ref id) = room.id {
+                by_id.entry(id.clone()).or_insert_with(|| room.clone());
+            }
+        }
+        Rooms {
+            list,
+            by_coord,
+            by_id,
+        }
+    }
+}
+
+impl Serialize for Rooms {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.list.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rooms {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Rooms, D::Error> {
+        Vec::deserialize(deserializer).map(Rooms::from)
+    }
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Room {
     pub title: String,
     pub coord: Coord,
-    pub description: String,
-    pub actions: Option<Vec<Action>>,
+    /// A name for the room, so that exits and actions can lead to it without using its
+    /// coordinate, which changes as the map is edited.
     #[serde(default)]
-    pub cached_formatted_description: RefCell<String>,
+    pub id: Option<String>,
+    pub description: Passage,
+    pub actions: Option<Vec<Action>>,
     #[serde(default)]
     pub items: Vec<RoomItem>,
     #[serde(default)]
     pub npcs: Vec<String>,
+    /// The ids of the vehicles that start in the room, see `Vehicle`.
+    #[serde(default)]
+    pub vehicles: Vec<String>,
     #[serde(default)]
     pub regions: Vec<String>,
+    /// Override how dense a crowd is in this room, or place a crowd outside of its
+    /// regions.
+    #[serde(default)]
+    pub crowds: HashMap<String, CrowdDensity>,
+    /// Exits that lead somewhere other than the next room on the map, like another
+    /// level of the campaign.
+    #[serde(default)]
+    pub exits: HashMap<Direction, Destination>,
+    /// Entering the room kills the player, and this is their epitaph.
+    #[serde(default)]
+    pub death: Option<Passage>,
+    /// Players who die respawn in the last checkpoint room they entered.
+    #[serde(default)]
+    pub checkpoint: bool,
+    #[serde(default)]
+    pub ambient: Option<Ambient>,
+    /// Traps and other dangers that spring when the player enters, see `Hazard`.
+    #[serde(default)]
+    pub hazards: Vec<Hazard>,
+    #[serde(default)]
+    pub puzzles: Vec<Puzzle>,
+    #[serde(default)]
+    pub lighting: Lighting,
+    /// The room is out in the weather, see `Climate`.
+    #[serde(default)]
+    pub outdoors: bool,
+    /// What's added to the end of the description in each kind of weather, when the
+    /// room is outdoors.
+    #[serde(default)]
+    pub weather: BTreeMap<Weather, Passage>,
+    /// The id of the music that loops while the player is in the room, see `Cue`. Rooms
+    /// without any play their regions' music.
+    #[serde(default)]
+    pub music: Option<String>,
+    /// Small things added to the end of the description, which can't be made out while
+    /// the room is dim.
+    #[serde(default)]
+    pub details: Option<Passage>,
+    /// The player can deposit and withdraw items from their stash here, like at a vault.
+    #[serde(default)]
+    pub bank: bool,
+    #[serde(default)]
+    pub scenery: Vec<Scenery>,
+    /// What happens when the player throws items at things in the room.
+    #[serde(default)]
+    pub throws: Vec<Throw>,
+    /// Items that grow back after they're taken, see `Spawn`.
+    #[serde(default)]
+    pub spawns: Vec<Spawn>,
+}
+
+/// Items that come back to a room after they're taken, like berries on a bush or fish
+/// in a pond. While there are fewer than `max` of the item in the room, `quantity` more
+/// appear every so many turns. The turns counted toward the next ones are kept in the
+/// save, so loading a game doesn't bring them back early.
+///
+/// ```yml
+/// spawns:
+///   - { id: berries, quantity: 2, every: 10, max: 6 }
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Spawn {
+    pub id: String,
+    #[serde(default = "default_spawn_quantity")]
+    pub quantity: usize,
+    pub every: usize,
+    pub max: usize,
+}
+
+fn default_spawn_quantity() -> usize {
+    1
+}
+
+/// Something too big to take that the player can push, pull, or move, like a boulder or
+/// a bookcase. Moving it shows the `value`, finds the room's hidden `exits`, and runs the
+/// effects, like revealing what was underneath. From then on, the room's description
+/// ends with `moved`. Scenery only moves once, and only while `when` holds, otherwise
+/// the player is told it's `stuck`.
+///
+/// ```yml
+/// scenery:
+///   - id: bookcase
+///     targets: [shelves]
+///     verbs: [pull, move]
+///     value: The bookcase swings away from the wall.
+///     moved: The bookcase stands away from the wall.
+///     exits: [west]
+///     effects: [{ reveal: { letter: 1 } }]
+///     when: stats.strength >= 3
+///     stuck: The bookcase is too heavy to budge.
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Scenery {
+    pub id: String,
+    /// Extra words that refer to it, in addition to its id.
+    #[serde(default)]
+    pub targets: Vec<String>,
+    /// The ways it can be moved, or any of them when empty.
+    #[serde(default)]
+    pub verbs: Vec<Shift>,
+    pub value: Passage,
+    #[serde(default)]
+    pub moved: Option<Passage>,
+    /// The hidden exits of the room that moving it uncovers.
+    #[serde(default)]
+    pub exits: Vec<Direction>,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+    #[serde(default)]
+    pub when: Option<Condition>,
+    #[serde(default)]
+    pub stuck: Option<Passage>,
+}
+
+/// The verbs for moving scenery around.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Shift {
+    Push,
+    Pull,
+    Move,
+}
+
+impl Shift {
+    pub fn lowercase_str(&self) -> &'static str {
+        match self {
+            Shift::Push => "push",
+            Shift::Pull => "pull",
+            Shift::Move => "move",
+        }
+    }
+}
+
+/// How well lit a room is. Dim rooms hide their `details`, and are too dark to search
+/// for `hidden` items, unless the player carries a `light`, like a lantern. Bright rooms
+/// leave nowhere to hide, and conditions can check the light with `light.dim`, `light.normal`, and
+/// `light.bright`, so that sneaking past a guard can be given `when: not light.bright`.
+///
+/// ```yml
+/// - title: Crypt
+///   lighting: dim
+///   details: Scratched into the wall is a name, Aldous.
+///   items: [{ id: ring, quantity: 1, name: ~, pickup: ~, hidden: true }]
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lighting {
+    Dim,
+    #[default]
+    Normal,
+    Bright,
+}
+
+impl Lighting {
+    pub fn lowercase_str(&self) -> &'static str {
+        match self {
+            Lighting::Dim => "dim",
+            Lighting::Normal => "normal",
+            Lighting::Bright => "bright",
+        }
+    }
+}
+
+/// A puzzle in a room, solved by saying the right answer, like a code or the answer to
+/// a riddle, or by doing the steps of a sequence in order. Solving it runs its effects,
+/// and conditions can check it as `puzzles.<id>`, so that it can open an exit.
+///
+/// ```yml
+/// puzzles:
+///   - id: vault-dial
+///     answers: [7-3-9] # Typed as: say 7-3-9
+///     solved: The dial clicks, and the vault door swings open.
+///     wrong: The dial spins back to zero.
+///     effects: [{ reveal: { gold: 20 } }]
+///   - id: levers
+///     sequence: [left, right, left] # Taken by actions with effects like { step: left }
+///     solved: Somewhere, a gate grinds open.
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Puzzle {
+    /// Solved puzzles are kept in the save by id, so it must be unique in the level.
+    pub id: String,
+    /// What can be said to solve it. Case, spacing, and punctuation don't matter.
+    #[serde(default)]
+    pub answers: Vec<String>,
+    /// The steps to take in order.
+    #[serde(default)]
+    pub sequence: Vec<String>,
+    pub solved: Passage,
+    /// Shown for a wrong answer, or a step out of order, which starts the sequence
+    /// over.
+    #[serde(default)]
+    pub wrong: Option<Passage>,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+}
+
+/// A trap or another danger that springs each time the player enters its room, until
+/// it's disarmed. Its effects can hurt the player, or drop them somewhere else.
+///
+/// ```yml
+/// hazards:
+///   - id: cellar-pit
+///     targets: [pit, trapdoor]
+///     message: The floor gives way beneath you!
+///     effects: [{ stats: { health: -3 } }, { teleport: { entry: pit-bottom } }]
+///     avoid: { when: items.rope > 0, message: You swing across on your rope. }
+///     disarm: { check: { stat: dexterity, target: 12 }, message: You jam the trapdoor. }
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hazard {
+    /// Disarmed hazards are kept in the save by id, so it must be unique in the level.
+    pub id: String,
+    /// What the player calls it when disarming it.
+    #[serde(default)]
+    pub targets: Vec<String>,
+    /// Shown when the hazard springs.
+    pub message: Passage,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+    /// How the player can get past it when entering the room.
+    #[serde(default)]
+    pub avoid: Option<Safeguard>,
+    /// How the "disarm" command gets rid of it for good. Failing springs it.
+    #[serde(default)]
+    pub disarm: Option<Safeguard>,
+    /// Only spring once, like a floor that has already collapsed.
+    #[serde(default)]
+    pub once: bool,
+}
+
+/// A way to get past a hazard. It works while its condition holds, and otherwise
+/// the player can try their luck with the check. With neither, it always works.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Safeguard {
+    #[serde(default)]
+    pub when: Option<Condition>,
+    #[serde(default)]
+    pub check: Option<Check>,
+    /// Shown when it works.
+    #[serde(default)]
+    pub message: Option<Passage>,
+    /// Shown when it doesn't.
+    #[serde(default)]
+    pub failed: Option<Passage>,
+}
+
+/// A roll of a die plus one of the player's stats, which passes when the total is at
+/// least the target.
+///
+/// ```yml
+/// check: { stat: dexterity, target: 12 } # Roll a 20 sided die, add dexterity.
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Check {
+    #[serde(default = "default_sides")]
+    pub sides: i64,
+    #[serde(default)]
+    pub stat: Option<String>,
+    pub target: i64,
+}
+
+fn default_sides() -> i64 {
+    20
+}
+
+/// Where an exit or an action takes the player. An exit with a condition can only be
+/// taken while it holds, and shows why not otherwise.
+///
+/// ```yml
+/// exits:
+///   east: { level: sewer, entry: east-gate }
+///   north: { entry: market-gate, when: flags.gate-open, blocked: The gate is locked. }
+///   west: { entry: hideout, hidden: true, found_by: bookcase }
+///   south: { entry: pit, irreversible: You won't be able to climb back out. Jump? }
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Destination {
+    /// Another level of the campaign, or None to stay in this one.
+    #[serde(default)]
+    pub level: Option<String>,
+    /// One of the named entries of the level, or the id of one of its rooms.
+    pub entry: String,
+    #[serde(default)]
+    pub when: Option<Condition>,
+    /// What's shown when the exit can't be taken.
+    #[serde(default)]
+    pub blocked: Option<Passage>,
+    /// The exit is secret, so it isn't there until the player finds it with `search`.
+    #[serde(default)]
+    pub hidden: bool,
+    /// Where the secret exit is, like "bookcase", the same as for a hidden item.
+    #[serde(default)]
+    pub found_by: Option<String>,
+    /// The exit is a point of no return, so the player is asked this before taking
+    /// it, unless they've turned off `confirm`.
+    #[serde(default)]
+    pub irreversible: Option<Passage>,
 }
 
 impl Room {
-    pub fn npcs_iter<'a>(&'a self, level: &'a Level) -> impl Iterator<Item = &'a NPC> {
-        self.npcs
+    /// Iterate over all of the crowds present in this room, and how dense they are.
+    pub fn crowds_iter<'a>(
+        &'a self,
+        level: &'a Level,
+    ) -> impl Iterator<Item = (&'a Crowd, CrowdDensity)> {
+        level.crowds.iter().filter_map(move |(crowd_id, crowd)| {
+            if let Some(density) = self.crowds.get(crowd_id) {
+                return Some((crowd, *density));
+            }
+            if self
+                .regions
+                .iter()
+                .any(|region| crowd.regions.contains(region))
+            {
+                return Some((crowd, crowd.density));
+            }
+            None
+        })
+    }
+
+    pub fn get_crowd<'a>(&'a self, level: &'a Level, target: &str) -> Option<&'a Crowd> {
+        self.crowds_iter(level)
+            .map(|(crowd, _)| crowd)
+            .find(|crowd| level.vocabulary.matches(crowd.targets.iter(), target))
+    }
+
+    /// Iterate over the actions in this room, and the actions of its regions.
+    /// The ambient lines of the room, and of each region it's in.
+    pub fn ambient_iter<'a>(&'a self, level: &'a Level) -> impl Iterator<Item = &'a Ambient> {
+        let region_ambient = self
+            .regions
             .iter()
-            .map(move |npc_id| match level.npcs.get(npc_id) {
-                Some(npc) => npc,
-                None => {
-                    eprintln!("Unable to find an npc by the id {:?}", npc_id);
-                    eprintln!("The available NPCs are:");
-                    for key in level.npcs.keys() {
-                        eprintln!("  {:?}", key);
-                    }
-                    panic!();
-                }
-            })
+            .filter_map(move |region| level.regions.get(region))
+            .filter_map(|region| region.ambient.as_ref());
+        self.ambient.iter().chain(region_ambient)
     }
 
-    pub fn get_npc<'a>(&'a self, level: &'a Level, target: &String) -> Option<&'a NPC> {
-        self.npcs_iter(&level)
-            .find(|npc| npc.targets.contains(target))
+    pub fn actions_iter<'a>(&'a self, level: &'a Level) -> impl Iterator<Item = &'a Action> {
+        let room_actions = self.actions.iter().flatten();
+        let region_actions = self
+            .regions
+            .iter()
+            .filter_map(move |region| level.regions.get(region))
+            .flat_map(|region| region.actions.iter());
+        room_actions.chain(region_actions)
     }
 
+    /// Find the first action for a command, in the room and then its regions. Actions
+    /// that aren't available, like ones whose condition isn't met, are skipped.
     pub fn find_action<'a>(
         &'a self,
         verb: Verb,
-        target: &String,
+        target: &str,
         level: &'a Level,
         alias: Option<&String>,
+        is_available: impl Fn(&Action) -> bool,
     ) -> Option<&'a Action> {
         let action_match = |action: &&Action| {
-            if action.verb == verb && action.targets.contains(target) {
+            if action.verb == verb
+                && level.vocabulary.matches(action.targets.iter(), target)
+                && is_available(action)
+            {
                 if let Some(alias) = alias {
                     if let Some(ref action_alias) = action.alias {
                         return *action_alias == *alias;
@@ -128,13 +1300,19 @@ impl Room {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize, Eq, Hash, PartialOrd, Ord)]
 pub struct Coord {
     pub x: usize,
     pub y: usize,
     pub z: usize,
 }
 
+impl std::fmt::Display for Coord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[{}, {}, {}]", self.x, self.y, self.z)
+    }
+}
+
 impl Coord {
     pub fn apply(&self, direction: &Direction) -> Coord {
         match direction {
@@ -162,6 +1340,8 @@ impl Coord {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Direction {
     North,
     East,
@@ -170,7 +1350,7 @@ pub enum Direction {
 }
 
 impl Direction {
-    pub fn lowercase_string(&self) -> &str {
+    pub fn lowercase_string(&self) -> &'static str {
         match self {
             Direction::North => "north",
             Direction::East => "east",
@@ -178,15 +1358,347 @@ impl Direction {
             Direction::South => "south",
         }
     }
+
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::West => Direction::East,
+            Direction::South => Direction::North,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Emote {
+    Smile,
+    Bow,
+    Wave,
+    Dance,
+}
+
+impl Emote {
+    pub fn lowercase_string(&self) -> &str {
+        match self {
+            Emote::Smile => "smile",
+            Emote::Bow => "bow",
+            Emote::Wave => "wave",
+            Emote::Dance => "dance",
+        }
+    }
+}
+
+/// What a room is on the map, by its character. Rooms drawn with `.` are land, and
+/// rooms drawn with `~` are water, which can only be crossed aboard a vehicle. The
+/// level's own tiles say which they are.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Terrain {
+    #[default]
+    Land,
+    Water,
+}
+
+/// A kind of room the level draws on its maps with its own character, besides `.` for
+/// land, `~` for water, and `#` or `-` for walls. Entering one can show a message, take
+/// some of the player's health, or need an item first.
+///
+/// ```yml
+/// tiles:
+///   "^":
+///     when: items.fire-boots > 0
+///     blocked: The lava would burn right through your shoes.
+///     damage: 1
+///     message: The heat of the lava washes over you.
+///   "*": { passable: false } # Boulders, drawn like walls.
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Tile {
+    /// Whether it's a room at all. Tiles that aren't passable are walls by another
+    /// name, like mountains, and need no room defined.
+    #[serde(default = "default_passable")]
+    pub passable: bool,
+    #[serde(default)]
+    pub terrain: Terrain,
+    /// The player can only enter it while this holds, like carrying the right item.
+    #[serde(default)]
+    pub when: Option<Condition>,
+    /// What's said when the condition doesn't hold, in place of the narrator's message.
+    #[serde(default)]
+    pub blocked: Option<Passage>,
+    /// How much health the player loses each time they enter it.
+    #[serde(default)]
+    pub damage: usize,
+    /// What's said each time the player enters it, before the room.
+    #[serde(default)]
+    pub message: Option<Passage>,
+}
+
+fn default_passable() -> bool {
+    true
+}
+
+/// Something the player can board or ride, like a rowboat or a horse, from the room
+/// that lists it in its `vehicles`. Aboard, the player can only go into rooms of the
+/// vehicle's terrain, or out of it to the edge, like a boat pulling up to a jetty. The
+/// vehicle goes along, and stays where the player gets off.
+///
+/// ```yml
+/// vehicles:
+///   rowboat:
+///     name: the rowboat
+///     targets: [rowboat, boat]
+///     terrain: [water]
+///     board: You climb into the rowboat, and take up the oars.
+///     dismount: You climb out onto dry land.
+///     moves: The oars creak as you row.
+///     blocked: The rowboat won't go over land.
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Vehicle {
+    pub name: String,
+    pub targets: Vec<String>,
+    #[serde(default = "default_terrain")]
+    pub terrain: Vec<Terrain>,
+    /// What's said when the player boards it, in place of the narrator's message.
+    #[serde(default)]
+    pub board: Option<Passage>,
+    /// What's said when the player gets off, in place of the narrator's message.
+    #[serde(default)]
+    pub dismount: Option<Passage>,
+    /// What's said each time the player moves aboard it, before the room they reach.
+    #[serde(default)]
+    pub moves: Option<Passage>,
+    /// What's said when it can't go where the player is heading.
+    #[serde(default)]
+    pub blocked: Option<Passage>,
+}
+
+fn default_terrain() -> Vec<Terrain> {
+    vec![Terrain::Land]
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct NPC {
+    #[serde(default)]
     pub name: String,
-    pub description: String,
+    #[serde(default)]
+    pub description: Passage,
+    /// A pool of names to pick from for each new game, in place of the name.
+    #[serde(default)]
+    pub names: Vec<String>,
+    /// A pool of descriptions to pick from for each new game, in place of the
+    /// description.
+    #[serde(default)]
+    pub descriptions: Vec<Passage>,
     pub targets: Vec<String>,
-    pub talk: String,
+    pub talk: Passage,
     pub items: Vec<SaleItem>,
+    #[serde(default)]
+    pub voice: Voice,
+    #[serde(default)]
+    pub movement: Movement,
+    /// The id of the faction the NPC belongs to.
+    #[serde(default)]
+    pub faction: Option<String>,
+    /// What the player can ask the NPC about. Talking to an NPC with topics starts a
+    /// conversation, where the player only has to type the topic.
+    #[serde(default)]
+    pub topics: Vec<Topic>,
+    /// What the NPC does with items the player gives them. Any other item is refused,
+    /// unless the NPC carries it.
+    #[serde(default)]
+    pub gifts: Vec<Gift>,
+    /// What happens when the player throws items at the NPC, who otherwise dodges.
+    #[serde(default)]
+    pub throws: Vec<Throw>,
+    /// How many items the NPC will carry for the player, which makes them a companion
+    /// that can be given items, and asked for them back.
+    #[serde(default)]
+    pub carry: Option<usize>,
+    /// The NPC keeps the player's stash, so they can deposit and withdraw items here.
+    #[serde(default)]
+    pub bank: bool,
+    /// How hard the NPC haggles over their prices, from 0 to 100. Stubborn NPCs come
+    /// down less, and are likelier to be insulted by a low offer.
+    #[serde(default = "default_stubbornness")]
+    pub stubbornness: usize,
+    /// The NPC can join the player's party, see `Companion`.
+    #[serde(default)]
+    pub companion: Option<Companion>,
+    /// The NPC is an animal that can be tamed, see `Pet`.
+    #[serde(default)]
+    pub pet: Option<Pet>,
+}
+
+/// An animal the player tames by feeding it one of the items it `eats`. Once it's tame
+/// it follows the player around the level, likes to be petted, and can be sent to fetch
+/// an item, which it sniffs out in the nearest room that has one. It's gone for the
+/// walk there and back, and drops the item at the player's feet wherever they are.
+///
+/// ```yml
+/// pet:
+///   eats: [bone, sausage]
+///   tamed: Rex wolfs down the food, and wags his tail at you.
+///   petted: Rex rolls over for a belly rub.
+///   wild: Rex growls.
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pet {
+    pub eats: Vec<String>,
+    #[serde(default)]
+    pub tamed: Option<Passage>,
+    /// Shown when the player pets it once it's tame.
+    #[serde(default)]
+    pub petted: Option<Passage>,
+    /// Shown when the player tries to pet it before it's tame.
+    #[serde(default)]
+    pub wild: Option<Passage>,
+}
+
+/// An NPC who can join the player's party, with a `join` effect from a topic, a gift, or
+/// a quest, and `leave` it again. Party members go wherever the player goes, even into
+/// the other levels of the campaign that have them. They say each of their `comments`
+/// the first time they're in a room with the player, by room id or entry, and add their
+/// `assists` to the player's stats for checks, like disarming a trap together. Give them
+/// `carry` to have them carry items too.
+///
+/// ```yml
+/// companion:
+///   joins: "\"Lead the way,\" says Mira."
+///   leaves: Mira heads back to the docks.
+///   comments:
+///     market: "\"Watch your purse in here,\" Mira mutters."
+///   assists: { dexterity: 2 }
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Companion {
+    #[serde(default)]
+    pub joins: Option<Passage>,
+    #[serde(default)]
+    pub leaves: Option<Passage>,
+    #[serde(default)]
+    pub comments: BTreeMap<String, Passage>,
+    #[serde(default)]
+    pub assists: BTreeMap<String, i64>,
+}
+
+fn default_stubbornness() -> usize {
+    50
+}
+
+/// Items an NPC takes from the player, like `give all gems to jeweler`. The value is
+/// said, and the effects applied, for each item given.
+///
+/// ```yml
+/// gifts:
+///   - { items: [ruby, emerald], value: "\"Fine stones.\"", effects: [{ set_flag: sold-gems }] }
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Gift {
+    /// The ids of the items.
+    pub items: Vec<String>,
+    pub value: Passage,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+}
+
+/// Items the player throws at an NPC or at something in a room, like
+/// `throw rope at chasm`. The value is shown, and the effects applied, for the first
+/// throw that lists the item, or any throw without `items`. Rooms say what each throw is
+/// aimed at with its `targets`. Thrown items land in the room, unless they `break`, or
+/// `land` in another room, by entry or room id, like across the chasm.
+///
+/// ```yml
+/// throws:
+///   - { targets: [chasm], items: [rope], value: The rope catches on the far side., land: ledge }
+///   - { targets: [window], items: [rock], value: The glass shatters!, break: true }
+///   - { targets: [window], value: It bounces off the glass. }
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Throw {
+    #[serde(default)]
+    pub targets: Vec<String>,
+    /// The ids of the items, or any item when empty.
+    #[serde(default)]
+    pub items: Vec<String>,
+    pub value: Passage,
+    #[serde(default, rename = "break")]
+    pub breaks: bool,
+    #[serde(default)]
+    pub land: Option<String>,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+}
+
+impl Throw {
+    pub fn throws(&self, item: &str) -> bool {
+        self.items.is_empty() || self.items.iter().any(|id| id == item)
+    }
+}
+
+/// Something an NPC can be asked about, like `ask fishmonger about eels`.
+///
+/// ```yml
+/// topics:
+///   - { targets: [eels, eel], value: "\"Fresh this morning!\"" }
+///   - { targets: [guild], value: "\"Don't cross them.\"", when: reputation.guild < 0 }
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Topic {
+    /// The words for the topic, the first of which is listed in conversations.
+    pub targets: Vec<String>,
+    pub value: Passage,
+    /// The topic can only be asked about while the condition holds.
+    #[serde(default)]
+    pub when: Option<Condition>,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+}
+
+/// How an NPC moves around the level after each of the player's turns. NPCs start in
+/// the room that lists them, and the ones that move are mentioned in the description of
+/// whichever room they are in.
+///
+/// ```yml
+/// movement: { wander: { region: docks, chance: 0.3 } }
+/// movement: { follow: { when: flags.freed-the-dog } }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Movement {
+    /// Stay in the room that lists the NPC.
+    #[default]
+    Static,
+    /// Sometimes walk to a room next door, without leaving the region.
+    Wander {
+        region: String,
+        /// How likely the NPC is to move after each turn, from 0 to 1.
+        #[serde(default = "default_wander_chance")]
+        chance: f64,
+    },
+    /// Once the condition holds, walk along with the player whenever they leave the
+    /// NPC's room.
+    Follow { when: Condition },
+}
+
+fn default_wander_chance() -> f64 {
+    0.3
+}
+
+/// How an NPC's dialogue is styled when printed. The color, capitalization, and
+/// stutter are applied to the quoted speech, while the prefix marks every line.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Voice {
+    #[serde(default)]
+    pub color: Option<Color>,
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub all_caps: bool,
+    #[serde(default)]
+    pub stutter: bool,
 }
 
 impl NPC {
@@ -196,30 +1708,184 @@ impl NPC {
     ) -> impl Iterator<Item = (&'a InventoryItem, usize)> {
         self.items
             .iter()
-            .map(move |SaleItem { ref id, ref cost }| (item_db.get(&id), *cost))
+            .map(move |SaleItem { ref id, price }| (item_db.get(id), *price))
     }
 }
 
+/// A group of people that spans several rooms, such as the throng of a market. Rather
+/// than authoring many identical NPCs, a crowd is placed into every room of its
+/// regions, and shares its description and dialogue between them.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Crowd {
+    pub name: String,
+    pub targets: Vec<String>,
+    #[serde(default)]
+    pub regions: Vec<String>,
+    pub description: String,
+    pub talk: String,
+    /// The density of the crowd for rooms that don't override it.
+    #[serde(default)]
+    pub density: CrowdDensity,
+    /// The line added to the room description for each density.
+    pub densities: HashMap<CrowdDensity, String>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CrowdDensity {
+    Empty,
+    Sparse,
+    #[default]
+    Busy,
+    Packed,
+}
+
+/// An item an NPC sells, for a `price` in the smallest denomination. Levels from before
+/// coins gave a `cost` in gold instead, which is worth 100 of the smallest denomination.
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "SaleItemFile")]
 pub struct SaleItem {
     pub id: String,
-    pub cost: usize,
+    pub price: usize,
+}
+
+#[derive(Deserialize)]
+struct SaleItemFile {
+    id: String,
+    #[serde(default)]
+    price: Option<usize>,
+    #[serde(default)]
+    cost: Option<usize>,
+}
+
+impl TryFrom<SaleItemFile> for SaleItem {
+    type Error = String;
+
+    fn try_from(file: SaleItemFile) -> Result<SaleItem, String> {
+        let price = match (file.price, file.cost) {
+            (Some(price), None) => price,
+            (None, Some(gold)) => gold * GOLD,
+            (Some(_), Some(_)) => {
+                return Err(format!(
+                    "The sale of {:?} has both a price and a cost in gold, remove the cost.",
+                    file.id
+                ))
+            }
+            (None, None) => return Err(format!("The sale of {:?} has no price.", file.id)),
+        };
+        Ok(SaleItem { id: file.id, price })
+    }
 }
 
+#[derive(Clone, Deserialize)]
+#[serde(from = "ItemsFile")]
 pub struct ItemDatabase {
     items: Vec<InventoryItem>,
+    /// The index of each item by its id.
+    by_id: HashMap<String, usize>,
+    recipes: Vec<Recipe>,
+}
+
+/// The items file, with every item in the game and the recipes for crafting them.
+/// Files from before recipes are just the list of items.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ItemsFile {
+    Items(Vec<InventoryItem>),
+    WithRecipes {
+        #[serde(default)]
+        recipes: Vec<Recipe>,
+        items: Vec<InventoryItem>,
+    },
+}
+
+impl From<ItemsFile> for ItemDatabase {
+    fn from(file: ItemsFile) -> ItemDatabase {
+        match file {
+            ItemsFile::Items(items) => ItemDatabase::from_items(items),
+            ItemsFile::WithRecipes { recipes, items } => ItemDatabase {
+                recipes,
+                ..ItemDatabase::from_items(items)
+            },
+        }
+    }
+}
+
+/// A way to make an item out of others, with `combine rope with stick` or
+/// `craft fishing rod`. The ingredients are used up.
+///
+/// ```yml
+/// recipes:
+///   - ingredients: { rope: 1, stick: 2 }
+///     output: fishing-rod
+///     message: You lash the sticks together, and tie on the rope.
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Recipe {
+    /// How many of each item are needed, by id.
+    pub ingredients: BTreeMap<String, usize>,
+    /// The id of the item that is made.
+    pub output: String,
+    /// How many of the item are made.
+    #[serde(default = "default_recipe_quantity")]
+    pub quantity: usize,
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+fn default_recipe_quantity() -> usize {
+    1
+}
+
+impl Default for ItemDatabase {
+    fn default() -> ItemDatabase {
+        ItemDatabase::new()
+    }
 }
 
 impl ItemDatabase {
     pub fn new() -> ItemDatabase {
+        parse_yml(&ITEMS_PATH.into())
+    }
+
+    pub fn from_items(mut items: Vec<InventoryItem>) -> ItemDatabase {
+        for item in items.iter_mut() {
+            if item.targets.is_empty() {
+                item.targets = derive_targets(&item.name);
+            }
+        }
+        let mut by_id = HashMap::new();
+        for (index, item) in items.iter().enumerate() {
+            by_id.entry(item.id.clone()).or_insert(index);
+        }
+        ItemDatabase {
+            items,
+            by_id,
+            recipes: Vec::new(),
+        }
+    }
+
+    /// A copy of the database with other items, and the same recipes.
+    pub fn with_items(&self, items: Vec<InventoryItem>) -> ItemDatabase {
         ItemDatabase {
-            items: parse_yml(&"data/items.yml".into()),
+            recipes: self.recipes.clone(),
+            ..ItemDatabase::from_items(items)
         }
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &InventoryItem> {
+        self.items.iter()
+    }
+
+    pub fn recipes(&self) -> &[Recipe] {
+        &self.recipes
+    }
+
+    pub fn find(&self, id: &str) -> Option<&InventoryItem> {
+        self.by_id.get(id).map(|index| &self.items[*index])
+    }
+
     pub fn get(&self, id: &str) -> &InventoryItem {
-        let item = self.items.iter().find(|item| item.id == id);
-        match item {
+        match self.find(id) {
             Some(item) => item,
             None => {
                 panic!("Unable to find the item with the id {}", id);
@@ -232,20 +1898,139 @@ impl ItemDatabase {
 pub struct InventoryItem {
     pub id: String,
     pub name: String,
-    pub targets: HashSet<String>,
+    /// What comes before the name in a sentence, like "an apple". Left out, it's "a" or
+    /// "an" by the first letter of the name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub article: Option<Article>,
+    /// The name of more than one, when it isn't the name with an "s", like "loaves of
+    /// bread".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plural: Option<String>,
+    /// The words that refer to this item. These are derived from the name when they
+    /// aren't provided.
+    #[serde(default)]
+    pub targets: BTreeSet<String>,
     #[serde(default)]
     pub sticky: bool,
+    /// Reading the item unlocks this entry of the level's lore, see `Lore`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lore: Option<String>,
+    /// Carrying the item lights up dim rooms, see `Lighting`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub light: bool,
     pub variant: ItemVariant,
     #[serde(default)]
     pub quantity: usize,
     #[serde(default)]
     pub max_quantity: Option<usize>,
     pub description: String,
+    #[serde(default)]
+    pub stats: ItemStats,
+    /// What eating or drinking the item adds to the player's stats, like
+    /// `{ food: 30 }`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub restores: BTreeMap<String, i64>,
+    /// What else happens when the item is eaten, like `[{ status: drunk }]`. Items
+    /// without any effects or anything to restore can't be eaten.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<Effect>,
+}
+
+/// The article that comes before an item's name in a sentence, see `grammar`. Items
+/// that are one of a kind are "the" one, and names like "Excalibur" or "gold" have
+/// none.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Article {
+    A,
+    An,
+    The,
+    None,
 }
 
+/// The numbers behind an item, for comparing equipment. Items only list the stats
+/// that make sense for them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ItemStats {
+    pub damage: Option<usize>,
+    pub weight: Option<usize>,
+    pub value: Option<usize>,
+    pub durability: Option<usize>,
+}
+
+/// A part of the map that rooms belong to, like a district of a city. Crossing into a
+/// region shows its banner, or says that the player has entered it by name, and looking
+/// around adds its description to the room's.
+///
+/// ```yml
+/// regions:
+///   docks:
+///     name: Dockside district
+///     banner: Gulls wheel over the masts as you come down to the water.
+///     description: The smell of tar and fish hangs over everything.
+///     actions: []
+/// ```
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Region {
+    /// What the region is called, like "Dockside district".
+    #[serde(default)]
+    pub name: Option<String>,
+    /// What's shown when the player crosses into the region, in place of its name.
+    #[serde(default)]
+    pub banner: Option<Passage>,
+    /// Added to the room's description when the player looks around.
+    #[serde(default)]
+    pub description: Option<Passage>,
+    /// What's added to the descriptions of the outdoor rooms in the region in each kind
+    /// of weather, see `Climate`.
+    #[serde(default)]
+    pub weather: BTreeMap<Weather, Passage>,
+    /// The id of the music that loops in the region's rooms, see `Cue`.
+    #[serde(default)]
+    pub music: Option<String>,
+    #[serde(default)]
     pub actions: Vec<Action>,
+    #[serde(default)]
+    pub ambient: Option<Ambient>,
+    /// Changes to what every merchant in the region charges, see `RegionPrice`.
+    #[serde(default)]
+    pub prices: Vec<RegionPrice>,
+}
+
+/// A percent of the usual price that merchants in a region charge while a condition
+/// holds, like a discount once the player has saved the market from thieves. The first
+/// one that holds is used, on top of any faction's prices.
+///
+/// ```yml
+/// prices:
+///   - { when: flags.saved-the-market, percent: 90 }
+///   - { when: "reputation.thieves > 5", percent: 120 }
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RegionPrice {
+    pub when: Condition,
+    pub percent: usize,
+}
+
+/// Lines that are sometimes shown after a turn spent in a room or region, to bring
+/// the place to life. They aren't shown after talking to someone, so they don't
+/// interrupt the conversation.
+///
+/// ```yml
+/// ambient:
+///   chance: 0.25
+///   lines: [A gull screeches overhead., The smell of fish drifts by.]
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Ambient {
+    /// How likely one of the lines is to be shown after each turn, from 0 to 1.
+    #[serde(default = "default_ambient_chance")]
+    pub chance: f64,
+    pub lines: Vec<Passage>,
+}
+
+fn default_ambient_chance() -> f64 {
+    0.2
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -253,16 +2038,51 @@ pub struct Action {
     pub verb: Verb,
     pub alias: Option<String>,
     pub targets: Vec<String>,
-    pub value: String,
+    pub value: Passage,
+    /// Move the player somewhere after the action, like `teleport: { entry: cellar }`.
+    #[serde(default)]
+    pub teleport: Option<Destination>,
+    /// Points and achievements for the first time the action is used.
+    #[serde(default)]
+    pub award: Option<Award>,
+    /// The action kills the player, and this is their epitaph.
+    #[serde(default)]
+    pub death: Option<Passage>,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+    /// The action can only be used while this holds, like `when: items.key > 0`.
+    /// Otherwise the next action for the command is used, if there is one.
+    #[serde(default)]
+    pub when: Option<Condition>,
+    /// The action can't be taken back, so the player is asked this before it's used,
+    /// the same as for an exit, see `Destination`.
+    #[serde(default)]
+    pub irreversible: Option<Passage>,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct RoomItem {
     pub id: String,
     pub quantity: usize,
+    /// What the item is called in this room, in place of its name. It's shown as it's
+    /// written, like a sentence, unless it's given an article of its own.
     pub name: Option<String>,
-    pub targets: HashSet<String>,
+    #[serde(default)]
+    pub article: Option<Article>,
+    #[serde(default)]
+    pub plural: Option<String>,
+    /// Extra words that refer to this item, in addition to the item's own targets.
+    #[serde(default)]
+    pub targets: BTreeSet<String>,
     pub pickup: Option<String>,
+    /// The item is tucked away, so it can't be seen or taken until the player finds it
+    /// with `search`.
+    #[serde(default)]
+    pub hidden: bool,
+    /// Where the hidden item is, like "desk", which the player has to search to find
+    /// it. It's also a hint, for when they search the room as a whole.
+    #[serde(default)]
+    pub found_by: Option<String>,
 }
 
 impl From<&InventoryItem> for RoomItem {
@@ -271,8 +2091,12 @@ impl From<&InventoryItem> for RoomItem {
             id: inventor_item.id.clone(),
             quantity: inventor_item.quantity,
             name: None,
-            targets: HashSet::new(),
+            article: None,
+            plural: None,
+            targets: BTreeSet::new(),
             pickup: None,
+            hidden: false,
+            found_by: None,
         }
     }
 }
@@ -282,6 +2106,7 @@ pub enum Verb {
     Help,
     Look,
     Talk,
+    Emote,
     Custom,
 }
 
@@ -291,3 +2116,13 @@ pub enum ItemVariant {
     Weapon,
     Money,
 }
+
+impl ItemVariant {
+    pub fn lowercase_str(&self) -> &'static str {
+        match self {
+            ItemVariant::Consumable => "consumable",
+            ItemVariant::Weapon => "weapon",
+            ItemVariant::Money => "money",
+        }
+    }
+}
@@ -0,0 +1,606 @@
+use crate::{
+    level::{Coord, Direction, Level},
+    utils::try_parse_yml_str,
+    validate::validate,
+    yml, ItemDatabase, LEVEL_PATH,
+};
+use serde_yaml::{Mapping, Value};
+use std::{
+    fs,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+const HELP: &str = "\
+Commands:
+  show               Show the room under the cursor, and the map around it.
+  north, east, ...   Move the cursor (Also: n, e, s, w).
+  goto <x> <y> <z>   Move the cursor to a coord.
+  goto <id>          Move the cursor to a room or entry by its id.
+  room <title>       Make a room under the cursor, or change the title of its room.
+  dig <dir> <title>  Make a room next to the cursor's, joined to it, and move there.
+  describe <text>    Change the description of the room.
+  id <id>            Give the room an id, for exits and entries to lead to.
+  link <dir> <id>    Add an exit to a room or entry elsewhere in the level.
+  unlink <dir>       Take an exit away.
+  item <id> [count]  Place an item in the room, by its id in data/items.yml.
+  npc <id>           Place one of the level's NPCs in the room.
+  delete             Take the room away, leaving a wall on the map.
+  check              List the problems with the level.
+  write              Save the changes, keeping the old level next to it as a backup.
+  quit               Stop editing.";
+
+/// How far around the cursor the map is shown.
+const VIEW_COLUMNS: usize = 12;
+const VIEW_ROWS: usize = 4;
+
+/// Edits a level from a prompt, with a cursor on its map, as a faster way to lay out
+/// rooms than working out their coords by hand. The level is edited as YML, so the
+/// fields that aren't edited are written back as they were, in the same order, and
+/// every edit is checked to still load.
+pub struct LevelEditor {
+    path: PathBuf,
+    yml: Value,
+    item_db: ItemDatabase,
+    cursor: Coord,
+    /// Whether there are edits that haven't been written.
+    changed: bool,
+}
+
+impl LevelEditor {
+    /// Open a level, with the cursor on its entry.
+    pub fn open(path: &Path) -> Result<LevelEditor, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("Unable to read {}: {}", path.display(), err))?;
+        let yml: Value = try_parse_yml_str(&text, &path.display().to_string())?;
+        let level: Level = try_parse_yml_str(&text, &path.display().to_string())?;
+        Ok(LevelEditor {
+            path: path.into(),
+            yml,
+            item_db: ItemDatabase::new(),
+            cursor: level.entry,
+            changed: false,
+        })
+    }
+
+    /// The level as it is with the edits so far.
+    fn level(&self) -> Result<Level, String> {
+        serde_yaml::from_value(self.yml.clone())
+            .map_err(|err| format!("The level no longer loads: {}", err))
+    }
+
+    /// Run one of the editor's commands, returning what to show the author. An edit
+    /// that would keep the level from loading is undone.
+    pub fn run(&mut self, line: &str) -> Result<String, String> {
+        let before = self.yml.clone();
+        let result = self.edit(line).and_then(|message| {
+            self.level()?;
+            Ok(message)
+        });
+        if result.is_err() {
+            self.yml = before;
+        } else if self.yml != before {
+            self.changed = true;
+        }
+        result
+    }
+
+    fn edit(&mut self, line: &str) -> Result<String, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let args: Vec<&str> = words.collect();
+        let rest = args.join(" ");
+        match (command, args.as_slice()) {
+            ("show", []) => self.show(),
+            (_, []) if parse_direction(command).is_some() => {
+                let direction = parse_direction(command).unwrap();
+                self.cursor = step(self.cursor, direction)?;
+                self.show()
+            }
+            ("goto", [id]) => {
+                self.cursor = self
+                    .level()?
+                    .find_entry(id)
+                    .ok_or_else(|| format!("The level has no room or entry {:?}.", id))?;
+                self.show()
+            }
+            ("goto", [x, y, z]) => {
+                self.cursor = parse_coord(x, y, z)?;
+                self.show()
+            }
+            ("room", [_, ..]) => self.make_room(&rest),
+            ("dig", [direction, title @ ..]) if !title.is_empty() => {
+                let direction = parse_direction(direction)
+                    .ok_or_else(|| format!("Expected a direction, not {:?}.", direction))?;
+                self.dig(direction, &title.join(" "))
+            }
+            ("describe", [_, ..]) => {
+                self.room_mut()?
+                    .insert("description".into(), Value::String(rest));
+                Ok("Changed the description.".into())
+            }
+            ("id", [id]) => {
+                if let Some(room) = self.level()?.rooms.get_by_id(id) {
+                    return Err(format!(
+                        "The room {:?} already has the id {:?}.",
+                        room.title, id
+                    ));
+                }
+                self.room_mut()?
+                    .insert("id".into(), Value::String(id.to_string()));
+                Ok(format!("Gave the room the id {:?}.", id))
+            }
+            ("link", [direction, id]) => {
+                let direction = parse_direction(direction)
+                    .ok_or_else(|| format!("Expected a direction, not {:?}.", direction))?;
+                if self.level()?.find_entry(id).is_none() {
+                    return Err(format!("The level has no room or entry {:?}.", id));
+                }
+                let mut destination = Mapping::new();
+                destination.insert("entry".into(), Value::String(id.to_string()));
+                self.exits_mut()?.insert(
+                    direction.lowercase_string().into(),
+                    Value::Mapping(destination),
+                );
+                Ok(format!(
+                    "The exit {} now leads to {:?}.",
+                    direction.lowercase_string(),
+                    id
+                ))
+            }
+            ("unlink", [direction]) => {
+                let direction = parse_direction(direction)
+                    .ok_or_else(|| format!("Expected a direction, not {:?}.", direction))?;
+                let name = direction.lowercase_string();
+                match self.exits_mut()?.remove(&Value::from(name)) {
+                    Some(_) => Ok(format!("Took the exit {} away.", name)),
+                    None => Err(format!("The room has no exit {}.", name)),
+                }
+            }
+            ("item", [id]) => self.item(id, 1),
+            ("item", [id, quantity]) => match quantity.parse() {
+                Ok(quantity) if quantity > 0 => self.item(id, quantity),
+                _ => Err(format!(
+                    "Expected a count of at least 1, not {:?}.",
+                    quantity
+                )),
+            },
+            ("npc", [id]) => {
+                if !self.level()?.npcs.contains_key(*id) {
+                    return Err(format!("The level has no NPC {:?}.", id));
+                }
+                list_mut(self.room_mut()?, "npcs").push(Value::String(id.to_string()));
+                Ok(format!("Placed {:?} in the room.", id))
+            }
+            ("delete", []) => {
+                let index = self.room_index().ok_or_else(|| self.no_room())?;
+                let room = rooms_mut(&mut self.yml).remove(index);
+                self.set_cell(self.cursor, '-');
+                let title = room.get("title").and_then(Value::as_str).unwrap_or("");
+                Ok(format!("Took the room {:?} away.", title))
+            }
+            ("check", []) => self.check(),
+            ("write", []) => self.write(),
+            ("help", []) => Ok(HELP.into()),
+            _ => Err(format!(
+                "Unknown command {:?}, type \"help\" for the commands.",
+                line.trim()
+            )),
+        }
+    }
+
+    /// Where the cursor is, the room under it, and the map around it, with the cursor
+    /// drawn as an @.
+    fn show(&self) -> Result<String, String> {
+        let level = self.level()?;
+        let mut lines = vec![format!("Cursor: {}", self.cursor)];
+        match level.get_room(&self.cursor) {
+            Some(room) => {
+                let id = room.id.as_deref().unwrap_or("no id");
+                lines.push(format!("Room:   {} ({})", room.title, id));
+                let description = self
+                    .room_yml()
+                    .and_then(|room| room.get(&Value::from("description")))
+                    .map(|description| match description.as_str() {
+                        Some("") => "(no description)",
+                        Some(description) => description,
+                        None => "(it varies)",
+                    })
+                    .unwrap_or("(no description)");
+                lines.push(format!("        {}", description));
+                for item in room.items.iter() {
+                    lines.push(format!("  ‣ item {} ({})", item.id, item.quantity));
+                }
+                for npc in room.npcs.iter() {
+                    lines.push(format!("  ‣ npc {}", npc));
+                }
+                let mut exits: Vec<_> = room.exits.iter().collect();
+                exits.sort_by_key(|(direction, _)| **direction);
+                for (direction, destination) in exits {
+                    lines.push(format!(
+                        "  ‣ exit {} to {}",
+                        direction.lowercase_string(),
+                        destination.entry
+                    ));
+                }
+            }
+            None => lines.push("Room:   (none)".into()),
+        }
+        lines.push(String::new());
+        let Coord { x, y, .. } = self.cursor;
+        for row in y.saturating_sub(VIEW_ROWS)..=y + VIEW_ROWS {
+            let line: String = (x.saturating_sub(VIEW_COLUMNS)..=x + VIEW_COLUMNS)
+                .map(|column| {
+                    let coord = Coord {
+                        x: column,
+                        y: row,
+                        z: self.cursor.z,
+                    };
+                    match coord == self.cursor {
+                        true => '@',
+                        false => self.cell(coord),
+                    }
+                })
+                .collect();
+            lines.push(format!("  {}", line.trim_end()));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    fn check(&self) -> Result<String, String> {
+        let problems = validate(&self.level()?, &self.item_db);
+        if problems.is_empty() {
+            return Ok("No problems were found.".into());
+        }
+        let mut lines: Vec<String> = problems
+            .iter()
+            .map(|problem| format!("  ‣ {}", problem))
+            .collect();
+        lines.push(format!("\nFound {} problems.", problems.len()));
+        Ok(lines.join("\n"))
+    }
+
+    /// Make a room under the cursor, drawing it on the map if it isn't already, or
+    /// change the title of the room that's there.
+    fn make_room(&mut self, title: &str) -> Result<String, String> {
+        if let Ok(room) = self.room_mut() {
+            room.insert("title".into(), Value::String(title.into()));
+            return Ok(format!("Changed the title to {:?}.", title));
+        }
+        if !matches!(self.cell(self.cursor), '.' | '~') {
+            self.set_cell(self.cursor, '.');
+        }
+        let mut room = Mapping::new();
+        room.insert("title".into(), Value::String(title.into()));
+        room.insert("coord".into(), coord_value(self.cursor));
+        room.insert("description".into(), Value::String(String::new()));
+        rooms_mut(&mut self.yml).push(Value::Mapping(room));
+        Ok(format!("Made the room {:?} at {}.", title, self.cursor))
+    }
+
+    /// Make a room next to the cursor's, which the map joins to it, and move there.
+    fn dig(&mut self, direction: Direction, title: &str) -> Result<String, String> {
+        if self.room_index().is_none() {
+            return Err(self.no_room());
+        }
+        let next = step(self.cursor, direction)?;
+        if self.level()?.get_room(&next).is_some() {
+            return Err(format!(
+                "There's already a room to the {}.",
+                direction.lowercase_string()
+            ));
+        }
+        self.cursor = next;
+        self.make_room(title)
+    }
+
+    fn item(&mut self, id: &str, quantity: usize) -> Result<String, String> {
+        if self.item_db.find(id).is_none() {
+            return Err(format!("There is no item {:?} in the item database.", id));
+        }
+        let mut item = Mapping::new();
+        item.insert("id".into(), Value::String(id.into()));
+        item.insert("quantity".into(), Value::Number(quantity.into()));
+        list_mut(self.room_mut()?, "items").push(Value::Mapping(item));
+        Ok(format!("Placed {} {:?} in the room.", quantity, id))
+    }
+
+    fn no_room(&self) -> String {
+        format!(
+            "There is no room at {}, make one with \"room <title>\".",
+            self.cursor
+        )
+    }
+
+    /// The index of the room under the cursor in the level's list of rooms.
+    fn room_index(&self) -> Option<usize> {
+        self.yml
+            .get("rooms")?
+            .as_sequence()?
+            .iter()
+            .position(|room| {
+                room.get("coord")
+                    .and_then(|coord| serde_yaml::from_value::<Coord>(coord.clone()).ok())
+                    == Some(self.cursor)
+            })
+    }
+
+    fn room_yml(&self) -> Option<&Mapping> {
+        let index = self.room_index()?;
+        self.yml.get("rooms")?.get(index)?.as_mapping()
+    }
+
+    fn room_mut(&mut self) -> Result<&mut Mapping, String> {
+        let index = self.room_index().ok_or_else(|| self.no_room())?;
+        rooms_mut(&mut self.yml)[index]
+            .as_mapping_mut()
+            .ok_or_else(|| "The room isn't written as a mapping.".to_string())
+    }
+
+    fn exits_mut(&mut self) -> Result<&mut Mapping, String> {
+        let room = self.room_mut()?;
+        let exits = room
+            .entry("exits".into())
+            .or_insert_with(|| Value::Mapping(Mapping::new()));
+        if !exits.is_mapping() {
+            *exits = Value::Mapping(Mapping::new());
+        }
+        Ok(exits.as_mapping_mut().unwrap())
+    }
+
+    /// The character of the map at a coord, or a space off the edge of the map. The
+    /// comment at the end of a row isn't part of it.
+    fn cell(&self, coord: Coord) -> char {
+        self.yml
+            .get("maps")
+            .and_then(|maps| maps.get(coord.z)?.get(coord.y)?.as_str())
+            .and_then(|row| row.split(' ').next()?.chars().nth(coord.x))
+            .unwrap_or(' ')
+    }
+
+    /// Draw a character on the map, growing it with walls to reach the coord, and
+    /// keeping the comment at the end of the row.
+    fn set_cell(&mut self, coord: Coord, ch: char) {
+        let maps = list_mut(self.yml.as_mapping_mut().unwrap(), "maps");
+        while maps.len() <= coord.z {
+            maps.push(Value::Sequence(Vec::new()));
+        }
+        let rows = match maps[coord.z] {
+            Value::Sequence(ref mut rows) => rows,
+            ref mut other => {
+                *other = Value::Sequence(Vec::new());
+                other.as_sequence_mut().unwrap()
+            }
+        };
+        while rows.len() <= coord.y {
+            rows.push(Value::String(String::new()));
+        }
+        let row = rows[coord.y].as_str().unwrap_or("").to_string();
+        let (cells, comment) = match row.find(' ') {
+            Some(index) => row.split_at(index),
+            None => (row.as_str(), ""),
+        };
+        let mut cells: Vec<char> = cells.chars().collect();
+        while cells.len() <= coord.x {
+            cells.push('-');
+        }
+        cells[coord.x] = ch;
+        let cells: String = cells.into_iter().collect();
+        rows[coord.y] = Value::String(format!("{}{}", cells, comment));
+    }
+
+    /// Write the level, keeping the old one as a backup. The new level is written next
+    /// to the old one first, so a failed write never leaves half a level behind.
+    fn write(&mut self) -> Result<String, String> {
+        // Levels are written by hand, so only what was edited is written anew.
+        let original = fs::read_to_string(&self.path)
+            .map_err(|err| format!("Unable to read {}: {}", self.path.display(), err))?;
+        let yml = yml::rewrite(&original, &self.yml)?;
+        let backup = self.path.with_extension("yml.bak");
+        let temporary = self.path.with_extension("yml.tmp");
+        let written = fs::copy(&self.path, &backup)
+            .and_then(|_| fs::write(&temporary, yml))
+            .and_then(|()| fs::rename(&temporary, &self.path));
+        if let Err(err) = written {
+            return Err(format!("Unable to write {}: {}", self.path.display(), err));
+        }
+        self.changed = false;
+        let mut message = format!(
+            "Saved {}, the old level is in {}.",
+            self.path.display(),
+            backup.display()
+        );
+        let problems = validate(&self.level()?, &self.item_db).len();
+        if problems > 0 {
+            message.push_str(&format!(
+                " The level has {} problems, type \"check\" to see them.",
+                problems
+            ));
+        }
+        Ok(message)
+    }
+}
+
+fn parse_direction(word: &str) -> Option<Direction> {
+    match word {
+        "north" | "n" => Some(Direction::North),
+        "east" | "e" => Some(Direction::East),
+        "south" | "s" => Some(Direction::South),
+        "west" | "w" => Some(Direction::West),
+        _ => None,
+    }
+}
+
+fn parse_coord(x: &str, y: &str, z: &str) -> Result<Coord, String> {
+    let number = |text: &str| {
+        text.trim_matches(|ch| ch == '[' || ch == ']' || ch == ',')
+            .parse()
+            .map_err(|_| format!("Expected a number for the coord, not {:?}.", text))
+    };
+    Ok(Coord {
+        x: number(x)?,
+        y: number(y)?,
+        z: number(z)?,
+    })
+}
+
+/// The coord in a direction, which can't go off the top or left of the map.
+fn step(coord: Coord, direction: Direction) -> Result<Coord, String> {
+    match direction {
+        Direction::North if coord.y == 0 => Err("The map starts at row 0.".into()),
+        Direction::West if coord.x == 0 => Err("The map starts at column 0.".into()),
+        _ => Ok(coord.apply(&direction)),
+    }
+}
+
+/// A coord as it's written in levels, like `[1, 2, 0]`.
+fn coord_value(coord: Coord) -> Value {
+    Value::Sequence(vec![
+        Value::Number(coord.x.into()),
+        Value::Number(coord.y.into()),
+        Value::Number(coord.z.into()),
+    ])
+}
+
+/// A list in a mapping, which is added if it's missing.
+fn list_mut<'a>(mapping: &'a mut Mapping, key: &str) -> &'a mut Vec<Value> {
+    let list = mapping
+        .entry(key.into())
+        .or_insert_with(|| Value::Sequence(Vec::new()));
+    if !list.is_sequence() {
+        *list = Value::Sequence(Vec::new());
+    }
+    list.as_sequence_mut().unwrap()
+}
+
+fn rooms_mut(yml: &mut Value) -> &mut Vec<Value> {
+    list_mut(yml.as_mapping_mut().unwrap(), "rooms")
+}
+
+/// Open a level and edit it with commands read from the input, one per line.
+pub fn edit_file(
+    path: Option<&str>,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<(), String> {
+    let mut editor = LevelEditor::open(Path::new(path.unwrap_or(LEVEL_PATH)))?;
+    let print = |output: &mut dyn Write, text: &str| writeln!(output, "{}\n", text).unwrap();
+    print(output, &editor.show()?);
+    print(output, "Type \"help\" for the commands.");
+    let mut confirmed_quit = false;
+    loop {
+        write!(output, "edit> ").unwrap();
+        output.flush().unwrap();
+        let mut line = String::new();
+        if input.read_line(&mut line).unwrap_or(0) == 0 {
+            // The end of piped input is the same as quitting.
+            line = "quit".into();
+        }
+        match line.trim() {
+            "" => continue,
+            "quit" | "q" => {
+                if editor.changed && !confirmed_quit {
+                    confirmed_quit = true;
+                    print(
+                        output,
+                        "There are changes that haven't been written. Type \"write\" to save \
+                         them, or \"quit\" again to leave without them.",
+                    );
+                    continue;
+                }
+                return Ok(());
+            }
+            line => match editor.run(line) {
+                Ok(message) => print(output, &message),
+                Err(message) => print(output, &message),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_level_editor() {
+        let path = std::env::temp_dir().join("text-adventure-test-level-edit.yml");
+        let level = "\
+maps:
+  - - \"---  # Row 0\"
+    - \"-.-  # Row 1\"
+    - \"---\"
+entry: [1, 1, 0]
+regions: {}
+npcs:
+  fisher: { name: Fisher, targets: [fisher], talk: Fish?, items: [] }
+rooms:
+  - { title: Pier, coord: [1, 1, 0], id: pier, description: A pier. }
+";
+        fs::write(&path, level).unwrap();
+
+        let mut editor = LevelEditor::open(&path).unwrap();
+        assert_eq!(
+            editor.run("dig north Shed"),
+            Ok("Made the room \"Shed\" at [1, 0, 0].".into())
+        );
+        assert_eq!(editor.run("north"), Err("The map starts at row 0.".into()));
+        assert_eq!(
+            editor.run("npc cat"),
+            Err("The level has no NPC \"cat\".".into())
+        );
+        assert!(editor.run("npc fisher").is_ok());
+        assert!(editor.run("describe A leaning shed.").is_ok());
+        assert_eq!(
+            editor.run("id pier"),
+            Err("The room \"Pier\" already has the id \"pier\".".into())
+        );
+        assert!(editor.run("goto 3 1 0").is_ok());
+        assert!(editor.run("room Boat").is_ok());
+        assert!(editor.run("item apple 2").is_ok());
+        assert!(editor.run("link west pier").is_ok());
+        insta::assert_snapshot!(editor.run("show").unwrap(), @r###"
+        ---
+        Cursor: [3, 1, 0]
+        Room:   Boat (no id)
+                (no description)
+          ‣ item apple (2)
+          ‣ exit west to pier
+
+          -.-
+          -.-@
+          ---
+        "###);
+        assert!(editor.run("write").unwrap().starts_with("Saved "));
+
+        let saved = fs::read_to_string(&path).unwrap();
+        insta::assert_snapshot!(saved, @r###"
+        ---
+        maps:
+          - - "-.-  # Row 0"
+            - "-.-.  # Row 1"
+            - "---"
+        entry: [1, 1, 0]
+        regions: {}
+        npcs:
+          fisher: { name: Fisher, targets: [fisher], talk: Fish?, items: [] }
+        rooms:
+          - { title: Pier, coord: [1, 1, 0], id: pier, description: A pier. }
+          - { title: Shed, coord: [1, 0, 0], description: A leaning shed., npcs: [fisher] }
+          - title: Boat
+            coord: [3, 1, 0]
+            description: ""
+            items:
+              - id: apple
+                quantity: 2
+            exits:
+              west:
+                entry: pier
+        "###);
+        let saved: Level = serde_yaml::from_str(&saved).unwrap();
+        assert_eq!(saved.rooms.iter().count(), 3);
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(path.with_extension("yml.bak")).unwrap();
+    }
+}
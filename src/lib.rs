@@ -0,0 +1,10645 @@
+mod audio;
+mod campaign;
+mod condition;
+mod config;
+mod currency;
+mod events;
+mod grammar;
+pub mod graph;
+mod handler;
+pub mod leaderboard;
+mod level;
+pub mod level_edit;
+pub mod locale;
+pub mod map;
+mod markup;
+mod narrator;
+pub mod pack;
+mod passage;
+mod print;
+pub mod save_edit;
+#[cfg(feature = "native")]
+pub mod server;
+pub mod stats;
+mod style;
+mod suggest;
+mod template;
+#[cfg(feature = "native")]
+mod terminal;
+#[cfg(test)]
+mod testing;
+mod text_filter;
+pub mod transcript;
+#[cfg(feature = "native")]
+mod tui;
+mod tutorial;
+mod utils;
+pub mod validate;
+mod vocabulary;
+pub mod walkthrough;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "web")]
+pub mod web;
+pub mod yml;
+
+pub use audio::Cue;
+pub use config::{Config, TextSpeed, CONFIG_PATH};
+pub use handler::CommandHandler;
+pub use level::ItemDatabase;
+pub use style::Theme;
+#[cfg(feature = "native")]
+pub use terminal::Terminal;
+#[cfg(feature = "native")]
+pub use tui::Tui;
+
+use crate::utils::{
+    format_elapsed, hash_str, parse_yml, parse_yml_str, scramble_words, try_parse_yml_str, wrap,
+};
+use campaign::{Campaign, Date, Today, CAMPAIGN_PATH};
+use condition::Condition;
+use events::GameEvent;
+use grammar::{capitalize, Noun};
+use leaderboard::GameResult;
+use level::{
+    Action, Award, Check, Coord, Destination, Direction, Effect, Emote, Faction, Hazard,
+    InventoryItem, ItemVariant, Level, Lighting, Lore, Movement, Puzzle, Quest, Recipe, Room,
+    RoomItem, Safeguard, Scenario, Shift, Split, Start, Terrain, Throw, Tile, Topic, Vehicle, Verb,
+    Weather, NPC,
+};
+use map::{search, RoomGraph, RoomMapInfo, DIRECTIONS};
+use narrator::Narrator;
+use pack::Pack;
+use passage::Passage;
+use print::{
+    print_ambient, print_box, print_comparison, print_dialogue, print_exit_destinations,
+    print_failure, print_handler_help, print_problems, print_region_map, print_room_description,
+    print_room_look, print_score, print_summary, print_text_file,
+};
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_pcg::Pcg32;
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    fs,
+    io::Write,
+    iter::Peekable,
+    path::{Path, PathBuf},
+    rc::Rc,
+    str::SplitWhitespace,
+    time::{Duration, Instant, SystemTime},
+};
+use style::{Color, Style};
+use suggest::{suggest_target, suggest_verb};
+use template::Variables;
+use text_filter::{FilterChain, Filtered, TextFilter};
+use vocabulary::Vocabulary;
+use walkthrough::open_steps;
+
+pub trait Environment: Write {
+    fn get_prompt(&mut self) -> String;
+
+    /// The location of the save file, or None if the game should not be persisted.
+    fn save_path(&self) -> Option<PathBuf> {
+        Some(PathBuf::from(SAVE_PATH))
+    }
+
+    /// The theme used to style the output, or None for plain text.
+    fn theme(&self) -> Option<&Theme> {
+        None
+    }
+
+    /// The seed for a new game, or None to pick one at random.
+    fn seed(&self) -> Option<u64> {
+        None
+    }
+
+    /// Read one of the game's data files, like the level or the help text.
+    fn read_text(&self, path: &str) -> String {
+        match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => panic!("Could not load {:?}", path),
+        }
+    }
+
+    /// Read one of the game's data files that might not be there, like a level's
+    /// translation.
+    fn try_read_text(&self, path: &str) -> Option<String> {
+        fs::read_to_string(path).ok()
+    }
+
+    /// The names of the data files in a directory, like the language catalogs.
+    fn list_files(&self, dir: &str) -> Vec<String> {
+        fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    /// Called before every prompt with the latest game status, for environments
+    /// that display it outside of the text output.
+    fn set_status(&mut self, _status: Status) {}
+
+    /// Whether to reload the level whenever its files change, for authors working
+    /// on it.
+    fn watch(&self) -> bool {
+        false
+    }
+
+    /// When the author started a jam session, or None when not in jam mode. Jam mode
+    /// skips the intro, turns on debug mode, validates the level whenever it's
+    /// reloaded, and shows the time spent before each prompt.
+    fn jam_started(&self) -> Option<Instant> {
+        None
+    }
+
+    /// The id of the level's scenario to begin a new game with, or None to let the
+    /// player choose.
+    fn scenario(&self) -> Option<&str> {
+        None
+    }
+
+    /// The file that the "feedback" command adds to, or None if feedback can't be
+    /// left.
+    fn feedback_path(&self) -> Option<PathBuf> {
+        Some(PathBuf::from("data/feedback.yml"))
+    }
+
+    /// Where unlocked achievements are kept, apart from the save so that they last
+    /// through restarts. None only remembers them for this game.
+    fn achievements_path(&self) -> Option<PathBuf> {
+        Some(PathBuf::from("data/achievements.yml"))
+    }
+
+    /// The results file that finished games are added to, see `GameResult`, or None to
+    /// not keep them.
+    fn results_path(&self) -> Option<PathBuf> {
+        Some(PathBuf::from(leaderboard::RESULTS_PATH))
+    }
+
+    /// Whether to time a new game through the level's race checkpoints, see `Split`.
+    fn race(&self) -> bool {
+        false
+    }
+
+    /// Whether to play the tutorial that's built into the engine, see `Lesson`, in place
+    /// of the campaign. Environments should keep the tutorial out of the save file.
+    fn tutorial(&self) -> bool {
+        false
+    }
+
+    /// Where the best times through each level's race are kept, or None to only
+    /// compare against the races of this game.
+    fn races_path(&self) -> Option<PathBuf> {
+        Some(PathBuf::from("data/races.yml"))
+    }
+
+    /// Where the calendar is kept between games for New Game Plus, apart from the
+    /// save, which is deleted on restart. None starts every game on the first day.
+    fn new_game_plus_path(&self) -> Option<PathBuf> {
+        Some(PathBuf::from("data/new-game-plus.yml"))
+    }
+
+    /// The real date, for content that's only shown on holidays, or None when there's
+    /// no clock to read it from.
+    fn today(&self) -> Option<Today> {
+        Some(Today::now())
+    }
+
+    /// The player's preferences, like how wide the output is.
+    fn config(&self) -> Config {
+        Config::default()
+    }
+
+    /// Where the "config" command saves the player's preferences, or None to only
+    /// change them for this game.
+    fn config_path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// Play a sound cue from the level, see `Cue`. Environments that can't play sound
+    /// leave this as it is, and the game goes on quietly.
+    fn play(&mut self, _cue: &Cue) {}
+
+    /// Called when the player changes their preferences, for environments that show
+    /// the prompt or theme themselves. Returning an error keeps the old preferences.
+    fn set_config(&mut self, _config: &Config) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// The language to play in, from the catalogs in data/lang, or None for the
+    /// level's own language.
+    fn lang(&self) -> Option<&str> {
+        None
+    }
+
+    /// The paths of the content packs to patch the levels and items with, in the
+    /// order they are patched, see `Pack`.
+    fn packs(&self) -> &[String] {
+        &[]
+    }
+
+    /// How many columns of text fit in the output right now, or None to wrap to the
+    /// width in the player's config. This is checked every time text is wrapped, so
+    /// resizing the terminal rewraps the output.
+    fn width(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// The options from the command line that change how a game is played.
+#[derive(Clone, Debug, Default)]
+pub struct GameOptions {
+    pub seed: Option<u64>,
+    pub watch: bool,
+    /// When jam mode started, see `Environment::jam_started`.
+    pub jam_started: Option<Instant>,
+    pub scenario: Option<String>,
+    /// Only print ASCII, in place of box drawing characters and other symbols.
+    pub ascii: bool,
+    /// The player's preferences, from config.toml.
+    pub config: Config,
+    /// The language to play in, see `Environment::lang`.
+    pub lang: Option<String>,
+    /// The content packs to play with, see `Environment::packs`.
+    pub packs: Vec<String>,
+    /// Time the game through the level's race checkpoints, see `Environment::race`.
+    pub race: bool,
+    /// Play the tutorial in place of the campaign, see `Environment::tutorial`.
+    pub tutorial: bool,
+}
+
+/// A summary of the game, shown in the status bar and sidebar of the TUI.
+pub struct Status {
+    pub room: String,
+    pub exits: String,
+    /// The directions that can be moved in, like "north".
+    pub directions: Vec<&'static str>,
+    /// The names of the items that can be taken from the room.
+    pub items: Vec<String>,
+    /// How much money the player has, like "2 gold, 5 silver".
+    pub money: String,
+    pub turn: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum ParsedCommand {
+    Look(Option<String>),
+    Talk(Option<String>),
+    Message(String),
+    Narrate(&'static str),
+    Inventory(InventoryView),
+    Help(Option<String>),
+    Move(Direction),
+    Drop(String),
+    Take(String),
+    /// Give items to an NPC, like "give all gems to jeweler".
+    Give(String, String),
+    /// Throw an item at an NPC or something in the room, like "throw rock at window".
+    Throw(String, String),
+    /// Pet an animal in the room, see `Pet`.
+    Pet(String),
+    /// Feed an item to an animal, which tames it, like "feed bone to dog".
+    Feed(String, String),
+    /// Send a tame animal to fetch an item, like "send dog to fetch stick".
+    Fetch(String, String),
+    /// Eat or drink an item.
+    Consume(String),
+    /// Make something out of the items, like "combine rope with stick".
+    Combine(Vec<String>),
+    /// Make an item by name from a recipe, like "craft fishing rod".
+    Craft(String),
+    /// Buy an item from an NPC in the room, or the given one.
+    Buy(String, Option<String>),
+    /// Haggle over an item an NPC in the room sells, like "offer 5 for apple".
+    Offer(String, String),
+    /// Count the player's money.
+    Money,
+    /// Leave items in the player's stash, at a bank.
+    Deposit(String),
+    /// Take items back out of the stash, at a bank.
+    Withdraw(String),
+    /// List what's in the stash, at a bank.
+    Stash,
+    /// Read something carried, like a book, or use a room's "read" action.
+    Read(String),
+    /// Look up an entry of the lore, or list them all.
+    Lore(Option<String>),
+    /// List the player's quests.
+    Journal,
+    /// Look for hidden things, in the room or in something, like "search desk".
+    Search(Option<String>),
+    /// Push, pull, or move something, like "push boulder".
+    Shift(Shift, String),
+    /// Get rid of a hazard in the room for good.
+    Disarm(String),
+    /// Get aboard a vehicle in the room, like "board boat" or "mount horse".
+    Board(String),
+    /// Get off the vehicle the player is aboard.
+    Dismount,
+    /// Say something out loud, like the answer to a puzzle.
+    Say(String),
+    /// Ask an NPC about a topic, or the NPC in the conversation when None.
+    Ask(Option<String>, String),
+    /// Ask a companion for items they are carrying, like "ask dog for bone".
+    AskFor(Option<String>, String),
+    /// End the conversation.
+    Bye,
+    Compare(String, String),
+    Quit,
+    Debug,
+    /// Draw the map with the rooms lettered by region, while debugging.
+    Map,
+    /// Jump to a room by its id, an entry, or its coordinate, while debugging.
+    Goto(String),
+    /// List the exits, and where they lead.
+    Exits,
+    /// Say which room the player is in, its regions, and where its exits lead.
+    Where,
+    /// Walk to a room the player has been to by its title or id, like "go to market
+    /// square". The walk is taken a step at a time, see `Game::route`.
+    Travel(String),
+    Restart,
+    Reload,
+    Feedback(String),
+    Score,
+    Undo,
+    /// Show the player's preferences, or change one of them.
+    Config(Option<(String, String)>),
+    /// Turn the sound and music off, or back on.
+    Mute,
+    Filter(Option<String>),
+    Emote(Emote, Option<String>),
+    Custom(String, Option<String>),
+    /// Run the last command again with another target, after it couldn't be found,
+    /// like "oops lantern".
+    Oops(String),
+}
+
+/// Which part of the inventory to show.
+#[derive(Clone, Debug, PartialEq)]
+enum InventoryView {
+    /// A page of the inventory, starting at 1.
+    Page(usize),
+    /// The items with a name, target, or kind matching a word.
+    Find(String),
+}
+
+/// How many items are shown on each page of the inventory.
+const INVENTORY_PAGE_SIZE: usize = 10;
+
+#[derive(Default, Serialize, Deserialize)]
+struct Inventory {
+    pub items: Vec<InventoryItem>,
+}
+
+impl From<Vec<InventoryItem>> for Inventory {
+    fn from(items: Vec<InventoryItem>) -> Inventory {
+        Inventory { items }
+    }
+}
+
+impl Inventory {
+    fn starting(start: &Start, item_db: &ItemDatabase) -> Inventory {
+        Inventory::from(
+            start
+                .items
+                .iter()
+                .map(|start_item| {
+                    let mut item = item_db.get(&start_item.id).clone();
+                    if let Some(quantity) = start_item.quantity {
+                        item.quantity = quantity;
+                    }
+                    item
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    fn add_item(&mut self, new_item: InventoryItem) {
+        match self.items.iter_mut().find(|item| item.id == new_item.id) {
+            Some(item) => item.quantity += new_item.quantity,
+            None => self.items.push(new_item),
+        }
+    }
+
+    /// Use up one of an item, like an apple that was eaten.
+    fn use_one(&mut self, id: &str) {
+        self.use_some(id, 1);
+    }
+
+    /// Use up some of an item, like the ingredients of a recipe.
+    fn use_some(&mut self, id: &str, count: usize) {
+        if let Some(item) = self.items.iter_mut().find(|item| item.id == id) {
+            item.quantity = item.quantity.saturating_sub(count);
+        }
+        self.items.retain(|item| item.id != id || item.quantity > 0);
+    }
+
+    /// Hand over the coins of a payment, and take the change.
+    fn pay(&mut self, payment: &currency::Payment) {
+        for (item, count) in payment.paid.iter() {
+            self.use_some(&item.id, *count);
+        }
+        for (item, count) in payment.change.iter() {
+            self.add_item(InventoryItem {
+                quantity: *count,
+                ..(*item).clone()
+            });
+        }
+    }
+
+    /// How many of an item the player has. Items without a quantity count as one.
+    fn count(&self, id: &str) -> usize {
+        self.items
+            .iter()
+            .find(|item| item.id == id)
+            .map_or(0, |item| item.quantity.max(1))
+    }
+}
+
+enum DropResult {
+    Item(Box<InventoryItem>),
+    Sticky,
+    None,
+}
+
+impl Inventory {
+    pub fn find_item(&self, name: &str, vocabulary: &Vocabulary) -> Option<&InventoryItem> {
+        self.items
+            .iter()
+            .find(|item| item_matches(item, name, vocabulary))
+    }
+
+    pub fn drop_item(&mut self, name: &str, vocabulary: &Vocabulary) -> DropResult {
+        // Find the item if it exists.
+        let tuple = self
+            .items
+            .iter()
+            .enumerate()
+            .find(|(_, item)| item_matches(item, name, vocabulary));
+
+        match tuple {
+            Some((index, item)) => {
+                if item.sticky {
+                    return DropResult::Sticky;
+                }
+
+                let removed_item = item.clone();
+
+                // Remove the item.
+                self.items = self
+                    .items
+                    .drain(..)
+                    .enumerate()
+                    .filter(|(i, _)| *i != index)
+                    .map(|(_, item)| item)
+                    .collect();
+
+                DropResult::Item(Box::new(removed_item))
+            }
+            None => DropResult::None,
+        }
+    }
+}
+
+fn item_matches(item: &InventoryItem, name: &str, vocabulary: &Vocabulary) -> bool {
+    item.name.to_lowercase() == name || vocabulary.matches(item.targets.iter(), name)
+}
+
+/// The items a target refers to, which is either a set like "all fish", or one item.
+fn select_items(
+    items: &[InventoryItem],
+    target: &str,
+    vocabulary: &Vocabulary,
+) -> Vec<InventoryItem> {
+    match ItemSet::parse(target) {
+        Some(set) => items
+            .iter()
+            .filter(|item| set.contains(item, vocabulary))
+            .cloned()
+            .collect(),
+        None => items
+            .iter()
+            .find(|item| item_matches(item, target, vocabulary))
+            .cloned()
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// Many items at once, written like "all", "all fish", or "all except sword, shield".
+#[derive(Debug, PartialEq)]
+struct ItemSet {
+    /// Only the items matching this word, by name, target, or kind.
+    kind: Option<String>,
+    /// Leave out the items matching any of these words.
+    except: Vec<String>,
+}
+
+impl ItemSet {
+    /// Read a target as a set of items, or None when it is a single item.
+    fn parse(target: &str) -> Option<ItemSet> {
+        let (first, rest) = target.split_once(' ').unwrap_or((target, ""));
+        if first != "all" && first != "everything" {
+            return None;
+        }
+        let (kind, except) = match rest.strip_prefix("except ") {
+            Some(except) => ("", except),
+            None => rest.split_once(" except ").unwrap_or((rest, "")),
+        };
+        let kind = kind.trim_start_matches("of ").trim_start_matches("the ");
+        Some(ItemSet {
+            kind: (!kind.is_empty()).then(|| kind.to_string()),
+            except: except
+                .split(',')
+                .map(str::trim)
+                .filter(|word| !word.is_empty())
+                .map(String::from)
+                .collect(),
+        })
+    }
+
+    fn contains(&self, item: &InventoryItem, vocabulary: &Vocabulary) -> bool {
+        // "gems" matches an item with the target "gem".
+        let matches = |word: &str| {
+            let word = vocabulary.noun(word);
+            let single = word.strip_suffix('s').unwrap_or(word);
+            [word, single].iter().any(|word| {
+                item_matches(item, word, vocabulary) || item.variant.lowercase_str() == *word
+            })
+        };
+        self.kind.as_deref().is_none_or(matches) && !self.except.iter().any(|word| matches(word))
+    }
+}
+
+/// Parse the two items in "compare sword with dagger".
+fn parse_compare(
+    command: &str,
+    words: &mut Peekable<SplitWhitespace>,
+    vocabulary: &Vocabulary,
+    narrator: &Narrator,
+) -> Result<ParsedCommand, String> {
+    let mut first = Vec::new();
+    for word in words.by_ref() {
+        match word {
+            "with" | "to" | "against" => break,
+            _ => first.push(word),
+        }
+    }
+    if first.is_empty() {
+        return Err(narrator.say_with("command-what", &[("command", command)]));
+    }
+    let first = first.join(" ");
+    match parse_command_target(command, words, vocabulary, narrator)? {
+        Some(second) => Ok(ParsedCommand::Compare(
+            vocabulary.noun(&first).to_string(),
+            second,
+        )),
+        None => Err(narrator.say_with(
+            "command-what",
+            &[("command", &format!("{} {} with", command, first))],
+        )),
+    }
+}
+
+fn parse_command_target(
+    command: &str,
+    words: &mut Peekable<SplitWhitespace>,
+    vocabulary: &Vocabulary,
+    narrator: &Narrator,
+) -> Result<Option<String>, String> {
+    let word = match words.next() {
+        Some(word) => word,
+        None => return Ok(None),
+    };
+
+    let mut target = match word {
+        "at" | "to" | "in" | "up" => {
+            if words.peek().is_none() {
+                let command = format!("{} {}", command, word);
+                return Err(narrator.say_with("command-what", &[("command", &command)]));
+            }
+            Vec::new()
+        }
+        _ => vec![word],
+    };
+    target.extend(words);
+
+    Ok(Some(vocabulary.noun(&target.join(" ")).to_string()))
+}
+
+impl ParsedCommand {
+    /// Meta commands like help or quit don't advance the game clock.
+    fn advances_turn(&self) -> bool {
+        !matches!(
+            self,
+            ParsedCommand::Message(_)
+                | ParsedCommand::Narrate(_)
+                | ParsedCommand::Inventory(_)
+                | ParsedCommand::Compare(_, _)
+                | ParsedCommand::Help(_)
+                | ParsedCommand::Quit
+                | ParsedCommand::Debug
+                | ParsedCommand::Map
+                | ParsedCommand::Goto(_)
+                | ParsedCommand::Travel(_)
+                | ParsedCommand::Exits
+                | ParsedCommand::Where
+                | ParsedCommand::Restart
+                | ParsedCommand::Reload
+                | ParsedCommand::Feedback(_)
+                | ParsedCommand::Score
+                | ParsedCommand::Undo
+                | ParsedCommand::Config(_)
+                | ParsedCommand::Mute
+                | ParsedCommand::Filter(_)
+                | ParsedCommand::Bye
+                | ParsedCommand::Money
+                | ParsedCommand::Stash
+                | ParsedCommand::Lore(_)
+                | ParsedCommand::Journal
+        )
+    }
+
+    /// What the command is done to, which "oops" corrects. Commands with two targets
+    /// have the first one corrected, like the item in "give apple to farmer".
+    fn target_mut(&mut self) -> Option<&mut String> {
+        match self {
+            ParsedCommand::Look(Some(target))
+            | ParsedCommand::Talk(Some(target))
+            | ParsedCommand::Help(Some(target))
+            | ParsedCommand::Drop(target)
+            | ParsedCommand::Take(target)
+            | ParsedCommand::Give(target, _)
+            | ParsedCommand::Throw(target, _)
+            | ParsedCommand::Pet(target)
+            | ParsedCommand::Feed(target, _)
+            | ParsedCommand::Fetch(_, target)
+            | ParsedCommand::Consume(target)
+            | ParsedCommand::Craft(target)
+            | ParsedCommand::Buy(target, _)
+            | ParsedCommand::Offer(_, target)
+            | ParsedCommand::Deposit(target)
+            | ParsedCommand::Withdraw(target)
+            | ParsedCommand::Read(target)
+            | ParsedCommand::Lore(Some(target))
+            | ParsedCommand::Search(Some(target))
+            | ParsedCommand::Shift(_, target)
+            | ParsedCommand::Disarm(target)
+            | ParsedCommand::Board(target)
+            | ParsedCommand::Ask(_, target)
+            | ParsedCommand::AskFor(_, target)
+            | ParsedCommand::Compare(target, _)
+            | ParsedCommand::Emote(_, Some(target))
+            | ParsedCommand::Travel(target)
+            | ParsedCommand::Custom(_, Some(target)) => Some(target),
+            _ => None,
+        }
+    }
+
+    /// Whether the command can be done more than once in a row, with "again" or a
+    /// count like "3 north".
+    fn repeats(&self) -> bool {
+        !matches!(
+            self,
+            ParsedCommand::Quit
+                | ParsedCommand::Restart
+                | ParsedCommand::Reload
+                | ParsedCommand::Feedback(_)
+                | ParsedCommand::Config(_)
+                | ParsedCommand::Debug
+        )
+    }
+}
+
+/// The verbs that take free text, like the answer to a riddle, which can have "and" and
+/// periods in it. Only "then" ends their commands.
+const FREE_TEXT_VERBS: [&str; 2] = ["say", "answer"];
+
+/// Split an input like "take sword then go north and look" into the individual
+/// commands "take sword", "go north", and "look".
+fn split_compound_command(input: &str) -> Vec<String> {
+    let mut commands = Vec::new();
+    let mut command = String::new();
+    for word in input.split_whitespace() {
+        let is_free_text = command
+            .split_whitespace()
+            .next()
+            .is_some_and(|verb| FREE_TEXT_VERBS.contains(&verb));
+        // Periods end commands too, like "look. inventory".
+        let mut pieces = Vec::new();
+        if is_free_text {
+            pieces.push(word);
+        } else {
+            for (index, piece) in word.split('.').enumerate() {
+                if index > 0 {
+                    pieces.push(".");
+                }
+                if !piece.is_empty() {
+                    pieces.push(piece);
+                }
+            }
+        }
+        for piece in pieces {
+            match piece {
+                "then" => {}
+                "and" | "." if !is_free_text => {}
+                _ => {
+                    if !command.is_empty() {
+                        command.push(' ');
+                    }
+                    command.push_str(piece);
+                    continue;
+                }
+            }
+            if !command.is_empty() {
+                commands.push(std::mem::take(&mut command));
+            }
+        }
+    }
+    if !command.is_empty() {
+        commands.push(command);
+    }
+    commands
+}
+
+/// The most characters a single piece of feedback can have.
+const MAX_FEEDBACK_LENGTH: usize = 1000;
+
+fn parse_command(
+    input: String,
+    vocabulary: &Vocabulary,
+    narrator: &Narrator,
+) -> Result<Vec<ParsedCommand>, String> {
+    // Feedback is free text, so it's kept whole rather than split into commands.
+    let mut words = input.trim_start().splitn(2, char::is_whitespace);
+    if words.next() == Some("feedback") {
+        let text = words.next().unwrap_or("").trim();
+        if text.is_empty() {
+            return Ok(vec![ParsedCommand::Message(narrator.say("feedback-empty"))]);
+        }
+        if text.chars().count() > MAX_FEEDBACK_LENGTH {
+            return Ok(vec![ParsedCommand::Message(narrator.say_with(
+                "feedback-too-long",
+                &[("max", &MAX_FEEDBACK_LENGTH.to_string())],
+            ))]);
+        }
+        return Ok(vec![ParsedCommand::Feedback(text.to_string())]);
+    }
+    // Settings like the prompt can contain anything, so they are kept whole too.
+    let mut words = input.trim().splitn(2, char::is_whitespace);
+    if words.next() == Some("config") {
+        let setting = match words.next() {
+            Some(setting) => setting,
+            None => return Ok(vec![ParsedCommand::Config(None)]),
+        };
+        let (key, value) = match setting.split_once(|ch: char| ch.is_whitespace() || ch == '=') {
+            Some((key, value)) => (key, value.trim_start().trim_start_matches('=')),
+            None => (setting, ""),
+        };
+        if value.trim().is_empty() {
+            return Ok(vec![ParsedCommand::Message(
+                narrator.say_with("config-empty", &[("key", key)]),
+            )]);
+        }
+        return Ok(vec![ParsedCommand::Config(Some((
+            key.to_string(),
+            value.to_string(),
+        )))]);
+    }
+
+    let commands = split_compound_command(&input);
+    if commands.is_empty() {
+        // No input was given.
+        return Ok(vec![ParsedCommand::Look(None)]);
+    }
+    let mut parsed = Vec::new();
+    for command in commands {
+        let (count, command) = split_repeat(&command);
+        if count > MAX_REPEAT {
+            return Err(narrator.say_with("repeat-too-many", &[("count", &MAX_REPEAT.to_string())]));
+        }
+        for _ in 0..count {
+            let parsed_command = parse_single_command(command.clone(), vocabulary, narrator)?;
+            if count > 1 && !parsed_command.repeats() {
+                return Err(narrator.say_with("repeat-unsafe", &[("command", &command)]));
+            }
+            parsed.push(parsed_command);
+        }
+    }
+    Ok(parsed)
+}
+
+/// The most times a single command can be repeated, like "20 north".
+const MAX_REPEAT: usize = 20;
+
+/// Split the number of times to do a command from the rest of it, like "3 north" or
+/// "take coin x5". Commands without a number are done once.
+fn split_repeat(command: &str) -> (usize, String) {
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let count = |word: &str| word.parse::<usize>().ok().filter(|count| *count > 0);
+    if let [first, rest @ ..] = words.as_slice() {
+        if let (Some(count), false) = (count(first), rest.is_empty()) {
+            return (count, rest.join(" "));
+        }
+    }
+    if let [rest @ .., last] = words.as_slice() {
+        if let (Some(count), false) = (last.strip_prefix('x').and_then(count), rest.is_empty()) {
+            return (count, rest.join(" "));
+        }
+    }
+    (1, command.to_string())
+}
+
+fn parse_inventory_view(
+    command: &str,
+    words: &mut Peekable<SplitWhitespace>,
+    vocabulary: &Vocabulary,
+    narrator: &Narrator,
+) -> Result<InventoryView, String> {
+    match words.peek() {
+        None => Ok(InventoryView::Page(1)),
+        Some(&"find") => {
+            words.next();
+            match parse_command_target(command, words, vocabulary, narrator)? {
+                Some(word) => Ok(InventoryView::Find(word)),
+                None => {
+                    Err(narrator
+                        .say_with("command-what", &[("command", &format!("{} find", command))]))
+                }
+            }
+        }
+        Some(word) => match word.parse() {
+            Ok(page) if page > 0 => Ok(InventoryView::Page(page)),
+            _ => Err(narrator.say_with("inventory-usage", &[("command", command)])),
+        },
+    }
+}
+
+fn parse_single_command(
+    input: String,
+    vocabulary: &Vocabulary,
+    narrator: &Narrator,
+) -> Result<ParsedCommand, String> {
+    let mut words = input.split_whitespace().peekable();
+    let command = match words.next() {
+        Some(command) => vocabulary.verb(command),
+        None => {
+            // No input was given.
+            return Ok(ParsedCommand::Look(None));
+        }
+    };
+
+    match command {
+        "look" | "l" => Ok(ParsedCommand::Look(parse_command_target(
+            command, &mut words, vocabulary, narrator,
+        )?)),
+        "talk" | "t" => Ok(ParsedCommand::Talk(parse_command_target(
+            command, &mut words, vocabulary, narrator,
+        )?)),
+        "north" | "n" => Ok(ParsedCommand::Move(Direction::North)),
+        "east" | "e" => Ok(ParsedCommand::Move(Direction::East)),
+        "south" | "s" => Ok(ParsedCommand::Move(Direction::South)),
+        "west" | "w" => Ok(ParsedCommand::Move(Direction::West)),
+        "inventory" | "inv" | "i" | "items" => Ok(ParsedCommand::Inventory(parse_inventory_view(
+            command, &mut words, vocabulary, narrator,
+        )?)),
+        "go" => {
+            let to_room = words.peek() == Some(&"to");
+            match parse_command_target(command, &mut words, vocabulary, narrator)? {
+                Some(ref s) => match s.as_str() {
+                    "north" => Ok(ParsedCommand::Move(Direction::North)),
+                    "east" => Ok(ParsedCommand::Move(Direction::East)),
+                    "south" => Ok(ParsedCommand::Move(Direction::South)),
+                    "west" => Ok(ParsedCommand::Move(Direction::West)),
+                    _ if to_room => Ok(ParsedCommand::Travel(s.clone())),
+                    _ => Err(narrator.say_with("go-unknown", &[("direction", s)])),
+                },
+                None => Ok(ParsedCommand::Narrate("go-nowhere")),
+            }
+        }
+        "" => Ok(ParsedCommand::Message("".into())),
+        "help" | "h" => Ok(ParsedCommand::Help(parse_command_target(
+            command, &mut words, vocabulary, narrator,
+        )?)),
+        "debug" => Ok(ParsedCommand::Debug),
+        "map" => Ok(ParsedCommand::Map),
+        "exits" => Ok(ParsedCommand::Exits),
+        "mute" => Ok(ParsedCommand::Mute),
+        "where" => Ok(ParsedCommand::Where),
+        "goto" => {
+            let target: Vec<_> = words.collect();
+            match target.is_empty() {
+                true => Ok(ParsedCommand::Narrate("goto-nothing")),
+                false => Ok(ParsedCommand::Goto(target.join(" "))),
+            }
+        }
+        "drop" => match parse_command_target(command, &mut words, vocabulary, narrator)? {
+            Some(target) => Ok(ParsedCommand::Drop(target)),
+            None => Ok(ParsedCommand::Narrate("drop-nothing")),
+        },
+        "give" => {
+            let words: Vec<_> = words.collect();
+            let words = words.join(" ");
+            match words.rsplit_once(" to ") {
+                _ if words.is_empty() => Ok(ParsedCommand::Narrate("give-nothing")),
+                Some((items, npc)) if !items.is_empty() && !npc.trim().is_empty() => {
+                    Ok(ParsedCommand::Give(
+                        vocabulary.noun(items).to_string(),
+                        vocabulary.noun(npc.trim()).to_string(),
+                    ))
+                }
+                _ => Ok(ParsedCommand::Narrate("give-whom")),
+            }
+        }
+        "throw" | "toss" | "hurl" => {
+            let words: Vec<_> = words.collect();
+            let words = words.join(" ");
+            match words.rsplit_once(" at ") {
+                _ if words.is_empty() => Ok(ParsedCommand::Narrate("throw-nothing")),
+                Some((item, target)) if !item.is_empty() && !target.trim().is_empty() => {
+                    Ok(ParsedCommand::Throw(
+                        vocabulary.noun(item).to_string(),
+                        vocabulary.noun(target.trim()).to_string(),
+                    ))
+                }
+                _ => Ok(ParsedCommand::Narrate("throw-at")),
+            }
+        }
+        "pet" | "pat" | "stroke" => {
+            match parse_command_target(command, &mut words, vocabulary, narrator)? {
+                Some(target) => Ok(ParsedCommand::Pet(target)),
+                None => Ok(ParsedCommand::Narrate("pet-nothing")),
+            }
+        }
+        "feed" => {
+            let words: Vec<_> = words.collect();
+            let words = words.join(" ");
+            match words.rsplit_once(" to ") {
+                Some((item, npc)) if !item.is_empty() && !npc.trim().is_empty() => {
+                    Ok(ParsedCommand::Feed(
+                        vocabulary.noun(item).to_string(),
+                        vocabulary.noun(npc.trim()).to_string(),
+                    ))
+                }
+                _ => Ok(ParsedCommand::Narrate("feed-nothing")),
+            }
+        }
+        "send" => {
+            let words: Vec<_> = words.collect();
+            let words = words.join(" ");
+            match words.split_once(" to fetch ") {
+                Some((npc, item)) if !npc.is_empty() && !item.trim().is_empty() => {
+                    Ok(ParsedCommand::Fetch(
+                        vocabulary.noun(npc).to_string(),
+                        vocabulary.noun(item.trim()).to_string(),
+                    ))
+                }
+                _ => Ok(ParsedCommand::Narrate("send-nothing")),
+            }
+        }
+        "pick" | "pickup" | "take" | "grab" => {
+            match parse_command_target(command, &mut words, vocabulary, narrator)? {
+                Some(target) => Ok(ParsedCommand::Take(target)),
+                None => match command {
+                    "pick" => Ok(ParsedCommand::Narrate("pick-nothing")),
+                    _ => Ok(ParsedCommand::Narrate("take-nothing")),
+                },
+            }
+        }
+        "buy" | "purchase" => {
+            let words: Vec<_> = words.collect();
+            let words = words.join(" ");
+            let (item, npc) = match words.rsplit_once(" from ") {
+                Some((item, npc)) => (item, Some(vocabulary.noun(npc.trim()).to_string())),
+                None => (words.as_str(), None),
+            };
+            match item.trim() {
+                "" => Ok(ParsedCommand::Narrate("buy-nothing")),
+                item => Ok(ParsedCommand::Buy(vocabulary.noun(item).to_string(), npc)),
+            }
+        }
+        "offer" | "haggle" | "bid" => {
+            let words: Vec<_> = words.collect();
+            let words = words.join(" ");
+            match words.split_once(" for ") {
+                Some((amount, item)) if !amount.trim().is_empty() && !item.trim().is_empty() => {
+                    Ok(ParsedCommand::Offer(
+                        amount.trim().to_string(),
+                        vocabulary.noun(item.trim()).to_string(),
+                    ))
+                }
+                _ => Ok(ParsedCommand::Narrate("offer-nothing")),
+            }
+        }
+        "money" | "purse" | "wallet" => Ok(ParsedCommand::Money),
+        "deposit" | "store" | "stash" => {
+            match parse_command_target(command, &mut words, vocabulary, narrator)? {
+                Some(target) => Ok(ParsedCommand::Deposit(target)),
+                None => match command {
+                    "stash" => Ok(ParsedCommand::Stash),
+                    _ => Ok(ParsedCommand::Narrate("deposit-nothing")),
+                },
+            }
+        }
+        "oops" => match parse_command_target(command, &mut words, vocabulary, narrator)? {
+            Some(target) => Ok(ParsedCommand::Oops(target)),
+            None => Ok(ParsedCommand::Narrate("oops-nothing")),
+        },
+        "read" => match parse_command_target(command, &mut words, vocabulary, narrator)? {
+            Some(target) => Ok(ParsedCommand::Read(target)),
+            None => Ok(ParsedCommand::Narrate("read-nothing")),
+        },
+        "journal" | "quests" => Ok(ParsedCommand::Journal),
+        "push" | "pull" | "move" => {
+            let shift = match command {
+                "push" => Shift::Push,
+                "pull" => Shift::Pull,
+                _ => Shift::Move,
+            };
+            match parse_command_target(command, &mut words, vocabulary, narrator)? {
+                Some(target) => Ok(ParsedCommand::Shift(shift, target)),
+                None => Ok(ParsedCommand::Narrate("shift-nothing")),
+            }
+        }
+        "search" => Ok(ParsedCommand::Search(parse_command_target(
+            command, &mut words, vocabulary, narrator,
+        )?)),
+        "lore" => Ok(ParsedCommand::Lore(parse_command_target(
+            command, &mut words, vocabulary, narrator,
+        )?)),
+        "withdraw" | "retrieve" => {
+            match parse_command_target(command, &mut words, vocabulary, narrator)? {
+                Some(target) => Ok(ParsedCommand::Withdraw(target)),
+                None => Ok(ParsedCommand::Narrate("withdraw-nothing")),
+            }
+        }
+        "combine" | "craft" => {
+            let words: Vec<_> = words.collect();
+            let words = words.join(" ");
+            let targets: Vec<_> = words
+                .split(" with ")
+                .flat_map(|part| part.split(','))
+                .map(str::trim)
+                .filter(|target| !target.is_empty())
+                .map(|target| vocabulary.noun(target).to_string())
+                .collect();
+            match (command, targets.as_slice()) {
+                ("combine", []) => Ok(ParsedCommand::Narrate("combine-nothing")),
+                ("combine", [_]) => Ok(ParsedCommand::Narrate("combine-with")),
+                (_, []) => Ok(ParsedCommand::Narrate("craft-nothing")),
+                (_, [target]) => Ok(ParsedCommand::Craft(target.clone())),
+                _ => Ok(ParsedCommand::Combine(targets)),
+            }
+        }
+        "eat" | "drink" | "consume" => {
+            match parse_command_target(command, &mut words, vocabulary, narrator)? {
+                Some(target) => Ok(ParsedCommand::Consume(target)),
+                None => Ok(ParsedCommand::Narrate("eat-nothing")),
+            }
+        }
+        "ask" => {
+            let words: Vec<_> = words.collect();
+            let words = words.join(" ");
+            if !words.starts_with("about ") && !words.contains(" about ") {
+                let asked_for = match words.strip_prefix("for ") {
+                    Some(items) => Some((None, items)),
+                    None => words
+                        .split_once(" for ")
+                        .map(|(npc, items)| (Some(vocabulary.noun(npc).to_string()), items)),
+                };
+                if let Some((npc, items)) = asked_for {
+                    return Ok(ParsedCommand::AskFor(
+                        npc,
+                        vocabulary.noun(items).to_string(),
+                    ));
+                }
+            }
+            let (npc, topic) = match words.strip_prefix("about ") {
+                Some(topic) => (None, topic),
+                None => match words.split_once(" about ") {
+                    Some((npc, topic)) => (Some(vocabulary.noun(npc).to_string()), topic),
+                    None => (None, words.as_str()),
+                },
+            };
+            match topic.trim() {
+                "" => Ok(ParsedCommand::Narrate("ask-nothing")),
+                topic => Ok(ParsedCommand::Ask(npc, vocabulary.noun(topic).to_string())),
+            }
+        }
+        "bye" | "goodbye" => Ok(ParsedCommand::Bye),
+        "say" | "answer" => {
+            let words: Vec<_> = words.collect();
+            match words.is_empty() {
+                true => Ok(ParsedCommand::Narrate("say-nothing")),
+                false => Ok(ParsedCommand::Say(words.join(" "))),
+            }
+        }
+        "disarm" => match parse_command_target(command, &mut words, vocabulary, narrator)? {
+            Some(target) => Ok(ParsedCommand::Disarm(target)),
+            None => Ok(ParsedCommand::Narrate("disarm-nothing")),
+        },
+        "board" | "mount" => {
+            match parse_command_target(command, &mut words, vocabulary, narrator)? {
+                Some(target) => Ok(ParsedCommand::Board(target)),
+                None => Ok(ParsedCommand::Narrate("board-nothing")),
+            }
+        }
+        "dismount" | "disembark" => Ok(ParsedCommand::Dismount),
+        "compare" => parse_compare(command, &mut words, vocabulary, narrator),
+        "smile" => Ok(ParsedCommand::Emote(
+            Emote::Smile,
+            parse_command_target(command, &mut words, vocabulary, narrator)?,
+        )),
+        "bow" => Ok(ParsedCommand::Emote(
+            Emote::Bow,
+            parse_command_target(command, &mut words, vocabulary, narrator)?,
+        )),
+        "wave" => Ok(ParsedCommand::Emote(
+            Emote::Wave,
+            parse_command_target(command, &mut words, vocabulary, narrator)?,
+        )),
+        "dance" => Ok(ParsedCommand::Emote(
+            Emote::Dance,
+            parse_command_target(command, &mut words, vocabulary, narrator)?,
+        )),
+        "quit" | "q" | "exit" => Ok(ParsedCommand::Quit),
+        "restart" => Ok(ParsedCommand::Restart),
+        "reload" => Ok(ParsedCommand::Reload),
+        "score" => Ok(ParsedCommand::Score),
+        "undo" => Ok(ParsedCommand::Undo),
+        "filter" => Ok(ParsedCommand::Filter(parse_command_target(
+            command, &mut words, vocabulary, narrator,
+        )?)),
+        _ => Ok(ParsedCommand::Custom(
+            command.to_string(),
+            parse_command_target(command, &mut words, vocabulary, narrator)?,
+        )),
+    }
+}
+
+const LEVEL_PATH: &str = "data/levels/stone-end-market.yml";
+/// Where the game is saved, unless the environment saves somewhere else.
+pub const SAVE_PATH: &str = "data/save-state.yml";
+const VOCABULARY_PATH: &str = "data/vocabulary.yml";
+const ITEMS_PATH: &str = "data/items.yml";
+/// How many turns can be undone.
+const UNDO_LIMIT: usize = 20;
+
+/// How many turns an NPC insulted by an offer won't trade with the player.
+const REFUSAL_TURNS: usize = 10;
+
+pub struct Game<T: Environment> {
+    level: Level,
+    room: Rc<Room>,
+    item_db: ItemDatabase,
+    save_state: SaveState,
+    room_graph: RoomGraph,
+    room_info: RoomMapInfo,
+    /// The levels the game was loaded from, or None if it was given a level directly.
+    campaign: Option<Campaign>,
+    /// Whether this is a new game, rather than one loaded from a save.
+    is_new: bool,
+    /// Where the last action is moving the player, see `follow`.
+    teleport: RefCell<Option<Destination>>,
+    /// The award from the last action, see `follow`.
+    award: RefCell<Option<Award>>,
+    /// The epitaph if the last action killed the player, see `follow`.
+    death: RefCell<Option<Passage>>,
+    /// The id of the NPC the player is in a conversation with, see `converse`.
+    conversation: RefCell<Option<String>>,
+    /// The price an NPC came down to for an item, see `offer_command`.
+    haggle: Option<Haggle>,
+    /// The effects of the last action and of the daemons, see `apply_effects`.
+    effects: RefCell<Vec<Effect>>,
+    /// What happened during the turn, in order, see `dispatch_events`.
+    events: RefCell<VecDeque<GameEvent>>,
+    /// The ids of every achievement the player has unlocked, in any game.
+    achievements: BTreeSet<String>,
+    config: Config,
+    /// The real date when the game was loaded, see `Environment::today`.
+    today: Option<Today>,
+    /// The player's language, and the engine's messages in it.
+    lang: Option<(String, HashMap<String, String>)>,
+    /// The content packs that patch every level as it's loaded.
+    packs: Vec<Pack>,
+    /// What the player last typed, which "again" does once more.
+    last_input: Option<String>,
+    /// The last command, if it failed, which "oops" runs again with another target.
+    failed: Option<ParsedCommand>,
+    /// Whether something happened to the player during the turn, like a daemon saying
+    /// something, which stops a walk with "go to".
+    interrupted: bool,
+    /// The music that was last cued, which keeps playing until the player walks into a
+    /// room with other music, see `update_music`.
+    music: Option<String>,
+    /// When the game was loaded, for timing its result, if results are kept.
+    started: Option<Instant>,
+    /// When the race through the level's checkpoints started, if it's being run.
+    race_started: Option<Instant>,
+    /// The seconds into the race that each checkpoint was reached so far.
+    splits: Vec<u64>,
+    /// The splits of the best finished race of each level, by the level's id.
+    races: BTreeMap<String, Vec<u64>>,
+    /// The serialized save state from before each of the last few turns, the newest
+    /// last, so that they can be undone.
+    history: VecDeque<String>,
+    /// Where the player's text filters are in the output, see `output`.
+    filter_chain: RefCell<FilterChain>,
+    /// The commands added by the program embedding the game, see `CommandHandler`.
+    handlers: Vec<Rc<dyn CommandHandler<T>>>,
+    environment: RefCell<T>,
+}
+
+impl<T: Environment> Game<T> {
+    fn new(item_db: ItemDatabase, mut environment: T) -> Result<Game<T>, String> {
+        if environment.tutorial() {
+            return Game::from_level(tutorial::level(), None, item_db, environment, None);
+        }
+        let campaign: Campaign = parse_yml_str(
+            &environment.read_text(CAMPAIGN_PATH),
+            Path::new(CAMPAIGN_PATH),
+        );
+        let mut save_state = Game::load_save(&environment);
+        // Resume in the level the player saved in, or at the start if the campaign no
+        // longer has it.
+        let mut level_gone = None;
+        let level_id = match save_state {
+            Some(ref mut save_state) if !save_state.level.is_empty() => {
+                if campaign.level_path(&save_state.level).is_err() {
+                    let gone = save_state.level.clone();
+                    save_state.switch_level(&campaign.start);
+                    save_state.level_inventories.remove(&gone);
+                    save_state.level_states.remove(&gone);
+                    level_gone = Some(gone);
+                }
+                save_state.level.clone()
+            }
+            _ => campaign.start.clone(),
+        };
+        let path = campaign.level_path(&level_id)?;
+        let packs = environment
+            .packs()
+            .iter()
+            .map(|pack_path| Pack::parse(&environment.read_text(pack_path), pack_path))
+            .collect::<Result<Vec<_>, _>>()?;
+        let item_db = pack::patch_items(&item_db, &packs)?;
+        let level_text = environment.read_text(path);
+        let level_text = pack::patch_level(&level_text, path, &level_id, &packs)?;
+        let level: Level = parse_yml_str(&level_text, Path::new(path));
+        if let (Some(gone), Some(save_state)) = (level_gone, save_state.as_mut()) {
+            save_state.coord = level.entry;
+            let warning = level
+                .narrator
+                .say_with("save-level-gone", &[("level", &gone)]);
+            print_save_warnings(&mut environment, &level.narrator, &[warning]);
+        }
+        let mut game = Game::from_level(level, Some(path), item_db, environment, save_state)?;
+        game.save_state.level = level_id;
+        game.campaign = Some(campaign);
+        game.packs = packs;
+        if game.is_new {
+            game.continue_calendar();
+        }
+        Ok(game)
+    }
+
+    /// Pick the calendar up where the last game left off, if the player restarted a
+    /// campaign with New Game Plus.
+    fn continue_calendar(&mut self) {
+        let path = match self.environment.borrow().new_game_plus_path() {
+            Some(path) if path.exists() => path,
+            _ => return,
+        };
+        let new_game_plus: NewGamePlus = parse_yml(&path);
+        self.save_state.clock = new_game_plus.clock;
+        self.save_state.playthrough = new_game_plus.playthrough;
+        fs::remove_file(path).expect("Unable to remove the New Game Plus file.");
+    }
+
+    /// Keep the calendar for the next game, when the campaign asks for New Game Plus.
+    fn save_calendar(&self) {
+        let new_game_plus = self
+            .campaign
+            .as_ref()
+            .and_then(|campaign| campaign.calendar.as_ref())
+            .is_some_and(|calendar| calendar.new_game_plus);
+        let path = match self.environment.borrow().new_game_plus_path() {
+            Some(path) if new_game_plus => path,
+            _ => return,
+        };
+        let yml = serde_yaml::to_string(&NewGamePlus {
+            clock: self.save_state.clock,
+            playthrough: self.save_state.playthrough + 1,
+        })
+        .expect("Unable to serialize New Game Plus.");
+        fs::write(path, yml).expect("Unable to save New Game Plus.");
+    }
+
+    /// Create a game from a level and items that have already been loaded, for
+    /// environments like the browser that don't have a filesystem.
+    pub fn new_from_strings(
+        level_yaml: &str,
+        items_yaml: &str,
+        environment: T,
+    ) -> Result<Game<T>, String> {
+        let level = serde_yaml::from_str(level_yaml)
+            .map_err(|err| format!("Unable to parse the level: {}", err))?;
+        let item_db = serde_yaml::from_str(items_yaml)
+            .map_err(|err| format!("Unable to parse the items: {}", err))?;
+        let save_state = Game::load_save(&environment);
+        Game::from_level(level, None, item_db, environment, save_state)
+    }
+
+    fn load_save(environment: &T) -> Option<SaveState> {
+        match environment.save_path() {
+            Some(ref path) if path.exists() => Some(parse_yml(path)),
+            _ => None,
+        }
+    }
+
+    /// Get a level that has been read ready to play, or report what's wrong with it.
+    fn from_level(
+        mut level: Level,
+        level_path: Option<&str>,
+        item_db: ItemDatabase,
+        mut environment: T,
+        save_state: Option<SaveState>,
+    ) -> Result<Game<T>, String> {
+        let catalog = environment
+            .lang()
+            .map(|lang| (lang.to_string(), locale::load_catalog(lang, &environment)));
+        let lang = match catalog {
+            Some((lang, Ok(catalog))) => Some((lang, catalog)),
+            // The game can still be played in the level's own language.
+            Some((lang, Err(error))) => {
+                let warning = level
+                    .narrator
+                    .say_with("lang-unavailable", &[("lang", &lang), ("error", &error)]);
+                writeln!(environment, "{}\n", warning).unwrap();
+                None
+            }
+            None => None,
+        };
+        if let Some((ref lang, ref catalog)) = lang {
+            let localized = locale::localize(&mut level, level_path, lang, catalog, &environment);
+            if let Err(error) = localized {
+                let warning = level
+                    .narrator
+                    .say_with("lang-level-unavailable", &[("error", &error)]);
+                writeln!(environment, "{}\n", warning).unwrap();
+            }
+        }
+        level.vocabulary.merge(parse_yml_str(
+            &environment.read_text(VOCABULARY_PATH),
+            Path::new(VOCABULARY_PATH),
+        ));
+        let is_new = save_state.is_none();
+        let mut save_state = save_state.unwrap_or_else(|| {
+            SaveState::initialize(
+                &item_db,
+                &level,
+                environment.seed().unwrap_or_else(rand::random),
+            )
+        });
+        level.resolve_npcs(save_state.seed);
+        let warnings = save_state.reconcile(&level, &item_db);
+        print_save_warnings(&mut environment, &level.narrator, &warnings);
+        let room_graph = RoomGraph::parse(&level)?;
+        let (room, room_info) = match (
+            level.get_room(&save_state.coord),
+            room_graph.get(&save_state.coord),
+        ) {
+            (Some(room), Some(room_info)) => (room.clone(), room_info.clone()),
+            _ => return Err("Unable to find the entry room.".into()),
+        };
+        let achievements = match environment.achievements_path() {
+            Some(ref path) if path.exists() => parse_yml(path),
+            _ => BTreeSet::new(),
+        };
+        let races = match environment.races_path() {
+            Some(ref path) if path.exists() => parse_yml(path),
+            _ => BTreeMap::new(),
+        };
+        let started = environment.results_path().map(|_| Instant::now());
+        let config = environment.config();
+        let today = environment.today();
+
+        Ok(Game {
+            level,
+            room,
+            item_db,
+            save_state,
+            room_graph,
+            room_info,
+            campaign: None,
+            is_new,
+            teleport: RefCell::new(None),
+            award: RefCell::new(None),
+            death: RefCell::new(None),
+            conversation: RefCell::new(None),
+            haggle: None,
+            effects: RefCell::new(Vec::new()),
+            events: RefCell::new(VecDeque::new()),
+            achievements,
+            config,
+            today,
+            lang,
+            packs: Vec::new(),
+            last_input: None,
+            failed: None,
+            interrupted: false,
+            music: None,
+            started,
+            race_started: None,
+            splits: Vec::new(),
+            races,
+            history: VecDeque::new(),
+            filter_chain: RefCell::new(FilterChain::default()),
+            handlers: Vec::new(),
+            environment: RefCell::new(environment),
+        })
+    }
+
+    /// Add a command to the game, see `CommandHandler`. Handlers registered first win
+    /// when they share a verb.
+    pub fn register_command(&mut self, handler: impl CommandHandler<T> + 'static) {
+        self.handlers.push(Rc::new(handler));
+    }
+
+    /// The handler for a verb that the engine doesn't know, if one was registered.
+    fn handler(&self, verb: &str) -> Option<Rc<dyn CommandHandler<T>>> {
+        self.handlers
+            .iter()
+            .find(|handler| handler.verbs().contains(&verb))
+            .map(Rc::clone)
+    }
+
+    /// Show some text to the player, wrapped to the width of the output, for commands
+    /// added by a `CommandHandler`.
+    pub fn print(&self, text: &str) {
+        let text = wrap(text.trim_end(), self.width());
+        writeln!(self.output(), "{}\n", text).unwrap();
+    }
+
+    /// Whether a flag is set, for commands added by a `CommandHandler`.
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.save_state.flags.contains(flag)
+    }
+
+    /// Set a flag once the command is done, for commands added by a `CommandHandler`.
+    /// Like the `set_flag` effect, it runs the level's scripts for the flag.
+    pub fn set_flag(&self, flag: &str) {
+        self.effects
+            .borrow_mut()
+            .push(Effect::SetFlag(flag.to_string()));
+    }
+
+    /// The path to the current level, if it was loaded from a file.
+    fn level_path(&self) -> Option<&str> {
+        self.campaign
+            .as_ref()
+            .and_then(|campaign| campaign.level_path(&self.save_state.level).ok())
+    }
+
+    /// Read a level file, and get it ready to play.
+    fn load_level(&self, level_id: &str, path: &str) -> Result<Level, String> {
+        let (level_text, vocabulary_text) = {
+            let environment = self.environment.borrow();
+            (
+                environment.read_text(path),
+                environment.read_text(VOCABULARY_PATH),
+            )
+        };
+        let level_text = pack::patch_level(&level_text, path, level_id, &self.packs)?;
+        let mut level: Level = try_parse_yml_str(&level_text, path)?;
+        if let Some((ref lang, ref catalog)) = self.lang {
+            let localized = locale::localize(
+                &mut level,
+                Some(path),
+                lang,
+                catalog,
+                &*self.environment.borrow(),
+            );
+            if let Err(error) = localized {
+                let warning = level
+                    .narrator
+                    .say_with("lang-level-unavailable", &[("error", &error)]);
+                writeln!(self.output(), "{}\n", warning).unwrap();
+            }
+        }
+        level
+            .vocabulary
+            .merge(try_parse_yml_str(&vocabulary_text, VOCABULARY_PATH)?);
+        level.resolve_npcs(self.save_state.seed);
+        Ok(level)
+    }
+
+    /// Switch to a level at a coordinate, or the level's entry if the coordinate isn't
+    /// in it. Nothing changes if the level has problems.
+    fn enter_level(
+        &mut self,
+        level_id: &str,
+        level: Level,
+        item_db: ItemDatabase,
+        coord: Coord,
+    ) -> Result<(), String> {
+        let room_graph = RoomGraph::parse(&level)?;
+        let coord = match level.get_room(&coord) {
+            Some(_) => coord,
+            None => level.entry,
+        };
+        let (room, room_info) = match (level.get_room(&coord), room_graph.get(&coord)) {
+            (Some(room), Some(room_info)) => (room.clone(), room_info.clone()),
+            _ => return Err("The room you are in isn't on the map.".into()),
+        };
+
+        if self.save_state.level != level_id {
+            self.save_state.switch_level(level_id);
+        }
+        self.save_state.coord = coord;
+        let warnings = self.save_state.reconcile(&level, &item_db);
+        print_save_warnings(&mut self.output(), &level.narrator, &warnings);
+
+        self.level = level;
+        self.item_db = item_db;
+        self.room_graph = room_graph;
+        self.room = room;
+        self.room_info = room_info;
+        Ok(())
+    }
+
+    /// Load the level and items again, keeping the player's progress, so that changes
+    /// can be seen without restarting. Nothing changes if the new level has problems.
+    fn reload(&mut self) -> Result<(), String> {
+        let path = match self.level_path() {
+            Some(path) => path.to_string(),
+            None => {
+                return Err("This level wasn't loaded from a file, so it can't be reloaded.".into())
+            }
+        };
+        let level_id = self.save_state.level.clone();
+        let level = self.load_level(&level_id, &path)?;
+        let items_text = self.environment.borrow().read_text(ITEMS_PATH);
+        let item_db = pack::patch_items(&try_parse_yml_str(&items_text, ITEMS_PATH)?, &self.packs)?;
+        self.enter_level(&level_id, level, item_db, self.save_state.coord)
+    }
+
+    /// Go to a destination, either in this level or another level of the campaign.
+    fn travel(&mut self, destination: &Destination) -> Result<(), String> {
+        let level_id = match destination.level {
+            Some(ref level_id) if *level_id != self.save_state.level => level_id,
+            _ => {
+                let coord = match self.level.find_entry(&destination.entry) {
+                    Some(coord) if self.room_graph.contains(&coord) => coord,
+                    Some(coord) => return Err(format!("The room at {} isn't on the map.", coord)),
+                    None => {
+                        return Err(format!(
+                            "This level has no entry or room {:?}.",
+                            destination.entry
+                        ))
+                    }
+                };
+                self.move_to(coord);
+                return Ok(());
+            }
+        };
+        let path = match self.campaign {
+            Some(ref campaign) => campaign.level_path(level_id)?.to_string(),
+            None => {
+                return Err("This level isn't part of a campaign, so you can't leave it.".into())
+            }
+        };
+        let level = self.load_level(level_id, &path)?;
+        let coord = match level.find_entry(&destination.entry) {
+            Some(coord) => coord,
+            None => {
+                return Err(format!(
+                    "The level {:?} has no entry or room {:?}.",
+                    level_id, destination.entry
+                ))
+            }
+        };
+        let item_db = self.item_db.clone();
+        self.enter_level(&level_id.clone(), level, item_db, coord)
+    }
+
+    /// Remember where an action moves the player and what it awards, as actions are
+    /// run while the game is borrowed. These happen once the command is done.
+    fn follow(&self, action: &Action) {
+        if let Some(ref destination) = action.teleport {
+            *self.teleport.borrow_mut() = Some(destination.clone());
+        }
+        if let Some(ref award) = action.award {
+            *self.award.borrow_mut() = Some(award.clone());
+        }
+        if let Some(ref epitaph) = action.death {
+            *self.death.borrow_mut() = Some(epitaph.clone());
+        }
+        self.effects
+            .borrow_mut()
+            .extend(action.effects.iter().cloned());
+    }
+
+    /// Run the daemons that are due at the end of a turn. Their effects are applied
+    /// along with the effects of the turn's action.
+    fn run_daemons(&mut self) {
+        let mut ids: Vec<&String> = self.level.daemons.keys().collect();
+        ids.sort();
+        for id in ids {
+            let daemon = &self.level.daemons[id];
+            let state = self
+                .save_state
+                .daemons
+                .entry(id.clone())
+                .or_insert_with(|| DaemonState {
+                    enabled: daemon.enabled,
+                    ..DaemonState::default()
+                });
+            if !state.enabled {
+                continue;
+            }
+            state.turns += 1;
+            if !state.turns.is_multiple_of(daemon.every.max(1)) {
+                continue;
+            }
+            state.runs += 1;
+            if daemon.times.is_some_and(|times| state.runs >= times) {
+                state.enabled = false;
+            }
+            self.effects
+                .borrow_mut()
+                .extend(daemon.effects.iter().cloned());
+        }
+    }
+
+    /// Count down the armed timers at the end of a turn, warning the player as they run
+    /// down, and setting off the ones that run out. Their effects are applied with the
+    /// daemons'.
+    fn run_timers(&mut self) {
+        let ids: Vec<String> = self.save_state.timers.keys().cloned().collect();
+        for id in ids {
+            // Timers the level no longer has, since it was reloaded, are left as they are.
+            let timer = match self.level.timers.get(&id) {
+                Some(timer) => timer,
+                None => continue,
+            };
+            let left = self.save_state.timers[&id].saturating_sub(1);
+            let mut effects = self.effects.borrow_mut();
+            if left == 0 {
+                self.save_state.timers.remove(&id);
+                effects.extend(timer.effects.iter().cloned());
+            } else {
+                self.save_state.timers.insert(id, left);
+                if let Some(warning) = timer.warnings.get(&left) {
+                    effects.push(Effect::Say(warning.clone()));
+                }
+            }
+        }
+    }
+
+    /// Run down the level's survival needs, after the player's turn.
+    fn run_survival(&mut self) {
+        let turn = self.save_state.turn + 1;
+        let ids: Vec<String> = self
+            .level
+            .mechanics
+            .survival
+            .iter()
+            .filter(|(_, need)| turn.is_multiple_of(need.every.max(1)))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in ids {
+            self.add_stat(&id, -1);
+        }
+    }
+
+    /// Give the player a status, or start its turns over if they already have it.
+    fn give_status(&mut self, id: &str) {
+        let status = match self.level.statuses.get(id) {
+            Some(status) => status,
+            None => return,
+        };
+        // The message is shown first, so that it can be read through a status that
+        // jumbles the text.
+        if let Some(ref message) = status.message {
+            let text = wrap(self.text(message).trim_end(), self.width());
+            writeln!(self.output(), "{}\n", text).unwrap();
+        }
+        match self.save_state.status_effects.get_mut(id) {
+            Some(effect) => {
+                effect.turns = status.turns;
+                effect.stacks = (effect.stacks + 1).min(status.max_stacks.max(1));
+            }
+            None => {
+                let effect = StatusEffect {
+                    turns: status.turns,
+                    stacks: 1,
+                };
+                self.save_state
+                    .status_effects
+                    .insert(id.to_string(), effect);
+                for (stat, amount) in status.stats.iter() {
+                    *self.save_state.stats.entry(stat.clone()).or_insert(0) += amount;
+                }
+            }
+        }
+    }
+
+    /// Take away a status, along with what it added to the player's stats.
+    fn end_status(&mut self, id: &str) {
+        if self.save_state.status_effects.remove(id).is_none() {
+            return;
+        }
+        let status = match self.level.statuses.get(id) {
+            Some(status) => status,
+            None => return,
+        };
+        for (stat, amount) in status.stats.iter() {
+            *self.save_state.stats.entry(stat.clone()).or_insert(0) -= amount;
+        }
+        if let Some(ref expires) = status.expires {
+            let text = wrap(self.text(expires).trim_end(), self.width());
+            writeln!(self.output(), "{}\n", text).unwrap();
+        }
+    }
+
+    /// Tick the player's statuses after their turn, and end the ones that wore off.
+    fn run_statuses(&mut self) {
+        let ids: Vec<String> = self.save_state.status_effects.keys().cloned().collect();
+        for id in ids {
+            let status = match self.level.statuses.get(&id) {
+                Some(status) => status,
+                // Statuses from another level of the campaign end when leaving it.
+                None => {
+                    self.save_state.status_effects.remove(&id);
+                    continue;
+                }
+            };
+            let effect = self.save_state.status_effects.get_mut(&id).unwrap();
+            effect.turns = effect.turns.saturating_sub(1);
+            let (stacks, expired) = (effect.stacks as i64, effect.turns == 0);
+            let tick: Vec<_> = status.tick.clone().into_iter().collect();
+            for (stat, amount) in tick {
+                self.add_stat(&stat, amount * stacks);
+            }
+            if expired {
+                self.end_status(&id);
+            }
+        }
+    }
+
+    /// Start a quest, unless the player has already been given it.
+    fn start_quest(&mut self, id: &str) {
+        let quest = match self.level.quests.get(id) {
+            Some(quest) => quest,
+            None => return,
+        };
+        if self.save_state.quests.contains_key(id) {
+            return;
+        }
+        let state = QuestState {
+            started: self.save_state.turn + 1,
+            outcome: None,
+            behind: 0,
+        };
+        self.save_state.quests.insert(id.to_string(), state);
+        let text = self
+            .level
+            .narrator
+            .say_with("quest-started", &[("quest", &quest.name)]);
+        writeln!(self.output(), "{}\n", text).unwrap();
+    }
+
+    /// Finish a quest the player is on, with the reward for being on time or late.
+    fn complete_quest(&mut self, id: &str) {
+        let (quest, state) = match (
+            self.level.quests.get(id),
+            self.save_state.quests.get_mut(id),
+        ) {
+            (Some(quest), Some(state)) if state.outcome.is_none() => (quest, state),
+            _ => return,
+        };
+        // The turn being taken counts.
+        let elapsed = (self.save_state.turn + 1).saturating_sub(state.started);
+        let (outcome, message, effects) = match quest.turns {
+            Some(turns) if elapsed > turns => (QuestOutcome::Late, "quest-late", &quest.late),
+            _ => (QuestOutcome::Done, "quest-done", &quest.reward),
+        };
+        state.outcome = Some(outcome);
+        let text = self
+            .level
+            .narrator
+            .say_with(message, &[("quest", &quest.name)]);
+        writeln!(self.output(), "{}\n", text).unwrap();
+        self.effects.borrow_mut().extend(effects.iter().cloned());
+    }
+
+    /// Count down the deadlines of the player's quests, after their turn.
+    fn run_quests(&mut self) {
+        self.run_escorts();
+        let turn = self.save_state.turn + 1;
+        let mut messages = Vec::new();
+        for (id, state) in self.save_state.quests.iter_mut() {
+            let (quest, turns) = match self.level.quests.get(id) {
+                Some(
+                    quest @ Quest {
+                        turns: Some(turns), ..
+                    },
+                ) if state.outcome.is_none() => (quest, *turns),
+                _ => continue,
+            };
+            let elapsed = turn.saturating_sub(state.started);
+            let message = if elapsed >= turns + quest.grace {
+                state.outcome = Some(QuestOutcome::Failed);
+                self.effects
+                    .borrow_mut()
+                    .extend(quest.failed.iter().cloned());
+                "quest-failed"
+            } else if elapsed == turns {
+                "quest-overdue"
+            } else {
+                continue;
+            };
+            messages.push(
+                self.level
+                    .narrator
+                    .say_with(message, &[("quest", &quest.name)]),
+            );
+        }
+        for text in messages {
+            writeln!(self.output(), "{}\n", text).unwrap();
+        }
+    }
+
+    /// Bring the NPCs the player is escorting along, a room a turn by the shortest way,
+    /// and fail the quests of the ones away for too long. Being brought to the quest's
+    /// room finishes it.
+    fn run_escorts(&mut self) {
+        let player = self.save_state.coord;
+        let narrator = &self.level.narrator;
+        let mut lines = Vec::new();
+        let mut arrived = Vec::new();
+        for (id, quest) in self.level.quests.iter() {
+            let escort = match quest.escort {
+                Some(ref escort)
+                    if self
+                        .save_state
+                        .quests
+                        .get(id)
+                        .is_some_and(|state| state.outcome.is_none()) =>
+                {
+                    escort
+                }
+                _ => continue,
+            };
+            let (npc, mut coord) = match (
+                self.level.npcs.get(&escort.npc),
+                self.npc_coord(&escort.npc),
+            ) {
+                (Some(npc), Some(coord)) => (npc, coord),
+                _ => continue,
+            };
+            if coord != player {
+                let next = self
+                    .room_graph
+                    .shortest_path(coord, player)
+                    .and_then(|path| path.first().copied())
+                    .and_then(|direction| *self.room_graph.get(&coord)?.in_direction(&direction));
+                if let Some(next) = next {
+                    coord = next;
+                    self.save_state.npc_coords.insert(escort.npc.clone(), coord);
+                    if coord == player {
+                        lines.push(narrator.say_with("npc-follows", &[("name", &npc.name)]));
+                    }
+                }
+            }
+            let state = match self.save_state.quests.get_mut(id) {
+                Some(state) => state,
+                None => continue,
+            };
+            if coord == player {
+                state.behind = 0;
+                if escort.to == Some(player) {
+                    arrived.push(id.clone());
+                }
+                continue;
+            }
+            state.behind += 1;
+            if state.behind > escort.behind {
+                state.outcome = Some(QuestOutcome::Failed);
+                self.effects
+                    .borrow_mut()
+                    .extend(quest.failed.iter().cloned());
+                lines.push(narrator.say_with(
+                    "quest-left-behind",
+                    &[("name", &npc.name), ("quest", &quest.name)],
+                ));
+            }
+        }
+        for line in lines {
+            let line = wrap(&line, self.width());
+            writeln!(self.output(), "{}\n", line).unwrap();
+        }
+        for id in arrived {
+            self.complete_quest(&id);
+        }
+    }
+
+    /// Hurt an NPC, which fails the quests to escort them.
+    fn harm(&mut self, id: &str) {
+        let name = match self.level.npcs.get(id) {
+            Some(npc) => &npc.name,
+            None => return,
+        };
+        let mut lines = Vec::new();
+        for (quest_id, quest) in self.level.quests.iter() {
+            let is_escort = quest.escort.as_ref().is_some_and(|escort| escort.npc == id);
+            match self.save_state.quests.get_mut(quest_id) {
+                Some(state) if is_escort && state.outcome.is_none() => {
+                    state.outcome = Some(QuestOutcome::Failed);
+                    self.effects
+                        .borrow_mut()
+                        .extend(quest.failed.iter().cloned());
+                    lines.push(
+                        self.level
+                            .narrator
+                            .say_with("quest-harmed", &[("name", name), ("quest", &quest.name)]),
+                    );
+                }
+                _ => {}
+            }
+        }
+        for line in lines {
+            let line = wrap(&line, self.width());
+            writeln!(self.output(), "{}\n", line).unwrap();
+        }
+    }
+
+    /// Take an NPC into the player's party, where they are, see `Companion`.
+    fn join_party(&mut self, id: &str) {
+        let npc = match self.level.npcs.get(id) {
+            Some(npc) if npc.companion.is_some() => npc,
+            _ => return,
+        };
+        if !self.save_state.party.insert(id.to_string()) {
+            return;
+        }
+        let text = match npc
+            .companion
+            .as_ref()
+            .and_then(|companion| companion.joins.as_ref())
+        {
+            Some(joins) => self.text(joins),
+            None => self
+                .level
+                .narrator
+                .say_with("party-join", &[("name", &npc.name)]),
+        };
+        let here = self.save_state.coord;
+        self.save_state.npc_coords.insert(id.to_string(), here);
+        let text = wrap(&text, self.width());
+        writeln!(self.output(), "{}\n", text).unwrap();
+    }
+
+    /// Have an NPC leave the player's party. They stay where they are.
+    fn leave_party(&mut self, id: &str) {
+        if !self.save_state.party.remove(id) {
+            return;
+        }
+        let npc = match self.level.npcs.get(id) {
+            Some(npc) => npc,
+            None => return,
+        };
+        let text = match npc
+            .companion
+            .as_ref()
+            .and_then(|companion| companion.leaves.as_ref())
+        {
+            Some(leaves) => self.text(leaves),
+            None => self
+                .level
+                .narrator
+                .say_with("party-leave", &[("name", &npc.name)]),
+        };
+        let text = wrap(&text, self.width());
+        writeln!(self.output(), "{}\n", text).unwrap();
+    }
+
+    /// Have the party members in the player's room comment on it, the first time
+    /// they're there together.
+    fn remark(&mut self) {
+        let here = self.save_state.coord;
+        let mut lines = Vec::new();
+        for id in self.save_state.party.iter() {
+            let companion = match self.level.npcs.get(id) {
+                Some(npc) if self.npc_coord(id) == Some(here) => match &npc.companion {
+                    Some(companion) => companion,
+                    None => continue,
+                },
+                _ => continue,
+            };
+            let remarked = self.save_state.remarks.get(id);
+            for (key, comment) in companion.comments.iter() {
+                if self.level.find_entry(key) == Some(here)
+                    && !remarked.is_some_and(|keys| keys.contains(key))
+                {
+                    lines.push((id.clone(), key.clone(), self.text(comment)));
+                }
+            }
+        }
+        for (id, key, line) in lines {
+            self.save_state.remarks.entry(id).or_default().insert(key);
+            let line = wrap(&line, self.width());
+            writeln!(self.output(), "{}\n", line).unwrap();
+        }
+    }
+
+    /// Whether the player is on a quest to escort the NPC.
+    fn is_escorted(&self, id: &str) -> bool {
+        self.level.quests.iter().any(|(quest_id, quest)| {
+            quest.escort.as_ref().is_some_and(|escort| escort.npc == id)
+                && self
+                    .save_state
+                    .quests
+                    .get(quest_id)
+                    .is_some_and(|state| state.outcome.is_none())
+        })
+    }
+
+    /// Whether any of the player's statuses has them reading jumbled words.
+    fn is_scrambled(&self) -> bool {
+        self.save_state.status_effects.keys().any(|id| {
+            self.level
+                .statuses
+                .get(id)
+                .is_some_and(|status| status.scramble)
+        })
+    }
+
+    /// The way the player ends up moving, which their statuses can send off in another
+    /// direction.
+    fn stumble(&self, direction: Direction) -> Direction {
+        let chance = self
+            .save_state
+            .status_effects
+            .keys()
+            .filter_map(|id| self.level.statuses.get(id))
+            .map(|status| status.stumble)
+            .fold(0.0, f64::max);
+        if chance <= 0.0 {
+            return direction;
+        }
+        let key = hash_str("stumble") ^ self.save_state.turn as u64;
+        let mut rng = Pcg32::seed_from_u64(self.save_state.seed ^ key);
+        if rng.gen::<f64>() >= chance {
+            return direction;
+        }
+        let directions: Vec<Direction> = [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ]
+        .iter()
+        .copied()
+        .filter(|other| *other != direction && self.room_info.in_direction(other).is_some())
+        .collect();
+        match directions.choose(&mut rng) {
+            Some(other) => *other,
+            None => direction,
+        }
+    }
+
+    /// Roll a check against the player's stats. The roll is seeded by the game, the
+    /// turn, and what it's for, so that a replay rolls the same.
+    fn roll(&self, key: &str, check: &Check) -> bool {
+        let seed = self.save_state.seed ^ hash_str(key) ^ self.save_state.turn as u64;
+        let mut rng = Pcg32::seed_from_u64(seed);
+        let roll = rng.gen_range(1..=check.sides.max(1));
+        let bonus = check
+            .stat
+            .as_ref()
+            .and_then(|stat| self.save_state.stats.get(stat))
+            .copied()
+            .unwrap_or(0);
+        // The party members in the room lend a hand.
+        let here = self.save_state.coord;
+        let assists: i64 = self
+            .save_state
+            .party
+            .iter()
+            .filter(|id| self.npc_coord(id) == Some(here))
+            .filter_map(|id| self.level.npcs.get(id)?.companion.as_ref())
+            .filter_map(|companion| companion.assists.get(check.stat.as_ref()?))
+            .sum();
+        roll + bonus + assists >= check.target
+    }
+
+    /// Whether a safeguard gets the player past a hazard, see `Safeguard`.
+    fn safeguard_works(&self, key: &str, safeguard: &Safeguard) -> bool {
+        let holds = safeguard
+            .when
+            .as_ref()
+            .map(|when| when.holds(&self.variables()));
+        match (holds, &safeguard.check) {
+            (Some(true), _) | (None, None) => true,
+            (_, Some(check)) => self.roll(key, check),
+            (Some(false), None) => false,
+        }
+    }
+
+    /// Spring the hazards of the room the player just entered, unless they get past
+    /// them.
+    fn spring_hazards(&mut self) {
+        let room = Rc::clone(&self.room);
+        for hazard in room.hazards.iter() {
+            if self.save_state.disarmed.contains(&hazard.id) {
+                continue;
+            }
+            if let Some(ref avoid) = hazard.avoid {
+                if self.safeguard_works(&hazard.id, avoid) {
+                    if let Some(ref message) = avoid.message {
+                        let text = wrap(self.text(message).trim_end(), self.width());
+                        writeln!(self.output(), "{}\n", text).unwrap();
+                    }
+                    continue;
+                }
+                if let Some(ref failed) = avoid.failed {
+                    let text = wrap(self.text(failed).trim_end(), self.width());
+                    writeln!(self.output(), "{}\n", text).unwrap();
+                }
+            }
+            self.spring(hazard);
+        }
+    }
+
+    /// Take a step of the sequence puzzles in the player's room. A step out of order
+    /// starts the sequence over.
+    /// Unlock an entry of the level's lore, and let the player know the first time.
+    fn learn_lore(&mut self, id: &str) {
+        let lore = match self.level.lore.get(id) {
+            Some(lore) => lore,
+            None => return,
+        };
+        if self.save_state.lore.insert(id.to_string()) {
+            let text = self
+                .level
+                .narrator
+                .say_with("lore-learned", &[("title", &lore.title)]);
+            writeln!(self.output(), "{}\n", text).unwrap();
+        }
+    }
+
+    fn take_step(&mut self, step: &str) {
+        let room = Rc::clone(&self.room);
+        for puzzle in room.puzzles.iter() {
+            if self.save_state.solved.contains(&puzzle.id)
+                || !puzzle.sequence.iter().any(|other| other == step)
+            {
+                continue;
+            }
+            let progress = self
+                .save_state
+                .puzzle_steps
+                .get(&puzzle.id)
+                .copied()
+                .unwrap_or(0);
+            let progress = if puzzle.sequence.get(progress).map(String::as_str) == Some(step) {
+                progress + 1
+            } else {
+                if let Some(ref wrong) = puzzle.wrong {
+                    let text = wrap(self.text(wrong).trim_end(), self.width());
+                    writeln!(self.output(), "{}\n", text).unwrap();
+                }
+                // The wrong step could be the start of the sequence.
+                (puzzle.sequence[0] == step) as usize
+            };
+            if progress == puzzle.sequence.len() {
+                self.save_state.puzzle_steps.remove(&puzzle.id);
+                self.solve(puzzle);
+            } else {
+                self.save_state
+                    .puzzle_steps
+                    .insert(puzzle.id.clone(), progress);
+            }
+        }
+    }
+
+    /// Mark a puzzle as solved, show its message, and queue up its effects.
+    fn solve(&mut self, puzzle: &Puzzle) {
+        self.save_state.solved.insert(puzzle.id.clone());
+        let text = wrap(self.text(&puzzle.solved).trim_end(), self.width());
+        writeln!(self.output(), "{}\n", text).unwrap();
+        self.effects
+            .borrow_mut()
+            .extend(puzzle.effects.iter().cloned());
+    }
+
+    /// Show a hazard's message, and queue up its effects.
+    fn spring(&mut self, hazard: &Hazard) {
+        let text = wrap(self.text(&hazard.message).trim_end(), self.width());
+        writeln!(self.output(), "{}\n", text).unwrap();
+        self.effects
+            .borrow_mut()
+            .extend(hazard.effects.iter().cloned());
+        if hazard.once {
+            self.save_state.disarmed.insert(hazard.id.clone());
+        }
+    }
+
+    /// Add to one of the player's stats. Survival needs stay between 0 and their max,
+    /// and warn the player as they fall, see `Warning`.
+    fn add_stat(&mut self, id: &str, amount: i64) {
+        let need = match self.level.mechanics.survival.get(id) {
+            Some(need) => need,
+            None => {
+                *self.save_state.stats.entry(id.to_string()).or_insert(0) += amount;
+                return;
+            }
+        };
+        let before = self.save_state.stats.get(id).copied().unwrap_or(need.max);
+        let after = (before + amount).clamp(0, need.max);
+        self.save_state.stats.insert(id.to_string(), after);
+        let mut messages = Vec::new();
+        for warning in need.warnings.iter() {
+            let sign = match (before < warning.below, after < warning.below) {
+                (false, true) => {
+                    messages.push(&warning.message);
+                    1
+                }
+                (true, false) => -1,
+                _ => continue,
+            };
+            for (stat, penalty) in warning.penalty.iter() {
+                *self.save_state.stats.entry(stat.clone()).or_insert(0) += sign * penalty;
+            }
+        }
+        for message in messages {
+            let text = wrap(self.text(message).trim_end(), self.width());
+            writeln!(self.output(), "{}\n", text).unwrap();
+        }
+    }
+
+    /// Apply the effects of the last action and of the daemons. Moving the player,
+    /// awards, and death are left to the rest of the turn, the same as for an action.
+    fn apply_effects(&mut self) {
+        for effect in self.effects.take() {
+            match effect {
+                Effect::Say(passage) => {
+                    let text = wrap(self.text(&passage).trim_end(), self.width());
+                    writeln!(self.output(), "{}\n", text).unwrap();
+                    self.interrupted = true;
+                }
+                Effect::Stats(stats) => {
+                    for (id, amount) in stats {
+                        self.add_stat(&id, amount);
+                    }
+                }
+                Effect::SetFlag(flag) => {
+                    if self.save_state.flags.insert(flag.clone()) {
+                        self.emit(GameEvent::FlagSet(flag));
+                    }
+                }
+                Effect::ClearFlag(flag) => {
+                    self.save_state.flags.remove(&flag);
+                }
+                Effect::Enable(id) => {
+                    let state = DaemonState {
+                        enabled: true,
+                        ..DaemonState::default()
+                    };
+                    self.save_state.daemons.insert(id, state);
+                }
+                Effect::Disable(id) => {
+                    self.save_state.daemons.entry(id).or_default().enabled = false;
+                }
+                Effect::Arm(id) => {
+                    if let Some(timer) = self.level.timers.get(&id) {
+                        self.save_state.timers.insert(id, timer.turns);
+                    }
+                }
+                Effect::Defuse(id) => {
+                    let defused = match self.level.timers.get(&id) {
+                        Some(timer) if self.save_state.timers.remove(&id).is_some() => {
+                            timer.defused.clone()
+                        }
+                        _ => continue,
+                    };
+                    self.effects.borrow_mut().extend(defused);
+                }
+                Effect::Teleport(destination) => *self.teleport.borrow_mut() = Some(destination),
+                Effect::Award(award) => *self.award.borrow_mut() = Some(award),
+                Effect::Death(epitaph) => *self.death.borrow_mut() = Some(epitaph),
+                Effect::Status(id) => self.give_status(&id),
+                Effect::Cure(id) => self.end_status(&id),
+                Effect::Step(step) => self.take_step(&step),
+                Effect::Lore(id) => self.learn_lore(&id),
+                Effect::Quest(id) => self.start_quest(&id),
+                Effect::Join(id) => self.join_party(&id),
+                Effect::Leave(id) => self.leave_party(&id),
+                Effect::Complete(id) => self.complete_quest(&id),
+                Effect::Harm(id) => self.harm(&id),
+                Effect::Sound(id) => self.play(Cue::Sound(id)),
+                Effect::Reveal(items) => {
+                    for (id, quantity) in items {
+                        let mut item = match self.item_db.find(&id) {
+                            Some(item) => item.clone(),
+                            None => continue,
+                        };
+                        item.quantity = quantity;
+                        self.save_state
+                            .room_inventory_mut(&self.room, &self.item_db)
+                            .add_item(item);
+                    }
+                }
+                Effect::Reputation(changes) => {
+                    for (id, amount) in changes {
+                        *self.save_state.reputation.entry(id.clone()).or_insert(0) += amount;
+                        let name = match self.level.factions.get(&id) {
+                            Some(faction) => faction.name.as_str(),
+                            None => id.as_str(),
+                        };
+                        let message = match amount.cmp(&0) {
+                            Ordering::Greater => "reputation-rises",
+                            Ordering::Less => "reputation-falls",
+                            Ordering::Equal => continue,
+                        };
+                        let text = self.level.narrator.say_with(message, &[("faction", name)]);
+                        writeln!(self.output(), "{}\n", text).unwrap();
+                    }
+                }
+            }
+        }
+        // Effects can lead to more effects, like solving a puzzle.
+        if !self.effects.borrow().is_empty() {
+            self.apply_effects();
+        }
+    }
+
+    /// Give the award of the last action or effect, if any, and take the player where
+    /// it leads.
+    fn follow_through(&mut self) {
+        if let Some(award) = self.award.take() {
+            self.give_award(&award);
+        }
+        self.follow_teleport();
+    }
+
+    /// Let something happen during the turn, for `dispatch_events` to respond to.
+    fn emit(&self, event: GameEvent) {
+        self.events.borrow_mut().push_back(event);
+    }
+
+    /// Emit that the player entered a room, if they're no longer where they were.
+    fn arrive(&self, from_level: &str, from: Coord) {
+        if (self.save_state.level.as_str(), self.save_state.coord) == (from_level, from) {
+            return;
+        }
+        let from = (self.save_state.level == from_level).then_some(from);
+        let to = self.save_state.coord;
+        self.emit(GameEvent::RoomEntered { from, to });
+    }
+
+    /// Respond to what happened during the turn, in order, with the engine's rules and
+    /// then the level's scripts. Whatever they lead to is followed through before the
+    /// next event, and the events they emit are dispatched in turn. If the command
+    /// ends the turn, it ends after everything the command led to.
+    fn dispatch_events(&mut self, ends_turn: bool) {
+        let mut ends_turn = ends_turn;
+        // Conversations aren't interrupted by ambient lines.
+        let mut talked = false;
+        loop {
+            let event = match self.events.borrow_mut().pop_front() {
+                Some(event) => event,
+                None if ends_turn => {
+                    ends_turn = false;
+                    GameEvent::TurnEnded
+                }
+                None => break,
+            };
+            let (level, coord) = (self.save_state.level.clone(), self.save_state.coord);
+            match event {
+                GameEvent::ItemTaken(ref id) => self.award_pickup(id),
+                GameEvent::RoomEntered { from, .. } => {
+                    let from_room = from.and_then(|from| self.level.get_room(&from));
+                    self.enter_regions(from_room.map(Rc::clone).as_deref());
+                    self.spring_hazards();
+                }
+                GameEvent::NpcTalkedTo(_) => talked = true,
+                GameEvent::FlagSet(_) => {}
+                GameEvent::TurnEnded => {
+                    self.run_daemons();
+                    self.run_timers();
+                    self.apply_effects();
+                    self.run_spawns();
+                    self.run_fetches();
+                    self.run_weather();
+                    self.run_survival();
+                    self.run_statuses();
+                    self.run_quests();
+                    if !talked {
+                        print_ambient(self);
+                    }
+                }
+            }
+            self.run_scripts(&event);
+            self.apply_effects();
+            self.follow_through();
+            self.arrive(&level, coord);
+        }
+    }
+
+    /// Queue the effects of the level's scripts that run on an event.
+    fn run_scripts(&self, event: &GameEvent) {
+        for script in self.level.scripts.iter() {
+            if event.triggers(&script.on, &self.level) && self.holds(&script.when) {
+                self.effects
+                    .borrow_mut()
+                    .extend(script.effects.iter().cloned());
+            }
+        }
+    }
+
+    /// Give the points for picking up an item, the first time in a game.
+    fn award_pickup(&mut self, id: &str) {
+        if let Some(&points) = self.level.pickup_points.get(id) {
+            self.give_award(&Award {
+                id: format!("pickup-{}", id),
+                points,
+                achievement: None,
+            });
+        }
+    }
+
+    /// The epitaph if the player just died, either from an action, a deadly room, or
+    /// running out of health.
+    fn take_death(&self) -> Option<String> {
+        if let Some(epitaph) = self.death.take() {
+            return Some(self.text(&epitaph));
+        }
+        if let Some(ref epitaph) = self.room.death {
+            return Some(self.text(epitaph));
+        }
+        if let Some(health) = self.save_state.stats.get("health") {
+            if *health <= 0 {
+                return Some(self.level.narrator.say("death-health"));
+            }
+        }
+        self.level
+            .mechanics
+            .survival
+            .iter()
+            .find(|(id, _)| self.save_state.stats.get(*id) == Some(&0))
+            .map(|(_, need)| self.text(&need.death))
+    }
+
+    /// Remember the room as the place to respawn, if it's a checkpoint.
+    fn reach_checkpoint(&mut self) {
+        let coord = self.save_state.coord;
+        if self.room.checkpoint && self.save_state.checkpoint != Some(coord) {
+            self.save_state.checkpoint = Some(coord);
+            writeln!(self.output(), "{}\n", self.level.narrator.say("checkpoint")).unwrap();
+        }
+    }
+
+    /// Time the next checkpoint of the race, if the player just reached it, and keep the
+    /// splits of a finished race if it's the best one yet.
+    fn check_race(&mut self) {
+        let started = match self.race_started {
+            Some(started) => started,
+            None => return,
+        };
+        let index = self.splits.len();
+        let split: &Split = match self.level.race.get(index) {
+            Some(split) => split,
+            None => return,
+        };
+        let is_at = split
+            .at
+            .as_ref()
+            .is_none_or(|at| self.level.find_entry(at) == Some(self.save_state.coord));
+        if !is_at || !self.holds(&split.when) {
+            return;
+        }
+        let seconds = started.elapsed().as_secs();
+        self.splits.push(seconds);
+        let time = format_elapsed(Duration::from_secs(seconds));
+        let best = self.races.get(&self.save_state.level);
+        let message = match best.and_then(|best| best.get(index)) {
+            Some(&best) => {
+                let delta = if seconds > best {
+                    format!("+{}", format_elapsed(Duration::from_secs(seconds - best)))
+                } else {
+                    format!("-{}", format_elapsed(Duration::from_secs(best - seconds)))
+                };
+                self.level.narrator.say_with(
+                    "race-split-best",
+                    &[("name", &split.name), ("time", &time), ("delta", &delta)],
+                )
+            }
+            None => self
+                .level
+                .narrator
+                .say_with("race-split", &[("name", &split.name), ("time", &time)]),
+        };
+        writeln!(self.output(), "{}\n", message).unwrap();
+        if self.splits.len() < self.level.race.len() {
+            return;
+        }
+
+        let best = best.and_then(|best| best.last().copied());
+        let message = match best {
+            Some(best) if best <= seconds => {
+                let best = format_elapsed(Duration::from_secs(best));
+                self.level
+                    .narrator
+                    .say_with("race-finish", &[("time", &time), ("best", &best)])
+            }
+            _ => {
+                self.races
+                    .insert(self.save_state.level.clone(), self.splits.clone());
+                if let Some(path) = self.output().races_path() {
+                    let yml =
+                        serde_yaml::to_string(&self.races).expect("Unable to serialize the races.");
+                    fs::write(path, yml).expect("Unable to save the races.");
+                }
+                self.level
+                    .narrator
+                    .say_with("race-record", &[("time", &time)])
+            }
+        };
+        print_box(self, &message);
+    }
+
+    /// Add the finished game to the results file, for comparing with other players.
+    fn record_result(&self, turns: usize) {
+        let path = match self.output().results_path() {
+            Some(path) => path,
+            None => return,
+        };
+        let seconds = self
+            .started
+            .map_or(0, |started| started.elapsed().as_secs());
+        let result = GameResult::new(
+            &self.config.name,
+            &self.save_state.level,
+            self.save_state.seed,
+            seconds,
+            turns,
+            self.save_state.score,
+        );
+        if let Err(message) = leaderboard::append(&path, result) {
+            print_failure(self, &message);
+        }
+    }
+
+    /// Give the player an award, unless they already got it this game.
+    fn give_award(&mut self, award: &Award) {
+        if !self.save_state.awards.insert(award.id.clone()) {
+            return;
+        }
+        if award.points > 0 {
+            self.save_state.score += award.points;
+            let points = format!("+{} points", award.points);
+            let points = self.styled(Style::Item, &points);
+            writeln!(self.output(), "{}\n", points).unwrap();
+        }
+        if let Some(ref id) = award.achievement {
+            self.unlock(id);
+        }
+    }
+
+    /// Unlock an achievement for good, saving it outside of the game.
+    fn unlock(&mut self, id: &str) {
+        let achievement = match self.level.achievements.get(id) {
+            Some(achievement) => achievement,
+            None => return,
+        };
+        if !self.achievements.insert(id.to_string()) {
+            return;
+        }
+        let title = self
+            .level
+            .narrator
+            .say_with("achievement", &[("name", &achievement.name)]);
+        print_box(self, &title);
+        if let Some(ref description) = achievement.description {
+            writeln!(self.output(), "{}\n", description).unwrap();
+        }
+        if let Some(path) = self.output().achievements_path() {
+            let yml = serde_yaml::to_string(&self.achievements)
+                .expect("Unable to serialize the achievements.");
+            fs::write(path, yml).expect("Unable to save the achievements.");
+        }
+    }
+
+    /// Print the introduction and the first room. New games of levels with scenarios
+    /// begin by picking one.
+    pub fn start(&mut self) {
+        if self.environment.borrow().jam_started().is_some() {
+            self.save_state.debug = true;
+        } else {
+            print_text_file(self, "data/intro.txt");
+        }
+        if self.is_new && !self.level.scenarios.is_empty() {
+            let index = choose_scenario(self);
+            let scenario = &self.level.scenarios[index];
+            self.save_state
+                .begin_scenario(scenario, &self.level, &self.item_db);
+            if let Some(ref intro) = scenario.intro {
+                writeln!(self.output(), "{}\n", self.text(intro)).unwrap();
+            }
+            self.move_to(self.save_state.coord);
+        }
+        let race = self.environment.borrow().race();
+        if race && !self.level.race.is_empty() {
+            let message = if self.is_new {
+                self.race_started = Some(Instant::now());
+                let count = self.level.race.len().to_string();
+                self.level
+                    .narrator
+                    .say_with("race-start", &[("count", &count)])
+            } else {
+                self.level.narrator.say("race-new-game")
+            };
+            writeln!(self.output(), "{}\n", message).unwrap();
+        }
+        self.update_music();
+        print_room_description(self);
+    }
+
+    /// Put the player in a room, without describing it.
+    fn move_to(&mut self, coord: Coord) {
+        self.save_state.coord = coord;
+        self.room_info = (self.room_graph.get(&coord).unwrap()).clone();
+        self.room = self
+            .level
+            .get_room(&coord)
+            .expect("Expected to find a room.")
+            .clone();
+        self.save_state
+            .room_inventory_mut(&self.room, &self.item_db);
+    }
+
+    /// Run a line of input from the player. This returns a response once the game
+    /// should stop, such as when the player quits.
+    pub fn step(&mut self, input: String) -> Option<GameLoopResponse> {
+        let is_again = matches!(input.trim(), "again" | "g");
+        let input = match self.last_input {
+            Some(ref last_input) if is_again => last_input.clone(),
+            None if is_again => {
+                let message = self.level.narrator.say("again-nothing");
+                writeln!(self.output(), "{}", message).unwrap();
+                return None;
+            }
+            _ => input,
+        };
+        self.last_input = Some(input.clone());
+        // However the player got to the room they're in, they can walk back to it.
+        self.save_state
+            .room_inventory_mut(&self.room, &self.item_db)
+            .visited = true;
+        let mut commands = match self.converse(&input) {
+            Some(command) => vec![command],
+            None => parse_command(input, &self.level.vocabulary, &self.level.narrator)
+                .unwrap_or_else(|message| vec![ParsedCommand::Message(message)]),
+        };
+        if is_again && !commands.iter().all(ParsedCommand::repeats) {
+            commands = vec![ParsedCommand::Narrate("again-unsafe")];
+        }
+        // A walk with "go to" becomes its steps, each with the room it should end in.
+        let commands: Vec<(ParsedCommand, Option<Coord>)> = commands
+            .into_iter()
+            .flat_map(|command| match command {
+                ParsedCommand::Travel(ref target) => match self.route(target) {
+                    Ok(route) => route
+                        .into_iter()
+                        .map(|(direction, coord)| (ParsedCommand::Move(direction), Some(coord)))
+                        .collect(),
+                    Err(_) => vec![(command, None)],
+                },
+                command => vec![(command, None)],
+            })
+            .collect();
+        let steps = commands.len();
+        // Only the command right before can be corrected.
+        let mut failed = self.failed.take();
+        for (index, (command, arrives)) in commands.into_iter().enumerate() {
+            let command = match command {
+                ParsedCommand::Oops(correction) => match failed.take() {
+                    Some(mut failed) => {
+                        if let Some(target) = failed.target_mut() {
+                            *target = correction;
+                        }
+                        failed
+                    }
+                    None => ParsedCommand::Narrate("oops-missing"),
+                },
+                command => command,
+            };
+            let mut retry = command.clone();
+            let can_retry = retry.target_mut().is_some();
+            let advances_turn = command.advances_turn();
+            let (from_level, from) = (self.save_state.level.clone(), self.save_state.coord);
+            if let Some(warning) = self.irreversible(&command) {
+                let question = self.text(warning);
+                if !prompt_yes_no(self, question.trim_end()) {
+                    let message = self.level.narrator.say("irreversible-cancel");
+                    writeln!(self.output(), "{}", message).unwrap();
+                    break;
+                }
+            }
+            let snapshot = advances_turn.then(|| self.snapshot());
+            self.interrupted = false;
+            let lesson = self
+                .environment
+                .borrow()
+                .tutorial()
+                .then(|| tutorial::lesson(self.room.id.as_deref()))
+                .flatten()
+                .filter(|lesson| {
+                    !self.save_state.flags.contains(lesson.id)
+                        && lesson.is_learned_by(
+                            &command,
+                            &self.level.vocabulary,
+                            &self.level.narrator,
+                        )
+                });
+            let result = run_command(self, command);
+            self.apply_effects();
+            self.follow_through();
+            if advances_turn {
+                self.arrive(&from_level, from);
+            }
+            self.dispatch_events(advances_turn && matches!(result, CommandResult::Success));
+            if let Some(epitaph) = self.take_death() {
+                // Dying can be undone, to get out of a trap.
+                self.remember(snapshot);
+                // Anything after the player's death is skipped, even if they respawn.
+                return die(self, &epitaph);
+            }
+            self.update_music();
+            self.reach_checkpoint();
+            self.check_race();
+            if let (Some(lesson), CommandResult::Success) = (lesson, &result) {
+                self.save_state.flags.insert(lesson.id.to_string());
+                let message = self.level.narrator.say("tutorial-learned");
+                writeln!(self.output(), "{}\n", message).unwrap();
+            }
+            match result {
+                CommandResult::Success => {
+                    if advances_turn {
+                        self.remember(snapshot);
+                        self.save_state.turn += 1;
+                        self.save_state.clock += 1;
+                        check_par(self);
+                        self.move_npcs(from, self.save_state.level == from_level);
+                        self.remark();
+                    }
+                    if let Some(arrives) = arrives {
+                        if index + 1 < steps && self.walk_interrupted(arrives) {
+                            let message = self.level.narrator.say("travel-interrupted");
+                            writeln!(self.output(), "{}", message).unwrap();
+                            break;
+                        }
+                    }
+                }
+                // Stop processing the rest of a compound command.
+                CommandResult::Failure => {
+                    if can_retry {
+                        self.failed = Some(retry);
+                    }
+                    break;
+                }
+                CommandResult::Exit(response) => return Some(response),
+            }
+        }
+        None
+    }
+
+    /// Give a cue to the environment to play, unless the player has muted the game.
+    fn play(&self, cue: Cue) {
+        if !self.config.mute {
+            self.output().play(&cue);
+        }
+    }
+
+    /// Change the music when the player's room has other music than what's playing. A
+    /// room's own music comes before its regions'.
+    fn update_music(&mut self) {
+        let music = self.room.music.clone().or_else(|| {
+            self.room
+                .regions
+                .iter()
+                .filter_map(|id| self.level.regions.get(id))
+                .find_map(|region| region.music.clone())
+        });
+        if music != self.music {
+            self.music = music.clone();
+            self.play(Cue::Music(music));
+        }
+    }
+
+    /// Stop the music when the player mutes the game, or start it again when they turn
+    /// the sound back on.
+    fn set_mute(&mut self, mute: bool) {
+        if mute == self.config.mute {
+            return;
+        }
+        let music = if mute { None } else { self.music.clone() };
+        self.output().play(&Cue::Music(music));
+        self.config.mute = mute;
+    }
+
+    /// Move the player where the last action or effect is taking them, if anywhere.
+    fn follow_teleport(&mut self) {
+        if let Some(destination) = self.teleport.take() {
+            // Vehicles only go where they're ridden.
+            self.leave_vehicle();
+            match self.travel(&destination) {
+                Ok(()) => print_room_description(self),
+                Err(message) => print_failure(self, &message),
+            }
+        }
+    }
+
+    /// The steps to walk to a room the player has been to, by its title or id, with the
+    /// room each step ends in. Only the rooms the player has been to are walked through.
+    /// Exits to other levels and points of no return are left out, but locked ones
+    /// aren't, so the walk stops at the door like it would for the player.
+    fn route(&self, target: &str) -> Result<Vec<(Direction, Coord)>, String> {
+        let visited = |coord: &Coord| self.visited(coord);
+        let to = self
+            .level
+            .rooms
+            .iter()
+            .find(|room| room.title.eq_ignore_ascii_case(target))
+            .map(|room| room.coord)
+            .or_else(|| self.level.find_entry(target))
+            .filter(|coord| visited(coord))
+            .ok_or_else(|| {
+                self.level
+                    .narrator
+                    .say_with("travel-unknown", &[("target", target)])
+            })?;
+        if to == self.save_state.coord {
+            return Err(self.level.narrator.say("travel-here"));
+        }
+        let path = search(self.save_state.coord, Some(to), |coord| {
+            let room = self.level.get_room(&coord);
+            DIRECTIONS
+                .iter()
+                .filter_map(|direction| {
+                    let next = match room.and_then(|room| self.exit_from(room, direction)) {
+                        Some(exit) if exit.level.is_none() && exit.irreversible.is_none() => {
+                            self.level.find_entry(&exit.entry)?
+                        }
+                        Some(_) => return None,
+                        None => (*self.room_graph.get(&coord)?.in_direction(direction))?,
+                    };
+                    let passable = self.terrain_blocking(&coord, &next).is_none();
+                    (visited(&next) && passable).then_some((next, (*direction, next)))
+                })
+                .collect::<Vec<_>>()
+        })
+        .path_to(&to);
+        path.ok_or_else(|| {
+            self.level
+                .narrator
+                .say_with("travel-no-way", &[("target", target)])
+        })
+    }
+
+    /// Announce the regions the player has crossed into, coming from a room of the same
+    /// level, or None from another level. Regions without a banner are announced by
+    /// name, if they have one.
+    fn enter_regions(&self, from: Option<&Room>) {
+        for id in self.room.regions.iter() {
+            if from.is_some_and(|from| from.regions.contains(id)) {
+                continue;
+            }
+            let region = match self.level.regions.get(id) {
+                Some(region) => region,
+                None => continue,
+            };
+            let text = match (&region.banner, &region.name) {
+                (Some(banner), _) => self.text(banner),
+                (None, Some(name)) => self
+                    .level
+                    .narrator
+                    .say_with("region-enter", &[("region", name)]),
+                (None, None) => continue,
+            };
+            let text = wrap(text.trim_end(), self.width());
+            writeln!(self.output(), "{}\n", text).unwrap();
+        }
+    }
+
+    /// Whether the player has been in a room of the current level.
+    fn visited(&self, coord: &Coord) -> bool {
+        self.save_state
+            .room_inventories
+            .get(coord)
+            .is_some_and(|room_inventory| room_inventory.visited)
+    }
+
+    /// Whether a walk with "go to" should stop short, after a step that was meant to end
+    /// in a room. The player might have been moved somewhere else, been told something,
+    /// or walked in on someone hostile.
+    fn walk_interrupted(&self, arrives: Coord) -> bool {
+        self.interrupted
+            || self.save_state.coord != arrives
+            || self
+                .npcs_at(&self.save_state.coord)
+                .into_iter()
+                .any(|(_, npc)| self.is_hostile(npc))
+    }
+
+    fn snapshot(&self) -> String {
+        serde_yaml::to_string(&self.save_state).expect("Unable to serialize the game state.")
+    }
+
+    /// Keep a snapshot from before a turn, forgetting the oldest one past the limit.
+    fn remember(&mut self, snapshot: Option<String>) {
+        if let Some(snapshot) = snapshot {
+            if self.history.len() == UNDO_LIMIT {
+                self.history.pop_front();
+            }
+            self.history.push_back(snapshot);
+        }
+    }
+
+    /// Go back to the state from before the last turn, if there is one. This can move
+    /// the player back to another level of the campaign.
+    fn undo(&mut self) -> Result<bool, String> {
+        let snapshot = match self.history.pop_back() {
+            Some(snapshot) => snapshot,
+            None => return Ok(false),
+        };
+        let save_state: SaveState = try_parse_yml_str(&snapshot, "the undo history")?;
+        if save_state.level == self.save_state.level {
+            self.save_state = save_state;
+            self.move_to(self.save_state.coord);
+            return Ok(true);
+        }
+        let path = match self.campaign {
+            Some(ref campaign) => campaign.level_path(&save_state.level)?.to_string(),
+            None => return Err("The level from before can't be found.".into()),
+        };
+        let level = self.load_level(&save_state.level, &path)?;
+        let (level_id, coord) = (save_state.level.clone(), save_state.coord);
+        self.save_state = save_state;
+        let item_db = self.item_db.clone();
+        self.enter_level(&level_id, level, item_db, coord)?;
+        Ok(true)
+    }
+
+    /// Write to the environment, through the player's text filters.
+    fn output(&self) -> Filtered<'_, T> {
+        Filtered {
+            inner: self.environment.borrow_mut(),
+            filters: &self.config.text_filters,
+            chain: self.filter_chain.borrow_mut(),
+        }
+    }
+
+    fn status(&self) -> Status {
+        let directions = [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ]
+        .iter()
+        .filter(|direction| {
+            self.room_info.in_direction(direction).is_some() || self.exit(direction).is_some()
+        })
+        // The weather can hide the way on, in the sidebar too.
+        .filter(|_| !self.exits_hidden())
+        .map(|direction| direction.lowercase_string())
+        .collect();
+        let items = self
+            .save_state
+            .room_inventories
+            .get(&self.room.coord)
+            .map(|room_inventory| {
+                room_inventory
+                    .visible_iter()
+                    .map(|(_, item)| item.name.to_lowercase())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Status {
+            room: self.room.title.clone(),
+            exits: match self.exits_hidden() {
+                true => "? ? ? ?".into(),
+                false => self
+                    .room_info
+                    .exits(|direction| self.exit(direction).is_some()),
+            },
+            directions,
+            items,
+            money: currency::format(
+                currency::total(&self.save_state.inventory.items),
+                &self.item_db,
+            ),
+            turn: self.save_state.turn,
+        }
+    }
+
+    /// The width to wrap the output to, which is the player's preferred width unless
+    /// the terminal is narrower.
+    fn width(&self) -> usize {
+        let width = match self.environment.borrow().width() {
+            Some(columns) => columns.min(self.config.width),
+            None => self.config.width,
+        };
+        let width = width.max(config::MIN_WIDTH);
+        if self
+            .config
+            .text_filters
+            .contains(&TextFilter::LetterSpacing)
+        {
+            // Spacing the letters about doubles the length of each line.
+            width / 2
+        } else {
+            width
+        }
+    }
+
+    /// Get the text of a passage, respecting the player's content filter, with the
+    /// values in its template filled in from the game.
+    fn text(&self, passage: &Passage) -> String {
+        let passage = self.pick(passage);
+        let text = template::render(
+            passage.text(&self.save_state.content_filter),
+            &self.variables(),
+        );
+        if self.is_scrambled() {
+            let seed = self.save_state.seed ^ self.save_state.turn as u64;
+            return scramble_words(&text, seed);
+        }
+        text
+    }
+
+    /// The room's own exit in a direction, unless it's a secret that hasn't been found.
+    fn exit(&self, direction: &Direction) -> Option<&Destination> {
+        self.exit_from(&self.room, direction)
+    }
+
+    /// The room of this level that going in a direction leads to, through the room's exit
+    /// or across the map, if anywhere.
+    fn leads_to(&self, direction: &Direction) -> Option<Coord> {
+        match self.exit(direction) {
+            Some(exit) if exit.level.is_none() => self.level.find_entry(&exit.entry),
+            Some(_) => None,
+            None => *self.room_info.in_direction(direction),
+        }
+    }
+
+    /// The exit from a room in a direction, unless it's a secret that hasn't been found.
+    fn exit_from<'a>(&self, room: &'a Room, direction: &Direction) -> Option<&'a Destination> {
+        let exit = room.exits.get(direction)?;
+        let found = self
+            .save_state
+            .room_inventories
+            .get(&room.coord)
+            .is_some_and(|room_inventory| room_inventory.found_exits.contains(direction));
+        (!exit.hidden || found).then_some(exit)
+    }
+
+    /// How well the player can see, which is the room's lighting, unless a light they
+    /// carry brightens a dim room.
+    fn light(&self) -> Lighting {
+        let carries_light = self
+            .save_state
+            .inventory
+            .items
+            .iter()
+            .any(|item| item.light);
+        match self.room.lighting {
+            Lighting::Dim if carries_light => Lighting::Normal,
+            lighting => lighting,
+        }
+    }
+
+    /// The level's weather right now, if it has a climate.
+    fn weather(&self) -> Option<Weather> {
+        let climate = self.level.climate.as_ref()?;
+        Some(self.save_state.weather.unwrap_or(climate.start))
+    }
+
+    /// Whether the weather hides the exits of the room the player is in.
+    fn exits_hidden(&self) -> bool {
+        let climate = match self.level.climate {
+            Some(ref climate) => climate,
+            None => return false,
+        };
+        self.room.outdoors
+            && self
+                .weather()
+                .is_some_and(|weather| climate.hides_exits.contains(&weather))
+    }
+
+    /// The weather that keeps the player from walking into a room, if it's outdoors.
+    fn weather_blocking(&self, to: &Coord) -> Option<Weather> {
+        let climate = self.level.climate.as_ref()?;
+        let weather = self.weather()?;
+        let outdoors = self.level.get_room(to).is_some_and(|room| room.outdoors);
+        (outdoors && climate.blocks_travel.contains(&weather)).then_some(weather)
+    }
+
+    /// Bring back the pets that are done fetching, with one of the item if it's still
+    /// where they went for it. They drop it at the player's feet. Pets sent off in
+    /// another level are left behind there, and wander back home.
+    fn run_fetches(&mut self) {
+        let turn = self.save_state.turn;
+        let level = self.save_state.level.clone();
+        self.save_state
+            .fetches
+            .retain(|_, fetch| fetch.level == level);
+        let done: Vec<String> = self
+            .save_state
+            .fetches
+            .iter()
+            .filter(|(_, fetch)| fetch.returns <= turn)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in done {
+            let fetch = self.save_state.fetches.remove(&id).unwrap();
+            let name = match self.level.npcs.get(&id) {
+                Some(npc) => &npc.name,
+                None => continue,
+            };
+            let here = self.save_state.coord;
+            self.save_state.npc_coords.insert(id, here);
+            let fetched = match self.level.get_room(&fetch.from) {
+                Some(room) => self
+                    .save_state
+                    .room_inventory_mut(room, &self.item_db)
+                    .take_one(&fetch.item),
+                None => None,
+            };
+            let text = match fetched {
+                Some(item) => {
+                    let noun = Noun::of(&item).the(&self.level.narrator, 1);
+                    let text = self
+                        .level
+                        .narrator
+                        .say_with("fetch-returns", &[("name", name), ("item", &noun)]);
+                    self.save_state
+                        .room_inventory_mut(&self.room, &self.item_db)
+                        .add_item(item);
+                    text
+                }
+                None => self
+                    .level
+                    .narrator
+                    .say_with("fetch-empty", &[("name", name)]),
+            };
+            let text = wrap(&text, self.width());
+            writeln!(self.output(), "{}\n", text).unwrap();
+        }
+    }
+
+    /// Grow back the items of rooms with spawns, every so many turns while there are
+    /// fewer than their most. The player sees them turn up in their own room.
+    fn run_spawns(&mut self) {
+        let mut appeared = Vec::new();
+        let here = self.save_state.coord;
+        for room in self.level.rooms.iter() {
+            for spawn in room.spawns.iter() {
+                let mut item = match self.item_db.find(&spawn.id) {
+                    Some(item) => item.clone(),
+                    None => continue,
+                };
+                let room_inventory = self.save_state.room_inventory_mut(room, &self.item_db);
+                let present: usize = room_inventory
+                    .inventory
+                    .iter()
+                    .filter(|(_, item)| item.id == spawn.id)
+                    .map(|(_, item)| item.quantity)
+                    .sum();
+                if present >= spawn.max {
+                    room_inventory.spawning.remove(&spawn.id);
+                    continue;
+                }
+                let turns = room_inventory.spawning.entry(spawn.id.clone()).or_insert(0);
+                *turns += 1;
+                if *turns < spawn.every.max(1) {
+                    continue;
+                }
+                room_inventory.spawning.remove(&spawn.id);
+                item.quantity = spawn.quantity.min(spawn.max - present);
+                if room.coord == here {
+                    appeared.push(Noun::of(&item).a(&self.level.narrator, item.quantity));
+                }
+                let existing = room_inventory
+                    .inventory
+                    .iter_mut()
+                    .find(|(room_item, existing)| existing.id == spawn.id && !room_item.hidden);
+                match existing {
+                    Some((_, existing)) => existing.quantity += item.quantity,
+                    None => room_inventory.add_item(item),
+                }
+            }
+        }
+        for item in appeared {
+            let text = self
+                .level
+                .narrator
+                .say_with("item-spawns", &[("item", &item)]);
+            let text = wrap(&text, self.width());
+            writeln!(self.output(), "{}\n", text).unwrap();
+        }
+    }
+
+    /// Change the weather, every so many turns, to one of the kinds that can follow it.
+    /// Players outdoors are told when it turns.
+    fn run_weather(&mut self) {
+        let climate = match self.level.climate {
+            Some(ref climate) => climate,
+            None => return,
+        };
+        let turn = self.save_state.turn + 1;
+        if !turn.is_multiple_of(climate.every.max(1)) {
+            return;
+        }
+        let weather = self.save_state.weather.unwrap_or(climate.start);
+        let mut rng =
+            Pcg32::seed_from_u64(self.save_state.seed ^ hash_str("weather") ^ turn as u64);
+        let next = match climate
+            .changes
+            .get(&weather)
+            .and_then(|changes| changes.choose(&mut rng))
+        {
+            Some(next) => *next,
+            None => return,
+        };
+        self.save_state.weather = Some(next);
+        if next == weather || !self.room.outdoors {
+            return;
+        }
+        if let Some(message) = climate.messages.get(&next) {
+            let text = wrap(self.text(message).trim_end(), self.width());
+            writeln!(self.output(), "{}\n", text).unwrap();
+            self.interrupted = true;
+        }
+    }
+
+    /// The values that templates and conditions are filled in from.
+    fn variables(&self) -> Variables<'_> {
+        Variables {
+            player_name: &self.config.name,
+            item_db: &self.item_db,
+            inventory: &self.save_state.inventory.items,
+            flags: &self.save_state.flags,
+            stats: &self.save_state.stats,
+            reputation: &self.save_state.reputation,
+            solved: &self.save_state.solved,
+            companions: &self.save_state.companions,
+            party: &self.save_state.party,
+            timers: &self.save_state.timers,
+            light: self.light(),
+            weather: self.weather(),
+            date: self.date(),
+            today: self.today,
+        }
+    }
+
+    /// Today's date in the campaign's calendar, if it has one. The player's `season`
+    /// setting wins over the calendar's own season.
+    fn date(&self) -> Option<Date<'_>> {
+        let calendar = self.campaign.as_ref()?.calendar.as_ref()?;
+        let mut date = calendar.date(self.save_state.clock, self.save_state.playthrough);
+        if let Some(ref season) = self.config.season {
+            date.season = season;
+        } else if let (true, Some(today)) = (calendar.real_seasons, self.today) {
+            date.season = calendar.real_season(today);
+        }
+        Some(date)
+    }
+
+    /// Whether a condition holds, which it always does when there isn't one.
+    fn holds(&self, condition: &Option<Condition>) -> bool {
+        condition
+            .as_ref()
+            .is_none_or(|condition| condition.holds(&self.variables()))
+    }
+
+    /// The warning for a command that can't be taken back, when it would take a point
+    /// of no return, or use an action that's marked as irreversible.
+    fn irreversible(&self, command: &ParsedCommand) -> Option<&Passage> {
+        let action = match command {
+            ParsedCommand::Move(direction) => {
+                return self
+                    .exit(direction)
+                    .filter(|exit| self.holds(&exit.when))
+                    .and_then(|exit| exit.irreversible.as_ref())
+            }
+            ParsedCommand::Look(Some(target)) => self.find_action(Verb::Look, target, None),
+            ParsedCommand::Talk(Some(target)) => self.find_action(Verb::Talk, target, None),
+            ParsedCommand::Help(Some(target)) => self.find_action(Verb::Help, target, None),
+            ParsedCommand::Custom(command, Some(target)) => {
+                self.find_action(Verb::Custom, target, Some(command))
+            }
+            ParsedCommand::Read(target) => {
+                self.find_action(Verb::Custom, target, Some(&"read".to_string()))
+            }
+            ParsedCommand::Shift(shift, target) => self.find_action(
+                Verb::Custom,
+                target,
+                Some(&shift.lowercase_str().to_string()),
+            ),
+            ParsedCommand::Emote(emote, Some(target)) => self.find_action(
+                Verb::Emote,
+                target,
+                Some(&emote.lowercase_string().to_string()),
+            ),
+            _ => None,
+        }?;
+        action.irreversible.as_ref().or_else(|| {
+            action
+                .teleport
+                .as_ref()
+                .and_then(|destination| destination.irreversible.as_ref())
+        })
+    }
+
+    /// Find the action in the player's room for a command, skipping the actions whose
+    /// conditions don't hold.
+    fn find_action(&self, verb: Verb, target: &str, alias: Option<&String>) -> Option<&Action> {
+        self.room
+            .find_action(verb, target, &self.level, alias, |action| {
+                self.holds(&action.when)
+            })
+    }
+
+    /// Pick which alternative of a pool to show. The pick is seeded by the game and
+    /// the turn, so replaying a game shows the same text. Passages that aren't pools
+    /// are shown as they are.
+    fn pick<'p>(&self, passage: &'p Passage) -> &'p Passage {
+        let (alternatives, repeat) = match passage.pool() {
+            Some((alternatives, repeat)) if !alternatives.is_empty() => (alternatives, repeat),
+            _ => return passage,
+        };
+        let key = hash_str(&serde_yaml::to_string(passage).unwrap_or_default());
+        let mut rng =
+            Pcg32::seed_from_u64(self.save_state.seed ^ key ^ self.save_state.turn as u64);
+        let index = if repeat {
+            rng.gen_range(0..alternatives.len())
+        } else {
+            let mut shown_passages = self.save_state.shown_passages.borrow_mut();
+            let shown = shown_passages.entry(key).or_default();
+            if shown.len() >= alternatives.len() {
+                shown.clear();
+            }
+            let unshown: Vec<usize> = (0..alternatives.len())
+                .filter(|index| !shown.contains(index))
+                .collect();
+            let index = unshown[rng.gen_range(0..unshown.len())];
+            shown.insert(index);
+            index
+        };
+        self.pick(&alternatives[index])
+    }
+
+    /// Where an NPC is, which is the room that lists it, unless it has moved.
+    fn npc_coord(&self, id: &str) -> Option<Coord> {
+        if let Some(coord) = self.save_state.npc_coords.get(id) {
+            return Some(*coord);
+        }
+        self.level
+            .rooms
+            .iter()
+            .find(|room| room.npcs.iter().any(|npc| npc == id))
+            .map(|room| room.coord)
+    }
+
+    /// The NPCs in a room, with their ids. The ones that the room lists come first,
+    /// unless they have moved away, then the ones that have moved in.
+    fn npcs_at(&self, coord: &Coord) -> Vec<(&str, &NPC)> {
+        let npc_coords = &self.save_state.npc_coords;
+        let mut ids: Vec<&String> = match self.level.get_room(coord) {
+            Some(room) => room
+                .npcs
+                .iter()
+                .filter(|id| npc_coords.get(*id).is_none_or(|moved| moved == coord))
+                .collect(),
+            None => Vec::new(),
+        };
+        for (id, moved) in npc_coords.iter() {
+            if moved == coord && !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        ids.into_iter()
+            .filter_map(|id| Some((id.as_str(), self.level.npcs.get(id)?)))
+            .collect()
+    }
+
+    /// The level's tile that a room is drawn with, if it is, see `Tile`.
+    fn tile(&self, coord: &Coord) -> Option<&Tile> {
+        let ch = self.room_graph.tile(coord)?;
+        self.level.tiles.get(&ch)
+    }
+
+    /// Show the message of the tile the player just entered, and take its damage.
+    fn cross_tile(&self) {
+        let tile = match self.tile(&self.save_state.coord) {
+            Some(tile) => tile,
+            None => return,
+        };
+        if let Some(ref message) = tile.message {
+            let text = wrap(self.text(message).trim_end(), self.width());
+            writeln!(self.output(), "{}\n", text).unwrap();
+        }
+        if tile.damage > 0 {
+            let damage = BTreeMap::from([("health".to_string(), -(tile.damage as i64))]);
+            self.effects.borrow_mut().push(Effect::Stats(damage));
+        }
+    }
+
+    /// The vehicle the player is aboard, with its id.
+    fn vehicle(&self) -> Option<(&str, &Vehicle)> {
+        let id = self.save_state.aboard.as_ref()?;
+        let vehicle = self.level.vehicles.get(id)?;
+        Some((id.as_str(), vehicle))
+    }
+
+    /// The vehicles in a room that the player isn't aboard, with their ids. They are
+    /// in the room that lists them, until the player rides them somewhere else.
+    fn vehicles_at(&self, coord: &Coord) -> Vec<(&str, &Vehicle)> {
+        self.level
+            .vehicles
+            .iter()
+            .filter(|(id, _)| self.save_state.aboard.as_ref() != Some(*id))
+            .filter(|(id, _)| {
+                let coord_of = |id: &String| match self.save_state.vehicle_coords.get(id) {
+                    Some(moved) => Some(*moved),
+                    None => self
+                        .level
+                        .rooms
+                        .iter()
+                        .find(|room| room.vehicles.contains(id))
+                        .map(|room| room.coord),
+                };
+                coord_of(id).as_ref() == Some(coord)
+            })
+            .map(|(id, vehicle)| (id.as_str(), vehicle))
+            .collect()
+    }
+
+    /// Why the player can't go from one room into the next because of the terrain, or
+    /// None if they can. Water can't be walked into, and aboard a vehicle, the player
+    /// can only go into its terrain, or out of it onto land. Tiles can have a condition.
+    fn terrain_blocking(&self, from: &Coord, to: &Coord) -> Option<String> {
+        if let Some(tile) = self.tile(to) {
+            if !self.holds(&tile.when) {
+                return Some(match tile.blocked {
+                    Some(ref blocked) => self.text(blocked),
+                    None => self.level.narrator.say("tile-blocked"),
+                });
+            }
+        }
+        let (from, to) = (self.room_graph.terrain(from), self.room_graph.terrain(to));
+        let vehicle = match self.vehicle() {
+            Some((_, vehicle)) => vehicle,
+            None if to == Terrain::Water => return Some(self.level.narrator.say("water-blocked")),
+            None => return None,
+        };
+        if vehicle.terrain.contains(&to) || (vehicle.terrain.contains(&from) && to == Terrain::Land)
+        {
+            return None;
+        }
+        Some(match vehicle.blocked {
+            Some(ref blocked) => self.text(blocked),
+            None => self
+                .level
+                .narrator
+                .say_with("vehicle-blocked", &[("name", &vehicle.name)]),
+        })
+    }
+
+    /// Get off the vehicle the player is aboard, leaving it in their room.
+    fn leave_vehicle(&mut self) {
+        if let Some(id) = self.save_state.aboard.take() {
+            self.save_state
+                .vehicle_coords
+                .insert(id, self.save_state.coord);
+        }
+    }
+
+    /// Find an NPC in the player's room.
+    fn get_npc(&self, target: &str) -> Option<&NPC> {
+        self.find_npc(target).map(|(_, npc)| npc)
+    }
+
+    /// Find an NPC in the player's room, along with their id.
+    fn find_npc(&self, target: &str) -> Option<(&str, &NPC)> {
+        self.npcs_at(&self.room.coord)
+            .into_iter()
+            .find(|(_, npc)| self.level.vocabulary.matches(npc.targets.iter(), target))
+    }
+
+    /// The NPC the player is in a conversation with, as long as they are still in the
+    /// player's room.
+    fn conversation_npc(&self) -> Option<&NPC> {
+        let id = self.conversation.borrow().clone()?;
+        let npc = self
+            .npcs_at(&self.room.coord)
+            .into_iter()
+            .find(|(other, _)| *other == id)
+            .map(|(_, npc)| npc);
+        if npc.is_none() {
+            self.conversation.replace(None);
+        }
+        npc
+    }
+
+    /// The topics an NPC can be asked about right now.
+    fn topics<'n>(&self, npc: &'n NPC) -> Vec<&'n Topic> {
+        npc.topics
+            .iter()
+            .filter(|topic| self.holds(&topic.when))
+            .collect()
+    }
+
+    /// The NPC the player is in a conversation with, along with their id.
+    fn conversation_partner(&self) -> Option<(&str, &NPC)> {
+        let npc = self.conversation_npc()?;
+        let id = self.conversation.borrow().clone()?;
+        self.level
+            .npcs
+            .get_key_value(&id)
+            .map(|(id, _)| (id.as_str(), npc))
+    }
+
+    /// Read the input as part of a conversation, if there is one. Topics can be typed
+    /// on their own, or picked by their number, and input that isn't a command is
+    /// taken as a topic too. Anything else is run as usual.
+    fn converse(&self, input: &str) -> Option<ParsedCommand> {
+        let npc = self.conversation_npc()?;
+        let input = input.trim().to_lowercase();
+        let topics = self.topics(npc);
+        let vocabulary = &self.level.vocabulary;
+        if let Ok(number) = input.parse::<usize>() {
+            let target = match number.checked_sub(1).and_then(|index| topics.get(index)) {
+                Some(topic) => vocabulary.noun(topic.targets.first()?),
+                None => &input,
+            };
+            return Some(ParsedCommand::Ask(None, target.to_string()));
+        }
+        let target = vocabulary.noun(&input);
+        if topics
+            .iter()
+            .any(|topic| vocabulary.matches(topic.targets.iter(), target))
+        {
+            return Some(ParsedCommand::Ask(None, target.to_string()));
+        }
+        match parse_command(input.clone(), vocabulary, &self.level.narrator) {
+            Ok(commands) => match commands.as_slice() {
+                [ParsedCommand::Custom(verb, _)]
+                    if !self
+                        .room
+                        .actions_iter(&self.level)
+                        .any(|action| action.alias.as_ref() == Some(verb)) =>
+                {
+                    Some(ParsedCommand::Ask(None, target.to_string()))
+                }
+                _ => None,
+            },
+            Err(_) => None,
+        }
+    }
+
+    /// The faction an NPC belongs to, if it's one the level defines.
+    fn faction(&self, npc: &NPC) -> Option<(&str, &Faction)> {
+        let (id, faction) = self.level.factions.get_key_value(npc.faction.as_ref()?)?;
+        Some((id, faction))
+    }
+
+    fn reputation(&self, faction: &str) -> i64 {
+        self.save_state
+            .reputation
+            .get(faction)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Whether the player can get to their stash here, which is at a bank room, or with
+    /// a banker who will deal with them.
+    fn at_bank(&self) -> bool {
+        self.room.bank
+            || self
+                .npcs_at(&self.room.coord)
+                .into_iter()
+                .any(|(_, npc)| npc.bank && !self.is_hostile(npc))
+    }
+
+    /// Whether an NPC's faction thinks too little of the player to talk or trade.
+    fn is_hostile(&self, npc: &NPC) -> bool {
+        self.faction(npc).is_some_and(|(_, faction)| {
+            faction
+                .hostile_when
+                .as_ref()
+                .is_some_and(|condition| condition.holds(&self.variables()))
+        })
+    }
+
+    /// What an NPC charges for an item, after their faction's discount or markup, and
+    /// then the prices of the regions the player is trading in.
+    fn price(&self, npc: &NPC, cost: usize) -> usize {
+        let percent = self.faction(npc).and_then(|(id, faction)| {
+            faction
+                .prices
+                .range(..=self.reputation(id))
+                .next_back()
+                .map(|(_, percent)| *percent)
+        });
+        let cost = match percent {
+            Some(percent) => cost * percent / 100,
+            None => cost,
+        };
+        let variables = self.variables();
+        self.room
+            .regions
+            .iter()
+            .filter_map(|id| self.level.regions.get(id))
+            .filter_map(|region| {
+                region
+                    .prices
+                    .iter()
+                    .find(|price| price.when.holds(&variables))
+            })
+            .fold(cost, |cost, price| cost * price.percent / 100)
+    }
+
+    /// The least an NPC comes down to from a price when haggling. Stubborn NPCs hardly
+    /// come down at all, and a good reputation with their faction helps.
+    fn lowest_price(&self, npc: &NPC, price: usize) -> usize {
+        let reputation = self.faction(npc).map_or(0, |(id, _)| self.reputation(id));
+        let discount = (100 - npc.stubbornness.min(100)) / 2 + reputation.clamp(0, 25) as usize;
+        price * (100 - discount.min(90)) / 100
+    }
+
+    /// Whether an NPC is still too insulted by an offer to trade.
+    fn refuses(&self, id: &str) -> bool {
+        self.save_state
+            .refusals
+            .get(id)
+            .is_some_and(|turn| self.save_state.turn < *turn)
+    }
+
+    /// What an NPC asks for an item right now, which is less if the player haggled.
+    fn asking_price(&self, id: &str, npc: &NPC, item: &InventoryItem, cost: usize) -> usize {
+        match self.haggle {
+            Some(ref haggle) if haggle.npc == id && haggle.item == item.id => haggle.price,
+            _ => self.price(npc, cost),
+        }
+    }
+
+    /// Move the NPCs that wander or follow the player, after the player's turn.
+    /// Followers and tame pets only come along when they were in the room the player
+    /// just left, while the party goes wherever the player goes, even to another level.
+    /// The player is told about anyone who comes or goes from their room.
+    fn move_npcs(&mut self, from: Coord, same_level: bool) {
+        let player = self.save_state.coord;
+        let narrator = &self.level.narrator;
+        let variables = self.variables();
+        let mut ids: Vec<&String> = self.level.npcs.keys().collect();
+        ids.sort();
+        let mut moves = Vec::new();
+        let mut lines = Vec::new();
+        for id in ids {
+            let npc = &self.level.npcs[id];
+            let coord = self.npc_coord(id);
+            if self.save_state.party.contains(id) {
+                if coord != Some(player) {
+                    lines.push(narrator.say_with("npc-follows", &[("name", &npc.name)]));
+                    moves.push((id.clone(), player));
+                }
+                continue;
+            }
+            let coord = match coord {
+                Some(coord) => coord,
+                None => continue,
+            };
+            // Escorts keep up with the player on their own, see `run_escorts`.
+            if self.is_escorted(id) {
+                continue;
+            }
+            // Tame pets follow the player, unless they're off fetching something.
+            if self.save_state.tamed.contains(id) {
+                if same_level
+                    && coord == from
+                    && coord != player
+                    && !self.save_state.fetches.contains_key(id)
+                {
+                    lines.push(narrator.say_with("npc-follows", &[("name", &npc.name)]));
+                    moves.push((id.clone(), player));
+                }
+                continue;
+            }
+            match npc.movement {
+                Movement::Static => {}
+                Movement::Follow { ref when } => {
+                    if same_level && coord == from && coord != player && when.holds(&variables) {
+                        lines.push(narrator.say_with("npc-follows", &[("name", &npc.name)]));
+                        moves.push((id.clone(), player));
+                    }
+                }
+                Movement::Wander { ref region, chance } => {
+                    let mut rng = Pcg32::seed_from_u64(
+                        self.save_state.seed ^ hash_str(id) ^ self.save_state.turn as u64,
+                    );
+                    if !rng.gen_bool(chance.clamp(0.0, 1.0)) {
+                        continue;
+                    }
+                    let choices: Vec<(Direction, Coord)> = self
+                        .room_graph
+                        .neighbors(&coord)
+                        .filter(|(_, next)| {
+                            self.level
+                                .get_room(next)
+                                .is_some_and(|room| room.regions.contains(region))
+                        })
+                        .collect();
+                    let (direction, next) = match choices.choose(&mut rng) {
+                        Some(choice) => *choice,
+                        None => continue,
+                    };
+                    if coord == player {
+                        lines.push(narrator.say_with(
+                            "npc-leaves",
+                            &[
+                                ("name", &npc.name),
+                                ("direction", direction.lowercase_string()),
+                            ],
+                        ));
+                    } else if next == player {
+                        lines.push(narrator.say_with(
+                            "npc-arrives",
+                            &[
+                                ("name", &npc.name),
+                                ("direction", direction.opposite().lowercase_string()),
+                            ],
+                        ));
+                    }
+                    moves.push((id.clone(), next));
+                }
+            }
+        }
+        for line in lines {
+            let line = wrap(&line, self.width());
+            writeln!(self.output(), "{}\n", line).unwrap();
+        }
+        self.save_state.npc_coords.extend(moves);
+    }
+
+    /// Style some text with the environment's theme. This borrows the environment,
+    /// so style the text before calling `output`.
+    fn styled(&self, style: Style, text: &str) -> String {
+        let text = &self.heading(style, text);
+        match self.environment.borrow().theme() {
+            Some(theme) => theme.apply(style, text),
+            None => text.to_string(),
+        }
+    }
+
+    /// Style some text, but override the theme's color.
+    fn styled_with(&self, style: Style, color: Option<Color>, text: &str) -> String {
+        let text = &self.heading(style, text);
+        match self.environment.borrow().theme() {
+            Some(theme) => {
+                let mut text_style = theme.text_style(style).clone();
+                if color.is_some() {
+                    text_style.color = color;
+                }
+                text_style.apply(text)
+            }
+            None => text.to_string(),
+        }
+    }
+
+    /// Apply the player's heading filters to a title.
+    fn heading(&self, style: Style, text: &str) -> String {
+        match style {
+            Style::Title => text_filter::heading(&self.config.text_filters, text),
+            _ => text.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveState {
+    /// The current room coordinate.
+    coord: Coord,
+    /// Turn on debug logging.
+    debug: bool,
+    /// The player's inventory.
+    inventory: Inventory,
+    /// The seed the game was started with, used to resolve anything random.
+    #[serde(default)]
+    seed: u64,
+    /// The number of turns that have been taken.
+    #[serde(default)]
+    turn: usize,
+    /// The content tags the player has chosen to filter out.
+    #[serde(default)]
+    content_filter: BTreeSet<String>,
+    /// How many turns it took to reach the level's par goal.
+    #[serde(default)]
+    par_turns: Option<usize>,
+    /// The id of the campaign level the player is in.
+    #[serde(default)]
+    level: String,
+    /// The id of the scenario the game began with.
+    #[serde(default)]
+    scenario: Option<String>,
+    #[serde(default)]
+    score: usize,
+    /// The ids of the awards that have been given, so each is only given once.
+    #[serde(default)]
+    awards: BTreeSet<String>,
+    /// The last checkpoint room reached in this level, where the player respawns.
+    #[serde(default)]
+    checkpoint: Option<Coord>,
+    /// The room inventories of the other levels the player has visited.
+    #[serde(default)]
+    level_inventories: BTreeMap<String, BTreeMap<Coord, RoomInventory>>,
+    /// What the player has done in the other levels they have visited, see
+    /// `LevelState`.
+    #[serde(default)]
+    level_states: BTreeMap<String, LevelState>,
+    /// The player's stats, like their health.
+    #[serde(default)]
+    stats: BTreeMap<String, i64>,
+    /// Facts about the player's progress.
+    #[serde(default)]
+    flags: BTreeSet<String>,
+    /// For each pool of passages that doesn't repeat, the alternatives that have been
+    /// shown, keyed by a hash of the pool.
+    #[serde(default)]
+    shown_passages: RefCell<BTreeMap<u64, BTreeSet<usize>>>,
+    /// Where the NPCs that have moved from their rooms are, in the current level.
+    #[serde(default)]
+    npc_coords: BTreeMap<String, Coord>,
+    /// The daemons of the current level that have started or been enabled or disabled,
+    /// by id.
+    #[serde(default)]
+    daemons: BTreeMap<String, DaemonState>,
+    /// The timers of the current level that are armed, by id, with how many turns they
+    /// have left.
+    #[serde(default)]
+    timers: BTreeMap<String, usize>,
+    /// Where the vehicles are that have been ridden away from their rooms, in the
+    /// current level.
+    #[serde(default)]
+    vehicle_coords: BTreeMap<String, Coord>,
+    /// The id of the vehicle the player is aboard, see `Vehicle`.
+    #[serde(default)]
+    aboard: Option<String>,
+    /// The player's reputation with each faction they have dealt with, by id.
+    #[serde(default)]
+    reputation: BTreeMap<String, i64>,
+    /// The turns that have passed in the campaign's calendar. Unlike the turn, this
+    /// carries on through New Game Plus.
+    #[serde(default)]
+    clock: usize,
+    /// How many times the campaign has been played, counting New Game Plus.
+    #[serde(default = "first_playthrough")]
+    playthrough: usize,
+    /// The statuses the player has right now, like poison, by id.
+    #[serde(default)]
+    status_effects: BTreeMap<String, StatusEffect>,
+    /// The ids of the hazards that have been disarmed in the current level, see
+    /// `Hazard`.
+    #[serde(default)]
+    disarmed: BTreeSet<String>,
+    /// The ids of the puzzles that have been solved in the current level, see
+    /// `Puzzle`.
+    #[serde(default)]
+    solved: BTreeSet<String>,
+    /// How many steps of each sequence puzzle in the current level have been taken
+    /// in order, by id.
+    #[serde(default)]
+    puzzle_steps: BTreeMap<String, usize>,
+    /// The quests the player has started, by id, see `Quest`.
+    #[serde(default)]
+    quests: BTreeMap<String, QuestState>,
+    /// The ids of the lore entries the player has unlocked, see `Lore`.
+    #[serde(default)]
+    lore: BTreeSet<String>,
+    /// The items the player has left at a bank, which can be taken out at any bank.
+    #[serde(default)]
+    stash: Inventory,
+    /// The items companions are carrying for the player, by the NPC's id.
+    #[serde(default)]
+    companions: BTreeMap<String, Vec<InventoryItem>>,
+    /// The level's weather, or None for the weather its climate starts with.
+    #[serde(default)]
+    weather: Option<Weather>,
+    /// The NPCs that won't trade after an insulting offer, by id, with the turn they
+    /// trade again.
+    #[serde(default)]
+    refusals: BTreeMap<String, usize>,
+    /// The ids of the NPCs in the player's party, see `Companion`.
+    #[serde(default)]
+    party: BTreeSet<String>,
+    /// The comments that each party member has made, by the NPC's id, with the room
+    /// ids or entries they were about.
+    #[serde(default)]
+    remarks: BTreeMap<String, BTreeSet<String>>,
+    /// The ids of the animals the player has tamed, see `Pet`.
+    #[serde(default)]
+    tamed: BTreeSet<String>,
+    /// The pets that are off fetching an item, by the NPC's id.
+    #[serde(default)]
+    fetches: BTreeMap<String, Fetch>,
+    room_inventories: BTreeMap<Coord, RoomInventory>,
+}
+
+impl SaveState {
+    /// Put away the room inventories and the level state of the current level, and
+    /// bring out the ones for the next level, if it has been visited before.
+    fn switch_level(&mut self, level_id: &str) {
+        let room_inventories = self.level_inventories.remove(level_id).unwrap_or_default();
+        let previous = std::mem::replace(&mut self.room_inventories, room_inventories);
+        let mut level_state = self.level_states.remove(level_id).unwrap_or_default();
+        self.swap_level_state(&mut level_state);
+        let previous_id = std::mem::replace(&mut self.level, level_id.to_string());
+        self.level_inventories.insert(previous_id.clone(), previous);
+        self.level_states.insert(previous_id, level_state);
+        // Checkpoints are rooms of a single level, and NPCs go back to their rooms.
+        self.checkpoint = None;
+        self.npc_coords.clear();
+        // Pets that are off fetching are left behind, and wander back home.
+        self.fetches.clear();
+        // Vehicles are left behind.
+        self.vehicle_coords.clear();
+        self.aboard = None;
+        // Each level has its own climate.
+        self.weather = None;
+    }
+
+    /// Swap the state of the current level with the state of another, see `LevelState`.
+    fn swap_level_state(&mut self, other: &mut LevelState) {
+        std::mem::swap(&mut self.disarmed, &mut other.disarmed);
+        std::mem::swap(&mut self.solved, &mut other.solved);
+        std::mem::swap(&mut self.puzzle_steps, &mut other.puzzle_steps);
+        std::mem::swap(&mut self.daemons, &mut other.daemons);
+        std::mem::swap(&mut self.timers, &mut other.timers);
+    }
+
+    /// Get the inventory of a room. Saves from before a room was added to the level
+    /// won't have its inventory yet, so it's created from the level when needed.
+    fn room_inventory_mut(&mut self, room: &Room, item_db: &ItemDatabase) -> &mut RoomInventory {
+        self.room_inventories
+            .entry(room.coord)
+            .or_insert_with(|| RoomInventory::new(room, item_db))
+    }
+}
+
+/// What the player has done in a level that is kept by the level's own ids, which
+/// another level could use for something else. The current level's is kept in the
+/// `SaveState` itself, and the others are put away until the player goes back.
+#[derive(Default, Serialize, Deserialize)]
+struct LevelState {
+    /// The ids of the hazards that have been disarmed, see `Hazard`.
+    #[serde(default)]
+    disarmed: BTreeSet<String>,
+    /// The ids of the puzzles that have been solved, see `Puzzle`.
+    #[serde(default)]
+    solved: BTreeSet<String>,
+    /// How many steps of each sequence puzzle have been taken in order, by id.
+    #[serde(default)]
+    puzzle_steps: BTreeMap<String, usize>,
+    /// How far along each daemon is, by id, see `Daemon`.
+    #[serde(default)]
+    daemons: BTreeMap<String, DaemonState>,
+    /// The turns left on each armed timer, by id, see `Timer`.
+    #[serde(default)]
+    timers: BTreeMap<String, usize>,
+}
+
+/// Where haggling with an NPC over an item has got to, see `offer_command`.
+struct Haggle {
+    npc: String,
+    item: String,
+    /// The price the NPC last asked.
+    price: usize,
+}
+
+/// A pet that is off fetching an item, see `fetch_command`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Fetch {
+    /// The id of the item.
+    item: String,
+    /// The level the pet was sent off in.
+    #[serde(default)]
+    level: String,
+    /// The room the item is in.
+    from: Coord,
+    /// The turn the pet comes back on.
+    returns: usize,
+}
+
+/// How far along a daemon is, see `Daemon`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct DaemonState {
+    enabled: bool,
+    /// The turns since it was enabled.
+    #[serde(default)]
+    turns: usize,
+    /// How many times it has run since it was enabled.
+    #[serde(default)]
+    runs: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RoomInventory {
+    inventory: Vec<(RoomItem, InventoryItem)>,
+    /// The secret exits that the player has found, see `Destination`.
+    #[serde(default)]
+    found_exits: BTreeSet<Direction>,
+    /// The ids of the scenery that the player has moved, see `Scenery`.
+    #[serde(default)]
+    moved: BTreeSet<String>,
+    /// Whether the player has been in the room, so they can walk back with "go to".
+    #[serde(default)]
+    visited: bool,
+    /// The turns counted toward the next of each spawning item, by id, see `Spawn`.
+    #[serde(default)]
+    spawning: BTreeMap<String, usize>,
+}
+
+impl RoomInventory {
+    /// Create the inventory for a room as it is defined in the level.
+    fn new(room: &Room, item_db: &ItemDatabase) -> RoomInventory {
+        // Fill the room item in with the actual item from the item db.
+        let inventory = room
+            .items
+            .iter()
+            .map(|room_item| {
+                let mut inventory_item = item_db.get(&room_item.id).clone();
+                inventory_item.quantity = room_item.quantity;
+                (room_item.clone(), inventory_item)
+            })
+            .collect();
+        RoomInventory {
+            inventory,
+            found_exits: BTreeSet::new(),
+            moved: BTreeSet::new(),
+            visited: false,
+            spawning: BTreeMap::new(),
+        }
+    }
+
+    /// Take an item out of the room. Hidden items can't be taken until they are found.
+    pub fn take_item(
+        &mut self,
+        id: &str,
+        vocabulary: &Vocabulary,
+    ) -> Option<(RoomItem, InventoryItem)> {
+        let mut inventory = Vec::new();
+        let mut found_item = None;
+        for item in self.inventory.drain(..) {
+            let (ref room_item, ref inventory_item) = item;
+            if found_item.is_some() || room_item.hidden {
+                inventory.push(item);
+            } else if vocabulary.matches(room_item.targets.iter(), id)
+                || vocabulary.matches(inventory_item.targets.iter(), id)
+            {
+                found_item = Some(item);
+            } else {
+                inventory.push(item);
+            }
+        }
+        self.inventory = inventory;
+        found_item
+    }
+
+    /// Take one of an item out of the room by id, as long as it isn't hidden.
+    fn take_one(&mut self, id: &str) -> Option<InventoryItem> {
+        let index = self
+            .inventory
+            .iter()
+            .position(|(room_item, item)| item.id == id && !room_item.hidden)?;
+        let item = &mut self.inventory[index].1;
+        if item.quantity <= 1 {
+            return Some(self.inventory.remove(index).1);
+        }
+        item.quantity -= 1;
+        let mut taken = item.clone();
+        taken.quantity = 1;
+        Some(taken)
+    }
+
+    fn add_item(&mut self, inventory_item: InventoryItem) {
+        self.inventory
+            .push((RoomItem::from(&inventory_item), inventory_item));
+    }
+
+    /// The items that can be seen, which leaves out the hidden ones that haven't been
+    /// found.
+    pub fn visible_iter(&self) -> impl Iterator<Item = &(RoomItem, InventoryItem)> {
+        self.inventory
+            .iter()
+            .filter(|(room_item, _)| !room_item.hidden)
+    }
+}
+
+impl SaveState {
+    /// Bring a save up to date with the level and items, which may have changed since
+    /// it was made. Items are refreshed from the item database, anything that no longer
+    /// exists is removed, and added rooms are given their inventories. A warning is
+    /// returned for everything the player lost.
+    fn reconcile(&mut self, level: &Level, item_db: &ItemDatabase) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        let narrator = &level.narrator;
+        if level.get_room(&self.coord).is_none() {
+            warnings.push(narrator.say("save-room-gone"));
+            self.coord = level.entry;
+        }
+
+        self.inventory.items.retain_mut(|item| {
+            let exists = refresh_item(item, item_db);
+            if !exists {
+                warnings.push(narrator.say_with("save-item-gone", &[("item", &item.name)]));
+            }
+            exists
+        });
+
+        let stash = std::iter::once(&mut self.stash.items);
+        for items in stash.chain(self.companions.values_mut()) {
+            items.retain_mut(|item| {
+                let exists = refresh_item(item, item_db);
+                if !exists {
+                    warnings.push(narrator.say_with("save-item-gone", &[("item", &item.name)]));
+                }
+                exists
+            });
+        }
+
+        self.room_inventories.retain(|coord, room_inventory| {
+            let room = match level.get_room(coord) {
+                Some(room) => room,
+                None => {
+                    let coord = coord.to_string();
+                    warnings.push(narrator.say_with("save-room-removed", &[("coord", &coord)]));
+                    return false;
+                }
+            };
+            room_inventory.inventory.retain_mut(|(_, item)| {
+                let exists = refresh_item(item, item_db);
+                if !exists {
+                    warnings.push(narrator.say_with(
+                        "save-room-item-gone",
+                        &[("item", &item.name), ("room", &room.title)],
+                    ));
+                }
+                exists
+            });
+            true
+        });
+
+        for room in level.rooms.iter() {
+            self.room_inventory_mut(room, item_db);
+        }
+
+        warnings
+    }
+
+    /// Set up a new game for a scenario, in place of the level's own start.
+    fn begin_scenario(&mut self, scenario: &Scenario, level: &Level, item_db: &ItemDatabase) {
+        self.scenario = Some(scenario.id.clone());
+        self.coord = scenario.entry.unwrap_or(level.entry);
+        if let Some(ref start) = scenario.start {
+            self.inventory = Inventory::starting(start, item_db);
+            self.stats = level.mechanics.starting_stats(&start.stats);
+            self.flags = start.flags.clone();
+        }
+    }
+
+    fn initialize(item_db: &ItemDatabase, level: &Level, seed: u64) -> SaveState {
+        SaveState {
+            coord: level.entry,
+            seed,
+            debug: false,
+            turn: 0,
+            content_filter: BTreeSet::new(),
+            par_turns: None,
+            level: String::new(),
+            scenario: None,
+            score: 0,
+            awards: BTreeSet::new(),
+            checkpoint: None,
+            level_inventories: BTreeMap::new(),
+            level_states: BTreeMap::new(),
+            stats: level.mechanics.starting_stats(&level.start.stats),
+            flags: level.start.flags.clone(),
+            inventory: Inventory::starting(&level.start, item_db),
+            shown_passages: RefCell::new(BTreeMap::new()),
+            npc_coords: BTreeMap::new(),
+            daemons: BTreeMap::new(),
+            timers: BTreeMap::new(),
+            vehicle_coords: BTreeMap::new(),
+            aboard: None,
+            reputation: BTreeMap::new(),
+            clock: 0,
+            playthrough: first_playthrough(),
+            status_effects: BTreeMap::new(),
+            disarmed: BTreeSet::new(),
+            solved: BTreeSet::new(),
+            puzzle_steps: BTreeMap::new(),
+            quests: BTreeMap::new(),
+            lore: BTreeSet::new(),
+            stash: Inventory::default(),
+            companions: BTreeMap::new(),
+            weather: None,
+            refusals: BTreeMap::new(),
+            party: BTreeSet::new(),
+            remarks: BTreeMap::new(),
+            tamed: BTreeSet::new(),
+            fetches: BTreeMap::new(),
+            room_inventories: level
+                .rooms
+                .iter()
+                .map(|room| (room.coord, RoomInventory::new(room, item_db)))
+                .collect(),
+        }
+    }
+}
+
+/// How a quest the player has started is going, see `Quest`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct QuestState {
+    /// The first turn that counts against the quest's deadline, which is the one after
+    /// it started.
+    started: usize,
+    /// How it ended, or None while the player is still on it.
+    #[serde(default)]
+    outcome: Option<QuestOutcome>,
+    /// How many turns in a row the NPC being escorted has been away from the player.
+    #[serde(default)]
+    behind: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum QuestOutcome {
+    Done,
+    Late,
+    Failed,
+}
+
+/// A status the player has, see `level::Status`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct StatusEffect {
+    /// The turns left until it wears off.
+    turns: usize,
+    stacks: usize,
+}
+
+fn first_playthrough() -> usize {
+    1
+}
+
+/// What a New Game Plus keeps from the last game, see `Calendar::new_game_plus`.
+#[derive(Serialize, Deserialize)]
+struct NewGamePlus {
+    clock: usize,
+    playthrough: usize,
+}
+
+/// Replace a saved item with the latest version from the item database, keeping its
+/// quantity. This returns false if the item no longer exists.
+fn refresh_item(item: &mut InventoryItem, item_db: &ItemDatabase) -> bool {
+    match item_db.find(&item.id) {
+        Some(latest) => {
+            *item = InventoryItem {
+                quantity: item.quantity,
+                ..latest.clone()
+            };
+            true
+        }
+        None => false,
+    }
+}
+
+/// Let the player know what was lost when their save was brought up to date.
+fn print_save_warnings(output: &mut impl Write, narrator: &Narrator, warnings: &[String]) {
+    if warnings.is_empty() {
+        return;
+    }
+    writeln!(output, "{}", narrator.say("save-changed")).unwrap();
+    for warning in warnings {
+        writeln!(output, "  ‣ {}", warning).unwrap();
+    }
+    writeln!(output).unwrap();
+}
+
+pub enum GameLoopResponse {
+    Restart,
+    Quit,
+    /// The player died, and wants to go back to their last save.
+    Death,
+}
+
+/// Run a game until the player quits or restarts, prompting the environment for
+/// input. An error is returned if the game couldn't be loaded.
+pub fn game_loop<T: Environment>(
+    item_db: &ItemDatabase,
+    environment: T,
+) -> Result<GameLoopResponse, String> {
+    let mut game = Game::new(item_db.clone(), environment)?;
+    game.start();
+    let mut watcher = match game.level_path() {
+        Some(path) if game.output().watch() => Some(LevelWatcher::new(path)),
+        _ => None,
+    };
+
+    loop {
+        let status = game.status();
+        game.output().set_status(status);
+        let jam_started = game.output().jam_started();
+        if let Some(started) = jam_started {
+            let time = format_elapsed(started.elapsed());
+            let message = game
+                .level
+                .narrator
+                .say_with("jam-timer", &[("time", &time)]);
+            writeln!(game.output(), "{}", message).unwrap();
+        }
+        let input = game.output().get_prompt();
+        if let Some(ref mut watcher) = watcher {
+            if game.level_path().is_some_and(|path| watcher.changed(path)) {
+                run_command(&mut game, ParsedCommand::Reload);
+            }
+        }
+        if let Some(response) = game.step(input) {
+            return Ok(response);
+        }
+    }
+}
+
+/// Notices when the level's files are saved, by checking when they were modified.
+struct LevelWatcher {
+    modified: Vec<(String, Option<SystemTime>)>,
+}
+
+impl LevelWatcher {
+    fn new(level_path: &str) -> LevelWatcher {
+        LevelWatcher {
+            modified: LevelWatcher::modified_times(level_path),
+        }
+    }
+
+    fn modified_times(level_path: &str) -> Vec<(String, Option<SystemTime>)> {
+        [level_path, ITEMS_PATH, VOCABULARY_PATH]
+            .iter()
+            .map(|path| {
+                let modified = fs::metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok();
+                (path.to_string(), modified)
+            })
+            .collect()
+    }
+
+    /// Check if any of the files changed since the last check. After traveling to
+    /// another level, this starts watching that level instead.
+    fn changed(&mut self, level_path: &str) -> bool {
+        let modified = LevelWatcher::modified_times(level_path);
+        let same_files = self
+            .modified
+            .iter()
+            .map(|(path, _)| path)
+            .eq(modified.iter().map(|(path, _)| path));
+        let changed = same_files && modified != self.modified;
+        self.modified = modified;
+        changed
+    }
+}
+
+/// Show the summary the first time the player reaches the level's goal.
+fn check_par<T: Environment>(game: &mut Game<T>) {
+    let par = match game.level.par {
+        Some(ref par) => par,
+        None => return,
+    };
+    if game.save_state.coord != par.goal || game.save_state.par_turns.is_some() {
+        return;
+    }
+    let turns = game.save_state.turn;
+    game.save_state.par_turns = Some(turns);
+    print_summary(game, par, turns);
+    game.record_result(turns);
+}
+
+enum CommandResult {
+    Success,
+    Failure,
+    Exit(GameLoopResponse),
+}
+
+fn run_command<T: Environment>(game: &mut Game<T>, command: ParsedCommand) -> CommandResult {
+    match command {
+        ParsedCommand::Look(Some(target)) => return look_command(game, &target),
+        ParsedCommand::Look(None) => print_room_look(game),
+        ParsedCommand::Help(Some(target)) => return help_target_command(game, &target),
+        ParsedCommand::Help(None) => {
+            print_text_file(game, "data/help.txt");
+            print_handler_help(game);
+        }
+        ParsedCommand::Move(direction) => {
+            let stumbled = game.stumble(direction);
+            if stumbled != direction {
+                let message = game.level.narrator.say_with(
+                    "status-stumble",
+                    &[("direction", stumbled.lowercase_string())],
+                );
+                writeln!(game.output(), "{}\n", message).unwrap();
+            }
+            let direction = stumbled;
+            let to = game.leads_to(&direction);
+            if let Some(weather) = to.and_then(|to| game.weather_blocking(&to)) {
+                let message = game
+                    .level
+                    .narrator
+                    .say_with("weather-blocked", &[("weather", weather.lowercase_str())]);
+                print_failure(game, &message);
+                return CommandResult::Failure;
+            }
+            let from = game.save_state.coord;
+            if let Some(message) = to.and_then(|to| game.terrain_blocking(&from, &to)) {
+                print_failure(game, message.trim_end());
+                return CommandResult::Failure;
+            }
+            if let Some(exit) = game.exit(&direction).cloned() {
+                if !game.holds(&exit.when) {
+                    let message = match exit.blocked {
+                        Some(ref blocked) => game.text(blocked),
+                        None => game.level.narrator.say_with(
+                            "cannot-move",
+                            &[("direction", direction.lowercase_string())],
+                        ),
+                    };
+                    print_failure(game, message.trim_end());
+                    return CommandResult::Failure;
+                }
+                if let Err(message) = game.travel(&exit) {
+                    print_failure(game, &message);
+                    return CommandResult::Failure;
+                }
+                print_vehicle_moves(game);
+                game.cross_tile();
+                print_room_description(game);
+                return CommandResult::Success;
+            }
+            let next_coord: Option<Coord> = *(game.room_info.in_direction(&direction));
+
+            match next_coord {
+                Some(next_coord) => {
+                    game.move_to(next_coord);
+                    print_vehicle_moves(game);
+                    game.cross_tile();
+                    print_room_description(game);
+                }
+                None => {
+                    let message = game.level.narrator.say_with(
+                        "cannot-move",
+                        &[("direction", direction.lowercase_string())],
+                    );
+                    print_failure(game, &message);
+                    return CommandResult::Failure;
+                }
+            };
+        }
+        ParsedCommand::Debug => {
+            game.save_state.debug = !game.save_state.debug;
+            let id = if game.save_state.debug {
+                "debug-on"
+            } else {
+                "debug-off"
+            };
+            writeln!(game.output(), "{}", game.level.narrator.say(id)).unwrap();
+        }
+        ParsedCommand::Map => {
+            if !game.save_state.debug {
+                writeln!(game.output(), "{}", game.level.narrator.say("map-debug")).unwrap();
+                return CommandResult::Failure;
+            }
+            print_region_map(game);
+        }
+        ParsedCommand::Drop(target) => return drop_command(game, &target),
+        ParsedCommand::Give(items, npc) => return give_command(game, &items, &npc),
+        ParsedCommand::Throw(item, target) => return throw_command(game, &item, &target),
+        ParsedCommand::Pet(target) => return pet_command(game, &target),
+        ParsedCommand::Feed(item, npc) => return feed_command(game, &item, &npc),
+        ParsedCommand::Fetch(npc, item) => return fetch_command(game, &npc, &item),
+        ParsedCommand::Consume(target) => return consume_command(game, &target),
+        ParsedCommand::Combine(targets) => return combine_command(game, &targets),
+        ParsedCommand::Craft(target) => return craft_command(game, &target),
+        ParsedCommand::Buy(target, npc) => return buy_command(game, &target, npc.as_deref()),
+        ParsedCommand::Offer(amount, target) => return offer_command(game, &amount, &target),
+        ParsedCommand::Money => {
+            let money = currency::total(&game.save_state.inventory.items);
+            let message = match money {
+                0 => game.level.narrator.say("money-none"),
+                _ => {
+                    let money = currency::format(money, &game.item_db);
+                    game.level.narrator.say_with("money", &[("money", &money)])
+                }
+            };
+            writeln!(game.output(), "{}", message).unwrap();
+        }
+        ParsedCommand::Deposit(target) => return deposit_command(game, &target),
+        ParsedCommand::Withdraw(target) => return withdraw_command(game, &target),
+        ParsedCommand::Stash => return stash_command(game),
+        ParsedCommand::Read(target) => return read_command(game, &target),
+        ParsedCommand::Lore(topic) => return lore_command(game, topic.as_deref()),
+        ParsedCommand::Journal => journal_command(game),
+        ParsedCommand::Search(target) => return search_command(game, target.as_deref()),
+        ParsedCommand::Shift(shift, target) => return shift_command(game, shift, &target),
+        ParsedCommand::Disarm(target) => return disarm_command(game, &target),
+        ParsedCommand::Board(target) => return board_command(game, &target),
+        ParsedCommand::Dismount => return dismount_command(game),
+        ParsedCommand::Say(words) => say_command(game, &words),
+        ParsedCommand::Ask(npc, topic) => return ask_command(game, npc.as_deref(), &topic),
+        ParsedCommand::AskFor(npc, items) => {
+            return ask_for_command(game, npc.as_deref(), &items);
+        }
+        ParsedCommand::Bye => bye_command(game),
+        ParsedCommand::Take(target) => return take_command(game, &target),
+        ParsedCommand::Compare(first, second) => {
+            let inventory = &game.save_state.inventory;
+            let vocabulary = &game.level.vocabulary;
+            match (
+                inventory.find_item(&first, vocabulary),
+                inventory.find_item(&second, vocabulary),
+            ) {
+                (Some(first), Some(second)) => print_comparison(game, first, second),
+                (first_item, _) => {
+                    let missing = if first_item.is_none() { first } else { second };
+                    let message = game
+                        .level
+                        .narrator
+                        .say_with("compare-missing", &[("target", &missing)]);
+                    print_failure(game, &message);
+                    suggest_target(game, &missing);
+                    return CommandResult::Failure;
+                }
+            }
+        }
+        ParsedCommand::Quit => {
+            if game.level.has_scoring() {
+                print_score(game);
+            }
+            if let Some(path) = game.output().save_path() {
+                let yml = serde_yaml::to_string(&game.save_state)
+                    .expect("Unable to serialize the game state.");
+                fs::write(path, yml).expect("Unable to save the game state.");
+            }
+
+            return CommandResult::Exit(GameLoopResponse::Quit);
+        }
+        ParsedCommand::Talk(Some(target)) => return talk_command(game, &target),
+        ParsedCommand::Talk(None) => {
+            writeln!(game.output(), "{}", game.level.narrator.say("talk-nothing")).unwrap();
+        }
+        ParsedCommand::Inventory(view) => return inventory_command(game, view),
+        ParsedCommand::Message(message) => print_failure(game, &message),
+        ParsedCommand::Narrate(id) => {
+            writeln!(game.output(), "{}", game.level.narrator.say(id)).unwrap();
+        }
+        // Corrections are run as the command they correct, see `Game::step`.
+        ParsedCommand::Oops(_) => {
+            let message = game.level.narrator.say("oops-missing");
+            writeln!(game.output(), "{}", message).unwrap();
+        }
+        ParsedCommand::Restart => {
+            let question = game.level.narrator.say("restart-confirm");
+            if prompt_yes_no(game, &question) {
+                game.save_calendar();
+                return CommandResult::Exit(GameLoopResponse::Restart);
+            } else {
+                writeln!(
+                    game.output(),
+                    "{}",
+                    game.level.narrator.say("restart-cancel")
+                )
+                .unwrap();
+            }
+        }
+        ParsedCommand::Reload => match game.reload() {
+            Ok(()) => {
+                writeln!(game.output(), "{}\n", game.level.narrator.say("reload")).unwrap();
+                let is_jam = game.output().jam_started().is_some();
+                if is_jam {
+                    print_problems(game, &validate::validate(&game.level, &game.item_db));
+                }
+            }
+            Err(message) => {
+                print_failure(game, &message);
+                return CommandResult::Failure;
+            }
+        },
+        ParsedCommand::Goto(target) => return goto_command(game, &target),
+        ParsedCommand::Exits => print_exit_destinations(game),
+        ParsedCommand::Where => where_command(game),
+        ParsedCommand::Travel(target) => {
+            // The steps of a walk are taken by `step`, so only a walk with no way there
+            // is run as a command.
+            if let Err(message) = game.route(&target) {
+                print_failure(game, &message);
+            }
+            return CommandResult::Failure;
+        }
+        ParsedCommand::Feedback(text) => return feedback_command(game, text),
+        ParsedCommand::Score => print_score(game),
+        ParsedCommand::Config(setting) => return config_command(game, setting),
+        ParsedCommand::Mute => {
+            let mute = (!game.config.mute).to_string();
+            return config_command(game, Some(("mute".into(), mute)));
+        }
+        ParsedCommand::Undo => match game.undo() {
+            Ok(true) => {
+                writeln!(game.output(), "{}\n", game.level.narrator.say("undo")).unwrap();
+                print_room_description(game);
+            }
+            Ok(false) => {
+                let message = game.level.narrator.say("undo-nothing");
+                print_failure(game, &message);
+                return CommandResult::Failure;
+            }
+            Err(message) => {
+                print_failure(game, &message);
+                return CommandResult::Failure;
+            }
+        },
+        ParsedCommand::Filter(target) => return filter_command(game, target),
+        ParsedCommand::Emote(emote, target) => return emote_command(game, &emote, target),
+        ParsedCommand::Custom(command, target) => {
+            let action = match target {
+                Some(ref target) => game.find_action(Verb::Custom, target, Some(&command)),
+                None => None,
+            };
+            match (action, game.handler(&command)) {
+                (Some(action), _) => {
+                    writeln!(game.output(), "{}", game.text(&action.value)).unwrap();
+                    game.follow(action);
+                }
+                (None, Some(handler)) => {
+                    if let Err(message) = handler.run(game, target.as_deref()) {
+                        print_failure(game, &message);
+                        return CommandResult::Failure;
+                    }
+                }
+                (None, None) => {
+                    let is_known_verb = game
+                        .room
+                        .actions_iter(&game.level)
+                        .any(|action| action.alias.as_ref() == Some(&command));
+                    match target {
+                        // The verb exists, so the target must be wrong.
+                        Some(ref target) if is_known_verb => {
+                            let message = game
+                                .level
+                                .narrator
+                                .say_with("cannot-do", &[("verb", &command), ("target", target)]);
+                            print_failure(game, &message);
+                            suggest_target(game, target);
+                        }
+                        _ => {
+                            let message = game
+                                .level
+                                .narrator
+                                .say_with("unknown-verb", &[("verb", &command)]);
+                            print_failure(game, &message);
+                            suggest_verb(game, &command);
+                        }
+                    }
+                    return CommandResult::Failure;
+                }
+            }
+        }
+    }
+    CommandResult::Success
+}
+
+/// Pick the scenario for a new game, either from the environment or by asking the
+/// player. Anything that isn't understood picks the first scenario.
+fn choose_scenario<T: Environment>(game: &Game<T>) -> usize {
+    let scenarios = &game.level.scenarios;
+    let find = |choice: &str| {
+        scenarios.iter().position(|scenario| {
+            scenario.id == choice || scenario.name.to_lowercase() == choice.to_lowercase()
+        })
+    };
+    let preselected = game.output().scenario().map(|id| id.to_string());
+    if let Some(id) = preselected {
+        match find(&id) {
+            Some(index) => return index,
+            None => {
+                let message = game
+                    .level
+                    .narrator
+                    .say_with("scenario-missing", &[("id", &id)]);
+                print_failure(game, &message);
+            }
+        }
+    }
+
+    print_box(game, &game.level.narrator.say("scenario-choose"));
+    for (number, scenario) in scenarios.iter().enumerate() {
+        let name = game.styled(Style::Item, &scenario.name);
+        match scenario.description {
+            Some(ref description) => writeln!(
+                game.output(),
+                "  {}. {} - {}",
+                number + 1,
+                name,
+                description
+            )
+            .unwrap(),
+            None => writeln!(game.output(), "  {}. {}", number + 1, name).unwrap(),
+        }
+    }
+    writeln!(game.output()).unwrap();
+
+    let choice = game.output().get_prompt();
+    let index = match choice.trim().parse::<usize>() {
+        Ok(number) if number >= 1 && number <= scenarios.len() => Some(number - 1),
+        _ => find(choice.trim()),
+    };
+    index.unwrap_or_else(|| {
+        let message = game
+            .level
+            .narrator
+            .say_with("scenario-default", &[("name", &scenarios[0].name)]);
+        writeln!(game.output(), "{}\n", message).unwrap();
+        0
+    })
+}
+
+/// Tell the player they died, and ask what to do next. This returns None if they
+/// respawn and play goes on.
+fn die<T: Environment>(game: &mut Game<T>, epitaph: &str) -> Option<GameLoopResponse> {
+    writeln!(game.output(), "{}\n", epitaph).unwrap();
+    print_box(game, &game.level.narrator.say("died"));
+    if game.level.has_scoring() {
+        print_score(game);
+    }
+    let can_respawn = game.level.respawn.is_some();
+    let choices = if can_respawn {
+        "respawn, load, restart, quit"
+    } else {
+        "load, restart, quit"
+    };
+    loop {
+        let message = game
+            .level
+            .narrator
+            .say_with("died-choices", &[("choices", choices)]);
+        writeln!(game.output(), "{}", message).unwrap();
+        let response = game.output().get_prompt();
+        match response.trim() {
+            // Environments that can't ask the player answer yes, which picks the first
+            // choice.
+            "respawn" | "r" | "yes" | "y" if can_respawn => {
+                respawn(game);
+                return None;
+            }
+            "load" | "l" | "yes" | "y" => return Some(GameLoopResponse::Death),
+            "restart" => {
+                game.save_calendar();
+                return Some(GameLoopResponse::Restart);
+            }
+            // The save is left as it was, rather than saving a dead player.
+            "quit" | "q" => return Some(GameLoopResponse::Quit),
+            _ => writeln!(game.output(), "{}", game.level.narrator.say("what")).unwrap(),
+        }
+    }
+}
+
+/// Bring the player back at their last checkpoint, minus the level's penalty.
+fn respawn<T: Environment>(game: &mut Game<T>) {
+    let (money, points, message) = match game.level.respawn {
+        Some(ref respawn) => (
+            respawn.money,
+            respawn.points,
+            match respawn.message {
+                Some(ref message) => game.text(message),
+                None => game.level.narrator.say("respawn"),
+            },
+        ),
+        None => return,
+    };
+    let inventory = &mut game.save_state.inventory;
+    let money = money.min(currency::total(&inventory.items));
+    if let Some(payment) = currency::pay(&inventory.items, money, &game.item_db) {
+        inventory.pay(&payment);
+    }
+    game.save_state.score = game.save_state.score.saturating_sub(points);
+    // Otherwise the player would die again right away.
+    if let Some(health) = game.level.start.stats.get("health") {
+        game.save_state.stats.insert("health".into(), *health);
+    }
+
+    writeln!(game.output(), "{}\n", message).unwrap();
+    let coord = game.save_state.checkpoint.unwrap_or(game.level.entry);
+    game.move_to(coord);
+    print_room_description(game);
+}
+
+fn prompt_yes_no<T: Environment>(game: &mut Game<T>, message: &str) -> bool {
+    if !game.config.confirm {
+        return true;
+    }
+    loop {
+        let question = game
+            .level
+            .narrator
+            .say_with("yes-no", &[("question", message)]);
+        writeln!(game.output(), "{}", question).unwrap();
+        let response = game.output().get_prompt();
+        match response.as_str() {
+            "yes" | "y" => {
+                return true;
+            }
+            "no" | "n" => {
+                return false;
+            }
+            _ => {
+                writeln!(game.output(), "{}", game.level.narrator.say("what")).unwrap();
+            }
+        }
+    }
+}
+
+fn inventory_command<T: Environment>(game: &Game<T>, view: InventoryView) -> CommandResult {
+    let items = &game.save_state.inventory.items;
+    let narrator = &game.level.narrator;
+    // What companions are carrying is shown along with the player's own items.
+    let show_companions = matches!(view, InventoryView::Page(_));
+    // The coins are counted up together at the top of the first page.
+    let money = currency::total(items);
+    let money = match view {
+        InventoryView::Page(1) if money > 0 => Some(currency::format(money, &game.item_db)),
+        _ => None,
+    };
+    let (title, shown, footer): (String, Vec<&InventoryItem>, Option<String>) = match view {
+        InventoryView::Page(page) => {
+            let items: Vec<_> = items
+                .iter()
+                .filter(|item| item.variant != ItemVariant::Money)
+                .collect();
+            let pages = items.len().div_ceil(INVENTORY_PAGE_SIZE).max(1);
+            if page > pages {
+                let pages = pages.to_string();
+                print_failure(
+                    game,
+                    &narrator.say_with("inventory-pages", &[("pages", &pages)]),
+                );
+                return CommandResult::Failure;
+            }
+            let shown = items
+                .into_iter()
+                .skip((page - 1) * INVENTORY_PAGE_SIZE)
+                .take(INVENTORY_PAGE_SIZE)
+                .collect();
+            let values = [
+                ("page", page.to_string()),
+                ("pages", pages.to_string()),
+                ("next", (page + 1).to_string()),
+            ];
+            let values: Vec<_> = values
+                .iter()
+                .map(|(key, value)| (*key, value.as_str()))
+                .collect();
+            let footer = if page < pages {
+                Some(narrator.say_with("inventory-page-more", &values))
+            } else if pages > 1 {
+                Some(narrator.say_with("inventory-page", &values))
+            } else {
+                None
+            };
+            (narrator.say("inventory"), shown, footer)
+        }
+        InventoryView::Find(word) => {
+            let shown: Vec<_> = items
+                .iter()
+                .filter(|item| {
+                    item.name.to_lowercase().contains(&word)
+                        || item.variant.lowercase_str() == word
+                        || game.level.vocabulary.matches(item.targets.iter(), &word)
+                })
+                .collect();
+            let footer = if shown.is_empty() {
+                Some(narrator.say_with("inventory-find-none", &[("word", &word)]))
+            } else {
+                None
+            };
+            (
+                narrator.say_with("inventory-find", &[("word", &word)]),
+                shown,
+                footer,
+            )
+        }
+    };
+
+    print_box(game, &title);
+    if shown.is_empty() && footer.is_none() && money.is_none() {
+        writeln!(game.output(), "    {}", narrator.say("inventory-empty")).unwrap();
+    }
+    if let Some(money) = money {
+        let money = game.styled(Style::Item, &money);
+        writeln!(game.output(), "  ‣ {}", money).unwrap();
+    }
+    for item in shown {
+        let name = game.styled(Style::Item, &item.name);
+        match item.max_quantity {
+            Some(_) => {
+                writeln!(game.output(), "  ‣ {} ({})", name, item.quantity).unwrap();
+            }
+            None => {
+                writeln!(game.output(), "  ‣ {}", name).unwrap();
+            }
+        }
+    }
+    if show_companions {
+        for (id, npc) in game.npcs_at(&game.room.coord) {
+            let carried = match game.save_state.companions.get(id) {
+                Some(carried) if !carried.is_empty() => carried,
+                _ => continue,
+            };
+            let names: Vec<_> = carried.iter().map(|item| item.name.as_str()).collect();
+            let message = narrator.say_with(
+                "inventory-companion",
+                &[("name", &npc.name), ("items", &names.join(", "))],
+            );
+            writeln!(game.output(), "\n{}", message).unwrap();
+        }
+    }
+    if let Some(footer) = footer {
+        writeln!(game.output(), "\n{}", footer).unwrap();
+    }
+    writeln!(game.output()).unwrap();
+    CommandResult::Success
+}
+
+/// Tell the player which room they are in, and the regions it's part of, along with
+/// the room's coordinate while debugging, and where its exits lead.
+fn where_command<T: Environment>(game: &mut Game<T>) {
+    let message = game
+        .level
+        .narrator
+        .say_with("where", &[("room", &game.room.title)]);
+    writeln!(game.output(), "{}", message).unwrap();
+    if game.save_state.debug {
+        let Coord { x, y, z } = game.save_state.coord;
+        writeln!(game.output(), "Coord: [{}, {}, {}]", x, y, z).unwrap();
+    }
+    if !game.room.regions.is_empty() {
+        let regions: Vec<&str> = game
+            .room
+            .regions
+            .iter()
+            .map(|id| {
+                let region = game.level.regions.get(id);
+                region
+                    .and_then(|region| region.name.as_deref())
+                    .unwrap_or(id)
+            })
+            .collect();
+        let message = game
+            .level
+            .narrator
+            .say_with("where-regions", &[("regions", &regions.join(", "))]);
+        writeln!(game.output(), "{}", message).unwrap();
+    }
+    writeln!(game.output()).unwrap();
+    print_exit_destinations(game);
+}
+
+/// Jump straight to a room while debugging, like "goto market-gate" or "goto 3 4 0".
+fn goto_command<T: Environment>(game: &mut Game<T>, target: &str) -> CommandResult {
+    if !game.save_state.debug {
+        writeln!(game.output(), "{}", game.level.narrator.say("goto-debug")).unwrap();
+        return CommandResult::Failure;
+    }
+    let numbers: Result<Vec<usize>, _> = target.split_whitespace().map(str::parse).collect();
+    let coord = match numbers.as_deref() {
+        Ok(&[x, y, z]) => Some(Coord { x, y, z }),
+        _ => game.level.find_entry(target),
+    };
+    match coord {
+        Some(coord) if game.room_graph.contains(&coord) => {
+            game.move_to(coord);
+            print_room_description(game);
+            CommandResult::Success
+        }
+        _ => {
+            let message = game
+                .level
+                .narrator
+                .say_with("goto-missing", &[("target", target)]);
+            print_failure(game, &message);
+            CommandResult::Failure
+        }
+    }
+}
+
+fn consume_command<T: Environment>(game: &mut Game<T>, target: &str) -> CommandResult {
+    let item = match game
+        .save_state
+        .inventory
+        .find_item(target, &game.level.vocabulary)
+    {
+        Some(item) => item.clone(),
+        None => {
+            let message = game
+                .level
+                .narrator
+                .say_with("eat-missing", &[("target", target)]);
+            print_failure(game, &message);
+            suggest_target(game, target);
+            return CommandResult::Failure;
+        }
+    };
+    let name = Noun::of(&item).the(&game.level.narrator, 1);
+    if item.restores.is_empty() && item.effects.is_empty() {
+        let message = game
+            .level
+            .narrator
+            .say_with("eat-inedible", &[("item", &name)]);
+        print_failure(game, &capitalize(&message));
+        return CommandResult::Failure;
+    }
+    game.save_state.inventory.use_one(&item.id);
+    let message = game.level.narrator.say_with("eat", &[("item", &name)]);
+    writeln!(game.output(), "{}", message).unwrap();
+    for (id, amount) in item.restores.iter() {
+        game.add_stat(id, *amount);
+    }
+    game.effects
+        .borrow_mut()
+        .extend(item.effects.iter().cloned());
+    CommandResult::Success
+}
+
+fn say_command<T: Environment>(game: &mut Game<T>, words: &str) {
+    let message = game.level.narrator.say_with("say", &[("words", words)]);
+    writeln!(game.output(), "{}\n", message).unwrap();
+    let room = Rc::clone(&game.room);
+    let mut puzzles = room
+        .puzzles
+        .iter()
+        .filter(|puzzle| !puzzle.answers.is_empty() && !game.save_state.solved.contains(&puzzle.id))
+        .peekable();
+    let first = match puzzles.peek() {
+        Some(puzzle) => *puzzle,
+        // There's no one to hear it.
+        None => return,
+    };
+    let answer = normalize_answer(words);
+    let solved = puzzles.find(|puzzle| {
+        puzzle
+            .answers
+            .iter()
+            .any(|other| normalize_answer(other) == answer)
+    });
+    match solved {
+        Some(puzzle) => game.solve(puzzle),
+        None => {
+            let message = match first.wrong {
+                Some(ref wrong) => game.text(wrong),
+                None => game.level.narrator.say("say-wrong"),
+            };
+            let text = wrap(message.trim_end(), game.width());
+            writeln!(game.output(), "{}\n", text).unwrap();
+        }
+    }
+}
+
+/// Put an answer into a form to compare it by, ignoring case, punctuation, spacing,
+/// and a leading article, so that "The Map!" answers "map".
+fn normalize_answer(answer: &str) -> String {
+    let lowercase = answer.to_lowercase();
+    let mut words = lowercase
+        .split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .peekable();
+    if let Some(&("a" | "an" | "the")) = words.peek() {
+        words.next();
+    }
+    words.collect()
+}
+
+/// Buy an item from an NPC in the room, paying with the player's coins and taking any
+/// change.
+fn buy_command<T: Environment>(
+    game: &mut Game<T>,
+    target: &str,
+    name: Option<&str>,
+) -> CommandResult {
+    let sellers = match name {
+        Some(name) => match game.find_npc(name) {
+            Some(seller) => vec![seller],
+            None => {
+                let message = game
+                    .level
+                    .narrator
+                    .say_with("talk-missing", &[("target", name)]);
+                print_failure(game, &message);
+                suggest_target(game, name);
+                return CommandResult::Failure;
+            }
+        },
+        None => game.npcs_at(&game.room.coord),
+    };
+    let (id, npc, item, cost) = match find_sale(game, sellers, target) {
+        Some(found) => found,
+        None => return CommandResult::Failure,
+    };
+    let price = game.asking_price(id, npc, item, cost);
+    let (name, item) = (npc.name.clone(), item.clone());
+    pay_for(game, &name, &item, price)
+}
+
+/// Find the NPC who sells an item, among some sellers, as long as they'll trade with
+/// the player. The player is told when they won't.
+fn find_sale<'a, T: Environment>(
+    game: &'a Game<T>,
+    sellers: Vec<(&'a str, &'a NPC)>,
+    target: &str,
+) -> Option<(&'a str, &'a NPC, &'a InventoryItem, usize)> {
+    let vocabulary = &game.level.vocabulary;
+    let found = sellers.into_iter().find_map(|(id, npc)| {
+        npc.items_iter(&game.item_db)
+            .find(|(item, _)| item_matches(item, target, vocabulary))
+            .map(|(item, cost)| (id, npc, item, cost))
+    });
+    let (id, npc, item, cost) = match found {
+        Some(found) => found,
+        None => {
+            let message = game
+                .level
+                .narrator
+                .say_with("buy-missing", &[("target", target)]);
+            print_failure(game, &message);
+            return None;
+        }
+    };
+    let refusal = if game.is_hostile(npc) {
+        "npc-wont-trade"
+    } else if game.refuses(id) {
+        "haggle-refusing"
+    } else {
+        return Some((id, npc, item, cost));
+    };
+    let message = game
+        .level
+        .narrator
+        .say_with(refusal, &[("name", &npc.name)]);
+    print_failure(game, &message);
+    None
+}
+
+/// Pay an NPC for an item, with the player's coins, taking any change.
+fn pay_for<T: Environment>(
+    game: &mut Game<T>,
+    name: &str,
+    item: &InventoryItem,
+    price: usize,
+) -> CommandResult {
+    let cost = currency::format(price, &game.item_db);
+    let values = [
+        ("item", item.name.as_str()),
+        ("name", name),
+        ("cost", &cost),
+    ];
+    let inventory = &game.save_state.inventory;
+    let payment = match currency::pay(&inventory.items, price, &game.item_db) {
+        Some(payment) => payment,
+        None => {
+            let money = currency::format(currency::total(&inventory.items), &game.item_db);
+            let message = game
+                .level
+                .narrator
+                .say_with("buy-afford", &[values[0], values[2], ("money", &money)]);
+            print_failure(game, &message);
+            return CommandResult::Failure;
+        }
+    };
+    writeln!(
+        game.output(),
+        "{}",
+        game.level.narrator.say_with("buy", &values)
+    )
+    .unwrap();
+    if !payment.change.is_empty() {
+        let paid = currency::format_coins(&payment.paid);
+        let change = currency::format_coins(&payment.change);
+        let message = game
+            .level
+            .narrator
+            .say_with("buy-change", &[("paid", &paid), ("change", &change)]);
+        writeln!(game.output(), "{}", message).unwrap();
+    }
+    let bought = InventoryItem {
+        quantity: 1,
+        ..item.clone()
+    };
+    let inventory = &mut game.save_state.inventory;
+    inventory.pay(&payment);
+    inventory.add_item(bought);
+    game.haggle = None;
+    CommandResult::Success
+}
+
+/// Offer an NPC in the room a price for an item they sell. They take any offer down to
+/// the lowest they'll go, see `Game::lowest_price`, and otherwise come down part of the
+/// way, to a price that "buy" then pays. An offer under half of the lowest insults
+/// them, and the more stubborn they are, the likelier they won't trade for a while.
+fn offer_command<T: Environment>(game: &mut Game<T>, amount: &str, target: &str) -> CommandResult {
+    let offer = match currency::parse(amount, &game.item_db) {
+        Some(offer) => offer,
+        None => {
+            let message = game
+                .level
+                .narrator
+                .say_with("offer-amount", &[("amount", amount)]);
+            print_failure(game, &message);
+            return CommandResult::Failure;
+        }
+    };
+    let sellers = game.npcs_at(&game.room.coord);
+    let (id, npc, item, cost) = match find_sale(game, sellers, target) {
+        Some(found) => found,
+        None => return CommandResult::Failure,
+    };
+    let asking = game.asking_price(id, npc, item, cost);
+    let lowest = game.lowest_price(npc, game.price(npc, cost));
+    let turn = game.save_state.turn;
+    let mut rng = Pcg32::seed_from_u64(game.save_state.seed ^ hash_str("haggle") ^ turn as u64);
+    let insulted = offer * 2 < lowest && rng.gen_range(0..100) < npc.stubbornness;
+    let (id, name, item) = (id.to_string(), npc.name.clone(), item.clone());
+
+    if offer >= lowest.min(asking) {
+        let message = game
+            .level
+            .narrator
+            .say_with("haggle-accept", &[("name", &name)]);
+        writeln!(game.output(), "{}", message).unwrap();
+        let price = offer.min(asking);
+        game.haggle = Some(Haggle {
+            npc: id,
+            item: item.id.clone(),
+            price,
+        });
+        return pay_for(game, &name, &item, price);
+    }
+    if insulted {
+        game.save_state.refusals.insert(id, turn + REFUSAL_TURNS);
+        game.haggle = None;
+        let message = game
+            .level
+            .narrator
+            .say_with("haggle-insulted", &[("name", &name)]);
+        print_failure(game, &message);
+        return CommandResult::Success;
+    }
+    let price = (asking + lowest) / 2;
+    let cost = currency::format(price, &game.item_db);
+    let message = game.level.narrator.say_with(
+        "haggle-counter",
+        &[("name", &name), ("item", &item.name), ("cost", &cost)],
+    );
+    writeln!(game.output(), "{}", message).unwrap();
+    game.haggle = Some(Haggle {
+        npc: id,
+        item: item.id,
+        price,
+    });
+    CommandResult::Success
+}
+
+/// Make something out of the items the player names, if there's a recipe that uses
+/// exactly those items.
+fn combine_command<T: Environment>(game: &mut Game<T>, targets: &[String]) -> CommandResult {
+    let mut ids = BTreeSet::new();
+    for target in targets {
+        match game
+            .save_state
+            .inventory
+            .find_item(target, &game.level.vocabulary)
+        {
+            Some(item) => ids.insert(item.id.clone()),
+            None => {
+                let message = game
+                    .level
+                    .narrator
+                    .say_with("combine-missing", &[("target", target)]);
+                print_failure(game, &message);
+                suggest_target(game, target);
+                return CommandResult::Failure;
+            }
+        };
+    }
+    let recipe = game
+        .item_db
+        .recipes()
+        .iter()
+        .find(|recipe| recipe.ingredients.keys().eq(ids.iter()))
+        .cloned();
+    match recipe {
+        Some(recipe) => make_recipe(game, &recipe),
+        None => {
+            print_failure(game, &game.level.narrator.say("combine-fails"));
+            CommandResult::Failure
+        }
+    }
+}
+
+/// Make an item by its name, from the first of its recipes the player has everything
+/// for.
+fn craft_command<T: Environment>(game: &mut Game<T>, target: &str) -> CommandResult {
+    let inventory = &game.save_state.inventory;
+    let recipes: Vec<&Recipe> = game
+        .item_db
+        .recipes()
+        .iter()
+        .filter(|recipe| {
+            game.item_db
+                .find(&recipe.output)
+                .is_some_and(|item| item_matches(item, target, &game.level.vocabulary))
+        })
+        .collect();
+    let recipe = recipes
+        .iter()
+        .find(|recipe| {
+            recipe
+                .ingredients
+                .iter()
+                .all(|(id, &needed)| inventory.count(id) >= needed)
+        })
+        .or_else(|| recipes.first());
+    match recipe {
+        Some(&recipe) => {
+            let recipe = recipe.clone();
+            make_recipe(game, &recipe)
+        }
+        None => {
+            let message = game
+                .level
+                .narrator
+                .say_with("craft-unknown", &[("target", target)]);
+            print_failure(game, &message);
+            CommandResult::Failure
+        }
+    }
+}
+
+/// Use up the ingredients of a recipe to make its item, as long as the player has
+/// enough of each.
+fn make_recipe<T: Environment>(game: &mut Game<T>, recipe: &Recipe) -> CommandResult {
+    let inventory = &game.save_state.inventory;
+    let narrator = &game.level.narrator;
+    let output = match game.item_db.find(&recipe.output) {
+        Some(output) => output.clone(),
+        None => {
+            let message = narrator.say_with("craft-broken", &[("item", &recipe.output)]);
+            print_failure(game, &message);
+            return CommandResult::Failure;
+        }
+    };
+    let name = Noun::of(&output).a(narrator, recipe.quantity);
+    let missing: Vec<_> = recipe
+        .ingredients
+        .iter()
+        .filter(|(id, &needed)| inventory.count(id) < needed)
+        .map(|(id, &needed)| {
+            let count = needed - inventory.count(id);
+            let item = match (game.item_db.find(id).map(Noun::of), count) {
+                (Some(noun), 1) => noun.name.to_string(),
+                (Some(noun), _) => noun.plural(),
+                (None, _) => id.to_string(),
+            };
+            narrator.say_with(
+                "craft-ingredient",
+                &[("count", &count.to_string()), ("item", &item)],
+            )
+        })
+        .collect();
+    if !missing.is_empty() {
+        let message = game.level.narrator.say_with(
+            "craft-missing",
+            &[("ingredients", &missing.join(", ")), ("item", &name)],
+        );
+        print_failure(game, &message);
+        return CommandResult::Failure;
+    }
+
+    let message = match recipe.message {
+        Some(ref message) => message.clone(),
+        None => game.level.narrator.say_with("craft", &[("item", &name)]),
+    };
+    writeln!(game.output(), "{}", message).unwrap();
+    for (id, &needed) in recipe.ingredients.iter() {
+        game.save_state.inventory.use_some(id, needed);
+    }
+    let mut item = output;
+    item.quantity = recipe.quantity;
+    game.save_state.inventory.add_item(item);
+    CommandResult::Success
+}
+
+fn take_command<T: Environment>(game: &mut Game<T>, target: &str) -> CommandResult {
+    let vocabulary = &game.level.vocabulary;
+    let room_inventory = game
+        .save_state
+        .room_inventory_mut(&game.room, &game.item_db);
+    let set = ItemSet::parse(target);
+    let taken = match set {
+        Some(ref set) => {
+            let (taken, left) = room_inventory
+                .inventory
+                .drain(..)
+                .partition(|(room_item, item)| !room_item.hidden && set.contains(item, vocabulary));
+            room_inventory.inventory = left;
+            taken
+        }
+        None => room_inventory
+            .take_item(target, vocabulary)
+            .into_iter()
+            .collect(),
+    };
+    if taken.is_empty() {
+        if set.is_some() {
+            print_failure(game, &game.level.narrator.say("take-all-none"));
+            return CommandResult::Failure;
+        }
+        let message = game
+            .level
+            .narrator
+            .say_with("take-missing", &[("target", target)]);
+        print_failure(game, &message);
+        suggest_target(game, target);
+        return CommandResult::Failure;
+    }
+
+    for (room_item, inventory_item) in taken {
+        let id = inventory_item.id.clone();
+        let message = match room_item.pickup {
+            Some(pickup) => pickup,
+            None => {
+                let noun = Noun::of(&inventory_item);
+                let item = noun.the(&game.level.narrator, inventory_item.quantity);
+                game.level.narrator.say_with("take", &[("item", &item)])
+            }
+        };
+        game.save_state.inventory.add_item(inventory_item);
+        writeln!(game.output(), "{}", message).unwrap();
+        game.emit(GameEvent::ItemTaken(id));
+    }
+    CommandResult::Success
+}
+
+fn drop_command<T: Environment>(game: &mut Game<T>, target: &str) -> CommandResult {
+    let dropped = match ItemSet::parse(target) {
+        Some(set) => {
+            let vocabulary = &game.level.vocabulary;
+            let inventory = &mut game.save_state.inventory;
+            let (dropped, kept) = inventory
+                .items
+                .drain(..)
+                .partition(|item| !item.sticky && set.contains(item, vocabulary));
+            inventory.items = kept;
+            if dropped.is_empty() {
+                print_failure(game, &game.level.narrator.say("drop-all-none"));
+                return CommandResult::Failure;
+            }
+            dropped
+        }
+        None => match game
+            .save_state
+            .inventory
+            .drop_item(target, &game.level.vocabulary)
+        {
+            DropResult::Item(item) => vec![*item],
+            DropResult::Sticky => {
+                let message = game
+                    .level
+                    .narrator
+                    .say_with("drop-sticky", &[("target", target)]);
+                print_failure(game, &message);
+                return CommandResult::Failure;
+            }
+            DropResult::None => {
+                let message = game
+                    .level
+                    .narrator
+                    .say_with("drop-missing", &[("target", target)]);
+                print_failure(game, &message);
+                suggest_target(game, target);
+                return CommandResult::Failure;
+            }
+        },
+    };
+
+    for item in dropped {
+        let name = Noun::of(&item).the(&game.level.narrator, item.quantity);
+        let message = game.level.narrator.say_with("drop", &[("item", &name)]);
+        writeln!(game.output(), "{}", message).unwrap();
+        game.save_state
+            .room_inventory_mut(&game.room, &game.item_db)
+            .add_item(item);
+    }
+    CommandResult::Success
+}
+
+/// Give items to an NPC, who only takes the ones they have a gift for, unless they are
+/// a companion who carries them for the player.
+fn give_command<T: Environment>(game: &mut Game<T>, target: &str, name: &str) -> CommandResult {
+    let (id, npc) = match game.find_npc(name) {
+        Some((id, npc)) => (id.to_string(), npc),
+        None => {
+            let message = game
+                .level
+                .narrator
+                .say_with("talk-missing", &[("target", name)]);
+            print_failure(game, &message);
+            suggest_target(game, name);
+            return CommandResult::Failure;
+        }
+    };
+    if game.is_hostile(npc) {
+        let message = game
+            .level
+            .narrator
+            .say_with("npc-hostile", &[("name", &npc.name)]);
+        print_failure(game, &message);
+        return CommandResult::Failure;
+    }
+
+    let vocabulary = &game.level.vocabulary;
+    let set = ItemSet::parse(target);
+    let items: Vec<InventoryItem> = match set {
+        Some(ref set) => game
+            .save_state
+            .inventory
+            .items
+            .iter()
+            .filter(|item| !item.sticky && set.contains(item, vocabulary))
+            .cloned()
+            .collect(),
+        None => game
+            .save_state
+            .inventory
+            .find_item(target, vocabulary)
+            .into_iter()
+            .cloned()
+            .collect(),
+    };
+    if items.is_empty() {
+        let message = match set {
+            Some(_) => game.level.narrator.say("give-all-none"),
+            None => game
+                .level
+                .narrator
+                .say_with("drop-missing", &[("target", target)]),
+        };
+        print_failure(game, &message);
+        return CommandResult::Failure;
+    }
+
+    let mut load = game
+        .save_state
+        .companions
+        .get(&id)
+        .map_or(0, |items| carried_count(items));
+    let mut given = Vec::new();
+    let mut carried = Vec::new();
+    for item in items {
+        let name = Noun::of(&item).the(&game.level.narrator, item.quantity);
+        let values = [("name", npc.name.as_str()), ("item", &name)];
+        let gift = npc.gifts.iter().find(|gift| gift.items.contains(&item.id));
+        match (gift, npc.carry) {
+            (_, _) if item.sticky => {
+                print_failure(game, &game.level.narrator.say_with("give-refused", &values));
+            }
+            (Some(gift), _) => {
+                let message = game.level.narrator.say_with("give", &values);
+                writeln!(game.output(), "{}", message).unwrap();
+                print_dialogue(game, &game.text(&gift.value), &npc.voice);
+                game.effects
+                    .borrow_mut()
+                    .extend(gift.effects.iter().cloned());
+                given.push(item.id);
+            }
+            (None, Some(carry)) if load + item.quantity.max(1) <= carry => {
+                load += item.quantity.max(1);
+                let message = game.level.narrator.say_with("companion-carry", &values);
+                writeln!(game.output(), "{}", message).unwrap();
+                given.push(item.id.clone());
+                carried.push(item);
+            }
+            (None, Some(_)) => {
+                print_failure(
+                    game,
+                    &game.level.narrator.say_with("companion-full", &values),
+                );
+            }
+            (None, None) => {
+                print_failure(game, &game.level.narrator.say_with("give-refused", &values));
+            }
+        }
+    }
+    if given.is_empty() {
+        return CommandResult::Failure;
+    }
+    game.save_state
+        .inventory
+        .items
+        .retain(|item| !given.contains(&item.id));
+    let companion = game.save_state.companions.entry(id).or_default();
+    for item in carried {
+        match companion.iter_mut().find(|other| other.id == item.id) {
+            Some(other) => other.quantity += item.quantity,
+            None => companion.push(item),
+        }
+    }
+    CommandResult::Success
+}
+
+/// Throw one of an item at an NPC or at something in the room. The first throw that
+/// matches decides what happens, see `Throw`, and otherwise the item misses and lands in
+/// the room.
+fn throw_command<T: Environment>(game: &mut Game<T>, target: &str, at: &str) -> CommandResult {
+    let vocabulary = &game.level.vocabulary;
+    let narrator = &game.level.narrator;
+    let item = match game.save_state.inventory.find_item(target, vocabulary) {
+        Some(item) if item.sticky => {
+            print_failure(
+                game,
+                &narrator.say_with("drop-sticky", &[("target", target)]),
+            );
+            return CommandResult::Failure;
+        }
+        Some(item) => item.clone(),
+        None => {
+            print_failure(
+                game,
+                &narrator.say_with("drop-missing", &[("target", target)]),
+            );
+            suggest_target(game, target);
+            return CommandResult::Failure;
+        }
+    };
+    let name = Noun::of(&item).the(narrator, 1);
+    let values = [("item", name.as_str()), ("target", at)];
+    let room = Rc::clone(&game.room);
+    let (throw, miss) = match game.find_npc(at) {
+        Some((_, npc)) => (
+            npc.throws
+                .iter()
+                .find(|throw| throw.throws(&item.id))
+                .cloned(),
+            narrator.say_with("throw-dodge", &[("name", &npc.name), values[0]]),
+        ),
+        None => {
+            let is_aimed = |throw: &&Throw| vocabulary.matches(throw.targets.iter(), at);
+            let is_scenery = room.scenery.iter().any(|scenery| {
+                let targets = std::iter::once(&scenery.id).chain(scenery.targets.iter());
+                vocabulary.matches(targets, at)
+            });
+            if !is_scenery && !room.throws.iter().any(|throw| is_aimed(&throw)) {
+                print_failure(game, &narrator.say_with("throw-missing", &values));
+                return CommandResult::Failure;
+            }
+            let throw = room
+                .throws
+                .iter()
+                .filter(is_aimed)
+                .find(|throw| throw.throws(&item.id));
+            let bounce = capitalize(&narrator.say_with("throw-bounce", &values));
+            (throw.cloned(), bounce)
+        }
+    };
+
+    let mut thrown = item.clone();
+    if thrown.quantity > 0 {
+        thrown.quantity = 1;
+    }
+    game.save_state.inventory.use_one(&item.id);
+    let lands_in = match throw {
+        Some(throw) => {
+            let text = game.text(&throw.value);
+            writeln!(game.output(), "{}", text).unwrap();
+            game.effects.borrow_mut().extend(throw.effects);
+            match throw.land {
+                _ if throw.breaks => None,
+                Some(ref entry) => game
+                    .level
+                    .find_entry(entry)
+                    .and_then(|coord| game.level.get_room(&coord))
+                    .cloned(),
+                None => Some(room),
+            }
+        }
+        None => {
+            writeln!(game.output(), "{}", miss).unwrap();
+            Some(room)
+        }
+    };
+    if let Some(lands_in) = lands_in {
+        game.save_state
+            .room_inventory_mut(&lands_in, &game.item_db)
+            .add_item(thrown);
+    }
+    CommandResult::Success
+}
+
+/// Find the id of the animal in the room that the player means, or tell them it isn't
+/// here.
+fn find_pet<T: Environment>(game: &Game<T>, name: &str) -> Option<String> {
+    match game.find_npc(name) {
+        Some((id, _)) => Some(id.to_string()),
+        None => {
+            let message = game
+                .level
+                .narrator
+                .say_with("talk-missing", &[("target", name)]);
+            print_failure(game, &message);
+            suggest_target(game, name);
+            None
+        }
+    }
+}
+
+/// Pet an animal, which only a tame one enjoys, see `Pet`.
+fn pet_command<T: Environment>(game: &mut Game<T>, name: &str) -> CommandResult {
+    let id = match find_pet(game, name) {
+        Some(id) => id,
+        None => return CommandResult::Failure,
+    };
+    let npc = &game.level.npcs[&id];
+    let narrator = &game.level.narrator;
+    let values = [("name", npc.name.as_str())];
+    let pet = match npc.pet {
+        Some(ref pet) => pet,
+        None => {
+            print_failure(game, &narrator.say_with("pet-refused", &values));
+            return CommandResult::Failure;
+        }
+    };
+    let text = match (game.save_state.tamed.contains(&id), &pet.petted, &pet.wild) {
+        (true, Some(petted), _) => game.text(petted),
+        (true, None, _) => narrator.say_with("pet-petted", &values),
+        (false, _, Some(wild)) => game.text(wild),
+        (false, _, None) => narrator.say_with("pet-wild", &values),
+    };
+    let text = wrap(&text, game.width());
+    writeln!(game.output(), "{}", text).unwrap();
+    CommandResult::Success
+}
+
+/// Feed an animal one of an item. The first time it gets something it eats, it's tamed
+/// and starts following the player.
+fn feed_command<T: Environment>(game: &mut Game<T>, target: &str, name: &str) -> CommandResult {
+    let id = match find_pet(game, name) {
+        Some(id) => id,
+        None => return CommandResult::Failure,
+    };
+    let npc = &game.level.npcs[&id];
+    let narrator = &game.level.narrator;
+    let item = match game
+        .save_state
+        .inventory
+        .find_item(target, &game.level.vocabulary)
+    {
+        Some(item) => item.clone(),
+        None => {
+            print_failure(
+                game,
+                &narrator.say_with("drop-missing", &[("target", target)]),
+            );
+            suggest_target(game, target);
+            return CommandResult::Failure;
+        }
+    };
+    let item_name = Noun::of(&item).the(narrator, 1);
+    let values = [("name", npc.name.as_str()), ("item", &item_name)];
+    let pet = match npc.pet {
+        Some(ref pet) if pet.eats.contains(&item.id) => pet,
+        _ => {
+            print_failure(game, &narrator.say_with("feed-refused", &values));
+            return CommandResult::Failure;
+        }
+    };
+    game.save_state.inventory.use_one(&item.id);
+    let text = match (game.save_state.tamed.insert(id), &pet.tamed) {
+        (true, Some(tamed)) => game.text(tamed),
+        (true, None) => narrator.say_with("pet-tamed", &values),
+        (false, _) => narrator.say_with("feed", &values),
+    };
+    let text = wrap(&text, game.width());
+    writeln!(game.output(), "{}", text).unwrap();
+    CommandResult::Success
+}
+
+/// Send a tame animal to fetch an item. It follows the map to the nearest room with
+/// one that it can reach on its own, see `open_steps`, and is gone for the walk there and back, see `run_fetches`.
+fn fetch_command<T: Environment>(game: &mut Game<T>, name: &str, target: &str) -> CommandResult {
+    let id = match find_pet(game, name) {
+        Some(id) => id,
+        None => return CommandResult::Failure,
+    };
+    let npc = &game.level.npcs[&id];
+    let narrator = &game.level.narrator;
+    let vocabulary = &game.level.vocabulary;
+    let values = [("name", npc.name.as_str()), ("target", target)];
+    if !game.save_state.tamed.contains(&id) {
+        print_failure(game, &narrator.say_with("fetch-wild", &values));
+        return CommandResult::Failure;
+    }
+    let here = game.room.coord;
+    let room_inventory = game
+        .save_state
+        .room_inventory_mut(&game.room, &game.item_db);
+    let is_target =
+        |(_, item): &&(RoomItem, InventoryItem)| vocabulary.matches(item.targets.iter(), target);
+    if room_inventory.visible_iter().any(|item| is_target(&item)) {
+        print_failure(game, &narrator.say_with("fetch-here", &values));
+        return CommandResult::Failure;
+    }
+
+    // Sniff out the nearest room with the item, keeping clear of the ways that depend on
+    // what the player has done.
+    let walk = search(here, None, |coord| {
+        open_steps(&game.level, &game.room_graph, &coord)
+            .into_iter()
+            .map(|(direction, next)| (next, direction))
+    });
+    let mut found = None;
+    for room in game.level.rooms.iter() {
+        let path = match walk.path_to(&room.coord) {
+            Some(path) if !path.is_empty() => path,
+            _ => continue,
+        };
+        if found
+            .as_ref()
+            .is_some_and(|(_, _, shortest): &(_, _, Vec<Direction>)| shortest.len() <= path.len())
+        {
+            continue;
+        }
+        let room_inventory = game.save_state.room_inventory_mut(room, &game.item_db);
+        if let Some((_, item)) = room_inventory.visible_iter().find(is_target) {
+            found = Some((room.coord, item.id.clone(), path));
+        }
+    }
+    let (coord, item, path) = match found {
+        Some(found) => found,
+        None => {
+            print_failure(game, &narrator.say_with("fetch-none", &values));
+            return CommandResult::Failure;
+        }
+    };
+    let fetch = Fetch {
+        item,
+        level: game.save_state.level.clone(),
+        from: coord,
+        returns: game.save_state.turn + path.len() * 2,
+    };
+    game.save_state.fetches.insert(id.clone(), fetch);
+    game.save_state.npc_coords.insert(id, coord);
+    let message = narrator.say_with(
+        "fetch-sent",
+        &[
+            ("name", &npc.name),
+            ("direction", path[0].lowercase_string()),
+        ],
+    );
+    writeln!(game.output(), "{}", message).unwrap();
+    CommandResult::Success
+}
+
+/// How many items a companion is carrying, where items without a quantity count as one.
+fn carried_count(items: &[InventoryItem]) -> usize {
+    items.iter().map(|item| item.quantity.max(1)).sum()
+}
+
+/// Read an item the player is carrying, which shows its lore and unlocks it. Rooms can
+/// have their own "read" actions too, like for a sign, which are used first.
+fn read_command<T: Environment>(game: &mut Game<T>, target: &str) -> CommandResult {
+    if let Some(action) = game.find_action(Verb::Custom, target, Some(&"read".to_string())) {
+        writeln!(game.output(), "{}", game.text(&action.value)).unwrap();
+        game.follow(action);
+        return CommandResult::Success;
+    }
+    let item = match game
+        .save_state
+        .inventory
+        .find_item(target, &game.level.vocabulary)
+    {
+        Some(item) => item.clone(),
+        None => {
+            let message = game
+                .level
+                .narrator
+                .say_with("read-missing", &[("target", target)]);
+            print_failure(game, &message);
+            suggest_target(game, target);
+            return CommandResult::Failure;
+        }
+    };
+    let (id, lore) = match item
+        .lore
+        .as_ref()
+        .and_then(|id| game.level.lore.get_key_value(id))
+    {
+        Some(entry) => entry,
+        None => {
+            let name = Noun::of(&item).the(&game.level.narrator, 1);
+            let message = game
+                .level
+                .narrator
+                .say_with("read-blank", &[("item", &name)]);
+            print_failure(game, &message);
+            return CommandResult::Failure;
+        }
+    };
+    print_lore(game, lore);
+    let id = id.clone();
+    game.learn_lore(&id);
+    CommandResult::Success
+}
+
+/// Show an entry of the lore that has been unlocked, or list them when there's no topic.
+fn lore_command<T: Environment>(game: &mut Game<T>, topic: Option<&str>) -> CommandResult {
+    let mut unlocked = game
+        .level
+        .lore
+        .iter()
+        .filter(|(id, _)| game.save_state.lore.contains(*id));
+    let topic = match topic {
+        Some(topic) => topic,
+        None => {
+            let titles: Vec<_> = unlocked.map(|(_, lore)| lore.title.as_str()).collect();
+            let message = match titles.is_empty() {
+                true => game.level.narrator.say("lore-none"),
+                false => game
+                    .level
+                    .narrator
+                    .say_with("lore-list", &[("entries", &titles.join(", "))]),
+            };
+            writeln!(game.output(), "{}", message).unwrap();
+            return CommandResult::Success;
+        }
+    };
+    let vocabulary = &game.level.vocabulary;
+    let found = unlocked.find(|(id, lore)| {
+        *id == topic
+            || lore.title.to_lowercase() == topic
+            || vocabulary.matches(lore.targets.iter(), topic)
+    });
+    match found {
+        Some((_, lore)) => {
+            print_lore(game, lore);
+            CommandResult::Success
+        }
+        None => {
+            let message = game
+                .level
+                .narrator
+                .say_with("lore-unknown", &[("topic", topic)]);
+            print_failure(game, &message);
+            CommandResult::Failure
+        }
+    }
+}
+
+/// List the quests the player is on, with their deadlines, and then the ones that are
+/// over.
+fn journal_command<T: Environment>(game: &Game<T>) {
+    let narrator = &game.level.narrator;
+    print_box(game, &narrator.say("journal"));
+    let quests: Vec<(&Quest, &QuestState)> = game
+        .save_state
+        .quests
+        .iter()
+        .filter_map(|(id, state)| Some((game.level.quests.get(id)?, state)))
+        .collect();
+    if quests.is_empty() {
+        writeln!(game.output(), "    {}", narrator.say("inventory-empty")).unwrap();
+    }
+    let (active, over): (Vec<_>, Vec<_>) = quests
+        .into_iter()
+        .partition(|(_, state)| state.outcome.is_none());
+    let width = game.width();
+    for (quest, state) in active {
+        let elapsed = game.save_state.turn.saturating_sub(state.started);
+        let line = match quest.turns {
+            Some(turns) if elapsed + 1 == turns => {
+                narrator.say_with("journal-turn", &[("quest", &quest.name)])
+            }
+            Some(turns) if elapsed < turns => {
+                let left = (turns - elapsed).to_string();
+                narrator.say_with("journal-turns", &[("quest", &quest.name), ("turns", &left)])
+            }
+            Some(_) => narrator.say_with("journal-overdue", &[("quest", &quest.name)]),
+            None => quest.name.clone(),
+        };
+        let line = game.styled(Style::Item, &line);
+        let description = wrap(game.text(&quest.description).trim_end(), width - 4);
+        writeln!(game.output(), "  ‣ {}", line).unwrap();
+        for description_line in description.lines() {
+            writeln!(game.output(), "    {}", description_line).unwrap();
+        }
+    }
+    for (quest, state) in over {
+        let id = match state.outcome {
+            Some(QuestOutcome::Late) => "journal-late",
+            Some(QuestOutcome::Failed) => "journal-failed",
+            _ => "journal-done",
+        };
+        let line = narrator.say_with(id, &[("quest", &quest.name)]);
+        writeln!(game.output(), "  ‣ {}", line).unwrap();
+    }
+    writeln!(game.output()).unwrap();
+}
+
+/// Print an entry of the lore, with its title.
+fn print_lore<T: Environment>(game: &Game<T>, lore: &Lore) {
+    let title = game.styled(Style::Title, &lore.title);
+    let text = wrap(game.text(&lore.text).trim_end(), game.width());
+    writeln!(game.output(), "{}\n\n{}\n", title, text).unwrap();
+}
+
+/// Find the hidden items and secret exits of the room, for good. Searching the room as a
+/// whole finds the ones that aren't in something, and searching something, like a desk,
+/// finds the ones that are `found_by` it.
+fn search_command<T: Environment>(game: &mut Game<T>, target: Option<&str>) -> CommandResult {
+    if game.light() == Lighting::Dim {
+        print_failure(game, &game.level.narrator.say("search-dark"));
+        return CommandResult::Failure;
+    }
+    let vocabulary = &game.level.vocabulary;
+    let is_here = |found_by: &Option<String>| match (found_by, target) {
+        (Some(found_by), Some(target)) => vocabulary.matches(std::iter::once(found_by), target),
+        (None, None) => true,
+        _ => false,
+    };
+    let room = Rc::clone(&game.room);
+    let room_inventory = game.save_state.room_inventory_mut(&room, &game.item_db);
+    let mut found = Vec::new();
+    let mut hints = Vec::new();
+    for (room_item, item) in room_inventory.inventory.iter_mut() {
+        if !room_item.hidden {
+            continue;
+        }
+        if is_here(&room_item.found_by) {
+            room_item.hidden = false;
+            let name = Noun::in_room(room_item, item).the(&game.level.narrator, item.quantity);
+            found.push(("search-item", vec![("item", name)]));
+        } else if let Some(ref found_by) = room_item.found_by {
+            hints.push(found_by.clone());
+        }
+    }
+    // The exits behind scenery are found by moving it instead.
+    let mut exits: Vec<_> = room
+        .exits
+        .iter()
+        .filter(|(direction, exit)| {
+            exit.hidden
+                && !room
+                    .scenery
+                    .iter()
+                    .any(|scenery| scenery.exits.contains(direction))
+        })
+        .collect();
+    exits.sort_by_key(|(direction, _)| **direction);
+    for (direction, exit) in exits {
+        if room_inventory.found_exits.contains(direction) {
+            continue;
+        }
+        if is_here(&exit.found_by) {
+            room_inventory.found_exits.insert(*direction);
+            let direction = direction.lowercase_string().to_string();
+            found.push(("search-exit", vec![("direction", direction)]));
+        } else if let Some(ref found_by) = exit.found_by {
+            hints.push(found_by.clone());
+        }
+    }
+
+    if found.is_empty() {
+        let narrator = &game.level.narrator;
+        let message = match (target, hints.first()) {
+            (Some(target), _) => narrator.say_with("search-nothing-in", &[("target", target)]),
+            (None, Some(hint)) => narrator.say_with("search-hint", &[("target", hint)]),
+            (None, None) => narrator.say("search-nothing"),
+        };
+        writeln!(game.output(), "{}", message).unwrap();
+        return CommandResult::Success;
+    }
+    for (id, values) in found {
+        let values: Vec<_> = values
+            .iter()
+            .map(|(key, value)| (*key, value.as_str()))
+            .collect();
+        let message = game.level.narrator.say_with(id, &values);
+        writeln!(game.output(), "{}", message).unwrap();
+    }
+    CommandResult::Success
+}
+
+/// Push, pull, or move something in the room. The room's own actions for the verb come
+/// first, like a lever to pull, and then its scenery, which only moves once.
+fn shift_command<T: Environment>(game: &mut Game<T>, shift: Shift, target: &str) -> CommandResult {
+    let verb = shift.lowercase_str().to_string();
+    if let Some(action) = game.find_action(Verb::Custom, target, Some(&verb)) {
+        writeln!(game.output(), "{}", game.text(&action.value)).unwrap();
+        game.follow(action);
+        return CommandResult::Success;
+    }
+    let room = Rc::clone(&game.room);
+    let vocabulary = &game.level.vocabulary;
+    let narrator = &game.level.narrator;
+    let values = [("verb", verb.as_str()), ("target", target)];
+    let scenery = room.scenery.iter().find(|scenery| {
+        let targets = std::iter::once(&scenery.id).chain(scenery.targets.iter());
+        vocabulary.matches(targets, target)
+    });
+    let scenery = match scenery {
+        Some(scenery) if scenery.verbs.is_empty() || scenery.verbs.contains(&shift) => scenery,
+        Some(_) => {
+            print_failure(game, &narrator.say_with("cannot-do", &values));
+            return CommandResult::Failure;
+        }
+        None => {
+            print_failure(game, &narrator.say_with("cannot-do", &values));
+            suggest_target(game, target);
+            return CommandResult::Failure;
+        }
+    };
+    let room_inventory = game.save_state.room_inventory_mut(&room, &game.item_db);
+    if room_inventory.moved.contains(&scenery.id) {
+        print_failure(game, &narrator.say_with("shift-moved", &values));
+        return CommandResult::Failure;
+    }
+    if !game.holds(&scenery.when) {
+        let message = match scenery.stuck {
+            Some(ref stuck) => game.text(stuck),
+            None => narrator.say_with("shift-stuck", &values),
+        };
+        print_failure(game, &message);
+        return CommandResult::Failure;
+    }
+    let room_inventory = game.save_state.room_inventory_mut(&room, &game.item_db);
+    room_inventory.moved.insert(scenery.id.clone());
+    room_inventory
+        .found_exits
+        .extend(scenery.exits.iter().copied());
+    writeln!(game.output(), "{}", game.text(&scenery.value)).unwrap();
+    game.effects
+        .borrow_mut()
+        .extend(scenery.effects.iter().cloned());
+    CommandResult::Success
+}
+
+/// Leave items in the stash, while at a bank.
+fn deposit_command<T: Environment>(game: &mut Game<T>, target: &str) -> CommandResult {
+    if !game.at_bank() {
+        print_failure(game, &game.level.narrator.say("bank-none"));
+        return CommandResult::Failure;
+    }
+    let items = &game.save_state.inventory.items;
+    let deposited = select_items(items, target, &game.level.vocabulary);
+    if deposited.is_empty() {
+        let message = match ItemSet::parse(target) {
+            Some(_) => game.level.narrator.say("deposit-all-none"),
+            None => game
+                .level
+                .narrator
+                .say_with("deposit-missing", &[("target", target)]),
+        };
+        print_failure(game, &message);
+        return CommandResult::Failure;
+    }
+    for item in deposited {
+        let name = Noun::of(&item).the(&game.level.narrator, item.quantity);
+        let message = game.level.narrator.say_with("deposit", &[("item", &name)]);
+        writeln!(game.output(), "{}", message).unwrap();
+        let save_state = &mut game.save_state;
+        save_state
+            .inventory
+            .items
+            .retain(|other| other.id != item.id);
+        save_state.stash.add_item(item);
+    }
+    CommandResult::Success
+}
+
+/// Take items back out of the stash, while at a bank.
+fn withdraw_command<T: Environment>(game: &mut Game<T>, target: &str) -> CommandResult {
+    if !game.at_bank() {
+        print_failure(game, &game.level.narrator.say("bank-none"));
+        return CommandResult::Failure;
+    }
+    let items = &game.save_state.stash.items;
+    let withdrawn = select_items(items, target, &game.level.vocabulary);
+    if withdrawn.is_empty() {
+        let message = match (items.is_empty(), ItemSet::parse(target)) {
+            (true, _) => game.level.narrator.say("stash-empty"),
+            (false, Some(_)) => game.level.narrator.say("withdraw-all-none"),
+            (false, None) => game
+                .level
+                .narrator
+                .say_with("withdraw-missing", &[("target", target)]),
+        };
+        print_failure(game, &message);
+        return CommandResult::Failure;
+    }
+    for item in withdrawn {
+        let name = Noun::of(&item).the(&game.level.narrator, item.quantity);
+        let message = game.level.narrator.say_with("withdraw", &[("item", &name)]);
+        writeln!(game.output(), "{}", message).unwrap();
+        let save_state = &mut game.save_state;
+        save_state.stash.items.retain(|other| other.id != item.id);
+        save_state.inventory.add_item(item);
+    }
+    CommandResult::Success
+}
+
+/// List what's in the stash, while at a bank.
+fn stash_command<T: Environment>(game: &mut Game<T>) -> CommandResult {
+    if !game.at_bank() {
+        print_failure(game, &game.level.narrator.say("bank-none"));
+        return CommandResult::Failure;
+    }
+    let names: Vec<_> = game
+        .save_state
+        .stash
+        .items
+        .iter()
+        .map(|item| match item.max_quantity {
+            Some(_) => format!("{} ({})", item.name, item.quantity),
+            None => item.name.clone(),
+        })
+        .collect();
+    let message = match names.is_empty() {
+        true => game.level.narrator.say("stash-empty"),
+        false => game
+            .level
+            .narrator
+            .say_with("stash", &[("items", &names.join(", "))]),
+    };
+    writeln!(game.output(), "{}", message).unwrap();
+    CommandResult::Success
+}
+
+/// Take items back from a companion, or the NPC in the conversation when None.
+fn ask_for_command<T: Environment>(
+    game: &mut Game<T>,
+    name: Option<&str>,
+    target: &str,
+) -> CommandResult {
+    let found = match name {
+        Some(name) => game.find_npc(name),
+        None => game.conversation_partner(),
+    };
+    let (id, npc) = match (found, name) {
+        (Some(found), _) => found,
+        (None, Some(name)) => {
+            let message = game
+                .level
+                .narrator
+                .say_with("talk-missing", &[("target", name)]);
+            print_failure(game, &message);
+            suggest_target(game, name);
+            return CommandResult::Failure;
+        }
+        (None, None) => {
+            print_failure(game, &game.level.narrator.say("ask-nobody"));
+            return CommandResult::Failure;
+        }
+    };
+    if game.is_hostile(npc) {
+        let message = game
+            .level
+            .narrator
+            .say_with("npc-hostile", &[("name", &npc.name)]);
+        print_failure(game, &message);
+        return CommandResult::Failure;
+    }
+
+    let carried = game
+        .save_state
+        .companions
+        .get(id)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let taken = select_items(carried, target, &game.level.vocabulary);
+    if taken.is_empty() {
+        let message = match carried.is_empty() {
+            true => game
+                .level
+                .narrator
+                .say_with("companion-empty", &[("name", &npc.name)]),
+            false => game.level.narrator.say_with(
+                "companion-missing",
+                &[("name", &npc.name), ("target", target)],
+            ),
+        };
+        print_failure(game, &message);
+        return CommandResult::Failure;
+    }
+
+    for item in taken.iter() {
+        let name = Noun::of(item).the(&game.level.narrator, item.quantity);
+        let message = game
+            .level
+            .narrator
+            .say_with("companion-return", &[("name", &npc.name), ("item", &name)]);
+        writeln!(game.output(), "{}", message).unwrap();
+    }
+    let id = id.to_string();
+    if let Some(carried) = game.save_state.companions.get_mut(&id) {
+        carried.retain(|item| !taken.iter().any(|other| other.id == item.id));
+    }
+    for item in taken {
+        game.save_state.inventory.add_item(item);
+    }
+    CommandResult::Success
+}
+
+fn board_command<T: Environment>(game: &mut Game<T>, target: &str) -> CommandResult {
+    if let Some((_, vehicle)) = game.vehicle() {
+        let message = game
+            .level
+            .narrator
+            .say_with("board-already", &[("name", &vehicle.name)]);
+        print_failure(game, &message);
+        return CommandResult::Failure;
+    }
+    let found = game
+        .vehicles_at(&game.save_state.coord)
+        .into_iter()
+        .find(|(_, vehicle)| {
+            game.level
+                .vocabulary
+                .matches(vehicle.targets.iter(), target)
+        })
+        .map(|(id, vehicle)| (id.to_string(), vehicle));
+    let (id, vehicle) = match found {
+        Some(found) => found,
+        None => {
+            let message = game
+                .level
+                .narrator
+                .say_with("board-missing", &[("target", target)]);
+            print_failure(game, &message);
+            return CommandResult::Failure;
+        }
+    };
+    let message = match vehicle.board {
+        Some(ref board) => game.text(board),
+        None => game
+            .level
+            .narrator
+            .say_with("board", &[("name", &vehicle.name)]),
+    };
+    let text = wrap(message.trim_end(), game.width());
+    writeln!(game.output(), "{}\n", text).unwrap();
+    game.save_state.vehicle_coords.remove(&id);
+    game.save_state.aboard = Some(id);
+    CommandResult::Success
+}
+
+fn dismount_command<T: Environment>(game: &mut Game<T>) -> CommandResult {
+    let vehicle = match game.vehicle() {
+        Some((_, vehicle)) => vehicle,
+        None => {
+            let message = game.level.narrator.say("dismount-nothing");
+            print_failure(game, &message);
+            return CommandResult::Failure;
+        }
+    };
+    // There's nowhere to step off to in the middle of a lake.
+    if game.room_graph.terrain(&game.save_state.coord) == Terrain::Water {
+        let message = game
+            .level
+            .narrator
+            .say_with("dismount-water", &[("name", &vehicle.name)]);
+        print_failure(game, &message);
+        return CommandResult::Failure;
+    }
+    let message = match vehicle.dismount {
+        Some(ref dismount) => game.text(dismount),
+        None => game
+            .level
+            .narrator
+            .say_with("dismount", &[("name", &vehicle.name)]),
+    };
+    let text = wrap(message.trim_end(), game.width());
+    writeln!(game.output(), "{}\n", text).unwrap();
+    game.leave_vehicle();
+    CommandResult::Success
+}
+
+/// Say how the player gets along aboard their vehicle, as they reach the next room.
+fn print_vehicle_moves<T: Environment>(game: &Game<T>) {
+    if let Some(moves) = game
+        .vehicle()
+        .and_then(|(_, vehicle)| vehicle.moves.as_ref())
+    {
+        let text = wrap(game.text(moves).trim_end(), game.width());
+        writeln!(game.output(), "{}\n", text).unwrap();
+    }
+}
+
+fn disarm_command<T: Environment>(game: &mut Game<T>, target: &str) -> CommandResult {
+    let room = Rc::clone(&game.room);
+    let hazard = room.hazards.iter().find(|hazard| {
+        !game.save_state.disarmed.contains(&hazard.id)
+            && game.level.vocabulary.matches(hazard.targets.iter(), target)
+    });
+    let (hazard, disarm) = match hazard {
+        Some(
+            hazard @ Hazard {
+                disarm: Some(disarm),
+                ..
+            },
+        ) => (hazard, disarm),
+        Some(_) => {
+            let message = game
+                .level
+                .narrator
+                .say_with("disarm-impossible", &[("target", target)]);
+            print_failure(game, &message);
+            return CommandResult::Failure;
+        }
+        None => {
+            let message = game
+                .level
+                .narrator
+                .say_with("disarm-missing", &[("target", target)]);
+            print_failure(game, &message);
+            return CommandResult::Failure;
+        }
+    };
+    // Without the right tools there's nothing to try, and so nothing to set off.
+    let holds = disarm
+        .when
+        .as_ref()
+        .map(|when| when.holds(&game.variables()));
+    if holds == Some(false) && disarm.check.is_none() {
+        let message = match disarm.failed {
+            Some(ref failed) => game.text(failed),
+            None => game
+                .level
+                .narrator
+                .say_with("disarm-cannot", &[("target", target)]),
+        };
+        print_failure(game, &message);
+        return CommandResult::Failure;
+    }
+    if game.safeguard_works(&hazard.id, disarm) {
+        game.save_state.disarmed.insert(hazard.id.clone());
+        let message = match disarm.message {
+            Some(ref message) => game.text(message),
+            None => game
+                .level
+                .narrator
+                .say_with("disarm", &[("target", target)]),
+        };
+        let text = wrap(message.trim_end(), game.width());
+        writeln!(game.output(), "{}\n", text).unwrap();
+    } else {
+        let message = match disarm.failed {
+            Some(ref failed) => game.text(failed),
+            None => game
+                .level
+                .narrator
+                .say_with("disarm-failed", &[("target", target)]),
+        };
+        let text = wrap(message.trim_end(), game.width());
+        writeln!(game.output(), "{}\n", text).unwrap();
+        game.spring(hazard);
+    }
+    CommandResult::Success
+}
+
+fn look_command<T: Environment>(game: &Game<T>, target: &str) -> CommandResult {
+    // Look at something in the room through an action?
+    if let Some(action) = game.find_action(Verb::Look, target, None) {
+        writeln!(game.output(), "{}\n", game.text(&action.value)).unwrap();
+        game.follow(action);
+        return CommandResult::Success;
+    }
+
+    // Look at an npc?
+    if let Some(npc) = game.get_npc(target) {
+        writeln!(game.output(), "{}\n", game.text(&npc.description)).unwrap();
+        if game.is_hostile(npc) && !npc.items.is_empty() {
+            let message = game
+                .level
+                .narrator
+                .say_with("npc-wont-trade", &[("name", &npc.name)]);
+            writeln!(game.output(), "{}", message).unwrap();
+        } else {
+            for (item, cost) in npc.items_iter(&game.item_db) {
+                let cost = currency::format(game.price(npc, cost), &game.item_db);
+                let price = game
+                    .level
+                    .narrator
+                    .say_with("price", &[("item", &item.name), ("cost", &cost)]);
+                writeln!(game.output(), "  ‣ {}", price).unwrap();
+            }
+        }
+        writeln!(game.output()).unwrap();
+        return CommandResult::Success;
+    }
+
+    // Look at a crowd?
+    if let Some(crowd) = game.room.get_crowd(&game.level, target) {
+        writeln!(game.output(), "{}", crowd.description).unwrap();
+        return CommandResult::Success;
+    }
+
+    // Look at an npc's item?
+    for (_, npc) in game.npcs_at(&game.room.coord) {
+        for sale_item in npc.items.iter() {
+            if *target == sale_item.id {
+                let item = game.item_db.get(target);
+                writeln!(game.output(), "{}\n", item.description).unwrap();
+                return CommandResult::Success;
+            }
+        }
+    }
+
+    // Look at your own items?
+    for inv_item in game.save_state.inventory.items.iter() {
+        if *target == inv_item.id {
+            let item = game.item_db.get(target);
+            writeln!(game.output(), "{}\n", item.description).unwrap();
+            return CommandResult::Success;
+        }
+    }
+
+    let message = game
+        .level
+        .narrator
+        .say_with("look-missing", &[("target", target)]);
+    print_failure(game, &message);
+    suggest_target(game, target);
+    writeln!(game.output()).unwrap();
+    CommandResult::Failure
+}
+
+fn talk_command<T: Environment>(game: &Game<T>, target: &str) -> CommandResult {
+    // Talk to something in the room through an action?
+    if let Some(action) = game.find_action(Verb::Talk, target, None) {
+        writeln!(game.output(), "{}", game.text(&action.value)).unwrap();
+        game.follow(action);
+        return CommandResult::Success;
+    }
+
+    // Talk to an npc?
+    if let Some((id, npc)) = game.find_npc(target) {
+        game.emit(GameEvent::NpcTalkedTo(id.to_string()));
+        if game.is_hostile(npc) {
+            let hostile = game
+                .faction(npc)
+                .and_then(|(_, faction)| faction.hostile.as_ref());
+            let text = match hostile {
+                Some(passage) => game.text(passage),
+                None => game
+                    .level
+                    .narrator
+                    .say_with("npc-hostile", &[("name", &npc.name)]),
+            };
+            print_dialogue(game, &text, &npc.voice);
+            return CommandResult::Success;
+        }
+        print_dialogue(game, &game.text(&npc.talk), &npc.voice);
+        let topics = game.topics(npc);
+        if !topics.is_empty() {
+            game.conversation.replace(Some(id.to_string()));
+            writeln!(
+                game.output(),
+                "{}",
+                game.level.narrator.say("conversation-topics")
+            )
+            .unwrap();
+            for (index, topic) in topics.iter().enumerate() {
+                let name = topic.targets.first().map(String::as_str).unwrap_or("");
+                writeln!(game.output(), "  {}. {}", index + 1, name).unwrap();
+            }
+            writeln!(
+                game.output(),
+                "{}\n",
+                game.level.narrator.say("conversation-bye")
+            )
+            .unwrap();
+        }
+        return CommandResult::Success;
+    }
+
+    // Talk to a crowd?
+    if let Some(crowd) = game.room.get_crowd(&game.level, target) {
+        writeln!(game.output(), "{}", crowd.talk).unwrap();
+        return CommandResult::Success;
+    }
+
+    let message = game
+        .level
+        .narrator
+        .say_with("talk-missing", &[("target", target)]);
+    print_failure(game, &message);
+    suggest_target(game, target);
+    CommandResult::Failure
+}
+
+fn ask_command<T: Environment>(game: &Game<T>, target: Option<&str>, topic: &str) -> CommandResult {
+    let found = match target {
+        Some(target) => game.find_npc(target),
+        None => game.conversation_partner(),
+    };
+    let npc = match (found, target) {
+        (Some((id, npc)), _) => {
+            game.emit(GameEvent::NpcTalkedTo(id.to_string()));
+            npc
+        }
+        (None, Some(target)) => {
+            let message = game
+                .level
+                .narrator
+                .say_with("talk-missing", &[("target", target)]);
+            print_failure(game, &message);
+            suggest_target(game, target);
+            return CommandResult::Failure;
+        }
+        (None, None) => {
+            print_failure(game, &game.level.narrator.say("ask-nobody"));
+            return CommandResult::Failure;
+        }
+    };
+    if game.is_hostile(npc) {
+        let message = game
+            .level
+            .narrator
+            .say_with("npc-hostile", &[("name", &npc.name)]);
+        print_failure(game, &message);
+        return CommandResult::Failure;
+    }
+    let found = game
+        .topics(npc)
+        .into_iter()
+        .find(|other| game.level.vocabulary.matches(other.targets.iter(), topic));
+    match found {
+        Some(found) => {
+            print_dialogue(game, &game.text(&found.value), &npc.voice);
+            game.effects
+                .borrow_mut()
+                .extend(found.effects.iter().cloned());
+            CommandResult::Success
+        }
+        None => {
+            let message = game
+                .level
+                .narrator
+                .say_with("ask-unknown", &[("name", &npc.name), ("topic", topic)]);
+            print_failure(game, &message);
+            CommandResult::Failure
+        }
+    }
+}
+
+fn bye_command<T: Environment>(game: &Game<T>) {
+    let npc = game.conversation_npc();
+    game.conversation.replace(None);
+    let message = match npc {
+        Some(npc) => game
+            .level
+            .narrator
+            .say_with("conversation-end", &[("name", &npc.name)]),
+        None => game.level.narrator.say("bye-nobody"),
+    };
+    writeln!(game.output(), "{}", message).unwrap();
+}
+
+fn emote_command<T: Environment>(
+    game: &Game<T>,
+    emote: &Emote,
+    target: Option<String>,
+) -> CommandResult {
+    let name = emote.lowercase_string().to_string();
+    match target {
+        Some(target) => {
+            // Does something react to the emote?
+            if let Some(action) = game.find_action(Verb::Emote, &target, Some(&name)) {
+                writeln!(game.output(), "{}", game.text(&action.value)).unwrap();
+                game.follow(action);
+                return CommandResult::Success;
+            }
+
+            if let Some(npc) = game.get_npc(&target) {
+                let message = game.level.narrator.say_with(
+                    "emote-npc",
+                    &[("verb", &name), ("target", &npc.name.to_lowercase())],
+                );
+                writeln!(game.output(), "{}", message).unwrap();
+                return CommandResult::Success;
+            }
+
+            let message = game
+                .level
+                .narrator
+                .say_with("emote-missing", &[("verb", &name), ("target", &target)]);
+            print_failure(game, &message);
+            suggest_target(game, &target);
+            CommandResult::Failure
+        }
+        None => {
+            // Emotes without a target can still get a reaction from the room.
+            let action = game.room.actions_iter(&game.level).find(|action| {
+                action.verb == Verb::Emote
+                    && action.targets.is_empty()
+                    && action.alias.as_ref() == Some(&name)
+            });
+            match action {
+                Some(action) => {
+                    writeln!(game.output(), "{}", game.text(&action.value)).unwrap();
+                    game.follow(action);
+                }
+                None => {
+                    let id = format!("{}-unnoticed", name);
+                    writeln!(game.output(), "{}", game.level.narrator.say(&id)).unwrap();
+                }
+            }
+            CommandResult::Success
+        }
+    }
+}
+
+/// List the level's content tags, or toggle whether a tag is filtered out. Flagged
+/// passages are swapped for their tamer alternatives while their tag is filtered.
+fn filter_command<T: Environment>(game: &mut Game<T>, target: Option<String>) -> CommandResult {
+    let narrator = &game.level.narrator;
+    if game.level.content_tags.is_empty() {
+        writeln!(game.output(), "{}", narrator.say("filter-nothing")).unwrap();
+        return CommandResult::Success;
+    }
+
+    match target.as_deref() {
+        None => {
+            print_box(game, &narrator.say("filter"));
+            for (tag, description) in game.level.content_tags.iter() {
+                let state = if game.save_state.content_filter.contains(tag) {
+                    narrator.say("filter-filtered")
+                } else {
+                    narrator.say("filter-shown")
+                };
+                let name = game.styled(Style::Item, tag);
+                writeln!(
+                    game.output(),
+                    "  ‣ {} ({}) {}",
+                    name,
+                    state,
+                    description.trim_end()
+                )
+                .unwrap();
+            }
+            writeln!(game.output(), "\n{}\n", narrator.say("filter-usage")).unwrap();
+        }
+        Some("all") => {
+            game.save_state.content_filter = game.level.content_tags.keys().cloned().collect();
+            writeln!(game.output(), "{}", narrator.say("filter-all")).unwrap();
+        }
+        Some("none") => {
+            game.save_state.content_filter.clear();
+            writeln!(game.output(), "{}", narrator.say("filter-none")).unwrap();
+        }
+        Some(tag) if game.level.content_tags.contains_key(tag) => {
+            let id = if game.save_state.content_filter.remove(tag) {
+                "filter-tag-shown"
+            } else {
+                game.save_state.content_filter.insert(tag.to_string());
+                "filter-tag-filtered"
+            };
+            let message = narrator.say_with(id, &[("tag", tag)]);
+            writeln!(game.output(), "{}", message).unwrap();
+        }
+        Some(tag) => {
+            print_failure(game, &narrator.say_with("filter-missing", &[("tag", tag)]));
+            return CommandResult::Failure;
+        }
+    }
+    CommandResult::Success
+}
+
+/// A playtester's note, along with where they were in the game when they left it.
+#[derive(Serialize)]
+struct FeedbackEntry {
+    /// Seconds since the Unix epoch.
+    time: u64,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    level: String,
+    room: String,
+    coord: Coord,
+    turn: usize,
+    /// A hash of the save state, to tell whether two notes were left in the same game.
+    state: String,
+    comment: String,
+}
+
+/// Hash the save state in a way that is the same from run to run.
+fn hash_save_state(save_state: &SaveState) -> u64 {
+    let yml = serde_yaml::to_string(save_state).expect("Unable to serialize the game state.");
+    hash_str(&yml)
+}
+
+/// Show the player's preferences, or change one and save it for next time.
+fn config_command<T: Environment>(
+    game: &mut Game<T>,
+    setting: Option<(String, String)>,
+) -> CommandResult {
+    let (key, value) = match setting {
+        Some(setting) => setting,
+        None => {
+            let toml = game.config.to_toml();
+            writeln!(game.output(), "{}", toml).unwrap();
+            writeln!(game.output(), "{}", game.level.narrator.say("config-usage")).unwrap();
+            return CommandResult::Success;
+        }
+    };
+    let mut config = game.config.clone();
+    let changed = config
+        .set(&key, &value)
+        .and_then(|()| game.output().set_config(&config));
+    if let Err(message) = changed {
+        let message = game
+            .level
+            .narrator
+            .say_with("config-invalid", &[("error", &message)]);
+        print_failure(game, &message);
+        return CommandResult::Failure;
+    }
+    game.set_mute(config.mute);
+    game.config = config;
+    let message = match key.as_str() {
+        "mute" if game.config.mute => game.level.narrator.say("mute-on"),
+        "mute" => game.level.narrator.say("mute-off"),
+        _ => game
+            .level
+            .narrator
+            .say_with("config-changed", &[("key", &key)]),
+    };
+    writeln!(game.output(), "{}", message).unwrap();
+    let path = game.output().config_path();
+    if let Some(path) = path {
+        if let Err(err) = fs::write(&path, game.config.to_toml()) {
+            let message = game.level.narrator.say_with(
+                "config-unsaved",
+                &[
+                    ("path", &path.display().to_string()),
+                    ("error", &err.to_string()),
+                ],
+            );
+            print_failure(game, &message);
+        }
+    }
+    CommandResult::Success
+}
+
+fn feedback_command<T: Environment>(game: &Game<T>, comment: String) -> CommandResult {
+    let path = match game.output().feedback_path() {
+        Some(path) => path,
+        None => {
+            print_failure(game, &game.level.narrator.say("feedback-unavailable"));
+            return CommandResult::Failure;
+        }
+    };
+    let entry = FeedbackEntry {
+        time: SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs()),
+        level: game.save_state.level.clone(),
+        room: game.room.title.clone(),
+        coord: game.save_state.coord,
+        turn: game.save_state.turn,
+        state: format!("{:016x}", hash_save_state(&game.save_state)),
+        comment,
+    };
+    // Each entry is added as an item of a YML list, so the file stays valid as it
+    // grows.
+    let yml = serde_yaml::to_string(&[entry]).expect("Unable to serialize the feedback.");
+    let yml = yml.trim_start_matches("---\n");
+    let written = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| writeln!(file, "{}", yml.trim_end()));
+    match written {
+        Ok(()) => {
+            writeln!(
+                game.output(),
+                "{}",
+                game.level.narrator.say("feedback-saved")
+            )
+            .unwrap();
+            CommandResult::Success
+        }
+        Err(err) => {
+            let message = game.level.narrator.say_with(
+                "feedback-unsaved",
+                &[
+                    ("path", &path.display().to_string()),
+                    ("error", &err.to_string()),
+                ],
+            );
+            print_failure(game, &message);
+            CommandResult::Failure
+        }
+    }
+}
+
+fn help_target_command<T: Environment>(game: &Game<T>, target: &str) -> CommandResult {
+    // Help something in the room through an action?
+    if let Some(action) = game.find_action(Verb::Help, target, None) {
+        writeln!(game.output(), "{}\n", game.text(&action.value)).unwrap();
+        game.follow(action);
+        return CommandResult::Success;
+    }
+
+    let message = game
+        .level
+        .narrator
+        .say_with("help-missing", &[("target", target)]);
+    writeln!(game.output(), "{}\n", message).unwrap();
+    CommandResult::Failure
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn test_look() {
+        insta::assert_yaml_snapshot!(run_game(vec!["look"]), @r###"
+        ---
+        - Stone End Docks
+        - ""
+        - "    You are standing at the Stone End docks. To the south, a city guard stands in a guard "
+        - "    post, blocking the entrance to the docks. You can see \"The Torbay\" anchored in the "
+        - "    port, the ship you came in on. The rowboat that brought you in from the ship is tied "
+        - "    up on the docks. The sailors are nowhere to be seen. "
+        - ""
+        - "    To the north the city awaits. "
+        - ""
+        - ""
+        - "Exits: n _ _ _"
+        "###);
+    }
+
+    #[test]
+    fn test_parse_compound_command() {
+        assert_eq!(
+            parse_command(
+                "take sword then go north and look. inventory".into(),
+                &Vocabulary::default(),
+                &Narrator::default()
+            ),
+            Ok(vec![
+                ParsedCommand::Take("sword".into()),
+                ParsedCommand::Move(Direction::North),
+                ParsedCommand::Look(None),
+                ParsedCommand::Inventory(InventoryView::Page(1)),
+            ])
+        );
+        assert_eq!(
+            parse_command("".into(), &Vocabulary::default(), &Narrator::default()),
+            Ok(vec![ParsedCommand::Look(None)])
+        );
+        assert_eq!(
+            parse_command("look.".into(), &Vocabulary::default(), &Narrator::default()),
+            Ok(vec![ParsedCommand::Look(None)])
+        );
+        // What's said is kept whole, up to a "then".
+        assert_eq!(
+            parse_command(
+                "say 3.14 then answer salt and pepper. then look".into(),
+                &Vocabulary::default(),
+                &Narrator::default()
+            ),
+            Ok(vec![
+                ParsedCommand::Say("3.14".into()),
+                ParsedCommand::Say("salt and pepper.".into()),
+                ParsedCommand::Look(None),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_compound_command() {
+        insta::assert_yaml_snapshot!(run_game(vec!["north then north"]), @r###"
+        ---
+        - Stone End Market Road
+        - ""
+        - "    The road you are on is surrounded by densely packed houses. A sailor from the ship is "
+        - "    chatting up a woman in a worn, but colorful dress. "
+        - ""
+        - "    To the north the city begins to open up. "
+        - ""
+        - "    The docks can be seen to the south. A forest of masts poke out from the bay. Smaller "
+        - "    boats can be seen shuffling people to the busy port. "
+        - ""
+        - ""
+        - "Exits: n _ s _"
+        - Stone End Market Gate
+        - ""
+        - "    You stand in front of a gate. Two guards stand there, pikes in hand. Over the gate "
+        - "    hangs a festive hand painted banner. "
+        - ""
+        - ""
+        - "Exits: n _ s _"
+        "###);
+    }
+
+    #[test]
+    fn test_compound_command_stops_on_failure() {
+        insta::assert_yaml_snapshot!(run_game(vec!["north then west then north"]), @r###"
+        ---
+        - Stone End Market Road
+        - ""
+        - "    The road you are on is surrounded by densely packed houses. A sailor from the ship is "
+        - "    chatting up a woman in a worn, but colorful dress. "
+        - ""
+        - "    To the north the city begins to open up. "
+        - ""
+        - "    The docks can be seen to the south. A forest of masts poke out from the bay. Smaller "
+        - "    boats can be seen shuffling people to the busy port. "
+        - ""
+        - ""
+        - "Exits: n _ s _"
+        - You cannot move west.
+        "###);
+    }
+
+    #[test]
+    fn test_compound_command_stops_on_failed_look() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+regions: {}
+npcs: {}
+rooms:
+  - { title: Shed, coord: [1, 1, 0], description: A shed., items: [{ id: lantern, quantity: 1 }] }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        game.output().output.clear();
+        assert!(game
+            .step("look at nothing then take lantern".into())
+            .is_none());
+        assert_eq!(game.save_state.inventory.count("lantern"), 0);
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - "You don't see a nothing."
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_crowd() {
+        insta::assert_yaml_snapshot!(run_game(vec!["north", "north", "north"]), @r###"
+        ---
+        - Stone End Market
+        - ""
+        - "    You step into a bustling market. Merchants are shouting into the crowd, hawking their "
+        - "    wares. You see small urchin children running around. You put your hand protectively "
+        - "    over your belt purse. "
+        - ""
+        - ""
+        - You are pressed shoulder to shoulder in the market throng.
+        - ""
+        - A dull piece of metal is embedded between two cobblestones.
+        - ""
+        - "Exits: n e s w"
+        - You enter the market.
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_talk_to_crowd() {
+        insta::assert_yaml_snapshot!(run_game(vec!["north", "north", "north", "talk to throng"]), @r###"
+        ---
+        - "You try to strike up a conversation, but the crowd flows around you like water"
+        - around a stone. Nobody has time to stop and chat on market day.
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_npc_voice() {
+        insta::assert_yaml_snapshot!(run_game(vec!["north", "north", "north", "west", "talk to farmer"]), @r###"
+        ---
+        - "│ The farmer eyes you with a merry twinkle. \"W-Would you like to b-buy something?"
+        - │ These apples a-are locally sourced from B-Buckleberry Farms. They make f-for great
+        - "│ eating as t-they are, or fermented i-in your next apple m-mash.\""
+        - "│ "
+        - "│ You see him happily pat some kind bulge in his pocket that from the smell of him,"
+        - │ you assume is a small bottle of apple jack.
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_content_filter() {
+        insta::assert_yaml_snapshot!(run_game(vec!["filter"]), @r###"
+        ---
+        - ╔═════════════════╗
+        - "║ Content filter: ║"
+        - ╚═════════════════╝
+        - "  ‣ alcohol (shown) References to drinking."
+        - "  ‣ innuendo (shown) Suggestive dialogue and descriptions."
+        - ""
+        - "Use \"filter <tag>\" to toggle a tag."
+        - ""
+        "###);
+        insta::assert_yaml_snapshot!(run_game(vec!["north", "filter innuendo", "talk to woman"]), @r###"
+        ---
+        - "The woman waves you off and says, \"Can't you see we're talking? Come back"
+        - "later.\""
+        - ""
+        "###);
+        insta::assert_yaml_snapshot!(run_game(vec!["filter all", "north", "north", "north", "west", "talk to farmer"]), @r###"
+        ---
+        - "│ The farmer eyes you with a merry twinkle. \"W-Would you like to b-buy something?"
+        - │ These apples a-are locally sourced from B-Buckleberry Farms. They make f-for great
+        - "│ eating as t-they are, or baked i-into your next apple p-pie.\""
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_par() {
+        let route = vec![
+            "north", "north", "north", "north", "east", "east", "east", "north", "north", "north",
+            "north",
+        ];
+        insta::assert_yaml_snapshot!(run_game(route), @r###"
+        ---
+        - Dark Alleyway
+        - ""
+        - "    A rope tumbles from the walls directly in front of you. It swings for a moment, then "
+        - "    stands still. You can still hear the shouts and clanging coming from above. "
+        - ""
+        - ""
+        - "Exits: n _ s _"
+        - ╔═════════╗
+        - ║ Summary ║
+        - ╚═════════╝
+        - "  Turns taken: 11"
+        - "  Par:         12"
+        - ""
+        - 1 under par!
+        - ""
+        - "You made it here in good time. Whatever is going on up on the walls, you have a"
+        - feeling that you arrived before anyone else.
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_npc_pools() {
+        insta::assert_yaml_snapshot!(run_game(vec!["north", "north", "north", "west", "look at farmer"]), @r###"
+        ---
+        - "A stout apple farmer stands before you, her sleeves rolled up past the elbows."
+        - Bits of straw cling to her apron.
+        - ""
+        - She is selling apples.
+        - ""
+        - ""
+        - "  ‣ apple (1 gold)"
+        - ""
+        "###);
+        insta::assert_yaml_snapshot!(run_game(vec!["north", "north", "north", "west", "bow to farmer"]), @r###"
+        ---
+        - "You bow at the apple farmer hettie. They don't seem to notice."
+        "###);
+    }
+
+    #[test]
+    fn test_derived_targets() {
+        insta::assert_yaml_snapshot!(run_game(vec!["drop sword", "take sword"]), @r###"
+        ---
+        - You place the sword in your inventory.
+        "###);
+    }
+
+    #[test]
+    fn test_save_level_gone() {
+        let path = std::env::temp_dir().join("text-adventure-test-level-gone.yml");
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = Game::new(ItemDatabase::new(), &mut command_runner).unwrap();
+        game.save_state.level = "sunken-city".into();
+        game.save_state.coord = Coord { x: 0, y: 0, z: 0 };
+        fs::write(&path, serde_yaml::to_string(&game.save_state).unwrap()).unwrap();
+        drop(game);
+
+        // The save is in a level the campaign no longer has, so it goes to the start.
+        let mut command_runner = CommandRunner::new(vec![]);
+        command_runner.save_path = Some(path.clone());
+        let game = Game::new(ItemDatabase::new(), &mut command_runner).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(game.save_state.level, "stone-end-market");
+        assert_eq!(game.save_state.coord, game.level.entry);
+        assert!(!game
+            .save_state
+            .level_inventories
+            .contains_key("sunken-city"));
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - "The level has changed since this game was saved:"
+        - "  ‣ The level sunken-city is no longer in the campaign, so you are back at the start."
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_new_from_strings() {
+        let level = fs::read_to_string(LEVEL_PATH).unwrap();
+        let items = fs::read_to_string("data/items.yml").unwrap();
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = Game::new_from_strings(&level, &items, &mut command_runner).unwrap();
+        game.start();
+        game.output().output.clear();
+        assert!(game.step("north then look at woman".into()).is_none());
+        drop(game);
+        insta::assert_yaml_snapshot!(command_runner.get_last_output(), @r###"
+        ---
+        - Stone End Market Road
+        - ""
+        - "    The road you are on is surrounded by densely packed houses. A sailor from the ship is "
+        - "    chatting up a woman in a worn, but colorful dress. "
+        - ""
+        - "    To the north the city begins to open up. "
+        - ""
+        - "    The docks can be seen to the south. A forest of masts poke out from the bay. Smaller "
+        - "    boats can be seen shuffling people to the busy port. "
+        - ""
+        - ""
+        - "Exits: n _ s _"
+        - The woman has heavy caked makeup on. Her dress is purple with a frayed
+        - "hem. There is lace. So much lace... well, spilling out of everywhere."
+        - ""
+        - ""
+        "###);
+
+        let mut command_runner = CommandRunner::new(vec![]);
+        assert!(Game::new_from_strings("maps: 0", &items, &mut command_runner).is_err());
+
+        // A map with a room that isn't described is reported, rather than exiting.
+        let level = r#"
+maps:
+  - - "----"
+    - "-..-"
+    - "----"
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+rooms:
+  - { title: Gate, coord: [1, 1, 0], description: A gate. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        assert!(Game::new_from_strings(level, &items, &mut command_runner).is_err());
+    }
+
+    #[test]
+    fn test_missing_room_inventory() {
+        let level = fs::read_to_string(LEVEL_PATH).unwrap();
+        let items = fs::read_to_string("data/items.yml").unwrap();
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = Game::new_from_strings(&level, &items, &mut command_runner).unwrap();
+        // Simulate a save from before any of the rooms had inventories.
+        game.save_state.room_inventories.clear();
+        for input in ["drop sword", "north", "north", "north", "take gold"].iter() {
+            assert!(game.step(input.to_string()).is_none());
+        }
+        game.output().output.clear();
+        assert!(game.step("inventory".into()).is_none());
+        drop(game);
+        insta::assert_yaml_snapshot!(command_runner.get_last_output(), @r###"
+        ---
+        - ╔═════════════════╗
+        - "║ Your inventory: ║"
+        - ╚═════════════════╝
+        - "  ‣ 18 gold"
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_reload() {
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = Game::new(ItemDatabase::new(), &mut command_runner).unwrap();
+        for input in ["drop sword", "north"].iter() {
+            assert!(game.step(input.to_string()).is_none());
+        }
+        let coord = game.save_state.coord;
+        game.output().output.clear();
+        assert!(game.step("reload".into()).is_none());
+        assert!(game.step("south then take sword".into()).is_none());
+        assert_eq!(game.save_state.turn, 4);
+        assert_ne!(game.save_state.coord, coord);
+        drop(game);
+        insta::assert_yaml_snapshot!(command_runner.get_last_output(), @r###"
+        ---
+        - Reloaded the level.
+        - ""
+        - Stone End Docks
+        - ""
+        - "    You are standing at the Stone End docks. To the south, a city guard stands in a guard "
+        - "    post, blocking the entrance to the docks. You can see \"The Torbay\" anchored in the "
+        - "    port, the ship you came in on. The rowboat that brought you in from the ship is tied "
+        - "    up on the docks. The sailors are nowhere to be seen. "
+        - ""
+        - "    To the north the city awaits. "
+        - ""
+        - ""
+        - a sword
+        - "Exits: n _ _ _"
+        - You place the sword in your inventory.
+        "###);
+
+        let level = fs::read_to_string(LEVEL_PATH).unwrap();
+        let items = fs::read_to_string(ITEMS_PATH).unwrap();
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = Game::new_from_strings(&level, &items, &mut command_runner).unwrap();
+        game.output().output.clear();
+        assert!(game.step("reload".into()).is_none());
+        drop(game);
+        insta::assert_yaml_snapshot!(command_runner.get_last_output(), @r###"
+        ---
+        - "This level wasn't loaded from a file, so it can't be reloaded."
+        "###);
+    }
+
+    #[test]
+    fn test_reconcile_save() {
+        let item_db = ItemDatabase::new();
+        let level: Level = parse_yml(&LEVEL_PATH.into());
+        let mut save_state = SaveState::initialize(&item_db, &level, 0);
+        assert!(save_state.reconcile(&level, &item_db).is_empty());
+
+        // Simulate a save made against an older version of the level.
+        let gone = Coord { x: 0, y: 0, z: 0 };
+        save_state.coord = gone;
+        save_state.room_inventories.insert(
+            gone,
+            RoomInventory {
+                inventory: vec![],
+                found_exits: BTreeSet::new(),
+                moved: BTreeSet::new(),
+                visited: true,
+                spawning: BTreeMap::new(),
+            },
+        );
+        save_state.room_inventories.remove(&level.entry);
+        let mut unicorn = item_db.get("apple").clone();
+        unicorn.id = "unicorn".into();
+        unicorn.name = "unicorn".into();
+        save_state.inventory.add_item(unicorn);
+        save_state.inventory.items[0].description = "An old description.".into();
+
+        insta::assert_yaml_snapshot!(save_state.reconcile(&level, &item_db), @r###"
+        ---
+        - "The room you were in is gone, so you are back at the start."
+        - Your unicorn is no longer in the game.
+        - "The room at [0, 0, 0] is no longer in the level."
+        "###);
+        assert_eq!(save_state.coord, level.entry);
+        assert!(level
+            .rooms
+            .iter()
+            .all(|room| save_state.room_inventories.contains_key(&room.coord)));
+        assert_eq!(
+            save_state.inventory.items[0].description,
+            item_db.get("sword").description
+        );
+    }
+
+    #[test]
+    fn test_stable_save() {
+        let item_db = ItemDatabase::new();
+        let level: Level = parse_yml(&LEVEL_PATH.into());
+        let to_yml = |save_state: &SaveState| serde_yaml::to_string(save_state).unwrap();
+        let yml = to_yml(&SaveState::initialize(&item_db, &level, 0));
+        assert_eq!(yml, to_yml(&SaveState::initialize(&item_db, &level, 0)));
+        let loaded: SaveState = serde_yaml::from_str(&yml).unwrap();
+        assert_eq!(yml, to_yml(&loaded));
+    }
+
+    #[test]
+    fn test_inventory_pages() {
+        let level = fs::read_to_string(LEVEL_PATH).unwrap();
+        let items = fs::read_to_string(ITEMS_PATH).unwrap();
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = Game::new_from_strings(&level, &items, &mut command_runner).unwrap();
+        for index in 0..12 {
+            let mut item = game.item_db.get("apple").clone();
+            item.id = format!("apple-{}", index);
+            item.name = format!("apple {}", index);
+            game.save_state.inventory.add_item(item);
+        }
+        game.output().output.clear();
+        assert!(game.step("inventory 2".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - ╔═════════════════╗
+        - "║ Your inventory: ║"
+        - ╚═════════════════╝
+        - "  ‣ apple 9"
+        - "  ‣ apple 10"
+        - "  ‣ apple 11"
+        - ""
+        - Page 2 of 2.
+        - ""
+        "###);
+        assert!(game.step("inventory".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - ╔═════════════════╗
+        - "║ Your inventory: ║"
+        - ╚═════════════════╝
+        - "  ‣ 17 gold"
+        - "  ‣ sword"
+        - "  ‣ apple 0"
+        - "  ‣ apple 1"
+        - "  ‣ apple 2"
+        - "  ‣ apple 3"
+        - "  ‣ apple 4"
+        - "  ‣ apple 5"
+        - "  ‣ apple 6"
+        - "  ‣ apple 7"
+        - "  ‣ apple 8"
+        - ""
+        - "Page 1 of 2, type \"inventory 2\" for more."
+        - ""
+        "###);
+        assert!(game.step("inventory find weapon".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - ╔═══════════════════════════════╗
+        - "║ Your items matching \"weapon\": ║"
+        - ╚═══════════════════════════════╝
+        - "  ‣ sword"
+        - ""
+        "###);
+        assert!(game.step("inv find pear".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - ╔═════════════════════════════╗
+        - "║ Your items matching \"pear\": ║"
+        - ╚═════════════════════════════╝
+        - ""
+        - "Nothing you carry matches \"pear\"."
+        - ""
+        "###);
+        assert!(game.step("inventory 3".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Your inventory only has 2 pages.
+        "###);
+    }
+
+    #[test]
+    fn test_compare() {
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = Game::new(ItemDatabase::new(), &mut command_runner).unwrap();
+        let apple = game.item_db.get("apple").clone();
+        game.save_state.inventory.add_item(apple);
+        game.output().output.clear();
+        assert!(game.step("compare sword with apple".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - ╔═════════════════╗
+        - ║ sword vs. apple ║
+        - ╚═════════════════╝
+        - "              sword   apple"
+        - "  Kind        weapon  consumable"
+        - "  Damage      6       -"
+        - "  Weight      3       1"
+        - "  Value       12      1"
+        - "  Durability  40      -"
+        - ""
+        "###);
+        assert!(game.step("compare apple against purse".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - ╔════════════════╗
+        - ║ apple vs. gold ║
+        - ╚════════════════╝
+        - "              apple       gold"
+        - "  Kind        consumable  money"
+        - "  Weight      1           -"
+        - "  Value       1           100"
+        - ""
+        "###);
+        drop(game);
+
+        insta::assert_yaml_snapshot!(run_game(vec!["compare sword to dagger"]), @r###"
+        ---
+        - "You pat your pockets, but you aren't carrying a dagger to compare."
+        "###);
+        insta::assert_yaml_snapshot!(run_game(vec!["compare sword"]), @r###"
+        ---
+        - compare sword with... what?
+        "###);
+    }
+
+    #[test]
+    fn test_starting_inventory() {
+        let item_db = ItemDatabase::new();
+        let mut level: Level = parse_yml(&LEVEL_PATH.into());
+        level.start = serde_yaml::from_str(
+            "{ items: [{ id: apple, quantity: 3 }], stats: { health: 5 }, flags: [sailor] }",
+        )
+        .unwrap();
+        let save_state = SaveState::initialize(&item_db, &level, 0);
+        let items: Vec<_> = save_state
+            .inventory
+            .items
+            .iter()
+            .map(|item| (item.id.as_str(), item.quantity))
+            .collect();
+        assert_eq!(items, vec![("apple", 3)]);
+        assert_eq!(save_state.stats.get("health"), Some(&5));
+        assert!(save_state.flags.contains("sailor"));
+
+        // Levels without a start section get the original sword and gold.
+        let start: level::Start = Default::default();
+        let ids: Vec<_> = start.items.iter().map(|item| item.id.as_str()).collect();
+        assert_eq!(ids, vec!["sword", "gold"]);
+    }
+
+    #[test]
+    fn test_jam_mode() {
+        let level = r#"
+maps: [["----", "-..-", "----"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+rooms:
+  - { title: Shore, coord: [1, 1, 0], description: A pebbly shore. }
+  - { title: Cave, coord: [2, 1, 0], id: cave, description: A dark cave. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        command_runner.jam_started = Some(Instant::now());
+        command_runner.files.insert(
+            CAMPAIGN_PATH,
+            "{ start: jam, levels: { jam: jam.yml } }".into(),
+        );
+        command_runner.files.insert("jam.yml", level.into());
+        let mut game = Game::new(ItemDatabase::new(), &mut command_runner).unwrap();
+        game.start();
+        assert!(game.save_state.debug);
+        for command in ["goto cave", "goto 1 1 0", "goto tower"] {
+            assert!(game.step(command.into()).is_none());
+        }
+        let broken = level.replace(
+            "id: cave,",
+            "id: cave, items: [{ id: unicorn, quantity: 1, name: ~, pickup: ~ }],",
+        );
+        game.output().files.insert("jam.yml", broken);
+        assert!(game.step("reload".into()).is_none());
+        assert_eq!(game.save_state.turn, 0);
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Shore
+        - ""
+        - "    A pebbly shore. "
+        - ""
+        - ""
+        - "Coord: [1, 1, 0]"
+        - "Exits: _ e _ _"
+        - Cave
+        - ""
+        - "    A dark cave. "
+        - ""
+        - ""
+        - "Coord: [2, 1, 0]"
+        - "Exits: _ _ _ w"
+        - Shore
+        - ""
+        - "    A pebbly shore. "
+        - ""
+        - ""
+        - "Coord: [1, 1, 0]"
+        - "Exits: _ e _ _"
+        - "There's no room tower on the map."
+        - Reloaded the level.
+        - ""
+        - "The level has some problems:"
+        - "  ‣ The room \"Cave\" has an unknown item \"unicorn\"."
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_scenarios() {
+        let level = r#"
+maps: [["----", "-..-", "----"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+rooms:
+  - { title: Dock, coord: [1, 1, 0], description: A busy dock. }
+  - { title: Hold, coord: [2, 1, 0], description: A dark ship's hold. }
+scenarios:
+  - { id: sailor, name: The Sailor, description: Step off the ship. }
+  - id: stowaway
+    name: The Stowaway
+    entry: [2, 1, 0]
+    start: { items: [{ id: apple, quantity: 2 }] }
+    intro: You climb out of a barrel.
+"#;
+        let mut command_runner = CommandRunner::new(vec!["the stowaway"]);
+        let mut game = game_from_level(level, &mut command_runner);
+        game.start();
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - You climb out of a barrel.
+        - ""
+        - Hold
+        - ""
+        - "    A dark ship's hold. "
+        - ""
+        - ""
+        - "Exits: _ _ _ w"
+        "###);
+        assert_eq!(game.save_state.scenario.as_deref(), Some("stowaway"));
+        let items: Vec<_> = game
+            .save_state
+            .inventory
+            .items
+            .iter()
+            .map(|item| (item.id.as_str(), item.quantity))
+            .collect();
+        assert_eq!(items, vec![("apple", 2)]);
+
+        // Choices that aren't understood begin the first scenario.
+        let mut command_runner = CommandRunner::new(vec!["pirate"]);
+        let mut game = game_from_level(level, &mut command_runner);
+        game.start();
+        assert_eq!(game.save_state.scenario.as_deref(), Some("sailor"));
+        assert_eq!(game.save_state.coord, Coord { x: 1, y: 1, z: 0 });
+        assert_eq!(game.save_state.inventory.items.len(), 2);
+    }
+
+    #[test]
+    fn test_teleport() {
+        let level = r#"
+maps: [["-----", "-.-.-", "-----"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+rooms:
+  - title: Dock
+    id: dock
+    coord: [1, 1, 0]
+    description: A busy dock, with a gangplank to the north.
+    exits: { north: { entry: hold } }
+  - title: Hold
+    id: hold
+    coord: [3, 1, 0]
+    description: A dark ship's hold.
+    actions:
+      - verb: Look
+        targets: [ladder]
+        value: You climb the ladder.
+        teleport: { entry: dock }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        assert!(game.step("north".into()).is_none());
+        assert_eq!(game.room.title, "Hold");
+        game.output().output.clear();
+        assert!(game.step("look ladder".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - You climb the ladder.
+        - ""
+        - Dock
+        - ""
+        - "    A busy dock, with a gangplank to the north. "
+        - ""
+        - ""
+        - "Exits: n _ _ _"
+        "###);
+        assert_eq!(game.room.title, "Dock");
+    }
+
+    #[test]
+    fn test_npc_movement() {
+        let level = r#"
+maps: [["-------", "-.....-", "-------"]]
+entry: [1, 1, 0]
+npcs:
+  cat:
+    name: A tabby cat
+    targets: [cat]
+    talk: Mrow.
+    items: []
+    movement: { wander: { region: market, chance: 1 } }
+  dog:
+    name: Your dog
+    description: A scruffy dog, wagging its tail.
+    targets: [dog]
+    talk: Woof.
+    items: []
+    movement: { follow: { when: flags.friends } }
+regions:
+  market: { actions: [] }
+start: { flags: [friends] }
+rooms:
+  - { title: Plaza, coord: [1, 1, 0], description: A plaza., npcs: [dog], regions: [market] }
+  - { title: Stalls, coord: [2, 1, 0], description: Stalls., npcs: [cat], regions: [market] }
+  - { title: Fountain, coord: [3, 1, 0], description: A fountain., regions: [market] }
+  - { title: Alley, coord: [4, 1, 0], description: An alley. }
+  - { title: Garden, coord: [5, 1, 0], description: A garden. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        for command in ["east", "east", "east", "east", "look cat", "look dog"] {
+            assert!(game.step(command.into()).is_none());
+        }
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Stalls
+        - ""
+        - "    Stalls. "
+        - ""
+        - ""
+        - A tabby cat is here.
+        - ""
+        - "Exits: _ e _ w"
+        - A tabby cat leaves to the east.
+        - ""
+        - Your dog follows you.
+        - ""
+        - Fountain
+        - ""
+        - "    A fountain. "
+        - ""
+        - ""
+        - A tabby cat is here.
+        - ""
+        - "Exits: _ e _ w"
+        - A tabby cat leaves to the west.
+        - ""
+        - Your dog follows you.
+        - ""
+        - Alley
+        - ""
+        - "    An alley. "
+        - ""
+        - ""
+        - "Exits: _ e _ w"
+        - Your dog follows you.
+        - ""
+        - Garden
+        - ""
+        - "    A garden. "
+        - ""
+        - ""
+        - "Exits: _ _ _ w"
+        - Your dog follows you.
+        - ""
+        - "You don't see a cat."
+        - ""
+        - "A scruffy dog, wagging its tail."
+        - ""
+        - ""
+        "###);
+
+        // The cat stays in the market, and undoing a turn puts the NPCs back.
+        let coord = |x| Some(Coord { x, y: 1, z: 0 });
+        assert_eq!(game.npc_coord("cat"), coord(1));
+        assert_eq!(game.npc_coord("dog"), coord(5));
+        let npc_coords = game.save_state.npc_coords.clone();
+        assert!(game.step("look".into()).is_none());
+        assert!(game.step("undo".into()).is_none());
+        assert_eq!(game.save_state.npc_coords, npc_coords);
+    }
+
+    #[test]
+    fn test_daemons() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+start: { stats: { health: 2 } }
+daemons:
+  hunger:
+    effects: [{ say: Your stomach growls. }, { stats: { health: -1 } }]
+  fuse:
+    enabled: false
+    every: 2
+    times: 1
+    effects: [{ say: The fuse burns out with a fizzle. }, { set_flag: fizzled }]
+rooms:
+  - title: Cellar
+    coord: [1, 1, 0]
+    description: "A damp cellar.{if flags.fizzled} It smells of smoke.{end}"
+    actions:
+      - verb: Look
+        targets: [fuse]
+        value: You light the fuse.
+        effects: [{ enable: fuse }]
+      - verb: Look
+        targets: [bread]
+        value: You eat the bread.
+        effects: [{ stats: { health: 1 } }, { disable: hunger }]
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        for command in ["look fuse", "look bread", "inventory", "look bread", "look"] {
+            assert!(game.step(command.into()).is_none());
+        }
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - You light the fuse.
+        - ""
+        - Your stomach growls.
+        - ""
+        - You eat the bread.
+        - ""
+        - The fuse burns out with a fizzle.
+        - ""
+        - ╔═════════════════╗
+        - "║ Your inventory: ║"
+        - ╚═════════════════╝
+        - "    (empty)"
+        - ""
+        - You eat the bread.
+        - ""
+        - Cellar
+        - ""
+        - "    A damp cellar. It smells of smoke. "
+        - ""
+        - ""
+        - "Exits: _ _ _ _"
+        "###);
+        assert_eq!(game.save_state.stats.get("health"), Some(&3));
+        assert!(!game.save_state.daemons["fuse"].enabled);
+    }
+
+    #[test]
+    fn test_timers() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+timers:
+  keg:
+    turns: 3
+    warnings: { 2: The fuse hisses., 1: The fuse is almost gone! }
+    effects: [{ say: The keg explodes! }, { set_flag: exploded }]
+    defused: [{ say: You pinch out the fuse just in time. }]
+rooms:
+  - title: Cellar
+    coord: [1, 1, 0]
+    description: "A cellar with a powder keg.{if timers.keg} Its fuse sputters.{end}"
+    actions:
+      - { verb: Custom, alias: light, targets: [fuse], value: You light the fuse., effects: [{ arm: keg }] }
+      - { verb: Custom, alias: pinch, targets: [fuse], value: You reach for the fuse., effects: [{ defuse: keg }] }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        let mut output = Vec::new();
+        for command in [
+            "light fuse",
+            "look",
+            "pinch fuse",
+            "light fuse",
+            "look",
+            "inventory",
+            "look",
+            "pinch fuse",
+        ] {
+            game.step(command.into());
+            output.extend(
+                take_output(&game)
+                    .into_iter()
+                    .filter(|line| !line.trim().is_empty() && !line.starts_with("Exits")),
+            );
+            if command == "look" && !game.save_state.flags.contains("exploded") {
+                // The countdown is kept in the save.
+                assert_eq!(game.save_state.timers.get("keg"), Some(&1));
+            }
+        }
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - You light the fuse.
+        - The fuse hisses.
+        - Cellar
+        - "    A cellar with a powder keg. Its fuse sputters. "
+        - The fuse is almost gone!
+        - You reach for the fuse.
+        - You pinch out the fuse just in time.
+        - You light the fuse.
+        - The fuse hisses.
+        - Cellar
+        - "    A cellar with a powder keg. Its fuse sputters. "
+        - The fuse is almost gone!
+        - ╔═════════════════╗
+        - "║ Your inventory: ║"
+        - ╚═════════════════╝
+        - "  ‣ 17 gold"
+        - "  ‣ sword"
+        - Cellar
+        - "    A cellar with a powder keg. Its fuse sputters. "
+        - The keg explodes!
+        - You reach for the fuse.
+        "###);
+        assert!(game.save_state.flags.contains("exploded"));
+        assert!(game.save_state.timers.is_empty());
+    }
+
+    #[test]
+    fn test_vehicles() {
+        let level = r#"
+maps: [["------", "-..~~-", "------"]]
+entry: [2, 1, 0]
+npcs: {}
+regions: {}
+vehicles:
+  rowboat:
+    name: the rowboat
+    targets: [rowboat, boat]
+    terrain: [water]
+    board: You climb into the rowboat.
+    moves: The oars creak.
+    blocked: The rowboat won't go over land.
+rooms:
+  - { title: Field, coord: [1, 1, 0], description: A muddy field. }
+  - { title: Jetty, coord: [2, 1, 0], description: A wooden jetty., vehicles: [rowboat] }
+  - { title: Bay, coord: [3, 1, 0], description: A calm bay. }
+  - { title: Open Water, coord: [4, 1, 0], description: Waves all around. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        let mut output = Vec::new();
+        for command in [
+            "east",
+            "look",
+            "board boat",
+            "board rowboat",
+            "east",
+            "dismount",
+            "east",
+            "west",
+            "west",
+            "west",
+            "dismount",
+            "west",
+            "east",
+        ] {
+            game.step(command.into());
+            output.extend(
+                take_output(&game)
+                    .into_iter()
+                    .filter(|line| !line.trim().is_empty() && !line.starts_with("Exits")),
+            );
+        }
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - "You'd sink like a stone. Maybe find a boat."
+        - Jetty
+        - "    A wooden jetty. "
+        - You see the rowboat here.
+        - You climb into the rowboat.
+        - "You're already aboard the rowboat."
+        - The oars creak.
+        - Bay
+        - "    A calm bay. "
+        - "You're aboard the rowboat."
+        - "There's nowhere to get off the rowboat out here."
+        - The oars creak.
+        - Open Water
+        - "    Waves all around. "
+        - "You're aboard the rowboat."
+        - The oars creak.
+        - Bay
+        - "    A calm bay. "
+        - "You're aboard the rowboat."
+        - The oars creak.
+        - Jetty
+        - "    A wooden jetty. "
+        - "You're aboard the rowboat."
+        - "The rowboat won't go over land."
+        - You get off the rowboat.
+        - Field
+        - "    A muddy field. "
+        - Jetty
+        - "    A wooden jetty. "
+        - You see the rowboat here.
+        "###);
+        assert_eq!(game.save_state.aboard, None);
+        assert_eq!(
+            game.save_state.vehicle_coords.get("rowboat"),
+            Some(&Coord { x: 2, y: 1, z: 0 })
+        );
+    }
+
+    #[test]
+    fn test_tiles() {
+        let level = r#"
+maps: [["--?---", "-.^%*-", "------"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+start: { stats: { health: 5 } }
+tiles:
+  "^": { damage: 2, message: Thorns scratch at your legs. }
+  "%": { when: items.lantern > 0 or flags.moonlit, blocked: It's too dark to go in without a light. }
+  "*": { passable: false }
+rooms:
+  - { title: Clearing, coord: [1, 1, 0], description: A sunny clearing., items: [{ id: lantern, quantity: 1 }] }
+  - { title: Thicket, coord: [2, 1, 0], description: A thorny thicket. }
+  - { title: Cave, coord: [3, 1, 0], description: A damp cave. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        // The unknown character is left as a wall, rather than failing to load.
+        let mut game = game_from_level(level, &mut command_runner);
+        let mut output = Vec::new();
+        for command in [
+            "north",
+            "east",
+            "east",
+            "west",
+            "take lantern",
+            "east",
+            "east",
+            "east",
+        ] {
+            game.step(command.into());
+            output.extend(
+                take_output(&game)
+                    .into_iter()
+                    .filter(|line| !line.trim().is_empty() && !line.starts_with("Exits")),
+            );
+        }
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - You cannot move north.
+        - Thorns scratch at your legs.
+        - Thicket
+        - "    A thorny thicket. "
+        - "It's too dark to go in without a light."
+        - Clearing
+        - "    A sunny clearing. "
+        - a lantern
+        - You place the lantern in your inventory.
+        - Thorns scratch at your legs.
+        - Thicket
+        - "    A thorny thicket. "
+        - Cave
+        - "    A damp cave. "
+        - You cannot move east.
+        "###);
+        assert_eq!(game.save_state.stats.get("health"), Some(&1));
+    }
+
+    #[test]
+    fn test_survival() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+start: { items: [{ id: apple, quantity: 1 }, { id: sword }], stats: { strength: 5 } }
+mechanics:
+  survival:
+    food:
+      max: 4
+      warnings:
+        - { below: 3, message: Your stomach growls. }
+        - { below: 2, message: You feel weak., penalty: { strength: -2 } }
+      death: You starve.
+rooms:
+  - { title: Camp, coord: [1, 1, 0], description: A cold camp. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        let stat = |game: &Game<_>, id| game.save_state.stats.get(id).copied();
+        assert_eq!(stat(&game, "food"), Some(4));
+        for command in ["smile", "smile", "eat sword", "eat apple", "inventory"] {
+            assert!(game.step(command.into()).is_none());
+        }
+        // Eating takes a turn too.
+        assert_eq!(stat(&game, "food"), Some(3));
+        assert_eq!(stat(&game, "strength"), Some(5));
+        for _ in 0..2 {
+            assert!(game.step("smile".into()).is_none());
+        }
+        assert_eq!(stat(&game, "strength"), Some(3));
+        assert!(game.step("smile".into()).is_some());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - You smile at no one in particular. It feels a little forced.
+        - You smile at no one in particular. It feels a little forced.
+        - Your stomach growls.
+        - ""
+        - "The sword isn't something you can eat or drink."
+        - You finish the apple.
+        - ╔═════════════════╗
+        - "║ Your inventory: ║"
+        - ╚═════════════════╝
+        - "  ‣ sword"
+        - ""
+        - You smile at no one in particular. It feels a little forced.
+        - Your stomach growls.
+        - ""
+        - You smile at no one in particular. It feels a little forced.
+        - You feel weak.
+        - ""
+        - You smile at no one in particular. It feels a little forced.
+        - You starve.
+        - ""
+        - ╔═══════════════╗
+        - ║ You have died ║
+        - ╚═══════════════╝
+        - "What now? (load, restart, quit)"
+        "###);
+    }
+
+    #[test]
+    fn test_statuses() {
+        let level = r#"
+maps: [["-----", "-...-", "-----"]]
+entry: [2, 1, 0]
+npcs: {}
+regions: {}
+start: { items: [{ id: ale, quantity: 1 }], stats: { health: 10, charm: 3 } }
+statuses:
+  drunk:
+    turns: 3
+    message: The room starts to spin.
+    expires: Your head clears.
+    stumble: 1
+    scramble: true
+    stats: { charm: -1 }
+  poison:
+    turns: 2
+    max_stacks: 3
+    tick: { health: -1 }
+    expires: The poison wears off.
+rooms:
+  - title: West Hall
+    coord: [1, 1, 0]
+    description: A drafty hall.
+  - title: Tavern
+    coord: [2, 1, 0]
+    description: A noisy tavern.
+    actions:
+      - { verb: Look, targets: [needle], value: Ouch!, effects: [{ status: poison }, { status: poison }] }
+  - title: East Hall
+    coord: [3, 1, 0]
+    description: A quiet hallway with portraits.
+"#;
+        let items_yaml = fs::read_to_string(ITEMS_PATH).unwrap()
+            + "- { id: ale, name: ale, variant: Consumable, description: Foamy., effects: [{ status: drunk }] }\n";
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = Game::new_from_strings(level, &items_yaml, &mut command_runner).unwrap();
+        let stat = |game: &Game<_>, id| game.save_state.stats.get(id).copied();
+        assert!(game.step("eat ale".into()).is_none());
+        assert_eq!(stat(&game, "charm"), Some(2));
+        for command in ["west", "look", "west", "look needle"] {
+            assert!(game.step(command.into()).is_none());
+        }
+        assert_eq!(stat(&game, "charm"), Some(3));
+        assert_eq!(game.save_state.status_effects["poison"].stacks, 2);
+        assert!(game.step("look".into()).is_none());
+        assert_eq!(stat(&game, "health"), Some(6));
+        assert!(game.save_state.status_effects.is_empty());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - You finish the ale.
+        - The room starts to spin.
+        - ""
+        - You stumble off to the east.
+        - ""
+        - East Hall
+        - ""
+        - "    A quiet halalwy with patrtiors. "
+        - ""
+        - ""
+        - "Exits: _ _ _ w"
+        - East Hall
+        - ""
+        - "    A quiet halawly with ptaorrtis. "
+        - ""
+        - ""
+        - "Exits: _ _ _ w"
+        - Your head clears.
+        - ""
+        - Tavern
+        - ""
+        - "    A noisy tavern. "
+        - ""
+        - ""
+        - "Exits: _ e _ w"
+        - Ouch!
+        - ""
+        - Tavern
+        - ""
+        - "    A noisy tavern. "
+        - ""
+        - ""
+        - "Exits: _ e _ w"
+        - The poison wears off.
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_hazards() {
+        let level = r#"
+maps: [["-----", "-...-", "--.--", "-----"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+start: { stats: { health: 10 } }
+rooms:
+  - title: Hall
+    coord: [1, 1, 0]
+    description: A hall.
+  - title: Cellar
+    coord: [2, 1, 0]
+    description: A damp cellar.
+    hazards:
+      - id: pit
+        targets: [pit, trapdoor]
+        message: The floor gives way!
+        effects: [{ stats: { health: -3 } }, { set_flag: knows-pit }, { teleport: { entry: bottom } }]
+        avoid: { when: flags.knows-pit, message: You edge around the trapdoor. }
+        disarm: { when: items.sword > 0, message: You wedge the sword into the trapdoor. }
+  - title: Vault
+    coord: [3, 1, 0]
+    description: A vault.
+    hazards:
+      - id: gas
+        targets: [gas]
+        message: Gas fills the room.
+        effects: [{ stats: { health: -1 } }]
+        avoid: { check: { stat: health, target: 40 } }
+        once: true
+  - title: Pit Bottom
+    id: bottom
+    coord: [2, 2, 0]
+    description: The bottom of a pit.
+    items: [{ id: sword, quantity: 1, name: ~, pickup: ~ }]
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        for command in ["east", "disarm pit", "take sword", "north", "disarm pit"] {
+            game.step(command.into());
+        }
+        assert!(game.save_state.disarmed.contains("pit"));
+        for command in ["west", "east", "east", "west", "east", "disarm gas"] {
+            game.step(command.into());
+        }
+        assert_eq!(game.save_state.stats.get("health"), Some(&6));
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Cellar
+        - ""
+        - "    A damp cellar. "
+        - ""
+        - ""
+        - "Exits: _ e s w"
+        - The floor gives way!
+        - ""
+        - Pit Bottom
+        - ""
+        - "    The bottom of a pit. "
+        - ""
+        - ""
+        - a sword
+        - ""
+        - "Exits: n _ _ _"
+        - "There's no pit to disarm here."
+        - You place the sword in your inventory.
+        - Cellar
+        - ""
+        - "    A damp cellar. "
+        - ""
+        - ""
+        - "Exits: _ e s w"
+        - You edge around the trapdoor.
+        - ""
+        - You wedge the sword into the trapdoor.
+        - ""
+        - Hall
+        - ""
+        - "    A hall. "
+        - ""
+        - ""
+        - "Exits: _ e _ _"
+        - Cellar
+        - ""
+        - "    A damp cellar. "
+        - ""
+        - ""
+        - "Exits: _ e s w"
+        - Vault
+        - ""
+        - "    A vault. "
+        - ""
+        - ""
+        - "Exits: _ _ _ w"
+        - Gas fills the room.
+        - ""
+        - Cellar
+        - ""
+        - "    A damp cellar. "
+        - ""
+        - ""
+        - "Exits: _ e s w"
+        - Vault
+        - ""
+        - "    A vault. "
+        - ""
+        - ""
+        - "Exits: _ _ _ w"
+        - "There's no gas to disarm here."
+        "###);
+    }
+
+    #[test]
+    fn test_puzzles() {
+        let level = r#"
+maps: [["-----", "-.-.-", "-----"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+rooms:
+  - title: Hall
+    coord: [1, 1, 0]
+    description: A hall with a dial, two levers, and a sphinx.
+    exits:
+      east: { entry: vault, when: puzzles.dial, blocked: The vault door is shut. }
+    actions:
+      - { verb: Custom, alias: pull, targets: [left], value: Clunk., effects: [{ step: left }] }
+      - { verb: Custom, alias: pull, targets: [right], value: Clank., effects: [{ step: right }] }
+    puzzles:
+      - id: dial
+        answers: [7-3-9]
+        solved: The dial clicks.
+        wrong: The dial spins back to zero.
+      - id: riddle
+        answers: [an echo]
+        solved: The sphinx nods, and drops an apple.
+        effects: [{ reveal: { apple: 1 } }]
+      - id: levers
+        sequence: [left, right, left]
+        solved: A gate grinds open.
+        wrong: The levers clunk back into place.
+  - { title: Vault, coord: [3, 1, 0], id: vault, description: An empty vault. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        for command in [
+            "east",
+            "say 1-2-3",
+            "say 7 3 9",
+            "answer Echo!",
+            "pull left",
+            "pull left",
+            "pull right",
+            "pull left",
+            "take apple",
+            "east",
+        ] {
+            assert!(game.step(command.into()).is_none());
+        }
+        let solved: Vec<_> = game.save_state.solved.iter().collect();
+        assert_eq!(solved, ["dial", "levers", "riddle"]);
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - The vault door is shut.
+        - "You say \"1-2-3\"."
+        - ""
+        - The dial spins back to zero.
+        - ""
+        - "You say \"7 3 9\"."
+        - ""
+        - The dial clicks.
+        - ""
+        - "You say \"Echo!\"."
+        - ""
+        - "The sphinx nods, and drops an apple."
+        - ""
+        - Clunk.
+        - Clunk.
+        - The levers clunk back into place.
+        - ""
+        - Clank.
+        - Clunk.
+        - A gate grinds open.
+        - ""
+        - You place the apple in your inventory.
+        - Vault
+        - ""
+        - "    An empty vault. "
+        - ""
+        - ""
+        - "Exits: _ _ _ _"
+        "###);
+    }
+
+    #[test]
+    fn test_conversation() {
+        let level = r#"
+maps: [["-----", "-..--", "-----"]]
+entry: [1, 1, 0]
+regions: {}
+npcs:
+  fishmonger:
+    name: Fishmonger
+    targets: [fishmonger]
+    talk: "\"Fresh fish!\""
+    items: []
+    topics:
+      - { targets: [eels, eel], value: "\"Caught this morning.\"", effects: [{ set_flag: asked }] }
+      - { targets: [guild], value: "\"Welcome, member.\"", when: flags.member }
+      - { targets: [tides], value: "\"Low at noon.\"" }
+rooms:
+  - { title: Market, coord: [1, 1, 0], description: A market., npcs: [fishmonger] }
+  - { title: Pier, coord: [2, 1, 0], description: A pier. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        for command in [
+            "talk fishmonger",
+            "eel",
+            "2",
+            "guild",
+            "bye",
+            "tides",
+            "ask fishmonger about tides",
+            "talk fishmonger",
+            "east",
+            "tides",
+        ] {
+            game.step(command.into());
+        }
+        assert!(game.save_state.flags.contains("asked"));
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - "\"Fresh fish!\""
+        - ""
+        - "You can ask about:"
+        - "  1. eels"
+        - "  2. tides"
+        - "Or type \"bye\" to end the conversation."
+        - ""
+        - "\"Caught this morning.\""
+        - ""
+        - "\"Low at noon.\""
+        - ""
+        - "Fishmonger doesn't know anything about guild."
+        - You say goodbye to Fishmonger.
+        - "You don't know how to \"tides\". Type \"help\" for help."
+        - "\"Low at noon.\""
+        - ""
+        - "\"Fresh fish!\""
+        - ""
+        - "You can ask about:"
+        - "  1. eels"
+        - "  2. tides"
+        - "Or type \"bye\" to end the conversation."
+        - ""
+        - Pier
+        - ""
+        - "    A pier. "
+        - ""
+        - ""
+        - "Exits: _ _ _ w"
+        - "You don't know how to \"tides\". Type \"help\" for help."
+        "###);
+    }
+
+    #[test]
+    fn test_item_set() {
+        let set = |kind: Option<&str>, except: &[&str]| ItemSet {
+            kind: kind.map(String::from),
+            except: except.iter().map(|word| word.to_string()).collect(),
+        };
+        assert_eq!(ItemSet::parse("all"), Some(set(None, &[])));
+        assert_eq!(ItemSet::parse("everything"), Some(set(None, &[])));
+        assert_eq!(ItemSet::parse("all fish"), Some(set(Some("fish"), &[])));
+        assert_eq!(
+            ItemSet::parse("all of the gems"),
+            Some(set(Some("gems"), &[]))
+        );
+        assert_eq!(
+            ItemSet::parse("all except sword, shield"),
+            Some(set(None, &["sword", "shield"]))
+        );
+        assert_eq!(
+            ItemSet::parse("all weapons except sword"),
+            Some(set(Some("weapons"), &["sword"]))
+        );
+        assert_eq!(ItemSet::parse("allspice"), None);
+        assert_eq!(ItemSet::parse("sword"), None);
+    }
+
+    #[test]
+    fn test_bulk_items() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+regions: {}
+npcs:
+  jeweler:
+    name: Jeweler
+    targets: [jeweler]
+    talk: "\"Gems?\""
+    items: []
+    gifts:
+      - { items: [ruby], value: "\"A fine ruby.\"", effects: [{ set_flag: sold-ruby }] }
+rooms:
+  - title: Shop
+    coord: [1, 1, 0]
+    description: A shop.
+    npcs: [jeweler]
+    items:
+      - { id: ruby, quantity: 1, name: ~, pickup: ~ }
+      - { id: emerald, quantity: 1, name: ~, pickup: ~ }
+      - { id: apple, quantity: 2, name: ~, pickup: ~ }
+      - { id: grilled-rat, quantity: 1, name: ~, pickup: ~ }
+"#;
+        let items_yaml = fs::read_to_string(ITEMS_PATH).unwrap()
+            + "
+- { id: ruby, name: ruby, targets: [ruby, gem], variant: Consumable, description: A ruby. }
+- { id: emerald, name: emerald, targets: [emerald, gem], variant: Consumable, description: An emerald. }
+";
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = Game::new_from_strings(level, &items_yaml, &mut command_runner).unwrap();
+        for command in [
+            "take all gems",
+            "take everything",
+            "take all",
+            "drop all consumables except apples, gems",
+            "give all to jeweler",
+            "give ruby",
+            "drop all except emerald",
+            "drop all except emerald",
+        ] {
+            game.step(command.into());
+        }
+        assert!(game.save_state.flags.contains("sold-ruby"));
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - You place the ruby in your inventory.
+        - You place the emerald in your inventory.
+        - You place the 2 apples in your inventory.
+        - You place the grilled rat in your inventory.
+        - "There's nothing like that to take."
+        - You dropped the grilled rat.
+        - "Jeweler doesn't want the sword."
+        - You give the ruby to Jeweler.
+        - "\"A fine ruby.\""
+        - ""
+        - "Jeweler doesn't want the emerald."
+        - "Jeweler doesn't want the 2 apples."
+        - "Generosity is nice, but it needs someone to receive it."
+        - You dropped the sword.
+        - You dropped the 2 apples.
+        - "You aren't carrying anything like that to drop."
+        "###);
+    }
+
+    #[test]
+    fn test_crafting() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+start: { items: [{ id: rope, quantity: 1 }, { id: stick, quantity: 1 }, { id: apple, quantity: 1 }] }
+rooms:
+  - title: Shore
+    coord: [1, 1, 0]
+    description: A shore.
+    items: [{ id: stick, quantity: 1, name: ~, pickup: ~ }]
+"#;
+        let items_yaml = fs::read_to_string(ITEMS_PATH).unwrap().replace(
+            "recipes: []",
+            "recipes: [{ ingredients: { rope: 1, stick: 2 }, output: fishing-rod, message: You tie it together. }, { ingredients: { rope: 1, apple: 1 }, output: cider }]",
+        ) + "
+- { id: rope, name: rope, variant: Consumable, description: A rope. }
+- { id: stick, name: stick, variant: Consumable, description: A stick. }
+- { id: fishing-rod, name: fishing rod, variant: Weapon, description: A fishing rod. }
+";
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = Game::new_from_strings(level, &items_yaml, &mut command_runner).unwrap();
+        for command in [
+            "combine rope",
+            "combine rope with unicorn",
+            "combine rope with apple",
+            "combine rope with stick",
+            "take stick",
+            "combine stick with rope",
+            "craft fishing rod",
+            "craft pie",
+        ] {
+            game.step(command.into());
+        }
+        let items: Vec<_> = game
+            .save_state
+            .inventory
+            .items
+            .iter()
+            .map(|item| (item.id.as_str(), item.quantity))
+            .collect();
+        assert_eq!(items, [("apple", 1), ("fishing-rod", 1)]);
+        // Items files from before recipes are just the list of items.
+        let item_db: ItemDatabase = serde_yaml::from_str(
+            "- { id: rope, name: rope, variant: Consumable, description: A rope. }",
+        )
+        .unwrap();
+        assert!(item_db.find("rope").is_some());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - It takes two to make a recipe.
+        - "You aren't carrying a unicorn."
+        - "The recipe makes \"cider\", which isn't an item."
+        - You need 1 more stick to make a fishing rod.
+        - You place the stick in your inventory.
+        - You tie it together.
+        - "You need 1 more rope, 2 more sticks to make a fishing rod."
+        - "You don't know how to make a pie."
+        "###);
+    }
+
+    #[test]
+    fn test_companions() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+regions: {}
+start: { items: [{ id: sword }, { id: apple, quantity: 2 }] }
+npcs:
+  dog:
+    name: Rex
+    targets: [dog, rex]
+    talk: Woof!
+    items: []
+    carry: 2
+rooms:
+  - { title: Yard, coord: [1, 1, 0], description: A yard., npcs: [dog] }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        for command in [
+            "give sword to dog",
+            "give apple to dog",
+            "inventory",
+            "ask dog for apple",
+            "ask rex for all",
+            "ask dog for sword",
+            "ask for sword",
+            "give apple to rex",
+        ] {
+            game.step(command.into());
+        }
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Rex carries the sword for you.
+        - "Rex can't carry the 2 apples as well."
+        - ╔═════════════════╗
+        - "║ Your inventory: ║"
+        - ╚═════════════════╝
+        - "  ‣ apple"
+        - ""
+        - "Rex is carrying: sword"
+        - ""
+        - "Rex isn't carrying a apple for you."
+        - Rex hands you the sword.
+        - "Rex isn't carrying anything for you."
+        - Who do you want to ask?
+        - Rex carries the 2 apples for you.
+        "###);
+        let carried = game.variables().get("companions.dog.apple");
+        assert_eq!(carried.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn test_lighting() {
+        let level = r#"
+maps: [["----", "-..-", "----"]]
+entry: [1, 1, 0]
+regions: {}
+npcs: {}
+rooms:
+  - title: Crypt
+    coord: [1, 1, 0]
+    description: A crypt.
+    lighting: dim
+    details: A name is scratched into the wall.
+    items:
+      - { id: apple, quantity: 1, name: ~, pickup: ~ }
+      - { id: sword, quantity: 1, name: ~, pickup: ~, hidden: true }
+  - title: Hall
+    coord: [2, 1, 0]
+    description: A hall.
+    lighting: bright
+    items: [{ id: lantern, quantity: 1, name: ~, pickup: ~ }]
+    actions:
+      - { verb: Custom, alias: hide, targets: [behind pillar], value: You slip behind a pillar., when: not light.bright }
+      - { verb: Custom, alias: hide, targets: [behind pillar], value: There's nowhere to hide in this glare. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        for command in [
+            "look",
+            "take sword",
+            "search",
+            "take all",
+            "east",
+            "hide behind pillar",
+            "take lantern",
+            "west",
+            "search",
+            "take sword",
+        ] {
+            game.step(command.into());
+        }
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Crypt
+        - ""
+        - "    A crypt. "
+        - ""
+        - "    It's too dim to make out much more. "
+        - ""
+        - ""
+        - an apple
+        - ""
+        - "Exits: _ e _ _"
+        - "You couldn't find a sword to take."
+        - "It's too dim to search here without a light."
+        - You place the apple in your inventory.
+        - Hall
+        - ""
+        - "    A hall. "
+        - ""
+        - ""
+        - a lantern
+        - ""
+        - "Exits: _ _ _ w"
+        - "There's nowhere to hide in this glare."
+        - You place the lantern in your inventory.
+        - Crypt
+        - ""
+        - "    A crypt. "
+        - ""
+        - "    A name is scratched into the wall. "
+        - ""
+        - ""
+        - ""
+        - "Exits: _ e _ _"
+        - You find the sword.
+        - You place the sword in your inventory.
+        "###);
+    }
+
+    #[test]
+    fn test_bank() {
+        let level = r#"
+maps: [["----", "-..-", "----"]]
+entry: [1, 1, 0]
+regions: {}
+start: { items: [{ id: sword }, { id: apple, quantity: 3 }, { id: gold, quantity: 5 }] }
+npcs:
+  clerk: { name: Clerk, targets: [clerk], talk: "\"Next.\"", items: [], bank: true }
+rooms:
+  - { title: Bank, coord: [1, 1, 0], description: A bank., npcs: [clerk] }
+  - { title: Street, coord: [2, 1, 0], description: A street. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        for command in [
+            "stash",
+            "deposit pear",
+            "deposit all except sword",
+            "stash",
+            "east",
+            "withdraw apple",
+            "west",
+            "withdraw sword",
+            "withdraw all",
+            "withdraw gold",
+        ] {
+            game.step(command.into());
+        }
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Your stash is empty.
+        - "You aren't carrying a pear."
+        - You deposit the 3 apples.
+        - You deposit 5 gold.
+        - "In your stash: apple, gold (5)."
+        - Street
+        - ""
+        - "    A street. "
+        - ""
+        - ""
+        - "Exits: _ _ _ w"
+        - "There's nowhere to keep your things here."
+        - Bank
+        - ""
+        - "    A bank. "
+        - ""
+        - ""
+        - "Exits: _ e _ _"
+        - "There's no sword in your stash."
+        - You withdraw the 3 apples.
+        - You withdraw 5 gold.
+        - Your stash is empty.
+        "###);
+        assert_eq!(game.save_state.inventory.count("gold"), 5);
+    }
+
+    #[test]
+    fn test_escort() {
+        let level = r#"
+maps: [["------", "-....-", "------"]]
+entry: [1, 1, 0]
+regions: {}
+npcs:
+  child:
+    name: Child
+    targets: [child]
+    talk: "\"I'm lost.\""
+    items: []
+    topics:
+      - { targets: [home], value: "\"Take me home?\"", effects: [{ quest: walk-home }, { quest: walk-dog }, { quest: mind-cat }] }
+  dog: { name: Dog, targets: [dog], talk: Woof., items: [] }
+  cat: { name: Cat, targets: [cat], talk: Meow., items: [] }
+quests:
+  walk-home:
+    name: Walk the Child Home
+    description: Bring the child home, and don't lose them.
+    reward: [{ say: "\"Thank you!\"" }]
+    escort: { npc: child, to: [3, 1, 0] }
+  walk-dog:
+    name: Walk the Dog
+    description: Keep the dog close.
+    escort: { npc: dog, behind: 1 }
+  mind-cat:
+    name: Mind the Cat
+    description: Keep the cat safe.
+    failed: [{ say: The cat stalks off. }]
+    escort: { npc: cat }
+rooms:
+  - title: Yard
+    coord: [1, 1, 0]
+    description: A yard with a cart.
+    npcs: [child, dog, cat]
+    actions:
+      - { verb: Custom, alias: ride, targets: [cart], value: The cart rattles east., teleport: { entry: market } }
+      - { verb: Custom, alias: kick, targets: [cat], value: You kick the cat., effects: [{ harm: cat }] }
+  - { title: Road, coord: [2, 1, 0], description: A road. }
+  - { title: Home, coord: [3, 1, 0], description: A house. }
+  - { title: Market, coord: [4, 1, 0], id: market, description: A market. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        for command in [
+            "ask child about home",
+            "kick cat",
+            "ride cart",
+            "smile",
+            "smile",
+            "smile",
+            "west",
+            "journal",
+        ] {
+            game.step(command.into());
+        }
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - "\"Take me home?\""
+        - ""
+        - "New quest: Walk the Child Home."
+        - ""
+        - "New quest: Walk the Dog."
+        - ""
+        - "New quest: Mind the Cat."
+        - ""
+        - You kick the cat.
+        - "Cat was hurt. Quest failed: Mind the Cat."
+        - ""
+        - The cat stalks off.
+        - ""
+        - The cart rattles east.
+        - Market
+        - ""
+        - "    A market. "
+        - ""
+        - ""
+        - "Exits: _ _ _ w"
+        - You smile at no one in particular. It feels a little forced.
+        - "Dog was left behind. Quest failed: Walk the Dog."
+        - ""
+        - You smile at no one in particular. It feels a little forced.
+        - Child follows you.
+        - ""
+        - You smile at no one in particular. It feels a little forced.
+        - Home
+        - ""
+        - "    A house. "
+        - ""
+        - ""
+        - Dog is here.
+        - ""
+        - "Exits: _ e _ w"
+        - Child follows you.
+        - ""
+        - "Quest complete: Walk the Child Home."
+        - ""
+        - "\"Thank you!\""
+        - ""
+        - ╔═══════════════╗
+        - "║ Your journal: ║"
+        - ╚═══════════════╝
+        - "  ‣ Mind the Cat (failed)"
+        - "  ‣ Walk the Dog (failed)"
+        - "  ‣ Walk the Child Home (done)"
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_scenery() {
+        let level = r#"
+maps: [["-----", "-.-.-", "-----"]]
+entry: [1, 1, 0]
+regions: {}
+npcs: {}
+rooms:
+  - title: Cellar
+    coord: [1, 1, 0]
+    description: A cellar with a boulder, a bookcase, and a lever.
+    items: [{ id: sword, quantity: 1, name: ~, pickup: ~ }]
+    actions:
+      - { verb: Custom, alias: pull, targets: [lever], value: Clunk. }
+    exits:
+      east: { entry: vault, hidden: true }
+    scenery:
+      - id: boulder
+        verbs: [push]
+        value: You heave the boulder aside.
+        moved: The boulder sits off to one side.
+        effects: [{ reveal: { apple: 1 } }]
+      - id: bookcase
+        targets: [shelves]
+        value: The bookcase swings out from the wall.
+        moved: The bookcase stands open.
+        exits: [east]
+        when: items.sword > 0
+        stuck: The bookcase is stuck fast.
+  - { title: Vault, coord: [3, 1, 0], id: vault, description: A vault. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        for command in [
+            "push",
+            "pull lever",
+            "pull boulder",
+            "push boulder",
+            "push boulder",
+            "search",
+            "pull bookcase",
+            "take sword",
+            "move shelves",
+            "look",
+            "east",
+        ] {
+            game.step(command.into());
+        }
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+            ---
+            - "You shove the air around. It doesn't mind."
+            - Clunk.
+            - "You can't pull the boulder."
+            - You heave the boulder aside.
+            - "You've already moved the boulder."
+            - "You search around, but don't find anything."
+            - The bookcase is stuck fast.
+            - You place the sword in your inventory.
+            - The bookcase swings out from the wall.
+            - Cellar
+            - ""
+            - "    A cellar with a boulder, a bookcase, and a lever. The boulder sits off to one side. "
+            - "    The bookcase stands open. "
+            - ""
+            - ""
+            - an apple
+            - ""
+            - "Exits: _ e _ _"
+            - Vault
+            - ""
+            - "    A vault. "
+            - ""
+            - ""
+            - "Exits: _ _ _ _"
+            "###);
+    }
+
+    #[test]
+    fn test_race() {
+        let level = r#"
+maps: [["-----", "-...-", "-----"]]
+entry: [1, 1, 0]
+regions: {}
+npcs: {}
+race:
+  - { name: Path, at: path }
+  - { name: Sword, when: items.sword > 0 }
+  - { name: Gate, at: gate }
+rooms:
+  - { title: Camp, coord: [1, 1, 0], description: A camp. }
+  - title: Path
+    coord: [2, 1, 0]
+    id: path
+    description: A path.
+    items: [{ id: sword, quantity: 1, name: ~, pickup: ~ }]
+  - { title: Gate, coord: [3, 1, 0], id: gate, description: A gate. }
+"#;
+        let path = std::env::temp_dir().join("text-adventure-test-races.yml");
+        // The splits are compared against a slower best run.
+        fs::write(&path, "\"\": [5, 10, 20]").unwrap();
+        let mut command_runner = CommandRunner::new(vec![]);
+        command_runner.race = true;
+        command_runner.races_path = Some(path.clone());
+        let mut game = game_from_level(level, &mut command_runner);
+        game.start();
+        take_output(&game);
+        let mut output = Vec::new();
+        for command in ["east", "take sword", "west", "east", "east"] {
+            game.step(command.into());
+            output.extend(take_output(&game));
+        }
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - Path
+        - ""
+        - "    A path. "
+        - ""
+        - ""
+        - a sword
+        - ""
+        - "Exits: _ e _ w"
+        - "Path: 0:00 (-0:05)"
+        - ""
+        - You place the sword in your inventory.
+        - "Sword: 0:00 (-0:10)"
+        - ""
+        - Camp
+        - ""
+        - "    A camp. "
+        - ""
+        - ""
+        - "Exits: _ e _ _"
+        - Path
+        - ""
+        - "    A path. "
+        - ""
+        - ""
+        - ""
+        - "Exits: _ e _ w"
+        - Gate
+        - ""
+        - "    A gate. "
+        - ""
+        - ""
+        - "Exits: _ _ _ w"
+        - "Gate: 0:00 (-0:20)"
+        - ""
+        - ╔═══════════════════════════════╗
+        - "║ Finished in 0:00, a new best! ║"
+        - ╚═══════════════════════════════╝
+        "###);
+        let races: BTreeMap<String, Vec<u64>> = parse_yml(&path);
+        assert_eq!(races[""], vec![0, 0, 0]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_oops() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+regions: {}
+npcs: {}
+rooms:
+  - title: Shed
+    coord: [1, 1, 0]
+    description: A shed.
+    items: [{ id: lantern, quantity: 1, name: ~, pickup: ~ }]
+    actions: [{ verb: Look, targets: [shelf], value: A dusty shelf. }]
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        let mut output = Vec::new();
+        for command in [
+            "oops",
+            "oops lantern",
+            "look at shlef",
+            "oops shelf",
+            "take lantren",
+            "oops lantern",
+            "oops lantern",
+            "drop lantren",
+            "north",
+            "oops lantern",
+        ] {
+            game.step(command.into());
+            output.extend(take_output(&game));
+        }
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - Oops indeed. But which word did you mean?
+        - "There's nothing to correct."
+        - "You don't see a shlef."
+        - "Did you mean 'shelf'?"
+        - ""
+        - A dusty shelf.
+        - ""
+        - "You couldn't find a lantren to take."
+        - "Did you mean 'lantern'?"
+        - You place the lantern in your inventory.
+        - "There's nothing to correct."
+        - It does not look like you have a lantren.
+        - "Did you mean 'lantern'?"
+        - You cannot move north.
+        - "There's nothing to correct."
+        "###);
+    }
+
+    #[test]
+    fn test_travel() {
+        let level = r#"
+maps: [["------", "-....-", "------"]]
+entry: [1, 1, 0]
+regions: {}
+npcs: {}
+daemons:
+  bell:
+    every: 5
+    times: 1
+    effects: [{ say: A bell rings out over the town. }]
+rooms:
+  - { title: Yard, coord: [1, 1, 0], description: A yard. }
+  - { title: Lane, coord: [2, 1, 0], description: A lane. }
+  - { title: Market Square, coord: [3, 1, 0], description: A square. }
+  - { title: Gate, coord: [4, 1, 0], description: A gate. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        let mut output = Vec::new();
+        for command in [
+            "go to market square",
+            "go to yard",
+            "east",
+            "east",
+            "east",
+            // The bell rings on the second step, which stops the walk.
+            "go to yard",
+            "go to yard",
+            "go to gate",
+            "go to castle",
+        ] {
+            game.step(command.into());
+            output.extend(
+                take_output(&game)
+                    .into_iter()
+                    // Only the room titles are kept from the descriptions.
+                    .filter(|line| !line.trim().is_empty() && !line.starts_with([' ', 'E'])),
+            );
+        }
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - "You've never heard of market square, let alone been there."
+        - "You're already here."
+        - Lane
+        - Market Square
+        - Gate
+        - Market Square
+        - Lane
+        - A bell rings out over the town.
+        - You stop on the way.
+        - Yard
+        - Lane
+        - Market Square
+        - Gate
+        - "You've never heard of castle, let alone been there."
+        "###);
+    }
+
+    #[test]
+    fn test_where() {
+        let level = r#"
+maps: [["----", "-..-", "----", "--.-", "----"]]
+entry: [1, 1, 0]
+regions: { town: { actions: [] } }
+npcs: {}
+rooms:
+  - { title: Yard, coord: [1, 1, 0], description: A yard. }
+  - title: Lane
+    coord: [2, 1, 0]
+    description: A lane.
+    regions: [town]
+    exits: { south: { entry: cellar }, north: { level: sewer, entry: drain } }
+  - { title: Cellar, id: cellar, coord: [2, 3, 0], description: A cellar. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        let mut output = Vec::new();
+        for command in ["exits", "east", "where am i", "debug", "where"] {
+            game.step(command.into());
+            output.extend(take_output(&game));
+        }
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - "Exits: _ e _ _"
+        - "  east   somewhere you haven't been"
+        - Lane
+        - ""
+        - "    A lane. "
+        - ""
+        - ""
+        - "Exits: n _ s w"
+        - "Room: Lane"
+        - "Regions: town"
+        - ""
+        - "Exits: n _ s w"
+        - "  north  somewhere you haven't been"
+        - "  south  somewhere you haven't been"
+        - "  west   Yard"
+        - Debug mode activated.
+        - "Room: Lane"
+        - "Coord: [2, 1, 0]"
+        - "Regions: town"
+        - ""
+        - "Exits: n _ s w"
+        - "  north  somewhere you haven't been"
+        - "  south  somewhere you haven't been"
+        - "  west   Yard"
+        "###);
+    }
+
+    #[test]
+    fn test_regions() {
+        let level = r#"
+maps: [["------", "-....-", "------"]]
+entry: [1, 1, 0]
+regions:
+  docks:
+    name: Dockside district
+    description: The smell of tar hangs over everything.
+  alley:
+    banner: It gets quiet.
+npcs: {}
+rooms:
+  - { title: Yard, coord: [1, 1, 0], description: A yard. }
+  - { title: Stall, coord: [2, 1, 0], description: A stall., regions: [docks] }
+  - { title: Quay, coord: [3, 1, 0], description: A quay., regions: [docks] }
+  - { title: Alley, coord: [4, 1, 0], description: An alley., regions: [alley, docks] }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        let mut output = Vec::new();
+        for command in [
+            "east", "look", "east", "east", "where", "west", "west", "west", "east",
+        ] {
+            game.step(command.into());
+            output.extend(
+                take_output(&game)
+                    .into_iter()
+                    .filter(|line| !line.trim().is_empty() && !line.starts_with("Exits")),
+            );
+        }
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - Stall
+        - "    A stall. "
+        - You enter the Dockside district.
+        - Stall
+        - "    A stall. "
+        - "    The smell of tar hangs over everything. "
+        - Quay
+        - "    A quay. "
+        - Alley
+        - "    An alley. "
+        - It gets quiet.
+        - "Room: Alley"
+        - "Regions: alley, Dockside district"
+        - "  west   Quay"
+        - Quay
+        - "    A quay. "
+        - Stall
+        - "    A stall. "
+        - Yard
+        - "    A yard. "
+        - Stall
+        - "    A stall. "
+        - You enter the Dockside district.
+        "###);
+    }
+
+    #[test]
+    fn test_weather() {
+        let level = r#"
+maps: [["-----", "-...-", "-----"]]
+entry: [1, 1, 0]
+climate:
+  every: 1
+  changes: { clear: [fog], fog: [storm], storm: [clear] }
+  messages: { fog: Fog rolls in., storm: A storm breaks. }
+  hides_exits: [fog]
+  blocks_travel: [storm]
+regions:
+  moor:
+    weather: { fog: The moor is lost in grey. }
+npcs: {}
+rooms:
+  - { title: Hall, coord: [1, 1, 0], description: A hall. }
+  - title: Porch
+    coord: [2, 1, 0]
+    description: A porch.
+    regions: [moor]
+    outdoors: true
+    weather: { fog: Mist beads on the railing. }
+  - { title: Field, coord: [3, 1, 0], description: A field., outdoors: true }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        let mut output = Vec::new();
+        // The weather changes each turn, but it's only told to a player who's outdoors.
+        for command in ["east", "exits", "look", "west", "look", "look", "east"] {
+            game.step(command.into());
+            output.extend(
+                take_output(&game)
+                    .into_iter()
+                    .filter(|line| !line.trim().is_empty()),
+            );
+        }
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - Porch
+        - "    A porch. "
+        - "Exits: _ e _ w"
+        - Fog rolls in.
+        - The fog hides the way on.
+        - Porch
+        - "    A porch. "
+        - "    Mist beads on the railing. "
+        - "    The moor is lost in grey. "
+        - The fog hides the way on.
+        - A storm breaks.
+        - Hall
+        - "    A hall. "
+        - "Exits: _ e _ _"
+        - Hall
+        - "    A hall. "
+        - "Exits: _ e _ _"
+        - Hall
+        - "    A hall. "
+        - "Exits: _ e _ _"
+        - "You can't head out into the storm."
+        "###);
+    }
+
+    #[test]
+    fn test_repeat() {
+        let level = r#"
+maps: [["-----", "-...-", "-----"]]
+entry: [1, 1, 0]
+regions: {}
+npcs: {}
+rooms:
+  - title: Orchard
+    coord: [1, 1, 0]
+    description: An orchard.
+    items:
+      - { id: apple, quantity: 1, name: ~, pickup: ~ }
+      - { id: apple, quantity: 1, name: ~, pickup: ~ }
+      - { id: apple, quantity: 1, name: ~, pickup: ~ }
+  - { title: Path, coord: [2, 1, 0], description: A path. }
+  - { title: Gate, coord: [3, 1, 0], description: A gate. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        let mut output = Vec::new();
+        for command in [
+            "again",
+            "take apple x2",
+            "g",
+            "3 east",
+            "2 restart",
+            "debug",
+            "again",
+            "50 west",
+        ] {
+            game.step(command.into());
+            output.extend(take_output(&game));
+        }
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - "Again? You haven't done anything the first time."
+        - You place the apple in your inventory.
+        - You place the apple in your inventory.
+        - You place the apple in your inventory.
+        - "You couldn't find a apple to take."
+        - Path
+        - ""
+        - "    A path. "
+        - ""
+        - ""
+        - "Exits: _ e _ w"
+        - Gate
+        - ""
+        - "    A gate. "
+        - ""
+        - ""
+        - "Exits: _ _ _ w"
+        - You cannot move east.
+        - "You can't repeat \"restart\"."
+        - Debug mode activated.
+        - Once was enough.
+        - You can only repeat a command up to 20 times.
+        "###);
+        let apples = game
+            .save_state
+            .inventory
+            .items
+            .iter()
+            .find(|item| item.id == "apple");
+        assert_eq!(apples.map(|apples| apples.quantity), Some(3));
+    }
+
+    #[test]
+    fn test_irreversible() {
+        let level = r#"
+maps: [["-----", "-.-.-", "-----"]]
+entry: [1, 1, 0]
+regions: {}
+npcs: {}
+rooms:
+  - title: Ledge
+    coord: [1, 1, 0]
+    description: A ledge over a pit, by a sealed door.
+    exits:
+      east: { entry: pit, irreversible: "You won't be able to climb back out. Jump?" }
+    actions:
+      - verb: Custom
+        alias: break
+        targets: [seal]
+        value: The seal crumbles, and the door grinds open.
+        irreversible: The seal can't be mended. Break it?
+  - { title: Pit, coord: [3, 1, 0], id: pit, description: A pit. }
+"#;
+        let mut command_runner = CommandRunner::new(vec!["no", "maybe", "yes"]);
+        let mut game = game_from_level(level, &mut command_runner);
+        // The runner clears the output at each prompt, so the questions aren't seen,
+        // only what happens after they're answered.
+        let mut output = Vec::new();
+        for command in ["east", "break seal", "config confirm off", "east"] {
+            game.step(command.into());
+            output.extend(take_output(&game));
+        }
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - You think better of it.
+        - "The seal crumbles, and the door grinds open."
+        - Changed the confirm setting.
+        - Pit
+        - ""
+        - "    A pit. "
+        - ""
+        - ""
+        - "Exits: _ _ _ _"
+        "###);
+    }
+
+    #[test]
+    fn test_throwing() {
+        let level = r#"
+maps: [["-----", "-.-.-", "-----"]]
+entry: [1, 1, 0]
+regions: {}
+start: { items: [{ id: apple, quantity: 3 }, { id: lantern }, { id: sword }] }
+factions:
+  watch: { name: the Watch }
+npcs:
+  guard:
+    name: Guard
+    targets: [guard]
+    talk: "\"Move along.\""
+    items: []
+    faction: watch
+    throws: [{ items: [apple], value: "\"Hey!\" The guard glares at you.", effects: [{ reputation: { watch: -2 } }] }]
+rooms:
+  - title: Cliff
+    coord: [1, 1, 0]
+    description: A cliff over a chasm, with a vase on a pedestal.
+    npcs: [guard]
+    throws:
+      - { targets: [chasm], items: [lantern], value: The lantern sails across to the ledge., land: ledge }
+      - { targets: [chasm], value: It falls into the darkness., break: true }
+      - { targets: [vase], items: [apple], value: The apple splatters against the vase., break: true }
+      - { targets: [vase], value: The vase wobbles. }
+  - { title: Ledge, coord: [3, 1, 0], id: ledge, description: A ledge. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        for command in [
+            "throw",
+            "throw apple",
+            "throw pear at vase",
+            "throw apple at moon",
+            "throw apple at guard",
+            "throw apple at vase",
+            "throw lantern at chasm",
+            "throw sword at vase",
+            "look",
+            "inventory",
+        ] {
+            game.step(command.into());
+        }
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+            ---
+            - "You wind up, and throw nothing at all."
+            - "You could throw it, but you'd rather hit something."
+            - It does not look like you have a pear.
+            - "There's no moon here to throw the apple at."
+            - "\"Hey!\" The guard glares at you."
+            - Your standing with the Watch falls.
+            - ""
+            - The apple splatters against the vase.
+            - The lantern sails across to the ledge.
+            - The vase wobbles.
+            - Cliff
+            - ""
+            - "    A cliff over a chasm, with a vase on a pedestal. "
+            - ""
+            - ""
+            - an apple
+            - a sword
+            - "Exits: _ _ _ _"
+            - ╔═════════════════╗
+            - "║ Your inventory: ║"
+            - ╚═════════════════╝
+            - "  ‣ apple"
+            - ""
+            "###);
+        let reputation = game.variables().get("reputation.watch");
+        assert_eq!(reputation.as_deref(), Some("-2"));
+        let ledge = game.save_state.room_inventories[&Coord { x: 3, y: 1, z: 0 }]
+            .visible_iter()
+            .map(|(_, item)| item.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ledge, vec!["lantern"]);
+    }
+
+    #[test]
+    fn test_search() {
+        let level = r#"
+maps: [["-----", "-.-.-", "-----"]]
+entry: [1, 1, 0]
+regions: {}
+npcs: {}
+rooms:
+  - title: Study
+    coord: [1, 1, 0]
+    description: A study with a desk and a bookcase.
+    items:
+      - { id: apple, quantity: 1, name: ~, pickup: ~, hidden: true }
+      - { id: sword, quantity: 1, name: ~, pickup: ~, hidden: true, found_by: desk }
+    exits:
+      east: { entry: vault, hidden: true, found_by: bookcase }
+  - { title: Vault, coord: [3, 1, 0], id: vault, description: A vault. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        for command in [
+            "look",
+            "east",
+            "take apple",
+            "search",
+            "search",
+            "search desk",
+            "take all",
+            "search bookcase",
+            "east",
+        ] {
+            game.step(command.into());
+        }
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Study
+        - ""
+        - "    A study with a desk and a bookcase. "
+        - ""
+        - ""
+        - ""
+        - "Exits: _ _ _ _"
+        - You cannot move east.
+        - "You couldn't find a apple to take."
+        - You find the apple.
+        - "You don't find anything, but the desk might be worth a closer look."
+        - You find the sword.
+        - You place the apple in your inventory.
+        - You place the sword in your inventory.
+        - You find a hidden way east.
+        - Vault
+        - ""
+        - "    A vault. "
+        - ""
+        - ""
+        - "Exits: _ _ _ _"
+        "###);
+    }
+
+    #[test]
+    fn test_quests() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+regions: {}
+start: { items: [{ id: apple, quantity: 1 }] }
+npcs:
+  innkeeper:
+    name: Innkeeper
+    targets: [innkeeper]
+    talk: "\"Welcome.\""
+    items: []
+    topics:
+      - { targets: [work], value: "\"Two jobs, quickly.\"", effects: [{ quest: apples }, { quest: sweep }] }
+    gifts: [{ items: [apple], value: "\"At last.\"", effects: [{ complete: apples }] }]
+quests:
+  apples:
+    name: An Apple for the Pie
+    description: Bring the innkeeper an apple.
+    turns: 2
+    grace: 1
+    reward: [{ say: A full reward. }]
+    late: [{ say: A smaller reward. }]
+  sweep:
+    name: Sweep the Floor
+    description: Sweep before the guests arrive.
+    turns: 1
+    failed: [{ say: The guests track in mud. }]
+rooms:
+  - { title: Inn, coord: [1, 1, 0], description: An inn., npcs: [innkeeper] }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        for command in [
+            "journal",
+            "ask innkeeper about work",
+            "journal",
+            "smile",
+            "smile",
+            "journal",
+            "give apple to innkeeper",
+            "journal",
+        ] {
+            game.step(command.into());
+        }
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - ╔═══════════════╗
+        - "║ Your journal: ║"
+        - ╚═══════════════╝
+        - "    (empty)"
+        - ""
+        - "\"Two jobs, quickly.\""
+        - ""
+        - "New quest: An Apple for the Pie."
+        - ""
+        - "New quest: Sweep the Floor."
+        - ""
+        - ╔═══════════════╗
+        - "║ Your journal: ║"
+        - ╚═══════════════╝
+        - "  ‣ An Apple for the Pie (2 turns left)"
+        - "    Bring the innkeeper an apple."
+        - "  ‣ Sweep the Floor (1 turn left)"
+        - "    Sweep before the guests arrive."
+        - ""
+        - You smile at no one in particular. It feels a little forced.
+        - "Quest failed: Sweep the Floor."
+        - ""
+        - The guests track in mud.
+        - ""
+        - You smile at no one in particular. It feels a little forced.
+        - "You're out of time for An Apple for the Pie, but it may not be too late."
+        - ""
+        - ╔═══════════════╗
+        - "║ Your journal: ║"
+        - ╚═══════════════╝
+        - "  ‣ An Apple for the Pie (overdue)"
+        - "    Bring the innkeeper an apple."
+        - "  ‣ Sweep the Floor (failed)"
+        - ""
+        - You give the apple to Innkeeper.
+        - "\"At last.\""
+        - ""
+        - "Quest complete, though late: An Apple for the Pie."
+        - ""
+        - A smaller reward.
+        - ""
+        - ╔═══════════════╗
+        - "║ Your journal: ║"
+        - ╚═══════════════╝
+        - "  ‣ An Apple for the Pie (done late)"
+        - "  ‣ Sweep the Floor (failed)"
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_lore() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+regions: {}
+start: { items: [{ id: almanac }, { id: sword }] }
+npcs:
+  sage:
+    name: Sage
+    targets: [sage]
+    talk: "\"Ask me anything.\""
+    items: []
+    topics: [{ targets: [king], value: "\"He drowned.\"", effects: [{ lore: drowned-king }] }]
+lore:
+  drowned-king: { title: The Drowned King, targets: [king], text: The last king was lost at sea. }
+  tides: { title: The Tides, text: The tide turns twice a day. }
+rooms:
+  - title: Library
+    coord: [1, 1, 0]
+    description: A library.
+    npcs: [sage]
+    actions:
+      - { verb: Custom, alias: read, targets: [plaque], value: It names the king., effects: [{ lore: drowned-king }] }
+"#;
+        let items_yaml = fs::read_to_string(ITEMS_PATH).unwrap()
+            + "- { id: almanac, name: almanac, variant: Consumable, description: A book., lore: tides }\n";
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = Game::new_from_strings(level, &items_yaml, &mut command_runner).unwrap();
+        for command in [
+            "lore",
+            "lore king",
+            "ask sage about king",
+            "read plaque",
+            "read almanac",
+            "read sword",
+            "lore",
+            "lore the drowned king",
+        ] {
+            game.step(command.into());
+        }
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - "You haven't learned any lore yet."
+        - "You don't know anything about king."
+        - "\"He drowned.\""
+        - ""
+        - "You learned about The Drowned King. Look it up any time with \"lore\"."
+        - ""
+        - It names the king.
+        - The Tides
+        - ""
+        - The tide turns twice a day.
+        - ""
+        - "You learned about The Tides. Look it up any time with \"lore\"."
+        - ""
+        - "There's nothing to read on the sword."
+        - "You know about: The Drowned King, The Tides."
+        - The Drowned King
+        - ""
+        - The last king was lost at sea.
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_buying() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+regions: {}
+start: { items: [{ id: gold, quantity: 2 }] }
+npcs:
+  farmer:
+    name: Farmer
+    targets: [farmer]
+    talk: "\"Apples!\""
+    items: [{ id: apple, price: 130 }]
+rooms:
+  - { title: Orchard, coord: [1, 1, 0], description: An orchard., npcs: [farmer] }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        for command in [
+            "look farmer",
+            "money",
+            "buy apple",
+            "buy apple from farmer",
+            "buy pear",
+            "inventory",
+        ] {
+            game.step(command.into());
+        }
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - ""
+        - ""
+        - "  ‣ apple (1 gold, 3 silver)"
+        - ""
+        - You have 2 gold.
+        - "You buy the apple from Farmer for 1 gold, 3 silver."
+        - "You hand over 2 gold, and get 7 silver back."
+        - "The apple costs 1 gold, 3 silver, but you only have 7 silver."
+        - Nobody here is selling a pear.
+        - ╔═════════════════╗
+        - "║ Your inventory: ║"
+        - ╚═════════════════╝
+        - "  ‣ 7 silver"
+        - "  ‣ apple"
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_haggling() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+regions: {}
+start: { items: [{ id: gold, quantity: 2 }] }
+npcs:
+  farmer: { name: Farmer, targets: [farmer], talk: Apples!, items: [{ id: apple, price: 100 }] }
+  tinker:
+    name: Tinker
+    targets: [tinker]
+    talk: Lanterns!
+    items: [{ id: lantern, price: 100 }]
+    stubbornness: 100
+rooms:
+  - { title: Orchard, coord: [1, 1, 0], description: An orchard., npcs: [farmer, tinker] }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        game.output().output.clear();
+        for command in [
+            "offer",
+            "offer lots for apple",
+            "offer 5 silver for apple",
+            "offer 8 silver for apple",
+            "offer 1 silver for lantern",
+            "buy lantern",
+        ] {
+            game.step(command.into());
+        }
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - "Haggling goes better with a price and an item, like: offer 5 for apple"
+        - "Nobody takes \"lots\" as payment."
+        - "Farmer shakes their head, and asks 8 silver, 7 copper for the apple."
+        - Farmer accepts your offer.
+        - You buy the apple from Farmer for 8 silver.
+        - "You hand over 1 gold, and get 2 silver back."
+        - "Tinker is insulted by your offer, and won't trade with you for a while."
+        - "Tinker won't trade with you for now."
+        "###);
+    }
+
+    #[test]
+    fn test_party() {
+        let level = r#"
+maps: [["----", "-..-", "----"]]
+entry: [1, 1, 0]
+regions: {}
+npcs:
+  mira:
+    name: Mira
+    targets: [mira]
+    talk: "\"Need a guide?\""
+    items: []
+    topics:
+      - { targets: [guide], value: "\"Sure.\"", effects: [{ join: mira }] }
+      - { targets: [home], value: "\"Suit yourself.\"", effects: [{ leave: mira }] }
+    companion:
+      joins: "\"Lead the way,\" says Mira."
+      comments: { market: "\"Mind the wires in here,\" Mira mutters." }
+      assists: { dexterity: 20 }
+rooms:
+  - { title: Docks, coord: [1, 1, 0], description: The docks., npcs: [mira] }
+  - title: Market
+    id: market
+    coord: [2, 1, 0]
+    description: A market.
+    hazards:
+      - id: wire
+        targets: [wire]
+        message: A wire snags your ankle.
+        disarm: { check: { stat: dexterity, target: 21 }, message: You cut the wire. }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        game.output().output.clear();
+        for command in [
+            "ask mira about guide",
+            "east",
+            "disarm wire",
+            "west",
+            "east",
+        ] {
+            game.step(command.into());
+        }
+        assert!(game.save_state.party.contains("mira"));
+        assert!(game.save_state.disarmed.contains("wire"));
+        for command in ["ask mira about home", "west"] {
+            game.step(command.into());
+        }
+        assert!(game.save_state.party.is_empty());
+        assert_eq!(game.npc_coord("mira"), Some(Coord { x: 2, y: 1, z: 0 }));
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - "\"Sure.\""
+        - ""
+        - "\"Lead the way,\" says Mira."
+        - ""
+        - Market
+        - ""
+        - "    A market. "
+        - ""
+        - ""
+        - "Exits: _ _ _ w"
+        - A wire snags your ankle.
+        - ""
+        - Mira follows you.
+        - ""
+        - "\"Mind the wires in here,\" Mira mutters."
+        - ""
+        - You cut the wire.
+        - ""
+        - Docks
+        - ""
+        - "    The docks. "
+        - ""
+        - ""
+        - "Exits: _ e _ _"
+        - Mira follows you.
+        - ""
+        - Market
+        - ""
+        - "    A market. "
+        - ""
+        - ""
+        - "Exits: _ _ _ w"
+        - Mira follows you.
+        - ""
+        - "\"Suit yourself.\""
+        - ""
+        - Mira leaves your party.
+        - ""
+        - Docks
+        - ""
+        - "    The docks. "
+        - ""
+        - ""
+        - "Exits: _ e _ _"
+        "###);
+    }
+
+    #[test]
+    fn test_pets() {
+        let level = r#"
+maps: [["------", "-...^-", "------"]]
+entry: [1, 1, 0]
+regions: {}
+start: { items: [{ id: apple, quantity: 2 }] }
+tiles:
+  "^": { when: flags.vault-open }
+npcs:
+  dog:
+    name: Rex
+    targets: [dog, rex]
+    talk: Woof.
+    items: []
+    pet: { eats: [apple], wild: Rex growls. }
+rooms:
+  - { title: Den, coord: [1, 1, 0], description: A den., npcs: [dog] }
+  - { title: Hall, coord: [2, 1, 0], description: A hall. }
+  - { title: Yard, coord: [3, 1, 0], description: A yard., items: [{ id: lantern, quantity: 2 }] }
+  - { title: Vault, coord: [4, 1, 0], description: A vault., items: [{ id: sword, quantity: 1 }] }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        game.output().output.clear();
+        for command in [
+            "pet dog",
+            "send dog to fetch lantern",
+            "feed apple to dog",
+            "pet rex",
+            "send",
+            "send dog to fetch unicorn",
+            // The vault can't be entered yet, by the player or the dog.
+            "send dog to fetch sword",
+            "east",
+            "send dog to fetch lantern",
+            "west",
+            "east",
+        ] {
+            game.step(command.into());
+        }
+        assert!(game.save_state.fetches.is_empty());
+        assert_eq!(game.npc_coord("dog"), Some(Coord { x: 2, y: 1, z: 0 }));
+        let hall = &game.save_state.room_inventories[&Coord { x: 2, y: 1, z: 0 }];
+        assert_eq!(hall.inventory[0].1.id, "lantern");
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Rex growls.
+        - "Rex doesn't listen to you yet."
+        - "Rex eats the apple, and decides to follow you."
+        - Rex leans into your hand.
+        - "Good help is hard to find. Try: send dog to fetch stick"
+        - "Rex sniffs around, but can't find any unicorn."
+        - "Rex sniffs around, but can't find any sword."
+        - Hall
+        - ""
+        - "    A hall. "
+        - ""
+        - ""
+        - "Exits: _ e _ w"
+        - Rex follows you.
+        - ""
+        - Rex bounds off to the east.
+        - Den
+        - ""
+        - "    A den. "
+        - ""
+        - ""
+        - "Exits: _ e _ _"
+        - Hall
+        - ""
+        - "    A hall. "
+        - ""
+        - ""
+        - "Exits: _ e _ w"
+        - "Rex comes back, and drops the lantern at your feet."
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_region_prices() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+regions:
+  market:
+    actions: []
+    prices: [{ when: flags.saved-the-market, percent: 90 }]
+start: { items: [{ id: gold, quantity: 2 }] }
+npcs:
+  farmer:
+    name: Farmer
+    targets: [farmer]
+    talk: "\"Apples!\""
+    items: [{ id: apple, price: 100 }]
+    topics:
+      - { targets: [thieves], value: "\"They're everywhere.\"", effects: [{ quest: thieves }] }
+quests:
+  thieves:
+    name: Thieves in the Market
+    description: Chase the thieves out of the market.
+    reward: [{ set_flag: saved-the-market }, { say: The merchants cheer. }]
+rooms:
+  - title: Market
+    coord: [1, 1, 0]
+    description: A market.
+    regions: [market]
+    npcs: [farmer]
+    actions:
+      - { verb: Custom, alias: chase, targets: [thieves], value: The thieves flee., effects: [{ complete: thieves }] }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        for command in [
+            "look farmer",
+            "ask farmer about thieves",
+            "chase thieves",
+            "look farmer",
+            "buy apple",
+        ] {
+            game.step(command.into());
+        }
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - ""
+        - ""
+        - "  ‣ apple (1 gold)"
+        - ""
+        - "\"They're everywhere.\""
+        - ""
+        - "New quest: Thieves in the Market."
+        - ""
+        - The thieves flee.
+        - "Quest complete: Thieves in the Market."
+        - ""
+        - The merchants cheer.
+        - ""
+        - ""
+        - ""
+        - "  ‣ apple (9 silver)"
+        - ""
+        - You buy the apple from Farmer for 9 silver.
+        - "You hand over 1 gold, and get 1 silver back."
+        "###);
+    }
+
+    #[test]
+    fn test_factions() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+regions: {}
+factions:
+  guild:
+    name: the Merchants' Guild
+    hostile_when: reputation.guild < 0
+    hostile: Thieves aren't welcome here.
+    prices: { 5: 50, 1: 80 }
+npcs:
+  merchant:
+    name: Merchant
+    targets: [merchant]
+    faction: guild
+    talk: "{if reputation.guild >= 5}Welcome back, friend!{else}Browse all you like.{end}"
+    items: [{ id: apple, price: 10 }]
+rooms:
+  - title: Market
+    coord: [1, 1, 0]
+    description: A busy market.
+    npcs: [merchant]
+    actions:
+      - { verb: Look, targets: [purse], value: You lift a purse., effects: [{ reputation: { guild: -2 } }] }
+      - { verb: Look, targets: [stall], value: You tidy the stall., effects: [{ reputation: { guild: 3 } }] }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        for command in [
+            "look merchant",
+            "look purse",
+            "talk merchant",
+            "look merchant",
+            "look stall",
+            "look merchant",
+            "look stall",
+            "look stall",
+            "talk merchant",
+            "look merchant",
+        ] {
+            assert!(game.step(command.into()).is_none());
+        }
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - ""
+        - ""
+        - "  ‣ apple (1 silver)"
+        - ""
+        - You lift a purse.
+        - ""
+        - "Your standing with the Merchants' Guild falls."
+        - ""
+        - "Thieves aren't welcome here."
+        - ""
+        - ""
+        - ""
+        - "Merchant won't trade with you."
+        - ""
+        - You tidy the stall.
+        - ""
+        - "Your standing with the Merchants' Guild rises."
+        - ""
+        - ""
+        - ""
+        - "  ‣ apple (8 copper)"
+        - ""
+        - You tidy the stall.
+        - ""
+        - "Your standing with the Merchants' Guild rises."
+        - ""
+        - You tidy the stall.
+        - ""
+        - "Your standing with the Merchants' Guild rises."
+        - ""
+        - "Welcome back, friend!"
+        - ""
+        - ""
+        - ""
+        - "  ‣ apple (5 copper)"
+        - ""
+        "###);
+        assert_eq!(game.save_state.reputation.get("guild"), Some(&7));
+    }
+
+    #[test]
+    fn test_ambient() {
+        let level = r#"
+maps: [["----", "-..-", "----"]]
+entry: [1, 1, 0]
+npcs:
+  fisher: { name: Fisher, description: A fisher., targets: [fisher], talk: "\"Fish?\"", items: [] }
+regions:
+  docks: { actions: [], ambient: { chance: 1, lines: [A gull screeches overhead.] } }
+rooms:
+  - { title: Pier, coord: [1, 1, 0], description: A pier., npcs: [fisher], regions: [docks] }
+  - title: Shed
+    coord: [2, 1, 0]
+    description: A shed.
+    ambient: { chance: 0, lines: [A rat scurries past.] }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        game.output().output.clear();
+        assert!(game.step("look fisher".into()).is_none());
+        // Talking isn't interrupted by the gulls.
+        assert!(game.step("talk to fisher".into()).is_none());
+        assert!(game.step("east".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - A fisher.
+        - ""
+        - ""
+        - A gull screeches overhead.
+        - ""
+        - "\"Fish?\""
+        - ""
+        - Shed
+        - ""
+        - "    A shed. "
+        - ""
+        - ""
+        - "Exits: _ _ _ w"
+        "###);
+    }
+
+    #[test]
+    fn test_spawns() {
+        let level = r#"
+maps: [["----", "-..-", "----"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+rooms:
+  - title: Orchard
+    coord: [1, 1, 0]
+    description: An orchard.
+    items: [{ id: apple, quantity: 1, name: ~, pickup: ~ }]
+    spawns: [{ id: apple, every: 2, max: 2 }]
+  - title: Shed
+    coord: [2, 1, 0]
+    description: A shed.
+    spawns: [{ id: lantern, quantity: 3, every: 3, max: 2 }]
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        game.output().output.clear();
+        let mut lines = Vec::new();
+        for command in ["take apple", "look", "take apple"] {
+            assert!(game.step(command.into()).is_none());
+            lines.extend(
+                take_output(&game)
+                    .into_iter()
+                    .filter(|line| !line.is_empty() && !line.starts_with("Exits")),
+            );
+        }
+        // The turns toward the next apple are kept in the save.
+        let orchard = &game.save_state.room_inventories[&Coord { x: 1, y: 1, z: 0 }];
+        assert_eq!(orchard.spawning.get("apple"), Some(&1));
+        assert!(game.step("east".into()).is_none());
+        lines.extend(
+            take_output(&game)
+                .into_iter()
+                .filter(|line| !line.is_empty()),
+        );
+        insta::assert_yaml_snapshot!(lines, @r###"
+        ---
+        - You place the apple in your inventory.
+        - Orchard
+        - "    An orchard. "
+        - You notice an apple here.
+        - You place the apple in your inventory.
+        - Shed
+        - "    A shed. "
+        - 2 lanterns
+        - "Exits: _ _ _ w"
+        "###);
+    }
+
+    #[test]
+    fn test_score() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+achievements:
+  stargazer: { name: Stargazer, description: Looked at the stars. }
+  sailor: { name: Sailor }
+pickup_points: { apple: 3 }
+rooms:
+  - title: Dock
+    coord: [1, 1, 0]
+    description: A busy dock under the night sky.
+    items: [{ id: apple, quantity: 2, name: ~, pickup: ~ }]
+    actions:
+      - verb: Look
+        targets: [stars, sky]
+        value: The stars glitter over the sea.
+        award: { id: stars, points: 10, achievement: stargazer }
+"#;
+        let path = std::env::temp_dir().join("text-adventure-test-achievements.yml");
+        let _ = fs::remove_file(&path);
+        let mut command_runner = CommandRunner::new(vec![]);
+        command_runner.achievements_path = Some(path.clone());
+        let mut game = game_from_level(level, &mut command_runner);
+        assert!(game.step("look stars".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - The stars glitter over the sea.
+        - ""
+        - +10 points
+        - ""
+        - ╔═════════════════════════════════╗
+        - "║ Achievement unlocked: Stargazer ║"
+        - ╚═════════════════════════════════╝
+        - Looked at the stars.
+        - ""
+        "###);
+
+        // Awards are only given once.
+        assert!(game.step("look sky then take apple".into()).is_none());
+        assert!(game.step("drop apple then take apple".into()).is_none());
+        assert_eq!(game.save_state.score, 13);
+        game.output().output.clear();
+        assert!(game.step("quit".into()).is_some());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - ╔═══════╗
+        - ║ Score ║
+        - ╚═══════╝
+        - "  Points: 13"
+        - "  Achievements: 1 of 2"
+        - "    ‣ Stargazer - Looked at the stars."
+        - ""
+        "###);
+
+        // The achievement is still unlocked in a new game.
+        let mut command_runner = CommandRunner::new(vec![]);
+        command_runner.achievements_path = Some(path.clone());
+        let mut game = game_from_level(level, &mut command_runner);
+        fs::remove_file(&path).unwrap();
+        assert!(game.step("score".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - ╔═══════╗
+        - ║ Score ║
+        - ╚═══════╝
+        - "  Points: 0"
+        - "  Achievements: 1 of 2"
+        - "    ‣ Stargazer - Looked at the stars."
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_death() {
+        let level = r#"
+maps: [["-----", "-...-", "-----"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+start: { items: [{ id: gold, quantity: 12 }] }
+respawn: { money: 500, message: You wake up on the bridge, your purse lighter. }
+rooms:
+  - { title: Temple, coord: [1, 1, 0], description: A quiet temple. }
+  - title: Bridge
+    coord: [2, 1, 0]
+    description: A rope bridge.
+    checkpoint: true
+    actions:
+      - { verb: Look, targets: [rope], value: You tug on the rope., death: It snaps. }
+  - { title: Chasm, coord: [3, 1, 0], description: Thin air., death: You fall. }
+"#;
+        let mut command_runner = CommandRunner::new(vec!["respawn"]);
+        let mut game = game_from_level(level, &mut command_runner);
+        assert!(game.step("east".into()).is_none());
+        assert_eq!(game.save_state.checkpoint, Some(Coord { x: 2, y: 1, z: 0 }));
+
+        // The rest of the commands are skipped once the player dies.
+        assert!(game.step("east then west".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - You wake up on the bridge
+        - ""
+        - Bridge
+        - ""
+        - "    A rope bridge. "
+        - ""
+        - ""
+        - "Exits: _ e _ w"
+        "###);
+        assert_eq!(game.room.title, "Bridge");
+        assert_eq!(game.save_state.inventory.items[0].quantity, 7);
+
+        assert!(matches!(
+            game.step("look rope".into()),
+            Some(GameLoopResponse::Quit)
+        ));
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - You tug on the rope.
+        - ""
+        - It snaps.
+        - ""
+        - ╔═══════════════╗
+        - ║ You have died ║
+        - ╚═══════════════╝
+        - "What now? (respawn, load, restart, quit)"
+        "###);
+    }
+
+    #[test]
+    fn test_undo() {
+        let level = r#"
+maps: [["----", "-..-", "----"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+rooms:
+  - title: Cellar
+    coord: [1, 1, 0]
+    description: A damp cellar.
+    items: [{ id: apple, quantity: 1 }]
+  - { title: Stairs, coord: [2, 1, 0], description: Stone stairs., death: You slip. }
+"#;
+        let mut command_runner = CommandRunner::new(vec!["quit"]);
+        let mut game = game_from_level(level, &mut command_runner);
+        assert!(game.step("undo".into()).is_none());
+        let carried = game.save_state.inventory.items.len();
+        assert!(game.step("take apple then look".into()).is_none());
+        assert_eq!(game.save_state.inventory.items.len(), carried + 1);
+        assert_eq!(game.save_state.turn, 2);
+        take_output(&game);
+
+        // Only the turn before the undo is taken back, not the whole compound command.
+        assert!(game.step("undo".into()).is_none());
+        assert_eq!(game.save_state.inventory.items.len(), carried + 1);
+        assert_eq!(game.save_state.turn, 1);
+        assert!(game.step("undo".into()).is_none());
+        assert_eq!(game.save_state.inventory.items.len(), carried);
+        assert_eq!(game.save_state.turn, 0);
+        assert!(game.step("undo".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - "Time rewinds. Let's pretend that never happened."
+        - ""
+        - Cellar
+        - ""
+        - "    A damp cellar. "
+        - ""
+        - ""
+        - ""
+        - "Exits: _ e _ _"
+        - "Time rewinds. Let's pretend that never happened."
+        - ""
+        - Cellar
+        - ""
+        - "    A damp cellar. "
+        - ""
+        - ""
+        - an apple
+        - ""
+        - "Exits: _ e _ _"
+        - "You can't undo what hasn't been done."
+        "###);
+
+        // Death can be undone too.
+        assert!(matches!(
+            game.step("east".into()),
+            Some(GameLoopResponse::Quit)
+        ));
+        take_output(&game);
+        assert!(game.step("undo".into()).is_none());
+        assert_eq!(game.room.title, "Cellar");
+
+        for _ in 0..UNDO_LIMIT + 5 {
+            assert!(game.step("look".into()).is_none());
+        }
+        assert_eq!(game.history.len(), UNDO_LIMIT);
+    }
+
+    #[test]
+    fn test_feedback() {
+        let path = std::env::temp_dir().join("text-adventure-test-feedback.yml");
+        let _ = fs::remove_file(&path);
+        let mut command_runner = CommandRunner::new(vec![]);
+        command_runner.feedback_path = Some(path.clone());
+        let mut game = Game::new(ItemDatabase::new(), &mut command_runner).unwrap();
+
+        assert!(game
+            .step("feedback the sword and the apple look the same. weird".into())
+            .is_none());
+        assert!(game.step("feedback".into()).is_none());
+        assert!(game
+            .step(format!("feedback {}", "a".repeat(MAX_FEEDBACK_LENGTH + 1)))
+            .is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - "Thanks, your feedback was saved."
+        - "Type your feedback after the command: feedback <text>"
+        - Feedback can be at most 1000 characters.
+        "###);
+
+        let entries: Vec<serde_yaml::Value> =
+            serde_yaml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0]["comment"].as_str(),
+            Some("the sword and the apple look the same. weird")
+        );
+        assert_eq!(entries[0]["turn"].as_u64(), Some(0));
+        assert_eq!(
+            entries[0]["state"].as_str().map(|state| state.len()),
+            Some(16)
+        );
+    }
+
+    #[test]
+    fn test_emote() {
+        insta::assert_yaml_snapshot!(run_game(vec!["wave at guard"]), @r###"
+        ---
+        - The guard does not wave back. He points firmly to the north.
+        - ""
+        "###);
+    }
+
+    #[test]
+    fn test_emote_without_target() {
+        insta::assert_yaml_snapshot!(run_game(vec!["bow"]), @r###"
+        ---
+        - You bow deeply. Nobody applauds.
+        "###);
+    }
+
+    #[test]
+    fn test_vocabulary() {
+        insta::assert_yaml_snapshot!(run_game(vec!["examine guardhouse then speak to soldier"]), @r###"
+        ---
+        - "The guard post is well worn. It looks old, but sturdy."
+        - ""
+        - "The guard glares at you and says, \"Welcome to Stone End, now scram!\""
+        - ""
+        "###);
+    }
+}
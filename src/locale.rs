@@ -0,0 +1,258 @@
+use crate::{
+    level::{Coord, Level, Rooms},
+    passage::Passage,
+    utils::try_parse_yml_str,
+    Environment,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// Where the catalogs of the engine's messages are kept, one for each language, like
+/// data/lang/fr.yml. Each is a map of the narrator's message ids to their text.
+pub const LANG_DIR: &str = "data/lang";
+
+/// Load the engine's messages in a language. Messages that are missing from the
+/// catalog are shown in English.
+pub fn load_catalog(
+    lang: &str,
+    environment: &impl Environment,
+) -> Result<HashMap<String, String>, String> {
+    let path = Path::new(LANG_DIR).join(format!("{}.yml", lang));
+    let path = path.display().to_string();
+    let text = match environment.try_read_text(&path) {
+        Some(text) => text,
+        None => {
+            return Err(format!(
+                "There is no language {:?}, the languages are: {}",
+                lang,
+                languages(environment).join(", ")
+            ))
+        }
+    };
+    try_parse_yml_str(&text, &path)
+}
+
+/// The languages that have a catalog.
+fn languages(environment: &impl Environment) -> Vec<String> {
+    let mut languages: Vec<String> = environment
+        .list_files(LANG_DIR)
+        .iter()
+        .filter_map(|file| file.strip_suffix(".yml"))
+        .map(String::from)
+        .collect();
+    languages.sort();
+    languages
+}
+
+/// A level's text in another language, kept next to the level, like
+/// data/levels/stone-end-market.fr.yml. Anything the translation leaves out is shown
+/// in the level's own language.
+///
+/// ```yml
+/// rooms:
+///   - coord: [3, 7, 0]
+///     title: Les quais de Stone End
+///     description: Vous êtes sur les quais.
+/// npcs:
+///   guard: { description: Un garde s'ennuie à son poste. }
+/// messages:
+///   pick-nothing: Vous vous curez le nez.
+/// ```
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LevelTranslation {
+    #[serde(default)]
+    pub rooms: Vec<RoomTranslation>,
+    #[serde(default)]
+    pub npcs: HashMap<String, NpcTranslation>,
+    /// The level's narrator messages, in this language.
+    #[serde(default)]
+    pub messages: HashMap<String, String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoomTranslation {
+    pub coord: Coord,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<Passage>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct NpcTranslation {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub description: Option<Passage>,
+    #[serde(default)]
+    pub talk: Option<Passage>,
+}
+
+/// Where the translation of a level into a language is kept.
+pub fn translation_path(level_path: &str, lang: &str) -> PathBuf {
+    Path::new(level_path).with_extension(format!("{}.yml", lang))
+}
+
+/// Put a level into the player's language, with the engine's messages from the
+/// catalog, and the level's own text from its translation if it has one.
+pub fn localize(
+    level: &mut Level,
+    level_path: Option<&str>,
+    lang: &str,
+    catalog: &HashMap<String, String>,
+    environment: &impl Environment,
+) -> Result<(), String> {
+    level.narrator.translated = catalog.clone();
+    let path = match level_path {
+        Some(level_path) => translation_path(level_path, lang),
+        None => return Ok(()),
+    };
+    let path = path.display().to_string();
+    let text = match environment.try_read_text(&path) {
+        Some(text) => text,
+        None => return Ok(()),
+    };
+    let translation: LevelTranslation = try_parse_yml_str(&text, &path)?;
+    translate(level, translation);
+    Ok(())
+}
+
+/// Swap the level's text for a translation's.
+pub fn translate(level: &mut Level, translation: LevelTranslation) {
+    let mut rooms = std::mem::take(&mut level.rooms).into_vec();
+    for room_translation in translation.rooms {
+        let room = match rooms
+            .iter_mut()
+            .find(|room| room.coord == room_translation.coord)
+        {
+            Some(room) => room,
+            None => continue,
+        };
+        if let Some(title) = room_translation.title {
+            room.title = title;
+        }
+        if let Some(description) = room_translation.description {
+            room.description = description;
+        }
+    }
+    level.rooms = Rooms::from(rooms.into_iter().map(Rc::new).collect::<Vec<_>>());
+
+    for (id, npc_translation) in translation.npcs {
+        let npc = match level.npcs.get_mut(&id) {
+            Some(npc) => npc,
+            None => continue,
+        };
+        if let Some(name) = npc_translation.name {
+            npc.name = name;
+        }
+        if let Some(description) = npc_translation.description {
+            npc.description = description;
+        }
+        if let Some(talk) = npc_translation.talk {
+            npc.talk = talk;
+        }
+    }
+    level.narrator.translated.extend(translation.messages);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::narrator::is_message;
+    use crate::{testing::*, Game, ItemDatabase};
+
+    #[test]
+    fn test_catalogs() {
+        let mut command_runner = CommandRunner::new(vec![]);
+        let environment = &mut command_runner;
+        for lang in languages(&environment) {
+            let catalog = load_catalog(&lang, &environment).unwrap();
+            for id in catalog.keys() {
+                assert!(
+                    is_message(id),
+                    "{}.yml has an unknown message {:?}",
+                    lang,
+                    id
+                );
+            }
+        }
+        assert_eq!(
+            translation_path("data/levels/stone-end-market.yml", "fr"),
+            PathBuf::from("data/levels/stone-end-market.fr.yml")
+        );
+        assert!(load_catalog("xx", &environment)
+            .unwrap_err()
+            .starts_with("There is no language \"xx\", the languages are: "));
+
+        // The catalogs are read through the environment, like the levels.
+        command_runner
+            .files
+            .insert("data/lang/xx.yml", "pick-nothing: Xx.".into());
+        let catalog = load_catalog("xx", &&mut command_runner).unwrap();
+        assert_eq!(catalog["pick-nothing"], "Xx.");
+    }
+
+    #[test]
+    fn test_lang() {
+        let mut command_runner = CommandRunner::new(vec![]);
+        command_runner.lang = Some("fr");
+        let mut game = Game::new(ItemDatabase::new(), &mut command_runner).unwrap();
+        assert!(game.step("look".into()).is_none());
+        assert!(game.step("take unicorn".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Les quais de Stone End
+        - ""
+        - "    Vous êtes sur les quais de Stone End. Au sud, un garde de la ville se tient dans une "
+        - "    guérite et bloque l'entrée des quais. Vous apercevez « The Torbay » ancré dans le "
+        - "    port, le navire qui vous a amené. La barque qui vous a conduit depuis le navire est "
+        - "    amarrée aux quais. Les marins ne sont nulle part. "
+        - ""
+        - "    Au nord, la ville vous attend. "
+        - ""
+        - ""
+        - "Sorties : n _ _ _"
+        - Vous ne trouvez pas de unicorn à prendre.
+        "###);
+
+        // Rooms without a translation are shown in English.
+        assert!(game.step("north then north".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - La route du marché de Stone End
+        - ""
+        - "    La route est bordée de maisons serrées les unes contre les autres. Un marin du navire "
+        - "    fait la cour à une femme vêtue d'une robe usée, mais colorée. "
+        - ""
+        - "    Au nord, la ville commence à s'ouvrir. "
+        - ""
+        - "    Les quais se trouvent au sud. Une forêt de mâts dépasse de la baie. De plus petits "
+        - "    bateaux font la navette avec le port animé. "
+        - ""
+        - ""
+        - "Sorties : n _ s _"
+        - Stone End Market Gate
+        - ""
+        - "    You stand in front of a gate. Two guards stand there, pikes in hand. Over the gate "
+        - "    hangs a festive hand painted banner. "
+        - ""
+        - ""
+        - "Sorties : n _ s _"
+        "###);
+
+        // A language without a catalog is played in the level's own language.
+        let mut command_runner = CommandRunner::new(vec![]);
+        command_runner.lang = Some("xx");
+        let game = Game::new(ItemDatabase::new(), &mut command_runner).unwrap();
+        assert!(game.lang.is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - "Unable to play in xx, so the game is in its own language. There is no language \"xx\", the languages are: fr"
+        - ""
+        "###);
+    }
+}
@@ -1,466 +1,317 @@
-mod level;
-mod print;
-mod utils;
-
-use crate::utils::parse_yml;
-use level::{Coord, Direction, InventoryItem, ItemDatabase, Level, Room, RoomItem, Verb};
-use print::{print_map_issue, print_room_description, print_text_file};
-use serde::{Deserialize, Serialize};
 use std::{
-    cell::{RefCell, RefMut},
-    collections::HashMap,
     fs,
-    io::{Stdout, Write},
-    iter::Peekable,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process,
-    rc::Rc,
-    str::SplitWhitespace,
+    time::Instant,
+};
+use text_adventure::{
+    game_loop,
+    graph::{self, GraphFormat},
+    leaderboard, level_edit,
+    pack::{self, Pack},
+    save_edit, server, stats,
+    transcript::{self, Recorder, TRANSCRIPT_PATH},
+    validate, walkthrough, Config, Environment, GameLoopResponse, GameOptions, ItemDatabase,
+    Terminal, Theme, Tui, CONFIG_PATH, SAVE_PATH,
 };
 
-pub trait Environment: Write {
-    fn get_prompt(&mut self) -> String;
-}
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let config = Config::load(Path::new(CONFIG_PATH)).unwrap_or_else(|message| {
+        eprintln!("{}", message);
+        process::exit(1);
+    });
+    let ascii = args.iter().any(|arg| arg == "--ascii");
+    let bullet = if ascii { "*" } else { "‣" };
+    let no_color =
+        args.iter().any(|arg| arg == "--no-color") || std::env::var_os("NO_COLOR").is_some();
+    let theme_name = match args.iter().position(|arg| arg == "--theme") {
+        Some(index) => match args.get(index + 1) {
+            Some(name) => name.as_str(),
+            None => {
+                eprintln!("Expected a theme name after --theme");
+                process::exit(1);
+            }
+        },
+        None => config.theme.as_str(),
+    };
+    let theme = if no_color {
+        None
+    } else {
+        Some(Theme::load(theme_name))
+    };
 
-struct Terminal {
-    stdout: Stdout,
-}
+    let packs: Vec<String> = args
+        .iter()
+        .enumerate()
+        .filter(|(_, arg)| *arg == "--pack")
+        .map(|(index, _)| match args.get(index + 1) {
+            Some(path) => path.clone(),
+            None => {
+                eprintln!("Expected a path after --pack, like: --pack mods/haunted.yml");
+                process::exit(1);
+            }
+        })
+        .collect();
 
-impl Terminal {
-    fn new() -> Terminal {
-        Terminal {
-            stdout: std::io::stdout(),
+    if args.get(1).map(String::as_str) == Some("validate") {
+        // The paths of the level and items, skipping over the packs.
+        let paths: Vec<&str> = args[2..]
+            .iter()
+            .enumerate()
+            .filter(|(index, arg)| *arg != "--pack" && args[index + 1] != "--pack")
+            .map(|(_, arg)| arg.as_str())
+            .collect();
+        let problems =
+            validate::validate_files(paths.first().copied(), paths.get(1).copied(), &packs);
+        if problems.is_empty() {
+            println!("No problems were found.");
+            return;
         }
+        for problem in problems.iter() {
+            eprintln!("  {} {}", bullet, problem);
+        }
+        eprintln!("\nFound {} problems.", problems.len());
+        process::exit(1);
     }
-}
-
-impl Environment for Terminal {
-    fn get_prompt(&mut self) -> String {
-        rprompt::prompt_reply_stdout("» ").unwrap().to_lowercase()
-    }
-}
-
-impl Write for Terminal {
-    fn write(&mut self, buffer: &[u8]) -> Result<usize, std::io::Error> {
-        self.stdout.write(buffer)
-    }
-
-    fn flush(&mut self) -> Result<(), std::io::Error> {
-        self.stdout.flush()
-    }
-}
 
-#[derive(Debug, Clone)]
-pub struct RoomMapInfo {
-    north: Option<Coord>,
-    east: Option<Coord>,
-    south: Option<Coord>,
-    west: Option<Coord>,
-}
-
-impl RoomMapInfo {
-    fn from_direction(&self, direction: &Direction) -> &Option<Coord> {
-        match direction {
-            Direction::North => &self.north,
-            Direction::East => &self.east,
-            Direction::West => &self.west,
-            Direction::South => &self.south,
+    if args.get(1).map(String::as_str) == Some("graph") {
+        let format = if args.iter().any(|arg| arg == "--mermaid") {
+            GraphFormat::Mermaid
+        } else {
+            GraphFormat::Dot
+        };
+        let level_path = args[2..]
+            .iter()
+            .find(|arg| !arg.starts_with("--"))
+            .map(String::as_str);
+        match graph::graph_file(level_path, format) {
+            Ok(graph) => print!("{}", graph),
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(1);
+            }
         }
+        return;
     }
-}
 
-enum RoomType {
-    Normal,
-}
-
-fn parse_map(level: &Level) -> HashMap<Coord, RoomMapInfo> {
-    // First build a map that can be queried by coordinates.
-    let mut coord_map: HashMap<Coord, RoomType> = HashMap::new();
-    for (z, map) in level.maps.iter().enumerate() {
-        for (y, row) in map.iter().enumerate() {
-            for (x, ch) in row.chars().enumerate() {
-                match ch {
-                    '.' => coord_map.insert(Coord { x, y, z }, RoomType::Normal),
-                    '#' | '-' => None,
-                    // This is a comment.
-                    ' ' => break,
-                    _ => {
-                        eprintln!("Unknown character in a map.");
-                        print_map_issue(&level, &Coord { x, y, z });
-                        process::exit(1);
-                    }
-                };
+    if args.get(1).map(String::as_str) == Some("stats") {
+        match stats::stats_file(args.get(2).map(String::as_str)) {
+            Ok(stats) => print!("{}", stats),
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(1);
             }
         }
+        return;
     }
 
-    let mut room_map: HashMap<Coord, RoomMapInfo> = HashMap::new();
-
-    for (coord, _room_type) in coord_map.iter() {
-        let north_coord = coord.apply(&Direction::North);
-        let east_coord = coord.apply(&Direction::East);
-        let south_coord = coord.apply(&Direction::South);
-        let west_coord = coord.apply(&Direction::West);
-
-        if let None = level.get_room(&coord) {
-            eprintln!("Empty rooms were found in the map. Add the following:\n");
-
-            for (coord, _) in coord_map.iter() {
-                if let None = level.get_room(&coord) {
-                    eprintln!("  - title: TODO",);
-                    eprintln!("    coord: [{}, {}, {}]", coord.x, coord.y, coord.z);
-                    eprintln!("    description: TODO",);
+    if args.get(1).map(String::as_str) == Some("walkthrough") {
+        match walkthrough::walkthrough_file(args.get(2).map(String::as_str)) {
+            Ok(commands) => {
+                for command in commands {
+                    println!("{}", command);
                 }
+                // End the replay, rather than waiting for more input.
+                println!("quit");
             }
-
-            eprintln!("");
-            print_map_issue(&level, &coord);
-            process::exit(1);
-        };
-
-        room_map.insert(
-            coord.clone(),
-            RoomMapInfo {
-                north: match coord_map.get(&coord.apply(&Direction::North)) {
-                    Some(RoomType::Normal) => Some(north_coord),
-                    None => None,
-                },
-                east: match coord_map.get(&coord.apply(&Direction::East)) {
-                    Some(RoomType::Normal) => Some(east_coord),
-                    None => None,
-                },
-                south: match coord_map.get(&coord.apply(&Direction::South)) {
-                    Some(RoomType::Normal) => Some(south_coord),
-                    None => None,
-                },
-                west: match coord_map.get(&coord.apply(&Direction::West)) {
-                    Some(RoomType::Normal) => Some(west_coord),
-                    None => None,
-                },
-            },
-        );
-    }
-
-    room_map
-}
-
-enum ParsedCommand {
-    Look(Option<String>),
-    Talk(Option<String>),
-    Message(String),
-    Inventory,
-    Help(Option<String>),
-    Move(Direction),
-    Drop(String),
-    Take(String),
-    Quit,
-    Debug,
-    Restart,
-    Custom(String, Option<String>),
-}
-
-#[derive(Serialize, Deserialize)]
-struct Inventory {
-    pub items: Vec<InventoryItem>,
-}
-
-impl From<Vec<InventoryItem>> for Inventory {
-    fn from(items: Vec<InventoryItem>) -> Inventory {
-        Inventory { items }
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(1);
+            }
+        }
+        return;
     }
-}
 
-impl Inventory {
-    fn add_item(&mut self, new_item: InventoryItem) {
-        match self.items.iter_mut().find(|item| item.id == new_item.id) {
-            Some(item) => item.quantity += new_item.quantity,
-            None => self.items.push(new_item),
+    if args.get(1).map(String::as_str) == Some("export") {
+        match args.get(2).map(String::as_str) {
+            Some("html") => print!(
+                "{}",
+                transcript::export_file(args.get(3).map(String::as_str))
+            ),
+            _ => {
+                eprintln!("Expected a format after export, like: export html");
+                process::exit(1);
+            }
         }
+        return;
     }
-}
-
-enum DropResult {
-    Item(InventoryItem),
-    Sticky,
-    None,
-}
 
-impl Inventory {
-    pub fn drop_item(&mut self, name: &str) -> DropResult {
-        // Find the item if it exists.
-        let tuple = self
-            .items
+    if args.get(1).map(String::as_str) == Some("leaderboard") {
+        let out =
+            args.iter()
+                .position(|arg| arg == "--out")
+                .map(|index| match args.get(index + 1) {
+                    Some(out) => out.as_str(),
+                    None => {
+                        eprintln!("Expected a path after --out");
+                        process::exit(1);
+                    }
+                });
+        let paths: Vec<String> = args[2..]
             .iter()
             .enumerate()
-            .find(|(_, item)| item.name.to_lowercase() == name || item.targets.contains(name));
-
-        match tuple {
-            Some((index, item)) => {
-                if item.sticky {
-                    return DropResult::Sticky;
+            .filter(|(index, arg)| *arg != "--out" && args[index + 1] != "--out")
+            .map(|(_, arg)| arg.clone())
+            .collect();
+        let paths = if paths.is_empty() {
+            vec![leaderboard::RESULTS_PATH.to_string()]
+        } else {
+            paths
+        };
+        match leaderboard::merge_files(&paths, out) {
+            Ok((ranking, problems)) => {
+                print!("{}", ranking);
+                for problem in problems.iter() {
+                    eprintln!("  {} {}", bullet, problem);
                 }
-
-                let removed_item = item.clone();
-
-                // Remove the item.
-                self.items = self
-                    .items
-                    .drain(..)
-                    .enumerate()
-                    .filter(|(i, _)| *i != index)
-                    .map(|(_, item)| item)
-                    .collect();
-
-                DropResult::Item(removed_item)
             }
-            None => DropResult::None,
-        }
-    }
-}
-
-fn parse_command_target(
-    command: &str,
-    words: &mut Peekable<SplitWhitespace>,
-) -> Result<Option<String>, String> {
-    let word = match words.next() {
-        Some(word) => word,
-        None => return Ok(None),
-    };
-
-    let mut target: String = match word {
-        "at" | "to" | "in" | "up" => {
-            if words.peek().is_none() {
-                return Err(format!("{} {}... what?", command, word));
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(1);
             }
-            String::new()
         }
-        _ => word.to_string(),
-    };
-
-    while let Some(word) = words.next() {
-        target.push_str(word);
-        if words.peek().is_some() {
-            target.push(' ');
-        }
-    }
-
-    Ok(Some(target))
-}
-
-fn parse_command(input: String) -> Result<ParsedCommand, String> {
-    let mut words = input.split_whitespace().peekable();
-    let command = match words.next() {
-        Some(command) => command,
-        None => {
-            // No input was given.
-            return Ok(ParsedCommand::Look(None));
-        }
-    };
-
-    match command {
-        "look" | "l" => Ok(ParsedCommand::Look(parse_command_target(
-            &command, &mut words,
-        )?)),
-        "talk" | "t" => Ok(ParsedCommand::Talk(parse_command_target(
-            &command, &mut words,
-        )?)),
-        "north" | "n" => Ok(ParsedCommand::Move(Direction::North)),
-        "east" | "e" => Ok(ParsedCommand::Move(Direction::East)),
-        "south" | "s" => Ok(ParsedCommand::Move(Direction::South)),
-        "west" | "w" => Ok(ParsedCommand::Move(Direction::West)),
-        "inventory" | "inv" | "i" | "items" => Ok(ParsedCommand::Inventory),
-        "go" => match parse_command_target(&command, &mut words)? {
-            Some(ref s) => match s.as_str() {
-                "north" => Ok(ParsedCommand::Move(Direction::North)),
-                "east" => Ok(ParsedCommand::Move(Direction::East)),
-                "south" => Ok(ParsedCommand::Move(Direction::South)),
-                "west" => Ok(ParsedCommand::Move(Direction::West)),
-                _ => Err(format!("You don't know how to go {:?}", s)),
-            },
-            None => Ok(ParsedCommand::Message("Where do you want to go?".into())),
-        },
-        "" => Ok(ParsedCommand::Message("".into())),
-        "help" | "h" => Ok(ParsedCommand::Help(parse_command_target(
-            &command, &mut words,
-        )?)),
-        "debug" => Ok(ParsedCommand::Debug),
-        "drop" => match parse_command_target(&command, &mut words)? {
-            Some(target) => Ok(ParsedCommand::Drop(target)),
-            None => Ok(ParsedCommand::Message("You stop drop and roll.".into())),
-        },
-        "pick" | "pickup" | "take" | "grab" => match parse_command_target(&command, &mut words)? {
-            Some(target) => Ok(ParsedCommand::Take(target)),
-            None => match command {
-                "pick" => Err(format!("You pick your nose. Gross.")),
-                _ => Err(format!(
-                    "This relationship is on the rocks, all you do is take take take."
-                )),
-            },
-        },
-        "quit" | "q" | "exit" => Ok(ParsedCommand::Quit),
-        "restart" => Ok(ParsedCommand::Restart),
-        _ => Ok(ParsedCommand::Custom(
-            command.to_string(),
-            parse_command_target(&command, &mut words)?,
-        )),
+        return;
     }
-}
 
-pub struct Game<'a, T: Environment> {
-    level: Level,
-    room: Rc<Room>,
-    item_db: &'a ItemDatabase,
-    save_state: SaveState,
-    lookup_room_info: HashMap<Coord, RoomMapInfo>,
-    room_info: RoomMapInfo,
-    environment: RefCell<T>,
-}
-
-impl<'a, T: Environment> Game<'a, T> {
-    fn new(item_db: &'a ItemDatabase, environment: T) -> Game<'a, T> {
-        let level: Level = parse_yml(&"data/levels/stone-end-market.yml".into());
-        let save_state = {
-            let path = PathBuf::from("data/save-state.yml");
-            if path.exists() {
-                parse_yml(&"data/save-state.yml".into())
-            } else {
-                SaveState::initialize(&item_db, &level)
+    if args.get(1).map(String::as_str) == Some("save") {
+        match args.get(2).map(String::as_str) {
+            Some("edit") => {
+                let path = PathBuf::from(args.get(3).map(String::as_str).unwrap_or(SAVE_PATH));
+                let stdin = std::io::stdin();
+                if let Err(message) =
+                    save_edit::edit_file(&path, &mut stdin.lock(), &mut std::io::stdout())
+                {
+                    eprintln!("{}", message);
+                    process::exit(1);
+                }
+            }
+            _ => {
+                eprintln!("Expected a save command, like: save edit");
+                process::exit(1);
             }
-        };
-        let lookup_room_info = parse_map(&level);
-        let room = (*level
-            .get_room(&save_state.coord)
-            .expect("Unable to find the entry room."))
-        .clone();
-
-        let room_info = (*lookup_room_info.get(&save_state.coord).unwrap()).clone();
-
-        Game {
-            level,
-            room,
-            item_db,
-            save_state,
-            lookup_room_info,
-            room_info,
-            environment: RefCell::new(environment),
         }
+        return;
     }
 
-    fn output(&self) -> RefMut<T> {
-        self.environment.borrow_mut()
-    }
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct SaveState {
-    /// The current room coordinate.
-    coord: Coord,
-    /// Turn on debug logging.
-    debug: bool,
-    /// The player's inventory.
-    inventory: Inventory,
-    room_inventories: HashMap<Coord, RoomInventory>,
-}
-
-impl SaveState {
-    fn room_inventory_mut(&mut self) -> &mut RoomInventory {
-        self.room_inventories
-            .get_mut(&self.coord)
-            .expect("Could not find a room inventory.")
+    if args.get(1).map(String::as_str) == Some("edit") {
+        let stdin = std::io::stdin();
+        let path = args.get(2).map(String::as_str);
+        if let Err(message) = level_edit::edit_file(path, &mut stdin.lock(), &mut std::io::stdout())
+        {
+            eprintln!("{}", message);
+            process::exit(1);
+        }
+        return;
     }
-}
-
-#[derive(Serialize, Deserialize)]
-struct RoomInventory {
-    inventory: Vec<(RoomItem, InventoryItem)>,
-}
 
-impl From<Vec<(RoomItem, InventoryItem)>> for RoomInventory {
-    fn from(inventory: Vec<(RoomItem, InventoryItem)>) -> RoomInventory {
-        RoomInventory { inventory }
+    if args.get(1).map(String::as_str) == Some("serve") {
+        server::serve(parse_port(&args, 4000), theme, ascii);
+        return;
     }
-}
 
-impl RoomInventory {
-    pub fn take_item(&mut self, id: &str) -> Option<(RoomItem, InventoryItem)> {
-        let mut inventory = Vec::new();
-        let mut found_item = None;
-        for item in self.inventory.drain(..) {
-            let (ref room_item, ref inventory_item) = item;
-            if found_item.is_some() {
-                inventory.push(item);
-            } else if room_item.targets.contains(id) {
-                found_item = Some(item);
-            } else if inventory_item.targets.contains(id) {
-                found_item = Some(item);
-            } else {
-                inventory.push(item);
-            }
+    if args.iter().any(|arg| arg == "--web") {
+        #[cfg(feature = "web")]
+        return text_adventure::web::serve(parse_port(&args, 8080));
+        #[cfg(not(feature = "web"))]
+        {
+            eprintln!("The web server isn't included, build with `--features web` to use it.");
+            process::exit(1);
         }
-        self.inventory = inventory;
-        found_item
     }
 
-    fn add_item(&mut self, inventory_item: InventoryItem) {
-        self.inventory
-            .push((RoomItem::from(&inventory_item), inventory_item));
-    }
-
-    pub fn item_names_iter<'a>(&'a self) -> impl Iterator<Item = &'a str> {
-        self.inventory
-            .iter()
-            .map(|(room_item, inv_item)| match room_item.name {
-                Some(ref name) => name.as_str(),
-                None => &inv_item.name,
+    let use_tui = args.iter().any(|arg| arg == "--tui");
+    let record = args.iter().any(|arg| arg == "--record");
+    // Jam mode is for authors iterating on a level, so it watches the level too.
+    let jam_started = args.iter().any(|arg| arg == "--jam").then(Instant::now);
+    let watch = jam_started.is_some() || args.iter().any(|arg| arg == "--watch");
+    let scenario = args
+        .iter()
+        .position(|arg| arg == "--scenario")
+        .map(|index| match args.get(index + 1) {
+            Some(scenario) => scenario.clone(),
+            None => {
+                eprintln!("Expected a scenario id after --scenario");
+                process::exit(1);
+            }
+        });
+    let lang = args
+        .iter()
+        .position(|arg| arg == "--lang")
+        .map(|index| match args.get(index + 1) {
+            Some(lang) => lang.clone(),
+            None => {
+                eprintln!("Expected a language after --lang, like: --lang fr");
+                process::exit(1);
+            }
+        });
+    let loaded_packs: Vec<Pack> = packs
+        .iter()
+        .map(|path| {
+            Pack::load(path).unwrap_or_else(|message| {
+                eprintln!("{}", message);
+                process::exit(1);
             })
-    }
-}
-
-impl SaveState {
-    fn initialize(item_db: &ItemDatabase, level: &Level) -> SaveState {
-        SaveState {
-            coord: level.entry,
-            debug: false,
-            inventory: Inventory::from(vec![
-                //
-                item_db.get("sword").clone(),
-                item_db.get("gold").clone(),
-            ]),
-            room_inventories: {
-                let mut room_inventories = HashMap::new();
-                for room in level.rooms.iter() {
-                    let mut room_inventory: Vec<(RoomItem, InventoryItem)> = Vec::new();
-                    // Fill the room item in with the actual item from the item db.
-                    for room_item in room.items.iter() {
-                        let room_item = room_item.clone();
-                        let mut inventory_item = item_db.get(&room_item.id).clone();
-                        inventory_item.quantity = room_item.quantity;
-                        room_inventory.push((room_item, inventory_item));
-                    }
-                    room_inventories.insert(room.coord, RoomInventory::from(room_inventory));
-                }
-                room_inventories
-            },
+        })
+        .collect();
+    // Conflicting packs can still be played, the later ones win.
+    for problem in pack::problems(&loaded_packs) {
+        eprintln!("{} {}", bullet, problem);
+    }
+    let seed = args.iter().position(|arg| arg == "--seed").map(|index| {
+        match args.get(index + 1).map(|seed| seed.parse()) {
+            Some(Ok(seed)) => seed,
+            _ => {
+                eprintln!("Expected a number after --seed");
+                process::exit(1);
+            }
         }
-    }
-}
+    });
+
+    let options = GameOptions {
+        seed,
+        watch,
+        jam_started,
+        scenario,
+        ascii,
+        config,
+        lang,
+        packs,
+        race: args.iter().any(|arg| arg == "--race"),
+        tutorial: args.iter().any(|arg| arg == "--tutorial"),
+    };
 
-enum GameLoopResponse {
-    Restart,
-    Quit,
-}
+    if record && Path::new(TRANSCRIPT_PATH).exists() {
+        // Restarts and deaths are part of the same recording.
+        fs::remove_file(TRANSCRIPT_PATH).expect("Unable to remove the old transcript.");
+    }
 
-fn main() {
     let item_db = ItemDatabase::new();
     loop {
-        match game_loop(&item_db, Terminal::new()) {
+        let response = if use_tui {
+            play(&item_db, Tui::new(options.clone()), record)
+        } else {
+            play(
+                &item_db,
+                Terminal::new(theme.clone(), options.clone()),
+                record,
+            )
+        };
+        let response = response.unwrap_or_else(|message| {
+            eprintln!("{}", message);
+            process::exit(1);
+        });
+        match response {
+            // The tutorial isn't saved, so there's nothing to erase.
+            GameLoopResponse::Restart if options.tutorial => {}
             GameLoopResponse::Restart => {
-                let save_file = PathBuf::from("data/save-state.yml");
+                let save_file = PathBuf::from(SAVE_PATH);
                 if save_file.exists() {
-                    fs::remove_file(PathBuf::from("data/save-state.yml"))
-                        .expect("Unable to remove the save file.");
+                    fs::remove_file(save_file).expect("Unable to remove the save file.");
                 }
             }
+            // Start again from the last save.
+            GameLoopResponse::Death => {}
             GameLoopResponse::Quit => {
                 println!("Thanks for playing!");
                 return;
@@ -469,326 +320,30 @@ fn main() {
     }
 }
 
-fn game_loop<'a, T: Environment>(item_db: &'a ItemDatabase, environment: T) -> GameLoopResponse {
-    let mut game = Game::new(&item_db, environment);
-
-    print_text_file(&game, "data/intro.txt");
-    print_room_description(&game);
-
-    loop {
-        let string = game.environment.borrow_mut().get_prompt();
-        // Add a newline after the prompt.
-        println!("");
-        match parse_command(string).unwrap_or_else(|message| ParsedCommand::Message(message)) {
-            ParsedCommand::Look(Some(target)) => {
-                look_command(&mut game, &target);
-            }
-            ParsedCommand::Look(None) => print_room_description(&game),
-            ParsedCommand::Help(Some(target)) => {
-                help_target_command(&game, &target);
-            }
-            ParsedCommand::Help(None) => print_text_file(&game, "data/help.txt"),
-            ParsedCommand::Move(direction) => {
-                let next_coord: Option<Coord> = (game.room_info.from_direction(&direction)).clone();
-
-                match next_coord {
-                    Some(next_coord) => {
-                        game.save_state.coord = next_coord.clone();
-                        game.room_info =
-                            (game.lookup_room_info.get(&game.save_state.coord).unwrap()).clone();
-
-                        game.room = game
-                            .level
-                            .get_room(&next_coord)
-                            .expect("Expected to find a room.")
-                            .clone();
-                        print_room_description(&game);
-                    }
-                    None => {
-                        eprintln!("You cannot move {}.", direction.lowercase_string());
-                    }
-                };
-            }
-            ParsedCommand::Debug => {
-                game.save_state.debug = !game.save_state.debug;
-                if game.save_state.debug {
-                    println!("Debug mode activated.");
-                } else {
-                    println!("Debug mode de-activated.");
-                }
-            }
-            ParsedCommand::Drop(target) => match game.save_state.inventory.drop_item(&target) {
-                DropResult::Item(item) => {
-                    println!("You dropped the {}.", item.name);
-                    game.save_state.room_inventory_mut().add_item(item);
-                }
-                DropResult::Sticky => {
-                    println!("The {} appear(s) to be sticking to your hand.", target)
-                }
-                DropResult::None => {
-                    println!("It does not look like you have a {}.", target);
-                }
-            },
-            ParsedCommand::Take(target) => {
-                match game.save_state.room_inventory_mut().take_item(&target) {
-                    Some((room_item, inventory_item)) => {
-                        game.save_state.inventory.add_item(inventory_item);
-                        match room_item.pickup {
-                            Some(pickup) => {
-                                println!("{}", pickup)
-                            }
-                            None => {
-                                println!("You place the {} in your inventory.", target)
-                            }
-                        }
-                    }
-                    None => {
-                        println!("You couldn't find a {} to take.", target);
-                    }
-                }
-            }
-            ParsedCommand::Quit => {
-                let path = PathBuf::from("data/save-state.yml");
-                let yml = serde_yaml::to_string(&game.save_state)
-                    .expect("Unable to serialize the game state.");
-                fs::write(path, yml).expect("Unable to save the game state.");
-
-                return GameLoopResponse::Quit;
-            }
-            ParsedCommand::Talk(Some(target)) => {
-                match game
-                    .room
-                    .find_action(Verb::Talk, &target, &game.level, None)
-                {
-                    Some(action) => {
-                        println!("{}", action.value);
-                    }
-                    None => {
-                        println!("You can't talk to {:?}", target);
-                    }
-                }
-            }
-            ParsedCommand::Talk(None) => {
-                println!("You talk outloud for a bit and feel much better, thank you.")
-            }
-            ParsedCommand::Inventory => {
-                print_box("Your inventory:");
-                if game.save_state.inventory.items.is_empty() {
-                    println!("    (empty)")
-                }
-                for item in game.save_state.inventory.items.iter() {
-                    match item.max_quantity {
-                        Some(_) => {
-                            println!("  ‣ {} ({})", item.name, item.quantity);
-                        }
-                        None => {
-                            println!("  ‣ {}", item.name);
-                        }
-                    }
-                }
-                println!("");
-            }
-            ParsedCommand::Message(message) => println!("{}", message),
-            ParsedCommand::Restart => {
-                if prompt_yes_no(
-                    &mut game,
-                    "Are you sure you want to erase your game and restart?",
-                ) {
-                    return GameLoopResponse::Restart;
-                } else {
-                    println!("Let's keep playing!");
-                }
-            }
-            ParsedCommand::Custom(command, target) => match target {
-                Some(target) => {
-                    match game
-                        .room
-                        .find_action(Verb::Custom, &target, &game.level, Some(&command))
-                    {
-                        Some(action) => println!("{}", action.value),
-                        None => println!(
-                            "You don't know how to {:?}. Type \"help\" for help.",
-                            command
-                        ),
-                    }
-                }
-                None => println!(
-                    "You don't know how to {:?}. Type \"help\" for help.",
-                    command
-                ),
-            },
-        }
-    }
-}
-
-fn print_box(text: &str) {
-    let len = text.len() + 2;
-    print!("╔");
-    for _ in 0..len {
-        print!("═");
-    }
-    print!("╗\n");
-
-    println!("║ {} ║", text);
-
-    print!("╚");
-    for _ in 0..len {
-        print!("═");
+fn play<T: Environment>(
+    item_db: &ItemDatabase,
+    environment: T,
+    record: bool,
+) -> Result<GameLoopResponse, String> {
+    if record {
+        game_loop(
+            item_db,
+            Recorder::new(environment, Path::new(TRANSCRIPT_PATH)),
+        )
+    } else {
+        game_loop(item_db, environment)
     }
-    print!("╝\n");
 }
 
-fn prompt_yes_no<T: Environment>(game: &mut Game<T>, message: &str) -> bool {
-    loop {
-        println!("{} (yes, no)", message);
-        let response = game.environment.borrow_mut().get_prompt();
-        match response.as_str() {
-            "yes" | "y" => {
-                return true;
-            }
-            "no" | "n" => {
-                return false;
-            }
+fn parse_port(args: &[String], default: u16) -> u16 {
+    match args.iter().position(|arg| arg == "--port") {
+        Some(index) => match args.get(index + 1).map(|port| port.parse()) {
+            Some(Ok(port)) => port,
             _ => {
-                println!("What was that?");
-            }
-        }
-    }
-}
-
-fn look_command<T: Environment>(game: &Game<T>, target: &String) {
-    // Look at something in the room through an action?
-    if let Some(action) = game
-        .room
-        .find_action(Verb::Look, &target, &game.level, None)
-    {
-        writeln!(game.output(), "{}\n", action.value).unwrap();
-        return;
-    }
-
-    // Look at an npc?
-    if let Some(npc) = game.room.get_npc(&game.level, &target) {
-        println!("{}\n", npc.description);
-        for (item, cost) in npc.items_iter(&game.item_db) {
-            println!("  ‣ {} ({} gp)", item.name, cost);
-        }
-        println!("");
-        return;
-    }
-
-    // Look at an npc's item?
-    for npc in game.room.npcs_iter(&game.level) {
-        for sale_item in npc.items.iter() {
-            if *target == sale_item.id {
-                let item = game.item_db.get(target);
-                println!("{}\n", item.description);
-                return;
+                eprintln!("Expected a port number after --port");
+                process::exit(1);
             }
-        }
-    }
-
-    // Look at your own items?
-    for inv_item in game.save_state.inventory.items.iter() {
-        if *target == inv_item.id {
-            let item = game.item_db.get(target);
-            println!("{}\n", item.description);
-            return;
-        }
-    }
-
-    println!("You don't see a {}.\n", target);
-}
-
-fn help_target_command<T: Environment>(game: &Game<T>, target: &String) {
-    // Help something in the room through an action?
-    if let Some(action) = game
-        .room
-        .find_action(Verb::Help, &target, &game.level, None)
-    {
-        println!("{}\n", action.value);
-        return;
-    }
-
-    println!("You can't help {}.\n", target);
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
-
-    struct CommandRunner {
-        commands: Vec<&'static str>,
-        output: Vec<u8>,
-    }
-
-    impl CommandRunner {
-        fn new(mut commands: Vec<&'static str>) -> CommandRunner {
-            commands.push("quit");
-            commands.reverse();
-            CommandRunner {
-                commands,
-                output: Vec::new(),
-            }
-        }
-
-        fn get_last_output<'a>(self) -> Vec<String> {
-            std::str::from_utf8(&self.output)
-                .unwrap()
-                .lines()
-                .map(|s| s.to_string())
-                .collect()
-        }
-    }
-
-    impl Write for CommandRunner {
-        fn write(&mut self, buffer: &[u8]) -> Result<usize, std::io::Error> {
-            for value in buffer.iter() {
-                self.output.push(*value);
-            }
-            Ok(buffer.len())
-        }
-
-        fn flush(&mut self) -> Result<(), std::io::Error> {
-            Ok(())
-        }
-    }
-
-    impl Environment for &mut CommandRunner {
-        fn get_prompt(&mut self) -> String {
-            if self.commands.len() > 1 {
-                // Retain the last output.
-                self.output.clear();
-            }
-            self.commands
-                .pop()
-                .expect("Expected to find a string in the CommandRunner.")
-                .to_string()
-        }
-    }
-
-    fn run_game(commands: Vec<&'static str>) -> Vec<String> {
-        let item_db = ItemDatabase::new();
-        let mut command_runner = CommandRunner::new(commands);
-
-        match game_loop(&item_db, &mut command_runner) {
-            GameLoopResponse::Quit => {}
-            GameLoopResponse::Restart => panic!("Unexpected restart."),
-        };
-
-        command_runner.get_last_output()
-    }
-
-    #[test]
-    fn test_look() {
-        insta::assert_yaml_snapshot!(run_game(vec!["look"]), @r###"
-        ---
-        - The Door to the Stone End Keep
-        - ""
-        - "    Stone steps lead up to two large sturdy doors. These are attached to the thick walls "
-        - "    of Stone End keep. Guards block the gate, standing at attention, pikes in hand. "
-        - ""
-        - ""
-        - "Exits: _ e s w"
-        "###);
+        },
+        None => default,
     }
 }
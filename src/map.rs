@@ -0,0 +1,408 @@
+use crate::{
+    level::{Coord, Direction, Level, Terrain, Tile},
+    print::print_map_issue,
+};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+    hash::Hash,
+};
+
+/// The directions that can be walked in, in the order they are tried.
+pub const DIRECTIONS: [Direction; 4] = [
+    Direction::North,
+    Direction::East,
+    Direction::South,
+    Direction::West,
+];
+
+const MAP_PROBLEMS: &str = "The map has problems, see the errors above.";
+
+/// The characters of the maps that the level's tiles can't change.
+pub const BUILT_IN_TILES: [char; 5] = ['.', '~', '#', '-', ' '];
+
+/// What a character of a map draws.
+pub enum Cell<'a> {
+    /// A room, with its terrain, and the level's tile if it has one.
+    Room(Terrain, Option<&'a Tile>),
+    Wall,
+    /// The rest of the row is a comment.
+    Comment,
+    /// A character that isn't built in or one of the level's tiles.
+    Unknown,
+}
+
+impl<'a> Cell<'a> {
+    /// Read a character of one of the level's maps.
+    pub fn read(level: &'a Level, ch: char) -> Cell<'a> {
+        match ch {
+            '.' => Cell::Room(Terrain::Land, None),
+            '~' => Cell::Room(Terrain::Water, None),
+            '#' | '-' => Cell::Wall,
+            ' ' => Cell::Comment,
+            _ => match level.tiles.get(&ch) {
+                Some(tile) if tile.passable => Cell::Room(tile.terrain, Some(tile)),
+                Some(_) => Cell::Wall,
+                None => Cell::Unknown,
+            },
+        }
+    }
+}
+
+/// Which rooms are next to a room on the map.
+#[derive(Debug, Clone)]
+pub struct RoomMapInfo {
+    north: Option<Coord>,
+    east: Option<Coord>,
+    south: Option<Coord>,
+    west: Option<Coord>,
+}
+
+impl RoomMapInfo {
+    /// The exits from a room, like "n _ s _", including the room's own exits, like any to
+    /// other levels, for the directions where `has_exit` is true.
+    pub fn exits(&self, has_exit: impl Fn(&Direction) -> bool) -> String {
+        [
+            (Direction::North, "n"),
+            (Direction::East, "e"),
+            (Direction::South, "s"),
+            (Direction::West, "w"),
+        ]
+        .iter()
+        .map(|(direction, name)| {
+            if self.in_direction(direction).is_some() || has_exit(direction) {
+                *name
+            } else {
+                "_"
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+
+    pub fn in_direction(&self, direction: &Direction) -> &Option<Coord> {
+        match direction {
+            Direction::North => &self.north,
+            Direction::East => &self.east,
+            Direction::West => &self.west,
+            Direction::South => &self.south,
+        }
+    }
+}
+
+/// The rooms of a level's map, and which of them are next to each other. The game,
+/// the walkthrough, and the validator all walk the map through this, so they agree
+/// on how it connects.
+#[derive(Debug, Clone, Default)]
+pub struct RoomGraph {
+    rooms: HashMap<Coord, RoomMapInfo>,
+    /// The rooms that are drawn as water, see `Terrain`.
+    water: HashSet<Coord>,
+    /// The rooms that are drawn with one of the level's tiles, by its character.
+    tiles: HashMap<Coord, char>,
+}
+
+impl RoomGraph {
+    /// Build the graph of a level's map. The details of any problems with the map are
+    /// reported to stderr. Unknown characters are only warned about, and left as walls.
+    pub fn parse(level: &Level) -> Result<RoomGraph, String> {
+        let mut cells: HashSet<Coord> = HashSet::new();
+        let mut water: HashSet<Coord> = HashSet::new();
+        let mut tiles: HashMap<Coord, char> = HashMap::new();
+        for (z, map) in level.maps.iter().enumerate() {
+            for (y, row) in map.iter().enumerate() {
+                for (x, ch) in row.chars().enumerate() {
+                    let coord = Coord { x, y, z };
+                    match Cell::read(level, ch) {
+                        Cell::Room(terrain, tile) => {
+                            cells.insert(coord);
+                            if terrain == Terrain::Water {
+                                water.insert(coord);
+                            }
+                            if tile.is_some() {
+                                tiles.insert(coord, ch);
+                            }
+                        }
+                        Cell::Wall => {}
+                        Cell::Comment => break,
+                        Cell::Unknown => {
+                            eprintln!(
+                                "Unknown character {:?} in a map, it's left as a wall. Add it \
+                                 to the level's tiles to make it a room:\n",
+                                ch
+                            );
+                            eprintln!("tiles:");
+                            eprintln!("  {:?}: {{ message: TODO }}\n", ch.to_string());
+                            print_map_issue(level, &coord);
+                            eprintln!();
+                        }
+                    };
+                }
+            }
+        }
+
+        if let Some(coord) = cells.iter().find(|coord| level.get_room(coord).is_none()) {
+            eprintln!("Empty rooms were found in the map. Add the following:\n");
+            for coord in cells.iter() {
+                if level.get_room(coord).is_none() {
+                    eprintln!("  - title: TODO",);
+                    eprintln!("    coord: [{}, {}, {}]", coord.x, coord.y, coord.z);
+                    eprintln!("    description: TODO",);
+                }
+            }
+            eprintln!();
+            print_map_issue(level, coord);
+            return Err(MAP_PROBLEMS.into());
+        }
+
+        let mut room_graph = RoomGraph::from_cells(&cells);
+        room_graph.water = water;
+        room_graph.tiles = tiles;
+        Ok(room_graph)
+    }
+
+    /// Connect the cells of a map to the cells next to them.
+    pub fn from_cells(cells: &HashSet<Coord>) -> RoomGraph {
+        let next_to = |coord: &Coord, direction: &Direction| {
+            let Coord { x, y, z } = *coord;
+            let next = match direction {
+                Direction::North => Coord {
+                    x,
+                    y: y.checked_sub(1)?,
+                    z,
+                },
+                Direction::East => Coord { x: x + 1, y, z },
+                Direction::South => Coord { x, y: y + 1, z },
+                Direction::West => Coord {
+                    x: x.checked_sub(1)?,
+                    y,
+                    z,
+                },
+            };
+            Some(next).filter(|next| cells.contains(next))
+        };
+        let rooms = cells
+            .iter()
+            .map(|coord| {
+                let room_info = RoomMapInfo {
+                    north: next_to(coord, &Direction::North),
+                    east: next_to(coord, &Direction::East),
+                    south: next_to(coord, &Direction::South),
+                    west: next_to(coord, &Direction::West),
+                };
+                (*coord, room_info)
+            })
+            .collect();
+        RoomGraph {
+            rooms,
+            water: HashSet::new(),
+            tiles: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, coord: &Coord) -> Option<&RoomMapInfo> {
+        self.rooms.get(coord)
+    }
+
+    pub fn contains(&self, coord: &Coord) -> bool {
+        self.rooms.contains_key(coord)
+    }
+
+    /// Whether a room is on land or water.
+    pub fn terrain(&self, coord: &Coord) -> Terrain {
+        match self.water.contains(coord) {
+            true => Terrain::Water,
+            false => Terrain::Land,
+        }
+    }
+
+    /// The character of the level's tile that a room is drawn with, if it is.
+    pub fn tile(&self, coord: &Coord) -> Option<char> {
+        self.tiles.get(coord).copied()
+    }
+
+    /// Every room on the map, in no particular order.
+    pub fn coords(&self) -> impl Iterator<Item = &Coord> {
+        self.rooms.keys()
+    }
+
+    /// The rooms that can be walked to from a room, and the direction of each.
+    pub fn neighbors(&self, coord: &Coord) -> impl Iterator<Item = (Direction, Coord)> + '_ {
+        let room_info = self.rooms.get(coord);
+        DIRECTIONS.iter().filter_map(move |direction| {
+            let next = (*room_info?.in_direction(direction))?;
+            Some((*direction, next))
+        })
+    }
+
+    /// Every room that can be walked to from a room, including itself.
+    pub fn reachable_from(&self, start: Coord) -> HashSet<Coord> {
+        search(start, None, |coord| {
+            self.neighbors(&coord)
+                .map(|(direction, next)| (next, direction))
+        })
+        .reached()
+        .copied()
+        .collect()
+    }
+
+    /// The directions to walk in to get from one room to another in the fewest steps.
+    pub fn shortest_path(&self, from: Coord, to: Coord) -> Option<Vec<Direction>> {
+        search(from, Some(to), |coord| {
+            self.neighbors(&coord)
+                .map(|(direction, next)| (next, direction))
+        })
+        .path_to(&to)
+    }
+}
+
+/// Search outwards from a node, breadth first, stopping once the goal is found. Each
+/// node is asked for the nodes it leads to, along with how to get there, like a
+/// direction or a command to type. This way a search can follow more than the map,
+/// like the exits and actions that move the player.
+pub fn search<N, L, I>(start: N, goal: Option<N>, mut next: impl FnMut(N) -> I) -> Search<N, L>
+where
+    N: Copy + Eq + Hash,
+    I: IntoIterator<Item = (N, L)>,
+{
+    let mut reached_from = HashMap::new();
+    let mut queue = VecDeque::new();
+    reached_from.insert(start, None);
+    queue.push_back(start);
+    while let Some(node) = queue.pop_front() {
+        if Some(node) == goal {
+            break;
+        }
+        for (next, how) in next(node) {
+            if let Entry::Vacant(entry) = reached_from.entry(next) {
+                entry.insert(Some((node, how)));
+                queue.push_back(next);
+            }
+        }
+    }
+    Search { reached_from }
+}
+
+/// The result of a `search`, remembering how each node was first reached.
+pub struct Search<N, L> {
+    reached_from: HashMap<N, Option<(N, L)>>,
+}
+
+impl<N: Copy + Eq + Hash, L: Clone> Search<N, L> {
+    /// The nodes that were reached, in no particular order.
+    pub fn reached(&self) -> impl Iterator<Item = &N> {
+        self.reached_from.keys()
+    }
+
+    /// How to get from the start to a node in the fewest steps, if it was reached.
+    pub fn path_to(&self, goal: &N) -> Option<Vec<L>> {
+        let mut path = Vec::new();
+        let mut node = self.reached_from.get(goal)?;
+        while let Some((previous, how)) = node {
+            path.push(how.clone());
+            node = &self.reached_from[previous];
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn test_room_graph() {
+        // A loop of rooms around a courtyard, with a room off on its own.
+        //   ....
+        //   .-..
+        //   ....  .
+        let cells: HashSet<Coord> = [
+            (0, 0),
+            (1, 0),
+            (2, 0),
+            (3, 0),
+            (0, 1),
+            (2, 1),
+            (3, 1),
+            (0, 2),
+            (1, 2),
+            (2, 2),
+            (3, 2),
+            (6, 2),
+        ]
+        .iter()
+        .map(|&(x, y)| Coord { x, y, z: 0 })
+        .collect();
+        let graph = RoomGraph::from_cells(&cells);
+        let coord = |x, y| Coord { x, y, z: 0 };
+
+        let neighbors: Vec<_> = graph.neighbors(&coord(0, 1)).collect();
+        assert_eq!(
+            neighbors,
+            vec![
+                (Direction::North, coord(0, 0)),
+                (Direction::South, coord(0, 2))
+            ]
+        );
+        assert_eq!(
+            graph.shortest_path(coord(0, 1), coord(2, 1)),
+            Some(vec![
+                Direction::North,
+                Direction::East,
+                Direction::East,
+                Direction::South
+            ])
+        );
+        assert_eq!(graph.shortest_path(coord(1, 0), coord(1, 0)), Some(vec![]));
+        assert_eq!(graph.shortest_path(coord(0, 0), coord(6, 2)), None);
+        assert_eq!(graph.reachable_from(coord(0, 0)).len(), 11);
+        assert_eq!(
+            graph.reachable_from(coord(6, 2)),
+            [coord(6, 2)].iter().copied().collect()
+        );
+    }
+
+    #[test]
+    fn test_region_map() {
+        let level = r#"
+maps: [["------", "-....-", "-..-.-", "------ The docks are at the bottom right."]]
+entry: [1, 1, 0]
+npcs: {}
+regions:
+  market: { actions: [] }
+  docks: { actions: [] }
+rooms:
+  - { title: Stall, coord: [1, 1, 0], description: A stall., regions: [market] }
+  - { title: Square, coord: [2, 1, 0], description: A square., regions: [market] }
+  - { title: Quay, coord: [3, 1, 0], description: A quay., regions: [market, docks] }
+  - { title: Pier, coord: [4, 1, 0], description: A pier., regions: [docks] }
+  - { title: Alley, coord: [1, 2, 0], description: An alley. }
+  - { title: Yard, coord: [2, 2, 0], description: A yard., regions: [yard] }
+  - { title: Boat, coord: [4, 2, 0], description: A boat., regions: [docks] }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        game.output().output.clear();
+        assert!(game.step("map".into()).is_none());
+        assert!(game.step("debug".into()).is_none());
+        assert!(game.step("map".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - "The region map is only shown in debug mode, type \"debug\" to turn it on."
+        - Debug mode activated.
+        - "------"
+        - "-@B+A-"
+        - "-.C-A-"
+        - "------"
+        - ""
+        - "  A  docks"
+        - "  B  market"
+        - "  C  yard (not a region of the level)"
+        - "  +  several regions"
+        - "  .  no region"
+        - "  @  you are here"
+        - ""
+        "###);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::style::Style;
+
+/// What starts each item of a list.
+const BULLET: &str = "‣ ";
+
+/// Some text, and how it's styled, if at all.
+type Span = (Option<Style>, String);
+
+/// Render a room's description, wrapped to the width with each line indented. It's
+/// written in a small markup: `*emphasis*` and `[item]` highlights are styled by
+/// `style`, which can leave them plain, lines starting with `- ` are items of a list,
+/// and a line ending in `\` keeps its line break, where otherwise lines run together.
+/// Every word is followed by a space, as paragraphs always have been.
+pub fn render(
+    text: &str,
+    width: usize,
+    indent: usize,
+    style: impl Fn(Style, &str) -> String,
+) -> String {
+    let mut rendered = String::new();
+    for paragraph in text.split("\n\n") {
+        let lines: Vec<String> = blocks(paragraph)
+            .iter()
+            .flat_map(|(bullet, block)| wrap_block(block, *bullet, width, indent, &style))
+            .collect();
+        rendered.push_str(&lines.join("\n"));
+        rendered.push_str("\n\n");
+    }
+    rendered
+}
+
+/// Split a paragraph into the parts that start on a line of their own: the items of a
+/// list, and the lines after a `\`. Each is kept with whether it's a list item.
+fn blocks(paragraph: &str) -> Vec<(bool, String)> {
+    let mut blocks: Vec<(bool, String)> = Vec::new();
+    let mut broken = false;
+    for line in paragraph.split('\n') {
+        let line = line.trim();
+        let (bullet, line) = match line.strip_prefix("- ") {
+            Some(item) => (true, item),
+            None => (false, line),
+        };
+        let (line, breaks) = match line.strip_suffix('\\') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        match blocks.last_mut() {
+            Some((_, block)) if !bullet && !broken => {
+                block.push(' ');
+                block.push_str(line);
+            }
+            _ => blocks.push((bullet, line.to_string())),
+        }
+        broken = breaks;
+    }
+    blocks
+}
+
+/// Split some text into plain, emphasized, and item spans. A marker without a partner
+/// is left as it is.
+fn spans(text: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut plain = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(['*', '[']) {
+        let (close, style) = match rest[start..].starts_with('*') {
+            true => ('*', Style::Emphasis),
+            false => (']', Style::Item),
+        };
+        let after = &rest[start + 1..];
+        match after.find(close) {
+            Some(end) if end > 0 => {
+                plain.push_str(&rest[..start]);
+                spans.push((None, std::mem::take(&mut plain)));
+                spans.push((Some(style), after[..end].to_string()));
+                rest = &after[end + 1..];
+            }
+            _ => {
+                plain.push_str(&rest[..=start]);
+                rest = after;
+            }
+        }
+    }
+    plain.push_str(rest);
+    spans.push((None, plain));
+    spans
+}
+
+/// Split some text into words, styling each part of them, along with how many
+/// characters wide they are on screen.
+fn words(text: &str, style: &impl Fn(Style, &str) -> String) -> Vec<(String, usize)> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut length = 0;
+    for (span_style, span) in spans(text) {
+        for (index, piece) in span.split(char::is_whitespace).enumerate() {
+            if index > 0 && length > 0 {
+                words.push((std::mem::take(&mut word), length));
+                length = 0;
+            }
+            if piece.is_empty() {
+                continue;
+            }
+            match span_style {
+                Some(span_style) => word.push_str(&style(span_style, piece)),
+                None => word.push_str(piece),
+            }
+            length += piece.chars().count();
+        }
+    }
+    if length > 0 {
+        words.push((word, length));
+    }
+    words
+}
+
+/// Wrap a block into lines, with a bullet for the items of a list, and a hanging
+/// indent under it.
+fn wrap_block(
+    block: &str,
+    bullet: bool,
+    width: usize,
+    indent: usize,
+    style: &impl Fn(Style, &str) -> String,
+) -> Vec<String> {
+    let indent = " ".repeat(indent);
+    let (first, hanging) = match bullet {
+        true => (
+            format!("{}{}", indent, BULLET),
+            format!("{}{}", indent, " ".repeat(BULLET.chars().count())),
+        ),
+        false => (indent.clone(), indent),
+    };
+    let mut lines = Vec::new();
+    let mut length = first.chars().count();
+    let mut line = first;
+    let mut is_empty = true;
+    for (word, word_length) in words(block, style) {
+        if !is_empty && length + word_length > width {
+            lines.push(std::mem::replace(&mut line, hanging.clone()));
+            length = hanging.len();
+        }
+        line.push_str(&word);
+        line.push(' ');
+        length += word_length + 1;
+        is_empty = false;
+    }
+    lines.push(line);
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn plain(_: Style, text: &str) -> String {
+        text.to_string()
+    }
+
+    fn tagged(style: Style, text: &str) -> String {
+        format!("<{:?}:{}>", style, text)
+    }
+
+    #[test]
+    fn test_render() {
+        let text = "A *very old* shop.\nA [brass lamp] hangs\nhere.\n\nShelves hold:\n- \
+                    jars\n- a *dusty* map of the\n  harbor\n\nLine one,\\\nline two. 3 * 4 [x";
+        let lines: Vec<_> = render(text, 24, 2, plain)
+            .lines()
+            .map(String::from)
+            .collect();
+        insta::assert_yaml_snapshot!(lines, @r###"
+        ---
+        - "  A very old shop. A "
+        - "  brass lamp hangs here. "
+        - ""
+        - "  Shelves hold: "
+        - "  ‣ jars "
+        - "  ‣ a dusty map of the "
+        - "    harbor "
+        - ""
+        - "  Line one, "
+        - "  line two. 3 * 4 [x "
+        - ""
+        "###);
+        let rendered = render(text, 80, 0, tagged);
+        insta::assert_yaml_snapshot!(rendered.lines().next(), @r###"
+        ---
+        "A <Emphasis:very> <Emphasis:old> shop. A <Item:brass> <Item:lamp> hangs here. "
+        "###);
+    }
+}
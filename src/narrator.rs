@@ -0,0 +1,748 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The narrator is the voice of the engine's built-in responses, such as failure
+/// messages. Levels can pick how snarky the narrator is, and override any of the
+/// messages by id so that the engine's jokes match the tone of their game. Players
+/// can pick another language, with its messages from data/lang.
+///
+/// narrator:
+///   snark: Polite
+///   messages:
+///     pick-nothing: You reach for nothing in particular.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Narrator {
+    #[serde(default)]
+    pub snark: Snark,
+    #[serde(default)]
+    pub messages: HashMap<String, String>,
+    /// The messages in the player's language, which take the place of the level's
+    /// messages, as those are in the level's language.
+    #[serde(skip)]
+    pub translated: HashMap<String, String>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Snark {
+    Polite,
+    #[default]
+    Snarky,
+}
+
+/// The built-in messages as (id, polite, snarky). Values in braces like {target} are
+/// filled in when the message is said.
+const MESSAGES: &[(&str, &str, &str)] = &[
+    (
+        "pick-nothing",
+        "Pick up what?",
+        "You pick your nose. Gross.",
+    ),
+    (
+        "take-nothing",
+        "Take what?",
+        "This relationship is on the rocks, all you do is take take take.",
+    ),
+    ("drop-nothing", "Drop what?", "You stop drop and roll."),
+    (
+        "eat-nothing",
+        "Eat what?",
+        "You chew on nothing for a while. It isn't very filling.",
+    ),
+    (
+        "ask-nothing",
+        "Ask about what?",
+        "You ask about nothing, and get nothing in return.",
+    ),
+    (
+        "combine-nothing",
+        "Combine what?",
+        "You rub your empty hands together.",
+    ),
+    (
+        "combine-with",
+        "Combine it with what?",
+        "It takes two to make a recipe.",
+    ),
+    (
+        "craft-nothing",
+        "Craft what?",
+        "You feel crafty, but have nothing in mind.",
+    ),
+    (
+        "buy-nothing",
+        "Buy what?",
+        "You jingle your coins, but there's nothing in mind to buy.",
+    ),
+    (
+        "offer-nothing",
+        "Offer how much for what? Like: offer 5 for apple",
+        "Haggling goes better with a price and an item, like: offer 5 for apple",
+    ),
+    (
+        "offer-amount",
+        "\"{amount}\" isn't an amount of money.",
+        "Nobody takes \"{amount}\" as payment.",
+    ),
+    (
+        "read-nothing",
+        "Read what?",
+        "You read between the lines of nothing at all.",
+    ),
+    (
+        "shift-nothing",
+        "Push, pull, or move what?",
+        "You shove the air around. It doesn't mind.",
+    ),
+    (
+        "deposit-nothing",
+        "Deposit what?",
+        "You slide an empty hand across the counter.",
+    ),
+    (
+        "withdraw-nothing",
+        "Withdraw what?",
+        "You ask for your things back, whatever they were.",
+    ),
+    (
+        "give-nothing",
+        "Give what?",
+        "You hold out your empty hands.",
+    ),
+    (
+        "throw-nothing",
+        "Throw what?",
+        "You wind up, and throw nothing at all.",
+    ),
+    (
+        "throw-at",
+        "Throw it at what?",
+        "You could throw it, but you'd rather hit something.",
+    ),
+    (
+        "pet-nothing",
+        "Pet what?",
+        "You pat the air. It purrs, probably.",
+    ),
+    (
+        "feed-nothing",
+        "Feed what to whom? Like: feed bone to dog",
+        "You hold out a snack for nobody in particular. Try: feed bone to dog",
+    ),
+    (
+        "send-nothing",
+        "Send whom to fetch what? Like: send dog to fetch stick",
+        "Good help is hard to find. Try: send dog to fetch stick",
+    ),
+    (
+        "oops-nothing",
+        "Oops what? Type the word you meant, like: oops lantern",
+        "Oops indeed. But which word did you mean?",
+    ),
+    (
+        "again-nothing",
+        "There's nothing to do again yet.",
+        "Again? You haven't done anything the first time.",
+    ),
+    (
+        "again-unsafe",
+        "That isn't something you can do again.",
+        "Once was enough.",
+    ),
+    (
+        "give-whom",
+        "Give it to whom?",
+        "Generosity is nice, but it needs someone to receive it.",
+    ),
+    (
+        "say-nothing",
+        "Say what?",
+        "You open your mouth, and think better of it.",
+    ),
+    (
+        "disarm-nothing",
+        "Disarm what?",
+        "You bravely disarm the empty air.",
+    ),
+    (
+        "board-nothing",
+        "Board what?",
+        "You climb aboard nothing, and go nowhere.",
+    ),
+    (
+        "water-blocked",
+        "The water is too deep to wade through.",
+        "You'd sink like a stone. Maybe find a boat.",
+    ),
+    (
+        "talk-nothing",
+        "Who do you want to talk to?",
+        "You talk outloud for a bit and feel much better, thank you.",
+    ),
+    (
+        "travel-unknown",
+        "You haven't been to {target} yet.",
+        "You've never heard of {target}, let alone been there.",
+    ),
+    (
+        "go-nowhere",
+        "Where do you want to go?",
+        "Where do you want to go?",
+    ),
+    (
+        "cannot-move",
+        "You cannot move {direction}.",
+        "You cannot move {direction}.",
+    ),
+    (
+        "look-missing",
+        "You don't see a {target}.",
+        "You don't see a {target}.",
+    ),
+    (
+        "talk-missing",
+        "You can't talk to {target}.",
+        "You can't talk to {target}.",
+    ),
+    (
+        "take-missing",
+        "You couldn't find a {target} to take.",
+        "You couldn't find a {target} to take.",
+    ),
+    (
+        "drop-missing",
+        "It does not look like you have a {target}.",
+        "It does not look like you have a {target}.",
+    ),
+    (
+        "compare-missing",
+        "You aren't carrying a {target} to compare.",
+        "You pat your pockets, but you aren't carrying a {target} to compare.",
+    ),
+    (
+        "drop-sticky",
+        "You can't drop the {target}.",
+        "The {target} appear(s) to be sticking to your hand.",
+    ),
+    (
+        "help-missing",
+        "You can't help {target}.",
+        "You can't help {target}.",
+    ),
+    (
+        "unknown-verb",
+        "You don't know how to \"{verb}\". Type \"help\" for help.",
+        "You don't know how to \"{verb}\". Type \"help\" for help.",
+    ),
+    (
+        "cannot-do",
+        "You can't {verb} the {target}.",
+        "You can't {verb} the {target}.",
+    ),
+    (
+        "emote-npc",
+        "You {verb} at the {target}.",
+        "You {verb} at the {target}. They don't seem to notice.",
+    ),
+    (
+        "emote-missing",
+        "You don't see a {target} to {verb} at.",
+        "You don't see a {target} to {verb} at.",
+    ),
+    (
+        "death-health",
+        "Your wounds are too much, and you fall.",
+        "You've run out of health, which is the bad kind of running out.",
+    ),
+    (
+        "checkpoint",
+        "This feels like a safe place to return to.",
+        "You make a mental note of this place, in case things go badly.",
+    ),
+    (
+        "respawn",
+        "You wake up somewhere familiar.",
+        "You wake up, none the wiser.",
+    ),
+    (
+        "undo",
+        "You take back your last move.",
+        "Time rewinds. Let's pretend that never happened.",
+    ),
+    (
+        "undo-nothing",
+        "There is nothing to undo.",
+        "You can't undo what hasn't been done.",
+    ),
+    (
+        "smile-unnoticed",
+        "You smile.",
+        "You smile at no one in particular. It feels a little forced.",
+    ),
+    (
+        "bow-unnoticed",
+        "You bow.",
+        "You bow deeply. Nobody applauds.",
+    ),
+    (
+        "wave-unnoticed",
+        "You wave.",
+        "You wave at nobody. Nobody waves back.",
+    ),
+    (
+        "dance-unnoticed",
+        "You dance.",
+        "You break out into a little jig. Passersby pretend not to see.",
+    ),
+];
+
+/// The built-in messages that read the same however snarky the narrator is, as
+/// (id, message).
+const PLAIN_MESSAGES: &[(&str, &str)] = &[
+    ("what", "What was that?"),
+    ("yes-no", "{question} (yes, no)"),
+    ("irreversible-cancel", "You think better of it."),
+    ("oops-missing", "There's nothing to correct."),
+    ("repeat-unsafe", "You can't repeat \"{command}\"."),
+    (
+        "repeat-too-many",
+        "You can only repeat a command up to {count} times.",
+    ),
+    (
+        "race-start",
+        "The race is on, with {count} checkpoints to reach.",
+    ),
+    (
+        "race-new-game",
+        "Races are run from a new game, restart to race.",
+    ),
+    ("race-split", "{name}: {time}"),
+    ("race-split-best", "{name}: {time} ({delta})"),
+    ("race-record", "Finished in {time}, a new best!"),
+    ("race-finish", "Finished in {time}, your best is {best}."),
+    ("command-what", "{command}... what?"),
+    ("go-unknown", "You don't know how to go \"{direction}\""),
+    (
+        "feedback-empty",
+        "Type your feedback after the command: feedback <text>",
+    ),
+    (
+        "feedback-unavailable",
+        "Feedback can't be left in this game.",
+    ),
+    (
+        "feedback-too-long",
+        "Feedback can be at most {max} characters.",
+    ),
+    ("feedback-saved", "Thanks, your feedback was saved."),
+    (
+        "feedback-unsaved",
+        "Unable to save the feedback to {path}: {error}",
+    ),
+    (
+        "config-empty",
+        "Type the new value after the setting: config {key} <value>",
+    ),
+    ("config-usage", "Change a setting with: config width 60"),
+    ("config-invalid", "That setting can't be changed: {error}."),
+    ("config-changed", "Changed the {key} setting."),
+    ("config-unsaved", "Unable to save {path}: {error}"),
+    (
+        "mute-on",
+        "The sound is off. Type mute again to turn it back on.",
+    ),
+    ("mute-off", "The sound is back on."),
+    ("exits", "Exits: {exits}"),
+    ("exits-unknown", "somewhere you haven't been"),
+    ("where", "Room: {room}"),
+    ("where-regions", "Regions: {regions}"),
+    ("region-enter", "You enter the {region}."),
+    ("weather-hides-exits", "The {weather} hides the way on."),
+    ("weather-blocked", "You can't head out into the {weather}."),
+    ("npc-here", "{name} is here."),
+    ("room-dim", "It's too dim to make out much more."),
+    ("npc-arrives", "{name} arrives from the {direction}."),
+    ("npc-leaves", "{name} leaves to the {direction}."),
+    ("npc-follows", "{name} follows you."),
+    ("party-join", "{name} joins you."),
+    ("party-leave", "{name} leaves your party."),
+    ("pet-refused", "{name} doesn't want to be petted."),
+    ("pet-petted", "{name} leans into your hand."),
+    ("pet-wild", "{name} shies away from your hand."),
+    (
+        "pet-tamed",
+        "{name} eats {item}, and decides to follow you.",
+    ),
+    ("feed", "{name} eats {item}."),
+    ("feed-refused", "{name} won't eat {item}."),
+    ("fetch-wild", "{name} doesn't listen to you yet."),
+    ("fetch-here", "The {target} is right here."),
+    (
+        "fetch-none",
+        "{name} sniffs around, but can't find any {target}.",
+    ),
+    ("fetch-sent", "{name} bounds off to the {direction}."),
+    (
+        "fetch-returns",
+        "{name} comes back, and drops {item} at your feet.",
+    ),
+    ("fetch-empty", "{name} comes back empty handed."),
+    ("status-stumble", "You stumble off to the {direction}."),
+    ("npc-hostile", "{name} turns away from you."),
+    ("npc-wont-trade", "{name} won't trade with you."),
+    ("reputation-rises", "Your standing with {faction} rises."),
+    ("reputation-falls", "Your standing with {faction} falls."),
+    ("take", "You place {item} in your inventory."),
+    ("drop", "You dropped {item}."),
+    (
+        "search-dark",
+        "It's too dim to search here without a light.",
+    ),
+    ("search-item", "You find {item}."),
+    ("search-exit", "You find a hidden way {direction}."),
+    (
+        "search-nothing",
+        "You search around, but don't find anything.",
+    ),
+    (
+        "search-nothing-in",
+        "You search the {target}, but don't find anything.",
+    ),
+    (
+        "search-hint",
+        "You don't find anything, but the {target} might be worth a closer look.",
+    ),
+    ("shift-moved", "You've already moved the {target}."),
+    ("shift-stuck", "The {target} won't budge."),
+    ("read-missing", "You aren't carrying a {target} to read."),
+    ("read-blank", "There's nothing to read on {item}."),
+    (
+        "lore-learned",
+        "You learned about {title}. Look it up any time with \"lore\".",
+    ),
+    ("lore-list", "You know about: {entries}."),
+    ("lore-none", "You haven't learned any lore yet."),
+    ("lore-unknown", "You don't know anything about {topic}."),
+    ("bank-none", "There's nowhere to keep your things here."),
+    ("deposit", "You deposit {item}."),
+    ("deposit-missing", "You aren't carrying a {target}."),
+    (
+        "deposit-all-none",
+        "You aren't carrying anything like that.",
+    ),
+    ("withdraw", "You withdraw {item}."),
+    ("withdraw-missing", "There's no {target} in your stash."),
+    (
+        "withdraw-all-none",
+        "There's nothing like that in your stash.",
+    ),
+    ("stash", "In your stash: {items}."),
+    ("stash-empty", "Your stash is empty."),
+    ("take-all-none", "There's nothing like that to take."),
+    ("combine-missing", "You aren't carrying a {target}."),
+    ("combine-fails", "Those don't go together."),
+    ("craft", "You make {item}."),
+    ("craft-unknown", "You don't know how to make a {target}."),
+    ("craft-missing", "You need {ingredients} to make {item}."),
+    ("craft-ingredient", "{count} more {item}"),
+    (
+        "craft-broken",
+        "The recipe makes \"{item}\", which isn't an item.",
+    ),
+    (
+        "drop-all-none",
+        "You aren't carrying anything like that to drop.",
+    ),
+    ("give", "You give {item} to {name}."),
+    ("give-refused", "{name} doesn't want {item}."),
+    (
+        "throw-dodge",
+        "{name} dodges {item}, which lands at your feet.",
+    ),
+    (
+        "throw-bounce",
+        "{item} bounces off the {target}, and lands at your feet.",
+    ),
+    (
+        "throw-missing",
+        "There's no {target} here to throw {item} at.",
+    ),
+    (
+        "give-all-none",
+        "You aren't carrying anything like that to give.",
+    ),
+    ("companion-carry", "{name} carries {item} for you."),
+    ("companion-full", "{name} can't carry {item} as well."),
+    ("companion-return", "{name} hands you {item}."),
+    ("companion-empty", "{name} isn't carrying anything for you."),
+    (
+        "companion-missing",
+        "{name} isn't carrying a {target} for you.",
+    ),
+    ("inventory-companion", "{name} is carrying: {items}"),
+    ("eat", "You finish {item}."),
+    ("eat-missing", "You aren't carrying a {target}."),
+    (
+        "eat-inedible",
+        "{item} isn't something you can eat or drink.",
+    ),
+    ("say", "You say \"{words}\"."),
+    ("ask-nobody", "Who do you want to ask?"),
+    ("ask-unknown", "{name} doesn't know anything about {topic}."),
+    ("conversation-topics", "You can ask about:"),
+    (
+        "conversation-bye",
+        "Or type \"bye\" to end the conversation.",
+    ),
+    ("conversation-end", "You say goodbye to {name}."),
+    ("bye-nobody", "There's no one to say goodbye to."),
+    ("say-wrong", "Nothing happens."),
+    ("disarm", "You disarm the {target}."),
+    ("disarm-missing", "There's no {target} to disarm here."),
+    ("board", "You get aboard {name}."),
+    ("board-missing", "There's no {target} here to board."),
+    ("board-already", "You're already aboard {name}."),
+    ("dismount", "You get off {name}."),
+    ("dismount-nothing", "You aren't aboard anything."),
+    (
+        "dismount-water",
+        "There's nowhere to get off {name} out here.",
+    ),
+    ("vehicle-blocked", "You can't take {name} that way."),
+    ("tile-blocked", "You can't go that way."),
+    ("article-a", "a {name}"),
+    ("article-an", "an {name}"),
+    ("article-the", "the {name}"),
+    ("item-count", "{count} {name}"),
+    ("vehicle-here", "You see {name} here."),
+    ("vehicle-aboard", "You're aboard {name}."),
+    ("item-spawns", "You notice {item} here."),
+    ("disarm-impossible", "The {target} can't be disarmed."),
+    (
+        "disarm-cannot",
+        "You don't have what you need to disarm the {target}.",
+    ),
+    (
+        "disarm-failed",
+        "You fumble with the {target}, and set it off!",
+    ),
+    ("debug-on", "Debug mode activated."),
+    ("debug-off", "Debug mode de-activated."),
+    (
+        "map-debug",
+        "The region map is only shown in debug mode, type \"debug\" to turn it on.",
+    ),
+    ("reload", "Reloaded the level."),
+    ("jam-timer", "⏱ {time} into the jam"),
+    ("jam-valid", "The level has no problems."),
+    ("jam-problems", "The level has some problems:"),
+    ("goto-nothing", "Which room do you want to go to?"),
+    (
+        "goto-debug",
+        "Jumping to a room only works in debug mode, type \"debug\" to turn it on.",
+    ),
+    ("goto-missing", "There's no room {target} on the map."),
+    ("travel-here", "You're already here."),
+    (
+        "tutorial-learned",
+        "Well done! Head east when you're ready.",
+    ),
+    (
+        "travel-no-way",
+        "You don't know a way to {target} from here.",
+    ),
+    ("travel-interrupted", "You stop on the way."),
+    (
+        "restart-confirm",
+        "Are you sure you want to erase your game and restart?",
+    ),
+    ("restart-cancel", "Let's keep playing!"),
+    ("died", "You have died"),
+    ("died-choices", "What now? ({choices})"),
+    ("achievement", "Achievement unlocked: {name}"),
+    ("scenario-choose", "Choose how to begin:"),
+    ("scenario-default", "You begin as {name}."),
+    ("scenario-missing", "There is no scenario \"{id}\"."),
+    ("inventory", "Your inventory:"),
+    ("inventory-empty", "(empty)"),
+    ("journal", "Your journal:"),
+    ("journal-turn", "{quest} (1 turn left)"),
+    ("journal-turns", "{quest} ({turns} turns left)"),
+    ("journal-overdue", "{quest} (overdue)"),
+    ("journal-done", "{quest} (done)"),
+    ("journal-late", "{quest} (done late)"),
+    ("journal-failed", "{quest} (failed)"),
+    ("quest-started", "New quest: {quest}."),
+    ("quest-done", "Quest complete: {quest}."),
+    ("quest-late", "Quest complete, though late: {quest}."),
+    (
+        "quest-overdue",
+        "You're out of time for {quest}, but it may not be too late.",
+    ),
+    ("quest-failed", "Quest failed: {quest}."),
+    (
+        "quest-left-behind",
+        "{name} was left behind. Quest failed: {quest}.",
+    ),
+    ("quest-harmed", "{name} was hurt. Quest failed: {quest}."),
+    ("money", "You have {money}."),
+    ("money-none", "You don't have any money."),
+    ("buy", "You buy the {item} from {name} for {cost}."),
+    ("buy-change", "You hand over {paid}, and get {change} back."),
+    ("buy-missing", "Nobody here is selling a {target}."),
+    (
+        "buy-afford",
+        "The {item} costs {cost}, but you only have {money}.",
+    ),
+    ("haggle-accept", "{name} accepts your offer."),
+    (
+        "haggle-counter",
+        "{name} shakes their head, and asks {cost} for the {item}.",
+    ),
+    (
+        "haggle-insulted",
+        "{name} is insulted by your offer, and won't trade with you for a while.",
+    ),
+    ("haggle-refusing", "{name} won't trade with you for now."),
+    ("inventory-page", "Page {page} of {pages}."),
+    (
+        "inventory-page-more",
+        "Page {page} of {pages}, type \"inventory {next}\" for more.",
+    ),
+    ("inventory-pages", "Your inventory only has {pages} pages."),
+    (
+        "inventory-usage",
+        "Use \"{command} <page>\" or \"{command} find <word>\" to look through your items.",
+    ),
+    ("inventory-find", "Your items matching \"{word}\":"),
+    (
+        "inventory-find-none",
+        "Nothing you carry matches \"{word}\".",
+    ),
+    ("price", "{item} ({cost})"),
+    ("compare", "{first} vs. {second}"),
+    ("compare-kind", "Kind"),
+    ("compare-damage", "Damage"),
+    ("compare-weight", "Weight"),
+    ("compare-value", "Value"),
+    ("compare-durability", "Durability"),
+    ("summary", "Summary"),
+    ("summary-turns", "Turns taken:"),
+    ("summary-par", "Par:"),
+    ("par-under", "{turns} under par!"),
+    ("par-equal", "Right on par."),
+    ("par-over", "{turns} over par."),
+    ("score", "Score"),
+    ("score-points", "Points: {points}"),
+    ("score-achievements", "Achievements: {unlocked} of {total}"),
+    ("filter-nothing", "This level has no content to filter."),
+    ("filter", "Content filter:"),
+    ("filter-shown", "shown"),
+    ("filter-filtered", "filtered"),
+    ("filter-usage", "Use \"filter <tag>\" to toggle a tag."),
+    ("filter-all", "All flagged content is now filtered."),
+    ("filter-none", "All content is now shown."),
+    ("filter-tag-shown", "Content tagged \"{tag}\" is now shown."),
+    (
+        "filter-tag-filtered",
+        "Content tagged \"{tag}\" is now filtered.",
+    ),
+    ("filter-missing", "There is no content tagged \"{tag}\"."),
+    (
+        "lang-unavailable",
+        "Unable to play in {lang}, so the game is in its own language. {error}",
+    ),
+    (
+        "lang-level-unavailable",
+        "Unable to translate the level, so it is in its own language. {error}",
+    ),
+    (
+        "save-changed",
+        "The level has changed since this game was saved:",
+    ),
+    (
+        "save-level-gone",
+        "The level {level} is no longer in the campaign, so you are back at the start.",
+    ),
+    (
+        "save-room-gone",
+        "The room you were in is gone, so you are back at the start.",
+    ),
+    ("save-item-gone", "Your {item} is no longer in the game."),
+    (
+        "save-room-removed",
+        "The room at {coord} is no longer in the level.",
+    ),
+    (
+        "save-room-item-gone",
+        "The {item} in {room} is no longer in the game.",
+    ),
+];
+
+impl Narrator {
+    /// Look up a message by its id, preferring the player's language, then the level's
+    /// override.
+    pub fn say(&self, id: &str) -> String {
+        self.say_with(id, &[])
+    }
+
+    /// Look up a message by its id, and fill in its values.
+    pub fn say_with(&self, id: &str, values: &[(&str, &str)]) -> String {
+        let overridden = self.translated.get(id).or_else(|| self.messages.get(id));
+        let mut message = match overridden {
+            Some(message) => message.trim_end().to_string(),
+            None => builtin_message(id, self.snark)
+                .unwrap_or_else(|| panic!("Unable to find the narrator message {:?}", id))
+                .to_string(),
+        };
+        for (key, value) in values {
+            message = message.replace(&format!("{{{}}}", key), value);
+        }
+        message
+    }
+}
+
+fn builtin_message(id: &str, snark: Snark) -> Option<&'static str> {
+    if let Some((_, polite, snarky)) = MESSAGES.iter().find(|(message_id, _, _)| *message_id == id)
+    {
+        return Some(match snark {
+            Snark::Polite => polite,
+            Snark::Snarky => snarky,
+        });
+    }
+    PLAIN_MESSAGES
+        .iter()
+        .find(|(message_id, _)| *message_id == id)
+        .map(|(_, message)| *message)
+}
+
+/// Whether the engine has a message with this id, for checking translations.
+#[cfg(test)]
+pub fn is_message(id: &str) -> bool {
+    builtin_message(id, Snark::Polite).is_some()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_narrator() {
+        let mut narrator = Narrator::default();
+        assert_eq!(narrator.say("pick-nothing"), "You pick your nose. Gross.");
+        assert_eq!(
+            narrator.say_with("take-missing", &[("target", "lantern")]),
+            "You couldn't find a lantern to take."
+        );
+
+        narrator.snark = Snark::Polite;
+        assert_eq!(narrator.say("pick-nothing"), "Pick up what?");
+
+        narrator
+            .messages
+            .insert("pick-nothing".into(), "Not now, {name}.\n".into());
+        assert_eq!(
+            narrator.say_with("pick-nothing", &[("name", "Raven")]),
+            "Not now, Raven."
+        );
+    }
+}
@@ -0,0 +1,470 @@
+use crate::{
+    level::{Coord, InventoryItem, ItemDatabase},
+    utils::try_parse_yml_str,
+};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::{borrow::Cow, collections::BTreeSet, fs};
+
+/// The lists that a pack adds to the front of, rather than replacing, so that its
+/// entries are used first, and the level's are still there for everything else.
+const PREPENDED: [&str; 4] = ["actions", "topics", "gifts", "throws"];
+
+/// A content pack, like a community mod, which adds to or changes a level as it loads,
+/// without editing the level's files. Packs are played with `--pack <path>`, and are
+/// patched in the order they are given, so later packs take precedence over earlier
+/// ones, and every pack takes precedence over the level.
+///
+/// The rest of the pack is written like part of a level:
+///
+///  - Sections that are maps, like `npcs` or `quests`, are patched key by key, so a pack
+///    can change an NPC's `talk` and leave the rest of them be, or add a new NPC.
+///  - A room with the same `coord`, or `id`, as one of the level's is patched the same
+///    way, and any other room is added.
+///  - The `actions`, `topics`, `gifts`, and `throws` of the pack go before the level's,
+///    so that they're used first.
+///  - Anything else, like a description or a list of exits, is replaced.
+///
+/// Items are patched by their `id` in the same way, or added.
+///
+/// Packs that build on others list them in `requires`, and have to be given after them.
+/// When two packs change the same thing, the later one still wins, but it's reported at
+/// startup and by the validator, see `problems`.
+///
+/// ```yml
+/// name: Haunted Docks
+/// level: stone-end-market
+/// requires: [Night Market]
+/// npcs:
+///   guard: { talk: "\"Did you hear that? Out on the water?\"" }
+/// rooms:
+///   - coord: [12, 18, 0]
+///     description: Fog rolls in over the silent docks.
+///     actions:
+///       - { verb: Look, targets: [fog], value: Shapes move in the fog. }
+/// items:
+///   - { id: lantern, description: A lantern that never goes out. }
+/// ```
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct Pack {
+    pub name: String,
+    /// The id of the campaign level to patch, or None to patch every level.
+    #[serde(default)]
+    pub level: Option<String>,
+    /// The names of the packs this one builds on.
+    #[serde(default)]
+    pub requires: Vec<String>,
+    #[serde(default)]
+    pub items: Vec<Mapping>,
+    #[serde(flatten)]
+    pub patch: Mapping,
+}
+
+impl Pack {
+    pub fn parse(text: &str, path: &str) -> Result<Pack, String> {
+        try_parse_yml_str(text, path)
+    }
+
+    /// Read a pack from a file.
+    pub fn load(path: &str) -> Result<Pack, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("Unable to read the pack {:?}: {}", path, err))?;
+        Pack::parse(&text, path)
+    }
+
+    fn patches(&self, level_id: &str) -> bool {
+        self.level.as_ref().is_none_or(|level| level == level_id)
+    }
+
+    /// Whether this pack and another can patch the same level.
+    fn overlaps(&self, other: &Pack) -> bool {
+        match (&self.level, &other.level) {
+            (Some(level), Some(other_level)) => level == other_level,
+            _ => true,
+        }
+    }
+
+    /// Everything the pack replaces, like `npcs.guard.talk` or `rooms[1, 1, 0].title`.
+    /// The lists that it adds to aren't included, as packs can add to them together.
+    fn fields(&self) -> BTreeSet<String> {
+        let mut fields = BTreeSet::new();
+        add_fields(&Value::Mapping(self.patch.clone()), "", &mut fields);
+        for item in self.items.iter() {
+            let mut item = item.clone();
+            if let Some(Value::String(id)) = item.remove(&Value::from("id")) {
+                add_fields(
+                    &Value::Mapping(item),
+                    &format!("items[{}]", id),
+                    &mut fields,
+                );
+            }
+        }
+        fields
+    }
+}
+
+/// Find the problems with a set of packs, in the order they're given: the packs they
+/// require that aren't loaded before them, and what more than one of them changes,
+/// where the later pack wins.
+pub fn problems(packs: &[Pack]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (index, pack) in packs.iter().enumerate() {
+        for required in pack.requires.iter() {
+            match packs.iter().position(|other| other.name == *required) {
+                Some(other_index) if other_index < index => {}
+                Some(_) => problems.push(format!(
+                    "The pack {:?} requires {:?}, which has to be loaded before it.",
+                    pack.name, required
+                )),
+                None => problems.push(format!(
+                    "The pack {:?} requires {:?}, which isn't loaded.",
+                    pack.name, required
+                )),
+            }
+        }
+    }
+    let fields: Vec<BTreeSet<String>> = packs.iter().map(Pack::fields).collect();
+    for (index, pack) in packs.iter().enumerate() {
+        for (other_index, other) in packs.iter().enumerate().skip(index + 1) {
+            if !pack.overlaps(other) {
+                continue;
+            }
+            for field in fields[index].intersection(&fields[other_index]) {
+                problems.push(format!(
+                    "The packs {:?} and {:?} both change {}, so {:?} wins.",
+                    pack.name, other.name, field, other.name
+                ));
+            }
+        }
+    }
+    problems
+}
+
+/// Patch the text of a level with the packs for it, in order. The text is left alone
+/// when no pack patches the level.
+pub fn patch_level<'a>(
+    text: &'a str,
+    path: &str,
+    level_id: &str,
+    packs: &[Pack],
+) -> Result<Cow<'a, str>, String> {
+    let mut packs = packs
+        .iter()
+        .filter(|pack| pack.patches(level_id))
+        .peekable();
+    if packs.peek().is_none() {
+        return Ok(Cow::Borrowed(text));
+    }
+    let mut level: Value = try_parse_yml_str(text, path)?;
+    for pack in packs {
+        merge(&mut level, &Value::Mapping(pack.patch.clone()), "");
+    }
+    serde_yaml::to_string(&level)
+        .map(Cow::Owned)
+        .map_err(|err| format!("Unable to patch {}: {}", path, err))
+}
+
+/// Patch the items with the packs, in order.
+pub fn patch_items(item_db: &ItemDatabase, packs: &[Pack]) -> Result<ItemDatabase, String> {
+    if packs.iter().all(|pack| pack.items.is_empty()) {
+        return Ok(item_db.clone());
+    }
+    let mut items: Vec<InventoryItem> = item_db.iter().cloned().collect();
+    for pack in packs {
+        for patch in pack.items.iter() {
+            let id = match patch.get(&Value::from("id")).and_then(Value::as_str) {
+                Some(id) => id,
+                None => return Err(format!("An item in the pack {:?} has no id.", pack.name)),
+            };
+            let error = |err: serde_yaml::Error| {
+                format!(
+                    "Unable to patch the item {:?} from {:?}: {}",
+                    id, pack.name, err
+                )
+            };
+            match items.iter_mut().find(|item| item.id == id) {
+                Some(item) => {
+                    let mut value = serde_yaml::to_value(&*item).map_err(error)?;
+                    merge(&mut value, &Value::Mapping(patch.clone()), "");
+                    *item = serde_yaml::from_value(value).map_err(error)?;
+                }
+                None => {
+                    let value = Value::Mapping(patch.clone());
+                    items.push(serde_yaml::from_value(value).map_err(error)?);
+                }
+            }
+        }
+    }
+    Ok(item_db.with_items(items))
+}
+
+/// Patch a value with a pack's, following the rules in `Pack`. The key is where the
+/// value is in its map, which decides how lists are patched.
+fn merge(base: &mut Value, patch: &Value, key: &str) {
+    if let (Value::Mapping(base), Value::Mapping(patch)) = (&mut *base, patch) {
+        for (key, value) in patch.iter() {
+            match base.get_mut(key) {
+                Some(existing) => merge(existing, value, key.as_str().unwrap_or("")),
+                None => {
+                    base.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        return;
+    }
+    if let (Value::Sequence(base), Value::Sequence(patch)) = (&mut *base, patch) {
+        if key == "rooms" {
+            for room in patch.iter() {
+                match base.iter_mut().find(|other| is_same_room(other, room)) {
+                    Some(other) => merge(other, room, ""),
+                    None => base.push(room.clone()),
+                }
+            }
+            return;
+        }
+        if PREPENDED.contains(&key) {
+            let existing = std::mem::take(base);
+            base.extend(patch.iter().cloned());
+            base.extend(existing);
+            return;
+        }
+    }
+    *base = patch.clone();
+}
+
+/// Add the paths to everything a patch replaces, following the rules in `Pack`.
+fn add_fields(patch: &Value, path: &str, fields: &mut BTreeSet<String>) {
+    let patch = match patch {
+        Value::Mapping(patch) => patch,
+        _ => {
+            fields.insert(path.to_string());
+            return;
+        }
+    };
+    for (key, value) in patch.iter() {
+        let key = match key.as_str() {
+            Some(key) => key,
+            None => continue,
+        };
+        let key_path = match path {
+            "" => key.to_string(),
+            _ => format!("{}.{}", path, key),
+        };
+        match value {
+            Value::Sequence(_) if PREPENDED.contains(&key) => {}
+            Value::Sequence(rooms) if key == "rooms" => {
+                for room in rooms.iter() {
+                    let coord = room
+                        .get("coord")
+                        .and_then(|coord| serde_yaml::from_value::<Coord>(coord.clone()).ok());
+                    let room_path = match (coord, room.get("id").and_then(Value::as_str)) {
+                        (Some(coord), _) => format!("{}{}", key_path, coord),
+                        (None, Some(id)) => format!("{}[{}]", key_path, id),
+                        (None, None) => continue,
+                    };
+                    // What the room is patched by isn't something the pack changes.
+                    let mut room = room.clone();
+                    if let Value::Mapping(ref mut room) = room {
+                        room.remove(&Value::from("coord"));
+                        room.remove(&Value::from("id"));
+                    }
+                    add_fields(&room, &room_path, fields);
+                }
+            }
+            _ => add_fields(value, &key_path, fields),
+        }
+    }
+}
+
+/// Whether a pack's room is one of the level's, by its coordinate or id.
+fn is_same_room(room: &Value, patch: &Value) -> bool {
+    let coord =
+        |room: &Value| -> Option<Coord> { serde_yaml::from_value(room.get("coord")?.clone()).ok() };
+    match (coord(patch), patch.get("id")) {
+        (Some(patch_coord), _) => coord(room) == Some(patch_coord),
+        (None, Some(id)) => room.get("id") == Some(id),
+        (None, None) => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::level::Level;
+    use crate::{testing::*, Game};
+
+    #[test]
+    fn test_packs() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+regions: {}
+npcs:
+  guard: { name: Guard, targets: [guard], talk: "\"Halt.\"", items: [] }
+rooms:
+  - title: Docks
+    coord: [1, 1, 0]
+    id: docks
+    description: The docks.
+    npcs: [guard]
+    actions:
+      - { verb: Look, targets: [sea], value: Waves. }
+      - { verb: Look, targets: [ship], value: A ship. }
+"#;
+        let packs = [
+            r#"
+name: Haunted Docks
+npcs:
+  guard: { talk: "\"Did you hear that?\"" }
+rooms:
+  - coord: [1, 1, 0]
+    description: Fog rolls in over the docks.
+    actions:
+      - { verb: Look, targets: [sea], value: Shapes move under the waves. }
+  - { title: Ghost Ship, coord: [2, 1, 0], description: A ghost ship. }
+items:
+  - { id: lantern, description: A lantern that never goes out. }
+  - { id: skull, name: skull, variant: Consumable, description: A grinning skull. }
+"#,
+            r#"
+name: Foggier Docks
+rooms:
+  - { id: docks, description: Thick fog rolls in over the docks. }
+"#,
+            r#"
+name: Sewers
+level: sewer
+rooms:
+  - { id: docks, description: A sewer. }
+"#,
+        ];
+        let packs: Vec<Pack> = packs
+            .iter()
+            .map(|text| Pack::parse(text, "pack.yml").unwrap())
+            .collect();
+
+        let text = patch_level(level, "level.yml", "market", &packs).unwrap();
+        let level: Level = serde_yaml::from_str(&text).unwrap();
+        let docks = level.get_room(&Coord { x: 1, y: 1, z: 0 }).unwrap();
+        assert_eq!(docks.title, "Docks");
+        assert_eq!(
+            docks.description.texts(),
+            vec!["Thick fog rolls in over the docks."]
+        );
+        let looks: Vec<_> = docks
+            .actions
+            .iter()
+            .flatten()
+            .map(|action| (action.targets[0].as_str(), action.value.texts()[0]))
+            .collect();
+        assert_eq!(
+            looks,
+            vec![
+                ("sea", "Shapes move under the waves."),
+                ("sea", "Waves."),
+                ("ship", "A ship."),
+            ]
+        );
+        assert!(level.get_room(&Coord { x: 2, y: 1, z: 0 }).is_some());
+        let guard = &level.npcs["guard"];
+        assert_eq!(guard.name, "Guard");
+        assert_eq!(guard.talk.texts(), vec!["\"Did you hear that?\""]);
+
+        // The level is left alone when no pack is for it.
+        assert!(matches!(
+            patch_level("rooms: []", "level.yml", "sewer", &packs[..1]),
+            Ok(Cow::Owned(_))
+        ));
+        assert!(matches!(
+            patch_level("rooms: []", "level.yml", "sewer", &packs[2..]),
+            Ok(Cow::Owned(_))
+        ));
+        assert!(matches!(
+            patch_level("rooms: []", "level.yml", "market", &packs[2..]),
+            Ok(Cow::Borrowed(_))
+        ));
+
+        let item_db = patch_items(&ItemDatabase::new(), &packs).unwrap();
+        let lantern = item_db.get("lantern");
+        assert_eq!(lantern.description, "A lantern that never goes out.");
+        assert!(lantern.light);
+        assert_eq!(item_db.get("skull").name, "skull");
+    }
+
+    #[test]
+    fn test_pack_problems() {
+        let packs = [
+            r#"
+name: Night Market
+rooms:
+  - { coord: [1, 1, 0], description: The market at night. }
+"#,
+            r#"
+name: Haunted Docks
+requires: [Night Market, Ghost Ships]
+npcs:
+  guard: { talk: "\"Did you hear that?\"" }
+rooms:
+  - coord: [1, 1, 0]
+    description: Fog rolls in over the market.
+    actions: [{ verb: Look, targets: [fog], value: Shapes move in the fog. }]
+items:
+  - { id: lantern, description: A lantern that never goes out. }
+"#,
+            r#"
+name: Chatty Guards
+requires: [Sewers]
+npcs:
+  guard: { talk: "\"Lovely weather.\"", name: Guard }
+rooms:
+  - coord: [1, 1, 0]
+    actions: [{ verb: Look, targets: [fog], value: It's just fog. }]
+items:
+  - { id: lantern, description: A dim lantern. }
+"#,
+            r#"
+name: Sewers
+level: sewer
+npcs:
+  guard: { talk: "\"Down here?\"" }
+"#,
+        ];
+        let packs: Vec<Pack> = packs
+            .iter()
+            .map(|text| Pack::parse(text, "pack.yml").unwrap())
+            .collect();
+        insta::assert_yaml_snapshot!(problems(&packs), @r###"
+        ---
+        - "The pack \"Haunted Docks\" requires \"Ghost Ships\", which isn't loaded."
+        - "The pack \"Chatty Guards\" requires \"Sewers\", which has to be loaded before it."
+        - "The packs \"Night Market\" and \"Haunted Docks\" both change rooms[1, 1, 0].description, so \"Haunted Docks\" wins."
+        - "The packs \"Haunted Docks\" and \"Chatty Guards\" both change items[lantern].description, so \"Chatty Guards\" wins."
+        - "The packs \"Haunted Docks\" and \"Chatty Guards\" both change npcs.guard.talk, so \"Chatty Guards\" wins."
+        - "The packs \"Haunted Docks\" and \"Sewers\" both change npcs.guard.talk, so \"Sewers\" wins."
+        - "The packs \"Chatty Guards\" and \"Sewers\" both change npcs.guard.talk, so \"Sewers\" wins."
+        "###);
+    }
+
+    #[test]
+    fn test_bad_pack() {
+        // A pack that can't be read is reported, rather than crashing the game.
+        let mut command_runner = CommandRunner::new(vec![]);
+        command_runner
+            .files
+            .insert("mods/bad.yml", "name: [unclosed".into());
+        command_runner.packs = vec!["mods/bad.yml".into()];
+        let message = Game::new(ItemDatabase::new(), &mut command_runner)
+            .err()
+            .unwrap();
+        assert!(message.starts_with("Unable to parse mods/bad.yml"));
+
+        // So is a pack that patches the items with something that isn't an item.
+        let mut command_runner = CommandRunner::new(vec![]);
+        command_runner.files.insert(
+            "mods/bad.yml",
+            "name: Bad\nitems:\n  - { id: lantern, light: lots }\n".into(),
+        );
+        command_runner.packs = vec!["mods/bad.yml".into()];
+        assert!(Game::new(ItemDatabase::new(), &mut command_runner).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+/// A piece of authored text. Most passages are plain text, but a passage can be
+/// flagged with content tags, and supply a tamer alternative that is shown when the
+/// player filters out any of those tags. A passage can also be a pool of alternatives,
+/// where one is picked each time it's shown, so that looking again doesn't read the
+/// same. Pools with `repeat: false` show each alternative once before any repeat.
+///
+/// ```yml
+/// value: |
+///   Plain text.
+/// value:
+///   text: The original passage.
+///   tags: [innuendo]
+///   tame: The tamer passage.
+/// value: [The gulls cry., The waves crash.]
+/// value:
+///   one_of: [The gulls cry., The waves crash., A bell rings.]
+///   repeat: false
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Passage {
+    Plain(String),
+    Rated {
+        text: String,
+        tags: Vec<String>,
+        tame: String,
+    },
+    List(Vec<Passage>),
+    Pool {
+        one_of: Vec<Passage>,
+        #[serde(default = "default_repeat")]
+        repeat: bool,
+    },
+}
+
+fn default_repeat() -> bool {
+    true
+}
+
+impl Default for Passage {
+    fn default() -> Passage {
+        Passage::Plain(String::new())
+    }
+}
+
+impl Passage {
+    /// Get the text to show for the player's content filter. A pool shows its first
+    /// alternative, use `pool` to pick one of them instead.
+    pub fn text(&self, filter: &BTreeSet<String>) -> &str {
+        match self {
+            Passage::List(alternatives)
+            | Passage::Pool {
+                one_of: alternatives,
+                ..
+            } => alternatives.first().map_or("", |first| first.text(filter)),
+            Passage::Plain(text) => text,
+            Passage::Rated { text, tags, tame } => {
+                if tags.iter().any(|tag| filter.contains(tag)) {
+                    tame
+                } else {
+                    text
+                }
+            }
+        }
+    }
+
+    /// The alternatives of a pool, and whether they can repeat before all of them are
+    /// shown.
+    pub fn pool(&self) -> Option<(&[Passage], bool)> {
+        match self {
+            Passage::List(alternatives) => Some((alternatives, true)),
+            Passage::Pool { one_of, repeat } => Some((one_of, *repeat)),
+            _ => None,
+        }
+    }
+
+    /// Every text the passage can show, no matter the content filter.
+    pub fn texts(&self) -> Vec<&str> {
+        match self {
+            Passage::Plain(text) => vec![text],
+            Passage::Rated { text, tame, .. } => vec![text, tame],
+            Passage::List(alternatives)
+            | Passage::Pool {
+                one_of: alternatives,
+                ..
+            } => alternatives.iter().flat_map(Passage::texts).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn test_passage() {
+        let plain: Passage = serde_yaml::from_str("Plain text.").unwrap();
+        let rated: Passage =
+            serde_yaml::from_str("{ text: Rowdy text., tags: [rowdy], tame: Tame text. }").unwrap();
+
+        let mut filter = BTreeSet::new();
+        assert_eq!(plain.text(&filter), "Plain text.");
+        assert_eq!(rated.text(&filter), "Rowdy text.");
+
+        filter.insert("gore".to_string());
+        assert_eq!(rated.text(&filter), "Rowdy text.");
+
+        filter.insert("rowdy".to_string());
+        assert_eq!(plain.text(&filter), "Plain text.");
+        assert_eq!(rated.text(&filter), "Tame text.");
+    }
+
+    #[test]
+    fn test_pools() {
+        let list: Passage = serde_yaml::from_str("[First., Second.]").unwrap();
+        let pool: Passage = serde_yaml::from_str(
+            "{ one_of: [First., { text: Rowdy., tags: [rowdy], tame: Tame. }], repeat: false }",
+        )
+        .unwrap();
+        assert_eq!(
+            list.pool()
+                .map(|(alternatives, repeat)| (alternatives.len(), repeat)),
+            Some((2, true))
+        );
+        assert_eq!(
+            pool.pool()
+                .map(|(alternatives, repeat)| (alternatives.len(), repeat)),
+            Some((2, false))
+        );
+        assert_eq!(list.text(&BTreeSet::new()), "First.");
+        assert_eq!(pool.texts(), vec!["First.", "Rowdy.", "Tame."]);
+        assert_eq!(Passage::Plain("Plain.".into()).pool(), None);
+    }
+
+    #[test]
+    fn test_passage_pools() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+rooms:
+  - title: Beach
+    coord: [1, 1, 0]
+    description: { one_of: [The gulls cry., The waves crash., A bell rings.], repeat: false }
+    actions:
+      - { verb: Look, targets: [sea], value: [Grey., Green., Blue.] }
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        let mut descriptions = Vec::new();
+        for _ in 0..6 {
+            game.output().output.clear();
+            assert!(game.step("look".into()).is_none());
+            descriptions.push(take_output(&game)[2].trim().to_string());
+        }
+        // Every alternative is shown once before any of them repeat.
+        for cycle in descriptions.chunks(3) {
+            let mut cycle = cycle.to_vec();
+            cycle.sort();
+            assert_eq!(
+                cycle,
+                vec!["A bell rings.", "The gulls cry.", "The waves crash."]
+            );
+        }
+
+        game.output().output.clear();
+        assert!(game.step("look sea".into()).is_none());
+        let sea = take_output(&game)[0].clone();
+        assert!(["Grey.", "Green.", "Blue."].contains(&sea.as_str()));
+    }
+}
@@ -1,34 +1,329 @@
 use crate::{
-    level::{Coord, Level},
+    grammar::Noun,
+    level::{Coord, InventoryItem, Level, Lighting, Movement, Par, Voice},
+    map::{Cell, DIRECTIONS},
+    markup,
+    passage::Passage,
+    style::{Color, Style},
+    utils::{hash_str, wrap},
     Environment, Game, RoomMapInfo,
 };
-use std::{fs, path::PathBuf};
-
-const LINE_WIDTH: usize = 90;
-const INDENT: usize = 4;
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_pcg::Pcg32;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap},
+    io::Write,
+};
 
 pub fn print_exits<T: Environment>(game: &Game<T>, room_map_info: &RoomMapInfo) {
-    let mut exits = String::from("Exits:");
+    if game.exits_hidden() {
+        let weather = game.weather().map_or("", |weather| weather.lowercase_str());
+        let message = game
+            .level
+            .narrator
+            .say_with("weather-hides-exits", &[("weather", weather)]);
+        let message = game.styled(Style::Exits, &wrap(&message, game.width()));
+        writeln!(game.output(), "{}", message).unwrap();
+        return;
+    }
+    let exits = game.level.narrator.say_with(
+        "exits",
+        &[(
+            "exits",
+            &room_map_info.exits(|direction| game.exit(direction).is_some()),
+        )],
+    );
+    let exits = game.styled(Style::Exits, &wrap(&exits, game.width()));
+    writeln!(game.output(), "{}", exits).unwrap();
+}
+
+/// Print the exits, and then where each of them leads, by the title of the room on the
+/// other side. Rooms the player hasn't been to yet stay a mystery.
+pub fn print_exit_destinations<T: Environment>(game: &Game<T>) {
+    print_exits(game, &game.room_info);
+    if game.exits_hidden() {
+        return;
+    }
+    for direction in DIRECTIONS.iter() {
+        let to = game.leads_to(direction);
+        if game.exit(direction).is_none() && to.is_none() {
+            continue;
+        }
+        let title = to
+            .filter(|coord| game.visited(coord))
+            .and_then(|coord| game.level.get_room(&coord))
+            .map_or_else(
+                || game.level.narrator.say("exits-unknown"),
+                |room| room.title.clone(),
+            );
+        let line = format!("  {:<5}  {}", direction.lowercase_string(), title);
+        let line = game.styled(Style::Exits, &line);
+        writeln!(game.output(), "{}", line).unwrap();
+    }
+}
+
+/// Print a message when something the player tried didn't work.
+pub fn print_failure<T: Environment>(game: &Game<T>, message: &str) {
+    let message = game.styled(Style::Error, message);
+    writeln!(game.output(), "{}", message).unwrap();
+}
+
+pub fn print_box<T: Environment>(game: &Game<T>, text: &str) {
+    let bar = "═".repeat(text.chars().count() + 2);
+    let text = game.styled(Style::Title, text);
+    let mut output = game.output();
+    writeln!(output, "╔{}╗", bar).unwrap();
+    writeln!(output, "║ {} ║", text).unwrap();
+    writeln!(output, "╚{}╝", bar).unwrap();
+}
+
+/// Print the stats of two items side by side. Stats that neither item has are left
+/// out.
+pub fn print_comparison<T: Environment>(
+    game: &Game<T>,
+    first: &InventoryItem,
+    second: &InventoryItem,
+) {
+    let narrator = &game.level.narrator;
+    let stat = |value: Option<usize>| value.map_or("-".to_string(), |value| value.to_string());
+    let mut rows = vec![(
+        narrator.say("compare-kind"),
+        first.variant.lowercase_str().to_string(),
+        second.variant.lowercase_str().to_string(),
+    )];
+    let stats = [
+        ("compare-damage", first.stats.damage, second.stats.damage),
+        ("compare-weight", first.stats.weight, second.stats.weight),
+        ("compare-value", first.stats.value, second.stats.value),
+        (
+            "compare-durability",
+            first.stats.durability,
+            second.stats.durability,
+        ),
+    ];
+    for (id, a, b) in stats.iter() {
+        if a.is_some() || b.is_some() {
+            rows.push((narrator.say(id), stat(*a), stat(*b)));
+        }
+    }
+
+    let title = narrator.say_with(
+        "compare",
+        &[("first", &first.name), ("second", &second.name)],
+    );
+    print_box(game, &title);
+    let width = rows
+        .iter()
+        .map(|(_, a, _)| a.chars().count())
+        .chain(std::iter::once(first.name.chars().count()))
+        .max()
+        .unwrap_or(0);
+    let first_name = game.styled(Style::Item, &first.name);
+    let second_name = game.styled(Style::Item, &second.name);
+    let mut output = game.output();
+    // Pad by the unstyled length, as the styles add invisible characters.
+    let padding = " ".repeat(width - first.name.chars().count());
+    writeln!(
+        output,
+        "  {:10}  {}{}  {}",
+        "", first_name, padding, second_name
+    )
+    .unwrap();
+    for (label, a, b) in rows {
+        writeln!(output, "  {:10}  {:width$}  {}", label, a, b, width = width).unwrap();
+    }
+    writeln!(output).unwrap();
+}
 
-    let mut push_dir = |option, string| match option {
-        Some(_) => exits.push_str(string),
-        None => exits.push_str(" _"),
+/// Print how the player's run compares to the level's par.
+pub fn print_summary<T: Environment>(game: &Game<T>, par: &Par, turns: usize) {
+    let narrator = &game.level.narrator;
+    print_box(game, &narrator.say("summary"));
+    let verdict = match turns.cmp(&par.turns) {
+        Ordering::Less => {
+            narrator.say_with("par-under", &[("turns", &(par.turns - turns).to_string())])
+        }
+        Ordering::Equal => narrator.say("par-equal"),
+        Ordering::Greater => {
+            narrator.say_with("par-over", &[("turns", &(turns - par.turns).to_string())])
+        }
     };
+    // Line up the numbers, however long the labels are in the player's language.
+    let labels = [narrator.say("summary-turns"), narrator.say("summary-par")];
+    let width = labels
+        .iter()
+        .map(|label| label.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut output = game.output();
+    writeln!(output, "  {:width$} {}", labels[0], turns, width = width).unwrap();
+    writeln!(
+        output,
+        "  {:width$} {}",
+        labels[1],
+        par.turns,
+        width = width
+    )
+    .unwrap();
+    writeln!(output, "\n{}\n", verdict).unwrap();
+    drop(output);
 
-    push_dir(room_map_info.north, " n");
-    push_dir(room_map_info.east, " e");
-    push_dir(room_map_info.south, " s");
-    push_dir(room_map_info.west, " w");
-    writeln!(game.output(), "{}", exits).unwrap();
+    if turns <= par.turns {
+        if let Some(ref bonus) = par.bonus {
+            writeln!(game.output(), "{}", game.text(bonus)).unwrap();
+        }
+    }
 }
 
-pub fn print_text_file<T: Environment>(game: &Game<T>, path_str: &str) {
-    let path = PathBuf::from(path_str);
-    let text = fs::read_to_string(path).expect("Could not find the intro.txt");
+/// Print the problems `validate` found with the level, for authors in jam mode.
+pub fn print_problems<T: Environment>(game: &Game<T>, problems: &[String]) {
+    if problems.is_empty() {
+        writeln!(game.output(), "{}\n", game.level.narrator.say("jam-valid")).unwrap();
+        return;
+    }
+    writeln!(game.output(), "{}", game.level.narrator.say("jam-problems")).unwrap();
+    for problem in problems {
+        let line = wrap(&format!("  ‣ {}", problem), game.width());
+        writeln!(game.output(), "{}", line).unwrap();
+    }
+    writeln!(game.output()).unwrap();
+}
+
+/// Print the player's score, and the level's achievements they have unlocked.
+pub fn print_score<T: Environment>(game: &Game<T>) {
+    let narrator = &game.level.narrator;
+    print_box(game, &narrator.say("score"));
+    let achievements = &game.level.achievements;
+    let unlocked: Vec<_> = achievements
+        .iter()
+        .filter(|(id, _)| game.achievements.contains(*id))
+        .map(|(_, achievement)| achievement)
+        .collect();
+    let mut output = game.output();
+    let points = game.save_state.score.to_string();
+    let message = narrator.say_with("score-points", &[("points", &points)]);
+    writeln!(output, "  {}", message).unwrap();
+    if !achievements.is_empty() {
+        let message = narrator.say_with(
+            "score-achievements",
+            &[
+                ("unlocked", &unlocked.len().to_string()),
+                ("total", &achievements.len().to_string()),
+            ],
+        );
+        writeln!(output, "  {}", message).unwrap();
+    }
+    drop(output);
+
+    for achievement in unlocked {
+        let name = game.styled(Style::Item, &achievement.name);
+        match achievement.description {
+            Some(ref description) => {
+                writeln!(game.output(), "    ‣ {} - {}", name, description).unwrap()
+            }
+            None => writeln!(game.output(), "    ‣ {}", name).unwrap(),
+        }
+    }
+    writeln!(game.output()).unwrap();
+}
+
+pub fn print_text_file<T: Environment>(game: &Game<T>, path: &str) {
+    let text = game.environment.borrow().read_text(path);
     writeln!(game.output(), "{}", text).unwrap();
 }
 
+/// List the commands added by the program embedding the game, in the columns of the
+/// help text.
+pub fn print_handler_help<T: Environment>(game: &Game<T>) {
+    if game.handlers.is_empty() {
+        return;
+    }
+    for handler in game.handlers.iter() {
+        let verb = handler.verbs().first().copied().unwrap_or_default();
+        writeln!(game.output(), "  {:<16}{}", verb, handler.help()).unwrap();
+    }
+    writeln!(game.output()).unwrap();
+}
+
+/// Print an NPC's dialogue in their voice. Any text inside of quotes is considered
+/// speech, and if there are no quotes then the entire text is speech.
+pub fn print_dialogue<T: Environment>(game: &Game<T>, text: &str, voice: &Voice) {
+    let has_quotes = text.contains('"');
+    let mut in_speech = !has_quotes;
+    let mut styled = String::new();
+    let mut speech = String::new();
+
+    for ch in text.chars() {
+        if ch == '"' {
+            if in_speech {
+                styled.push_str(&style_speech(game, &speech, voice));
+                speech.clear();
+                styled.push(ch);
+            } else {
+                styled.push(ch);
+            }
+            in_speech = !in_speech;
+        } else if in_speech {
+            speech.push(ch);
+        } else {
+            styled.push(ch);
+        }
+    }
+    styled.push_str(&style_speech(game, &speech, voice));
+
+    for line in styled.trim_end().lines() {
+        match voice.prefix {
+            Some(ref prefix) => writeln!(game.output(), "{}{}", prefix, line).unwrap(),
+            None => writeln!(game.output(), "{}", line).unwrap(),
+        }
+    }
+    writeln!(game.output()).unwrap();
+}
+
+fn style_speech<T: Environment>(game: &Game<T>, speech: &str, voice: &Voice) -> String {
+    let mut speech = if voice.all_caps {
+        speech.to_uppercase()
+    } else {
+        speech.to_string()
+    };
+
+    if voice.stutter {
+        // Stutter on the first letter of every few words, e.g. "W-would you".
+        let mut stuttered = String::new();
+        let mut word_index = 0;
+        let mut at_word_start = true;
+        for ch in speech.chars() {
+            if at_word_start && ch.is_alphabetic() {
+                if word_index % 4 == 0 {
+                    stuttered.push(ch);
+                    stuttered.push('-');
+                }
+                word_index += 1;
+            }
+            at_word_start = ch.is_whitespace();
+            stuttered.push(ch);
+        }
+        speech = stuttered;
+    }
+
+    match voice.color {
+        Some(color) => game.styled_with(Style::Dialogue, Some(color), &speech),
+        None => game.styled(Style::Dialogue, &speech),
+    }
+}
+
 pub fn print_room_description<T: Environment>(game: &Game<T>) {
+    describe_room(game, false);
+}
+
+/// Describe the room when the player looks around on purpose, which also takes in the
+/// regions the room is part of.
+pub fn print_room_look<T: Environment>(game: &Game<T>) {
+    describe_room(game, true);
+}
+
+fn describe_room<T: Environment>(game: &Game<T>, looking: bool) {
     let Game {
         ref room,
         ref save_state,
@@ -36,43 +331,105 @@ pub fn print_room_description<T: Environment>(game: &Game<T>) {
         ..
     } = game;
 
-    writeln!(game.output(), "{}\n", room.title).unwrap();
-
-    let mut formatted_description = room.cached_formatted_description.borrow_mut();
+    let title = game.styled(Style::Title, &room.title);
+    writeln!(game.output(), "{}\n", title).unwrap();
 
-    if formatted_description.len() == 0 {
-        let paragraphs = room.description.split("\n\n");
-        let mut formatted_lines = Vec::new();
-        for paragraph in paragraphs {
-            let paragraph = paragraph.replace('\n', " ");
-            let mut formatted_line = " ".repeat(INDENT);
-            for word in paragraph.split(' ') {
-                let word = word.trim();
-                if word.is_empty() {
-                    continue;
-                }
-                if formatted_line.len() + word.len() > LINE_WIDTH {
-                    formatted_line.push('\n');
-                    formatted_lines.push(formatted_line);
-                    formatted_line = " ".repeat(INDENT);
+    // The description is a template, so it's filled in and wrapped each time it's
+    // shown, as the game's state changes what it says.
+    let dim = game.light() == Lighting::Dim;
+    let mut description = game.text(&room.description);
+    // Scenery that has been moved stays that way.
+    if let Some(room_inventory) = save_state.room_inventories.get(&room.coord) {
+        for scenery in room.scenery.iter() {
+            match scenery.moved {
+                Some(ref moved) if room_inventory.moved.contains(&scenery.id) => {
+                    description = format!("{} {}", description.trim_end(), game.text(moved));
                 }
-                formatted_line.push_str(word);
-                formatted_line.push(' ');
+                _ => {}
+            }
+        }
+    }
+    // A dim room says so in place of its details.
+    let details = match room.details {
+        _ if dim => Some(game.level.narrator.say("room-dim")),
+        Some(ref details) => Some(game.text(details)),
+        None => None,
+    };
+    if let Some(details) = details {
+        description = format!("{}\n\n{}", description.trim_end(), details);
+    }
+    // Outdoors, the weather is added, first the room's and then its regions'.
+    if let Some(weather) = game.weather().filter(|_| room.outdoors) {
+        let regions = room
+            .regions
+            .iter()
+            .filter_map(|id| game.level.regions.get(id))
+            .map(|region| &region.weather);
+        for overlays in std::iter::once(&room.weather).chain(regions) {
+            if let Some(overlay) = overlays.get(&weather) {
+                description = format!("{}\n\n{}", description.trim_end(), game.text(overlay));
+            }
+        }
+    }
+    if looking && !dim {
+        for id in room.regions.iter() {
+            let region = game.level.regions.get(id);
+            if let Some(region_description) = region.and_then(|region| region.description.as_ref())
+            {
+                let text = game.text(region_description);
+                description = format!("{}\n\n{}", description.trim_end(), text);
             }
-            formatted_lines.push(formatted_line);
-            formatted_lines.push(String::from("\n\n"));
         }
-        *formatted_description = formatted_lines.join("");
     }
-    writeln!(game.output(), "{}", formatted_description).unwrap();
+    let width = game.width();
+    let rendered = markup::render(&description, width, game.config.indent, |style, text| {
+        game.styled(style, text)
+    });
+    writeln!(game.output(), "{}", rendered).unwrap();
+
+    let mut has_company = false;
+    for (crowd, density) in room.crowds_iter(&game.level) {
+        if let Some(line) = crowd.densities.get(&density) {
+            writeln!(game.output(), "{}", wrap(line.trim_end(), width)).unwrap();
+            has_company = true;
+        }
+    }
+    // The NPCs that move can't be written into a room's description, so they are
+    // mentioned wherever they are, like escorts who aren't in the room that lists them.
+    for (id, npc) in game.npcs_at(&room.coord) {
+        if npc.movement != Movement::Static || !room.npcs.iter().any(|other| other == id) {
+            let line = game
+                .level
+                .narrator
+                .say_with("npc-here", &[("name", &npc.name)]);
+            writeln!(game.output(), "{}", wrap(&line, width)).unwrap();
+            has_company = true;
+        }
+    }
+    // Vehicles are mentioned too, as they can be ridden away from the rooms that list
+    // them.
+    let vehicles = game.vehicles_at(&room.coord);
+    let aboard = game
+        .vehicle()
+        .map(|(_, vehicle)| ("vehicle-aboard", vehicle));
+    let here = vehicles
+        .into_iter()
+        .map(|(_, vehicle)| ("vehicle-here", vehicle));
+    for (id, vehicle) in aboard.into_iter().chain(here) {
+        let line = game.level.narrator.say_with(id, &[("name", &vehicle.name)]);
+        writeln!(game.output(), "{}", wrap(&line, width)).unwrap();
+        has_company = true;
+    }
+    if has_company {
+        writeln!(game.output()).unwrap();
+    }
 
-    for name in save_state
-        .room_inventories
-        .get(&room.coord)
-        .expect("room inventory")
-        .item_names_iter()
-    {
-        writeln!(game.output(), "{}", name).unwrap();
+    if let Some(room_inventory) = save_state.room_inventories.get(&room.coord) {
+        for (room_item, item) in room_inventory.visible_iter() {
+            let name = Noun::in_room(room_item, item).a(&game.level.narrator, item.quantity);
+            let name = game.styled(Style::Item, &wrap(&name, width));
+            writeln!(game.output(), "{}", name).unwrap();
+        }
     }
 
     if !room.items.is_empty() {
@@ -87,6 +444,102 @@ pub fn print_room_description<T: Environment>(game: &Game<T>) {
     print_exits(game, room_info);
 }
 
+/// Maybe show one of the ambient lines of the room the player is in, or of its
+/// regions. Each place rolls its own chance, and if several of them succeed, one of
+/// their lines is picked. The rolls are seeded by the game and the turn that's ending,
+/// so a replay shows the same lines.
+pub fn print_ambient<T: Environment>(game: &Game<T>) {
+    let turn = game.save_state.turn + 1;
+    let mut rng = Pcg32::seed_from_u64(game.save_state.seed ^ hash_str("ambient") ^ turn as u64);
+    let lines: Vec<&Passage> = game
+        .room
+        .ambient_iter(&game.level)
+        .filter(|ambient| rng.gen_bool(ambient.chance.clamp(0.0, 1.0)))
+        .flat_map(|ambient| ambient.lines.iter())
+        .collect();
+    if let Some(line) = lines.choose(&mut rng) {
+        let line = wrap(game.text(line).trim_end(), game.width());
+        writeln!(game.output(), "{}\n", line).unwrap();
+    }
+}
+
+/// Draw the player's layer of the map with each room lettered by its region, so that
+/// designers can check that the regions cover the rooms they meant them to. Rooms in no
+/// region are left as dots, and rooms in several regions are drawn as a +.
+pub fn print_region_map<T: Environment>(game: &Game<T>) {
+    const LETTERS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    const COLORS: [Color; 6] = [
+        Color::Red,
+        Color::Green,
+        Color::Yellow,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+    ];
+    let level = &game.level;
+    let player = game.save_state.coord;
+
+    // Rooms can name regions that the level doesn't define, which is worth seeing too.
+    let mut regions: BTreeSet<&str> = level.regions.keys().map(String::as_str).collect();
+    regions.extend(
+        level
+            .rooms
+            .iter()
+            .flat_map(|room| room.regions.iter().map(String::as_str)),
+    );
+    let mut letters = HashMap::new();
+    for (index, region) in regions.iter().enumerate() {
+        let letter = LETTERS.chars().nth(index).unwrap_or('?').to_string();
+        let letter = game.styled_with(Style::Item, Some(COLORS[index % COLORS.len()]), &letter);
+        letters.insert(*region, letter);
+    }
+
+    let map = level.maps.get(player.z).map_or(&[][..], |map| &map[..]);
+    for (y, row) in map.iter().enumerate() {
+        let mut line = String::new();
+        for (x, ch) in row.chars().enumerate() {
+            if ch == ' ' {
+                // The rest of the row is a comment.
+                break;
+            }
+            let coord = Coord { x, y, z: player.z };
+            let room_regions = match level.get_room(&coord) {
+                Some(room) if matches!(Cell::read(level, ch), Cell::Room(..)) => &room.regions[..],
+                _ => {
+                    line.push(ch);
+                    continue;
+                }
+            };
+            if coord == player {
+                line.push('@');
+                continue;
+            }
+            match room_regions {
+                [] => line.push(ch),
+                [region] => line.push_str(&letters[region.as_str()]),
+                _ => line.push('+'),
+            }
+        }
+        writeln!(game.output(), "{}", line).unwrap();
+    }
+    writeln!(game.output()).unwrap();
+    for region in regions.iter() {
+        if level.regions.contains_key(*region) {
+            writeln!(game.output(), "  {}  {}", letters[region], region).unwrap();
+        } else {
+            let line = format!(
+                "  {}  {} (not a region of the level)",
+                letters[region], region
+            );
+            writeln!(game.output(), "{}", line).unwrap();
+        }
+    }
+    writeln!(game.output(), "  +  several regions").unwrap();
+    writeln!(game.output(), "  .  no region").unwrap();
+    writeln!(game.output(), "  @  you are here").unwrap();
+    writeln!(game.output()).unwrap();
+}
+
 pub fn print_map_issue(level: &Level, coord: &Coord) {
     let map = match level.maps.get(coord.z) {
         Some(map) => map,
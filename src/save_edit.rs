@@ -0,0 +1,354 @@
+use crate::{
+    campaign::{Campaign, CAMPAIGN_PATH},
+    level::{Coord, Level},
+    utils::try_parse_yml_str,
+    ItemDatabase, SaveState,
+};
+use std::{
+    fs,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+const HELP: &str = "\
+Commands:
+  show               Show where the player is and what they carry.
+  check              List everything in the save that doesn't match the level or items.
+  coord <x> <y> <z>  Move the player to a room.
+  coord <id>         Move the player to a room or entry by its id.
+  add <item> [count] Give the player an item, by its id in data/items.yml.
+  remove <item>      Take an item away from the player.
+  write              Save the changes, keeping the old save next to it as a backup.
+  quit               Stop editing.";
+
+/// Edits a save file for testing, as a safer way than changing the YML by hand. Every
+/// edit is checked against the level and the items, and the save is written in full,
+/// so it always has the shape the game expects.
+pub struct SaveEditor {
+    path: PathBuf,
+    save_state: SaveState,
+    level: Level,
+    level_id: String,
+    campaign: Campaign,
+    item_db: ItemDatabase,
+    /// Whether there are edits that haven't been written.
+    changed: bool,
+}
+
+impl SaveEditor {
+    /// Open a save, along with the level it was saved in.
+    pub fn open(path: &Path) -> Result<SaveEditor, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("Unable to read {}: {}", path.display(), err))?;
+        let save_state: SaveState = try_parse_yml_str(&text, &path.display().to_string())?;
+        let campaign_text = fs::read_to_string(CAMPAIGN_PATH)
+            .map_err(|err| format!("Unable to read {}: {}", CAMPAIGN_PATH, err))?;
+        let campaign: Campaign = try_parse_yml_str(&campaign_text, CAMPAIGN_PATH)?;
+        let level_id = match save_state.level.as_str() {
+            "" => campaign.start.clone(),
+            level_id => level_id.to_string(),
+        };
+        let level_path = campaign.level_path(&level_id)?;
+        let level_text = fs::read_to_string(level_path)
+            .map_err(|err| format!("Unable to read {}: {}", level_path, err))?;
+        let level = try_parse_yml_str(&level_text, level_path)?;
+        Ok(SaveEditor {
+            path: path.into(),
+            save_state,
+            level,
+            level_id,
+            campaign,
+            item_db: ItemDatabase::new(),
+            changed: false,
+        })
+    }
+
+    /// Everything in the save that doesn't match the level or the items, which the
+    /// game would drop or move when the save is loaded.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let save_state = &self.save_state;
+        if self.level.get_room(&save_state.coord).is_none() {
+            problems.push(format!(
+                "The player is at {}, which isn't a room in the level.",
+                save_state.coord
+            ));
+        }
+        if let Some(checkpoint) = save_state.checkpoint {
+            if self.level.get_room(&checkpoint).is_none() {
+                problems.push(format!(
+                    "The checkpoint at {} isn't a room in the level.",
+                    checkpoint
+                ));
+            }
+        }
+        for item in save_state.inventory.items.iter() {
+            if self.item_db.find(&item.id).is_none() {
+                problems.push(format!("The player carries an unknown item {:?}.", item.id));
+            }
+        }
+        for (coord, room_inventory) in save_state.room_inventories.iter() {
+            if self.level.get_room(coord).is_none() {
+                problems.push(format!(
+                    "There are items at {}, which isn't a room in the level.",
+                    coord
+                ));
+            }
+            for (_, item) in room_inventory.inventory.iter() {
+                if self.item_db.find(&item.id).is_none() {
+                    problems.push(format!(
+                        "The room at {} has an unknown item {:?}.",
+                        coord, item.id
+                    ));
+                }
+            }
+        }
+        for level_id in save_state.level_inventories.keys() {
+            if self.campaign.level_path(level_id).is_err() {
+                problems.push(format!(
+                    "There are items for the level {:?}, which isn't in the campaign.",
+                    level_id
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Run one of the editor's commands, returning what to show the player.
+    pub fn run(&mut self, line: &str) -> Result<String, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let args: Vec<&str> = words.collect();
+        match (command, args.as_slice()) {
+            ("show", []) => Ok(self.show()),
+            ("check", []) => Ok(self.check()),
+            ("coord", [id]) => {
+                let coord = self
+                    .level
+                    .find_entry(id)
+                    .ok_or_else(|| format!("The level has no room or entry {:?}.", id))?;
+                self.set_coord(coord)
+            }
+            ("coord", [x, y, z]) => {
+                let number = |text: &str| {
+                    text.trim_matches(|ch| ch == '[' || ch == ']' || ch == ',')
+                        .parse()
+                        .map_err(|_| format!("Expected a number for the coord, not {:?}.", text))
+                };
+                let coord = Coord {
+                    x: number(x)?,
+                    y: number(y)?,
+                    z: number(z)?,
+                };
+                self.set_coord(coord)
+            }
+            ("add", [id]) => self.add(id, None),
+            ("add", [id, quantity]) => match quantity.parse() {
+                Ok(quantity) if quantity > 0 => self.add(id, Some(quantity)),
+                _ => Err(format!(
+                    "Expected a count of at least 1, not {:?}.",
+                    quantity
+                )),
+            },
+            ("remove", [id]) => {
+                let inventory = &mut self.save_state.inventory;
+                let count = inventory.items.len();
+                inventory.items.retain(|item| item.id != *id);
+                if inventory.items.len() == count {
+                    return Err(format!("The player isn't carrying {:?}.", id));
+                }
+                self.changed = true;
+                Ok(format!("Removed the {}.", id))
+            }
+            ("write", []) => self.write(),
+            ("help", []) => Ok(HELP.into()),
+            _ => Err(format!(
+                "Unknown command {:?}, type \"help\" for the commands.",
+                line.trim()
+            )),
+        }
+    }
+
+    fn show(&self) -> String {
+        let save_state = &self.save_state;
+        let room = match self.level.get_room(&save_state.coord) {
+            Some(room) => room.title.as_str(),
+            None => "(not a room)",
+        };
+        let mut lines = vec![
+            format!("Level: {}", self.level_id),
+            format!("Room:  {} {}", room, save_state.coord),
+            format!("Turn:  {}", save_state.turn),
+            format!("Score: {}", save_state.score),
+            "Inventory:".into(),
+        ];
+        for item in save_state.inventory.items.iter() {
+            match item.max_quantity {
+                Some(_) => lines.push(format!("  ‣ {} ({})", item.id, item.quantity)),
+                None => lines.push(format!("  ‣ {}", item.id)),
+            }
+        }
+        if save_state.inventory.items.is_empty() {
+            lines.push("  (empty)".into());
+        }
+        lines.join("\n")
+    }
+
+    fn check(&self) -> String {
+        let problems = self.problems();
+        if problems.is_empty() {
+            return "No problems were found.".into();
+        }
+        let mut lines: Vec<String> = problems
+            .iter()
+            .map(|problem| format!("  ‣ {}", problem))
+            .collect();
+        lines.push(format!("\nFound {} problems.", problems.len()));
+        lines.join("\n")
+    }
+
+    fn set_coord(&mut self, coord: Coord) -> Result<String, String> {
+        let room = self
+            .level
+            .get_room(&coord)
+            .ok_or_else(|| format!("There is no room at {} in the level.", coord))?;
+        self.save_state.coord = coord;
+        self.changed = true;
+        Ok(format!("Moved the player to {} {}.", room.title, coord))
+    }
+
+    fn add(&mut self, id: &str, quantity: Option<usize>) -> Result<String, String> {
+        let mut item = match self.item_db.find(id) {
+            Some(item) => item.clone(),
+            None => return Err(format!("There is no item {:?} in the item database.", id)),
+        };
+        if let Some(quantity) = quantity {
+            item.quantity = quantity;
+        }
+        let name = item.name.clone();
+        self.save_state.inventory.add_item(item);
+        self.changed = true;
+        Ok(format!("Gave the player the {}.", name))
+    }
+
+    /// Write the save, keeping the old one as a backup. The new save is written next
+    /// to the old one first, so a failed write never leaves half a save behind.
+    fn write(&mut self) -> Result<String, String> {
+        let yml =
+            serde_yaml::to_string(&self.save_state).expect("Unable to serialize the game state.");
+        let backup = self.path.with_extension("yml.bak");
+        let temporary = self.path.with_extension("yml.tmp");
+        let written = fs::copy(&self.path, &backup)
+            .and_then(|_| fs::write(&temporary, yml))
+            .and_then(|()| fs::rename(&temporary, &self.path));
+        if let Err(err) = written {
+            return Err(format!("Unable to write {}: {}", self.path.display(), err));
+        }
+        self.changed = false;
+        let mut message = format!(
+            "Saved {}, the old save is in {}.",
+            self.path.display(),
+            backup.display()
+        );
+        let problems = self.problems().len();
+        if problems > 0 {
+            message.push_str(&format!(
+                " The save still has {} problems, type \"check\" to see them.",
+                problems
+            ));
+        }
+        Ok(message)
+    }
+}
+
+/// Open a save and edit it with commands read from the input, one per line.
+pub fn edit_file(
+    path: &Path,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<(), String> {
+    let mut editor = SaveEditor::open(path)?;
+    let print = |output: &mut dyn Write, text: &str| writeln!(output, "{}\n", text).unwrap();
+    print(output, &editor.show());
+    print(output, &editor.check());
+    print(output, "Type \"help\" for the commands.");
+    let mut confirmed_quit = false;
+    loop {
+        write!(output, "save> ").unwrap();
+        output.flush().unwrap();
+        let mut line = String::new();
+        if input.read_line(&mut line).unwrap_or(0) == 0 {
+            // The end of piped input is the same as quitting.
+            line = "quit".into();
+        }
+        match line.trim() {
+            "" => continue,
+            "quit" | "q" => {
+                if editor.changed && !confirmed_quit {
+                    confirmed_quit = true;
+                    print(
+                        output,
+                        "There are changes that haven't been written. Type \"write\" to save \
+                         them, or \"quit\" again to leave without them.",
+                    );
+                    continue;
+                }
+                return Ok(());
+            }
+            line => match editor.run(line) {
+                Ok(message) => print(output, &message),
+                Err(message) => print(output, &message),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::LEVEL_PATH;
+
+    #[test]
+    fn test_save_editor() {
+        let item_db = ItemDatabase::new();
+        let level: Level = crate::utils::parse_yml(&LEVEL_PATH.into());
+        let mut save_state = SaveState::initialize(&item_db, &level, 0);
+        save_state.coord = Coord { x: 0, y: 0, z: 0 };
+        let path = std::env::temp_dir().join("text-adventure-test-save-edit.yml");
+        fs::write(&path, serde_yaml::to_string(&save_state).unwrap()).unwrap();
+
+        let mut editor = SaveEditor::open(&path).unwrap();
+        assert_eq!(
+            editor.problems(),
+            vec!["The player is at [0, 0, 0], which isn't a room in the level."]
+        );
+        assert_eq!(
+            editor.run("coord 1 1 0"),
+            Err("There is no room at [1, 1, 0] in the level.".into())
+        );
+        assert_eq!(
+            editor.run("coord 12 17 0"),
+            Ok("Moved the player to Stone End Market Road [12, 17, 0].".into())
+        );
+        assert_eq!(
+            editor.run("add unicorn"),
+            Err("There is no item \"unicorn\" in the item database.".into())
+        );
+        assert_eq!(
+            editor.run("add apple 3"),
+            Ok("Gave the player the apple.".into())
+        );
+        assert!(editor.problems().is_empty());
+        assert!(editor.run("write").unwrap().starts_with("Saved "));
+
+        let saved: SaveState = crate::utils::parse_yml(&path);
+        assert_eq!(saved.coord, Coord { x: 12, y: 17, z: 0 });
+        let apple = saved.inventory.items.iter().find(|item| item.id == "apple");
+        assert_eq!(apple.map(|item| item.quantity), Some(3));
+        let backup: SaveState = crate::utils::parse_yml(&path.with_extension("yml.bak"));
+        assert_eq!(backup.coord, Coord { x: 0, y: 0, z: 0 });
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(path.with_extension("yml.bak")).unwrap();
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{
+    game_loop, level::ItemDatabase, style::Theme, utils::to_ascii, Environment, GameLoopResponse,
+};
+use std::{
+    collections::HashSet,
+    fs,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+const SAVE_DIRECTORY: &str = "data/saves";
+
+/// The names of the players that are connected. The save and the other files of a
+/// player are found by their name, so only one connection can play as each name.
+type Players = Arc<Mutex<HashSet<String>>>;
+
+/// A name that a connection is playing as, which is free again once it's dropped.
+struct PlayingAs {
+    name: String,
+    players: Players,
+}
+
+impl Drop for PlayingAs {
+    fn drop(&mut self) {
+        self.players.lock().unwrap().remove(&self.name);
+    }
+}
+
+/// A player connected over telnet or netcat. Each connection runs its own game, and
+/// saves to its own file based on the player's name.
+struct Connection<'a> {
+    reader: &'a mut BufReader<TcpStream>,
+    stream: TcpStream,
+    save_path: PathBuf,
+    theme: Option<Theme>,
+    ascii: bool,
+    /// Whether the player hung up, after which the output goes nowhere, so that the
+    /// game can still save on the way out.
+    hung_up: bool,
+}
+
+impl<'a> Environment for Connection<'a> {
+    fn get_prompt(&mut self) -> String {
+        let prompt = if self.ascii { "> " } else { "» " };
+        let prompted = write!(self.stream, "{}", prompt).and_then(|_| self.stream.flush());
+        let line = match prompted {
+            Ok(()) if !self.hung_up => read_line(self.reader),
+            _ => None,
+        };
+        match line {
+            Some(line) => {
+                writeln!(self).unwrap();
+                line.to_lowercase()
+            }
+            // The player hung up, save their game.
+            None => {
+                self.hung_up = true;
+                "quit".into()
+            }
+        }
+    }
+
+    fn save_path(&self) -> Option<PathBuf> {
+        Some(self.save_path.clone())
+    }
+
+    fn achievements_path(&self) -> Option<PathBuf> {
+        Some(self.save_path.with_extension("achievements.yml"))
+    }
+
+    fn results_path(&self) -> Option<PathBuf> {
+        Some(self.save_path.with_extension("results.yml"))
+    }
+
+    fn races_path(&self) -> Option<PathBuf> {
+        Some(self.save_path.with_extension("races.yml"))
+    }
+
+    fn new_game_plus_path(&self) -> Option<PathBuf> {
+        Some(self.save_path.with_extension("new-game-plus.yml"))
+    }
+
+    fn feedback_path(&self) -> Option<PathBuf> {
+        Some(self.save_path.with_extension("feedback.yml"))
+    }
+
+    fn theme(&self) -> Option<&Theme> {
+        self.theme.as_ref()
+    }
+}
+
+impl<'a> Write for Connection<'a> {
+    fn write(&mut self, buffer: &[u8]) -> Result<usize, std::io::Error> {
+        if self.hung_up {
+            return Ok(buffer.len());
+        }
+        let output = if self.ascii {
+            to_ascii(buffer)
+        } else {
+            buffer.to_vec()
+        };
+        // Telnet expects carriage returns before every newline.
+        for line in output.split_inclusive(|byte| *byte == b'\n') {
+            let written = match line.strip_suffix(b"\n") {
+                Some(line) => self
+                    .stream
+                    .write_all(line)
+                    .and_then(|_| self.stream.write_all(b"\r\n")),
+                None => self.stream.write_all(line),
+            };
+            if written.is_err() {
+                self.hung_up = true;
+                break;
+            }
+        }
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        if self.stream.flush().is_err() {
+            self.hung_up = true;
+        }
+        Ok(())
+    }
+}
+
+/// Read a line of input, or None if the connection was closed.
+fn read_line(reader: &mut BufReader<TcpStream>) -> Option<String> {
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim().to_string()),
+    }
+}
+
+/// Ask for the player's name, which is used for their save file, until they give one
+/// that no one else is playing as.
+fn prompt_name(
+    reader: &mut BufReader<TcpStream>,
+    stream: &mut TcpStream,
+    players: &Players,
+) -> Option<PlayingAs> {
+    loop {
+        write!(stream, "What is your name? ").ok()?;
+        let name: String = read_line(reader)?
+            .to_lowercase()
+            .chars()
+            .filter(|ch| ch.is_ascii_alphanumeric() || *ch == '-' || *ch == '_')
+            .collect();
+        if name.is_empty() {
+            write!(stream, "Names can only use letters, numbers, - and _.\r\n").ok()?;
+            continue;
+        }
+        if !players.lock().unwrap().insert(name.clone()) {
+            write!(stream, "Someone is already playing as {}.\r\n", name).ok()?;
+            continue;
+        }
+        return Some(PlayingAs {
+            name,
+            players: players.clone(),
+        });
+    }
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    theme: Option<Theme>,
+    ascii: bool,
+    players: &Players,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let playing_as = match prompt_name(&mut reader, &mut stream, players) {
+        Some(playing_as) => playing_as,
+        None => return Ok(()),
+    };
+    let name = &playing_as.name;
+    let save_path = PathBuf::from(SAVE_DIRECTORY).join(format!("{}.yml", name));
+    let item_db = ItemDatabase::new();
+
+    loop {
+        let connection = Connection {
+            reader: &mut reader,
+            stream: stream.try_clone()?,
+            save_path: save_path.clone(),
+            theme: theme.clone(),
+            ascii,
+            hung_up: false,
+        };
+        let response = match game_loop(&item_db, connection) {
+            Ok(response) => response,
+            Err(message) => {
+                eprintln!("Unable to start a game for {}: {}", name, message);
+                write!(stream, "Sorry, the game couldn't be started.\r\n")?;
+                return Ok(());
+            }
+        };
+        match response {
+            GameLoopResponse::Restart => {
+                if save_path.exists() {
+                    fs::remove_file(&save_path)?;
+                }
+            }
+            // Start again from the last save.
+            GameLoopResponse::Death => {}
+            GameLoopResponse::Quit => {
+                write!(stream, "Thanks for playing!\r\n")?;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Listen for players on a port, and run a separate game for each of them. Pass
+/// `ascii` for clients that can't show box drawing characters.
+pub fn serve(port: u16, theme: Option<Theme>, ascii: bool) {
+    fs::create_dir_all(SAVE_DIRECTORY).expect("Unable to create the save directory.");
+    let listener = match TcpListener::bind(("0.0.0.0", port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("Unable to listen on port {}: {}", port, err);
+            std::process::exit(1);
+        }
+    };
+    println!("Listening for players on port {}", port);
+    let players = Players::default();
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("Unable to accept a connection: {}", err);
+                continue;
+            }
+        };
+        let theme = theme.clone();
+        let players = players.clone();
+        thread::spawn(move || {
+            let peer = stream.peer_addr().ok();
+            if let Err(err) = handle_connection(stream, theme, ascii, &players) {
+                eprintln!("Connection {:?} ended with an error: {}", peer, err);
+            }
+        });
+    }
+}
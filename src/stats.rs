@@ -0,0 +1,348 @@
+use crate::{
+    campaign::{Campaign, CAMPAIGN_PATH},
+    level::{Coord, ItemVariant, Level, Verb},
+    map::{search, RoomGraph, DIRECTIONS},
+    passage::Passage,
+    utils::try_parse_yml_str,
+    ItemDatabase, ITEMS_PATH,
+};
+use serde_yaml::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    fs,
+};
+
+/// Load the levels of the campaign, or a single level at a path, and report on their
+/// content.
+pub fn stats_file(level_path: Option<&str>) -> Result<String, String> {
+    let load = |path: &str| {
+        fs::read_to_string(path).map_err(|err| format!("Unable to read {}: {}", path, err))
+    };
+    let paths: Vec<(String, String)> = match level_path {
+        Some(path) => vec![(path.to_string(), path.to_string())],
+        None => {
+            let campaign: Campaign = try_parse_yml_str(&load(CAMPAIGN_PATH)?, CAMPAIGN_PATH)?;
+            campaign.levels.into_iter().collect()
+        }
+    };
+    let mut levels = Vec::new();
+    for (id, path) in paths {
+        let level: Level = try_parse_yml_str(&load(&path)?, &path)?;
+        levels.push((id, level));
+    }
+    let item_db: ItemDatabase = try_parse_yml_str(&load(ITEMS_PATH)?, ITEMS_PATH)?;
+    Ok(stats(&levels, &item_db))
+}
+
+/// Count what's in each level, to find the content that players never see and the
+/// rooms that take the longest to get to. The items are shared between levels, so
+/// the ones that are never placed are reported once for all of them.
+pub fn stats(levels: &[(String, Level)], item_db: &ItemDatabase) -> String {
+    let mut out = String::new();
+    let mut placed = BTreeSet::new();
+    for (id, level) in levels {
+        writeln!(out, "{}", id).unwrap();
+        level_stats(level, &mut out);
+        let value = serde_yaml::to_value(level).expect("Unable to serialize the level.");
+        find_placed_items(&value, None, &mut placed);
+        writeln!(out).unwrap();
+    }
+    placed.extend(item_db.recipes().iter().map(|recipe| recipe.output.clone()));
+    // Money is given as change, so it doesn't need to be placed.
+    let unplaced: Vec<&str> = item_db
+        .iter()
+        .filter(|item| item.variant != ItemVariant::Money && !placed.contains(&item.id))
+        .map(|item| item.id.as_str())
+        .collect();
+    writeln!(out, "Items never placed: {}", list(&unplaced)).unwrap();
+    out
+}
+
+fn level_stats(level: &Level, out: &mut String) {
+    let words = |passages: &mut dyn Iterator<Item = &Passage>| -> usize {
+        passages
+            .flat_map(Passage::texts)
+            .map(|text| text.split_whitespace().count())
+            .sum()
+    };
+    let room_words: usize = level
+        .rooms
+        .iter()
+        .map(|room| {
+            let actions = room.actions.iter().flatten().map(|action| &action.value);
+            room.title.split_whitespace().count()
+                + words(
+                    &mut std::iter::once(&room.description)
+                        .chain(room.details.iter())
+                        .chain(room.death.iter())
+                        .chain(actions),
+                )
+        })
+        .sum();
+    let region_words: usize = level
+        .regions
+        .values()
+        .map(|region| {
+            let actions = region.actions.iter().map(|action| &action.value);
+            words(&mut region.description.iter().chain(actions))
+        })
+        .sum();
+    let npc_words: usize = level
+        .npcs
+        .values()
+        .map(|npc| {
+            let topics = npc.topics.iter().map(|topic| &topic.value);
+            words(
+                &mut std::iter::once(&npc.description)
+                    .chain(npc.descriptions.iter())
+                    .chain(std::iter::once(&npc.talk))
+                    .chain(topics),
+            )
+        })
+        .sum();
+    writeln!(
+        out,
+        "  Words:       {} (rooms {}, regions {}, NPCs {})",
+        room_words + region_words + npc_words,
+        room_words,
+        region_words,
+        npc_words
+    )
+    .unwrap();
+
+    let rooms: Vec<_> = level.rooms.iter().collect();
+    writeln!(out, "  Rooms:       {}", rooms.len()).unwrap();
+    let mut regions: BTreeMap<&str, usize> = level
+        .regions
+        .keys()
+        .map(|region| (region.as_str(), 0))
+        .collect();
+    for room in rooms.iter() {
+        for region in room.regions.iter() {
+            *regions.entry(region).or_default() += 1;
+        }
+    }
+    let outside = rooms.iter().filter(|room| room.regions.is_empty()).count();
+    for (region, count) in regions.iter() {
+        writeln!(out, "    {:<16}{}", region, count).unwrap();
+    }
+    if !regions.is_empty() && outside > 0 {
+        writeln!(out, "    {:<16}{}", "(no region)", outside).unwrap();
+    }
+
+    // Walk the map from the entry, following the exits within the level.
+    let room_graph = match RoomGraph::parse(level) {
+        Ok(room_graph) => room_graph,
+        Err(message) => {
+            writeln!(out, "  Unable to walk the map: {}", message).unwrap();
+            return;
+        }
+    };
+    let walk = search(level.entry, None, |coord: Coord| {
+        let room = level.get_room(&coord);
+        let room_info = room_graph.get(&coord);
+        DIRECTIONS
+            .iter()
+            .filter_map(|direction| match room?.exits.get(direction) {
+                Some(exit) if exit.level.is_none() => level.find_entry(&exit.entry),
+                Some(_) => None,
+                None => *room_info?.in_direction(direction),
+            })
+            .map(|next| (next, ()))
+            .collect::<Vec<_>>()
+    });
+    let farthest = rooms
+        .iter()
+        .filter_map(|room| Some((walk.path_to(&room.coord)?.len(), *room)))
+        .max_by_key(|(moves, room)| (*moves, std::cmp::Reverse(room.coord)));
+    if let Some((moves, room)) = farthest {
+        writeln!(
+            out,
+            "  Farthest:    {} {}, {} moves from the entry",
+            room.title, room.coord, moves
+        )
+        .unwrap();
+    }
+    let unreachable: Vec<&str> = rooms
+        .iter()
+        .filter(|room| walk.path_to(&room.coord).is_none())
+        .map(|room| room.title.as_str())
+        .collect();
+    if !unreachable.is_empty() {
+        writeln!(out, "  Unreachable: {}", list(&unreachable)).unwrap();
+    }
+
+    let mut verbs: BTreeMap<String, usize> = BTreeMap::new();
+    let actions = rooms
+        .iter()
+        .flat_map(|room| room.actions.iter().flatten())
+        .chain(
+            level
+                .regions
+                .values()
+                .flat_map(|region| region.actions.iter()),
+        );
+    for action in actions {
+        let verb = match (&action.verb, &action.alias) {
+            (Verb::Custom, Some(alias)) => alias.to_lowercase(),
+            (verb, _) => format!("{:?}", verb).to_lowercase(),
+        };
+        *verbs.entry(verb).or_default() += 1;
+    }
+    let verbs: Vec<String> = verbs
+        .iter()
+        .map(|(verb, count)| format!("{} {}", verb, count))
+        .collect();
+    writeln!(out, "  Verbs:       {}", list(&verbs)).unwrap();
+
+    // An NPC is referenced if anything but the NPC itself mentions its id.
+    let mut mentioned = BTreeSet::new();
+    let value = serde_yaml::to_value(level).expect("Unable to serialize the level.");
+    if let Value::Mapping(mapping) = value {
+        for (key, value) in mapping.iter() {
+            if key.as_str() != Some("npcs") {
+                find_strings(value, &mut mentioned);
+            }
+        }
+    }
+    let mut unreferenced: Vec<&str> = level
+        .npcs
+        .keys()
+        .map(String::as_str)
+        .filter(|id| {
+            let by_others = level.npcs.iter().any(|(other, npc)| {
+                let mut strings = BTreeSet::new();
+                let value = serde_yaml::to_value(npc).expect("Unable to serialize the NPC.");
+                find_strings(&value, &mut strings);
+                other != id && strings.contains(*id)
+            });
+            !mentioned.contains(*id) && !by_others
+        })
+        .collect();
+    unreferenced.sort_unstable();
+    if !unreferenced.is_empty() {
+        writeln!(out, "  Unreferenced NPCs: {}", list(&unreferenced)).unwrap();
+    }
+}
+
+/// Every string in a value.
+fn find_strings(value: &Value, strings: &mut BTreeSet<String>) {
+    match value {
+        Value::String(text) => {
+            strings.insert(text.clone());
+        }
+        Value::Sequence(items) => items.iter().for_each(|item| find_strings(item, strings)),
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping.iter() {
+                find_strings(key, strings);
+                find_strings(value, strings);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The ids of the items that a level puts somewhere the player can get them: in rooms,
+/// with NPCs, in the player's start, or revealed and given by effects.
+fn find_placed_items(value: &Value, key: Option<&str>, placed: &mut BTreeSet<String>) {
+    match value {
+        Value::String(id) if key == Some("id") || key == Some("items") => {
+            placed.insert(id.clone());
+        }
+        Value::Sequence(values) => {
+            for value in values {
+                find_placed_items(value, key, placed);
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (inner_key, value) in mapping.iter() {
+                let inner_key = inner_key.as_str();
+                if let (Some("reveal"), Value::Mapping(items)) = (inner_key, value) {
+                    placed.extend(
+                        items
+                            .iter()
+                            .filter_map(|(id, _)| id.as_str())
+                            .map(String::from),
+                    );
+                }
+                find_placed_items(value, inner_key, placed);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn list<T: AsRef<str>>(names: &[T]) -> String {
+    match names.len() {
+        0 => "none".into(),
+        _ => names
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let level: Level = serde_yaml::from_str(
+            r#"
+maps:
+  - - "------"
+    - "-..-.-"
+    - "--.---"
+    - "------"
+entry: [1, 1, 0]
+npcs:
+  fisher: { name: Fisher, targets: [fisher], talk: Fish for sale., items: [] }
+  hermit: { name: Hermit, targets: [hermit], talk: Go away., items: [] }
+regions:
+  docks: { actions: [{ verb: Look, targets: [sea], value: The sea is grey. }] }
+rooms:
+  - title: Gate
+    coord: [1, 1, 0]
+    description: A gate.
+    items: [{ id: apple, quantity: 1 }]
+  - title: Road
+    coord: [2, 1, 0]
+    description: A road to the docks.
+    regions: [docks]
+    npcs: [fisher]
+    actions:
+      - { verb: Custom, alias: Dance, targets: [road], value: You dance. }
+  - { title: Pier, coord: [2, 2, 0], description: A pier., regions: [docks] }
+  - { title: Island, coord: [4, 1, 0], description: An island. }
+"#,
+        )
+        .unwrap();
+        let item_db = ItemDatabase::from_items(
+            serde_yaml::from_str(
+                r#"
+- { id: apple, name: apple, variant: Consumable, targets: [apple], description: An apple. }
+- { id: crown, name: crown, variant: Weapon, targets: [crown], description: A crown. }
+- { id: gold, name: gold, variant: Money, targets: [gold], description: Gold. }
+"#,
+            )
+            .unwrap(),
+        );
+        insta::assert_snapshot!(stats(&[("docks".into(), level)], &item_db), @r###"
+        ---
+        docks
+          Words:       26 (rooms 17, regions 4, NPCs 5)
+          Rooms:       4
+            docks           2
+            (no region)     2
+          Farthest:    Pier [2, 2, 0], 2 moves from the entry
+          Unreachable: Island
+          Verbs:       dance 1, look 1
+          Unreferenced NPCs: hermit
+
+        Items never placed: crown
+        "###);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::utils::parse_yml;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, process};
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    Grey,
+}
+
+impl Color {
+    /// The ANSI escape code for the foreground color.
+    fn ansi_code(&self) -> &str {
+        match self {
+            Color::Red => "31",
+            Color::Green => "32",
+            Color::Yellow => "33",
+            Color::Blue => "34",
+            Color::Magenta => "35",
+            Color::Cyan => "36",
+            Color::White => "97",
+            Color::Grey => "90",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TextStyle {
+    #[serde(default)]
+    pub color: Option<Color>,
+    #[serde(default)]
+    pub bold: bool,
+    #[serde(default)]
+    pub italic: bool,
+}
+
+impl TextStyle {
+    /// Apply the style to every line separately, so that indentation and line
+    /// prefixes are left alone.
+    pub fn apply(&self, text: &str) -> String {
+        text.split('\n')
+            .map(|line| {
+                let trimmed = line.trim_start();
+                if trimmed.is_empty() {
+                    return line.to_string();
+                }
+                let indent = &line[..line.len() - trimmed.len()];
+                format!("{}{}", indent, self.apply_line(trimmed))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn apply_line(&self, text: &str) -> String {
+        let mut codes = Vec::new();
+        if self.bold {
+            codes.push("1");
+        }
+        if self.italic {
+            codes.push("3");
+        }
+        if let Some(ref color) = self.color {
+            codes.push(color.ansi_code());
+        }
+        if codes.is_empty() {
+            return text.to_string();
+        }
+        format!("\u{1b}[{}m{}\u{1b}[0m", codes.join(";"), text)
+    }
+}
+
+/// The different kinds of text that a theme can style.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Style {
+    Title,
+    Exits,
+    Item,
+    Dialogue,
+    Error,
+    /// Text marked with `*emphasis*` in a room's description.
+    Emphasis,
+}
+
+/// A theme assigns a text style to each kind of output. Themes are defined in
+/// data/themes.yml.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Theme {
+    #[serde(default)]
+    pub title: TextStyle,
+    #[serde(default)]
+    pub exits: TextStyle,
+    #[serde(default)]
+    pub item: TextStyle,
+    #[serde(default)]
+    pub dialogue: TextStyle,
+    #[serde(default)]
+    pub error: TextStyle,
+    #[serde(default)]
+    pub emphasis: TextStyle,
+}
+
+impl Theme {
+    pub fn load(name: &str) -> Theme {
+        Theme::try_load(name).unwrap_or_else(|message| {
+            eprintln!("{}", message);
+            process::exit(1);
+        })
+    }
+
+    /// Load a theme by name, or explain which themes there are.
+    pub fn try_load(name: &str) -> Result<Theme, String> {
+        let mut themes: HashMap<String, Theme> = parse_yml(&"data/themes.yml".into());
+        themes.remove(name).ok_or_else(|| {
+            let mut names: Vec<String> = themes.keys().map(|key| format!("{:?}", key)).collect();
+            names.sort();
+            format!(
+                "Unable to find the theme {:?}. The available themes are: {}",
+                name,
+                names.join(", ")
+            )
+        })
+    }
+
+    pub fn text_style(&self, style: Style) -> &TextStyle {
+        match style {
+            Style::Title => &self.title,
+            Style::Exits => &self.exits,
+            Style::Item => &self.item,
+            Style::Dialogue => &self.dialogue,
+            Style::Error => &self.error,
+            Style::Emphasis => &self.emphasis,
+        }
+    }
+
+    pub fn apply(&self, style: Style, text: &str) -> String {
+        self.text_style(style).apply(text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::strip_ansi;
+
+    #[test]
+    fn test_theme() {
+        let theme: Theme = serde_yaml::from_str(
+            "
+            title:
+              color: Cyan
+              bold: true
+            ",
+        )
+        .unwrap();
+        let styled = theme.apply(Style::Title, "  Stone End\n\nDocks");
+        assert_ne!(styled, "  Stone End\n\nDocks");
+        // Indentation and blank lines are left unstyled.
+        assert!(styled.starts_with("  \u{1b}["));
+        assert!(styled.contains("\n\n\u{1b}["));
+        assert_eq!(
+            strip_ansi(styled.as_bytes()),
+            "  Stone End\n\nDocks".as_bytes()
+        );
+        assert_eq!(theme.apply(Style::Error, "Oops"), "Oops");
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{level::Verb, utils::edit_distance, Environment, Game};
+use std::io::Write;
+
+/// The verbs understood by the parser, used to suggest corrections to typos. A test
+/// checks that these keep up with `parse_single_command`.
+const VERBS: &[&str] = &[
+    "look",
+    "talk",
+    "north",
+    "east",
+    "south",
+    "west",
+    "inventory",
+    "inv",
+    "items",
+    "go",
+    "help",
+    "debug",
+    "map",
+    "exits",
+    "where",
+    "drop",
+    "pick",
+    "pickup",
+    "take",
+    "grab",
+    "board",
+    "mount",
+    "dismount",
+    "disembark",
+    "quit",
+    "exit",
+    "restart",
+    "compare",
+    "reload",
+    "feedback",
+    "score",
+    "undo",
+    "config",
+    "mute",
+    "filter",
+    "eat",
+    "drink",
+    "consume",
+    "say",
+    "answer",
+    "ask",
+    "bye",
+    "goodbye",
+    "give",
+    "combine",
+    "craft",
+    "buy",
+    "purchase",
+    "money",
+    "purse",
+    "wallet",
+    "deposit",
+    "store",
+    "stash",
+    "withdraw",
+    "retrieve",
+    "read",
+    "lore",
+    "journal",
+    "quests",
+    "search",
+    "push",
+    "pull",
+    "move",
+    "throw",
+    "toss",
+    "hurl",
+    "disarm",
+    "goto",
+    "offer",
+    "haggle",
+    "bid",
+    "pet",
+    "pat",
+    "stroke",
+    "feed",
+    "send",
+    "oops",
+    "smile",
+    "bow",
+    "wave",
+    "dance",
+];
+
+/// Find the closest candidate to a mistyped word, if it is within the level's
+/// configured suggestion distance. Very short candidates like "n" are skipped, as
+/// nearly any short typo would be close to them.
+fn closest<'a, T: Environment>(
+    game: &Game<T>,
+    word: &str,
+    candidates: impl Iterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let threshold = game.level.suggestion_distance;
+    candidates
+        .filter(|candidate| candidate.len() >= 3 && *candidate != word)
+        .map(|candidate| (edit_distance(word, candidate), candidate))
+        .filter(|(distance, _)| *distance <= threshold && *distance < word.len())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// All of the verbs the player could use in the current room, including custom
+/// verbs from the room's actions, and the verbs of registered commands.
+fn known_verbs<T: Environment>(game: &Game<T>) -> Vec<&str> {
+    let mut verbs: Vec<&str> = VERBS.to_vec();
+    for handler in game.handlers.iter() {
+        verbs.extend(handler.verbs());
+    }
+    for action in game.room.actions_iter(&game.level) {
+        if action.verb == Verb::Custom {
+            if let Some(ref alias) = action.alias {
+                verbs.push(alias);
+            }
+        }
+    }
+    verbs
+}
+
+/// All of the nouns the player can currently see or is carrying.
+fn visible_targets<T: Environment>(game: &Game<T>) -> Vec<&str> {
+    let mut targets: Vec<&str> = Vec::new();
+    for action in game.room.actions_iter(&game.level) {
+        targets.extend(action.targets.iter().map(String::as_str));
+    }
+    for (_, npc) in game.npcs_at(&game.room.coord) {
+        targets.extend(npc.targets.iter().map(String::as_str));
+    }
+    for (crowd, _) in game.room.crowds_iter(&game.level) {
+        targets.extend(crowd.targets.iter().map(String::as_str));
+    }
+    if let Some(room_inventory) = game.save_state.room_inventories.get(&game.room.coord) {
+        for (room_item, inventory_item) in room_inventory.visible_iter() {
+            targets.extend(room_item.targets.iter().map(String::as_str));
+            targets.extend(inventory_item.targets.iter().map(String::as_str));
+        }
+    }
+    for item in game.save_state.inventory.items.iter() {
+        targets.push(&item.name);
+        targets.extend(item.targets.iter().map(String::as_str));
+    }
+    targets
+}
+
+/// Print a suggestion for a mistyped verb, if there is a close match.
+pub fn suggest_verb<T: Environment>(game: &Game<T>, verb: &str) {
+    let verbs = known_verbs(game);
+    if let Some(suggestion) = closest(game, verb, verbs.into_iter()) {
+        writeln!(game.output(), "Did you mean '{}'?", suggestion).unwrap();
+    }
+}
+
+/// Print a suggestion for a mistyped target, if there is a close match.
+pub fn suggest_target<T: Environment>(game: &Game<T>, target: &str) {
+    let targets = visible_targets(game);
+    if let Some(suggestion) = closest(game, target, targets.into_iter()) {
+        writeln!(game.output(), "Did you mean '{}'?", suggestion).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::VERBS;
+    use crate::testing::*;
+
+    #[test]
+    fn test_suggest_verb() {
+        insta::assert_yaml_snapshot!(run_game(vec!["lok"]), @r###"
+        ---
+        - "You don't know how to \"lok\". Type \"help\" for help."
+        - "Did you mean 'look'?"
+        "###);
+        for (typo, verb) in [("serch", "search"), ("depositt", "deposit")] {
+            let suggestion = format!("Did you mean '{}'?", verb);
+            assert!(run_game(vec![typo]).contains(&suggestion));
+        }
+    }
+
+    /// The parser matches on the verbs in `parse_single_command`, so read them from
+    /// there to check that none of them are left out of the suggestions. Verbs shorter
+    /// than three letters are never suggested.
+    #[test]
+    fn test_verbs_match_the_parser() {
+        let source = include_str!("lib.rs");
+        let start = source.find("fn parse_single_command(").unwrap();
+        let end = start + source[start..].find("\n}\n").unwrap();
+        let parsed: Vec<&str> = source[start..end]
+            .lines()
+            .filter(|line| line.starts_with("        \"") && line.contains("=>"))
+            .flat_map(|line| line.split("=>").next().unwrap().split('|'))
+            .map(|verb| verb.trim().trim_matches('"'))
+            .filter(|verb| verb.len() >= 3)
+            .collect();
+        assert!(parsed.len() > 50);
+        for verb in parsed {
+            assert!(VERBS.contains(&verb), "{:?} is missing from VERBS.", verb);
+        }
+    }
+
+    #[test]
+    fn test_suggest_target() {
+        insta::assert_yaml_snapshot!(run_game(vec!["look at gaurd post"]), @r###"
+        ---
+        - "You don't see a gaurd post."
+        - "Did you mean 'guard post'?"
+        - ""
+        "###);
+    }
+}
@@ -0,0 +1,488 @@
+use crate::{
+    campaign::{Date, Today},
+    condition, currency,
+    level::{InventoryItem, Lighting, Weather},
+    ItemDatabase,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What the values in a template are filled in from.
+pub struct Variables<'a> {
+    pub player_name: &'a str,
+    pub item_db: &'a ItemDatabase,
+    pub inventory: &'a [InventoryItem],
+    pub flags: &'a BTreeSet<String>,
+    pub stats: &'a BTreeMap<String, i64>,
+    pub reputation: &'a BTreeMap<String, i64>,
+    /// The ids of the puzzles that have been solved.
+    pub solved: &'a BTreeSet<String>,
+    /// The items companions are carrying for the player, by the NPC's id.
+    pub companions: &'a BTreeMap<String, Vec<InventoryItem>>,
+    /// The ids of the NPCs in the player's party.
+    pub party: &'a BTreeSet<String>,
+    /// The turns left on the timers that are armed, by id.
+    pub timers: &'a BTreeMap<String, usize>,
+    /// How well the player can see in the room they're in.
+    pub light: Lighting,
+    /// The level's weather, when it has a climate.
+    pub weather: Option<Weather>,
+    /// Today's date, when the campaign has a calendar.
+    pub date: Option<Date<'a>>,
+    /// The real date, when the environment has a clock.
+    pub today: Option<Today>,
+}
+
+impl<'a> Variables<'a> {
+    /// Get the text of a value in a template. These are:
+    ///
+    /// - `player_name`, the name from the player's config.
+    /// - `flags.<id>`, 1 if the flag is set and 0 if it isn't.
+    /// - `stats.<id>`, the player's stat, or 0 if they don't have it.
+    /// - `items.<id>`, how many of the item the player is carrying.
+    /// - `items.<id>.<field>`, the item's name, description, or quantity carried.
+    /// - `money`, what the player's coins are worth in the smallest denomination.
+    /// - `reputation.<id>`, the player's reputation with a faction.
+    /// - `puzzles.<id>`, 1 if the puzzle is solved and 0 if it isn't.
+    /// - `companions.<npc>.<id>`, how many of the item the companion is carrying.
+    /// - `party.<npc>`, 1 if the NPC is in the player's party and 0 if they aren't.
+    /// - `timers.<id>`, how many turns are left before the timer goes off, or 0 if it
+    ///   isn't armed.
+    /// - `light.dim`, `light.normal`, and `light.bright`, 1 if the player's room is lit
+    ///   that way and 0 if it isn't.
+    /// - `weather.clear`, `weather.rain`, `weather.fog`, and `weather.storm`, 1 if it's
+    ///   the level's weather and 0 if it isn't.
+    /// - `calendar.day`, `calendar.season`, and `calendar.playthrough`, from the
+    ///   campaign's calendar.
+    /// - `today.month` and `today.day`, from the real date.
+    pub fn get(&self, name: &str) -> Option<String> {
+        if name == "player_name" {
+            return Some(self.player_name.to_string());
+        }
+        if name == "money" {
+            return Some(currency::total(self.inventory).to_string());
+        }
+        if let Some(id) = name.strip_prefix("flags.") {
+            return Some((self.flags.contains(id) as i64).to_string());
+        }
+        if let Some(id) = name.strip_prefix("puzzles.") {
+            return Some((self.solved.contains(id) as i64).to_string());
+        }
+        if let Some(id) = name.strip_prefix("party.") {
+            return Some((self.party.contains(id) as i64).to_string());
+        }
+        if let Some(id) = name.strip_prefix("stats.") {
+            return Some(self.stats.get(id).copied().unwrap_or(0).to_string());
+        }
+        if let Some(id) = name.strip_prefix("timers.") {
+            return Some(self.timers.get(id).copied().unwrap_or(0).to_string());
+        }
+        if let Some(level) = name.strip_prefix("light.") {
+            return match level {
+                "dim" | "normal" | "bright" => {
+                    Some(((self.light.lowercase_str() == level) as i64).to_string())
+                }
+                _ => None,
+            };
+        }
+        if let Some(kind) = name.strip_prefix("weather.") {
+            return match kind {
+                "clear" | "rain" | "fog" | "storm" => {
+                    let weather = self.weather.map(|weather| weather.lowercase_str());
+                    Some(((weather == Some(kind)) as i64).to_string())
+                }
+                _ => None,
+            };
+        }
+        if let Some(field) = name.strip_prefix("calendar.") {
+            let date = self.date?;
+            return match field {
+                "day" => Some(date.day.to_string()),
+                "season" => Some(date.season.to_string()),
+                "playthrough" => Some(date.playthrough.to_string()),
+                _ => None,
+            };
+        }
+        if let Some(field) = name.strip_prefix("today.") {
+            let today = self.today?;
+            return match field {
+                "month" => Some(today.month.to_string()),
+                "day" => Some(today.day.to_string()),
+                _ => None,
+            };
+        }
+        if let Some(id) = name.strip_prefix("reputation.") {
+            return Some(self.reputation.get(id).copied().unwrap_or(0).to_string());
+        }
+        if let Some(item) = name.strip_prefix("items.") {
+            let (id, field) = match item.split_once('.') {
+                Some((id, field)) => (id, field),
+                None => (item, "quantity"),
+            };
+            let item = self.item_db.find(id)?;
+            return match field {
+                "name" => Some(item.name.clone()),
+                "description" => Some(item.description.trim_end().to_string()),
+                "quantity" => Some(self.carried(id).to_string()),
+                _ => None,
+            };
+        }
+        if let Some(rest) = name.strip_prefix("companions.") {
+            let (npc, id) = rest.split_once('.')?;
+            self.item_db.find(id)?;
+            let carried: usize = self
+                .companions
+                .get(npc)
+                .into_iter()
+                .flatten()
+                .filter(|item| item.id == id)
+                .map(|item| item.quantity)
+                .sum();
+            return Some(carried.to_string());
+        }
+        None
+    }
+
+    fn carried(&self, id: &str) -> usize {
+        self.inventory
+            .iter()
+            .filter(|item| item.id == id)
+            .map(|item| item.quantity)
+            .sum()
+    }
+}
+
+/// A piece of a parsed template.
+#[derive(Debug, PartialEq)]
+enum Node<'t> {
+    Text(&'t str),
+    /// A value to fill in, like {player_name}.
+    Value(&'t str),
+    /// A block like {if flags.sailor}...{else}...{end}, see `Condition`.
+    If {
+        condition: &'t str,
+        then: Vec<Node<'t>>,
+        otherwise: Vec<Node<'t>>,
+    },
+}
+
+/// Fill in the values of a template, and choose its conditional blocks, like:
+///
+/// ```yml
+/// description: |
+///   "Welcome back, {player_name}." {if items.torch == 0}It's dark in here.{end}
+/// ```
+///
+/// Values that aren't known are left as they are written, as is a template that
+/// doesn't parse, so that the mistake can be seen. Validating the level reports both.
+pub fn render(text: &str, variables: &Variables) -> String {
+    if !text.contains('{') {
+        return text.to_string();
+    }
+    match parse(text) {
+        Ok(nodes) => {
+            let mut rendered = String::new();
+            render_nodes(&nodes, variables, &mut rendered);
+            rendered
+        }
+        Err(_) => text.to_string(),
+    }
+}
+
+fn render_nodes(nodes: &[Node], variables: &Variables, rendered: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => rendered.push_str(text),
+            Node::Value(name) => match variables.get(name) {
+                Some(value) => rendered.push_str(&value),
+                None => {
+                    rendered.push('{');
+                    rendered.push_str(name);
+                    rendered.push('}');
+                }
+            },
+            Node::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                if condition::evaluate(condition, variables).unwrap_or(false) {
+                    render_nodes(then, variables, rendered);
+                } else {
+                    render_nodes(otherwise, variables, rendered);
+                }
+            }
+        }
+    }
+}
+
+/// Check that a template parses, and that every value in it is known. The flags and
+/// stats can't be checked, as any of them can be set while playing.
+pub fn check(text: &str, item_db: &ItemDatabase) -> Result<(), String> {
+    let variables = Variables {
+        player_name: "",
+        item_db,
+        inventory: &[],
+        flags: &BTreeSet::new(),
+        stats: &BTreeMap::new(),
+        reputation: &BTreeMap::new(),
+        solved: &BTreeSet::new(),
+        companions: &BTreeMap::new(),
+        party: &BTreeSet::new(),
+        timers: &BTreeMap::new(),
+        light: Lighting::Normal,
+        weather: Some(Weather::Clear),
+        // Any campaign could have a calendar.
+        date: Some(Date::default()),
+        today: Some(Today::default()),
+    };
+    let nodes = parse(text)?;
+    let mut names = Vec::new();
+    let mut conditions = Vec::new();
+    collect_names(&nodes, &mut names, &mut conditions);
+    for condition in conditions {
+        condition::check(condition, item_db)
+            .map_err(|problem| format!("{} in the condition {{if {}}}", problem, condition))?;
+    }
+    for name in names {
+        if variables.get(name).is_none() {
+            return Err(match name.strip_prefix("items.") {
+                Some(item) if item_db.find(item.split('.').next().unwrap_or("")).is_some() => {
+                    format!(
+                        "the unknown item value {{{}}}, the values are name, description, and \
+                     quantity",
+                        name
+                    )
+                }
+                Some(_) => format!("the unknown item {{{}}}", name),
+                None => format!("the unknown value {{{}}}", name),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn collect_names<'t>(nodes: &[Node<'t>], names: &mut Vec<&'t str>, conditions: &mut Vec<&'t str>) {
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Value(name) => names.push(name),
+            Node::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                conditions.push(condition);
+                collect_names(then, names, conditions);
+                collect_names(otherwise, names, conditions);
+            }
+        }
+    }
+}
+
+/// An {if} block that hasn't reached its {end} yet.
+struct OpenBlock<'t> {
+    condition: &'t str,
+    /// The nodes from before the block.
+    before: Vec<Node<'t>>,
+    /// The nodes of the first branch, once the {else} is reached.
+    then: Option<Vec<Node<'t>>>,
+}
+
+fn parse(text: &str) -> Result<Vec<Node<'_>>, String> {
+    let mut open: Vec<OpenBlock> = Vec::new();
+    let mut nodes = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let after = &rest[start + 1..];
+        // Braces that don't close on the same line are just text.
+        let end = match after.find(['}', '{', '\n']) {
+            Some(end) if after[end..].starts_with('}') => end,
+            _ => {
+                nodes.push(Node::Text(&rest[..start + 1]));
+                rest = after;
+                continue;
+            }
+        };
+        if start > 0 {
+            nodes.push(Node::Text(&rest[..start]));
+        }
+        let tag = after[..end].trim();
+        rest = &after[end + 1..];
+
+        if let Some(condition) = tag.strip_prefix("if ") {
+            open.push(OpenBlock {
+                condition: condition.trim(),
+                before: std::mem::take(&mut nodes),
+                then: None,
+            });
+        } else if tag == "else" {
+            match open.last_mut() {
+                Some(block) if block.then.is_none() => {
+                    block.then = Some(std::mem::take(&mut nodes))
+                }
+                Some(_) => return Err("an {if} block with two {else}s".into()),
+                None => return Err("an {else} outside of an {if} block".into()),
+            }
+        } else if tag == "end" {
+            let block = match open.pop() {
+                Some(block) => block,
+                None => return Err("an {end} outside of an {if} block".into()),
+            };
+            let (then, otherwise) = match block.then {
+                Some(then) => (then, std::mem::replace(&mut nodes, block.before)),
+                None => (std::mem::replace(&mut nodes, block.before), Vec::new()),
+            };
+            nodes.push(Node::If {
+                condition: block.condition,
+                then,
+                otherwise,
+            });
+        } else {
+            nodes.push(Node::Value(tag));
+        }
+    }
+    if !rest.is_empty() {
+        nodes.push(Node::Text(rest));
+    }
+    match open.last() {
+        Some(block) => Err(format!(
+            "an {{if {}}} block with no {{end}}",
+            block.condition
+        )),
+        None => Ok(nodes),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn test_render() {
+        let item_db = ItemDatabase::new();
+        let mut sword = item_db.find("sword").unwrap().clone();
+        sword.quantity = 1;
+        let flags: BTreeSet<String> = vec!["sailor".to_string()].into_iter().collect();
+        let stats: BTreeMap<String, i64> = vec![("visits".to_string(), 3)].into_iter().collect();
+        let variables = Variables {
+            player_name: "Ada",
+            item_db: &item_db,
+            inventory: &[sword],
+            flags: &flags,
+            stats: &stats,
+            reputation: &BTreeMap::new(),
+            solved: &BTreeSet::new(),
+            companions: &BTreeMap::new(),
+            party: &BTreeSet::new(),
+            timers: &BTreeMap::new(),
+            light: Lighting::Normal,
+            weather: Some(Weather::Clear),
+            date: None,
+            today: None,
+        };
+        let render = |text| render(text, &variables);
+
+        assert_eq!(render("Hello, {player_name}."), "Hello, Ada.");
+        assert_eq!(
+            render("You hold a {items.sword.name}."),
+            "You hold a sword."
+        );
+        assert_eq!(
+            render("Visit {stats.visits}, {flags.sailor}, {flags.cook}."),
+            "Visit 3, 1, 0."
+        );
+        assert_eq!(
+            render("{if items.sword}Light.{else}Dark.{end} {if not flags.cook}No cook.{end}"),
+            "Light. No cook."
+        );
+        assert_eq!(
+            render("{if flags.sailor}Ahoy{if items.gold}, rich one{end}!{end}"),
+            "Ahoy!"
+        );
+        // Mistakes are left in the text.
+        assert_eq!(
+            render("A {mystery} and a { brace"),
+            "A {mystery} and a { brace"
+        );
+        assert_eq!(
+            render("{if flags.sailor}Unclosed."),
+            "{if flags.sailor}Unclosed."
+        );
+
+        assert_eq!(
+            check("{if items.sword}{items.sword.name}{end}", &item_db),
+            Ok(())
+        );
+        assert_eq!(
+            check("{items.sword.color}", &item_db),
+            Err(
+                "the unknown item value {items.sword.color}, the values are name, description, \
+                 and quantity"
+                    .into()
+            )
+        );
+        assert_eq!(
+            check("{if flags.sailor}Ahoy", &item_db),
+            Err("an {if flags.sailor} block with no {end}".into())
+        );
+        assert_eq!(
+            check("{if items.gold >= 10 && flags.rich}Rich.{end}", &item_db),
+            Ok(())
+        );
+        assert_eq!(
+            check("{if items.sword 2}Two.{end}", &item_db),
+            Err("an unexpected 2 in the condition {if items.sword 2}".into())
+        );
+        assert_eq!(
+            check("Ahoy{end}", &item_db),
+            Err("an {end} outside of an {if} block".into())
+        );
+    }
+
+    #[test]
+    fn test_templates() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+start: { items: [{ id: apple, quantity: 2 }], flags: [sailor] }
+rooms:
+  - title: Galley
+    coord: [1, 1, 0]
+    description: |
+      Welcome aboard, {player_name}.{if flags.sailor} The cook salutes you.{end}
+
+      {if items.apple}You have {items.apple} {items.apple.name}s.{else}Your pockets are empty.{end}
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        assert!(game.step("config name Ada".into()).is_none());
+        assert!(game.step("look".into()).is_none());
+        assert!(game.step("drop apple".into()).is_none());
+        assert!(game.step("look".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Changed the name setting.
+        - Galley
+        - ""
+        - "    Welcome aboard, Ada. The cook salutes you. "
+        - ""
+        - "    You have 2 apples. "
+        - ""
+        - ""
+        - "Exits: _ _ _ _"
+        - You dropped the 2 apples.
+        - Galley
+        - ""
+        - "    Welcome aboard, Ada. The cook salutes you. "
+        - ""
+        - "    Your pockets are empty. "
+        - ""
+        - ""
+        - 2 apples
+        - "Exits: _ _ _ _"
+        "###);
+    }
+}
@@ -0,0 +1,249 @@
+use crate::{
+    audio::Audio,
+    utils::{strip_ansi, to_ascii},
+    Config, Cue, Environment, GameOptions, TextSpeed, Theme, CONFIG_PATH, SAVE_PATH,
+};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal::{disable_raw_mode, enable_raw_mode},
+};
+use std::{
+    io::{IsTerminal, Stdout, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// The plain terminal frontend, which reads input a line at a time from stdin.
+pub struct Terminal {
+    stdout: Stdout,
+    theme: Option<Theme>,
+    options: GameOptions,
+    /// Whether a player is at the keyboard, rather than the input being piped in.
+    interactive: bool,
+    /// Whether the player pressed enter to show the rest of this turn's text at once.
+    skipping: bool,
+    /// Whether the terminal is in raw mode, which it stays in while this turn's text is
+    /// typed out, and leaves for the prompt.
+    raw: bool,
+    /// What the player typed while the text was typed out, which starts their next
+    /// command.
+    typed_ahead: String,
+    audio: Audio,
+}
+
+impl Terminal {
+    pub fn new(theme: Option<Theme>, options: GameOptions) -> Terminal {
+        Terminal {
+            stdout: std::io::stdout(),
+            theme,
+            options,
+            interactive: std::io::stdin().is_terminal() && std::io::stdout().is_terminal(),
+            skipping: false,
+            raw: false,
+            typed_ahead: String::new(),
+            audio: Audio::new(),
+        }
+    }
+
+    /// Type out the text a character or a line at a time, as set by the player's
+    /// config. The keys are read in raw mode so that enter can skip ahead without being
+    /// echoed, which means newlines have to return the cursor themselves. Raw mode lasts
+    /// until the next prompt, see `end_raw_mode`.
+    fn type_out(&mut self, text: &str) -> Result<(), std::io::Error> {
+        if !self.raw {
+            enable_raw_mode()?;
+            self.raw = true;
+        }
+        self.type_out_raw(text)
+    }
+
+    fn end_raw_mode(&mut self) -> Result<(), std::io::Error> {
+        if self.raw {
+            disable_raw_mode()?;
+            self.raw = false;
+        }
+        Ok(())
+    }
+
+    fn type_out_raw(&mut self, text: &str) -> Result<(), std::io::Error> {
+        let speed = self.options.config.text_speed;
+        let delay = Duration::from_millis(self.options.config.text_delay);
+        let mut pending = String::new();
+        let mut chars = text.chars();
+        while let Some(ch) = chars.next() {
+            if ch == '\n' {
+                pending.push('\r');
+            }
+            pending.push(ch);
+            if ch == '\x1b' {
+                // Colors are written along with the next character.
+                for ch in chars.by_ref() {
+                    pending.push(ch);
+                    if ch.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+                continue;
+            }
+            let pause = match speed {
+                TextSpeed::Instant => false,
+                TextSpeed::Characters => !ch.is_whitespace(),
+                TextSpeed::Lines => ch == '\n',
+            };
+            if pause && !self.skipping {
+                self.stdout.write_all(pending.as_bytes())?;
+                self.stdout.flush()?;
+                pending.clear();
+                self.skipping = self.wait_for_enter(delay)?;
+            }
+        }
+        self.stdout.write_all(pending.as_bytes())
+    }
+
+    /// Wait for the delay, returning early with true if enter is pressed. Any other
+    /// typing is kept for the next prompt.
+    fn wait_for_enter(&mut self, delay: Duration) -> Result<bool, std::io::Error> {
+        let deadline = Instant::now() + delay;
+        while event::poll(deadline.saturating_duration_since(Instant::now()))? {
+            let key = match event::read()? {
+                Event::Key(key) if key.kind != KeyEventKind::Release => key,
+                _ => continue,
+            };
+            match key.code {
+                KeyCode::Enter => return Ok(true),
+                KeyCode::Backspace => {
+                    self.typed_ahead.pop();
+                }
+                KeyCode::Char(ch) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.typed_ahead.push(ch)
+                }
+                _ => {}
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.end_raw_mode();
+    }
+}
+
+impl Environment for Terminal {
+    fn get_prompt(&mut self) -> String {
+        self.skipping = false;
+        let mut prompt = format!("{} ", self.options.config.prompt);
+        if self.options.ascii {
+            prompt = String::from_utf8_lossy(&to_ascii(prompt.as_bytes())).into_owned();
+        }
+        self.end_raw_mode().unwrap();
+        // Show what was typed ahead as the start of the command.
+        let typed_ahead = std::mem::take(&mut self.typed_ahead);
+        write!(self.stdout, "{}{}", prompt, typed_ahead).unwrap();
+        self.stdout.flush().unwrap();
+        let reply = rprompt::read_reply().unwrap();
+        let response = format!("{}{}", typed_ahead, reply).to_lowercase();
+        // Add a newline after the prompt.
+        println!();
+        response
+    }
+
+    fn theme(&self) -> Option<&Theme> {
+        self.theme.as_ref()
+    }
+
+    fn seed(&self) -> Option<u64> {
+        self.options.seed
+    }
+
+    fn watch(&self) -> bool {
+        self.options.watch
+    }
+
+    fn jam_started(&self) -> Option<Instant> {
+        self.options.jam_started
+    }
+
+    fn scenario(&self) -> Option<&str> {
+        self.options.scenario.as_deref()
+    }
+
+    fn lang(&self) -> Option<&str> {
+        self.options.lang.as_deref()
+    }
+
+    fn packs(&self) -> &[String] {
+        &self.options.packs
+    }
+
+    fn race(&self) -> bool {
+        self.options.race
+    }
+
+    fn tutorial(&self) -> bool {
+        self.options.tutorial
+    }
+
+    fn save_path(&self) -> Option<PathBuf> {
+        (!self.options.tutorial).then(|| PathBuf::from(SAVE_PATH))
+    }
+
+    fn config(&self) -> Config {
+        self.options.config.clone()
+    }
+
+    fn config_path(&self) -> Option<PathBuf> {
+        Some(PathBuf::from(CONFIG_PATH))
+    }
+
+    fn width(&self) -> Option<usize> {
+        // Leave the last column empty, as some terminals wrap early when a line fills
+        // it.
+        crossterm::terminal::size()
+            .ok()
+            .map(|(columns, _)| (columns as usize).saturating_sub(1))
+    }
+
+    fn play(&mut self, cue: &Cue) {
+        self.audio.play(cue);
+    }
+
+    fn set_config(&mut self, config: &Config) -> Result<(), String> {
+        if config.theme != self.options.config.theme {
+            let theme = Theme::try_load(&config.theme)?;
+            // Colors stay off if they were turned off.
+            if self.theme.is_some() {
+                self.theme = Some(theme);
+            }
+        }
+        self.options.config = config.clone();
+        Ok(())
+    }
+}
+
+impl Write for Terminal {
+    fn write(&mut self, buffer: &[u8]) -> Result<usize, std::io::Error> {
+        let mut output = if self.options.ascii {
+            to_ascii(buffer)
+        } else {
+            buffer.to_vec()
+        };
+        if self.theme.is_none() {
+            // Plain output, strip out any colors that made it into the text.
+            output = strip_ansi(&output);
+        }
+        // Piped input is for scripts and tests, which shouldn't wait on the text.
+        if self.interactive && self.options.config.text_speed != TextSpeed::Instant {
+            self.type_out(&String::from_utf8_lossy(&output))?;
+        } else {
+            self.end_raw_mode()?;
+            self.stdout.write_all(&output)?;
+        }
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        self.stdout.flush()
+    }
+}
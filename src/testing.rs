@@ -0,0 +1,196 @@
+//! The environment that tests play the game in, shared by the tests of every module.
+
+use crate::{
+    audio::Cue, campaign::Today, game_loop, Environment, Game, GameLoopResponse, ItemDatabase,
+    ITEMS_PATH,
+};
+use std::{collections::HashMap, fs, io::Write, path::PathBuf, time::Instant};
+
+pub(crate) struct CommandRunner {
+    pub(crate) commands: Vec<&'static str>,
+    pub(crate) output: Vec<u8>,
+    /// Data files to use in place of the ones on disk.
+    pub(crate) files: HashMap<&'static str, String>,
+    pub(crate) save_path: Option<PathBuf>,
+    pub(crate) feedback_path: Option<PathBuf>,
+    pub(crate) achievements_path: Option<PathBuf>,
+    pub(crate) results_path: Option<PathBuf>,
+    pub(crate) race: bool,
+    pub(crate) races_path: Option<PathBuf>,
+    pub(crate) tutorial: bool,
+    pub(crate) width: Option<usize>,
+    pub(crate) lang: Option<&'static str>,
+    pub(crate) packs: Vec<String>,
+    pub(crate) jam_started: Option<Instant>,
+    pub(crate) new_game_plus_path: Option<PathBuf>,
+    pub(crate) today: Option<Today>,
+    /// The sound cues that the game has played.
+    pub(crate) cues: Vec<Cue>,
+}
+
+impl CommandRunner {
+    pub(crate) fn new(mut commands: Vec<&'static str>) -> CommandRunner {
+        commands.push("quit");
+        commands.reverse();
+        CommandRunner {
+            commands,
+            output: Vec::new(),
+            files: HashMap::new(),
+            save_path: None,
+            feedback_path: None,
+            achievements_path: None,
+            results_path: None,
+            race: false,
+            races_path: None,
+            tutorial: false,
+            width: None,
+            lang: None,
+            packs: Vec::new(),
+            jam_started: None,
+            new_game_plus_path: None,
+            today: None,
+            cues: Vec::new(),
+        }
+    }
+
+    pub(crate) fn get_last_output(self) -> Vec<String> {
+        std::str::from_utf8(&self.output)
+            .unwrap()
+            .lines()
+            .map(|s| s.to_string())
+            .collect()
+    }
+}
+
+/// Take the output of the commands that have been run so far.
+pub(crate) fn take_output(game: &Game<&mut CommandRunner>) -> Vec<String> {
+    let output = std::mem::take(&mut game.output().output);
+    String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+impl Write for CommandRunner {
+    fn write(&mut self, buffer: &[u8]) -> Result<usize, std::io::Error> {
+        for value in buffer.iter() {
+            self.output.push(*value);
+        }
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+}
+
+impl Environment for &mut CommandRunner {
+    fn get_prompt(&mut self) -> String {
+        if self.commands.len() > 1 {
+            // Retain the last output.
+            self.output.clear();
+        }
+        self.commands
+            .pop()
+            .expect("Expected to find a string in the CommandRunner.")
+            .to_string()
+    }
+
+    fn save_path(&self) -> Option<PathBuf> {
+        // Tests start from a fresh game, unless they're given a save.
+        self.save_path.clone()
+    }
+
+    fn seed(&self) -> Option<u64> {
+        Some(0)
+    }
+
+    fn feedback_path(&self) -> Option<PathBuf> {
+        self.feedback_path.clone()
+    }
+
+    fn achievements_path(&self) -> Option<PathBuf> {
+        self.achievements_path.clone()
+    }
+
+    fn results_path(&self) -> Option<PathBuf> {
+        self.results_path.clone()
+    }
+
+    fn race(&self) -> bool {
+        self.race
+    }
+
+    fn tutorial(&self) -> bool {
+        self.tutorial
+    }
+
+    fn races_path(&self) -> Option<PathBuf> {
+        self.races_path.clone()
+    }
+
+    fn width(&self) -> Option<usize> {
+        self.width
+    }
+
+    fn lang(&self) -> Option<&str> {
+        self.lang
+    }
+
+    fn packs(&self) -> &[String] {
+        &self.packs
+    }
+
+    fn jam_started(&self) -> Option<Instant> {
+        self.jam_started
+    }
+
+    fn new_game_plus_path(&self) -> Option<PathBuf> {
+        self.new_game_plus_path.clone()
+    }
+
+    fn today(&self) -> Option<Today> {
+        self.today
+    }
+
+    fn play(&mut self, cue: &Cue) {
+        self.cues.push(cue.clone());
+    }
+
+    fn read_text(&self, path: &str) -> String {
+        match self.files.get(path) {
+            Some(text) => text.clone(),
+            None => fs::read_to_string(path).unwrap(),
+        }
+    }
+
+    fn try_read_text(&self, path: &str) -> Option<String> {
+        match self.files.get(path) {
+            Some(text) => Some(text.clone()),
+            None => fs::read_to_string(path).ok(),
+        }
+    }
+}
+
+pub(crate) fn run_game(commands: Vec<&'static str>) -> Vec<String> {
+    let item_db = ItemDatabase::new();
+    let mut command_runner = CommandRunner::new(commands);
+
+    match game_loop(&item_db, &mut command_runner).unwrap() {
+        GameLoopResponse::Quit => {}
+        GameLoopResponse::Restart => panic!("Unexpected restart."),
+        GameLoopResponse::Death => panic!("Unexpected death."),
+    };
+
+    command_runner.get_last_output()
+}
+
+/// Start a game in a level written for a test, with the game's usual items.
+pub(crate) fn game_from_level<'a>(
+    level: &str,
+    command_runner: &'a mut CommandRunner,
+) -> Game<&'a mut CommandRunner> {
+    let items_yaml = fs::read_to_string(ITEMS_PATH).unwrap();
+    Game::new_from_strings(level, &items_yaml, command_runner).unwrap()
+}
@@ -0,0 +1,287 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefMut,
+    io::{self, Write},
+    ops::{Deref, DerefMut},
+};
+
+/// Optional changes to the game's output, for accessibility, or for frontends that
+/// can't show everything a terminal can, like IRC. They are listed in config.toml, and
+/// applied in the order they are listed.
+///
+/// ```toml
+/// text_filters = ["strip_emoji", "letter_spacing"]
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextFilter {
+    /// Put a space between each letter, which some dyslexic readers find easier. The
+    /// output is wrapped to half the width to make room.
+    LetterSpacing,
+    /// Leave out emoji, for frontends that can't show them.
+    StripEmoji,
+    /// Show the titles of rooms in capitals, so they stand out without colors.
+    UppercaseHeadings,
+}
+
+const FILTERS: &[TextFilter] = &[
+    TextFilter::LetterSpacing,
+    TextFilter::StripEmoji,
+    TextFilter::UppercaseHeadings,
+];
+
+impl TextFilter {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TextFilter::LetterSpacing => "letter_spacing",
+            TextFilter::StripEmoji => "strip_emoji",
+            TextFilter::UppercaseHeadings => "uppercase_headings",
+        }
+    }
+
+    /// Read a list of filters like "strip_emoji, letter_spacing", as the player types
+    /// them in the "config" command.
+    pub fn parse_list(text: &str) -> Result<Vec<TextFilter>, String> {
+        text.split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                FILTERS
+                    .iter()
+                    .find(|filter| filter.as_str() == name)
+                    .copied()
+                    .ok_or_else(|| {
+                        let names: Vec<_> = FILTERS.iter().map(TextFilter::as_str).collect();
+                        format!(
+                            "there is no text filter {:?}, the filters are {}",
+                            name,
+                            names.join(", ")
+                        )
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Runs everything the game writes through the player's filters. Text can arrive in
+/// pieces, so the filter remembers the end of the last piece.
+#[derive(Debug, Default)]
+pub struct FilterChain {
+    /// The last character written, to space letters across pieces.
+    last: Option<char>,
+    /// Whether the text is inside of a terminal escape code, like a color.
+    in_escape: bool,
+}
+
+impl FilterChain {
+    pub fn apply(&mut self, filters: &[TextFilter], text: &str) -> String {
+        let mut text = text.to_string();
+        for filter in filters {
+            text = match filter {
+                TextFilter::LetterSpacing => self.space_letters(&text),
+                TextFilter::StripEmoji => strip_emoji(&text),
+                // Only the headings are changed, see `heading`.
+                TextFilter::UppercaseHeadings => text,
+            };
+        }
+        text
+    }
+
+    fn space_letters(&mut self, text: &str) -> String {
+        let mut spaced = String::with_capacity(text.len() * 2);
+        for ch in text.chars() {
+            if self.in_escape {
+                self.in_escape = !ch.is_ascii_alphabetic();
+                spaced.push(ch);
+                continue;
+            }
+            if ch == '\u{1b}' {
+                self.in_escape = true;
+                spaced.push(ch);
+                continue;
+            }
+            let after_letter = self.last.is_some_and(|last| !last.is_whitespace());
+            if ch == ' ' && after_letter {
+                // Widen the gaps between words too, so they still stand apart.
+                spaced.push_str("  ");
+            } else if !ch.is_whitespace() && after_letter {
+                spaced.push(' ');
+            }
+            spaced.push(ch);
+            self.last = Some(ch);
+        }
+        spaced
+    }
+}
+
+/// The game's output, with the player's filters applied to everything written to it.
+/// It dereferences to the environment, for everything besides writing.
+pub struct Filtered<'a, T> {
+    pub inner: RefMut<'a, T>,
+    pub filters: &'a [TextFilter],
+    pub chain: RefMut<'a, FilterChain>,
+}
+
+impl<T> Deref for Filtered<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for Filtered<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<T: Write> Write for Filtered<'_, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match std::str::from_utf8(buf) {
+            Ok(text) if !self.filters.is_empty() => {
+                let filtered = self.chain.apply(self.filters, text);
+                self.inner.write_all(filtered.as_bytes())?;
+                Ok(buf.len())
+            }
+            _ => self.inner.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Apply the filters that only change headings, like the title of a room.
+pub fn heading(filters: &[TextFilter], text: &str) -> String {
+    if filters.contains(&TextFilter::UppercaseHeadings) {
+        text.to_uppercase()
+    } else {
+        text.to_string()
+    }
+}
+
+/// Leave out the emoji, along with the space after one that stood on its own, so that
+/// "books 📚 reach" becomes "books reach".
+fn strip_emoji(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if !is_emoji(ch) {
+            stripped.push(ch);
+            continue;
+        }
+        while chars.peek().copied().is_some_and(is_emoji) {
+            chars.next();
+        }
+        if stripped.ends_with(' ') && chars.peek() == Some(&' ') {
+            chars.next();
+        }
+    }
+    stripped
+}
+
+fn is_emoji(ch: char) -> bool {
+    matches!(
+        ch as u32,
+        // Pictographs, emoticons, transport, and the supplemental symbols.
+        0x1F000..=0x1FAFF
+            // Miscellaneous symbols and dingbats, like ☀ and ✂.
+            | 0x2600..=0x27BF
+            // The joiner and variation selector that combine emoji.
+            | 0x200D
+            | 0xFE0F
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn test_text_filters() {
+        assert_eq!(
+            TextFilter::parse_list(" strip_emoji,letter_spacing "),
+            Ok(vec![TextFilter::StripEmoji, TextFilter::LetterSpacing])
+        );
+        assert_eq!(TextFilter::parse_list(""), Ok(vec![]));
+        assert_eq!(
+            TextFilter::parse_list("bold"),
+            Err(
+                "there is no text filter \"bold\", the filters are letter_spacing, \
+                 strip_emoji, uppercase_headings"
+                    .into()
+            )
+        );
+
+        let filters = [TextFilter::StripEmoji, TextFilter::LetterSpacing];
+        let mut chain = FilterChain::default();
+        assert_eq!(chain.apply(&filters, "Go 🐀 n"), "G o   n");
+        assert_eq!(strip_emoji("Rats!🐀 Run 🏃‍♀️."), "Rats! Run .");
+        // Letters are spaced across pieces, but not inside of color codes.
+        let mut chain = FilterChain::default();
+        let pieces = ["Ca", "t \u{1b}[31mre", "d\u{1b}[0m"];
+        let spaced: String = pieces
+            .iter()
+            .map(|piece| chain.apply(&filters, piece))
+            .collect();
+        assert_eq!(spaced, "C a t   \u{1b}[31mr e d\u{1b}[0m");
+
+        assert_eq!(heading(&[TextFilter::UppercaseHeadings], "Dock"), "DOCK");
+        assert_eq!(heading(&filters, "Dock"), "Dock");
+    }
+
+    #[test]
+    fn test_text_filters_in_game() {
+        let level = r#"
+maps: [["---", "-.-", "---"]]
+entry: [1, 1, 0]
+npcs: {}
+regions: {}
+rooms:
+  - title: Library
+    coord: [1, 1, 0]
+    description: Dusty books 📚 reach up to a painted ceiling ✨.
+"#;
+        let mut command_runner = CommandRunner::new(vec![]);
+        let mut game = game_from_level(level, &mut command_runner);
+        assert!(game
+            .step("config text_filters strip_emoji, uppercase_headings".into())
+            .is_none());
+        assert!(game.step("look".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - Changed the text_filters setting.
+        - LIBRARY
+        - ""
+        - "    Dusty books reach up to a painted ceiling . "
+        - ""
+        - ""
+        - "Exits: _ _ _ _"
+        "###);
+
+        assert!(game
+            .step("config text_filters letter_spacing".into())
+            .is_none());
+        game.output().output.clear();
+        assert!(game.step("look".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - L i b r a r y
+        - ""
+        - "    D u s t y   b o o k s   📚   r e a c h   u p   t o   a   p a i n t e d   "
+        - "    c e i l i n g   ✨ .   "
+        - ""
+        - ""
+        - "E x i t s :   _   _   _   _"
+        "###);
+
+        assert!(game.step("config text_filters bold".into()).is_none());
+        insta::assert_yaml_snapshot!(take_output(&game), @r###"
+        ---
+        - "T h a t   s e t t i n g   c a n ' t   b e   c h a n g e d :   t h e r e   i s   n o   t e x t   f i l t e r   \" b o l d \" ,   t h e   f i l t e r s   a r e   l e t t e r _ s p a c i n g ,   s t r i p _ e m o j i ,   u p p e r c a s e _ h e a d i n g s ."
+        "###);
+    }
+}
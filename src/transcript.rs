@@ -0,0 +1,356 @@
+use crate::{
+    campaign::Today,
+    utils::{parse_yml, strip_ansi},
+    Config, Cue, Environment, Status, Theme,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Write as _,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+pub const TRANSCRIPT_PATH: &str = "data/transcript.yml";
+
+/// A command the player typed, and everything the game printed in response. The
+/// first entry is the introduction, which has no input.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    /// The room the player was in once the command was done.
+    pub room: String,
+    #[serde(default)]
+    pub input: Option<String>,
+    pub output: String,
+}
+
+/// Records a playthrough to a transcript file, while passing everything through to
+/// the environment that is really being played in. Each entry is added to the file
+/// as soon as it's done, so nothing is lost if the game doesn't quit cleanly.
+pub struct Recorder<T: Environment> {
+    environment: T,
+    path: PathBuf,
+    entry: Option<TranscriptEntry>,
+    /// The room from the latest status.
+    room: String,
+    /// Whether the next prompt is for a new command, rather than a question asked in
+    /// the middle of one.
+    at_turn: bool,
+}
+
+impl<T: Environment> Recorder<T> {
+    pub fn new(environment: T, path: &Path) -> Recorder<T> {
+        Recorder {
+            environment,
+            path: path.into(),
+            entry: None,
+            room: String::new(),
+            at_turn: false,
+        }
+    }
+
+    fn entry(&mut self) -> &mut TranscriptEntry {
+        self.entry.get_or_insert_with(|| TranscriptEntry {
+            room: String::new(),
+            input: None,
+            output: String::new(),
+        })
+    }
+
+    fn finish_entry(&mut self) {
+        let mut entry = match self.entry.take() {
+            Some(entry) => entry,
+            None => return,
+        };
+        entry.room = self.room.clone();
+        // Each entry is added as an item of a YML list, so the file stays valid as it
+        // grows.
+        let yml = serde_yaml::to_string(&[entry]).expect("Unable to serialize the transcript.");
+        let written = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{}", yml.trim_start_matches("---\n").trim_end()));
+        if let Err(err) = written {
+            eprintln!("Unable to record to {}: {}", self.path.display(), err);
+        }
+    }
+}
+
+impl<T: Environment> Environment for Recorder<T> {
+    fn get_prompt(&mut self) -> String {
+        let input = self.environment.get_prompt();
+        if self.at_turn {
+            self.at_turn = false;
+            self.finish_entry();
+            self.entry().input = Some(input.clone());
+        } else {
+            // Answers to questions like "Are you sure?" are part of the output.
+            let line = format!("{} {}\n", self.environment.config().prompt, input);
+            self.entry().output.push_str(&line);
+        }
+        input
+    }
+
+    fn save_path(&self) -> Option<PathBuf> {
+        self.environment.save_path()
+    }
+
+    fn theme(&self) -> Option<&Theme> {
+        self.environment.theme()
+    }
+
+    fn seed(&self) -> Option<u64> {
+        self.environment.seed()
+    }
+
+    fn read_text(&self, path: &str) -> String {
+        self.environment.read_text(path)
+    }
+
+    fn try_read_text(&self, path: &str) -> Option<String> {
+        self.environment.try_read_text(path)
+    }
+
+    fn list_files(&self, dir: &str) -> Vec<String> {
+        self.environment.list_files(dir)
+    }
+
+    fn set_status(&mut self, status: Status) {
+        self.room = status.room.clone();
+        self.at_turn = true;
+        self.environment.set_status(status);
+    }
+
+    fn watch(&self) -> bool {
+        self.environment.watch()
+    }
+
+    fn jam_started(&self) -> Option<Instant> {
+        self.environment.jam_started()
+    }
+
+    fn new_game_plus_path(&self) -> Option<PathBuf> {
+        self.environment.new_game_plus_path()
+    }
+
+    fn today(&self) -> Option<Today> {
+        self.environment.today()
+    }
+
+    fn scenario(&self) -> Option<&str> {
+        self.environment.scenario()
+    }
+
+    fn lang(&self) -> Option<&str> {
+        self.environment.lang()
+    }
+
+    fn packs(&self) -> &[String] {
+        self.environment.packs()
+    }
+
+    fn race(&self) -> bool {
+        self.environment.race()
+    }
+
+    fn feedback_path(&self) -> Option<PathBuf> {
+        self.environment.feedback_path()
+    }
+
+    fn achievements_path(&self) -> Option<PathBuf> {
+        self.environment.achievements_path()
+    }
+
+    fn tutorial(&self) -> bool {
+        self.environment.tutorial()
+    }
+
+    fn results_path(&self) -> Option<PathBuf> {
+        self.environment.results_path()
+    }
+
+    fn races_path(&self) -> Option<PathBuf> {
+        self.environment.races_path()
+    }
+
+    fn config(&self) -> Config {
+        self.environment.config()
+    }
+
+    fn config_path(&self) -> Option<PathBuf> {
+        self.environment.config_path()
+    }
+
+    fn play(&mut self, cue: &Cue) {
+        self.environment.play(cue)
+    }
+
+    fn set_config(&mut self, config: &Config) -> Result<(), String> {
+        self.environment.set_config(config)
+    }
+
+    fn width(&self) -> Option<usize> {
+        self.environment.width()
+    }
+}
+
+impl<T: Environment> Write for Recorder<T> {
+    fn write(&mut self, buffer: &[u8]) -> Result<usize, std::io::Error> {
+        let text = String::from_utf8_lossy(&strip_ansi(buffer)).into_owned();
+        self.entry().output.push_str(&text);
+        self.environment.write(buffer)
+    }
+
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        self.environment.flush()
+    }
+}
+
+impl<T: Environment> Drop for Recorder<T> {
+    fn drop(&mut self) {
+        self.finish_entry();
+    }
+}
+
+/// Load a transcript and export it. The last recording is used when no path is given.
+pub fn export_file(transcript_path: Option<&str>) -> String {
+    let entries: Vec<TranscriptEntry> =
+        parse_yml(&PathBuf::from(transcript_path.unwrap_or(TRANSCRIPT_PATH)));
+    export_html(&entries)
+}
+
+/// Turn a transcript into a single HTML page for sharing a playthrough. Each stay in a
+/// room is a section that can be collapsed.
+pub fn export_html(entries: &[TranscriptEntry]) -> String {
+    let mut out = String::new();
+    writeln!(out, "<!DOCTYPE html>").unwrap();
+    writeln!(out, "<html lang=\"en\">").unwrap();
+    writeln!(out, "<head>").unwrap();
+    writeln!(out, "<meta charset=\"utf-8\">").unwrap();
+    writeln!(out, "<title>Playthrough</title>").unwrap();
+    writeln!(out, "<style>{}</style>", STYLE).unwrap();
+    writeln!(out, "</head>").unwrap();
+    writeln!(out, "<body>").unwrap();
+    writeln!(out, "<h1>Playthrough</h1>").unwrap();
+
+    let mut entries = entries.iter().peekable();
+    while let Some(first) = entries.next() {
+        writeln!(out, "<details open>").unwrap();
+        writeln!(out, "<summary>{}</summary>", escape(&first.room)).unwrap();
+        let mut entry = first;
+        loop {
+            if let Some(ref input) = entry.input {
+                writeln!(out, "<p class=\"input\">» {}</p>", escape(input)).unwrap();
+            }
+            let output = entry.output.trim_matches('\n');
+            if !output.is_empty() {
+                writeln!(out, "<pre>{}</pre>", escape(output)).unwrap();
+            }
+            match entries.next_if(|next| next.room == first.room) {
+                Some(next) => entry = next,
+                None => break,
+            }
+        }
+        writeln!(out, "</details>").unwrap();
+    }
+
+    writeln!(out, "</body>").unwrap();
+    writeln!(out, "</html>").unwrap();
+    out
+}
+
+const STYLE: &str = "
+body { background: #1d1f21; color: #c5c8c6; font-family: sans-serif; max-width: 50em; margin: 2em auto; padding: 0 1em; }
+summary { color: #81a2be; cursor: pointer; font-size: 1.2em; font-weight: bold; padding: 0.5em 0; }
+details { border-bottom: 1px solid #373b41; }
+pre { font-family: monospace; white-space: pre-wrap; }
+.input { color: #b5bd68; font-family: monospace; font-weight: bold; }
+";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{game_loop, testing::*, GameLoopResponse, ItemDatabase};
+
+    #[test]
+    fn test_export_html() {
+        let entry = |room: &str, input: Option<&str>, output: &str| TranscriptEntry {
+            room: room.into(),
+            input: input.map(String::from),
+            output: output.into(),
+        };
+        let entries = [
+            entry("Gate", None, "Welcome!\n\nGate\n"),
+            entry("Gate", Some("take <apple>"), "You take the apple.\n"),
+            entry("Road", Some("east"), "Road\n"),
+            entry("Gate", Some("west"), "Gate\n"),
+        ];
+        let html = export_html(&entries);
+        let body = &html[html.find("<body>").unwrap()..];
+        insta::assert_snapshot!(body, @r###"
+        <body>
+        <h1>Playthrough</h1>
+        <details open>
+        <summary>Gate</summary>
+        <pre>Welcome!
+
+        Gate</pre>
+        <p class="input">» take &lt;apple&gt;</p>
+        <pre>You take the apple.</pre>
+        </details>
+        <details open>
+        <summary>Road</summary>
+        <p class="input">» east</p>
+        <pre>Road</pre>
+        </details>
+        <details open>
+        <summary>Gate</summary>
+        <p class="input">» west</p>
+        <pre>Gate</pre>
+        </details>
+        </body>
+        </html>
+        "###);
+    }
+
+    #[test]
+    fn test_recorder() {
+        let path = std::env::temp_dir().join("text-adventure-test-transcript.yml");
+        let _ = fs::remove_file(&path);
+        let mut command_runner = CommandRunner::new(vec!["north", "restart", "no"]);
+        let recorder = Recorder::new(&mut command_runner, &path);
+        assert!(matches!(
+            game_loop(&ItemDatabase::new(), recorder),
+            Ok(GameLoopResponse::Quit)
+        ));
+
+        let entries: Vec<TranscriptEntry> =
+            serde_yaml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        let inputs: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.room.as_str(), entry.input.as_deref()))
+            .collect();
+        insta::assert_yaml_snapshot!(inputs, @r###"
+        ---
+        - - Stone End Docks
+          - ~
+        - - Stone End Market Road
+          - north
+        - - Stone End Market Road
+          - restart
+        - - Stone End Market Road
+          - quit
+        "###);
+        assert!(entries[2].output.ends_with("» no\nLet's keep playing!\n"));
+    }
+}
@@ -0,0 +1,417 @@
+use crate::{
+    audio::Audio,
+    utils::{strip_ansi, to_ascii},
+    Config, Cue, Environment, GameOptions, Status, Theme, CONFIG_PATH, SAVE_PATH,
+};
+use crossterm::{
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+        MouseButton, MouseEventKind,
+    },
+    execute,
+};
+use ratatui::{
+    layout::{Constraint, Layout, Position, Rect},
+    style::{Modifier, Style},
+    symbols::border,
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+    DefaultTerminal, Frame,
+};
+use std::{io::Write, path::PathBuf, time::Instant};
+
+/// How many lines of output are kept for scrolling back through.
+const SCROLLBACK: usize = 1000;
+
+/// How many lines the mouse wheel scrolls.
+const WHEEL_LINES: usize = 3;
+
+/// The width of the sidebar, including its border.
+const SIDEBAR_WIDTH: u16 = 24;
+
+/// The input box's border when only ASCII is used.
+const ASCII_BORDER: border::Set = border::Set {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    vertical_left: "|",
+    vertical_right: "|",
+    horizontal_top: "-",
+    horizontal_bottom: "-",
+};
+
+/// A full screen frontend, with a scrolling output pane, an input line, a status bar,
+/// and a sidebar with a minimap of the exits and the items in the room. The game
+/// engine is shared with the plain terminal, this only changes how the output is
+/// displayed and how the input is read.
+pub struct Tui {
+    terminal: DefaultTerminal,
+    screen: Screen,
+    options: GameOptions,
+    audio: Audio,
+}
+
+/// Everything that is drawn, kept apart from the terminal so that it can be borrowed
+/// while drawing.
+struct Screen {
+    /// The finished lines of output.
+    lines: Vec<String>,
+    /// Output that has been written, but not yet ended with a newline.
+    partial: String,
+    input: String,
+    /// What's shown before the input, from the player's config.
+    prompt: String,
+    /// How many lines the output pane is scrolled up from the bottom.
+    scroll: usize,
+    status: Option<Status>,
+    ascii: bool,
+    /// The parts of the screen that run a command when clicked, from the last draw.
+    clickable: Vec<(Rect, String)>,
+}
+
+impl Tui {
+    pub fn new(options: GameOptions) -> Tui {
+        let terminal = ratatui::init();
+        execute!(std::io::stdout(), EnableMouseCapture).expect("Unable to capture the mouse.");
+        Tui {
+            terminal,
+            screen: Screen {
+                lines: Vec::new(),
+                partial: String::new(),
+                input: String::new(),
+                prompt: prompt(&options),
+                scroll: 0,
+                status: None,
+                ascii: options.ascii,
+                clickable: Vec::new(),
+            },
+            options,
+            audio: Audio::new(),
+        }
+    }
+
+    fn push_line(&mut self, line: String) {
+        self.screen.lines.push(line);
+        if self.screen.lines.len() > SCROLLBACK {
+            self.screen.lines.remove(0);
+        }
+    }
+
+    fn draw(&mut self) {
+        let screen = &mut self.screen;
+        self.terminal
+            .draw(|frame| screen.clickable = draw_frame(frame, screen))
+            .expect("Unable to draw the terminal.");
+    }
+
+    /// Show a command as if it was typed, and hand it to the game.
+    fn submit(&mut self, input: String) -> String {
+        let line = format!("{} {}", self.screen.prompt, input);
+        self.push_line(line);
+        self.push_line(String::new());
+        self.screen.scroll = 0;
+        input.to_lowercase()
+    }
+
+    /// Find the command for a click on the screen.
+    fn clicked(&self, column: u16, row: u16) -> Option<String> {
+        let position = Position::new(column, row);
+        self.screen
+            .clickable
+            .iter()
+            .find(|(area, _)| area.contains(position))
+            .map(|(_, command)| command.clone())
+    }
+}
+
+fn prompt(options: &GameOptions) -> String {
+    let prompt = &options.config.prompt;
+    if options.ascii {
+        String::from_utf8_lossy(&to_ascii(prompt.as_bytes())).into_owned()
+    } else {
+        prompt.clone()
+    }
+}
+
+/// Draw the screen, returning the parts of it that can be clicked.
+fn draw_frame(frame: &mut Frame, screen: &Screen) -> Vec<(Rect, String)> {
+    let separator = if screen.ascii { "|" } else { "│" };
+    let [main_area, status_area, input_area] = Layout::vertical([
+        Constraint::Min(1),
+        Constraint::Length(1),
+        Constraint::Length(3),
+    ])
+    .areas(frame.area());
+    let [output_area, sidebar_area] =
+        Layout::horizontal([Constraint::Min(1), Constraint::Length(SIDEBAR_WIDTH)])
+            .areas(main_area);
+
+    // Show the bottom of the output, minus however far it's scrolled back.
+    let height = output_area.height as usize;
+    let mut visible: Vec<&str> = screen.lines.iter().map(String::as_str).collect();
+    if !screen.partial.is_empty() {
+        visible.push(&screen.partial);
+    }
+    let end = visible.len().saturating_sub(screen.scroll);
+    let start = end.saturating_sub(height);
+    let output: Vec<Line> = visible[start..end]
+        .iter()
+        .map(|line| Line::raw(*line))
+        .collect();
+    frame.render_widget(Paragraph::new(output), output_area);
+
+    let mut clickable = Vec::new();
+    if let Some(ref status) = screen.status {
+        clickable = draw_sidebar(frame, sidebar_area, status, screen.ascii);
+
+        let mut spans = vec![
+            Span::styled(
+                format!(" {} ", status.room),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(format!("{} Exits: {} ", separator, status.exits)),
+            Span::raw(format!("{} Money: {} ", separator, status.money)),
+            Span::raw(format!("{} Turn: {} ", separator, status.turn)),
+        ];
+        if screen.scroll > 0 {
+            spans.push(Span::raw(format!(
+                "{} Scrolled back {} lines ",
+                separator, screen.scroll
+            )));
+        }
+        frame.render_widget(
+            Paragraph::new(Line::from(spans))
+                .style(Style::default().add_modifier(Modifier::REVERSED)),
+            status_area,
+        );
+    }
+
+    let prompt = format!("{} {}", screen.prompt, screen.input);
+    let cursor_x = input_area.x + 1 + prompt.chars().count() as u16;
+    frame.render_widget(
+        Paragraph::new(prompt).block(bordered(screen.ascii)),
+        input_area,
+    );
+    frame.set_cursor_position(Position::new(cursor_x, input_area.y + 1));
+    clickable
+}
+
+fn bordered(ascii: bool) -> Block<'static> {
+    let block = Block::default().borders(Borders::ALL);
+    if ascii {
+        block.border_set(ASCII_BORDER)
+    } else {
+        block
+    }
+}
+
+/// Draw the minimap of the exits, and the items in the room. Clicking an exit moves
+/// that way, and clicking an item takes it.
+fn draw_sidebar(
+    frame: &mut Frame,
+    area: Rect,
+    status: &Status,
+    ascii: bool,
+) -> Vec<(Rect, String)> {
+    let block = bordered(ascii).title(" Map ");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let mut clickable = Vec::new();
+    let mut place = |text: String, x: u16, y: u16, command: Option<String>| {
+        let width = (text.chars().count() as u16).min(inner.width.saturating_sub(x));
+        if y >= inner.height || width == 0 {
+            return;
+        }
+        let rect = Rect::new(inner.x + x, inner.y + y, width, 1);
+        let style = match command {
+            Some(_) => Style::default().add_modifier(Modifier::UNDERLINED),
+            None => Style::default(),
+        };
+        frame.render_widget(Paragraph::new(Span::styled(text, style)), rect);
+        if let Some(command) = command {
+            clickable.push((rect, command));
+        }
+    };
+
+    // The exits are laid out like a compass around the player.
+    let center = inner.width / 2;
+    let compass = [
+        ("north", "N", center.saturating_sub(1), 0),
+        ("west", "W", center.saturating_sub(5), 1),
+        ("east", "E", center + 3, 1),
+        ("south", "S", center.saturating_sub(1), 2),
+    ];
+    place("@".into(), center, 1, None);
+    for (direction, letter, x, y) in compass.iter() {
+        if status.directions.contains(direction) {
+            place(format!("[{}]", letter), *x, *y, Some(direction.to_string()));
+        }
+    }
+
+    let bullet = if ascii { "*" } else { "‣" };
+    place("Items here:".into(), 0, 4, None);
+    if status.items.is_empty() {
+        place("  nothing".into(), 0, 5, None);
+    }
+    for (index, item) in status.items.iter().enumerate() {
+        place(
+            format!("  {} {}", bullet, item),
+            0,
+            5 + index as u16,
+            Some(format!("take {}", item)),
+        );
+    }
+    clickable
+}
+
+impl Environment for Tui {
+    fn get_prompt(&mut self) -> String {
+        loop {
+            self.draw();
+            let page = self
+                .terminal
+                .size()
+                .map_or(10, |size| size.height as usize / 2);
+            match event::read().expect("Unable to read from the terminal.") {
+                Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Enter => {
+                        let input = std::mem::take(&mut self.screen.input);
+                        return self.submit(input);
+                    }
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return "quit".into();
+                    }
+                    KeyCode::Char(ch) => self.screen.input.push(ch),
+                    KeyCode::Backspace => {
+                        self.screen.input.pop();
+                    }
+                    KeyCode::Up => self.screen.scroll += 1,
+                    KeyCode::Down => self.screen.scroll = self.screen.scroll.saturating_sub(1),
+                    KeyCode::PageUp => self.screen.scroll += page,
+                    KeyCode::PageDown => {
+                        self.screen.scroll = self.screen.scroll.saturating_sub(page)
+                    }
+                    _ => {}
+                },
+                Event::Mouse(mouse) => match mouse.kind {
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        if let Some(command) = self.clicked(mouse.column, mouse.row) {
+                            return self.submit(command);
+                        }
+                    }
+                    MouseEventKind::ScrollUp => self.screen.scroll += WHEEL_LINES,
+                    MouseEventKind::ScrollDown => {
+                        self.screen.scroll = self.screen.scroll.saturating_sub(WHEEL_LINES)
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+            self.screen.scroll = self.screen.scroll.min(self.screen.lines.len());
+        }
+    }
+
+    fn seed(&self) -> Option<u64> {
+        self.options.seed
+    }
+
+    fn set_status(&mut self, status: Status) {
+        self.screen.status = Some(status);
+    }
+
+    fn watch(&self) -> bool {
+        self.options.watch
+    }
+
+    fn jam_started(&self) -> Option<Instant> {
+        self.options.jam_started
+    }
+
+    fn scenario(&self) -> Option<&str> {
+        self.options.scenario.as_deref()
+    }
+
+    fn lang(&self) -> Option<&str> {
+        self.options.lang.as_deref()
+    }
+
+    fn packs(&self) -> &[String] {
+        &self.options.packs
+    }
+
+    fn race(&self) -> bool {
+        self.options.race
+    }
+
+    fn tutorial(&self) -> bool {
+        self.options.tutorial
+    }
+
+    fn save_path(&self) -> Option<PathBuf> {
+        (!self.options.tutorial).then(|| PathBuf::from(SAVE_PATH))
+    }
+
+    fn config(&self) -> Config {
+        self.options.config.clone()
+    }
+
+    fn config_path(&self) -> Option<PathBuf> {
+        Some(PathBuf::from(CONFIG_PATH))
+    }
+
+    fn width(&self) -> Option<usize> {
+        // The output pane is beside the sidebar, and the last column is left empty.
+        self.terminal
+            .size()
+            .ok()
+            .map(|size| size.width.saturating_sub(SIDEBAR_WIDTH + 1) as usize)
+    }
+
+    fn play(&mut self, cue: &Cue) {
+        self.audio.play(cue);
+    }
+
+    fn set_config(&mut self, config: &Config) -> Result<(), String> {
+        // The output pane doesn't show colors, but the theme is saved for the plain
+        // terminal.
+        if config.theme != self.options.config.theme {
+            Theme::try_load(&config.theme)?;
+        }
+        self.options.config = config.clone();
+        self.screen.prompt = prompt(&self.options);
+        Ok(())
+    }
+}
+
+impl Write for Tui {
+    fn write(&mut self, buffer: &[u8]) -> Result<usize, std::io::Error> {
+        // The output pane is plain text, so drop any colors.
+        let mut plain = strip_ansi(buffer);
+        if self.options.ascii {
+            plain = to_ascii(&plain);
+        }
+        let text = String::from_utf8_lossy(&plain).into_owned();
+        for ch in text.chars() {
+            if ch == '\n' {
+                let line = std::mem::take(&mut self.screen.partial);
+                self.push_line(line);
+            } else {
+                self.screen.partial.push(ch);
+            }
+        }
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        let _ = execute!(std::io::stdout(), DisableMouseCapture);
+        ratatui::restore();
+    }
+}
@@ -0,0 +1,233 @@
+use crate::{
+    level::Level, narrator::Narrator, parse_command, vocabulary::Vocabulary, ParsedCommand,
+};
+use std::{fmt::Write as _, mem::discriminant};
+
+/// A room of the tutorial that teaches one command. The way on is blocked until the
+/// player has done the command once.
+pub struct Lesson {
+    /// The id of the room, which is also the flag that's set once it's learned.
+    pub id: &'static str,
+    pub title: &'static str,
+    /// What the player is asked to do.
+    pub text: &'static str,
+    /// The command to type, which is parsed like any other, so the lesson is learned by
+    /// anything the engine parses into the same kind of command.
+    pub example: &'static str,
+    /// An item to leave in the room to practice on.
+    pub item: Option<&'static str>,
+}
+
+/// The lessons in the order they are walked through, from west to east.
+pub const LESSONS: &[Lesson] = &[
+    Lesson {
+        id: "porch",
+        title: "Porch",
+        text: "Welcome! You play by typing what you want to do, and pressing enter. Start by \
+               taking a look around.",
+        example: "look",
+        item: None,
+    },
+    Lesson {
+        id: "shed",
+        title: "Shed",
+        text: "There's a lantern on a hook here. Pick it up.",
+        example: "take lantern",
+        item: Some("lantern"),
+    },
+    Lesson {
+        id: "path",
+        title: "Path",
+        text: "Check what you're carrying.",
+        example: "inventory",
+        item: None,
+    },
+    Lesson {
+        id: "orchard",
+        title: "Orchard",
+        text: "An apple has fallen from the tree. Take it, and then eat it. Commands can be \
+               joined with \"then\", like: take apple then eat apple",
+        example: "eat apple",
+        item: Some("apple"),
+    },
+    Lesson {
+        id: "gate",
+        title: "Gate",
+        text: "Whenever you're stuck, ask for help to see every command.",
+        example: "help",
+        item: None,
+    },
+];
+
+const ROAD: &str = "road";
+
+/// The lesson that's taught in a room, by the room's id.
+pub fn lesson(room_id: Option<&str>) -> Option<&'static Lesson> {
+    LESSONS.iter().find(|lesson| Some(lesson.id) == room_id)
+}
+
+impl Lesson {
+    /// Whether a command that was done is the one that's being taught.
+    pub fn is_learned_by(
+        &self,
+        command: &ParsedCommand,
+        vocabulary: &Vocabulary,
+        narrator: &Narrator,
+    ) -> bool {
+        let example = parse_command(self.example.into(), vocabulary, narrator)
+            .expect("Expected the tutorial's commands to parse.");
+        example
+            .first()
+            .is_some_and(|example| discriminant(example) == discriminant(command))
+    }
+}
+
+/// Build the tutorial level, with a room for each lesson in a row. Each room's exit to
+/// the east can only be taken once its lesson is learned, and the last one leads out to
+/// the road.
+pub fn level() -> Level {
+    let mut yml = String::new();
+    let mut map = String::from("-");
+    for _ in 0..=LESSONS.len() {
+        map.push_str(".-");
+    }
+    writeln!(
+        yml,
+        "maps: [[{:?}, {:?}, {:?}]]",
+        "-".repeat(map.len()),
+        map,
+        "-".repeat(map.len())
+    )
+    .unwrap();
+    writeln!(yml, "entry: [1, 1, 0]\nregions: {{}}\nnpcs: {{}}\nrooms:").unwrap();
+    for (index, lesson) in LESSONS.iter().enumerate() {
+        let next = LESSONS.get(index + 1).map_or(ROAD, |next| next.id);
+        let prompt = format!("Try typing: {}", lesson.example);
+        writeln!(yml, "  - id: {}", lesson.id).unwrap();
+        writeln!(yml, "    title: {}", lesson.title).unwrap();
+        writeln!(yml, "    coord: [{}, 1, 0]", index * 2 + 1).unwrap();
+        writeln!(
+            yml,
+            "    description: {:?}",
+            format!("{}\n\n{}", lesson.text, prompt)
+        )
+        .unwrap();
+        writeln!(yml, "    exits:").unwrap();
+        writeln!(
+            yml,
+            "      east: {{ entry: {}, when: flags.{}, blocked: {:?} }}",
+            next, lesson.id, prompt
+        )
+        .unwrap();
+        if index > 0 {
+            writeln!(yml, "      west: {{ entry: {} }}", LESSONS[index - 1].id).unwrap();
+        }
+        if let Some(item) = lesson.item {
+            writeln!(
+                yml,
+                "    items: [{{ id: {}, quantity: 1, name: ~, pickup: ~ }}]",
+                item
+            )
+            .unwrap();
+        }
+    }
+    let last = LESSONS
+        .last()
+        .expect("Expected the tutorial to have lessons.");
+    writeln!(yml, "  - id: {}", ROAD).unwrap();
+    writeln!(yml, "    title: Road").unwrap();
+    writeln!(yml, "    coord: [{}, 1, 0]", LESSONS.len() * 2 + 1).unwrap();
+    writeln!(
+        yml,
+        "    description: \"That's everything you need to get going. Type quit to leave the \
+         tutorial.\""
+    )
+    .unwrap();
+    writeln!(yml, "    exits:\n      west: {{ entry: {} }}", last.id).unwrap();
+    serde_yaml::from_str(&yml).expect("Unable to parse the tutorial level.")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{testing::*, Game, ItemDatabase};
+
+    #[test]
+    fn test_lessons() {
+        // Each lesson has to teach a command the engine knows, and a different one.
+        let (vocabulary, narrator) = (Vocabulary::default(), Narrator::default());
+        let mut taught = Vec::new();
+        for lesson in LESSONS.iter() {
+            let commands = parse_command(lesson.example.into(), &vocabulary, &narrator).unwrap();
+            assert_eq!(commands.len(), 1, "{}", lesson.example);
+            assert!(
+                !matches!(
+                    commands[0],
+                    ParsedCommand::Message(_) | ParsedCommand::Narrate(_)
+                ),
+                "{}",
+                lesson.example
+            );
+            assert!(!taught.contains(&discriminant(&commands[0])));
+            taught.push(discriminant(&commands[0]));
+        }
+        level();
+    }
+
+    #[test]
+    fn test_tutorial() {
+        let mut command_runner = CommandRunner::new(vec![]);
+        command_runner.tutorial = true;
+        let mut game = Game::new(ItemDatabase::new(), &mut command_runner).unwrap();
+        let mut output = Vec::new();
+        for command in [
+            "east",
+            "look",
+            "east",
+            "east",
+            "take lantern",
+            "east",
+            "inventory",
+            "east",
+            "eat apple",
+            "take apple then eat apple",
+            "east",
+            "help",
+            "east",
+        ] {
+            game.step(command.into());
+            output.extend(
+                take_output(&game)
+                    .into_iter()
+                    // Leave out the help, and the rest of the room descriptions.
+                    .filter(|line| !line.trim().is_empty())
+                    .filter(|line| !line.starts_with([' ', '│', '┌', '└', 'E'])),
+            );
+        }
+        insta::assert_yaml_snapshot!(output, @r###"
+        ---
+        - "Try typing: look"
+        - Porch
+        - "Well done! Head east when you're ready."
+        - Shed
+        - a lantern
+        - "Try typing: take lantern"
+        - You place the lantern in your inventory.
+        - "Well done! Head east when you're ready."
+        - Path
+        - ╔═════════════════╗
+        - "║ Your inventory: ║"
+        - ╚═════════════════╝
+        - "Well done! Head east when you're ready."
+        - Orchard
+        - an apple
+        - "You aren't carrying a apple."
+        - You place the apple in your inventory.
+        - You finish the apple.
+        - "Well done! Head east when you're ready."
+        - Gate
+        - "Well done! Head east when you're ready."
+        - Road
+        "###);
+    }
+}
@@ -1,5 +1,13 @@
-use std::{fs, path::PathBuf, process};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    process,
+    time::Duration,
+};
 
+use rand::{seq::SliceRandom, SeedableRng};
+use rand_pcg::Pcg32;
 use serde::de::DeserializeOwned;
 
 pub fn parse_yml<T>(path: &PathBuf) -> T
@@ -10,12 +18,20 @@ where
         Ok(s) => s,
         Err(_) => panic!("Could not load {:?}", path),
     };
+    parse_yml_str(&yml_string, path)
+}
 
-    match serde_yaml::from_str(&yml_string) {
+/// Parse YML that has already been read, reporting errors against the path it came
+/// from.
+pub fn parse_yml_str<T>(yml_string: &str, path: &Path) -> T
+where
+    T: DeserializeOwned,
+{
+    match serde_yaml::from_str(yml_string) {
         Ok(t) => t,
         Err(err) => {
             eprintln!("======================================================================");
-            eprintln!("Unable to deserialize, {}", path.as_path().display());
+            eprintln!("Unable to deserialize, {}", path.display());
             eprintln!("======================================================================");
             if let Some(location) = err.location() {
                 let backscroll = 10;
@@ -34,7 +50,7 @@ where
                 }
                 eprintln!(
                     "\n{}:{}:{}",
-                    path.as_path().display(),
+                    path.display(),
                     location.line(),
                     location.column()
                 );
@@ -43,3 +59,273 @@ where
         }
     }
 }
+
+/// Parse YML, returning a message for any errors rather than exiting. This is for
+/// files that are loaded again while the game is running.
+pub fn try_parse_yml_str<T>(yml_string: &str, path: &str) -> Result<T, String>
+where
+    T: DeserializeOwned,
+{
+    serde_yaml::from_str(yml_string).map_err(|err| format!("Unable to parse {}: {}", path, err))
+}
+
+/// Derive the targets for an item that doesn't list its own, from its name. A
+/// "pair of old boots" can be found by its full name, its last word, and the singular
+/// forms of those, like "pair of old boot" and "boot".
+pub fn derive_targets(name: &str) -> BTreeSet<String> {
+    let name = name.trim().to_lowercase();
+    let mut targets = BTreeSet::new();
+    if let Some(last_word) = name.split_whitespace().last() {
+        targets.insert(last_word.to_string());
+        targets.insert(singular(last_word));
+    }
+    targets.insert(singular(&name));
+    targets.insert(name);
+    targets
+}
+
+/// Make a best guess at the singular form of an English word.
+fn singular(word: &str) -> String {
+    if let Some(stem) = word.strip_suffix("ies") {
+        return format!("{}y", stem);
+    }
+    for suffix in ["ses", "xes", "ches", "shes"].iter() {
+        if word.ends_with(suffix) {
+            return word[..word.len() - 2].to_string();
+        }
+    }
+    if word.ends_with('s') && !word.ends_with("ss") {
+        return word[..word.len() - 1].to_string();
+    }
+    word.to_string()
+}
+
+/// Hash a string with FNV-1a. Unlike the standard library's hasher, this is stable
+/// across runs and versions, so it's safe to use for seeding.
+pub fn hash_str(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Compute the Levenshtein edit distance between two strings.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a_ch) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_ch) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_ch == *b_ch { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+/// Wrap a line of text at the spaces between words, so that no line is longer than the
+/// width unless a single word is. The lines after the first are indented, so that they
+/// read as part of the same line.
+pub fn wrap(text: &str, width: usize) -> String {
+    const HANGING_INDENT: &str = "  ";
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut wrapped = String::new();
+    let mut line_length = 0;
+    for word in text.split(' ').filter(|word| !word.is_empty()) {
+        let word_length = word.chars().count();
+        if line_length == 0 {
+            wrapped.push_str(word);
+            line_length = word_length;
+        } else if line_length + 1 + word_length > width {
+            wrapped.push('\n');
+            wrapped.push_str(HANGING_INDENT);
+            wrapped.push_str(word);
+            line_length = HANGING_INDENT.len() + word_length;
+        } else {
+            wrapped.push(' ');
+            wrapped.push_str(word);
+            line_length += 1 + word_length;
+        }
+    }
+    wrapped
+}
+
+/// Remove ANSI escape sequences, like colors, from some output.
+pub fn strip_ansi(buffer: &[u8]) -> Vec<u8> {
+    let mut stripped = Vec::with_capacity(buffer.len());
+    let mut bytes = buffer.iter().peekable();
+    while let Some(byte) = bytes.next() {
+        if *byte == 0x1b && bytes.peek() == Some(&&b'[') {
+            bytes.next();
+            // Skip the parameters until the final byte of the sequence.
+            for byte in bytes.by_ref() {
+                if (0x40..=0x7e).contains(byte) {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(*byte);
+        }
+    }
+    stripped
+}
+
+/// Swap box drawing characters, bullets, and curly punctuation for plain ASCII, for
+/// terminals and fonts that render them poorly. Other text is left alone.
+#[cfg_attr(not(feature = "native"), allow(dead_code))]
+pub fn to_ascii(buffer: &[u8]) -> Vec<u8> {
+    let text = match std::str::from_utf8(buffer) {
+        Ok(text) if !text.is_ascii() => text,
+        _ => return buffer.to_vec(),
+    };
+    let mut ascii = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '╔' | '╗' | '╚' | '╝' | '┌' | '┐' | '└' | '┘' | '╭' | '╮' | '╯' | '╰' | '├' | '┤'
+            | '┬' | '┴' | '┼' => ascii.push('+'),
+            '═' | '─' | '━' | '–' => ascii.push('-'),
+            '║' | '│' | '┃' => ascii.push('|'),
+            '‣' | '•' => ascii.push('*'),
+            '»' => ascii.push('>'),
+            '«' => ascii.push('<'),
+            '“' | '”' => ascii.push('"'),
+            '‘' | '’' => ascii.push('\''),
+            '—' => ascii.push_str("--"),
+            '…' => ascii.push_str("..."),
+            _ => ascii.push(ch),
+        }
+    }
+    ascii.into_bytes()
+}
+
+/// Jumble the middle letters of the longer words, keeping the first and last letters in
+/// place so that the text can still just about be read. The same seed always jumbles
+/// the same way.
+pub fn scramble_words(text: &str, seed: u64) -> String {
+    let mut scrambled = String::with_capacity(text.len());
+    let mut word = Vec::new();
+    let flush = |word: &mut Vec<char>, scrambled: &mut String| {
+        if word.len() > 3 {
+            let key = hash_str(&word.iter().collect::<String>());
+            let mut rng = Pcg32::seed_from_u64(seed ^ key);
+            let end = word.len() - 1;
+            word[1..end].shuffle(&mut rng);
+        }
+        scrambled.extend(word.drain(..));
+    };
+    for ch in text.chars() {
+        if ch.is_alphabetic() {
+            word.push(ch);
+        } else {
+            flush(&mut word, &mut scrambled);
+            scrambled.push(ch);
+        }
+    }
+    flush(&mut word, &mut scrambled);
+    scrambled
+}
+
+/// Show a length of time like a stopwatch, as "4:05" or "1:02:03".
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("A short line", 20), "A short line");
+        assert_eq!(
+            wrap("A rusty sword  with a notched blade", 16),
+            "A rusty sword\n  with a notched\n  blade"
+        );
+        assert_eq!(wrap("Unbreakable", 4), "Unbreakable");
+    }
+
+    #[test]
+    fn test_derive_targets() {
+        let mut targets: Vec<String> = derive_targets("Pair of Old Boots").into_iter().collect();
+        targets.sort();
+        assert_eq!(
+            targets,
+            vec!["boot", "boots", "pair of old boot", "pair of old boots"]
+        );
+
+        let mut targets: Vec<String> = derive_targets("sword").into_iter().collect();
+        targets.sort();
+        assert_eq!(targets, vec!["sword"]);
+
+        assert_eq!(singular("berries"), "berry");
+        assert_eq!(singular("boxes"), "box");
+        assert_eq!(singular("glass"), "glass");
+    }
+
+    #[test]
+    fn test_hash_str() {
+        assert_eq!(hash_str(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash_str("a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(hash_str("apple-farmer"), hash_str("grill-merchant"));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("lantern", "lantern"), 0);
+        assert_eq!(edit_distance("lanter", "lantern"), 1);
+        assert_eq!(edit_distance("lnatern", "lantern"), 2);
+        assert_eq!(edit_distance("", "look"), 4);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_to_ascii() {
+        let boxed = "╔═════╗\n║ Box ║\n╚═════╝\n  ‣ “Hi” — it’s me…";
+        assert_eq!(
+            String::from_utf8(to_ascii(boxed.as_bytes())).unwrap(),
+            "+-----+\n| Box |\n+-----+\n  * \"Hi\" -- it's me..."
+        );
+        assert_eq!(to_ascii("Café".as_bytes()), "Café".as_bytes());
+    }
+
+    #[test]
+    fn test_scramble_words() {
+        let scrambled = scramble_words("The tavern spins, doesn't it?", 7);
+        assert_eq!(
+            scrambled,
+            scramble_words("The tavern spins, doesn't it?", 7)
+        );
+        assert_ne!(scrambled, "The tavern spins, doesn't it?");
+        let words: Vec<_> = scrambled.split(' ').collect();
+        assert_eq!(words[0], "The");
+        assert!(words[1].starts_with('t') && words[1].ends_with('n'));
+        // Each side of the apostrophe is its own word.
+        assert!(words[3].starts_with('d') && words[3].ends_with("'t"));
+        assert_eq!(words[4], "it?");
+    }
+
+    #[test]
+    fn test_format_elapsed() {
+        assert_eq!(format_elapsed(Duration::from_secs(245)), "4:05");
+        assert_eq!(format_elapsed(Duration::from_secs(3723)), "1:02:03");
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(
+            strip_ansi("\x1b[1m\x1b[36mTitle\x1b[0m ‣ item".as_bytes()),
+            "Title ‣ item".as_bytes()
+        );
+    }
+}
@@ -0,0 +1,1444 @@
+use crate::{
+    audio,
+    condition::{self, Condition},
+    level::{
+        Action, Ambient, Coord, Destination, Effect, Hazard, Level, Movement, Puzzle, Room,
+        Scenery, Terrain, Throw, Trigger, Vehicle, Verb,
+    },
+    map::{Cell, RoomGraph, BUILT_IN_TILES},
+    pack::{self, Pack},
+    passage::Passage,
+    template,
+    utils::try_parse_yml_str,
+    walkthrough::{walkthrough, Stuck},
+    ItemDatabase, ITEMS_PATH, LEVEL_PATH,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+/// Load a level and its items, and check them for problems. The default files are
+/// used when no paths are given. The level and items are patched with the content packs
+/// first, which are checked too, taking the level's id from its file name.
+pub fn validate_files(
+    level_path: Option<&str>,
+    items_path: Option<&str>,
+    pack_paths: &[String],
+) -> Vec<String> {
+    let level_path = level_path.unwrap_or(LEVEL_PATH);
+    let items_path = items_path.unwrap_or(ITEMS_PATH);
+    let load = |path: &str| {
+        fs::read_to_string(path).map_err(|err| format!("Unable to read {}: {}", path, err))
+    };
+
+    let mut problems = Vec::new();
+    let packs: Vec<Pack> = pack_paths
+        .iter()
+        .filter_map(|path| {
+            Pack::load(path)
+                .map_err(|problem| problems.push(problem))
+                .ok()
+        })
+        .collect();
+    problems.extend(pack::problems(&packs));
+    let level_id = Path::new(level_path)
+        .file_stem()
+        .map_or(String::new(), |stem| stem.to_string_lossy().to_string());
+    let level: Option<Level> = load(level_path)
+        .and_then(|text| {
+            let text = pack::patch_level(&text, level_path, &level_id, &packs)?;
+            try_parse_yml_str(&text, level_path)
+        })
+        .map_err(|problem| problems.push(problem))
+        .ok();
+    let item_db = load(items_path)
+        .and_then(|text| try_parse_yml_str::<ItemDatabase>(&text, items_path))
+        .and_then(|item_db| pack::patch_items(&item_db, &packs))
+        .map_err(|problem| problems.push(problem))
+        .ok();
+    if let (Some(level), Some(item_db)) = (level, item_db) {
+        problems.extend(validate(&level, &item_db));
+    }
+    problems
+}
+
+/// Check a level for problems, returning all of them rather than stopping at the
+/// first one like loading the level does.
+pub fn validate(level: &Level, item_db: &ItemDatabase) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    // Find the rooms on the map.
+    let mut cells: HashSet<Coord> = HashSet::new();
+    let mut water: HashSet<Coord> = HashSet::new();
+    let mut is_map_sound = true;
+    for (z, map) in level.maps.iter().enumerate() {
+        for (y, row) in map.iter().enumerate() {
+            for (x, ch) in row.chars().enumerate() {
+                let coord = Coord { x, y, z };
+                match Cell::read(level, ch) {
+                    Cell::Room(terrain, _) => {
+                        cells.insert(coord);
+                        if terrain == Terrain::Water {
+                            water.insert(coord);
+                        }
+                    }
+                    Cell::Wall => {}
+                    Cell::Comment => break,
+                    Cell::Unknown => problems.push(format!(
+                        "Unknown character {:?} in the map at {}, add it to the level's tiles \
+                         to make it a room.",
+                        ch, coord
+                    )),
+                }
+            }
+        }
+    }
+    for (ch, tile) in level.tiles.iter() {
+        let owner = format!("The tile {:?}", ch.to_string());
+        if BUILT_IN_TILES.contains(ch) {
+            problems.push(format!("{} is built in, so it can't be changed.", owner));
+        }
+        check_condition(&owner, tile.when.as_ref(), item_db, &mut problems);
+        if tile.blocked.is_some() && tile.when.is_none() {
+            problems.push(format!(
+                "{} has a blocked message, but no condition.",
+                owner
+            ));
+        }
+        if !tile.passable && (tile.when.is_some() || tile.damage > 0 || tile.message.is_some()) {
+            problems.push(format!(
+                "{} isn't passable, so its rules for entering it are never used.",
+                owner
+            ));
+        }
+        let has_health = level.start.stats.contains_key("health")
+            || level.mechanics.survival.contains_key("health");
+        if tile.damage > 0 && !has_health {
+            problems.push(format!(
+                "{} does damage, but the player starts with no health to lose.",
+                owner
+            ));
+        }
+        for passage in [&tile.blocked, &tile.message].iter().copied().flatten() {
+            check_passage(&owner, passage, item_db, &mut problems);
+        }
+    }
+
+    let mut rooms_by_coord: HashMap<Coord, &str> = HashMap::new();
+    let mut rooms_by_id: HashMap<&str, &str> = HashMap::new();
+    let mut hazard_ids: HashSet<&str> = HashSet::new();
+    let mut puzzle_ids: HashSet<&str> = HashSet::new();
+    for room in level.rooms.iter() {
+        if let Some(ref id) = room.id {
+            match rooms_by_id.get(id.as_str()) {
+                Some(first) => problems.push(format!(
+                    "The rooms {:?} and {:?} both have the id {:?}, only the first is used.",
+                    first, room.title, id
+                )),
+                None => {
+                    rooms_by_id.insert(id, &room.title);
+                }
+            }
+        }
+        match rooms_by_coord.get(&room.coord) {
+            Some(first) => problems.push(format!(
+                "The rooms {:?} and {:?} are both at {}, only the first is used.",
+                first, room.title, room.coord
+            )),
+            None => {
+                rooms_by_coord.insert(room.coord, &room.title);
+            }
+        }
+        if !cells.contains(&room.coord) {
+            problems.push(format!(
+                "The room {:?} at {} isn't on the map.",
+                room.title, room.coord
+            ));
+        }
+        for item in room.items.iter() {
+            if item_db.find(&item.id).is_none() {
+                problems.push(format!(
+                    "The room {:?} has an unknown item {:?}.",
+                    room.title, item.id
+                ));
+            }
+            if item.found_by.is_some() && !item.hidden {
+                problems.push(format!(
+                    "The item {:?} in the room {:?} is found by searching, but isn't hidden.",
+                    item.id, room.title
+                ));
+            }
+            if item.name.is_none() && (item.article.is_some() || item.plural.is_some()) {
+                problems.push(format!(
+                    "The item {:?} in the room {:?} has an article or plural, but no name \
+                     of its own.",
+                    item.id, room.title
+                ));
+            }
+        }
+        for spawn in room.spawns.iter() {
+            let owner = format!("The spawn of {:?} in the room {:?}", spawn.id, room.title);
+            if item_db.find(&spawn.id).is_none() {
+                problems.push(format!("{} is of an unknown item.", owner));
+            }
+            if spawn.every == 0 {
+                problems.push(format!(
+                    "{} runs every 0 turns, but it must be at least 1.",
+                    owner
+                ));
+            }
+            if spawn.quantity == 0 || spawn.max == 0 {
+                problems.push(format!("{} never spawns any.", owner));
+            }
+        }
+        for npc in room.npcs.iter() {
+            if !level.npcs.contains_key(npc) {
+                problems.push(format!(
+                    "The room {:?} has an unknown NPC {:?}.",
+                    room.title, npc
+                ));
+            }
+        }
+        for id in room.vehicles.iter() {
+            match level.vehicles.get(id) {
+                None => problems.push(format!(
+                    "The room {:?} has an unknown vehicle {:?}.",
+                    room.title, id
+                )),
+                Some(vehicle)
+                    if water.contains(&room.coord)
+                        && !vehicle.terrain.contains(&Terrain::Water) =>
+                {
+                    problems.push(format!(
+                        "The vehicle {:?} starts on the water of the room {:?}, but it can't \
+                         go on water.",
+                        id, room.title
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+        for region in room.regions.iter() {
+            if !level.regions.contains_key(region) {
+                problems.push(format!(
+                    "The room {:?} is in an unknown region {:?}.",
+                    room.title, region
+                ));
+            }
+        }
+        for crowd in room.crowds.keys() {
+            if !level.crowds.contains_key(crowd) {
+                problems.push(format!(
+                    "The room {:?} has an unknown crowd {:?}.",
+                    room.title, crowd
+                ));
+            }
+        }
+        let owner = format!("the room {:?}", room.title);
+        if let Some(ref music) = room.music {
+            check_cue(&format!("The music of {}", owner), music, &mut problems);
+        }
+        check_passage(
+            &format!("The description of {}", owner),
+            &room.description,
+            item_db,
+            &mut problems,
+        );
+        if let Some(ref details) = room.details {
+            check_passage(
+                &format!("The details of {}", owner),
+                details,
+                item_db,
+                &mut problems,
+            );
+        }
+        for (weather, overlay) in room.weather.iter() {
+            check_passage(
+                &format!("The {} weather of {}", weather.lowercase_str(), owner),
+                overlay,
+                item_db,
+                &mut problems,
+            );
+        }
+        if level.climate.is_none() && (room.outdoors || !room.weather.is_empty()) {
+            problems.push(format!(
+                "The room {:?} is out in the weather, but the level has no climate.",
+                room.title
+            ));
+        }
+        let mut exits: Vec<_> = room.exits.iter().collect();
+        exits.sort_by_key(|(direction, _)| direction.lowercase_string());
+        for (direction, destination) in exits {
+            if destination.found_by.is_some() && !destination.hidden {
+                problems.push(format!(
+                    "The {} exit of {} is found by searching, but isn't hidden.",
+                    direction.lowercase_string(),
+                    owner
+                ));
+            }
+            check_destination(
+                &format!("The {} exit of {}", direction.lowercase_string(), owner),
+                destination,
+                level,
+                item_db,
+                &mut problems,
+            );
+        }
+        if let Some(ref ambient) = room.ambient {
+            check_ambient(&owner, ambient, item_db, &mut problems);
+        }
+        for hazard in room.hazards.iter() {
+            if !hazard_ids.insert(&hazard.id) {
+                problems.push(format!(
+                    "More than one hazard has the id {:?}, so disarming one disarms them all.",
+                    hazard.id
+                ));
+            }
+            check_hazard(&owner, hazard, level, item_db, &mut problems);
+        }
+        for puzzle in room.puzzles.iter() {
+            if !puzzle_ids.insert(&puzzle.id) {
+                problems.push(format!(
+                    "More than one puzzle has the id {:?}, so solving one solves them all.",
+                    puzzle.id
+                ));
+            }
+            check_puzzle(&owner, puzzle, level, item_db, &mut problems);
+        }
+        for scenery in room.scenery.iter() {
+            check_scenery(&owner, room, scenery, level, item_db, &mut problems);
+        }
+        for throw in room.throws.iter() {
+            let owner = format!("A throw in {}", owner);
+            if throw.targets.is_empty() {
+                problems.push(format!("{} has no targets to aim it at.", owner));
+            }
+            check_throw(&owner, throw, level, item_db, &mut problems);
+        }
+        check_actions(
+            &owner,
+            room.actions.iter().flatten(),
+            level,
+            item_db,
+            &mut problems,
+        );
+    }
+
+    if let Some(ref climate) = level.climate {
+        for (weather, message) in climate.messages.iter() {
+            check_passage(
+                &format!("The {} message of the climate", weather.lowercase_str()),
+                message,
+                item_db,
+                &mut problems,
+            );
+        }
+    }
+
+    let mut regions: Vec<_> = level.regions.iter().collect();
+    regions.sort_by_key(|(id, _)| *id);
+    for (id, region) in regions {
+        let weather = region.weather.values();
+        for passage in region
+            .banner
+            .iter()
+            .chain(region.description.iter())
+            .chain(weather)
+        {
+            check_passage(
+                &format!("The region {:?}", id),
+                passage,
+                item_db,
+                &mut problems,
+            );
+        }
+        if let Some(ref music) = region.music {
+            check_cue(
+                &format!("The music of the region {:?}", id),
+                music,
+                &mut problems,
+            );
+        }
+        if let Some(ref ambient) = region.ambient {
+            check_ambient(
+                &format!("the region {:?}", id),
+                ambient,
+                item_db,
+                &mut problems,
+            );
+        }
+        check_actions(
+            &format!("the region {:?}", id),
+            region.actions.iter(),
+            level,
+            item_db,
+            &mut problems,
+        );
+        for price in region.prices.iter() {
+            check_condition(
+                &format!("The price of the region {:?}", id),
+                Some(&price.when),
+                item_db,
+                &mut problems,
+            );
+        }
+    }
+
+    for item in level.start.items.iter() {
+        if item_db.find(&item.id).is_none() {
+            problems.push(format!(
+                "The player starts with an unknown item {:?}.",
+                item.id
+            ));
+        }
+    }
+
+    for scenario in level.scenarios.iter() {
+        if let Some(entry) = scenario.entry {
+            if !cells.contains(&entry) {
+                problems.push(format!(
+                    "The scenario {:?} starts at {}, which isn't on the map.",
+                    scenario.id, entry
+                ));
+            }
+        }
+        for item in scenario.start.iter().flat_map(|start| start.items.iter()) {
+            if item_db.find(&item.id).is_none() {
+                problems.push(format!(
+                    "The scenario {:?} starts with an unknown item {:?}.",
+                    scenario.id, item.id
+                ));
+            }
+        }
+    }
+
+    let mut pickups: Vec<_> = level.pickup_points.keys().collect();
+    pickups.sort();
+    for id in pickups {
+        if item_db.find(id).is_none() {
+            problems.push(format!("Points are given for an unknown item {:?}.", id));
+        }
+    }
+
+    for (id, vehicle) in level.vehicles.iter() {
+        let owner = format!("The vehicle {:?}", id);
+        let rooms = level
+            .rooms
+            .iter()
+            .filter(|room| room.vehicles.contains(id))
+            .count();
+        if rooms != 1 {
+            problems.push(format!(
+                "{} is listed by {} rooms, but it must start in one.",
+                owner, rooms
+            ));
+        }
+        if vehicle.targets.is_empty() {
+            problems.push(format!("{} has no targets, so it can't be boarded.", owner));
+        }
+        let passages = [
+            &vehicle.board,
+            &vehicle.dismount,
+            &vehicle.moves,
+            &vehicle.blocked,
+        ];
+        for passage in passages.iter().copied().flatten() {
+            check_passage(&owner, passage, item_db, &mut problems);
+        }
+    }
+    let mut water: Vec<&Coord> = water.iter().collect();
+    water.sort();
+    let crosses_water = |vehicle: &Vehicle| vehicle.terrain.contains(&Terrain::Water);
+    if let Some(coord) = water.first() {
+        if !level.vehicles.values().any(crosses_water) {
+            problems.push(format!(
+                "The map has water at {}, but no vehicle can go on it.",
+                coord
+            ));
+        }
+    }
+
+    let mut npcs: Vec<_> = level.npcs.iter().collect();
+    npcs.sort_by_key(|(id, _)| *id);
+    for (id, npc) in npcs {
+        let owner = format!("the NPC {:?}", id);
+        check_passage(
+            &format!("The description of {}", owner),
+            &npc.description,
+            item_db,
+            &mut problems,
+        );
+        check_passage(
+            &format!("What {} says", owner),
+            &npc.talk,
+            item_db,
+            &mut problems,
+        );
+        for item in npc.items.iter() {
+            if item_db.find(&item.id).is_none() {
+                problems.push(format!(
+                    "The NPC {:?} sells an unknown item {:?}.",
+                    id, item.id
+                ));
+            }
+        }
+        if let Movement::Wander { ref region, chance } = npc.movement {
+            if !level.regions.contains_key(region) {
+                problems.push(format!(
+                    "The NPC {:?} wanders in an unknown region {:?}.",
+                    id, region
+                ));
+            }
+            if !(0.0..=1.0).contains(&chance) {
+                problems.push(format!(
+                    "The wander chance of the NPC {:?} is {}, but it must be from 0 to 1.",
+                    id, chance
+                ));
+            }
+        }
+        if npc.carry == Some(0) {
+            problems.push(format!(
+                "The NPC {:?} can carry 0 items, but it must be at least 1.",
+                id
+            ));
+        }
+        if let Some(ref companion) = npc.companion {
+            for key in companion.comments.keys() {
+                if level.find_entry(key).is_none() {
+                    problems.push(format!(
+                        "The NPC {:?} comments on an unknown room {:?}.",
+                        id, key
+                    ));
+                }
+            }
+        }
+        if let Some(ref pet) = npc.pet {
+            for item in pet.eats.iter() {
+                if item_db.find(item).is_none() {
+                    problems.push(format!("The NPC {:?} eats an unknown item {:?}.", id, item));
+                }
+            }
+        }
+        if npc.stubbornness > 100 {
+            problems.push(format!(
+                "The stubbornness of the NPC {:?} is {}, but it must be from 0 to 100.",
+                id, npc.stubbornness
+            ));
+        }
+        if let Some(ref faction) = npc.faction {
+            if !level.factions.contains_key(faction) {
+                problems.push(format!(
+                    "The NPC {:?} belongs to an unknown faction {:?}.",
+                    id, faction
+                ));
+            }
+        }
+        if let Movement::Follow { ref when } = npc.movement {
+            check_condition(
+                &format!("The NPC {:?}", id),
+                Some(when),
+                item_db,
+                &mut problems,
+            );
+        }
+        for topic in npc.topics.iter() {
+            let owner = match topic.targets.first() {
+                Some(name) => format!("The topic {:?} of the NPC {:?}", name, id),
+                None => {
+                    problems.push(format!("The NPC {:?} has a topic with no targets.", id));
+                    continue;
+                }
+            };
+            check_passage(&owner, &topic.value, item_db, &mut problems);
+            check_condition(&owner, topic.when.as_ref(), item_db, &mut problems);
+            check_effects(&owner, &topic.effects, level, item_db, &mut problems);
+        }
+        for gift in npc.gifts.iter() {
+            let owner = format!("A gift of the NPC {:?}", id);
+            if gift.items.is_empty() {
+                problems.push(format!("{} has no items.", owner));
+            }
+            for item in gift.items.iter() {
+                if item_db.find(item).is_none() {
+                    problems.push(format!("{} takes an unknown item {:?}.", owner, item));
+                }
+            }
+            check_passage(&owner, &gift.value, item_db, &mut problems);
+            check_effects(&owner, &gift.effects, level, item_db, &mut problems);
+        }
+        for throw in npc.throws.iter() {
+            let owner = format!("A throw at the NPC {:?}", id);
+            check_throw(&owner, throw, level, item_db, &mut problems);
+        }
+    }
+
+    let mut daemons: Vec<_> = level.daemons.iter().collect();
+    daemons.sort_by_key(|(id, _)| *id);
+    for (id, daemon) in daemons {
+        let owner = format!("The daemon {:?}", id);
+        if daemon.every == 0 {
+            problems.push(format!(
+                "{} runs every 0 turns, but it must be at least 1.",
+                owner
+            ));
+        }
+        if daemon.effects.is_empty() {
+            problems.push(format!("{} has no effects.", owner));
+        }
+        check_effects(&owner, &daemon.effects, level, item_db, &mut problems);
+    }
+
+    let mut timers: Vec<_> = level.timers.iter().collect();
+    timers.sort_by_key(|(id, _)| *id);
+    for (id, timer) in timers {
+        let owner = format!("The timer {:?}", id);
+        if timer.turns == 0 {
+            problems.push(format!(
+                "{} counts down from 0 turns, but it must be at least 1.",
+                owner
+            ));
+        }
+        for (left, warning) in timer.warnings.iter() {
+            if *left == 0 || *left >= timer.turns {
+                problems.push(format!(
+                    "{} warns with {} turns left, which it never has.",
+                    owner, left
+                ));
+            }
+            check_passage(&owner, warning, item_db, &mut problems);
+        }
+        check_effects(&owner, &timer.effects, level, item_db, &mut problems);
+        check_effects(
+            &format!("The defused effects of the timer {:?}", id),
+            &timer.defused,
+            level,
+            item_db,
+            &mut problems,
+        );
+    }
+
+    for (index, script) in level.scripts.iter().enumerate() {
+        let owner = format!("The script #{}", index + 1);
+        match script.on {
+            Trigger::ItemTaken(ref id) if item_db.find(id).is_none() => {
+                problems.push(format!("{} runs on an unknown item {:?}.", owner, id));
+            }
+            Trigger::RoomEntered(ref entry) if level.find_entry(entry).is_none() => {
+                problems.push(format!(
+                    "{} runs on an unknown entry or room {:?}.",
+                    owner, entry
+                ));
+            }
+            Trigger::NpcTalkedTo(ref id) if !level.npcs.contains_key(id) => {
+                problems.push(format!("{} runs on an unknown NPC {:?}.", owner, id));
+            }
+            _ => {}
+        }
+        if script.effects.is_empty() {
+            problems.push(format!("{} has no effects.", owner));
+        }
+        check_condition(&owner, script.when.as_ref(), item_db, &mut problems);
+        check_effects(&owner, &script.effects, level, item_db, &mut problems);
+    }
+
+    for (id, need) in level.mechanics.survival.iter() {
+        let owner = format!("The survival need {:?}", id);
+        if need.every == 0 {
+            problems.push(format!(
+                "{} runs down every 0 turns, but it must be at least 1.",
+                owner
+            ));
+        }
+        for warning in need.warnings.iter() {
+            if !(1..=need.max).contains(&warning.below) {
+                problems.push(format!(
+                    "{} warns below {}, but it must be from 1 to {}.",
+                    owner, warning.below, need.max
+                ));
+            }
+            check_passage(
+                &format!("A warning of {}", owner.to_lowercase()),
+                &warning.message,
+                item_db,
+                &mut problems,
+            );
+        }
+        check_passage(
+            &format!("The death of {}", owner.to_lowercase()),
+            &need.death,
+            item_db,
+            &mut problems,
+        );
+    }
+
+    for (id, status) in level.statuses.iter() {
+        let owner = format!("the status {:?}", id);
+        if status.turns == 0 {
+            problems.push(format!("The status {:?} lasts for 0 turns.", id));
+        }
+        if !(0.0..=1.0).contains(&status.stumble) {
+            problems.push(format!(
+                "The stumble chance of {} is {}, but it must be from 0 to 1.",
+                owner, status.stumble
+            ));
+        }
+        for (name, passage) in [("message", &status.message), ("expires", &status.expires)] {
+            if let Some(passage) = passage {
+                check_passage(
+                    &format!("The {} of {}", name, owner),
+                    passage,
+                    item_db,
+                    &mut problems,
+                );
+            }
+        }
+    }
+
+    for (id, lore) in level.lore.iter() {
+        check_passage(
+            &format!("The text of the lore entry {:?}", id),
+            &lore.text,
+            item_db,
+            &mut problems,
+        );
+    }
+
+    for (id, quest) in level.quests.iter() {
+        let owner = format!("the quest {:?}", id);
+        if quest.turns == Some(0) {
+            problems.push(format!("The quest {:?} has 0 turns to finish it.", id));
+        }
+        if quest.turns.is_none() && (quest.grace > 0 || !quest.late.is_empty()) {
+            problems.push(format!(
+                "The quest {:?} has a grace period, but no turns, so it can't be late.",
+                id
+            ));
+        }
+        if let Some(ref escort) = quest.escort {
+            if !level.npcs.contains_key(&escort.npc) {
+                problems.push(format!(
+                    "The quest {:?} escorts an unknown NPC {:?}.",
+                    id, escort.npc
+                ));
+            }
+            if let Some(to) = escort.to.filter(|to| level.get_room(to).is_none()) {
+                problems.push(format!(
+                    "The quest {:?} escorts to {}, but no room is there.",
+                    id, to
+                ));
+            }
+        }
+        check_passage(
+            &format!("The description of {}", owner),
+            &quest.description,
+            item_db,
+            &mut problems,
+        );
+        for (name, effects) in [
+            ("reward", &quest.reward),
+            ("late", &quest.late),
+            ("failed", &quest.failed),
+        ] {
+            check_effects(
+                &format!("The {} effects of {}", name, owner),
+                effects,
+                level,
+                item_db,
+                &mut problems,
+            );
+        }
+    }
+
+    for (id, faction) in level.factions.iter() {
+        check_condition(
+            &format!("The faction {:?}", id),
+            faction.hostile_when.as_ref(),
+            item_db,
+            &mut problems,
+        );
+        if let Some(ref hostile) = faction.hostile {
+            check_passage(
+                &format!("The hostile text of the faction {:?}", id),
+                hostile,
+                item_db,
+                &mut problems,
+            );
+        }
+    }
+
+    let mut missing_rooms: Vec<&Coord> = cells
+        .iter()
+        .filter(|coord| !rooms_by_coord.contains_key(coord))
+        .collect();
+    missing_rooms.sort_by_key(|coord| (coord.z, coord.y, coord.x));
+    for coord in missing_rooms {
+        is_map_sound = false;
+        problems.push(format!(
+            "The map has a room at {}, but no room is defined.",
+            coord
+        ));
+    }
+
+    if cells.contains(&level.entry) {
+        let reachable = RoomGraph::from_cells(&cells).reachable_from(level.entry);
+        let mut unreachable: Vec<_> = level
+            .rooms
+            .iter()
+            .filter(|room| cells.contains(&room.coord) && !reachable.contains(&room.coord))
+            .collect();
+        unreachable.sort_by_key(|room| (room.coord.z, room.coord.y, room.coord.x));
+        for room in unreachable {
+            problems.push(format!(
+                "The room {:?} at {} can't be reached from the entry.",
+                room.title, room.coord
+            ));
+        }
+    } else {
+        is_map_sound = false;
+        problems.push(format!("The entry {} isn't on the map.", level.entry));
+    }
+
+    for split in level.race.iter() {
+        let owner = format!("The race checkpoint {:?}", split.name);
+        match split.at {
+            Some(ref at) if level.find_entry(at).is_none() => {
+                problems.push(format!(
+                    "{} is at an unknown entry or room {:?}.",
+                    owner, at
+                ));
+            }
+            None if split.when.is_none() => {
+                problems.push(format!(
+                    "{} has no room or condition, so it's reached right away.",
+                    owner
+                ));
+            }
+            _ => {}
+        }
+        check_condition(&owner, split.when.as_ref(), item_db, &mut problems);
+    }
+
+    for recipe in item_db.recipes() {
+        let owner = format!("The recipe for {:?}", recipe.output);
+        if item_db.find(&recipe.output).is_none() {
+            problems.push(format!("{} makes an unknown item.", owner));
+        }
+        if recipe.ingredients.is_empty() {
+            problems.push(format!("{} has no ingredients.", owner));
+        }
+        for (id, &count) in recipe.ingredients.iter() {
+            if item_db.find(id).is_none() {
+                problems.push(format!("{} needs an unknown item {:?}.", owner, id));
+            }
+            if count == 0 {
+                problems.push(format!(
+                    "{} needs 0 of {:?}, but it must be at least 1.",
+                    owner, id
+                ));
+            }
+        }
+    }
+
+    // Loading the map would stop at any of its problems.
+    if is_map_sound && level.par.is_some() {
+        match walkthrough(level) {
+            // Gated levels can only be checked by playing them.
+            Ok(_) | Err(Stuck::Gated(..)) => {}
+            Err(stuck) => problems.push(stuck.to_string()),
+        }
+    }
+
+    problems
+}
+
+/// Check that a destination in this level exists. Destinations in other levels are
+/// only known once the campaign loads them.
+fn check_destination(
+    owner: &str,
+    destination: &Destination,
+    level: &Level,
+    item_db: &ItemDatabase,
+    problems: &mut Vec<String>,
+) {
+    if destination.level.is_none() && level.find_entry(&destination.entry).is_none() {
+        problems.push(format!(
+            "{} leads to an unknown entry or room {:?}.",
+            owner, destination.entry
+        ));
+    }
+    check_condition(owner, destination.when.as_ref(), item_db, problems);
+    if let Some(ref blocked) = destination.blocked {
+        check_passage(
+            &format!("The blocked text of {}", owner.to_lowercase()),
+            blocked,
+            item_db,
+            problems,
+        );
+    }
+}
+
+fn check_actions<'a>(
+    owner: &str,
+    actions: impl Iterator<Item = &'a Action>,
+    level: &Level,
+    item_db: &ItemDatabase,
+    problems: &mut Vec<String>,
+) {
+    for action in actions {
+        check_passage(
+            &format!("An action in {}", owner),
+            &action.value,
+            item_db,
+            problems,
+        );
+        let achievement = action
+            .award
+            .as_ref()
+            .and_then(|award| award.achievement.as_ref());
+        if let Some(achievement) = achievement {
+            if !level.achievements.contains_key(achievement) {
+                problems.push(format!(
+                    "An action in {} awards an unknown achievement {:?}.",
+                    owner, achievement
+                ));
+            }
+        }
+        if let Some(ref destination) = action.teleport {
+            check_destination(
+                &format!("An action in {}", owner),
+                destination,
+                level,
+                item_db,
+                problems,
+            );
+        }
+        check_effects(
+            &format!("An action in {}", owner),
+            &action.effects,
+            level,
+            item_db,
+            problems,
+        );
+        check_condition(
+            &format!("An action in {}", owner),
+            action.when.as_ref(),
+            item_db,
+            problems,
+        );
+        if action.targets.iter().any(|target| target.trim().is_empty()) {
+            problems.push(format!("An action in {} has an empty target.", owner));
+        } else if action.targets.is_empty() && action.verb != Verb::Emote {
+            // Only emotes can be used without a target.
+            problems.push(format!(
+                "An action in {} has no targets, so it can never be used.",
+                owner
+            ));
+        }
+    }
+}
+
+fn check_effects(
+    owner: &str,
+    effects: &[Effect],
+    level: &Level,
+    item_db: &ItemDatabase,
+    problems: &mut Vec<String>,
+) {
+    for effect in effects {
+        match effect {
+            Effect::Say(passage) | Effect::Death(passage) => {
+                check_passage(owner, passage, item_db, problems)
+            }
+            Effect::Teleport(destination) => {
+                check_destination(owner, destination, level, item_db, problems)
+            }
+            Effect::Award(award) => {
+                if let Some(ref achievement) = award.achievement {
+                    if !level.achievements.contains_key(achievement) {
+                        problems.push(format!(
+                            "{} awards an unknown achievement {:?}.",
+                            owner, achievement
+                        ));
+                    }
+                }
+            }
+            Effect::Status(id) | Effect::Cure(id) => {
+                if !level.statuses.contains_key(id) {
+                    let verb = match effect {
+                        Effect::Status(_) => "gives",
+                        _ => "cures",
+                    };
+                    problems.push(format!("{} {} an unknown status {:?}.", owner, verb, id));
+                }
+            }
+            Effect::Enable(id) | Effect::Disable(id) => {
+                if !level.daemons.contains_key(id) {
+                    let verb = match effect {
+                        Effect::Enable(_) => "enables",
+                        _ => "disables",
+                    };
+                    problems.push(format!("{} {} an unknown daemon {:?}.", owner, verb, id));
+                }
+            }
+            Effect::Reputation(changes) => {
+                for id in changes.keys() {
+                    if !level.factions.contains_key(id) {
+                        problems.push(format!(
+                            "{} changes the reputation of an unknown faction {:?}.",
+                            owner, id
+                        ));
+                    }
+                }
+            }
+            Effect::Step(step) => {
+                let is_known = level
+                    .rooms
+                    .iter()
+                    .flat_map(|room| room.puzzles.iter())
+                    .any(|puzzle| puzzle.sequence.contains(step));
+                if !is_known {
+                    problems.push(format!(
+                        "{} takes the step {:?}, which isn't in the sequence of any puzzle.",
+                        owner, step
+                    ));
+                }
+            }
+            Effect::Reveal(items) => {
+                for id in items.keys() {
+                    if item_db.find(id).is_none() {
+                        problems.push(format!("{} reveals an unknown item {:?}.", owner, id));
+                    }
+                }
+            }
+            Effect::Quest(id) | Effect::Complete(id) => {
+                if !level.quests.contains_key(id) {
+                    let verb = match effect {
+                        Effect::Quest(_) => "starts",
+                        _ => "completes",
+                    };
+                    problems.push(format!("{} {} an unknown quest {:?}.", owner, verb, id));
+                }
+            }
+            Effect::Arm(id) | Effect::Defuse(id) => {
+                if !level.timers.contains_key(id) {
+                    let verb = match effect {
+                        Effect::Arm(_) => "arms",
+                        _ => "defuses",
+                    };
+                    problems.push(format!("{} {} an unknown timer {:?}.", owner, verb, id));
+                }
+            }
+            Effect::Harm(id) => {
+                if !level.npcs.contains_key(id) {
+                    problems.push(format!("{} harms an unknown NPC {:?}.", owner, id));
+                }
+            }
+            Effect::Join(id) | Effect::Leave(id) => match level.npcs.get(id) {
+                Some(npc) if npc.companion.is_none() => problems.push(format!(
+                    "{} has the NPC {:?} join or leave the party, but it isn't a companion.",
+                    owner, id
+                )),
+                None => problems.push(format!(
+                    "{} has an unknown NPC {:?} join or leave the party.",
+                    owner, id
+                )),
+                Some(_) => {}
+            },
+            Effect::Lore(id) => {
+                if !level.lore.contains_key(id) {
+                    problems.push(format!("{} unlocks an unknown lore entry {:?}.", owner, id));
+                }
+            }
+            Effect::Sound(id) => check_cue(owner, id, problems),
+            Effect::Stats(_) | Effect::SetFlag(_) | Effect::ClearFlag(_) => {}
+        }
+    }
+}
+
+/// Check that a sound cue's id can name one of the files in data/audio, see `Cue`.
+fn check_cue(owner: &str, id: &str, problems: &mut Vec<String>) {
+    if !audio::is_cue_id(id) {
+        problems.push(format!(
+            "{} plays {:?}, but sounds can only be named with letters, numbers, - and _.",
+            owner, id
+        ));
+    }
+}
+
+fn check_puzzle(
+    owner: &str,
+    puzzle: &Puzzle,
+    level: &Level,
+    item_db: &ItemDatabase,
+    problems: &mut Vec<String>,
+) {
+    let owner = format!("The puzzle {:?} of {}", puzzle.id, owner);
+    if puzzle.answers.is_empty() && puzzle.sequence.is_empty() {
+        problems.push(format!(
+            "{} has no answers or sequence, so it can't be solved.",
+            owner
+        ));
+    }
+    check_passage(&owner, &puzzle.solved, item_db, problems);
+    if let Some(ref wrong) = puzzle.wrong {
+        check_passage(&owner, wrong, item_db, problems);
+    }
+    check_effects(&owner, &puzzle.effects, level, item_db, problems);
+}
+
+fn check_hazard(
+    owner: &str,
+    hazard: &Hazard,
+    level: &Level,
+    item_db: &ItemDatabase,
+    problems: &mut Vec<String>,
+) {
+    let room = owner;
+    let owner = format!("The hazard {:?} of {}", hazard.id, room);
+    check_passage(&owner, &hazard.message, item_db, problems);
+    check_effects(&owner, &hazard.effects, level, item_db, problems);
+    if hazard.disarm.is_some() && hazard.targets.is_empty() {
+        problems.push(format!(
+            "{} can be disarmed, but has no targets to call it by.",
+            owner
+        ));
+    }
+    let safeguards = [("avoid", &hazard.avoid), ("disarm", &hazard.disarm)];
+    for (name, safeguard) in safeguards.iter() {
+        let safeguard = match safeguard {
+            Some(safeguard) => safeguard,
+            None => continue,
+        };
+        let owner = format!("The {} of the hazard {:?} of {}", name, hazard.id, room);
+        check_condition(&owner, safeguard.when.as_ref(), item_db, problems);
+        if let Some(ref check) = safeguard.check {
+            if check.sides < 1 {
+                problems.push(format!(
+                    "{} rolls a die with {} sides, but it needs at least 1.",
+                    owner, check.sides
+                ));
+            }
+        }
+        for passage in safeguard.message.iter().chain(safeguard.failed.iter()) {
+            check_passage(&owner, passage, item_db, problems);
+        }
+    }
+}
+
+fn check_scenery(
+    owner: &str,
+    room: &Room,
+    scenery: &Scenery,
+    level: &Level,
+    item_db: &ItemDatabase,
+    problems: &mut Vec<String>,
+) {
+    let owner = format!("The scenery {:?} of {}", scenery.id, owner);
+    for passage in std::iter::once(&scenery.value)
+        .chain(scenery.moved.iter())
+        .chain(scenery.stuck.iter())
+    {
+        check_passage(&owner, passage, item_db, problems);
+    }
+    check_condition(&owner, scenery.when.as_ref(), item_db, problems);
+    check_effects(&owner, &scenery.effects, level, item_db, problems);
+    for direction in scenery.exits.iter() {
+        if !room.exits.get(direction).is_some_and(|exit| exit.hidden) {
+            problems.push(format!(
+                "{} uncovers the {} exit, but it isn't a hidden exit.",
+                owner,
+                direction.lowercase_string()
+            ));
+        }
+    }
+}
+
+fn check_throw(
+    owner: &str,
+    throw: &Throw,
+    level: &Level,
+    item_db: &ItemDatabase,
+    problems: &mut Vec<String>,
+) {
+    for item in throw.items.iter() {
+        if item_db.find(item).is_none() {
+            problems.push(format!("{} takes an unknown item {:?}.", owner, item));
+        }
+    }
+    if let Some(ref land) = throw.land {
+        if throw.breaks {
+            problems.push(format!("{} breaks the item, so it can't land.", owner));
+        }
+        if level.find_entry(land).is_none() {
+            problems.push(format!(
+                "{} lands in an unknown entry or room {:?}.",
+                owner, land
+            ));
+        }
+    }
+    check_passage(owner, &throw.value, item_db, problems);
+    check_effects(owner, &throw.effects, level, item_db, problems);
+}
+
+fn check_ambient(
+    owner: &str,
+    ambient: &Ambient,
+    item_db: &ItemDatabase,
+    problems: &mut Vec<String>,
+) {
+    if !(0.0..=1.0).contains(&ambient.chance) {
+        problems.push(format!(
+            "The ambient chance of {} is {}, but it must be from 0 to 1.",
+            owner, ambient.chance
+        ));
+    }
+    if ambient.lines.is_empty() {
+        problems.push(format!("The ambient lines of {} are empty.", owner));
+    }
+    for line in ambient.lines.iter() {
+        check_passage(
+            &format!("An ambient line of {}", owner),
+            line,
+            item_db,
+            problems,
+        );
+    }
+}
+
+/// Check that a `when` condition can be read, see `condition::evaluate`.
+fn check_condition(
+    owner: &str,
+    condition: Option<&Condition>,
+    item_db: &ItemDatabase,
+    problems: &mut Vec<String>,
+) {
+    if let Some(Condition(text)) = condition {
+        if let Err(problem) = condition::check(text, item_db) {
+            problems.push(format!(
+                "{} has {} in its condition {:?}.",
+                owner, problem, text
+            ));
+        }
+    }
+}
+
+/// Check the templates in a passage, see `template::render`.
+fn check_passage(
+    owner: &str,
+    passage: &Passage,
+    item_db: &ItemDatabase,
+    problems: &mut Vec<String>,
+) {
+    for text in passage.texts() {
+        if let Err(problem) = template::check(text, item_db) {
+            problems.push(format!("{} has {}.", owner, problem));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let level: Level = serde_yaml::from_str(
+            r#"
+maps:
+  - - "-----"
+    - "-..--"
+    - "--.-."
+    - "----~"
+    - "-%^*-"
+entry: [1, 1, 0]
+tiles:
+  "^": { when: items.lava-boots > 0, damage: 2, message: "{if flags.hot}Hot." }
+  "*": { passable: false, message: Boulders. }
+  "-": { blocked: No way. }
+npcs:
+  cat: { targets: [cat], talk: Meow., items: [], faction: gang, movement: { wander: { region: city, chance: 2 } }, companion: { comments: { moon: Pretty. } }, pet: { eats: [catnip] } }
+  dog: { targets: [dog], talk: Woof., items: [], topics: [{ targets: [], value: Woof. }, { targets: [bone], value: Woof!, when: "items.bone >" }], gifts: [{ items: [bone], value: Woof. }], carry: 0, stubbornness: 150, throws: [{ items: [stick], value: Fetch!, break: true, land: park }] }
+regions:
+  town:
+    banner: "{if flags.fair}Bunting hangs everywhere."
+    weather: { rain: "{if weather.wet}Puddles.{end}" }
+    prices: [{ when: "money >", percent: 90 }]
+    actions:
+      - { verb: Look, targets: [], value: Nothing to see. }
+      - { verb: Talk, targets: [guide], value: This way., teleport: { entry: docks } }
+      - { verb: Look, targets: [sky], value: Blue., award: { id: sky, achievement: skywatcher } }
+      - { verb: Look, targets: [moon], value: "{if flags.night}Bright.{else}Pale." }
+      - { verb: Look, targets: [bell], value: Dong., effects: [{ enable: curfew }] }
+      - { verb: Look, targets: [cloud], value: Puffy., when: "items.cloud > 0" }
+    ambient: { chance: 1.5, lines: [] }
+pickup_points: { unicorn: 5 }
+race: [{ name: Moon, at: moon }, { name: Start }, { name: Key, when: "items.key >" }]
+daemons:
+  tide: { every: 0, effects: [{ say: "{if flags.wet}Splash." }, { disable: moon }, { reputation: { guild: 1 } }, { status: drunk }, { lore: atlantis }] }
+timers:
+  keg:
+    turns: 3
+    warnings: { 1: Hiss., 3: Fizz. }
+    effects: [{ say: Boom. }, { arm: bomb }]
+    defused: [{ defuse: fuse }]
+scripts:
+  - { on: { item_taken: grail }, effects: [{ say: Holy. }] }
+  - { on: { room_entered: vault }, when: "flags.x &&", effects: [] }
+  - { on: { npc_talked_to: dog }, effects: [{ arm: bomb }, { join: dog }, { leave: ghost }] }
+vehicles:
+  raft: { name: a raft, targets: [], moves: "{if flags.wet}Paddling." }
+  cart: { name: a cart, targets: [cart] }
+quests:
+  errand: { name: Errand, description: Go., turns: 0, failed: [{ complete: chores }] }
+  chore: { name: Chore, description: "{if flags.x}Do it.", grace: 2, failed: [{ harm: guard }] }
+  escort: { name: Escort, description: Walk., escort: { npc: child, to: [8, 8, 0] } }
+lore:
+  founding: { title: The Founding, text: "{if flags.old}Long ago." }
+statuses:
+  poison: { turns: 0, stumble: -1, expires: "{if flags.cured}Better." }
+mechanics:
+  survival:
+    water: { max: 10, every: 0, warnings: [{ below: 12, message: Thirsty. }], death: "{if flags.sea}Parched." }
+factions:
+  thieves: { name: Thieves, hostile: "{if flags.caught}Leave." }
+rooms:
+  - { title: Gate, coord: [1, 1, 0], id: gate, description: A gate., npcs: [guard], vehicles: [raft, wagon], exits: { north: { entry: tower, when: "flags.open &&" } } }
+  - title: Road
+    coord: [2, 1, 0]
+    description: A road.
+    items: [{ id: unicorn, quantity: 1, name: ~, pickup: ~, found_by: hay, article: an }]
+    details: "{if light.dark}Shadows.{end}"
+    hazards: [{ id: pit, message: A pit!, disarm: { when: items.unicorn, check: { target: 10, sides: 0 } } }]
+    scenery:
+      - { id: rock, value: "{if flags.x}Rolls.", effects: [{ harm: rock }], exits: [east] }
+    throws: [{ value: Thud. }]
+    puzzles:
+      - { id: dial, solved: Click., effects: [{ reveal: { unicorn: 1 } }, { step: left }] }
+  - { title: Other Road, coord: [2, 1, 0], description: A road., regions: [town, city], outdoors: true, music: ../theme }
+  - { title: Island, coord: [4, 2, 0], id: gate, description: An island., vehicles: [raft] }
+  - { title: Cloud, coord: [9, 9, 0], description: "A {items.cloud.name}.", spawns: [{ id: gem, every: 0, max: 0 }] }
+scenarios:
+  - { id: lost, name: Lost, entry: [0, 0, 0], start: { items: [{ id: map }] } }
+"#,
+        )
+        .unwrap();
+        let items = fs::read_to_string(ITEMS_PATH).unwrap().replace(
+            "recipes: []",
+            "recipes: [{ ingredients: { apple: 0, map: 1 }, output: pie }]",
+        );
+        let item_db: ItemDatabase = serde_yaml::from_str(&items).unwrap();
+        insta::assert_yaml_snapshot!(validate(&level, &item_db), @r###"
+        ---
+        - "Unknown character '%' in the map at [1, 4, 0], add it to the level's tiles to make it a room."
+        - "The tile \"*\" isn't passable, so its rules for entering it are never used."
+        - "The tile \"-\" is built in, so it can't be changed."
+        - "The tile \"-\" has a blocked message, but no condition."
+        - "The tile \"^\" has the unknown value \"items.lava-boots\" in its condition \"items.lava-boots > 0\"."
+        - "The tile \"^\" does damage, but the player starts with no health to lose."
+        - "The tile \"^\" has an {if flags.hot} block with no {end}."
+        - "The room \"Gate\" has an unknown NPC \"guard\"."
+        - "The room \"Gate\" has an unknown vehicle \"wagon\"."
+        - "The north exit of the room \"Gate\" leads to an unknown entry or room \"tower\"."
+        - "The north exit of the room \"Gate\" has something missing at the end in its condition \"flags.open &&\"."
+        - "The room \"Road\" has an unknown item \"unicorn\"."
+        - "The item \"unicorn\" in the room \"Road\" is found by searching, but isn't hidden."
+        - "The item \"unicorn\" in the room \"Road\" has an article or plural, but no name of its own."
+        - "The details of the room \"Road\" has the unknown value \"light.dark\" in the condition {if light.dark}."
+        - "The hazard \"pit\" of the room \"Road\" can be disarmed, but has no targets to call it by."
+        - "The disarm of the hazard \"pit\" of the room \"Road\" has the unknown value \"items.unicorn\" in its condition \"items.unicorn\"."
+        - "The disarm of the hazard \"pit\" of the room \"Road\" rolls a die with 0 sides, but it needs at least 1."
+        - "The puzzle \"dial\" of the room \"Road\" has no answers or sequence, so it can't be solved."
+        - "The puzzle \"dial\" of the room \"Road\" reveals an unknown item \"unicorn\"."
+        - "The puzzle \"dial\" of the room \"Road\" takes the step \"left\", which isn't in the sequence of any puzzle."
+        - "The scenery \"rock\" of the room \"Road\" has an {if flags.x} block with no {end}."
+        - "The scenery \"rock\" of the room \"Road\" harms an unknown NPC \"rock\"."
+        - "The scenery \"rock\" of the room \"Road\" uncovers the east exit, but it isn't a hidden exit."
+        - "A throw in the room \"Road\" has no targets to aim it at."
+        - "The rooms \"Road\" and \"Other Road\" are both at [2, 1, 0], only the first is used."
+        - "The room \"Other Road\" is in an unknown region \"city\"."
+        - "The music of the room \"Other Road\" plays \"../theme\", but sounds can only be named with letters, numbers, - and _."
+        - "The room \"Other Road\" is out in the weather, but the level has no climate."
+        - "The rooms \"Gate\" and \"Island\" both have the id \"gate\", only the first is used."
+        - "The room \"Cloud\" at [9, 9, 0] isn't on the map."
+        - "The spawn of \"gem\" in the room \"Cloud\" is of an unknown item."
+        - "The spawn of \"gem\" in the room \"Cloud\" runs every 0 turns, but it must be at least 1."
+        - "The spawn of \"gem\" in the room \"Cloud\" never spawns any."
+        - "The description of the room \"Cloud\" has the unknown item {items.cloud.name}."
+        - "The region \"town\" has an {if flags.fair} block with no {end}."
+        - "The region \"town\" has the unknown value \"weather.wet\" in the condition {if weather.wet}."
+        - "The ambient chance of the region \"town\" is 1.5, but it must be from 0 to 1."
+        - "The ambient lines of the region \"town\" are empty."
+        - "An action in the region \"town\" has no targets, so it can never be used."
+        - "An action in the region \"town\" leads to an unknown entry or room \"docks\"."
+        - "An action in the region \"town\" awards an unknown achievement \"skywatcher\"."
+        - "An action in the region \"town\" has an {if flags.night} block with no {end}."
+        - "An action in the region \"town\" enables an unknown daemon \"curfew\"."
+        - "An action in the region \"town\" has the unknown value \"items.cloud\" in its condition \"items.cloud > 0\"."
+        - "The price of the region \"town\" has something missing at the end in its condition \"money >\"."
+        - "The scenario \"lost\" starts at [0, 0, 0], which isn't on the map."
+        - "The scenario \"lost\" starts with an unknown item \"map\"."
+        - "Points are given for an unknown item \"unicorn\"."
+        - "The vehicle \"cart\" is listed by 0 rooms, but it must start in one."
+        - "The vehicle \"raft\" is listed by 2 rooms, but it must start in one."
+        - "The vehicle \"raft\" has no targets, so it can't be boarded."
+        - "The vehicle \"raft\" has an {if flags.wet} block with no {end}."
+        - "The map has water at [4, 3, 0], but no vehicle can go on it."
+        - "The NPC \"cat\" wanders in an unknown region \"city\"."
+        - "The wander chance of the NPC \"cat\" is 2, but it must be from 0 to 1."
+        - "The NPC \"cat\" comments on an unknown room \"moon\"."
+        - "The NPC \"cat\" eats an unknown item \"catnip\"."
+        - "The NPC \"cat\" belongs to an unknown faction \"gang\"."
+        - "The NPC \"dog\" can carry 0 items, but it must be at least 1."
+        - "The stubbornness of the NPC \"dog\" is 150, but it must be from 0 to 100."
+        - "The NPC \"dog\" has a topic with no targets."
+        - "The topic \"bone\" of the NPC \"dog\" has something missing at the end in its condition \"items.bone >\"."
+        - "A gift of the NPC \"dog\" takes an unknown item \"bone\"."
+        - "A throw at the NPC \"dog\" takes an unknown item \"stick\"."
+        - "A throw at the NPC \"dog\" breaks the item, so it can't land."
+        - "A throw at the NPC \"dog\" lands in an unknown entry or room \"park\"."
+        - "The daemon \"tide\" runs every 0 turns, but it must be at least 1."
+        - "The daemon \"tide\" has an {if flags.wet} block with no {end}."
+        - "The daemon \"tide\" disables an unknown daemon \"moon\"."
+        - "The daemon \"tide\" changes the reputation of an unknown faction \"guild\"."
+        - "The daemon \"tide\" gives an unknown status \"drunk\"."
+        - "The daemon \"tide\" unlocks an unknown lore entry \"atlantis\"."
+        - "The timer \"keg\" warns with 3 turns left, which it never has."
+        - "The timer \"keg\" arms an unknown timer \"bomb\"."
+        - "The defused effects of the timer \"keg\" defuses an unknown timer \"fuse\"."
+        - "The script #1 runs on an unknown item \"grail\"."
+        - "The script #2 runs on an unknown entry or room \"vault\"."
+        - "The script #2 has no effects."
+        - "The script #2 has something missing at the end in its condition \"flags.x &&\"."
+        - "The script #3 arms an unknown timer \"bomb\"."
+        - "The script #3 has the NPC \"dog\" join or leave the party, but it isn't a companion."
+        - "The script #3 has an unknown NPC \"ghost\" join or leave the party."
+        - "The survival need \"water\" runs down every 0 turns, but it must be at least 1."
+        - "The survival need \"water\" warns below 12, but it must be from 1 to 10."
+        - "The death of the survival need \"water\" has an {if flags.sea} block with no {end}."
+        - "The status \"poison\" lasts for 0 turns."
+        - "The stumble chance of the status \"poison\" is -1, but it must be from 0 to 1."
+        - "The expires of the status \"poison\" has an {if flags.cured} block with no {end}."
+        - "The text of the lore entry \"founding\" has an {if flags.old} block with no {end}."
+        - "The quest \"chore\" has a grace period, but no turns, so it can't be late."
+        - "The description of the quest \"chore\" has an {if flags.x} block with no {end}."
+        - "The failed effects of the quest \"chore\" harms an unknown NPC \"guard\"."
+        - "The quest \"errand\" has 0 turns to finish it."
+        - "The failed effects of the quest \"errand\" completes an unknown quest \"chores\"."
+        - "The quest \"escort\" escorts an unknown NPC \"child\"."
+        - "The quest \"escort\" escorts to [8, 8, 0], but no room is there."
+        - "The hostile text of the faction \"thieves\" has an {if flags.caught} block with no {end}."
+        - "The map has a room at [2, 2, 0], but no room is defined."
+        - "The map has a room at [4, 3, 0], but no room is defined."
+        - "The map has a room at [2, 4, 0], but no room is defined."
+        - "The room \"Island\" at [4, 2, 0] can't be reached from the entry."
+        - "The race checkpoint \"Moon\" is at an unknown entry or room \"moon\"."
+        - "The race checkpoint \"Start\" has no room or condition, so it's reached right away."
+        - "The race checkpoint \"Key\" has something missing at the end in its condition \"items.key >\"."
+        - "The recipe for \"pie\" makes an unknown item."
+        - "The recipe for \"pie\" needs 0 of \"apple\", but it must be at least 1."
+        - "The recipe for \"pie\" needs an unknown item \"map\"."
+        "###);
+    }
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Synonyms for verbs and nouns, mapping a canonical word to its alternatives. The
+/// global vocabulary lives in data/vocabulary.yml, and levels can add their own.
+///
+/// verbs:
+///   look: [examine, inspect]
+/// nouns:
+///   dog: [hound, mutt]
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Vocabulary {
+    #[serde(default)]
+    pub verbs: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub nouns: HashMap<String, Vec<String>>,
+}
+
+impl Vocabulary {
+    /// Get the canonical verb for a word, or the word itself if it has no synonyms.
+    pub fn verb<'a>(&'a self, word: &'a str) -> &'a str {
+        canonical(&self.verbs, word)
+    }
+
+    /// Get the canonical noun for a word, or the word itself if it has no synonyms.
+    pub fn noun<'a>(&'a self, word: &'a str) -> &'a str {
+        canonical(&self.nouns, word)
+    }
+
+    /// Check if any of the targets refer to the same thing as a canonical target.
+    pub fn matches<'a>(&self, mut targets: impl Iterator<Item = &'a String>, target: &str) -> bool {
+        targets.any(|t| self.noun(t) == target)
+    }
+
+    /// Add the synonyms from another vocabulary. Synonyms already defined in this
+    /// vocabulary take precedence.
+    pub fn merge(&mut self, other: Vocabulary) {
+        merge_table(&mut self.verbs, other.verbs);
+        merge_table(&mut self.nouns, other.nouns);
+    }
+}
+
+fn canonical<'a>(table: &'a HashMap<String, Vec<String>>, word: &'a str) -> &'a str {
+    for (canonical, synonyms) in table.iter() {
+        if synonyms.iter().any(|synonym| synonym == word) {
+            return canonical;
+        }
+    }
+    word
+}
+
+fn merge_table(table: &mut HashMap<String, Vec<String>>, other: HashMap<String, Vec<String>>) {
+    for (canonical_word, synonyms) in other {
+        for synonym in synonyms {
+            if canonical(table, &synonym) != synonym || table.contains_key(&synonym) {
+                // This word already has a meaning.
+                continue;
+            }
+            table
+                .entry(canonical_word.clone())
+                .or_default()
+                .push(synonym);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_vocabulary() {
+        let mut vocabulary: Vocabulary = serde_yaml::from_str(
+            "
+            verbs:
+              look: [examine]
+            nouns:
+              dog: [hound, mutt]
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(vocabulary.verb("examine"), "look");
+        assert_eq!(vocabulary.verb("look"), "look");
+        assert_eq!(vocabulary.noun("mutt"), "dog");
+        assert_eq!(vocabulary.noun("cat"), "cat");
+
+        vocabulary.merge(
+            serde_yaml::from_str(
+                "
+                verbs:
+                  look: [inspect]
+                  search: [examine]
+                ",
+            )
+            .unwrap(),
+        );
+        assert_eq!(vocabulary.verb("inspect"), "look");
+        // The existing synonym wins.
+        assert_eq!(vocabulary.verb("examine"), "look");
+    }
+}
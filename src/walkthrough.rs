@@ -0,0 +1,371 @@
+use crate::{
+    level::{Action, Coord, Direction, Level, Room, Shift, Terrain, Verb},
+    map::{search, RoomGraph, DIRECTIONS},
+    utils::parse_yml,
+    LEVEL_PATH,
+};
+use std::{fmt, path::PathBuf};
+
+/// Why a walkthrough couldn't be found.
+#[derive(Debug, PartialEq)]
+pub enum Stuck {
+    NoGoal,
+    /// The map has problems, and can't be walked.
+    Map(String),
+    /// The goal can't be reached from the start without dying.
+    Unreachable(Coord),
+    /// The only ways to the goal go past something that depends on what the player
+    /// has done, like an exit with a condition or a tile that needs an item. The search
+    /// only follows the map, so it can't tell whether the level can be finished.
+    Gated(Coord, String),
+}
+
+impl fmt::Display for Stuck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stuck::NoGoal => write!(f, "The level has no par goal to walk through to."),
+            Stuck::Map(message) => write!(f, "{}", message),
+            Stuck::Unreachable(goal) => write!(
+                f,
+                "The par goal at {} can't be reached from the start without dying.",
+                goal
+            ),
+            Stuck::Gated(goal, gate) => write!(
+                f,
+                "The way to the par goal at {} goes past {}, so it can't be walked through \
+                 without playing.",
+                goal, gate
+            ),
+        }
+    }
+}
+
+/// Load a level and find a walkthrough for it. The default level is used when no path
+/// is given.
+pub fn walkthrough_file(level_path: Option<&str>) -> Result<Vec<String>, Stuck> {
+    let level: Level = parse_yml(&PathBuf::from(level_path.unwrap_or(LEVEL_PATH)));
+    walkthrough(&level)
+}
+
+/// Find the shortest list of commands that takes a new game to the level's par goal.
+/// The search walks the map, follows the exits within the level, and uses actions that
+/// teleport the player, but it never enters a deadly room or uses a deadly action. The
+/// commands are in the order they are typed, so they can be replayed in a new game.
+///
+/// The search only knows where the player is, and not what they carry or what they've
+/// done. So it stays clear of anything that depends on that, see `gate`, and when the
+/// goal can only be reached past one of them, it says so rather than guessing.
+pub fn walkthrough(level: &Level) -> Result<Vec<String>, Stuck> {
+    let goal = match level.par {
+        Some(ref par) => par.goal,
+        None => return Err(Stuck::NoGoal),
+    };
+    let room_graph = RoomGraph::parse(level).map_err(Stuck::Map)?;
+
+    // New games of levels with scenarios begin by picking one.
+    let mut commands = Vec::new();
+    let mut start = level.entry;
+    if let Some(scenario) = level.scenarios.first() {
+        commands.push(scenario.id.clone());
+        start = scenario.entry.unwrap_or(level.entry);
+    }
+
+    // Search outwards from the start, following every way out of each room. Each move
+    // is labeled with the command that makes it, and what it depends on, if anything.
+    let moves = |coord: Coord| -> Vec<(Coord, (String, Option<String>))> {
+        let room = match level.get_room(&coord) {
+            Some(room) => room,
+            None => return Vec::new(),
+        };
+        let mut moves: Vec<_> = steps(level, &room_graph, &coord)
+            .into_iter()
+            .map(|step| (step.to, (step.command, step.gate)))
+            .collect();
+        for action in room.actions_iter(level) {
+            let destination = match action.teleport {
+                Some(ref destination) if destination.level.is_none() => destination,
+                _ => continue,
+            };
+            if action.death.is_some() {
+                continue;
+            }
+            let next = level.find_entry(&destination.entry);
+            if let (Some(next), Some(command)) = (next, action_command(level, room, action)) {
+                let gate = action
+                    .when
+                    .as_ref()
+                    .map(|_| format!("the condition on \"{}\" in {}", command, room.title));
+                moves.push((next, (command, gate)));
+            }
+        }
+        moves.retain(|(next, _)| {
+            level
+                .get_room(next)
+                .is_some_and(|room| room.death.is_none())
+        });
+        for (next, (_, gate)) in moves.iter_mut() {
+            if gate.is_none() {
+                *gate = entry_gate(level, &room_graph, next);
+            }
+        }
+        moves
+    };
+
+    let ungated = search(start, Some(goal), |coord| {
+        moves(coord)
+            .into_iter()
+            .filter(|(_, (_, gate))| gate.is_none())
+    });
+    if let Some(path) = ungated.path_to(&goal) {
+        commands.extend(path.into_iter().map(|(command, _)| command));
+        return Ok(commands);
+    }
+    // See if the goal could be reached past the gates, to say which one is in the way.
+    let gated = search(start, Some(goal), moves);
+    match gated.path_to(&goal) {
+        Some(path) => {
+            let gate = path.into_iter().find_map(|(_, gate)| gate);
+            Err(Stuck::Gated(goal, gate.unwrap_or_default()))
+        }
+        None => Err(Stuck::Unreachable(goal)),
+    }
+}
+
+/// A way out of a room on foot, see `steps`.
+struct Step {
+    direction: Direction,
+    to: Coord,
+    /// The command that takes it.
+    command: String,
+    /// It's a secret exit, which has to be found or uncovered first.
+    hidden: bool,
+    /// What taking it depends on, if anything, besides entering the next room.
+    gate: Option<String>,
+}
+
+/// The ways out of a room on foot: along the map, and through the exits within the
+/// level, which take the place of the next room on the map.
+fn steps(level: &Level, room_graph: &RoomGraph, coord: &Coord) -> Vec<Step> {
+    let (room, room_info) = match (level.get_room(coord), room_graph.get(coord)) {
+        (Some(room), Some(room_info)) => (room, room_info),
+        _ => return Vec::new(),
+    };
+
+    let mut steps = Vec::new();
+    for direction in DIRECTIONS.iter() {
+        let next = match room.exits.get(direction) {
+            Some(exit) if exit.level.is_none() => level.find_entry(&exit.entry),
+            Some(_) => None,
+            None => *room_info.in_direction(direction),
+        };
+        let scenery = room
+            .scenery
+            .iter()
+            .find(|scenery| scenery.exits.contains(direction));
+        let mut gate = match room.exits.get(direction) {
+            Some(exit) if exit.when.is_some() => Some(format!(
+                "the condition on the {} exit of {}",
+                direction.lowercase_string(),
+                room.title
+            )),
+            _ => None,
+        };
+        let command = match (room.exits.get(direction), scenery) {
+            // Secret exits have to be found before they can be taken, or uncovered by
+            // moving the scenery in front of them.
+            (Some(exit), Some(scenery)) if exit.hidden => {
+                if scenery.when.is_some() {
+                    gate = Some(format!("moving the {} in {}", scenery.id, room.title));
+                }
+                let verb = scenery.verbs.first().unwrap_or(&Shift::Move);
+                format!(
+                    "{} {} then {}",
+                    verb.lowercase_str(),
+                    scenery.id,
+                    direction.lowercase_string()
+                )
+            }
+            (Some(exit), None) if exit.hidden => match exit.found_by {
+                Some(ref found_by) => {
+                    format!("search {} then {}", found_by, direction.lowercase_string())
+                }
+                None => format!("search then {}", direction.lowercase_string()),
+            },
+            _ => direction.lowercase_string().to_string(),
+        };
+        if let Some(to) = next {
+            steps.push(Step {
+                direction: *direction,
+                to,
+                command,
+                hidden: room.exits.get(direction).is_some_and(|exit| exit.hidden),
+                gate,
+            });
+        }
+    }
+    steps
+}
+
+/// The rooms that can be walked to from a room without depending on anything the
+/// player has done, and the direction of each. Like the walkthrough, this stays clear
+/// of the gates, see `entry_gate`, and of deadly rooms. Secret exits are left out too,
+/// so animals sent off on their own don't find them before the player does.
+pub fn open_steps(level: &Level, room_graph: &RoomGraph, coord: &Coord) -> Vec<(Direction, Coord)> {
+    steps(level, room_graph, coord)
+        .into_iter()
+        .filter(|step| !step.hidden && step.gate.is_none())
+        .filter(|step| {
+            level
+                .get_room(&step.to)
+                .is_some_and(|room| room.death.is_none())
+                && entry_gate(level, room_graph, &step.to).is_none()
+        })
+        .map(|step| (step.direction, step.to))
+        .collect()
+}
+
+/// What entering a room depends on, besides getting next to it: traps that could
+/// hurt the player or drop them elsewhere, tiles with a condition, and water, which
+/// needs a vehicle.
+fn entry_gate(level: &Level, room_graph: &RoomGraph, coord: &Coord) -> Option<String> {
+    let room = level.get_room(coord)?;
+    if !room.hazards.is_empty() {
+        return Some(format!("the hazards in {}", room.title));
+    }
+    let tile = room_graph.tile(coord).and_then(|ch| level.tiles.get(&ch));
+    if tile.is_some_and(|tile| tile.when.is_some()) {
+        return Some(format!("the condition on entering {}", room.title));
+    }
+    if room_graph.terrain(coord) == Terrain::Water {
+        return Some(format!(
+            "the water of {}, which needs a vehicle",
+            room.title
+        ));
+    }
+    None
+}
+
+/// The command that uses an action, if the player can type one that finds it. An
+/// earlier action with the same target would be used instead.
+fn action_command(level: &Level, room: &Room, action: &Action) -> Option<String> {
+    let target = action.targets.first()?;
+    let verb = match action.verb {
+        Verb::Look => "look",
+        Verb::Talk => "talk to",
+        Verb::Help => "help",
+        Verb::Custom => action.alias.as_ref()?,
+        Verb::Emote => return None,
+    };
+    let alias = match action.verb {
+        Verb::Custom => action.alias.as_ref(),
+        _ => None,
+    };
+    let found = room.find_action(action.verb.clone(), target, level, alias, |_| true)?;
+    if !std::ptr::eq(found, action) {
+        return None;
+    }
+    Some(format!("{} {}", verb, target))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_walkthrough() {
+        let level = |par: &str| -> Level {
+            serde_yaml::from_str(&format!(
+                r#"
+maps:
+  - - "------"
+    - "-..-.-"
+    - "-..-.-"
+    - "------"
+entry: [1, 1, 0]
+npcs: {{}}
+regions: {{}}
+{}
+rooms:
+  - {{ title: Gate, coord: [1, 1, 0], description: A gate. }}
+  - {{ title: Pit, coord: [2, 1, 0], description: A pit., death: You fall. }}
+  - title: Road
+    coord: [1, 2, 0]
+    description: A road.
+    actions:
+      - {{ verb: Look, targets: [well], value: It's deep., teleport: {{ entry: bottom }}, death: Splash. }}
+      - {{ verb: Custom, alias: climb, targets: [well], value: Down you go., teleport: {{ entry: bottom }} }}
+  - {{ title: Square, coord: [2, 2, 0], description: A square. }}
+  - {{ title: Well top, coord: [4, 1, 0], description: A well. }}
+  - {{ title: Well bottom, coord: [4, 2, 0], id: bottom, description: A well. }}
+"#,
+                par
+            ))
+            .unwrap()
+        };
+        assert_eq!(
+            walkthrough(&level("par: { turns: 5, goal: [4, 1, 0] }")),
+            Ok(vec![
+                "south".to_string(),
+                "climb well".to_string(),
+                "north".to_string()
+            ])
+        );
+        assert_eq!(
+            walkthrough(&level("par: { turns: 5, goal: [2, 1, 0] }")),
+            Err(Stuck::Unreachable(Coord { x: 2, y: 1, z: 0 }))
+        );
+        assert_eq!(walkthrough(&level("")), Err(Stuck::NoGoal));
+    }
+
+    #[test]
+    fn test_walkthrough_gates() {
+        // The way around the top row, through the yard and the pond, is gated too.
+        let level = |row: &str| -> Level {
+            serde_yaml::from_str(&format!(
+                r#"
+maps:
+  - - "------"
+    - "{}"
+    - "-.~..-"
+    - "------"
+entry: [1, 1, 0]
+npcs: {{}}
+regions: {{}}
+par: {{ turns: 5, goal: [4, 1, 0] }}
+tiles:
+  "^": {{ when: items.lantern > 0 }}
+rooms:
+  - {{ title: Gate, coord: [1, 1, 0], description: A gate. }}
+  - {{ title: Cave, coord: [2, 1, 0], description: A cave. }}
+  - {{ title: Road, coord: [3, 1, 0], description: A road. }}
+  - {{ title: Vault, coord: [4, 1, 0], description: A vault. }}
+  - title: Yard
+    coord: [1, 2, 0]
+    description: A yard.
+    hazards: [{{ id: pit, message: You fall. }}]
+  - {{ title: Pond, coord: [2, 2, 0], description: A pond. }}
+  - {{ title: Hall, coord: [3, 2, 0], description: A hall. }}
+  - {{ title: Vault door, coord: [4, 2, 0], description: A door. }}
+"#,
+                row
+            ))
+            .unwrap()
+        };
+        let goal = Coord { x: 4, y: 1, z: 0 };
+        assert_eq!(
+            walkthrough(&level("-....-")),
+            Ok(vec!["east".to_string(), "east".into(), "east".into()])
+        );
+        assert_eq!(
+            walkthrough(&level("-.^..-")),
+            Err(Stuck::Gated(goal, "the condition on entering Cave".into()))
+        );
+        assert_eq!(
+            walkthrough(&level("-.~..-")),
+            Err(Stuck::Gated(
+                goal,
+                "the water of Cave, which needs a vehicle".into()
+            ))
+        );
+    }
+}
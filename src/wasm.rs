@@ -0,0 +1,131 @@
+use crate::{campaign::Today, Environment, Game, GameLoopResponse};
+use std::{io::Write, path::PathBuf};
+use wasm_bindgen::prelude::*;
+
+/// Collects the game's output so that it can be handed back to JavaScript. There is
+/// no filesystem in the browser, so the data files are built in.
+struct Browser {
+    output: Vec<u8>,
+}
+
+impl Environment for Browser {
+    fn get_prompt(&mut self) -> String {
+        // The browser can't block waiting for input, so questions asked in the middle
+        // of a command, like confirming a restart, are answered with yes.
+        "yes".into()
+    }
+
+    fn save_path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    fn feedback_path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    fn achievements_path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    fn results_path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    fn races_path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    fn new_game_plus_path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    fn today(&self) -> Option<Today> {
+        // The standard library has no clock in the browser.
+        None
+    }
+
+    fn read_text(&self, path: &str) -> String {
+        match self.try_read_text(path) {
+            Some(text) => text,
+            None => panic!("The file {:?} isn't available in the browser.", path),
+        }
+    }
+
+    fn try_read_text(&self, path: &str) -> Option<String> {
+        let text = match path {
+            "data/intro.txt" => include_str!("../data/intro.txt"),
+            "data/help.txt" => include_str!("../data/help.txt"),
+            "data/vocabulary.yml" => include_str!("../data/vocabulary.yml"),
+            "data/lang/fr.yml" => include_str!("../data/lang/fr.yml"),
+            _ => return None,
+        };
+        Some(text.into())
+    }
+
+    fn list_files(&self, dir: &str) -> Vec<String> {
+        match dir {
+            "data/lang" => vec!["fr.yml".into()],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Write for Browser {
+    fn write(&mut self, buffer: &[u8]) -> Result<usize, std::io::Error> {
+        self.output.extend_from_slice(buffer);
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+}
+
+fn start(level_yaml: &str, items_yaml: &str) -> Result<Game<Browser>, String> {
+    let mut game = Game::new_from_strings(level_yaml, items_yaml, Browser { output: Vec::new() })?;
+    game.start();
+    Ok(game)
+}
+
+/// The game, as seen from JavaScript. Read the introduction with `output`, then pass
+/// each line the player types to `step`, and print what it returns.
+#[wasm_bindgen]
+pub struct WebGame {
+    level_yaml: String,
+    items_yaml: String,
+    game: Game<Browser>,
+}
+
+#[wasm_bindgen]
+impl WebGame {
+    #[wasm_bindgen(constructor)]
+    pub fn new(level_yaml: &str, items_yaml: &str) -> Result<WebGame, JsValue> {
+        Ok(WebGame {
+            level_yaml: level_yaml.into(),
+            items_yaml: items_yaml.into(),
+            game: start(level_yaml, items_yaml)?,
+        })
+    }
+
+    /// Take all of the output that hasn't been read yet.
+    pub fn output(&mut self) -> String {
+        let output = std::mem::take(&mut self.game.output().output);
+        String::from_utf8_lossy(&output).into_owned()
+    }
+
+    /// Run a line of input, and return the output it produced.
+    pub fn step(&mut self, input: &str) -> String {
+        match self.game.step(input.to_lowercase()) {
+            // There are no saves in the browser, so dying starts over too.
+            Some(GameLoopResponse::Restart) | Some(GameLoopResponse::Death) => {
+                self.game = start(&self.level_yaml, &self.items_yaml)
+                    .expect("The level was already loaded once.");
+            }
+            Some(GameLoopResponse::Quit) => {
+                writeln!(self.game.output(), "Thanks for playing!").unwrap();
+            }
+            None => {}
+        }
+        self.output()
+    }
+}
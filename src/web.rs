@@ -0,0 +1,352 @@
+use crate::{Environment, Game, GameLoopResponse, ItemDatabase};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, State,
+    },
+    http::StatusCode,
+    response::Response,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::PathBuf,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use tokio::sync::{broadcast, oneshot};
+
+/// Collects a session's output, so that it can be sent back to the client.
+#[derive(Default)]
+struct WebOutput {
+    output: Vec<u8>,
+}
+
+impl Environment for WebOutput {
+    fn get_prompt(&mut self) -> String {
+        // Requests can't block waiting for more input, so questions asked in the
+        // middle of a command, like confirming a restart, are answered with yes.
+        "yes".into()
+    }
+
+    fn save_path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    // Anyone can start a session, so they can't write to the host's files.
+    fn feedback_path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    // Sessions aren't tied to a player, so their achievements aren't kept either.
+    fn achievements_path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    fn results_path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    fn races_path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    fn new_game_plus_path(&self) -> Option<PathBuf> {
+        None
+    }
+}
+
+impl Write for WebOutput {
+    fn write(&mut self, buffer: &[u8]) -> Result<usize, std::io::Error> {
+        self.output.extend_from_slice(buffer);
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+}
+
+/// How many games can be running at once. Each one has its own thread.
+const MAX_SESSIONS: usize = 100;
+
+/// How long a session can go without a command before it is ended.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// How often to look for idle sessions.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A line of input for a session, and where to send the output it produced.
+type Command = (String, oneshot::Sender<String>);
+
+/// The handles for talking to a running game. The game itself lives on its own
+/// thread, as it can't be shared between threads. Once every handle is dropped, the
+/// game's thread stops.
+#[derive(Clone)]
+struct Session {
+    commands: mpsc::Sender<Command>,
+    output: broadcast::Sender<String>,
+    last_used: Arc<Mutex<Instant>>,
+}
+
+type Sessions = Arc<Mutex<HashMap<String, Session>>>;
+
+#[derive(Clone)]
+struct AppState {
+    item_db: Arc<ItemDatabase>,
+    sessions: Sessions,
+}
+
+#[derive(Serialize)]
+struct SessionResponse {
+    id: String,
+    output: String,
+}
+
+#[derive(Deserialize)]
+struct CommandRequest {
+    input: String,
+}
+
+#[derive(Serialize)]
+struct CommandResponse {
+    output: String,
+}
+
+fn take_output(game: &Game<WebOutput>) -> String {
+    let output = std::mem::take(&mut game.output().output);
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+fn start(item_db: &ItemDatabase) -> Result<Game<WebOutput>, String> {
+    let mut game = Game::new(item_db.clone(), WebOutput::default())?;
+    game.start();
+    Ok(game)
+}
+
+/// Run a game until the player quits or every handle to the session is dropped.
+/// The output of each command is sent to whoever ran it, and to every stream. The
+/// introduction is sent back once the game has started, or why it couldn't be.
+fn run_session(
+    item_db: ItemDatabase,
+    commands: mpsc::Receiver<Command>,
+    output: broadcast::Sender<String>,
+    intro: oneshot::Sender<Result<String, String>>,
+) {
+    let mut game = match start(&item_db) {
+        Ok(game) => game,
+        Err(message) => {
+            let _ = intro.send(Err(message));
+            return;
+        }
+    };
+    let _ = intro.send(Ok(take_output(&game)));
+
+    for (input, reply) in commands {
+        let response = game.step(input.to_lowercase());
+        match response {
+            // Sessions aren't saved, so dying starts over too.
+            Some(GameLoopResponse::Restart) | Some(GameLoopResponse::Death) => {
+                match start(&item_db) {
+                    Ok(new_game) => game = new_game,
+                    // The session ends when a command's reply is dropped.
+                    Err(message) => {
+                        eprintln!("Unable to restart a session: {}", message);
+                        return;
+                    }
+                }
+            }
+            Some(GameLoopResponse::Quit) => {
+                writeln!(game.output(), "Thanks for playing!").unwrap();
+            }
+            None => {}
+        }
+        let text = take_output(&game);
+        // Nobody may be listening, which is fine.
+        let _ = output.send(text.clone());
+        let _ = reply.send(text);
+        if let Some(GameLoopResponse::Quit) = response {
+            return;
+        }
+    }
+}
+
+impl AppState {
+    /// Get a session that is about to be used, which keeps it from going idle.
+    fn session(&self, id: &str) -> Result<Session, StatusCode> {
+        let session = self
+            .sessions
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or(StatusCode::NOT_FOUND)?;
+        *session.last_used.lock().unwrap() = Instant::now();
+        Ok(session)
+    }
+
+    /// Forget the sessions that haven't been used in a while, which ends their games.
+    fn sweep(&self) {
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|_, session| session.last_used.lock().unwrap().elapsed() < IDLE_TIMEOUT);
+    }
+
+    /// Run a command in a session. Once the game has ended the session is forgotten.
+    async fn run(&self, id: &str, session: &Session, input: String) -> Result<String, StatusCode> {
+        let (reply, output) = oneshot::channel();
+        let result = match session.commands.send((input, reply)) {
+            Ok(()) => output.await.map_err(|_| StatusCode::GONE),
+            Err(_) => Err(StatusCode::GONE),
+        };
+        if result.is_err() {
+            self.sessions.lock().unwrap().remove(id);
+        }
+        result
+    }
+}
+
+/// POST /session
+async fn create_session(
+    State(state): State<AppState>,
+) -> Result<Json<SessionResponse>, StatusCode> {
+    let (commands, receiver) = mpsc::channel();
+    let (output, _) = broadcast::channel(64);
+    let (intro, intro_output) = oneshot::channel();
+    let id = format!("{:016x}", rand::random::<u64>());
+    {
+        let mut sessions = state.sessions.lock().unwrap();
+        if sessions.len() >= MAX_SESSIONS {
+            return Err(StatusCode::SERVICE_UNAVAILABLE);
+        }
+        let session = Session {
+            commands,
+            output: output.clone(),
+            last_used: Arc::new(Mutex::new(Instant::now())),
+        };
+        sessions.insert(id.clone(), session);
+    }
+
+    let item_db = ItemDatabase::clone(&state.item_db);
+    let sessions = state.sessions.clone();
+    let session_id = id.clone();
+    thread::spawn(move || {
+        run_session(item_db, receiver, output, intro);
+        // The game has ended, so there is nothing left to send commands to.
+        sessions.lock().unwrap().remove(&session_id);
+    });
+
+    match intro_output.await {
+        Ok(Ok(output)) => Ok(Json(SessionResponse { id, output })),
+        Ok(Err(message)) => {
+            eprintln!("Unable to start a session: {}", message);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+/// POST /session/{id}/command
+async fn run_command(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<CommandRequest>,
+) -> Result<Json<CommandResponse>, StatusCode> {
+    let session = state.session(&id)?;
+    let output = state.run(&id, &session, request.input).await?;
+    Ok(Json(CommandResponse { output }))
+}
+
+/// GET /session/{id}/stream
+async fn stream(
+    websocket: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Response, StatusCode> {
+    let output = state.session(&id)?.output.subscribe();
+    Ok(websocket.on_upgrade(move |socket| stream_session(socket, state, id, output)))
+}
+
+/// Send all of a session's output down the socket. Text sent up the socket is run
+/// as a command, so a client can play entirely over the stream. The stream doesn't
+/// hold on to the session, so it ends once the session is ended.
+async fn stream_session(
+    mut socket: WebSocket,
+    state: AppState,
+    id: String,
+    mut output: broadcast::Receiver<String>,
+) {
+    loop {
+        tokio::select! {
+            text = output.recv() => match text {
+                Ok(text) => {
+                    if socket.send(Message::Text(text.into())).await.is_err() {
+                        return;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(input))) => {
+                    // The output arrives through the broadcast, like everyone else's.
+                    let result = match state.session(&id) {
+                        Ok(session) => state.run(&id, &session, input.to_string()).await,
+                        Err(status) => Err(status),
+                    };
+                    if result.is_err() {
+                        return;
+                    }
+                }
+                Some(Ok(_)) => {}
+                Some(Err(_)) | None => return,
+            },
+        }
+    }
+}
+
+fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/session", post(create_session))
+        .route("/session/{id}/command", post(run_command))
+        .route("/session/{id}/stream", get(stream))
+        .with_state(state)
+}
+
+/// Serve games over HTTP. Each session runs its own game, which isn't saved. Only so
+/// many sessions can run at once, and idle ones are ended.
+pub fn serve(port: u16) {
+    let runtime = tokio::runtime::Runtime::new().expect("Unable to start the web server.");
+    runtime.block_on(async {
+        let listener = match tokio::net::TcpListener::bind(("0.0.0.0", port)).await {
+            Ok(listener) => listener,
+            Err(err) => {
+                eprintln!("Unable to listen on port {}: {}", port, err);
+                std::process::exit(1);
+            }
+        };
+        println!("Serving games at http://localhost:{}/session", port);
+        let state = AppState {
+            item_db: Arc::new(ItemDatabase::new()),
+            sessions: Default::default(),
+        };
+        {
+            let state = state.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    state.sweep();
+                }
+            });
+        }
+        if let Err(err) = axum::serve(listener, router(state)).await {
+            eprintln!("The web server stopped: {}", err);
+        }
+    });
+}
@@ -0,0 +1,706 @@
+use serde_yaml::{Mapping, Value};
+use std::ops::Range;
+
+/// How long a line can get before a collection is written in block style.
+const WIDTH: usize = 90;
+
+/// The part of a document that a node was written as, by lines. Nodes in a sequence can
+/// start partway through their first line, after the `- ` of the item.
+enum Node {
+    Mapping(Vec<Entry>),
+    Sequence(Vec<Entry>),
+    /// A scalar, a flow collection, or a block scalar, which is kept or written again
+    /// as a whole.
+    Leaf(Leaf),
+}
+
+/// How a scalar or a collection was written on its line, to write its new value the
+/// same way.
+#[derive(Clone, Copy, PartialEq)]
+enum Style {
+    Plain,
+    Quoted,
+    Flow,
+    Block,
+}
+
+struct Leaf {
+    style: Style,
+    /// The comment after a value on one line, with the space before it.
+    comment: Option<String>,
+}
+
+/// A key and its value in a mapping, or an item in a sequence.
+struct Entry {
+    /// The key as it's read, or None for the items of a sequence.
+    key: Option<Value>,
+    /// The key as it was written, with its quotes.
+    key_text: String,
+    /// The comments and blank lines before the entry.
+    comments: Range<usize>,
+    lines: Range<usize>,
+    /// The column of the key, or of the item's `-`.
+    col: usize,
+    /// The column that the value starts in when it's on the first line, like a mapping
+    /// that starts after the `- ` of an item.
+    inline: Option<usize>,
+    value: Node,
+}
+
+/// Write a YML document again after it's been edited, keeping the text of everything
+/// that wasn't edited as it was, with its comments, the order of its keys, and its
+/// formatting. Edited values are written in the style they were, and new ones in the
+/// style of the levels: short lists on one line, and descriptions as blocks.
+pub fn rewrite(original: &str, edited: &Value) -> Result<String, String> {
+    let old: Value = serde_yaml::from_str(original)
+        .map_err(|err| format!("Unable to parse the original: {}", err))?;
+    let lines: Vec<&str> = original.lines().collect();
+    let start = (0..lines.len())
+        .find(|&index| is_content(lines[index]) && !lines[index].starts_with("---"))
+        .unwrap_or(lines.len());
+    let mut out = String::new();
+    for line in &lines[..start] {
+        out.push_str(line);
+        out.push('\n');
+    }
+    if start == lines.len() {
+        out.push_str(&block(edited, 0, ""));
+        return Ok(out);
+    }
+    let node = parse_node(&lines, start, indent(lines[start]), lines.len());
+    let writer = Writer { lines: &lines };
+    if !writer.write_node(&node, &old, edited, None, &mut out) {
+        out.truncate(lines[..start].iter().map(|line| line.len() + 1).sum());
+        out.push_str(&block(edited, 0, ""));
+    }
+    Ok(out)
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Whether a line has something other than a comment on it.
+fn is_content(line: &str) -> bool {
+    let text = line.trim();
+    !text.is_empty() && !text.starts_with('#')
+}
+
+fn is_dash(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Where the colon of a mapping's key is, if the text starts with one.
+fn key_end(text: &str) -> Option<usize> {
+    let after_quote = match text.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let mut escaped = false;
+            let close = text[1..].char_indices().find(|&(_, ch)| {
+                let close = ch == quote && !escaped;
+                escaped = quote == '"' && ch == '\\' && !escaped;
+                close
+            })?;
+            1 + close.0 + 1
+        }
+        '[' | '{' | '|' | '>' | '!' | '&' | '*' | '#' | '%' | '@' | '`' => return None,
+        _ if is_dash(text) => return None,
+        _ => 0,
+    };
+    let bytes = text.as_bytes();
+    for index in after_quote..bytes.len() {
+        let next_is_space = bytes.get(index + 1).is_none_or(|next| *next == b' ');
+        match bytes[index] {
+            b'#' if index > 0 && bytes[index - 1] == b' ' => return None,
+            b':' if next_is_space => return Some(index),
+            _ if after_quote > 0 => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split the comment off the end of a line's value, keeping the space before it.
+fn split_comment(text: &str) -> (&str, Option<&str>) {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (index, ch) in text.char_indices() {
+        match quote {
+            Some(open) if ch == open => quote = None,
+            Some(_) => {}
+            None if (ch == '"' || ch == '\'') && " [{,:".contains(previous) => quote = Some(ch),
+            None if ch == '#' && previous == ' ' => {
+                let value = text[..index].trim_end();
+                return (value, Some(&text[value.len()..]));
+            }
+            None => {}
+        }
+        previous = ch;
+    }
+    (text.trim_end(), None)
+}
+
+fn leaf(text: &str) -> Leaf {
+    let (value, comment) = split_comment(text);
+    let style = match value.chars().next() {
+        Some('{' | '[') => Style::Flow,
+        Some('"' | '\'') => Style::Quoted,
+        Some('|' | '>') => Style::Block,
+        _ => Style::Plain,
+    };
+    Leaf {
+        style,
+        comment: comment.map(String::from),
+    }
+}
+
+/// Read the node that starts in a column of a line, and runs until the end line.
+fn parse_node(lines: &[&str], first: usize, col: usize, end: usize) -> Node {
+    let text = &lines[first][col..];
+    if is_dash(text) {
+        Node::Sequence(parse_entries(lines, first, col, end, false))
+    } else if key_end(text).is_some() {
+        Node::Mapping(parse_entries(lines, first, col, end, true))
+    } else {
+        Node::Leaf(leaf(text))
+    }
+}
+
+/// Read the entries of a mapping or a sequence in a column. The comments between two
+/// entries go with the second one.
+fn parse_entries(lines: &[&str], first: usize, col: usize, end: usize, keyed: bool) -> Vec<Entry> {
+    let starts: Vec<usize> = (first..end)
+        .filter(|&index| {
+            index == first
+                || (is_content(lines[index])
+                    && indent(lines[index]) == col
+                    && is_dash(&lines[index][col..]) != keyed)
+        })
+        .collect();
+    let mut entries = Vec::new();
+    let mut comments_start = first;
+    for (number, &start) in starts.iter().enumerate() {
+        let next = starts.get(number + 1).copied().unwrap_or(end);
+        // Leave the comments before the next entry to it.
+        let lines_end = match starts.get(number + 1) {
+            Some(_) => (start + 1..next)
+                .rev()
+                .find(|&index| is_content(lines[index]))
+                .map_or(start + 1, |index| index + 1),
+            None => end,
+        };
+        let text = &lines[start][col..];
+        let (key, key_text, rest_col) = match keyed {
+            true => {
+                let colon = key_end(text).unwrap_or(text.len().saturating_sub(1));
+                let key_text = text[..colon].to_string();
+                let key = serde_yaml::from_str(&key_text).unwrap_or(Value::Null);
+                (Some(key), key_text, col + colon + 1)
+            }
+            false => (None, String::new(), col + 1),
+        };
+        let rest = &lines[start][rest_col.min(lines[start].len())..];
+        let value_col = rest_col + indent(rest);
+        let (value, inline) = match split_comment(rest.trim_start()) {
+            ("", _) => {
+                let child = (start + 1..lines_end).find(|&index| is_content(lines[index]));
+                let value = match child {
+                    Some(child) => parse_node(lines, child, indent(lines[child]), lines_end),
+                    None => Node::Leaf(leaf(rest)),
+                };
+                (value, None)
+            }
+            (value, _) if !keyed && (is_dash(value) || key_end(value).is_some()) => (
+                parse_node(lines, start, value_col, lines_end),
+                Some(value_col),
+            ),
+            _ => (Node::Leaf(leaf(rest.trim_start())), Some(value_col)),
+        };
+        entries.push(Entry {
+            key,
+            key_text,
+            comments: comments_start..start,
+            lines: start..lines_end,
+            col,
+            inline,
+            value,
+        });
+        comments_start = lines_end;
+    }
+    entries
+}
+
+struct Writer<'a> {
+    lines: &'a [&'a str],
+}
+
+impl Writer<'_> {
+    /// Write a mapping or sequence node with its new value, returning false if the
+    /// node doesn't match its old value, so it can be written from scratch.
+    fn write_node(
+        &self,
+        node: &Node,
+        old: &Value,
+        new: &Value,
+        prefix: Option<&str>,
+        out: &mut String,
+    ) -> bool {
+        match (node, old, new) {
+            (Node::Mapping(entries), Value::Mapping(old), Value::Mapping(new)) => {
+                self.write_mapping(entries, old, new, prefix, out)
+            }
+            (Node::Sequence(entries), Value::Sequence(old), Value::Sequence(new)) => {
+                self.write_sequence(entries, old, new, prefix, out)
+            }
+            _ => false,
+        }
+    }
+
+    fn write_mapping(
+        &self,
+        entries: &[Entry],
+        old: &Mapping,
+        new: &Mapping,
+        prefix: Option<&str>,
+        out: &mut String,
+    ) -> bool {
+        let keys_match = entries.len() == old.len()
+            && entries
+                .iter()
+                .all(|entry| entry.key.as_ref().is_some_and(|key| old.contains_key(key)));
+        if !keys_match || new.is_empty() {
+            return false;
+        }
+        let col = entries[0].col;
+        // The first entry carries the `- ` of the item the mapping is in, if it is.
+        let first_prefix = prefix
+            .map(String::from)
+            .unwrap_or_else(|| self.lines[entries[0].lines.start][..col].to_string());
+        let mut prefix = Some(first_prefix);
+        for entry in entries {
+            let key = entry.key.as_ref().unwrap();
+            if let Some(value) = new.get(key) {
+                let line_prefix = prefix.take().unwrap_or_else(|| " ".repeat(col));
+                self.write_entry(entry, old.get(key), value, &line_prefix, out);
+            }
+        }
+        for (key, value) in new.iter() {
+            if !old.contains_key(key) {
+                let line_prefix = prefix.take().unwrap_or_else(|| " ".repeat(col));
+                out.push_str(&fresh_entry(
+                    &line_prefix,
+                    &scalar_key(key),
+                    value,
+                    col,
+                    None,
+                ));
+            }
+        }
+        true
+    }
+
+    fn write_sequence(
+        &self,
+        entries: &[Entry],
+        old: &[Value],
+        new: &[Value],
+        prefix: Option<&str>,
+        out: &mut String,
+    ) -> bool {
+        if entries.len() != old.len() || new.is_empty() {
+            return false;
+        }
+        let col = entries[0].col;
+        let first_prefix = prefix
+            .map(String::from)
+            .unwrap_or_else(|| self.lines[entries[0].lines.start][..col].to_string());
+        let mut prefix = Some(first_prefix);
+        let mut next_prefix = || prefix.take().unwrap_or_else(|| " ".repeat(col));
+        // Line the items up, so that adding or taking one away leaves the rest as they
+        // were.
+        let (mut i, mut j) = (0, 0);
+        while i < old.len() || j < new.len() {
+            let (old_left, new_left) = (i < old.len(), j < new.len());
+            if old_left && new_left && old[i] == new[j] {
+                self.write_entry(&entries[i], Some(&old[i]), &new[j], &next_prefix(), out);
+                i += 1;
+                j += 1;
+            } else if old_left
+                && !new[j..].contains(&old[i])
+                && (!new_left || old[i + 1..].contains(&new[j]))
+            {
+                i += 1;
+            } else if new_left
+                && !old[i..].contains(&new[j])
+                && (!old_left || new[j + 1..].contains(&old[i]))
+            {
+                let style = entries[i.min(entries.len() - 1)].style();
+                let item_prefix = format!("{}- ", next_prefix());
+                out.push_str(&fresh_item(&item_prefix, &new[j], col + 2, style));
+                j += 1;
+            } else {
+                self.write_entry(&entries[i], Some(&old[i]), &new[j], &next_prefix(), out);
+                i += 1;
+                j += 1;
+            }
+        }
+        true
+    }
+
+    /// Write an entry with its new value, starting its first line with a prefix as
+    /// wide as its column.
+    fn write_entry(
+        &self,
+        entry: &Entry,
+        old: Option<&Value>,
+        new: &Value,
+        prefix: &str,
+        out: &mut String,
+    ) {
+        for line in &self.lines[entry.comments.clone()] {
+            out.push_str(line);
+            out.push('\n');
+        }
+        let first = self.lines[entry.lines.start];
+        let rest_lines = &self.lines[entry.lines.start + 1..entry.lines.end];
+        if old == Some(new) {
+            out.push_str(prefix);
+            out.push_str(&first[entry.col..]);
+            out.push('\n');
+            for line in rest_lines {
+                out.push_str(line);
+                out.push('\n');
+            }
+            return;
+        }
+        if let (Some(old), Node::Mapping(_) | Node::Sequence(_)) = (old, &entry.value) {
+            let mut written = String::new();
+            let fits = match entry.inline {
+                // The value starts on this line, after the `- `.
+                Some(value_col) => {
+                    let child_prefix = format!("{}{}", prefix, &first[entry.col..value_col]);
+                    self.write_node(&entry.value, old, new, Some(&child_prefix), &mut written)
+                }
+                None => {
+                    let child = (entry.lines.start + 1..entry.lines.end)
+                        .find(|&index| is_content(self.lines[index]))
+                        .unwrap_or(entry.lines.end);
+                    written.push_str(prefix);
+                    written.push_str(&first[entry.col..]);
+                    written.push('\n');
+                    for line in &self.lines[entry.lines.start + 1..child] {
+                        written.push_str(line);
+                        written.push('\n');
+                    }
+                    self.write_node(&entry.value, old, new, None, &mut written)
+                }
+            };
+            if fits {
+                out.push_str(&written);
+                return;
+            }
+        }
+        let style = entry.style();
+        let mut text = match entry.key {
+            Some(_) => fresh_entry(prefix, &entry.key_text, new, entry.col, style),
+            None => fresh_item(&format!("{}- ", prefix), new, entry.col + 2, style),
+        };
+        // Keep the comment at the end of a value on one line.
+        if let Node::Leaf(Leaf {
+            comment: Some(ref comment),
+            ..
+        }) = entry.value
+        {
+            if text.matches('\n').count() == 1 {
+                text.pop();
+                text.push_str(comment);
+                text.push('\n');
+            }
+        }
+        out.push_str(&text);
+    }
+}
+
+impl Entry {
+    fn style(&self) -> Option<Style> {
+        match self.value {
+            Node::Leaf(ref leaf) => Some(leaf.style),
+            _ => None,
+        }
+    }
+}
+
+/// A scalar as it's written on one line, or None for text that needs a block.
+fn scalar(value: &Value, in_flow: bool, style: Option<Style>) -> Option<String> {
+    match value {
+        Value::Null => Some("~".into()),
+        Value::Bool(value) => Some(value.to_string()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::String(text) if text.contains('\n') => None,
+        Value::String(text) if style != Some(Style::Quoted) && is_plain(text, in_flow) => {
+            Some(text.clone())
+        }
+        Value::String(text) => Some(quote(text)),
+        Value::Sequence(_) | Value::Mapping(_) => None,
+    }
+}
+
+fn scalar_key(key: &Value) -> String {
+    scalar(key, false, None).unwrap_or_else(|| quote(&format!("{:?}", key)))
+}
+
+/// Whether text can be written without quotes, and read back as the same text.
+fn is_plain(text: &str, in_flow: bool) -> bool {
+    let indicator = match text.chars().next() {
+        Some('-' | '?' | ':') => text[1..].starts_with(' ') || text.len() == 1,
+        Some(first) => ",[]{}#&*!|>'\"%@`".contains(first),
+        None => true,
+    };
+    let breaks = indicator
+        || text.trim() != text
+        || text.contains(": ")
+        || text.contains(" #")
+        || text.ends_with(':')
+        || (in_flow && text.contains([',', '[', ']', '{', '}']));
+    !breaks && serde_yaml::from_str::<Value>(text).ok() == Some(Value::String(text.into()))
+}
+
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for ch in text.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            ch if ch.is_control() => quoted.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A collection on one line, like `[1, 2, 0]` or `{ id: apple, quantity: 2 }`.
+fn flow(value: &Value) -> Option<String> {
+    match value {
+        Value::Sequence(items) if items.is_empty() => Some("[]".into()),
+        Value::Sequence(items) => {
+            let items: Option<Vec<String>> = items.iter().map(flow).collect();
+            Some(format!("[{}]", items?.join(", ")))
+        }
+        Value::Mapping(mapping) if mapping.is_empty() => Some("{}".into()),
+        Value::Mapping(mapping) => {
+            let entries: Option<Vec<String>> = mapping
+                .iter()
+                .map(|(key, value)| Some(format!("{}: {}", scalar(key, true, None)?, flow(value)?)))
+                .collect();
+            Some(format!("{{ {} }}", entries?.join(", ")))
+        }
+        scalar_value => scalar(scalar_value, true, None),
+    }
+}
+
+/// Whether a collection reads best on one line, like a coord or a list of names.
+fn is_short(value: &Value) -> bool {
+    match value {
+        Value::Sequence(items) => items
+            .iter()
+            .all(|item| !matches!(item, Value::Sequence(_) | Value::Mapping(_))),
+        Value::Mapping(mapping) => mapping.is_empty(),
+        _ => true,
+    }
+}
+
+/// Text with more than one line, as a literal block indented to a column.
+fn literal(text: &str, col: usize) -> Option<String> {
+    let chomp = match text.strip_suffix('\n') {
+        Some(rest) if rest.ends_with('\n') => return None,
+        Some(_) => "",
+        None => "-",
+    };
+    if text.starts_with(' ') {
+        return None;
+    }
+    let mut block = format!("|{}\n", chomp);
+    for line in text.trim_end_matches('\n').split('\n') {
+        if !line.is_empty() {
+            block.push_str(&" ".repeat(col));
+            block.push_str(line);
+        }
+        block.push('\n');
+    }
+    Some(block)
+}
+
+/// A key and its value written from scratch, with the key in a column.
+fn fresh_entry(prefix: &str, key: &str, value: &Value, col: usize, style: Option<Style>) -> String {
+    let head = format!("{}{}:", prefix, key);
+    match value {
+        Value::Sequence(_) | Value::Mapping(_) => {
+            let flow = flow(value).filter(|flow| {
+                let prefers_flow = style == Some(Style::Flow) || is_short(value);
+                let empty = flow == "[]" || flow == "{}";
+                empty || (prefers_flow && head.len() + 1 + flow.len() <= WIDTH)
+            });
+            match flow {
+                Some(flow) => format!("{} {}\n", head, flow),
+                None => format!("{}\n{}", head, block(value, col + 2, &" ".repeat(col + 2))),
+            }
+        }
+        _ => match scalar(value, false, style) {
+            Some(text) => format!("{} {}\n", head, text),
+            None => match value.as_str().and_then(|text| literal(text, col + 2)) {
+                Some(block) => format!("{} {}", head, block),
+                None => format!("{} {}\n", head, quote(value.as_str().unwrap_or(""))),
+            },
+        },
+    }
+}
+
+/// An item of a sequence written from scratch, after a prefix that ends in `- `, with
+/// its value in a column.
+fn fresh_item(prefix: &str, value: &Value, col: usize, style: Option<Style>) -> String {
+    match value {
+        Value::Sequence(_) | Value::Mapping(_) => {
+            let flow = flow(value).filter(|flow| {
+                let prefers_flow = style == Some(Style::Flow) || is_short(value);
+                let empty = flow == "[]" || flow == "{}";
+                empty || (prefers_flow && prefix.len() + flow.len() <= WIDTH)
+            });
+            match flow {
+                Some(flow) => format!("{}{}\n", prefix, flow),
+                None => block(value, col, prefix),
+            }
+        }
+        _ => match scalar(value, false, style) {
+            Some(text) => format!("{}{}\n", prefix, text),
+            None => match value.as_str().and_then(|text| literal(text, col)) {
+                Some(block) => format!("{}{}", prefix, block),
+                None => format!("{}{}\n", prefix, quote(value.as_str().unwrap_or(""))),
+            },
+        },
+    }
+}
+
+/// A collection written from scratch in block style, in a column, with its first line
+/// starting with a prefix as wide as the column.
+fn block(value: &Value, col: usize, first_prefix: &str) -> String {
+    let mut text = String::new();
+    let prefix = |index: usize| match index {
+        0 => first_prefix.to_string(),
+        _ => " ".repeat(col),
+    };
+    match value {
+        Value::Mapping(mapping) => {
+            for (index, (key, value)) in mapping.iter().enumerate() {
+                text.push_str(&fresh_entry(
+                    &prefix(index),
+                    &scalar_key(key),
+                    value,
+                    col,
+                    None,
+                ));
+            }
+        }
+        Value::Sequence(items) => {
+            for (index, item) in items.iter().enumerate() {
+                let item_prefix = format!("{}- ", prefix(index));
+                text.push_str(&fresh_item(&item_prefix, item, col + 2, None));
+            }
+        }
+        value => text.push_str(&fresh_item(first_prefix, value, col, None)),
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LEVEL: &str = "\
+# The docks, at the edge of town.
+maps:
+  -
+    # 0123
+    - ----  0
+    - -..- 1 # [1, 1, 0] Pier
+    - ----  2
+entry: [1, 1, 0]
+npcs:
+  fisher: { name: Fisher, targets: [fisher], talk: Fish?, items: [] }
+
+rooms:
+  # Where the boats come in.
+  - title: Pier
+    coord: [1, 1, 0]
+    description: |
+      A pier.
+
+      Gulls wheel overhead.
+    items: [{ id: apple, quantity: 1 }]
+  - { title: Shed, coord: [2, 1, 0], description: A shed. }
+  # Soon to be taken away.
+  - { title: Hut, coord: [9, 9, 0], description: A hut. }
+";
+
+    #[test]
+    fn test_rewrite() {
+        let mut level: Value = serde_yaml::from_str(LEVEL).unwrap();
+        assert_eq!(rewrite(LEVEL, &level).unwrap(), LEVEL);
+
+        let rooms = level["rooms"].as_sequence_mut().unwrap();
+        rooms.remove(1);
+        rooms[0]["npcs"] = serde_yaml::from_str("[fisher]").unwrap();
+        rooms[0]["description"] = "A long pier.\n\nGulls wheel overhead.\n".into();
+        rooms.push(
+            serde_yaml::from_str(
+                "{ title: Boat, coord: [3, 1, 0], description: \"A boat: small.\" }",
+            )
+            .unwrap(),
+        );
+        level["maps"][0][1] = "-... 1".into();
+        level["entry"] = serde_yaml::from_str("[2, 1, 0]").unwrap();
+        level["regions"] = serde_yaml::from_str("{ docks: { name: Docks } }").unwrap();
+        let rewritten = rewrite(LEVEL, &level).unwrap();
+        insta::assert_snapshot!(rewritten, @r###"
+        ---
+        # The docks, at the edge of town.
+        maps:
+          -
+            # 0123
+            - ----  0
+            - -... 1 # [1, 1, 0] Pier
+            - ----  2
+        entry: [2, 1, 0]
+        npcs:
+          fisher: { name: Fisher, targets: [fisher], talk: Fish?, items: [] }
+
+        rooms:
+          # Where the boats come in.
+          - title: Pier
+            coord: [1, 1, 0]
+            description: |
+              A long pier.
+
+              Gulls wheel overhead.
+            items: [{ id: apple, quantity: 1 }]
+            npcs: [fisher]
+          # Soon to be taken away.
+          - { title: Hut, coord: [9, 9, 0], description: A hut. }
+          - { title: Boat, coord: [3, 1, 0], description: "A boat: small." }
+        regions:
+          docks:
+            name: Docks
+        "###);
+        assert_eq!(serde_yaml::from_str::<Value>(&rewritten).unwrap(), level);
+    }
+
+    #[test]
+    fn test_rewrite_levels() {
+        for entry in std::fs::read_dir("data/levels").unwrap() {
+            let text = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            let level: Value = serde_yaml::from_str(&text).unwrap();
+            assert_eq!(rewrite(&text, &level).unwrap(), text);
+        }
+    }
+}